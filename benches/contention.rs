@@ -0,0 +1,110 @@
+//! Measures the throughput of the file appender, which encodes records before
+//! taking its lock, next to an appender which encodes them while holding it,
+//! as the number of threads logging concurrently grows.
+//!
+//! Any difference between the two can only show with several cores; on a
+//! single CPU they are within noise of each other.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{Level, Record};
+use log4rs::{
+    append::{file::FileAppender, Append},
+    encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+};
+use tempfile::tempdir;
+
+const RECORDS_PER_THREAD: u32 = 50_000;
+const PATTERN: &str = "{d(%Y-%m-%dT%H:%M:%S%.6f%:z)} {h({l}):<5} [{T}] {M}:{L} - {m}{n}";
+
+fn main() {
+    let dir = tempdir().unwrap();
+
+    println!(
+        "{:>7} {:>16} {:>16} {:>8}",
+        "threads", "locked (rec/s)", "file (rec/s)", "ratio"
+    );
+    for &threads in &[1, 2, 4, 8, 16] {
+        let locked = Arc::new(Locked {
+            encoder: Box::new(PatternEncoder::new(PATTERN)),
+            file: Mutex::new(BufWriter::new(
+                File::create(dir.path().join("locked.log")).unwrap(),
+            )),
+        });
+        let file = Arc::new(
+            FileAppender::builder()
+                .encoder(Box::new(PatternEncoder::new(PATTERN)))
+                .append(false)
+                .build(dir.path().join("file.log"))
+                .unwrap(),
+        );
+
+        let locked = throughput(locked, threads);
+        let file = throughput(file, threads);
+        println!(
+            "{:>7} {:>16.0} {:>16.0} {:>7.2}x",
+            threads,
+            locked,
+            file,
+            file / locked
+        );
+    }
+}
+
+/// Returns the records per second appended by `threads` threads.
+fn throughput(appender: Arc<dyn Append>, threads: u32) -> f64 {
+    let start = Instant::now();
+    let handles = (0..threads)
+        .map(|_| {
+            let appender = appender.clone();
+            thread::spawn(move || {
+                for i in 0..RECORDS_PER_THREAD {
+                    appender
+                        .append(
+                            &Record::builder()
+                                .level(Level::Info)
+                                .target("contention")
+                                .module_path(Some("contention"))
+                                .line(Some(line!()))
+                                .args(format_args!("request {} served in {}us", i, i % 997))
+                                .build(),
+                        )
+                        .unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    appender.flush();
+    rate(threads * RECORDS_PER_THREAD, start.elapsed())
+}
+
+fn rate(records: u32, elapsed: Duration) -> f64 {
+    f64::from(records) / elapsed.as_secs_f64()
+}
+
+/// An appender which encodes records while holding its lock.
+#[derive(Debug)]
+struct Locked {
+    encoder: Box<dyn Encode>,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl Append for Locked {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        self.encoder.encode(&mut SimpleWriter(&mut *file), record)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,153 @@
+//! Criterion benchmarks of the throughput of the encoders and of the console,
+//! file and rolling file appenders, and of the file appender under contention
+//! from several threads.
+//!
+//! Run with `cargo bench --all-features --bench throughput 2>/dev/null`; the
+//! console benchmark writes its records to stderr. The `log4rs::load` module
+//! drives the same load against other configurations.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use log::{Level, Record};
+use log4rs::{
+    append::{
+        console::{ConsoleAppender, Target},
+        file::FileAppender,
+        rolling_file::{
+            policy::compound::{
+                roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
+            },
+            RollingFileAppender,
+        },
+        Append,
+    },
+    encode::{json::JsonEncoder, pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+    load::Load,
+};
+use tempfile::tempdir;
+
+const PATTERN: &str = "{d(%Y-%m-%dT%H:%M:%S%.6f%:z)} {l:<5} [{T}] {M}:{L} - {m}{n}";
+
+fn record<F: FnOnce(&Record)>(i: u64, f: F) {
+    let fields = [("user", "alice"), ("status", "200")];
+    f(&Record::builder()
+        .level(Level::Info)
+        .target("throughput")
+        .module_path_static(Some(module_path!()))
+        .file_static(Some(file!()))
+        .line(Some(line!()))
+        .args(format_args!("request {} served in {}us", i, i % 997))
+        .key_values(&fields)
+        .build())
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Elements(1));
+    let encoders: [(&str, Box<dyn Encode>); 2] = [
+        ("pattern", Box::new(PatternEncoder::new(PATTERN))),
+        ("json", Box::new(JsonEncoder::new())),
+    ];
+    for (name, encoder) in &encoders {
+        let mut buf = SimpleWriter(Vec::with_capacity(1024));
+        let mut i = 0;
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                i += 1;
+                buf.0.clear();
+                record(i, |record| encoder.encode(&mut buf, record).unwrap());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn append(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+    let rolling_policy = CompoundPolicy::new(
+        Box::new(SizeTrigger::new(10 * 1024 * 1024)),
+        Box::new(
+            FixedWindowRoller::builder()
+                .build(&dir.path().join("rolling.{}.log").to_string_lossy(), 3)
+                .unwrap(),
+        ),
+    );
+    let appenders: [(&str, Box<dyn Append>); 3] = [
+        (
+            "console",
+            Box::new(
+                ConsoleAppender::builder()
+                    .target(Target::Stderr)
+                    .encoder(Box::new(PatternEncoder::new(PATTERN)))
+                    .build(),
+            ),
+        ),
+        (
+            "file",
+            Box::new(
+                FileAppender::builder()
+                    .encoder(Box::new(PatternEncoder::new(PATTERN)))
+                    .append(false)
+                    .build(dir.path().join("file.log"))
+                    .unwrap(),
+            ),
+        ),
+        (
+            "rolling_file",
+            Box::new(
+                RollingFileAppender::builder()
+                    .encoder(Box::new(PatternEncoder::new(PATTERN)))
+                    .append(false)
+                    .build(dir.path().join("rolling.log"), Box::new(rolling_policy))
+                    .unwrap(),
+            ),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("append");
+    group.throughput(Throughput::Elements(1));
+    for (name, appender) in &appenders {
+        let mut i = 0;
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                i += 1;
+                record(i, |record| appender.append(record).unwrap());
+            })
+        });
+        appender.flush();
+    }
+    group.finish();
+}
+
+fn contention(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+    let appender = FileAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(PATTERN)))
+        .append(false)
+        .build(dir.path().join("contention.log"))
+        .unwrap();
+
+    let mut group = c.benchmark_group("contention");
+    for &threads in &[1, 2, 4, 8] {
+        // each iteration logs one record on every thread
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::new("file", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    Load::builder()
+                        .threads(threads)
+                        .records(iters)
+                        .fields(2)
+                        .build()
+                        .run_appender(&appender)
+                        .elapsed()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, append, contention);
+criterion_main!(benches);
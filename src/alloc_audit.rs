@@ -0,0 +1,133 @@
+//! Measurement of the heap allocations made while logging.
+//!
+//! Requires the `alloc_audit` feature.
+//!
+//! Allocations are only seen when `CountingAllocator` is installed as the
+//! global allocator, typically in a test binary:
+//!
+//! ```
+//! #[global_allocator]
+//! static ALLOCATOR: log4rs::alloc_audit::CountingAllocator =
+//!     log4rs::alloc_audit::CountingAllocator;
+//! # fn main() {}
+//! ```
+//!
+//! The logger then counts the allocations made by each call to `Log::log`,
+//! covering dispatch to the loggers and the encoding and appending done on
+//! the calling thread, and `Handle::allocation_stats` reports them.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+thread_local! {
+    static ALLOCATIONS: Cell<(u64, u64)> = const { Cell::new((0, 0)) };
+}
+
+/// Returns the number of allocations and bytes allocated on the current
+/// thread so far.
+fn current() -> (u64, u64) {
+    // The thread local may already be gone while the thread exits.
+    ALLOCATIONS.try_with(Cell::get).unwrap_or_default()
+}
+
+fn count(size: usize) {
+    let _ = ALLOCATIONS.try_with(|allocations| {
+        let (count, bytes) = allocations.get();
+        allocations.set((count + 1, bytes + size as u64));
+    });
+}
+
+/// A global allocator which delegates to the system allocator, counting the
+/// allocations made on each thread.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Statistics about the allocations made while logging, returned by
+/// `Handle::allocation_stats`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub struct AllocationStats {
+    /// The number of records measured.
+    pub records: u64,
+    /// The total number of allocations made while logging them.
+    pub allocations: u64,
+    /// The total number of bytes allocated while logging them.
+    pub bytes: u64,
+    /// The largest number of allocations made while logging a single record.
+    pub max_allocations: u64,
+}
+
+/// The running totals behind `AllocationStats`.
+#[derive(Debug, Default)]
+pub(crate) struct AllocationCounters {
+    records: AtomicU64,
+    allocations: AtomicU64,
+    bytes: AtomicU64,
+    max_allocations: AtomicU64,
+}
+
+impl AllocationCounters {
+    pub(crate) fn add(&self, scope: Scope) {
+        let (allocations, bytes) = current();
+        let (allocations, bytes) = (
+            allocations.saturating_sub(scope.0),
+            bytes.saturating_sub(scope.1),
+        );
+        self.records.fetch_add(1, Ordering::Relaxed);
+        self.allocations.fetch_add(allocations, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.max_allocations
+            .fetch_max(allocations, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> AllocationStats {
+        AllocationStats {
+            records: self.records.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            max_allocations: self.max_allocations.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.records.store(0, Ordering::Relaxed);
+        self.allocations.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.max_allocations.store(0, Ordering::Relaxed);
+    }
+}
+
+/// The allocation counts of the current thread when a measurement started.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Scope(u64, u64);
+
+impl Scope {
+    pub(crate) fn start() -> Scope {
+        let (allocations, bytes) = current();
+        Scope(allocations, bytes)
+    }
+}
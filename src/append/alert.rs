@@ -0,0 +1,1033 @@
+//! The alert appender.
+//!
+//! Requires the `alert_appender` feature.
+
+use base64::Engine as _;
+use log::{Level, LevelFilter, Record};
+use rustls::pki_types::ServerName;
+use serde_json::json;
+use std::{
+    convert::TryFrom,
+    fmt::{self, Write as _},
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::{
+    append::{
+        flush::{FlushToken, FlushTracker},
+        Append, AppendHealth,
+    },
+    background::{self, Message, Task, Worker},
+    encode,
+    history::format_time,
+};
+
+/// The alert appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertAppenderConfig {
+    level: Option<LevelFilter>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    batch_window: Option<Duration>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    min_interval: Option<Duration>,
+    max_records: Option<usize>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    timeout: Option<Duration>,
+    webhook: Option<WebhookConfig>,
+    email: Option<EmailConfig>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WebhookConfig {
+    url: String,
+    format: Option<WebhookFormat>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmailConfig {
+    server: String,
+    security: Option<EmailSecurity>,
+    username: Option<String>,
+    password: Option<String>,
+    from: String,
+    to: Vec<String>,
+    subject: Option<String>,
+}
+
+/// The body of the requests sent to a webhook.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum WebhookFormat {
+    /// A JSON object with the alert as readable text under `text`, as Slack
+    /// and Microsoft Teams incoming webhooks expect.
+    #[default]
+    Text,
+    /// A JSON object with the `host`, the `count` of records alerted on,
+    /// the number of them `omitted` from the alert, and the `records`
+    /// themselves, each with its `time`, `level`, `target` and `message`.
+    Json,
+}
+
+/// A webhook alerts are posted to.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Webhook {
+    url: String,
+    format: WebhookFormat,
+}
+
+impl Webhook {
+    /// Creates a webhook posting to `url`, which may be `http://` or
+    /// `https://`.
+    pub fn new<U: Into<String>>(url: U) -> Webhook {
+        Webhook {
+            url: url.into(),
+            format: WebhookFormat::Text,
+        }
+    }
+
+    /// Sets the body of the requests.
+    ///
+    /// Defaults to `WebhookFormat::Text`.
+    pub fn format(mut self, format: WebhookFormat) -> Webhook {
+        self.format = format;
+        self
+    }
+}
+
+/// How the connection to a mail server is secured.
+///
+/// Alerts are never sent to a mail server in plaintext.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum EmailSecurity {
+    /// The connection is upgraded to TLS with `STARTTLS` before anything
+    /// else is sent, as is usual on port 587. A server which does not offer
+    /// `STARTTLS` is not sent the alert.
+    #[default]
+    StartTls,
+    /// The connection uses TLS from the start, as is usual on port 465.
+    Tls,
+}
+
+/// The email alerts are sent as.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Email {
+    server: String,
+    security: EmailSecurity,
+    credentials: Option<(String, String)>,
+    from: String,
+    to: Vec<String>,
+    subject: Option<String>,
+}
+
+impl Email {
+    /// Creates an email from the address `from` to the addresses `to`, sent
+    /// through the SMTP server at `server`, such as `smtp.example.com:587`.
+    ///
+    /// The port defaults to the usual one of the security.
+    pub fn new<S, F, T>(server: S, from: F, to: Vec<T>) -> Email
+    where
+        S: Into<String>,
+        F: Into<String>,
+        T: Into<String>,
+    {
+        Email {
+            server: server.into(),
+            security: EmailSecurity::StartTls,
+            credentials: None,
+            from: from.into(),
+            to: to.into_iter().map(Into::into).collect(),
+            subject: None,
+        }
+    }
+
+    /// Sets how the connection to the server is secured.
+    ///
+    /// Defaults to `EmailSecurity::StartTls`.
+    pub fn security(mut self, security: EmailSecurity) -> Email {
+        self.security = security;
+        self
+    }
+
+    /// Sets the username and password the server is logged into with, with
+    /// `AUTH PLAIN` once the connection is secured.
+    ///
+    /// Defaults to not logging in.
+    pub fn credentials<U, P>(mut self, username: U, password: P) -> Email
+    where
+        U: Into<String>,
+        P: Into<String>,
+    {
+        self.credentials = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Sets the subject of the email.
+    ///
+    /// Defaults to the number and highest level of the records alerted on,
+    /// and the host they were logged on.
+    pub fn subject<S: Into<String>>(mut self, subject: S) -> Email {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    // Splits the server into its host and port.
+    fn host_and_port(&self) -> anyhow::Result<(&str, u16)> {
+        let (host, port) = match self.server.rsplit_once(':') {
+            // an IPv6 address without a port has several colons
+            Some((host, port)) if !host.contains(':') || host.ends_with(']') => {
+                let port = port
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid mail server `{}`", self.server))?;
+                (host, port)
+            }
+            _ => match self.security {
+                EmailSecurity::StartTls => (&*self.server, 587),
+                EmailSecurity::Tls => (&*self.server, 465),
+            },
+        };
+        Ok((host.trim_start_matches('[').trim_end_matches(']'), port))
+    }
+}
+
+impl fmt::Debug for Email {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Email")
+            .field("server", &self.server)
+            .field("security", &self.security)
+            .field(
+                "username",
+                &self.credentials.as_ref().map(|(username, _)| username),
+            )
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("subject", &self.subject)
+            .finish()
+    }
+}
+
+struct Alerted {
+    time: String,
+    level: Level,
+    target: String,
+    message: String,
+}
+
+// The records of the next alert.
+struct Pending {
+    records: Vec<Alerted>,
+    omitted: usize,
+    deadline: Instant,
+}
+
+/// An appender which sends an alert to a webhook or by email when records at
+/// or above a level are logged.
+///
+/// Records below the level are ignored. The records logged during a batch
+/// window, which opens with the first record of an alert, are sent in a
+/// single alert, and alerts are sent at most once per minimum interval, with
+/// the records logged in between held for the next one. An alert lists at
+/// most a maximum number of records and counts the rest, so that a storm of
+/// errors produces a handful of alerts rather than a thousand.
+///
+/// Alerts are sent in the background. An alert which cannot be sent is
+/// reported to the error handler and dropped.
+#[derive(Debug)]
+pub struct AlertAppender {
+    level: LevelFilter,
+    sender: Mutex<Option<background::Sender<Alerted>>>,
+    tracker: Arc<FlushTracker>,
+    worker: Option<Task>,
+}
+
+impl AlertAppender {
+    /// Creates a new `AlertAppender` builder.
+    pub fn builder() -> AlertAppenderBuilder {
+        AlertAppenderBuilder {
+            level: LevelFilter::Error,
+            batch_window: Duration::from_secs(10),
+            min_interval: Duration::from_secs(5 * 60),
+            max_records: 20,
+            timeout: Duration::from_secs(10),
+            webhook: None,
+            email: None,
+        }
+    }
+}
+
+impl Append for AlertAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        if record.level() > self.level {
+            return Ok(());
+        }
+        let alerted = Alerted {
+            time: format_time(encode::record_time()),
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+        };
+
+        let sender = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        let token = self.tracker.track();
+        let sent = match *sender {
+            Some(ref sender) => sender.send(Message::Item(token, alerted)),
+            None => false,
+        };
+        if !sent {
+            self.tracker.complete(token);
+            anyhow::bail!("alerting has stopped");
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {
+        self.flush_to(FlushToken::issue());
+    }
+
+    /// Sends the pending alert at once, without waiting for the batch window
+    /// or the minimum interval.
+    fn flush_to(&self, token: FlushToken) {
+        if let Some(ref sender) = *self.sender.lock().unwrap_or_else(|e| e.into_inner()) {
+            sender.send(Message::Flush);
+        }
+        if let Some(ref worker) = self.worker {
+            worker.wait(|| self.tracker.wait(token));
+        }
+    }
+
+    fn health(&self) -> AppendHealth {
+        AppendHealth {
+            queue_depth: Some(self.tracker.pending()),
+            ..AppendHealth::default()
+        }
+    }
+}
+
+impl Drop for AlertAppender {
+    fn drop(&mut self) {
+        // Sends what is still pending before the worker exits.
+        self.sender
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(worker) = self.worker.take() {
+            worker.join();
+        }
+    }
+}
+
+/// A builder for `AlertAppender`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct AlertAppenderBuilder {
+    level: LevelFilter,
+    batch_window: Duration,
+    min_interval: Duration,
+    max_records: usize,
+    timeout: Duration,
+    webhook: Option<Webhook>,
+    email: Option<Email>,
+}
+
+impl AlertAppenderBuilder {
+    /// Sets the level at and above which records are alerted on.
+    ///
+    /// Defaults to `LevelFilter::Error`.
+    pub fn level(mut self, level: LevelFilter) -> AlertAppenderBuilder {
+        self.level = level;
+        self
+    }
+
+    /// Sets how long records are collected into an alert after the first.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn batch_window(mut self, batch_window: Duration) -> AlertAppenderBuilder {
+        self.batch_window = batch_window;
+        self
+    }
+
+    /// Sets the shortest time between two alerts.
+    ///
+    /// Defaults to 5 minutes.
+    pub fn min_interval(mut self, min_interval: Duration) -> AlertAppenderBuilder {
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// Sets the most records an alert lists. Further records are only
+    /// counted.
+    ///
+    /// Defaults to 20.
+    pub fn max_records(mut self, max_records: usize) -> AlertAppenderBuilder {
+        self.max_records = max_records.max(1);
+        self
+    }
+
+    /// Sets the timeout of connecting to the webhook or mail server, and of
+    /// each request to it.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> AlertAppenderBuilder {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the webhook alerts are posted to.
+    pub fn webhook(mut self, webhook: Webhook) -> AlertAppenderBuilder {
+        self.webhook = Some(webhook);
+        self
+    }
+
+    /// Sets the email alerts are sent as.
+    pub fn email(mut self, email: Email) -> AlertAppenderBuilder {
+        self.email = Some(email);
+        self
+    }
+
+    /// Consumes the `AlertAppenderBuilder`, producing an `AlertAppender`.
+    ///
+    /// A webhook, an email or both must be set.
+    pub fn build(self) -> anyhow::Result<AlertAppender> {
+        if self.webhook.is_none() && self.email.is_none() {
+            anyhow::bail!("an alert appender needs a webhook or an email");
+        }
+        let email = match self.email {
+            Some(email) => {
+                if email.to.is_empty() {
+                    anyhow::bail!("an alert email needs at least one recipient");
+                }
+                let (host, _) = email.host_and_port()?;
+                ServerName::try_from(host.to_owned())
+                    .map_err(|_| anyhow::anyhow!("invalid mail server `{}`", email.server))?;
+                Some((email, tls_config()?))
+            }
+            None => None,
+        };
+
+        let tracker = Arc::new(FlushTracker::new());
+        let alerter = Alerter {
+            notifier: Notifier {
+                agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+                timeout: self.timeout,
+                webhook: self.webhook,
+                email,
+            },
+            pending: None,
+            last_sent: None,
+            batch_window: self.batch_window,
+            min_interval: self.min_interval,
+            max_records: self.max_records,
+        };
+        let (sender, worker) = background::serve("log4rs alert", tracker.clone(), alerter)?;
+
+        Ok(AlertAppender {
+            level: self.level,
+            sender: Mutex::new(Some(sender)),
+            tracker,
+            worker: Some(worker),
+        })
+    }
+}
+
+/// Collects records into alerts and sends them.
+struct Alerter {
+    notifier: Notifier,
+    pending: Option<Pending>,
+    last_sent: Option<Instant>,
+    batch_window: Duration,
+    min_interval: Duration,
+    max_records: usize,
+}
+
+impl Worker<Alerted> for Alerter {
+    fn push(&mut self, alerted: Alerted) -> bool {
+        match self.pending {
+            Some(ref mut pending) => {
+                if pending.records.len() < self.max_records {
+                    pending.records.push(alerted);
+                } else {
+                    pending.omitted += 1;
+                }
+            }
+            // the first record of the next alert
+            None => {
+                let mut deadline = Instant::now() + self.batch_window;
+                if let Some(last_sent) = self.last_sent {
+                    deadline = deadline.max(last_sent + self.min_interval);
+                }
+                self.pending = Some(Pending {
+                    records: vec![alerted],
+                    omitted: 0,
+                    deadline,
+                });
+            }
+        }
+        false
+    }
+
+    fn due(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|pending| pending.deadline)
+    }
+
+    fn run(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            if let Err(e) = self.notifier.notify(&pending.records, pending.omitted) {
+                crate::handle_error(&e.context("error sending alert"));
+            }
+            self.last_sent = Some(Instant::now());
+        }
+    }
+}
+
+/// Sends alerts to a webhook and by email.
+struct Notifier {
+    agent: ureq::Agent,
+    timeout: Duration,
+    webhook: Option<Webhook>,
+    email: Option<(Email, Arc<rustls::ClientConfig>)>,
+}
+
+impl Notifier {
+    fn notify(&self, records: &[Alerted], omitted: usize) -> anyhow::Result<()> {
+        let mut text = String::new();
+        for record in records {
+            let _ = writeln!(
+                text,
+                "{} {} {} - {}",
+                record.time, record.level, record.target, record.message
+            );
+        }
+        if omitted > 0 {
+            let _ = writeln!(text, "and {} more", omitted);
+        }
+
+        let mut result = Ok(());
+        if let Some(ref webhook) = self.webhook {
+            result = self.post(webhook, records, omitted, &text);
+        }
+        if let Some((ref email, ref tls)) = self.email {
+            let level = records
+                .iter()
+                .map(|r| r.level)
+                .min()
+                .unwrap_or(Level::Error);
+            let count = records.len() + omitted;
+            let subject = email.subject.clone().unwrap_or_else(|| {
+                format!(
+                    "{}: {} log record{} on {}",
+                    level,
+                    count,
+                    if count == 1 { "" } else { "s" },
+                    encode::hostname()
+                )
+            });
+            if let Err(e) = send_email(email, tls, self.timeout, &subject, &text) {
+                match result {
+                    Ok(()) => result = Err(e),
+                    Err(_) => crate::handle_error(&e.context("error sending alert")),
+                }
+            }
+        }
+        result
+    }
+
+    fn post(
+        &self,
+        webhook: &Webhook,
+        records: &[Alerted],
+        omitted: usize,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        let body = match webhook.format {
+            WebhookFormat::Text => json!({
+                "text": format!("log4rs alert from {}:\n{}", encode::hostname(), text),
+            }),
+            WebhookFormat::Json => json!({
+                "host": encode::hostname(),
+                "count": records.len() + omitted,
+                "omitted": omitted,
+                "records": records
+                    .iter()
+                    .map(|r| json!({
+                        "time": r.time,
+                        "level": r.level.as_str(),
+                        "target": r.target,
+                        "message": r.message,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+        };
+        self.agent
+            .post(&webhook.url)
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string())
+            .map_err(|e| anyhow::anyhow!("webhook `{}` failed: {}", webhook.url, e))?;
+        Ok(())
+    }
+}
+
+/// The TLS configuration mail servers are spoken to with, trusting the
+/// Mozilla root certificates as the webhook does.
+fn tls_config() -> anyhow::Result<Arc<rustls::ClientConfig>> {
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// Sends an email over SMTP, secured with TLS before anything but the
+/// greeting and `STARTTLS` is exchanged.
+fn send_email(
+    email: &Email,
+    tls: &Arc<rustls::ClientConfig>,
+    timeout: Duration,
+    subject: &str,
+    text: &str,
+) -> anyhow::Result<()> {
+    let (host, port) = email.host_and_port()?;
+    let name = ServerName::try_from(host.to_owned())?;
+    let addr = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("`{}` did not resolve to an address", email.server))?;
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let connection = rustls::ClientConnection::new(tls.clone(), name)?;
+    let ehlo = format!("EHLO {}", encode::hostname());
+
+    let mut smtp = match email.security {
+        EmailSecurity::Tls => {
+            let mut smtp = Smtp::new(email, rustls::StreamOwned::new(connection, stream));
+            smtp.command("", &[220])?;
+            smtp
+        }
+        EmailSecurity::StartTls => {
+            let mut smtp = Smtp::new(email, stream);
+            smtp.command("", &[220])?;
+            let reply = smtp.command(&ehlo, &[250])?;
+            let starttls = reply.lines().any(|line| {
+                line.get(4..)
+                    .map_or(false, |ext| ext.trim().eq_ignore_ascii_case("STARTTLS"))
+            });
+            if !starttls {
+                let _ = smtp.command("QUIT", &[221]);
+                anyhow::bail!("mail server `{}` does not offer STARTTLS", email.server);
+            }
+            smtp.command("STARTTLS", &[220])?;
+            let stream = smtp.reader.into_inner();
+            Smtp::new(email, rustls::StreamOwned::new(connection, stream))
+        }
+    };
+
+    smtp.command(&ehlo, &[250])?;
+    if let Some((ref username, ref password)) = email.credentials {
+        let plain = format!("\0{}\0{}", username, password);
+        let plain = base64::engine::general_purpose::STANDARD.encode(plain);
+        smtp.command(&format!("AUTH PLAIN {}", plain), &[235])?;
+    }
+    smtp.command(&format!("MAIL FROM:<{}>", email.from), &[250])?;
+    for to in &email.to {
+        smtp.command(&format!("RCPT TO:<{}>", to), &[250, 251])?;
+    }
+    smtp.command("DATA", &[354])?;
+
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+        email.from,
+        email.to.join(", "),
+        subject.replace(&['\r', '\n'][..], " "),
+    );
+    for line in text.lines() {
+        // lines starting with a dot are escaped by doubling it
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    smtp.command(&message, &[250])?;
+    let _ = smtp.command("QUIT", &[221]);
+    Ok(())
+}
+
+/// A conversation with a mail server.
+struct Smtp<'a, S> {
+    email: &'a Email,
+    reader: BufReader<S>,
+}
+
+impl<'a, S: Read + Write> Smtp<'a, S> {
+    fn new(email: &'a Email, stream: S) -> Smtp<'a, S> {
+        Smtp {
+            email,
+            reader: BufReader::new(stream),
+        }
+    }
+
+    /// Sends a command, unless it is empty, and returns the reply if its
+    /// code is one of `expected`.
+    fn command(&mut self, command: &str, expected: &[u16]) -> anyhow::Result<String> {
+        if !command.is_empty() {
+            let stream = self.reader.get_mut();
+            stream.write_all(command.as_bytes())?;
+            stream.write_all(b"\r\n")?;
+            stream.flush()?;
+        }
+        let (code, reply) = read_reply(&mut self.reader)?;
+        if !expected.contains(&code) {
+            anyhow::bail!(
+                "mail server `{}` replied `{}`",
+                self.email.server,
+                reply.trim_end()
+            );
+        }
+        Ok(reply)
+    }
+}
+
+/// Reads an SMTP reply, which may span several lines, returning its code.
+fn read_reply<R: BufRead>(reader: &mut R) -> anyhow::Result<(u16, String)> {
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("mail server closed the connection");
+        }
+        reply.push_str(&line);
+        // the last line of a reply has a space after its code
+        if line.as_bytes().get(3) != Some(&b'-') {
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("invalid reply `{}` from mail server", line.trim_end())
+                })?;
+            return Ok((code, reply));
+        }
+    }
+}
+
+/// A deserializer for the `AlertAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: alert
+///
+/// # The level at and above which records are alerted on. Defaults to error.
+/// level: error
+///
+/// # How long records are collected into an alert after the first. Defaults
+/// # to 10 seconds.
+/// batch_window: 10 seconds
+///
+/// # The shortest time between two alerts. Defaults to 5 minutes.
+/// min_interval: 5 minutes
+///
+/// # The most records an alert lists; further records are only counted.
+/// # Defaults to 20.
+/// max_records: 20
+///
+/// # The timeout of connecting to the webhook or mail server and of each
+/// # request. Defaults to 10 seconds.
+/// timeout: 10 seconds
+///
+/// # The webhook alerts are posted to. At least one of webhook and email is
+/// # required.
+/// webhook:
+///   url: https://hooks.slack.com/services/T000/B000/XXXX
+///
+///   # The body of the requests: "text", a JSON object with the alert under
+///   # `text` as Slack and Microsoft Teams expect, or "json", a JSON object
+///   # with the records alerted on. Defaults to "text".
+///   format: text
+///
+/// # The email alerts are sent as, through an SMTP server which is always
+/// # spoken to over TLS.
+/// email:
+///   # The server, with a port which defaults to 587 or 465 according to the
+///   # security.
+///   server: smtp.example.com:587
+///
+///   # How the connection is secured: "starttls", which upgrades it with
+///   # STARTTLS and refuses servers which do not offer it, or "tls", which
+///   # uses TLS from the start. Defaults to "starttls".
+///   security: starttls
+///
+///   # The username and password logged in with, if any. Both or neither are
+///   # required.
+///   username: alerts@example.com
+///   password: ${SMTP_PASSWORD}
+///
+///   from: app@example.com
+///   to:
+///     - oncall@example.com
+///
+///   # Defaults to the number and highest level of the records, and the host.
+///   subject: checkout is failing
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct AlertAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for AlertAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = AlertAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: AlertAppenderConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut builder = AlertAppender::builder();
+        if let Some(level) = config.level {
+            builder = builder.level(level);
+        }
+        if let Some(batch_window) = config.batch_window {
+            builder = builder.batch_window(batch_window);
+        }
+        if let Some(min_interval) = config.min_interval {
+            builder = builder.min_interval(min_interval);
+        }
+        if let Some(max_records) = config.max_records {
+            builder = builder.max_records(max_records);
+        }
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(webhook) = config.webhook {
+            let mut hook = Webhook::new(webhook.url);
+            if let Some(format) = webhook.format {
+                hook = hook.format(format);
+            }
+            builder = builder.webhook(hook);
+        }
+        if let Some(email) = config.email {
+            let mut mail = Email::new(email.server, email.from, email.to);
+            if let Some(security) = email.security {
+                mail = mail.security(security);
+            }
+            match (email.username, email.password) {
+                (Some(username), Some(password)) => mail = mail.credentials(username, password),
+                (None, None) => {}
+                _ => anyhow::bail!("an alert email needs both a username and a password"),
+            }
+            if let Some(subject) = email.subject {
+                mail = mail.subject(subject);
+            }
+            builder = builder.email(mail);
+        }
+        Ok(Box::new(builder.build()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value as Json;
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+
+    fn log(appender: &AlertAppender, level: Level, message: &str) {
+        appender
+            .append(
+                &Record::builder()
+                    .level(level)
+                    .target("checkout")
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buf = [0; 4096];
+            let body_start = loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                if let Some(idx) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break idx + 4;
+                }
+            };
+            let head = String::from_utf8_lossy(&request[..body_start]).into_owned();
+            let len = head
+                .lines()
+                .find_map(|l| {
+                    l.to_ascii_lowercase()
+                        .strip_prefix("content-length: ")
+                        .map(str::to_owned)
+                })
+                .unwrap()
+                .parse::<usize>()
+                .unwrap();
+            while request.len() < body_start + len {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            serde_json::from_slice::<Json>(&request[body_start..]).unwrap()
+        });
+
+        let appender = AlertAppender::builder()
+            .level(LevelFilter::Warn)
+            .max_records(2)
+            .batch_window(Duration::from_secs(60))
+            .webhook(Webhook::new(url).format(WebhookFormat::Json))
+            .build()
+            .unwrap();
+        log(&appender, Level::Info, "ignored");
+        log(&appender, Level::Warn, "slow");
+        log(&appender, Level::Error, "failed");
+        log(&appender, Level::Error, "failed again");
+        appender.flush();
+
+        let body = server.join().unwrap();
+        assert_eq!(body["count"], 3);
+        assert_eq!(body["omitted"], 1);
+        let records = body["records"].as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["level"], "WARN");
+        assert_eq!(records[1]["message"], "failed");
+    }
+
+    // The plaintext lines a mail server was sent, and the first byte sent
+    // after STARTTLS.
+    type Transcript = (Vec<String>, Option<u8>);
+
+    // Runs a mail server which offers STARTTLS or not.
+    fn mail_server(starttls: bool) -> (String, thread::JoinHandle<Transcript>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server_addr = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut transcript = vec![];
+            writer.write_all(b"220 mail ready\r\n").unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    return (transcript, None);
+                }
+                let line = line.trim_end().to_owned();
+                transcript.push(line.clone());
+                if line.starts_with("EHLO") {
+                    if starttls {
+                        writer.write_all(b"250-mail\r\n250 STARTTLS\r\n").unwrap();
+                    } else {
+                        writer.write_all(b"250-mail\r\n250 8BITMIME\r\n").unwrap();
+                    }
+                } else if line == "STARTTLS" {
+                    writer.write_all(b"220 go ahead\r\n").unwrap();
+                    let mut handshake = [0];
+                    reader.read_exact(&mut handshake).unwrap();
+                    return (transcript, Some(handshake[0]));
+                } else if line == "QUIT" {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    return (transcript, None);
+                } else {
+                    writer.write_all(b"250 ok\r\n").unwrap();
+                }
+            }
+        });
+        (server_addr, server)
+    }
+
+    fn email_appender(server_addr: String) -> AlertAppender {
+        AlertAppender::builder()
+            .batch_window(Duration::from_secs(60))
+            .email(
+                Email::new(server_addr, "app@example.com", vec!["oncall@example.com"])
+                    .credentials("app", "secret"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn email_starttls() {
+        let (server_addr, server) = mail_server(true);
+        let appender = email_appender(server_addr);
+        log(&appender, Level::Error, "payment failed");
+        appender.flush();
+
+        let (transcript, handshake) = server.join().unwrap();
+        assert_eq!(
+            transcript,
+            [
+                format!("EHLO {}", encode::hostname()),
+                "STARTTLS".to_owned()
+            ]
+        );
+        // a TLS handshake record
+        assert_eq!(handshake, Some(0x16));
+    }
+
+    #[test]
+    fn email_needs_starttls() {
+        let (server_addr, server) = mail_server(false);
+        let appender = email_appender(server_addr);
+        log(&appender, Level::Error, "payment failed");
+        appender.flush();
+
+        let (transcript, _) = server.join().unwrap();
+        assert_eq!(
+            transcript,
+            [format!("EHLO {}", encode::hostname()), "QUIT".to_owned()]
+        );
+    }
+
+    #[test]
+    fn email_server() {
+        let email = Email::new("smtp.example.com", "a@example.com", vec!["b@example.com"]);
+        assert_eq!(email.host_and_port().unwrap(), ("smtp.example.com", 587));
+        let email = email.security(EmailSecurity::Tls);
+        assert_eq!(email.host_and_port().unwrap(), ("smtp.example.com", 465));
+        let email = Email::new("[::1]:2525", "a@example.com", vec!["b@example.com"]);
+        assert_eq!(email.host_and_port().unwrap(), ("::1", 2525));
+        assert!(!format!("{:?}", email.credentials("a", "secret")).contains("secret"));
+    }
+
+    #[test]
+    fn needs_destination() {
+        assert!(AlertAppender::builder().build().is_err());
+        assert!(AlertAppender::builder()
+            .email(Email::new(
+                "smtp.example.com",
+                "a@example.com",
+                Vec::<String>::new()
+            ))
+            .build()
+            .is_err());
+    }
+}
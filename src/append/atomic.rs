@@ -0,0 +1,96 @@
+//! Writing each record to a log file with a single write call.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+};
+
+use super::Oversized;
+
+/// The default longest record written with a single write call.
+pub const DEFAULT_LIMIT: usize = 64 * 1024;
+
+/// Writes each record to a file opened for appending with a single write
+/// call, so that records from appenders or processes sharing the file
+/// land whole rather than interleaved.
+#[derive(Copy, Clone, Debug)]
+pub struct AtomicWriter {
+    pub limit: usize,
+    pub oversized: Oversized,
+}
+
+impl AtomicWriter {
+    /// Writes `record` to `file`, returning the number of bytes written.
+    pub fn write(&self, mut file: &File, record: &[u8]) -> io::Result<u64> {
+        let limit = self.limit.max(1);
+        if record.len() <= limit {
+            write_once(&mut file, record)?;
+            return Ok(record.len() as u64);
+        }
+
+        match self.oversized {
+            Oversized::Split => {
+                for piece in record.chunks(limit) {
+                    write_once(&mut file, piece)?;
+                }
+                Ok(record.len() as u64)
+            }
+            Oversized::Truncate => {
+                let mut truncated = record[..limit].to_vec();
+                if record.ends_with(b"\n") {
+                    truncated[limit - 1] = b'\n';
+                }
+                write_once(&mut file, &truncated)?;
+                Ok(truncated.len() as u64)
+            }
+        }
+    }
+}
+
+/// Writes `buf` with one write call, unless the operating system
+/// performs a short write, in which case the rest follows in further
+/// calls.
+fn write_once(file: &mut &File, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match file.write(buf) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => buf = &buf[n..],
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn oversized() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .unwrap();
+
+        let split = AtomicWriter {
+            limit: 4,
+            oversized: Oversized::Split,
+        };
+        assert_eq!(split.write(&file, b"abc\n").unwrap(), 4);
+        assert_eq!(split.write(&file, b"abcdefg\n").unwrap(), 8);
+        let truncate = AtomicWriter {
+            limit: 4,
+            oversized: Oversized::Truncate,
+        };
+        assert_eq!(truncate.write(&file, b"abcdefg\n").unwrap(), 4);
+        assert_eq!(truncate.write(&file, b"abcdefg").unwrap(), 4);
+
+        assert_eq!(fs::read(&path).unwrap(), b"abc\nabcdefg\nabc\nabcd");
+    }
+}
@@ -0,0 +1,268 @@
+//! The capturing appender, for asserting on log output in tests.
+//!
+//! Requires the `test_support` feature.
+
+use log::{
+    kv::{self, VisitSource},
+    Level, LevelFilter, Record,
+};
+use std::{
+    cell::RefCell,
+    marker::PhantomData,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    append::Append,
+    config::{Appender, Config, Root},
+};
+
+thread_local! {
+    static INSTALLED: RefCell<Vec<CapturingAppender>> = const { RefCell::new(Vec::new()) };
+}
+
+static LOGGER_INSTALLED: Mutex<bool> = Mutex::new(false);
+
+/// A copy of a record kept by a `CapturingAppender`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
+pub struct CapturedRecord {
+    /// The level of the record.
+    pub level: Level,
+    /// The target of the record.
+    pub target: String,
+    /// The formatted message of the record.
+    pub message: String,
+    /// The module path of the record, if known.
+    pub module_path: Option<String>,
+    /// The file of the record, if known.
+    pub file: Option<String>,
+    /// The line of the record, if known.
+    pub line: Option<u32>,
+    /// The key-value fields of the record, formatted, in the order they were
+    /// logged.
+    pub key_values: Vec<(String, String)>,
+}
+
+impl CapturedRecord {
+    fn new(record: &Record) -> CapturedRecord {
+        struct Collect<'a>(&'a mut Vec<(String, String)>);
+
+        impl<'kvs> VisitSource<'kvs> for Collect<'_> {
+            fn visit_pair(
+                &mut self,
+                key: kv::Key<'kvs>,
+                value: kv::Value<'kvs>,
+            ) -> Result<(), kv::Error> {
+                self.0.push((key.to_string(), value.to_string()));
+                Ok(())
+            }
+        }
+
+        let mut key_values = vec![];
+        let _ = record.key_values().visit(&mut Collect(&mut key_values));
+
+        CapturedRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            module_path: record.module_path().map(ToOwned::to_owned),
+            file: record.file().map(ToOwned::to_owned),
+            line: record.line(),
+            key_values,
+        }
+    }
+
+    /// Returns the value of the key-value field `key`, if the record has one.
+    pub fn key_value(&self, key: &str) -> Option<&str> {
+        self.key_values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &**v)
+    }
+}
+
+/// An appender which keeps a copy of every record it is passed, so tests can
+/// assert on what was logged without writing to and parsing files.
+///
+/// Clones share the same records. The appender can be added to a `Config`
+/// like any other, or installed with `install` to capture what the code under
+/// test logs on the current thread.
+#[derive(Clone, Debug, Default)]
+pub struct CapturingAppender {
+    records: Arc<Mutex<Vec<CapturedRecord>>>,
+}
+
+impl CapturingAppender {
+    /// Creates a new, empty `CapturingAppender`.
+    pub fn new() -> CapturingAppender {
+        CapturingAppender::default()
+    }
+
+    /// Returns copies of the records captured so far, oldest first.
+    pub fn records(&self) -> Vec<CapturedRecord> {
+        self.lock().clone()
+    }
+
+    /// Discards the records captured so far.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Returns the number of records captured so far.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no records have been captured.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the captured records for which `predicate` returns true.
+    pub fn matching<F>(&self, mut predicate: F) -> Vec<CapturedRecord>
+    where
+        F: FnMut(&CapturedRecord) -> bool,
+    {
+        self.lock()
+            .iter()
+            .filter(|record| predicate(record))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns true if a record at `level` whose message contains `text` has
+    /// been captured.
+    pub fn contains(&self, level: Level, text: &str) -> bool {
+        self.lock()
+            .iter()
+            .any(|record| record.level == level && record.message.contains(text))
+    }
+
+    /// Returns the number of records captured at `level`.
+    pub fn count(&self, level: Level) -> usize {
+        self.lock()
+            .iter()
+            .filter(|record| record.level == level)
+            .count()
+    }
+
+    /// Starts capturing every record logged on the current thread, until the
+    /// returned guard is dropped.
+    ///
+    /// The first call installs a log4rs logger which passes each record to the
+    /// appenders installed on the thread it was logged on, so tests running in
+    /// parallel each see only their own records. Records logged on other
+    /// threads, such as ones the code under test spawns, are not captured;
+    /// add the appender to a `Config` to capture those.
+    ///
+    /// Fails if another logger has already been installed.
+    pub fn install(&self) -> Result<CaptureGuard, log::SetLoggerError> {
+        let mut logger_installed = LOGGER_INSTALLED.lock().unwrap_or_else(|e| e.into_inner());
+        if !*logger_installed {
+            let config = Config::builder()
+                .appender(Appender::builder().build("capture", Box::new(Dispatch)))
+                .build(
+                    Root::builder()
+                        .appender("capture")
+                        .build(LevelFilter::Trace),
+                )
+                .expect("valid capture config");
+            crate::init_config(config)?;
+            *logger_installed = true;
+        }
+
+        INSTALLED.with(|installed| installed.borrow_mut().push(self.clone()));
+        Ok(CaptureGuard {
+            appender: self.clone(),
+            _not_send: PhantomData,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<CapturedRecord>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Append for CapturingAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        self.lock().push(CapturedRecord::new(record));
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+/// Stops a `CapturingAppender` installed with `CapturingAppender::install`
+/// from capturing records when dropped.
+#[derive(Debug)]
+pub struct CaptureGuard {
+    appender: CapturingAppender,
+    // The appender is installed on the thread that created the guard.
+    _not_send: PhantomData<Rc<()>>,
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        let _ = INSTALLED.try_with(|installed| {
+            let mut installed = installed.borrow_mut();
+            if let Some(i) = installed
+                .iter()
+                .rposition(|appender| Arc::ptr_eq(&appender.records, &self.appender.records))
+            {
+                installed.remove(i);
+            }
+        });
+    }
+}
+
+/// The appender of the logger installed by `CapturingAppender::install`.
+#[derive(Debug)]
+struct Dispatch;
+
+impl Append for Dispatch {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let _ = INSTALLED.try_with(|installed| {
+            for appender in installed.borrow().iter() {
+                let _ = appender.append(record);
+            }
+        });
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn capture() {
+        let appender = CapturingAppender::new();
+        let kvs = [("user", "alice")];
+        appender
+            .append(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("app")
+                    .args(format_args!("disk {} full", "nearly"))
+                    .key_values(&kvs)
+                    .build(),
+            )
+            .unwrap();
+
+        let records = appender.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].message, "disk nearly full");
+        assert_eq!(records[0].target, "app");
+        assert_eq!(records[0].key_value("user"), Some("alice"));
+        assert!(appender.contains(Level::Warn, "full"));
+        assert!(!appender.contains(Level::Error, "full"));
+        assert_eq!(appender.count(Level::Warn), 1);
+
+        appender.clear();
+        assert!(appender.is_empty());
+    }
+}
@@ -2,11 +2,19 @@
 //!
 //! Requires the `console_appender` feature.
 
+use chrono::{DateTime, Local};
 use derivative::Derivative;
-use log::Record;
+use log::{Level, Record};
 use std::{
-    fmt,
-    io::{self, Write},
+    collections::HashMap,
+    env,
+    fmt::{self, Write as FmtWrite},
+    io,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
 };
 
 #[cfg(feature = "config_parsing")]
@@ -14,15 +22,20 @@ use crate::config::{Deserialize, Deserializers};
 #[cfg(feature = "config_parsing")]
 use crate::encode::EncoderConfig;
 use crate::{
-    append::Append,
+    append::{
+        count::CountingWriter,
+        flush::{FlushToken, FlushTracker},
+        Append, AppendHealth, AppendStats, EncodeFailure,
+    },
     encode::{
         self,
         pattern::PatternEncoder,
         writer::{
+            ansi::AnsiWriter,
             console::{ConsoleWriter, ConsoleWriterLock},
             simple::SimpleWriter,
         },
-        Encode, Style,
+        Color, Encode, Style,
     },
     priv_io::{StdWriter, StdWriterLock},
 };
@@ -34,7 +47,12 @@ use crate::{
 pub struct ConsoleAppenderConfig {
     target: Option<ConfigTarget>,
     encoder: Option<EncoderConfig>,
+    encode_failure: Option<EncodeFailure>,
     tty_only: Option<bool>,
+    color: Option<ConfigColorMode>,
+    styles: Option<HashMap<Level, StyleConfig>>,
+    columns: Option<ColumnsConfig>,
+    non_blocking: Option<usize>,
 }
 
 #[cfg(feature = "config_parsing")]
@@ -46,31 +64,54 @@ enum ConfigTarget {
     Stderr,
 }
 
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize)]
+enum ConfigColorMode {
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "always")]
+    Always,
+    #[serde(rename = "never")]
+    Never,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnsConfig {
+    header: Option<bool>,
+    time_format: Option<String>,
+    max_target_width: Option<usize>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleConfig {
+    text: Option<Color>,
+    background: Option<Color>,
+    intense: Option<bool>,
+}
+
 enum Writer {
     Tty(ConsoleWriter),
+    Ansi(StdWriter),
     Raw(StdWriter),
 }
 
 impl Writer {
-    fn lock(&self) -> WriterLock {
+    fn lock(&self) -> WriterLock<'_> {
         match *self {
             Writer::Tty(ref w) => WriterLock::Tty(w.lock()),
+            Writer::Ansi(ref w) => WriterLock::Ansi(AnsiWriter(w.lock())),
             Writer::Raw(ref w) => WriterLock::Raw(SimpleWriter(w.lock())),
         }
     }
-
-    fn is_tty(&self) -> bool {
-        // 1.40 compat
-        #[allow(clippy::match_like_matches_macro)]
-        match self {
-            Self::Tty(_) => true,
-            _ => false,
-        }
-    }
 }
 
 enum WriterLock<'a> {
     Tty(ConsoleWriterLock<'a>),
+    Ansi(AnsiWriter<StdWriterLock<'a>>),
     Raw(SimpleWriter<StdWriterLock<'a>>),
 }
 
@@ -78,6 +119,7 @@ impl<'a> io::Write for WriterLock<'a> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         match *self {
             WriterLock::Tty(ref mut w) => w.write(buf),
+            WriterLock::Ansi(ref mut w) => w.write(buf),
             WriterLock::Raw(ref mut w) => w.write(buf),
         }
     }
@@ -85,6 +127,7 @@ impl<'a> io::Write for WriterLock<'a> {
     fn flush(&mut self) -> io::Result<()> {
         match *self {
             WriterLock::Tty(ref mut w) => w.flush(),
+            WriterLock::Ansi(ref mut w) => w.flush(),
             WriterLock::Raw(ref mut w) => w.flush(),
         }
     }
@@ -92,6 +135,7 @@ impl<'a> io::Write for WriterLock<'a> {
     fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
         match *self {
             WriterLock::Tty(ref mut w) => w.write_all(buf),
+            WriterLock::Ansi(ref mut w) => w.write_all(buf),
             WriterLock::Raw(ref mut w) => w.write_all(buf),
         }
     }
@@ -99,6 +143,7 @@ impl<'a> io::Write for WriterLock<'a> {
     fn write_fmt(&mut self, fmt: fmt::Arguments) -> io::Result<()> {
         match *self {
             WriterLock::Tty(ref mut w) => w.write_fmt(fmt),
+            WriterLock::Ansi(ref mut w) => w.write_fmt(fmt),
             WriterLock::Raw(ref mut w) => w.write_fmt(fmt),
         }
     }
@@ -108,53 +153,472 @@ impl<'a> encode::Write for WriterLock<'a> {
     fn set_style(&mut self, style: &Style) -> io::Result<()> {
         match *self {
             WriterLock::Tty(ref mut w) => w.set_style(style),
+            WriterLock::Ansi(ref mut w) => w.set_style(style),
             WriterLock::Raw(ref mut w) => w.set_style(style),
         }
     }
 }
 
+/// An `encode::Write`r which replaces any non-default style requested by the
+/// encoder with a fixed style, used to apply per-level style overrides.
+struct LevelStyleWriter<'a, W> {
+    w: W,
+    style: &'a Style,
+}
+
+impl<'a, W: encode::Write> io::Write for LevelStyleWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.w.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+impl<'a, W: encode::Write> encode::Write for LevelStyleWriter<'a, W> {
+    fn set_style(&mut self, style: &Style) -> io::Result<()> {
+        if *style == Style::new() {
+            self.w.set_style(style)
+        } else {
+            self.w.set_style(self.style)
+        }
+    }
+}
+
+/// The settings of the console appender's columnar output mode.
+///
+/// In columnar mode the appender's encoder is not used. Instead, the time,
+/// level, target and message of each record are written in aligned columns.
+/// The time and target columns widen to fit the values seen so far, up to a
+/// maximum width beyond which targets are abbreviated.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Columns {
+    header: bool,
+    time_format: String,
+    max_target_width: usize,
+}
+
+impl Default for Columns {
+    fn default() -> Columns {
+        Columns::builder().build()
+    }
+}
+
+impl Columns {
+    /// Creates a new `Columns` builder.
+    pub fn builder() -> ColumnsBuilder {
+        ColumnsBuilder {
+            header: false,
+            time_format: "%Y-%m-%d %H:%M:%S%.3f".to_owned(),
+            max_target_width: 32,
+        }
+    }
+
+    /// Returns whether a header row is written.
+    pub fn header(&self) -> bool {
+        self.header
+    }
+
+    /// Returns the chrono format string of the time column.
+    pub fn time_format(&self) -> &str {
+        &self.time_format
+    }
+
+    /// Returns the maximum width of the target column.
+    pub fn max_target_width(&self) -> usize {
+        self.max_target_width
+    }
+}
+
+/// A builder for `Columns`.
+#[derive(Clone, Debug)]
+pub struct ColumnsBuilder {
+    header: bool,
+    time_format: String,
+    max_target_width: usize,
+}
+
+impl ColumnsBuilder {
+    /// Sets whether a header row naming the columns is written before the
+    /// first record, and again whenever the column widths change.
+    ///
+    /// Defaults to `false`.
+    pub fn header(mut self, header: bool) -> ColumnsBuilder {
+        self.header = header;
+        self
+    }
+
+    /// Sets the chrono format string of the time column, in local time.
+    ///
+    /// Defaults to `%Y-%m-%d %H:%M:%S%.3f`.
+    pub fn time_format(mut self, time_format: String) -> ColumnsBuilder {
+        self.time_format = time_format;
+        self
+    }
+
+    /// Sets the maximum width of the target column, in characters.
+    ///
+    /// Longer targets have their leading path segments shortened to their
+    /// first letter, then are cut from the left. Defaults to 32.
+    pub fn max_target_width(mut self, max_target_width: usize) -> ColumnsBuilder {
+        self.max_target_width = max_target_width;
+        self
+    }
+
+    /// Consumes the `ColumnsBuilder`, producing `Columns`.
+    pub fn build(self) -> Columns {
+        Columns {
+            header: self.header,
+            time_format: self.time_format,
+            max_target_width: self.max_target_width.max(1),
+        }
+    }
+}
+
+const LEVEL_WIDTH: usize = 5;
+const HEADER: [&str; 4] = ["TIME", "LEVEL", "TARGET", "MESSAGE"];
+
+#[derive(Debug)]
+struct ColumnLayout {
+    columns: Columns,
+    state: Mutex<ColumnState>,
+}
+
+#[derive(Debug, Default)]
+struct ColumnState {
+    time_width: usize,
+    target_width: usize,
+    header_written: bool,
+}
+
+impl ColumnLayout {
+    fn new(columns: Columns) -> ColumnLayout {
+        let state = ColumnState {
+            time_width: HEADER[0].len(),
+            target_width: HEADER[2].len().min(columns.max_target_width),
+            header_written: false,
+        };
+        ColumnLayout {
+            columns,
+            state: Mutex::new(state),
+        }
+    }
+
+    fn write(
+        &self,
+        w: &mut dyn encode::Write,
+        record: &Record,
+        styles: &HashMap<Level, Style>,
+    ) -> anyhow::Result<()> {
+        let mut time = String::new();
+        write!(
+            time,
+            "{}",
+            DateTime::<Local>::from(encode::record_time()).format(&self.columns.time_format)
+        )
+        .map_err(|_| anyhow::anyhow!("invalid time format"))?;
+        let target = abbreviate(record.target(), self.columns.max_target_width);
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let time_len = time.chars().count();
+        let target_len = target.chars().count();
+        let mut resized = false;
+        if time_len > state.time_width {
+            state.time_width = time_len;
+            resized = true;
+        }
+        if target_len > state.target_width {
+            state.target_width = target_len;
+            resized = true;
+        }
+        if self.columns.header && (resized || !state.header_written) {
+            writeln!(
+                w,
+                "{:time$}  {:level$}  {:target$}  {}",
+                HEADER[0],
+                HEADER[1],
+                HEADER[2],
+                HEADER[3],
+                time = state.time_width,
+                level = LEVEL_WIDTH,
+                target = state.target_width,
+            )?;
+            state.header_written = true;
+        }
+
+        write!(w, "{:width$}  ", time, width = state.time_width)?;
+        let style = styles
+            .get(&record.level())
+            .cloned()
+            .unwrap_or_else(|| level_style(record.level()));
+        w.set_style(&style)?;
+        write!(w, "{:width$}", record.level(), width = LEVEL_WIDTH)?;
+        w.set_style(&Style::new())?;
+        write!(w, "  {:width$}  ", target, width = state.target_width)?;
+
+        // continuation lines of the message are indented to its column
+        let indent = state.time_width + LEVEL_WIDTH + state.target_width + 6;
+        drop(state);
+        let mut message = String::new();
+        write!(message, "{}", record.args())?;
+        for (i, line) in message.lines().enumerate() {
+            if i > 0 {
+                write!(w, "{:indent$}", "", indent = indent)?;
+            }
+            writeln!(w, "{}", line)?;
+        }
+        if message.is_empty() {
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the style the pattern encoder's `{h(..)}` formatter uses for a
+/// level.
+fn level_style(level: Level) -> Style {
+    let mut style = Style::new();
+    match level {
+        Level::Error => style.text(Color::Red).intense(true),
+        Level::Warn => style.text(Color::Yellow),
+        Level::Info => style.text(Color::Green),
+        Level::Trace => style.text(Color::Cyan),
+        Level::Debug => &mut style,
+    };
+    style
+}
+
+/// Fits a target into `width` characters, first shortening its leading path
+/// segments to their first letter and then cutting it from the left.
+fn abbreviate(target: &str, width: usize) -> String {
+    if target.chars().count() <= width {
+        return target.to_owned();
+    }
+
+    let mut segments = target.split("::").map(str::to_owned).collect::<Vec<_>>();
+    let last = segments.len() - 1;
+    for i in 0..last {
+        if let Some(c) = segments[i].chars().next() {
+            segments[i] = c.to_string();
+        }
+        let abbreviated = segments.join("::");
+        if abbreviated.chars().count() <= width {
+            return abbreviated;
+        }
+    }
+
+    let abbreviated = segments.join("::");
+    let len = abbreviated.chars().count();
+    let tail = abbreviated.chars().skip(len - width + 1);
+    std::iter::once('…').chain(tail).collect()
+}
+
+/// The queue of a console appender in non-blocking mode, whose records are
+/// written to the stream by a background thread.
+#[derive(Debug)]
+struct NonBlocking {
+    queue: Mutex<mpsc::Sender<(FlushToken, Vec<u8>)>>,
+    tracker: Arc<FlushTracker>,
+    capacity: usize,
+    queued: Arc<AtomicUsize>,
+    dropped: AtomicU64,
+}
+
+impl NonBlocking {
+    fn new<W>(
+        mut writer: W,
+        capacity: usize,
+        bytes_written: Arc<AtomicU64>,
+    ) -> io::Result<NonBlocking>
+    where
+        W: io::Write + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<(FlushToken, Vec<u8>)>();
+        let tracker = Arc::new(FlushTracker::new());
+        let queued = Arc::new(AtomicUsize::new(0));
+        let worker = (tracker.clone(), queued.clone());
+        thread::Builder::new()
+            .name("log4rs console".to_owned())
+            .spawn(move || {
+                let (tracker, queued) = worker;
+                for (token, record) in rx {
+                    if let Err(e) = writer.write_all(&record).and_then(|()| writer.flush()) {
+                        crate::handle_error(
+                            &anyhow::Error::new(e).context("error writing to console"),
+                        );
+                    } else {
+                        bytes_written.fetch_add(record.len() as u64, Ordering::Relaxed);
+                    }
+                    queued.fetch_sub(1, Ordering::SeqCst);
+                    tracker.complete(token);
+                }
+            })?;
+        Ok(NonBlocking {
+            queue: Mutex::new(tx),
+            tracker,
+            capacity: capacity.max(1),
+            queued,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    fn send(&self, record: Vec<u8>) -> anyhow::Result<()> {
+        let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if self.queued.load(Ordering::SeqCst) >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        let token = self.tracker.track();
+        if queue.send((token, record)).is_err() {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            self.tracker.complete(token);
+            anyhow::bail!("console writer thread has stopped");
+        }
+        Ok(())
+    }
+}
+
 /// An appender which logs to standard out.
 ///
 /// It supports output styling if standard out is a console buffer on Windows
-/// or is a TTY on Unix.
+/// or is a TTY on Unix. Styling can be forced on or off with
+/// `ConsoleAppenderBuilder::color`, and the styles used for each level can be
+/// overridden with `ConsoleAppenderBuilder::style`. Records can be written in
+/// aligned columns rather than through an encoder with
+/// `ConsoleAppenderBuilder::columns`.
+///
+/// By default records are written as they are logged, so a stalled terminal
+/// or pipe reader stalls logging. In non-blocking mode, set with
+/// `ConsoleAppenderBuilder::non_blocking`, records are written by a
+/// background thread instead, and dropped when it falls too far behind.
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct ConsoleAppender {
     #[derivative(Debug = "ignore")]
     writer: Writer,
     encoder: Box<dyn Encode>,
+    encode_failure: EncodeFailure,
     do_write: bool,
+    styles: HashMap<Level, Style>,
+    columns: Option<ColumnLayout>,
+    non_blocking: Option<NonBlocking>,
+    bytes_written: Arc<AtomicU64>,
 }
 
 impl Append for ConsoleAppender {
     fn append(&self, record: &Record) -> anyhow::Result<()> {
-        if self.do_write {
-            let mut writer = self.writer.lock();
-            self.encoder.encode(&mut writer, record)?;
-            writer.flush()?;
+        if !self.do_write {
+            return Ok(());
         }
-        Ok(())
+        if let Some(ref non_blocking) = self.non_blocking {
+            let mut buf = vec![];
+            match self.writer {
+                Writer::Raw(_) => self.write(&mut SimpleWriter(&mut buf), record)?,
+                Writer::Tty(_) | Writer::Ansi(_) => {
+                    self.write(&mut AnsiWriter(&mut buf), record)?
+                }
+            }
+            return non_blocking.send(buf);
+        }
+
+        let mut writer = CountingWriter::new(self.writer.lock());
+        let result = self.write(&mut writer, record);
+        self.bytes_written
+            .fetch_add(writer.bytes(), Ordering::Relaxed);
+        result
     }
 
     fn flush(&self) {}
+
+    fn flush_to(&self, token: FlushToken) {
+        if let Some(ref non_blocking) = self.non_blocking {
+            non_blocking.tracker.wait(token);
+        }
+    }
+
+    fn stats(&self) -> AppendStats {
+        AppendStats {
+            bytes_written: Some(self.bytes_written.load(Ordering::Relaxed)),
+            dropped: self
+                .non_blocking
+                .as_ref()
+                .map(|non_blocking| non_blocking.dropped.load(Ordering::Relaxed)),
+            ..AppendStats::default()
+        }
+    }
+
+    fn health(&self) -> AppendHealth {
+        AppendHealth {
+            queue_depth: self
+                .non_blocking
+                .as_ref()
+                .map(|non_blocking| non_blocking.queued.load(Ordering::SeqCst)),
+            ..AppendHealth::default()
+        }
+    }
 }
 
 impl ConsoleAppender {
+    fn write<W: encode::Write>(&self, writer: &mut W, record: &Record) -> anyhow::Result<()> {
+        if let Some(ref columns) = self.columns {
+            columns.write(writer, record, &self.styles)?;
+            writer.flush()?;
+            return Ok(());
+        }
+        let result = match self.styles.get(&record.level()) {
+            Some(style) => {
+                let mut writer = LevelStyleWriter {
+                    w: &mut *writer,
+                    style,
+                };
+                self.encoder.encode(&mut writer, record)
+            }
+            None => self.encoder.encode(writer, record),
+        };
+        // The plain line follows whatever the encoder wrote before failing.
+        if let Err(e) = result {
+            writer.set_style(&Style::new())?;
+            self.encode_failure.recover(e, record, writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
     /// Creates a new `ConsoleAppender` builder.
     pub fn builder() -> ConsoleAppenderBuilder {
         ConsoleAppenderBuilder {
             encoder: None,
+            encode_failure: EncodeFailure::Report,
             target: Target::Stdout,
             tty_only: false,
+            color: ColorMode::Auto,
+            styles: HashMap::new(),
+            columns: None,
+            non_blocking: None,
         }
     }
+
+    /// Returns the number of records dropped in non-blocking mode because
+    /// the background thread had fallen too far behind.
+    pub fn dropped(&self) -> u64 {
+        self.non_blocking.as_ref().map_or(0, |non_blocking| {
+            non_blocking.dropped.load(Ordering::Relaxed)
+        })
+    }
 }
 
 /// A builder for `ConsoleAppender`s.
 pub struct ConsoleAppenderBuilder {
     encoder: Option<Box<dyn Encode>>,
+    encode_failure: EncodeFailure,
     target: Target,
     tty_only: bool,
+    color: ColorMode,
+    styles: HashMap<Level, Style>,
+    columns: Option<Columns>,
+    non_blocking: Option<usize>,
 }
 
 impl ConsoleAppenderBuilder {
@@ -164,6 +628,14 @@ impl ConsoleAppenderBuilder {
         self
     }
 
+    /// Sets how a record the encoder fails to encode is handled.
+    ///
+    /// Defaults to `EncodeFailure::Report`.
+    pub fn encode_failure(mut self, encode_failure: EncodeFailure) -> ConsoleAppenderBuilder {
+        self.encode_failure = encode_failure;
+        self
+    }
+
     /// Sets the output stream to log to.
     ///
     /// Defaults to `Target::Stdout`.
@@ -180,31 +652,120 @@ impl ConsoleAppenderBuilder {
         self
     }
 
+    /// Sets when the output is styled.
+    ///
+    /// Defaults to `ColorMode::Auto`.
+    pub fn color(mut self, color: ColorMode) -> ConsoleAppenderBuilder {
+        self.color = color;
+        self
+    }
+
+    /// Sets the style used for records of the specified level.
+    ///
+    /// The style replaces whatever non-default style the encoder applies to
+    /// those records, for example the one chosen by the pattern encoder's
+    /// `{h(..)}` formatter.
+    pub fn style(mut self, level: Level, style: Style) -> ConsoleAppenderBuilder {
+        self.styles.insert(level, style);
+        self
+    }
+
+    /// Writes records in aligned columns instead of through the encoder.
+    ///
+    /// The level column uses the per-level styles, falling back to those of
+    /// the pattern encoder's `{h(..)}` formatter.
+    pub fn columns(mut self, columns: Columns) -> ConsoleAppenderBuilder {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// Writes records from a background thread, so that logging never waits
+    /// for a stalled terminal or pipe reader.
+    ///
+    /// Records are encoded as they are logged and queued for the thread. Once
+    /// `capacity` records are queued, further records are dropped until the
+    /// thread catches up, and counted by `ConsoleAppender::dropped` and
+    /// `Handle::stats`. Styled output is written as ANSI escape codes.
+    ///
+    /// By default records are written as they are logged.
+    pub fn non_blocking(mut self, capacity: usize) -> ConsoleAppenderBuilder {
+        self.non_blocking = Some(capacity);
+        self
+    }
+
     /// Consumes the `ConsoleAppenderBuilder`, producing a `ConsoleAppender`.
+    ///
+    /// Falls back to writing records as they are logged if the background
+    /// thread of non-blocking mode cannot be started.
     pub fn build(self) -> ConsoleAppender {
-        let writer = match self.target {
-            Target::Stderr => match ConsoleWriter::stderr() {
-                Some(writer) => Writer::Tty(writer),
-                None => Writer::Raw(StdWriter::stderr()),
-            },
-            Target::Stdout => match ConsoleWriter::stdout() {
-                Some(writer) => Writer::Tty(writer),
-                None => Writer::Raw(StdWriter::stdout()),
-            },
+        let target = self.target;
+        let std_writer = || match target {
+            Target::Stderr => StdWriter::stderr(),
+            Target::Stdout => StdWriter::stdout(),
+        };
+        let console = match target {
+            Target::Stderr => ConsoleWriter::stderr(),
+            Target::Stdout => ConsoleWriter::stdout(),
         };
+        let is_tty = console.is_some();
 
-        let do_write = writer.is_tty() || !self.tty_only;
+        let writer = match (self.color, console) {
+            (ColorMode::Never, _) => Writer::Raw(std_writer()),
+            (ColorMode::Auto, Some(_)) if no_color() => Writer::Raw(std_writer()),
+            (ColorMode::Auto, None) => Writer::Raw(std_writer()),
+            (ColorMode::Always, None) => Writer::Ansi(std_writer()),
+            (_, Some(writer)) => Writer::Tty(writer),
+        };
+
+        let do_write = is_tty || !self.tty_only;
+
+        let bytes_written = Arc::new(AtomicU64::new(0));
+        let non_blocking = self.non_blocking.and_then(|capacity| {
+            match NonBlocking::new(std_writer(), capacity, bytes_written.clone()) {
+                Ok(non_blocking) => Some(non_blocking),
+                Err(e) => {
+                    crate::handle_error(
+                        &anyhow::Error::new(e).context("error starting console writer thread"),
+                    );
+                    None
+                }
+            }
+        });
 
         ConsoleAppender {
             writer,
             encoder: self
                 .encoder
                 .unwrap_or_else(|| Box::<PatternEncoder>::default()),
+            encode_failure: self.encode_failure,
             do_write,
+            styles: self.styles,
+            columns: self.columns.map(ColumnLayout::new),
+            non_blocking,
+            bytes_written,
         }
     }
 }
 
+/// Returns true if the `NO_COLOR` environment variable is set to a non-empty
+/// value.
+fn no_color() -> bool {
+    env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty())
+}
+
+/// When the console appender styles its output.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ColorMode {
+    /// Style output only if the target is a console, and the `NO_COLOR`
+    /// environment variable is not set.
+    Auto,
+    /// Always style output, emitting ANSI escape codes if the target is not a
+    /// console.
+    Always,
+    /// Never style output.
+    Never,
+}
+
 /// The stream to log to.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Target {
@@ -227,9 +788,54 @@ pub enum Target {
 /// # Set this boolean when the console appender must only write when the target is a TTY.
 /// tty_only: false
 ///
+/// # When to style output. One of `auto`, `always` or `never`. `auto` styles
+/// # output only when the target is a TTY and the `NO_COLOR` environment
+/// # variable is unset. Defaults to `auto`.
+/// color: auto
+///
+/// # Per-level styles, replacing the styles applied by the encoder (for
+/// # example by the pattern encoder's `{h(..)}` formatter). Each style may set
+/// # `text` and `background` to one of `black`, `red`, `green`, `yellow`,
+/// # `blue`, `magenta`, `cyan` or `white`, and `intense` to a boolean.
+/// # Defaults to the encoder's styles.
+/// styles:
+///   error:
+///     text: red
+///     intense: true
+///   warn:
+///     text: yellow
+///
+/// # If present, records are written in aligned time, level, target and
+/// # message columns, and the encoder is not used.
+/// columns:
+///
+///   # Whether to write a header row before the first record, and again
+///   # whenever the column widths change. Defaults to `false`.
+///   header: true
+///
+///   # The chrono format string of the time column, in local time. Defaults
+///   # to `%Y-%m-%d %H:%M:%S%.3f`.
+///   time_format: "%H:%M:%S%.3f"
+///
+///   # The maximum width of the target column. Longer targets are
+///   # abbreviated. Defaults to 32.
+///   max_target_width: 24
+///
+/// # If set, records are written by a background thread so that a stalled
+/// # terminal or pipe reader never blocks logging. Once this many records are
+/// # queued, further records are dropped and counted until the thread catches
+/// # up. By default records are written as they are logged.
+/// non_blocking: 10000
+///
 /// # The encoder to use to format output. Defaults to `kind: pattern`.
 /// encoder:
 ///   kind: pattern
+///
+/// # How a record the encoder fails to encode is handled. "report" reports the
+/// # error and the record's message to the error handler, and "plain" writes a
+/// # plain line with the record's level, target and message and the error
+/// # after anything the encoder wrote. Defaults to "report".
+/// encode_failure: plain
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -257,9 +863,203 @@ impl Deserialize for ConsoleAppenderDeserializer {
         if let Some(tty_only) = config.tty_only {
             appender = appender.tty_only(tty_only);
         }
+        if let Some(color) = config.color {
+            let color = match color {
+                ConfigColorMode::Auto => ColorMode::Auto,
+                ConfigColorMode::Always => ColorMode::Always,
+                ConfigColorMode::Never => ColorMode::Never,
+            };
+            appender = appender.color(color);
+        }
+        for (level, style_config) in config.styles.unwrap_or_default() {
+            let mut style = Style::new();
+            if let Some(text) = style_config.text {
+                style.text(text);
+            }
+            if let Some(background) = style_config.background {
+                style.background(background);
+            }
+            if let Some(intense) = style_config.intense {
+                style.intense(intense);
+            }
+            appender = appender.style(level, style);
+        }
+        if let Some(columns_config) = config.columns {
+            let mut columns = Columns::builder();
+            if let Some(header) = columns_config.header {
+                columns = columns.header(header);
+            }
+            if let Some(time_format) = columns_config.time_format {
+                columns = columns.time_format(time_format);
+            }
+            if let Some(max_target_width) = columns_config.max_target_width {
+                columns = columns.max_target_width(max_target_width);
+            }
+            appender = appender.columns(columns.build());
+        }
+        if let Some(capacity) = config.non_blocking {
+            appender = appender.non_blocking(capacity);
+        }
         if let Some(encoder) = config.encoder {
             appender = appender.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
         }
+        if let Some(encode_failure) = config.encode_failure {
+            appender = appender.encode_failure(encode_failure);
+        }
         Ok(Box::new(appender.build()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encode::Write as EncodeWrite;
+
+    #[derive(Default)]
+    struct StyleRecorder(Vec<Style>);
+
+    impl io::Write for StyleRecorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl encode::Write for StyleRecorder {
+        fn set_style(&mut self, style: &Style) -> io::Result<()> {
+            self.0.push(style.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn level_style_override() {
+        let mut error_style = Style::new();
+        error_style.text(Color::Magenta).intense(false);
+
+        let mut recorder = StyleRecorder::default();
+        let mut w = LevelStyleWriter {
+            w: &mut recorder,
+            style: &error_style,
+        };
+        w.set_style(Style::new().text(Color::Red).intense(true))
+            .unwrap();
+        w.set_style(&Style::new()).unwrap();
+
+        assert_eq!(recorder.0, vec![error_style, Style::new()]);
+    }
+
+    /// A writer which blocks until it is released.
+    struct Stalled(Arc<Mutex<Vec<u8>>>, Mutex<mpsc::Receiver<()>>);
+
+    impl io::Write for Stalled {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let _ = self.1.lock().unwrap().recv();
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn non_blocking_drops() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let (release, stalled) = mpsc::channel();
+        let bytes_written = Arc::new(AtomicU64::new(0));
+        let non_blocking = NonBlocking::new(
+            Stalled(output.clone(), Mutex::new(stalled)),
+            2,
+            bytes_written.clone(),
+        )
+        .unwrap();
+
+        // The first record is taken by the stalled thread and the next two
+        // fill the queue.
+        for record in ["1", "2", "3", "4", "5"] {
+            non_blocking.send(record.as_bytes().to_vec()).unwrap();
+        }
+        assert_eq!(non_blocking.dropped.load(Ordering::Relaxed), 3);
+
+        for _ in 0..2 {
+            release.send(()).unwrap();
+        }
+        non_blocking.tracker.wait(FlushToken::issue());
+        assert_eq!(*output.lock().unwrap(), b"12");
+        assert_eq!(bytes_written.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn abbreviate_targets() {
+        assert_eq!(abbreviate("app::db", 10), "app::db");
+        assert_eq!(abbreviate("app::db::pool", 12), "a::db::pool");
+        assert_eq!(abbreviate("app::db::pool", 10), "a::d::pool");
+        assert_eq!(abbreviate("app::db::connection", 8), "…nection");
+    }
+
+    #[test]
+    fn columns() {
+        let layout = ColumnLayout::new(
+            Columns::builder()
+                .header(true)
+                .time_format("T".to_owned())
+                .max_target_width(10)
+                .build(),
+        );
+        let mut w = SimpleWriter(vec![]);
+        let styles = HashMap::new();
+        let mut record = |target, message| {
+            layout
+                .write(
+                    &mut w,
+                    &Record::builder()
+                        .level(Level::Info)
+                        .target(target)
+                        .args(format_args!("{}", message))
+                        .build(),
+                    &styles,
+                )
+                .unwrap()
+        };
+        record("app", "one");
+        record("app::db::pool", "two\nlines");
+
+        let expected = [
+            "TIME  LEVEL  TARGET  MESSAGE",
+            "T     INFO   app     one",
+            "TIME  LEVEL  TARGET      MESSAGE",
+            "T     INFO   a::d::pool  two",
+            "                         lines",
+        ];
+        assert_eq!(String::from_utf8(w.0).unwrap(), expected.join("\n") + "\n");
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn deserialize_styles() {
+        use crate::config::{Deserializers, RawConfig};
+
+        let config = "
+appenders:
+  console:
+    kind: console
+    color: never
+    styles:
+      error:
+        text: red
+        background: white
+        intense: true
+      info:
+        text: blue
+";
+        let config = ::serde_yaml::from_str::<RawConfig>(config).unwrap();
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::new());
+        assert!(errors.is_empty());
+        assert_eq!(appenders.len(), 1);
+    }
+}
@@ -0,0 +1,39 @@
+//! Counting the bytes appenders write.
+
+use std::io;
+
+use crate::encode::{self, Style};
+
+/// A writer which counts the bytes written through it.
+pub struct CountingWriter<W> {
+    w: W,
+    bytes: u64,
+}
+
+impl<W> CountingWriter<W> {
+    pub fn new(w: W) -> CountingWriter<W> {
+        CountingWriter { w, bytes: 0 }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl<W: io::Write> io::Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.w.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+impl<W: encode::Write> encode::Write for CountingWriter<W> {
+    fn set_style(&mut self, style: &Style) -> io::Result<()> {
+        self.w.set_style(style)
+    }
+}
@@ -0,0 +1,89 @@
+//! Creating the directories holding the files appenders write.
+
+use std::{fs, io, path::Path};
+
+/// How the directories holding the files an appender writes are created.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Directories {
+    pub create: bool,
+    pub max_depth: Option<usize>,
+}
+
+impl Default for Directories {
+    fn default() -> Directories {
+        Directories {
+            create: true,
+            max_depth: None,
+        }
+    }
+}
+
+impl Directories {
+    /// Creates the missing directories leading to the file at a path.
+    pub fn create_for(&self, path: &Path) -> io::Result<()> {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => return Ok(()),
+        };
+        if parent.is_dir() {
+            return Ok(());
+        }
+        if !self.create {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "log directory `{}` does not exist and creating it is disabled",
+                    parent.display()
+                ),
+            ));
+        }
+        if let Some(max_depth) = self.max_depth {
+            let missing = parent
+                .ancestors()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .take_while(|dir| !dir.exists())
+                .count();
+            if missing > max_depth {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "log directory `{}` needs {} missing directories created, more than the limit of {}",
+                        parent.display(),
+                        missing,
+                        max_depth
+                    ),
+                ));
+            }
+        }
+        fs::create_dir_all(parent)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn create_for() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a/b/c/app.log");
+
+        let disabled = Directories {
+            create: false,
+            ..Directories::default()
+        };
+        assert!(disabled.create_for(&path).is_err());
+        disabled.create_for(&dir.path().join("app.log")).unwrap();
+
+        let shallow = Directories {
+            max_depth: Some(2),
+            ..Directories::default()
+        };
+        assert!(shallow.create_for(&path).is_err());
+        assert!(!dir.path().join("a").exists());
+
+        fs::create_dir(dir.path().join("a")).unwrap();
+        shallow.create_for(&path).unwrap();
+        assert!(dir.path().join("a/b/c").is_dir());
+    }
+}
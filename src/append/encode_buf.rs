@@ -0,0 +1,64 @@
+//! The per-thread buffers records are encoded into before being written.
+
+use log::Record;
+use std::cell::RefCell;
+
+use crate::{
+    append::EncodeFailure,
+    encode::{writer::simple::SimpleWriter, Encode},
+};
+
+/// Buffers larger than this are not kept for the thread's next record.
+const MAX_RETAINED: usize = 64 * 1024;
+
+thread_local! {
+    static BUF: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Encodes `record` into a per-thread buffer and calls `f` with the
+/// bytes.
+///
+/// Appenders encode records before taking their lock, so that the lock
+/// is only held for the write itself. If the encoder fails, `failure`
+/// decides what `f` is called with.
+pub fn with_encoded<F, R>(
+    encoder: &dyn Encode,
+    failure: EncodeFailure,
+    record: &Record,
+    f: F,
+) -> anyhow::Result<R>
+where
+    F: FnOnce(&[u8]) -> anyhow::Result<R>,
+{
+    BUF.with(|buf| {
+        // An encoder or appender which logs while the buffer is in use
+        // gets a buffer of its own.
+        let mut buf = match buf.try_borrow_mut() {
+            Ok(buf) => buf,
+            Err(_) => return encode_into(&mut vec![], encoder, failure, record, f),
+        };
+        buf.clear();
+        let ret = encode_into(&mut buf, encoder, failure, record, f);
+        if buf.capacity() > MAX_RETAINED {
+            *buf = vec![];
+        }
+        ret
+    })
+}
+
+fn encode_into<F, R>(
+    buf: &mut Vec<u8>,
+    encoder: &dyn Encode,
+    failure: EncodeFailure,
+    record: &Record,
+    f: F,
+) -> anyhow::Result<R>
+where
+    F: FnOnce(&[u8]) -> anyhow::Result<R>,
+{
+    if let Err(e) = encoder.encode(&mut SimpleWriter(&mut *buf), record) {
+        buf.clear();
+        failure.recover(e, record, buf)?;
+    }
+    f(buf)
+}
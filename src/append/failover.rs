@@ -0,0 +1,307 @@
+//! The failover appender.
+//!
+//! Requires the `failover_appender` feature.
+
+use log::Record;
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::{
+    io,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::append::{flush::FlushToken, Append, Tail};
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+
+/// The failover appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailoverAppenderConfig {
+    primary: Inner,
+    secondary: Inner,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    probe_interval: Option<Duration>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct Inner {
+    kind: String,
+    config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Inner {
+    fn deserialize<D>(d: D) -> Result<Inner, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(Inner {
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
+
+/// An appender which writes to a primary appender, falling back to a
+/// secondary appender while the primary returns errors.
+///
+/// Once the primary fails, records go to the secondary, and every
+/// `probe_interval` one record is tried on the primary again to check whether
+/// it has recovered. The failure is reported to stderr when the appender
+/// fails over; records are only lost if the secondary fails as well.
+#[derive(Debug)]
+pub struct FailoverAppender {
+    primary: Box<dyn Append>,
+    secondary: Box<dyn Append>,
+    probe_interval: Duration,
+    // The time of the next probe of the primary, if it has failed.
+    next_probe: Mutex<Option<Instant>>,
+}
+
+impl FailoverAppender {
+    /// Creates a new `FailoverAppender` builder.
+    pub fn builder() -> FailoverAppenderBuilder {
+        FailoverAppenderBuilder {
+            probe_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// Returns true if the primary appender is currently failed over from.
+    pub fn is_failed_over(&self) -> bool {
+        self.next_probe
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    /// Returns true if the record should be tried on the primary appender,
+    /// claiming the probe if one is due.
+    fn try_primary(&self) -> bool {
+        let mut next_probe = self.next_probe.lock().unwrap_or_else(|e| e.into_inner());
+        match *next_probe {
+            None => true,
+            Some(at) => {
+                let now = Instant::now();
+                if now < at {
+                    return false;
+                }
+                *next_probe = Some(now + self.probe_interval);
+                true
+            }
+        }
+    }
+
+    fn primary_failed(&self, error: anyhow::Error) {
+        let mut next_probe = self.next_probe.lock().unwrap_or_else(|e| e.into_inner());
+        let failing_over = next_probe.is_none();
+        *next_probe = Some(Instant::now() + self.probe_interval);
+        drop(next_probe);
+
+        if failing_over {
+            crate::handle_error(&error.context("primary appender failed, failing over"));
+        }
+    }
+
+    fn primary_succeeded(&self) {
+        let mut next_probe = self.next_probe.lock().unwrap_or_else(|e| e.into_inner());
+        *next_probe = None;
+    }
+}
+
+impl Append for FailoverAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        if self.try_primary() {
+            match self.primary.append(record) {
+                Ok(()) => {
+                    self.primary_succeeded();
+                    return Ok(());
+                }
+                Err(e) => self.primary_failed(e),
+            }
+        }
+
+        self.secondary.append(record)
+    }
+
+    fn flush(&self) {
+        self.primary.flush();
+        self.secondary.flush();
+    }
+
+    fn flush_to(&self, token: FlushToken) {
+        self.primary.flush_to(token);
+        self.secondary.flush_to(token);
+    }
+
+    fn tail(&self, tail: Tail) -> Option<io::Result<Vec<u8>>> {
+        self.primary.tail(tail)
+    }
+
+    fn roll_now(&self) -> Option<anyhow::Result<()>> {
+        self.primary.roll_now()
+    }
+}
+
+/// A builder for `FailoverAppender`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct FailoverAppenderBuilder {
+    probe_interval: Duration,
+}
+
+impl FailoverAppenderBuilder {
+    /// Sets how often a failed primary appender is probed for recovery.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn probe_interval(mut self, probe_interval: Duration) -> FailoverAppenderBuilder {
+        self.probe_interval = probe_interval;
+        self
+    }
+
+    /// Consumes the `FailoverAppenderBuilder`, producing a `FailoverAppender`.
+    pub fn build(self, primary: Box<dyn Append>, secondary: Box<dyn Append>) -> FailoverAppender {
+        FailoverAppender {
+            primary,
+            secondary,
+            probe_interval: self.probe_interval,
+            next_probe: Mutex::new(None),
+        }
+    }
+}
+
+/// A deserializer for the `FailoverAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: failover
+///
+/// # The appender records are written to while it works. Required.
+/// primary:
+///
+///   # Identifies which appender is to be used. Required.
+///   kind: rolling_file
+///
+///   # The remainder of the configuration is passed to the appender's
+///   # deserializer, and will vary based on the kind of appender.
+///   path: /mnt/logs/app.log
+///
+/// # The appender records are written to while the primary fails. Required.
+/// secondary:
+///   kind: file
+///   path: /var/tmp/app.log
+///
+/// # How often a failed primary is probed for recovery. Defaults to 30
+/// # seconds.
+/// probe_interval: 1 minute
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct FailoverAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for FailoverAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = FailoverAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: FailoverAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let primary = deserializers.deserialize(&config.primary.kind, config.primary.config)?;
+        let secondary =
+            deserializers.deserialize(&config.secondary.kind, config.secondary.config)?;
+        let mut builder = FailoverAppender::builder();
+        if let Some(probe_interval) = config.probe_interval {
+            builder = builder.probe_interval(probe_interval);
+        }
+        Ok(Box::new(builder.build(primary, secondary)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct State {
+        broken: AtomicBool,
+        records: Mutex<Vec<String>>,
+    }
+
+    #[derive(Debug)]
+    struct TestAppender(Arc<State>);
+
+    impl Append for TestAppender {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            if self.0.broken.load(Ordering::SeqCst) {
+                anyhow::bail!("broken");
+            }
+            self.0
+                .records
+                .lock()
+                .unwrap()
+                .push(record.args().to_string());
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn failover() {
+        let primary = Arc::new(State::default());
+        let secondary = Arc::new(State::default());
+        let appender = FailoverAppender::builder()
+            .probe_interval(Duration::from_millis(50))
+            .build(
+                Box::new(TestAppender(primary.clone())),
+                Box::new(TestAppender(secondary.clone())),
+            );
+        let append = |message: &str| {
+            appender.append(&Record::builder().args(format_args!("{}", message)).build())
+        };
+
+        append("one").unwrap();
+        primary.broken.store(true, Ordering::SeqCst);
+        append("two").unwrap();
+        assert!(appender.is_failed_over());
+        primary.broken.store(false, Ordering::SeqCst);
+        append("three").unwrap();
+
+        thread::sleep(Duration::from_millis(60));
+        append("four").unwrap();
+        assert!(!appender.is_failed_over());
+
+        secondary.broken.store(true, Ordering::SeqCst);
+        primary.broken.store(true, Ordering::SeqCst);
+        assert!(append("five").is_err());
+
+        assert_eq!(*primary.records.lock().unwrap(), ["one", "four"]);
+        assert_eq!(*secondary.records.lock().unwrap(), ["two", "three"]);
+    }
+}
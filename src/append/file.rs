@@ -7,8 +7,10 @@ use log::Record;
 use parking_lot::Mutex;
 use std::{
     fs::{self, File, OpenOptions},
-    io::{self, BufWriter, Write},
+    io::{self, Write},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
 };
 
 #[cfg(feature = "config_parsing")]
@@ -17,7 +19,18 @@ use crate::config::{Deserialize, Deserializers};
 use crate::encode::EncoderConfig;
 
 use crate::{
-    append::{env_util::expand_env_vars, Append},
+    append::{
+        atomic::{self, AtomicWriter},
+        count::CountingWriter,
+        dir::Directories,
+        encode_buf::with_encoded,
+        env_util::expand_path,
+        output::{file_len, Output, DEFAULT_REGION},
+        perm::Permissions,
+        sync::{self, SyncData, Syncer},
+        tail::{open_tail, read_tail},
+        Append, AppendHealth, AppendStats, Backend, Durability, EncodeFailure, Oversized, Tail,
+    },
     encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
 };
 
@@ -28,7 +41,30 @@ use crate::{
 pub struct FileAppenderConfig {
     path: String,
     encoder: Option<EncoderConfig>,
+    encode_failure: Option<EncodeFailure>,
     append: Option<bool>,
+    header: Option<String>,
+    footer: Option<String>,
+    durability: Option<Durability>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    sync_interval: Option<Duration>,
+    preallocate: Option<u64>,
+    backend: Option<Backend>,
+    region_size: Option<u64>,
+    reopen_on_rotate: Option<bool>,
+    atomic_writes: Option<bool>,
+    atomic_write_limit: Option<usize>,
+    oversized_records: Option<Oversized>,
+    #[serde(deserialize_with = "crate::append::perm::de_mode", default)]
+    mode: Option<u32>,
+    #[serde(deserialize_with = "crate::append::perm::de_id", default)]
+    owner: Option<String>,
+    #[serde(deserialize_with = "crate::append::perm::de_id", default)]
+    group: Option<String>,
+    create_dirs: Option<bool>,
+    max_dir_depth: Option<usize>,
+    fallback_path: Option<String>,
+    lazy: Option<bool>,
 }
 
 /// An appender which logs to a file.
@@ -37,19 +73,83 @@ pub struct FileAppenderConfig {
 pub struct FileAppender {
     path: PathBuf,
     #[derivative(Debug = "ignore")]
-    file: Mutex<SimpleWriter<BufWriter<File>>>,
+    file: Mutex<Option<SimpleWriter<Output>>>,
+    append: bool,
+    directories: Directories,
+    backend: Backend,
+    region_size: u64,
     encoder: Box<dyn Encode>,
+    encode_failure: EncodeFailure,
+    header: Option<String>,
+    footer: Option<String>,
+    syncer: Syncer,
+    #[derivative(Debug = "ignore")]
+    watch: Option<Mutex<Watch>>,
+    atomic: Option<AtomicWriter>,
+    permissions: Permissions,
+    bytes_written: AtomicU64,
+}
+
+/// Tracks the log file to notice it being rotated by another program.
+struct Watch {
+    interval: Duration,
+    last_check: Instant,
+    // The length of the file as far as the appender knows.
+    len: u64,
+}
+
+impl Drop for FileAppender {
+    fn drop(&mut self) {
+        if let (Some(file), Some(footer)) = (self.file.get_mut(), &self.footer) {
+            if let Err(e) = file
+                .write_all(footer.as_bytes())
+                .and_then(|()| file.flush())
+            {
+                crate::handle_error(&anyhow::Error::new(e).context("error writing log footer"));
+            }
+        }
+    }
 }
 
 impl Append for FileAppender {
     fn append(&self, record: &Record) -> anyhow::Result<()> {
-        let mut file = self.file.lock();
-        self.encoder.encode(&mut *file, record)?;
-        file.flush()?;
-        Ok(())
+        with_encoded(&*self.encoder, self.encode_failure, record, |encoded| {
+            self.write(encoded)
+        })
+    }
+
+    fn flush(&self) {
+        if let Some(ref file) = *self.file.lock() {
+            if let Err(e) = self.syncer.flush(&file.0) {
+                crate::handle_error(&anyhow::Error::new(e).context("error syncing log file"));
+            }
+        }
+    }
+
+    fn tail(&self, tail: Tail) -> Option<io::Result<Vec<u8>>> {
+        Some(FileAppender::tail(self, tail))
+    }
+
+    fn stats(&self) -> AppendStats {
+        let (syncs, sync_time) = self.syncer.stats();
+        AppendStats {
+            bytes_written: Some(self.bytes_written.load(Ordering::Relaxed)),
+            syncs,
+            sync_time,
+            ..AppendStats::default()
+        }
     }
 
-    fn flush(&self) {}
+    fn health(&self) -> AppendHealth {
+        AppendHealth {
+            queue_depth: self
+                .file
+                .lock()
+                .as_ref()
+                .and_then(|file| file.0.queue_depth()),
+            ..AppendHealth::default()
+        }
+    }
 }
 
 impl FileAppender {
@@ -57,15 +157,140 @@ impl FileAppender {
     pub fn builder() -> FileAppenderBuilder {
         FileAppenderBuilder {
             encoder: None,
+            encode_failure: EncodeFailure::Report,
             append: true,
+            header: None,
+            footer: None,
+            durability: Durability::None,
+            sync_interval: sync::DEFAULT_INTERVAL,
+            preallocate: None,
+            backend: Backend::Write,
+            region_size: DEFAULT_REGION,
+            reopen_on_rotate: false,
+            atomic_writes: false,
+            atomic_write_limit: atomic::DEFAULT_LIMIT,
+            oversized_records: Oversized::Split,
+            permissions: Permissions::default(),
+            directories: Directories::default(),
+            fallback_path: None,
+            lazy: false,
+        }
+    }
+
+    /// Reads the last portion of the log file.
+    ///
+    /// Writes are only blocked while the records written so far are flushed
+    /// and the file is opened. The file is then read up to its length at that
+    /// point, so the result never ends partway through a record.
+    pub fn tail(&self, tail: Tail) -> io::Result<Vec<u8>> {
+        let (file, len) = {
+            let mut file = self.file.lock();
+            let written = match *file {
+                Some(ref mut file) => {
+                    file.flush()?;
+                    file.0.complete()?;
+                    match file.0 {
+                        // Only the start of the mapped region holds records.
+                        #[cfg(unix)]
+                        Output::Mmap(ref w) => Some(w.len()),
+                        _ => None,
+                    }
+                }
+                None => None,
+            };
+            open_tail(&self.path, written)?
+        };
+        read_tail(file, len, tail)
+    }
+
+    /// Writes an encoded record to the log file.
+    fn write(&self, encoded: &[u8]) -> anyhow::Result<()> {
+        let mut file = self.file.lock();
+        let mut watch = self.watch.as_ref().map(|watch| watch.lock());
+        if file.is_none() {
+            self.directories.create_for(&self.path)?;
+            let (opened, len) = open(
+                &self.path,
+                self.append,
+                self.header.as_deref(),
+                &self.permissions,
+                self.backend,
+                self.region_size,
+            )?;
+            *file = Some(opened);
+            if let Some(ref mut watch) = watch {
+                watch.last_check = Instant::now();
+                watch.len = len;
+            }
+            self.syncer.opened();
         }
+        // :( unwrap
+        let file = file.as_mut().unwrap();
+
+        if let Some(ref mut watch) = watch {
+            if watch.last_check.elapsed() >= watch.interval {
+                watch.last_check = Instant::now();
+                // Records still being written don't count towards the length.
+                file.0.complete()?;
+                if rotated(&self.path, file.0.file(), watch.len)? {
+                    // Records already written went to the rotated file.
+                    let _ = file.flush();
+                    let (reopened, len) = open(
+                        &self.path,
+                        true,
+                        self.header.as_deref(),
+                        &self.permissions,
+                        self.backend,
+                        self.region_size,
+                    )?;
+                    *file = reopened;
+                    watch.len = len;
+                    self.syncer.opened();
+                }
+            }
+        }
+
+        let (result, bytes) = match self.atomic {
+            Some(atomic) => match atomic.write(file.0.file(), encoded) {
+                Ok(bytes) => (Ok(()), bytes),
+                Err(e) => (Err(e), 0),
+            },
+            None => {
+                let mut writer = CountingWriter::new(&mut *file);
+                let result = writer.write_all(encoded).and_then(|()| writer.flush());
+                (result, writer.bytes())
+            }
+        };
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(ref mut watch) = watch {
+            watch.len += bytes;
+        }
+        result?;
+        self.syncer.written(&file.0, bytes)?;
+        Ok(())
     }
 }
 
 /// A builder for `FileAppender`s.
 pub struct FileAppenderBuilder {
     encoder: Option<Box<dyn Encode>>,
+    encode_failure: EncodeFailure,
     append: bool,
+    header: Option<String>,
+    footer: Option<String>,
+    durability: Durability,
+    sync_interval: Duration,
+    preallocate: Option<u64>,
+    backend: Backend,
+    region_size: u64,
+    reopen_on_rotate: bool,
+    atomic_writes: bool,
+    atomic_write_limit: usize,
+    oversized_records: Oversized,
+    permissions: Permissions,
+    directories: Directories,
+    fallback_path: Option<PathBuf>,
+    lazy: bool,
 }
 
 impl FileAppenderBuilder {
@@ -75,6 +300,14 @@ impl FileAppenderBuilder {
         self
     }
 
+    /// Sets how a record the encoder fails to encode is handled.
+    ///
+    /// Defaults to `EncodeFailure::Report`.
+    pub fn encode_failure(mut self, encode_failure: EncodeFailure) -> FileAppenderBuilder {
+        self.encode_failure = encode_failure;
+        self
+    }
+
     /// Determines if the appender will append to or truncate the output file.
     ///
     /// Defaults to `true`.
@@ -83,32 +316,345 @@ impl FileAppenderBuilder {
         self
     }
 
+    /// Sets text written at the start of the log file if it is empty when
+    /// opened, such as the column names of a CSV log.
+    ///
+    /// The header is written as is, so it should usually end with a newline.
+    pub fn header(mut self, header: &str) -> FileAppenderBuilder {
+        self.header = Some(header.to_owned());
+        self
+    }
+
+    /// Sets text written at the end of the log file when the appender is
+    /// dropped.
+    pub fn footer(mut self, footer: &str) -> FileAppenderBuilder {
+        self.footer = Some(footer.to_owned());
+        self
+    }
+
+    /// Sets how durably records are written.
+    ///
+    /// Defaults to `Durability::None`.
+    pub fn durability(mut self, durability: Durability) -> FileAppenderBuilder {
+        self.durability = durability;
+        self
+    }
+
+    /// Sets how long after a sync `Durability::Batched` waits before syncing
+    /// the file again.
+    ///
+    /// Defaults to 1 second.
+    pub fn sync_interval(mut self, sync_interval: Duration) -> FileAppenderBuilder {
+        self.sync_interval = sync_interval;
+        self
+    }
+
+    /// Reserves disk space for the log file ahead of the records written to
+    /// it, `preallocate` bytes at a time, so that writing a record rarely has
+    /// to allocate blocks and running out of disk space is noticed early.
+    ///
+    /// The reserved space does not count towards the file's length. Only
+    /// supported on Linux, and ignored elsewhere and by file systems which do
+    /// not support it. By default no space is reserved.
+    pub fn preallocate(mut self, preallocate: u64) -> FileAppenderBuilder {
+        self.preallocate = Some(preallocate);
+        self
+    }
+
+    /// Sets how records are written to the log file.
+    ///
+    /// Defaults to `Backend::Write`.
+    pub fn backend(mut self, backend: Backend) -> FileAppenderBuilder {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets the length in bytes of the regions of the log file the
+    /// `Backend::Mmap` backend maps at a time.
+    ///
+    /// Defaults to 16 MiB.
+    pub fn region_size(mut self, region_size: u64) -> FileAppenderBuilder {
+        self.region_size = region_size;
+        self
+    }
+
+    /// Determines if the appender checks whether the log file has been
+    /// deleted, moved or truncated by another program such as logrotate,
+    /// and reopens it if so.
+    ///
+    /// The path is checked at most once a second, before a record is written.
+    /// Once reopened, the file is always appended to.
+    ///
+    /// Defaults to `false`.
+    pub fn reopen_on_rotate(mut self, reopen_on_rotate: bool) -> FileAppenderBuilder {
+        self.reopen_on_rotate = reopen_on_rotate;
+        self
+    }
+
+    /// Determines if each record is written with a single write call to the
+    /// file opened for appending, so that records written by other appenders
+    /// or processes to the same file never land in the middle of it.
+    ///
+    /// The file is always appended to rather than truncated.
+    ///
+    /// Defaults to `false`.
+    pub fn atomic_writes(mut self, atomic_writes: bool) -> FileAppenderBuilder {
+        self.atomic_writes = atomic_writes;
+        self
+    }
+
+    /// Sets the length in bytes of the longest record written with a single
+    /// write call when `atomic_writes` is enabled.
+    ///
+    /// Defaults to 64 KiB.
+    pub fn atomic_write_limit(mut self, atomic_write_limit: usize) -> FileAppenderBuilder {
+        self.atomic_write_limit = atomic_write_limit;
+        self
+    }
+
+    /// Sets how records longer than the atomic write limit are written when
+    /// `atomic_writes` is enabled.
+    ///
+    /// Defaults to `Oversized::Split`.
+    pub fn oversized_records(mut self, oversized_records: Oversized) -> FileAppenderBuilder {
+        self.oversized_records = oversized_records;
+        self
+    }
+
+    /// Sets the permissions of the log file when the appender creates it,
+    /// such as `0o640`, regardless of the process umask.
+    ///
+    /// Only applies on Unix. Defaults to the permissions allowed by the umask.
+    pub fn mode(mut self, mode: u32) -> FileAppenderBuilder {
+        self.permissions.mode = Some(mode);
+        self
+    }
+
+    /// Sets the user owning the log file when the appender creates it, as a
+    /// name or a numeric ID.
+    ///
+    /// Only applies on Unix, and changing the owner usually requires root.
+    /// Defaults to the user running the process.
+    pub fn owner(mut self, owner: &str) -> FileAppenderBuilder {
+        self.permissions.owner = Some(owner.to_owned());
+        self
+    }
+
+    /// Sets the group owning the log file when the appender creates it, as a
+    /// name or a numeric ID.
+    ///
+    /// Only applies on Unix. The process must belong to the group unless it
+    /// runs as root. Defaults to the group the operating system chooses.
+    pub fn group(mut self, group: &str) -> FileAppenderBuilder {
+        self.permissions.group = Some(group.to_owned());
+        self
+    }
+
+    /// Determines if missing directories leading to the log file are created
+    /// when the appender is built.
+    ///
+    /// Defaults to `true`.
+    pub fn create_dirs(mut self, create_dirs: bool) -> FileAppenderBuilder {
+        self.directories.create = create_dirs;
+        self
+    }
+
+    /// Sets the most directories leading to the log file the appender
+    /// creates, so that a mistyped path fails rather than creating a tree of
+    /// directories in an unexpected place.
+    ///
+    /// Defaults to no limit.
+    pub fn max_dir_depth(mut self, max_dir_depth: usize) -> FileAppenderBuilder {
+        self.directories.max_depth = Some(max_dir_depth);
+        self
+    }
+
+    /// Determines if the log file is only created when the first record is
+    /// written to it, rather than when the appender is built.
+    ///
+    /// Missing directories are created at the same time, and a failure to
+    /// open the file is reported as an error appending that record. A lazily
+    /// created log file cannot have a fallback path. Defaults to `false`.
+    pub fn lazy(mut self, lazy: bool) -> FileAppenderBuilder {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Sets a path the appender logs to instead if the log file cannot be
+    /// opened at its path, for example because its directory cannot be
+    /// created.
+    ///
+    /// The failure is reported to the error handler. The fallback path can
+    /// contain environment variables like the path passed to `build`. By
+    /// default building the appender fails instead.
+    pub fn fallback_path<P: AsRef<Path>>(mut self, fallback_path: P) -> FileAppenderBuilder {
+        self.fallback_path = Some(fallback_path.as_ref().into());
+        self
+    }
+
     /// Consumes the `FileAppenderBuilder`, producing a `FileAppender`.
     /// The path argument can contain environment variables of the form $ENV{name_here},
     /// where 'name_here' will be the name of the environment variable that
     /// will be resolved. Note that if the variable fails to resolve,
-    /// $ENV{name_here} will NOT be replaced in the path.
+    /// $ENV{name_here} will NOT be replaced in the path. `{hostname}` and
+    /// `{pid}` are replaced with the name of the host and the ID of the process.
     pub fn build<P: AsRef<Path>>(self, path: P) -> io::Result<FileAppender> {
-        let path_cow = path.as_ref().to_string_lossy();
-        let path: PathBuf = expand_env_vars(path_cow).as_ref().into();
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+        if self.lazy && self.fallback_path.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a fallback path is not supported by lazily created log files",
+            ));
         }
-        let file = OpenOptions::new()
-            .write(true)
-            .append(self.append)
-            .truncate(!self.append)
-            .create(true)
-            .open(&path)?;
+        if self.reopen_on_rotate && self.backend == Backend::Mmap {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "reopening rotated log files is not supported by the Mmap backend, as truncating \
+                 a mapped file kills the process",
+            ));
+        }
+        if self.atomic_writes && self.backend != Backend::Write {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "atomic writes are not supported by the {:?} backend",
+                    self.backend
+                ),
+            ));
+        }
+
+        let path_cow = path.as_ref().to_string_lossy();
+        let path: PathBuf = expand_path(path_cow).as_ref().into();
+        let (path, (file, len)) = match self.open(&path) {
+            Ok(file) => (path, file),
+            Err(e) => match self.fallback_path {
+                Some(ref fallback) => {
+                    let fallback: PathBuf = expand_path(fallback.to_string_lossy()).as_ref().into();
+                    crate::handle_error(&anyhow::Error::new(e).context(format!(
+                        "error opening log file `{}`, logging to `{}` instead",
+                        path.display(),
+                        fallback.display()
+                    )));
+                    let file = self.open(&fallback)?;
+                    (fallback, file)
+                }
+                None => return Err(e),
+            },
+        };
+        // Mapped regions are allocated as they are mapped.
+        let preallocate = self.preallocate.filter(|_| self.backend != Backend::Mmap);
+        let watch = if self.reopen_on_rotate {
+            Some(Mutex::new(Watch {
+                interval: Duration::from_secs(1),
+                last_check: Instant::now(),
+                len,
+            }))
+        } else {
+            None
+        };
 
         Ok(FileAppender {
             path,
-            file: Mutex::new(SimpleWriter(BufWriter::with_capacity(1024, file))),
+            file: Mutex::new(file),
+            append: self.append || self.atomic_writes,
+            directories: self.directories,
+            backend: self.backend,
+            region_size: self.region_size,
             encoder: self
                 .encoder
                 .unwrap_or_else(|| Box::<PatternEncoder>::default()),
+            encode_failure: self.encode_failure,
+            header: self.header,
+            footer: self.footer,
+            syncer: Syncer::new(self.durability, self.sync_interval, preallocate),
+            watch,
+            atomic: if self.atomic_writes {
+                Some(AtomicWriter {
+                    limit: self.atomic_write_limit,
+                    oversized: self.oversized_records,
+                })
+            } else {
+                None
+            },
+            permissions: self.permissions,
+            bytes_written: AtomicU64::new(0),
         })
     }
+
+    /// Creates the directories leading to the log file and opens it, unless
+    /// it is created lazily.
+    fn open(&self, path: &Path) -> io::Result<(Option<SimpleWriter<Output>>, u64)> {
+        if self.lazy {
+            return Ok((None, 0));
+        }
+        self.directories.create_for(path)?;
+        let (file, len) = open(
+            path,
+            self.append || self.atomic_writes,
+            self.header.as_deref(),
+            &self.permissions,
+            self.backend,
+            self.region_size,
+        )?;
+        Ok((Some(file), len))
+    }
+}
+
+/// Opens the log file, writing the header if it is empty, and returns it
+/// along with its length, wrapped in the writer for `backend`.
+fn open(
+    path: &Path,
+    append: bool,
+    header: Option<&str>,
+    permissions: &Permissions,
+    backend: Backend,
+    region_size: u64,
+) -> io::Result<(SimpleWriter<Output>, u64)> {
+    let mut file = permissions.open(
+        OpenOptions::new()
+            // mapping the file for writing requires it to be readable
+            .read(backend == Backend::Mmap)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .create(true),
+        path,
+    )?;
+    let mut len = file_len(backend, &file)?;
+
+    if let (0, Some(header)) = (len, header) {
+        file.write_all(header.as_bytes())?;
+        len = header.len() as u64;
+    }
+
+    let output = Output::new(backend, file, len, region_size)?;
+    Ok((SimpleWriter(output), len))
+}
+
+/// Returns true if the file at `path` is no longer `file`, or is shorter than
+/// the `len` written to it.
+fn rotated(path: &Path, file: &File, len: u64) -> io::Result<bool> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(true),
+        Err(e) => return Err(e),
+    };
+    if metadata.len() < len {
+        return Ok(true);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let open = file.metadata()?;
+        if (metadata.dev(), metadata.ino()) != (open.dev(), open.ino()) {
+            return Ok(true);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = file;
+
+    Ok(false)
 }
 
 /// A deserializer for the `FileAppender`.
@@ -122,16 +668,108 @@ impl FileAppenderBuilder {
 /// # The path can contain environment variables of the form $ENV{name_here},
 /// # where 'name_here' will be the name of the environment variable that
 /// # will be resolved. Note that if the variable fails to resolve,
-/// # $ENV{name_here} will NOT be replaced in the path.
+/// # $ENV{name_here} will NOT be replaced in the path. `{hostname}` and `{pid}`
+/// # are replaced with the name of the host and the ID of the process.
 /// path: log/foo.log
 ///
 /// # Specifies if the appender should append to or truncate the log file if it
 /// # already exists. Defaults to `true`.
 /// append: true
 ///
+/// # Text written at the start of the log file if it is empty, and at its end
+/// # when the appender is dropped. Both are written as is, and are not written
+/// # by default.
+/// header: "time,level,message\n"
+/// footer: "log closed\n"
+///
+/// # How durably records are written. "none" leaves syncing the file to disk
+/// # to the operating system, "on_flush" syncs it when the appender is
+/// # flushed, "batched" syncs it at most once per sync interval and when the
+/// # appender is flushed, and "strict" syncs it after every record. Defaults
+/// # to "none".
+/// durability: batched
+///
+/// # How long "batched" durability waits after a sync before syncing again.
+/// # Defaults to 1 second.
+/// sync_interval: 5 seconds
+///
+/// # If set, disk space is reserved for the log file ahead of the records
+/// # written to it, this many bytes at a time. Only supported on Linux. By
+/// # default no space is reserved.
+/// preallocate: 67108864
+///
+/// # How records are written to the log file. "write" writes each record with
+/// # a write call, "mmap" copies records into a memory mapping of a region at
+/// # the end of the file, which is extended a region at a time and truncated
+/// # to the records written when the appender is dropped, and "io_uring"
+/// # hands records to a background thread which writes them with io_uring.
+/// # "mmap" is only supported on Unix, and "io_uring" only on Linux with the
+/// # `io_uring` feature. Neither is supported with atomic writes. Defaults to
+/// # "write".
+/// backend: mmap
+///
+/// # The length in bytes of the regions the "mmap" backend maps at a time.
+/// # Defaults to 16777216.
+/// region_size: 67108864
+///
+/// # Specifies if the appender should check whether the log file has been
+/// # deleted, moved or truncated by another program such as logrotate, and
+/// # reopen it if so. The path is checked at most once a second. Defaults to
+/// # `false`.
+/// reopen_on_rotate: true
+///
+/// # Specifies if each record should be written with a single write call to
+/// # the file opened for appending, so that records from other appenders or
+/// # processes writing to the same file cannot land in the middle of it. The
+/// # file is always appended to if enabled. Defaults to `false`.
+/// atomic_writes: true
+///
+/// # The length in bytes of the longest record written with a single write
+/// # call. Defaults to 65536.
+/// atomic_write_limit: 16384
+///
+/// # How records longer than the limit are written. "split" writes them in
+/// # pieces no longer than the limit, and "truncate" cuts them short, keeping
+/// # the final newline. Defaults to "split".
+/// oversized_records: truncate
+///
+/// # The permissions of the log file when the appender creates it, written in
+/// # octal, and the user and group owning it, as names or numeric IDs. Only
+/// # applied on Unix. By default the file is created as the process umask
+/// # allows, owned by the user running the process.
+/// mode: "0640"
+/// owner: app
+/// group: adm
+///
+/// # Specifies if missing directories leading to the log file are created.
+/// # Defaults to `true`.
+/// create_dirs: true
+///
+/// # The most directories leading to the log file that are created. Defaults
+/// # to no limit.
+/// max_dir_depth: 2
+///
+/// # A path logged to instead if the log file cannot be opened at its path,
+/// # for example because its directory cannot be created. The failure is
+/// # reported to the error handler. By default the appender fails to build
+/// # instead.
+/// fallback_path: /tmp/foo.log
+///
+/// # If set to `true`, the log file and the directories leading to it are
+/// # only created when the first record is written, rather than when the
+/// # appender is built. Cannot be combined with `fallback_path`. Defaults to
+/// # `false`.
+/// lazy: false
+///
 /// # The encoder to use to format output. Defaults to `kind: pattern`.
 /// encoder:
 ///   kind: pattern
+///
+/// # How a record the encoder fails to encode is handled. "report" reports the
+/// # error and the record's message to the error handler, and "plain" writes a
+/// # plain line with the record's level, target and message and the error in
+/// # its place. Defaults to "report".
+/// encode_failure: plain
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -152,9 +790,66 @@ impl Deserialize for FileAppenderDeserializer {
         if let Some(append) = config.append {
             appender = appender.append(append);
         }
+        if let Some(header) = config.header {
+            appender = appender.header(&header);
+        }
+        if let Some(footer) = config.footer {
+            appender = appender.footer(&footer);
+        }
+        if let Some(durability) = config.durability {
+            appender = appender.durability(durability);
+        }
+        if let Some(sync_interval) = config.sync_interval {
+            appender = appender.sync_interval(sync_interval);
+        }
+        if let Some(preallocate) = config.preallocate {
+            appender = appender.preallocate(preallocate);
+        }
+        if let Some(backend) = config.backend {
+            appender = appender.backend(backend);
+        }
+        if let Some(region_size) = config.region_size {
+            appender = appender.region_size(region_size);
+        }
+        if let Some(reopen_on_rotate) = config.reopen_on_rotate {
+            appender = appender.reopen_on_rotate(reopen_on_rotate);
+        }
+        if let Some(atomic_writes) = config.atomic_writes {
+            appender = appender.atomic_writes(atomic_writes);
+        }
+        if let Some(atomic_write_limit) = config.atomic_write_limit {
+            appender = appender.atomic_write_limit(atomic_write_limit);
+        }
+        if let Some(oversized_records) = config.oversized_records {
+            appender = appender.oversized_records(oversized_records);
+        }
+        if let Some(mode) = config.mode {
+            appender = appender.mode(mode);
+        }
+        if let Some(owner) = config.owner {
+            appender = appender.owner(&owner);
+        }
+        if let Some(group) = config.group {
+            appender = appender.group(&group);
+        }
+        if let Some(create_dirs) = config.create_dirs {
+            appender = appender.create_dirs(create_dirs);
+        }
+        if let Some(max_dir_depth) = config.max_dir_depth {
+            appender = appender.max_dir_depth(max_dir_depth);
+        }
+        if let Some(fallback_path) = config.fallback_path {
+            appender = appender.fallback_path(fallback_path);
+        }
+        if let Some(lazy) = config.lazy {
+            appender = appender.lazy(lazy);
+        }
         if let Some(encoder) = config.encoder {
             appender = appender.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
         }
+        if let Some(encode_failure) = config.encode_failure {
+            appender = appender.encode_failure(encode_failure);
+        }
         Ok(Box::new(appender.build(&config.path)?))
     }
 }
@@ -172,6 +867,27 @@ mod test {
             .unwrap();
     }
 
+    #[test]
+    fn fallback_path() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("missing").join("foo.log");
+        let fallback = tempdir.path().join("fallback.log");
+
+        assert!(FileAppender::builder()
+            .create_dirs(false)
+            .build(&path)
+            .is_err());
+
+        let appender = FileAppender::builder()
+            .create_dirs(false)
+            .fallback_path(&fallback)
+            .build(&path)
+            .unwrap();
+        assert_eq!(appender.path, fallback);
+        assert!(fallback.exists());
+        assert!(!path.exists());
+    }
+
     #[test]
     fn append_false() {
         let tempdir = tempfile::tempdir().unwrap();
@@ -180,4 +896,405 @@ mod test {
             .build(tempdir.path().join("foo.log"))
             .unwrap();
     }
+
+    #[test]
+    fn header_footer() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("foo.log");
+        for message in ["one", "two"] {
+            let appender = FileAppender::builder()
+                .encoder(Box::new(PatternEncoder::new("{m}\n")))
+                .header("header\n")
+                .footer("footer\n")
+                .build(&path)
+                .unwrap();
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+
+        assert_eq!(
+            fs::read(&path).unwrap(),
+            b"header\none\nfooter\ntwo\nfooter\n"
+        );
+    }
+
+    #[test]
+    fn lazy() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("logs").join("foo.log");
+
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}\n")))
+            .header("header\n")
+            .footer("footer\n")
+            .lazy(true)
+            .build(&path)
+            .unwrap();
+        appender.flush();
+        drop(appender);
+        assert!(!path.parent().unwrap().exists());
+
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}\n")))
+            .header("header\n")
+            .lazy(true)
+            .build(&path)
+            .unwrap();
+        assert!(!path.exists());
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"header\none\n");
+
+        assert!(FileAppender::builder()
+            .lazy(true)
+            .fallback_path(tempdir.path().join("fallback.log"))
+            .build(&path)
+            .is_err());
+    }
+
+    #[test]
+    fn encode_failure() {
+        #[derive(Debug)]
+        struct Failing;
+
+        impl Encode for Failing {
+            fn encode(&self, w: &mut dyn crate::encode::Write, _: &Record) -> anyhow::Result<()> {
+                w.write_all(b"partial")?;
+                Err(anyhow::anyhow!("unserializable field"))
+            }
+        }
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("foo.log");
+        let record = Record::builder()
+            .level(log::Level::Warn)
+            .target("app")
+            .args(format_args!("hello"))
+            .build();
+
+        let appender = FileAppender::builder()
+            .encoder(Box::new(Failing))
+            .build(&path)
+            .unwrap();
+        let error = appender.append(&record).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "error encoding record `hello`: unserializable field"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+        let appender = FileAppender::builder()
+            .encoder(Box::new(Failing))
+            .encode_failure(EncodeFailure::Plain)
+            .build(&path)
+            .unwrap();
+        appender.append(&record).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "WARN app - hello (error encoding record: unserializable field)\n"
+        );
+    }
+
+    #[test]
+    fn durability() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let appender = FileAppender::builder()
+            .build(tempdir.path().join("none.log"))
+            .unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+        assert_eq!(appender.stats().syncs, None);
+
+        let appender = FileAppender::builder()
+            .durability(Durability::Strict)
+            .build(tempdir.path().join("strict.log"))
+            .unwrap();
+        for message in ["one", "two"] {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+        assert_eq!(appender.stats().syncs, Some(2));
+
+        let appender = FileAppender::builder()
+            .durability(Durability::Batched)
+            .build(tempdir.path().join("batched.log"))
+            .unwrap();
+        for message in ["one", "two"] {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+        appender.flush();
+        // Flushing syncs whatever the batch left unsynced.
+        assert!((1..=3).contains(&appender.stats().syncs.unwrap()));
+        assert!(appender.stats().sync_time.is_some());
+
+        let appender = FileAppender::builder()
+            .durability(Durability::Batched)
+            .sync_interval(Duration::from_secs(3600))
+            .build(tempdir.path().join("batched_hourly.log"))
+            .unwrap();
+        for message in ["one", "two", "three"] {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+        assert_eq!(appender.stats().syncs, Some(0));
+
+        let appender = FileAppender::builder()
+            .durability(Durability::OnFlush)
+            .build(tempdir.path().join("on_flush.log"))
+            .unwrap();
+        for message in ["one", "two"] {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+        assert_eq!(appender.stats().syncs, Some(0));
+        appender.flush();
+        assert_eq!(appender.stats().syncs, Some(1));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn preallocate() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("preallocated.log");
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .preallocate(1024 * 1024)
+            .build(&path)
+            .unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+
+        // the reserved space is not part of the file's contents
+        assert_eq!(fs::read(&path).unwrap(), b"one\n");
+        let metadata = fs::metadata(&path).unwrap();
+        // unless the file system cannot preallocate
+        if metadata.blocks() > 8 {
+            assert!(metadata.blocks() * 512 >= 1024 * 1024);
+        }
+    }
+
+    #[test]
+    fn atomic_writes() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("shared.log");
+        let appenders = (0..2)
+            .map(|_| {
+                FileAppender::builder()
+                    .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+                    .atomic_writes(true)
+                    .build(&path)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        std::thread::scope(|s| {
+            for (appender, c) in appenders.iter().zip(["a", "b"]) {
+                s.spawn(move || {
+                    let message = c.repeat(3000);
+                    for _ in 0..200 {
+                        appender
+                            .append(&Record::builder().args(format_args!("{}", message)).build())
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 400);
+        for line in contents.lines() {
+            assert!(line == "a".repeat(3000) || line == "b".repeat(3000));
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn reopen_on_rotate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("foo.log");
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}\n")))
+            .reopen_on_rotate(true)
+            .build(&path)
+            .unwrap();
+        appender.watch.as_ref().unwrap().lock().interval = Duration::from_secs(0);
+        let log = |message| {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        };
+
+        log("one");
+        fs::rename(&path, path.with_extension("1")).unwrap();
+        log("two");
+        assert_eq!(fs::read(path.with_extension("1")).unwrap(), b"one\n");
+        assert_eq!(fs::read(&path).unwrap(), b"two\n");
+
+        fs::remove_file(&path).unwrap();
+        log("three");
+        assert_eq!(fs::read(&path).unwrap(), b"three\n");
+
+        File::create(&path).unwrap();
+        log("four");
+        assert_eq!(fs::read(&path).unwrap(), b"four\n");
+    }
+
+    #[test]
+    fn tail() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}\n")))
+            .build(tempdir.path().join("foo.log"))
+            .unwrap();
+        assert_eq!(appender.tail(Tail::Lines(2)).unwrap(), b"");
+
+        for message in ["one", "two", "three"] {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+
+        assert_eq!(appender.tail(Tail::Lines(2)).unwrap(), b"two\nthree\n");
+        assert_eq!(appender.tail(Tail::Lines(5)).unwrap(), b"one\ntwo\nthree\n");
+        assert_eq!(appender.tail(Tail::Bytes(4)).unwrap(), b"ree\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mmap_backend() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("mmap.log");
+        fs::write(&path, "zero\n").unwrap();
+
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}\n")))
+            .backend(Backend::Mmap)
+            .region_size(8)
+            .footer("end\n")
+            .durability(Durability::OnFlush)
+            .build(&path)
+            .unwrap();
+        for message in ["one", "two", "a record longer than a region"] {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+        appender.flush();
+        assert_eq!(appender.stats().syncs, Some(1));
+
+        // the file is extended by whole pages while mapped
+        assert!(fs::metadata(&path).unwrap().len() % 4096 == 0);
+        assert_eq!(
+            appender.tail(Tail::Lines(2)).unwrap(),
+            b"two\na record longer than a region\n"
+        );
+
+        drop(appender);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "zero\none\ntwo\na record longer than a region\nend\n"
+        );
+
+        assert!(FileAppender::builder()
+            .backend(Backend::Mmap)
+            .atomic_writes(true)
+            .build(&path)
+            .is_err());
+        assert!(FileAppender::builder()
+            .backend(Backend::Mmap)
+            .reopen_on_rotate(true)
+            .build(&path)
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mmap_backend_after_crash() {
+        use std::io::Write;
+
+        use crate::append::mmap::MmapWriter;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("mmap.log");
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        // output which ends in zeroes, from a process which crashed with the
+        // region still mapped
+        let mut writer = MmapWriter::new(file, 0, 100_000);
+        writer.write_all(b"zero\n\0\0").unwrap();
+        std::mem::forget(writer);
+        assert!(fs::metadata(&path).unwrap().len() >= 100_000);
+
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}\n")))
+            .backend(Backend::Mmap)
+            .build(&path)
+            .unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+        drop(appender);
+        assert_eq!(fs::read(&path).unwrap(), b"zero\n\0\0one\n");
+
+        // a file which does not end in a trailer is kept as it is
+        fs::write(&path, b"zero\0\0").unwrap();
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}\n")))
+            .backend(Backend::Mmap)
+            .build(&path)
+            .unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+        drop(appender);
+        assert_eq!(fs::read(&path).unwrap(), b"zero\0\0one\n");
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", feature = "io_uring"))]
+    fn io_uring_backend() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("io_uring.log");
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}\n")))
+            .backend(Backend::IoUring)
+            .durability(Durability::OnFlush)
+            .build(&path)
+            .unwrap();
+        for i in 0..1000 {
+            appender
+                .append(&Record::builder().args(format_args!("{}", i)).build())
+                .unwrap();
+        }
+
+        // flushing waits for the writes in flight
+        appender.flush();
+        assert_eq!(appender.stats().syncs, Some(1));
+        assert_eq!(appender.health().queue_depth, Some(0));
+        let expected = (0..1000).map(|i| format!("{}\n", i)).collect::<String>();
+        assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+        assert_eq!(appender.tail(Tail::Lines(1)).unwrap(), b"999\n");
+
+        appender
+            .append(&Record::builder().args(format_args!("last")).build())
+            .unwrap();
+        drop(appender);
+        assert!(fs::read_to_string(&path).unwrap().ends_with("999\nlast\n"));
+    }
 }
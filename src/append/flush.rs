@@ -0,0 +1,147 @@
+//! Flush tokens for appenders which buffer records.
+//!
+//! An appender which hands records off to be written later, for example by a
+//! background thread, cannot flush them from `Append::flush` alone: records it
+//! is still writing when the flush starts must be waited for, while records
+//! appended after it started need not be. log4rs passes such appenders a
+//! `FlushToken` through `Append::flush_to`, and a `FlushTracker` lets them
+//! wait for exactly the records appended before the token was issued.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// A point in the sequence of records passed to appenders.
+///
+/// Tokens are ordered by the time they were issued.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct FlushToken(u64);
+
+impl FlushToken {
+    /// Issues a new token, ordered after every token issued before it.
+    pub fn issue() -> FlushToken {
+        FlushToken(NEXT_TOKEN.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Tracks the records buffered by an appender until they are durable.
+///
+/// Call `track` as each record is buffered, `complete` once records have been
+/// written, and `wait` from `Append::flush_to`.
+#[derive(Debug, Default)]
+pub struct FlushTracker {
+    // The tokens of the buffered records, in the order they were tracked.
+    pending: Mutex<VecDeque<FlushToken>>,
+    completed: Condvar,
+}
+
+impl FlushTracker {
+    /// Creates a new `FlushTracker`.
+    pub fn new() -> FlushTracker {
+        FlushTracker::default()
+    }
+
+    /// Issues the token of a record being buffered.
+    ///
+    /// Records must be tracked in the order they will be written, for
+    /// example by calling this while holding the lock of the appender's
+    /// queue.
+    pub fn track(&self) -> FlushToken {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let token = FlushToken::issue();
+        pending.push_back(token);
+        token
+    }
+
+    /// Marks the records tracked up to and including `token` as durable,
+    /// waking the flushes waiting for them.
+    ///
+    /// Records which could not be written must be completed as well, or
+    /// flushes will wait for them forever.
+    pub fn complete(&self, token: FlushToken) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        while pending.front().map_or(false, |&front| front <= token) {
+            pending.pop_front();
+        }
+        drop(pending);
+        self.completed.notify_all();
+    }
+
+    /// Returns the number of tracked records which are not yet durable.
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Blocks until every record tracked before `token` was issued is
+    /// durable.
+    pub fn wait(&self, token: FlushToken) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        while pending.front().map_or(false, |&front| front < token) {
+            pending = self
+                .completed
+                .wait(pending)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Like `wait`, but gives up after `timeout`, returning false if records
+    /// tracked before `token` was issued are still pending.
+    pub fn wait_timeout(&self, token: FlushToken, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        while pending.front().map_or(false, |&front| front < token) {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            pending = self
+                .completed
+                .wait_timeout(pending, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::*;
+
+    #[test]
+    fn wait() {
+        let tracker = Arc::new(FlushTracker::new());
+        let first = tracker.track();
+        let second = tracker.track();
+        let token = FlushToken::issue();
+        let third = tracker.track();
+        assert!(first < second && second < token && token < third);
+
+        assert!(!tracker.wait_timeout(token, Duration::from_millis(10)));
+        tracker.complete(first);
+        assert!(!tracker.wait_timeout(token, Duration::from_millis(10)));
+
+        let writer = {
+            let tracker = tracker.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                tracker.complete(second);
+            })
+        };
+        tracker.wait(token);
+        writer.join().unwrap();
+
+        // The third record was tracked after the token was issued.
+        assert!(tracker.wait_timeout(token, Duration::from_millis(0)));
+        assert!(!tracker.wait_timeout(FlushToken::issue(), Duration::from_millis(0)));
+    }
+}
@@ -0,0 +1,517 @@
+//! The Kafka appender.
+//!
+//! Requires the `kafka` feature.
+
+use ::kafka::producer::{Producer, Record as KafkaRecord, RequiredAcks};
+use derivative::Derivative;
+use log::Record;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+#[cfg(feature = "config_parsing")]
+use crate::encode::EncoderConfig;
+use crate::{
+    append::{
+        flush::{FlushToken, FlushTracker},
+        Append, AppendHealth,
+    },
+    background::{self, Message, Task},
+    encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+};
+
+/// The Kafka appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaAppenderConfig {
+    brokers: Vec<String>,
+    topic: String,
+    key: Option<Key>,
+    encoder: Option<EncoderConfig>,
+    acks: Option<Acks>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    ack_timeout: Option<Duration>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    batch_interval: Option<Duration>,
+    max_batch: Option<usize>,
+}
+
+/// The key records are published with, which decides the partition they are
+/// published to.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Key {
+    /// Records are published without a key, spread across the partitions.
+    #[default]
+    None,
+    /// Records are keyed by their target.
+    Target,
+    /// Records are keyed by the value of an MDC key, and published without a
+    /// key when it is not set.
+    Mdc(String),
+}
+
+/// The acknowledgement the brokers give for published records.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Acks {
+    /// The brokers do not acknowledge records, so failures to store them go
+    /// unnoticed.
+    None,
+    /// The leader of the partition acknowledges records once it has stored
+    /// them.
+    #[default]
+    One,
+    /// The leader acknowledges records once all in-sync replicas have stored
+    /// them.
+    All,
+}
+
+impl From<Acks> for RequiredAcks {
+    fn from(acks: Acks) -> RequiredAcks {
+        match acks {
+            Acks::None => RequiredAcks::None,
+            Acks::One => RequiredAcks::One,
+            Acks::All => RequiredAcks::All,
+        }
+    }
+}
+
+// The key and value of a record.
+type KeyValue = (Vec<u8>, Vec<u8>);
+
+type Publish = Box<dyn FnMut(&[KeyValue]) -> anyhow::Result<()> + Send>;
+
+/// An appender which publishes encoded records to a Kafka topic.
+///
+/// Records are encoded as they are appended and published in batches in the
+/// background. The producer connects to the brokers when the first batch is
+/// published, and again after a batch fails. A batch which cannot be
+/// published, or which the brokers reject, is reported to the error handler
+/// and dropped.
+///
+/// Only plaintext connections to the brokers are supported.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct KafkaAppender {
+    #[derivative(Debug = "ignore")]
+    sender: Mutex<Option<background::Sender<KeyValue>>>,
+    tracker: Arc<FlushTracker>,
+    worker: Option<Task>,
+    // Whether the last batch reached the brokers, unless publishing is faked.
+    connected: Option<Arc<AtomicBool>>,
+    key: Key,
+    encoder: Box<dyn Encode>,
+}
+
+impl KafkaAppender {
+    /// Creates a new `KafkaAppender` builder.
+    pub fn builder() -> KafkaAppenderBuilder {
+        KafkaAppenderBuilder {
+            key: Key::None,
+            encoder: None,
+            acks: Acks::One,
+            ack_timeout: Duration::from_secs(30),
+            batch_interval: Duration::from_millis(100),
+            max_batch: 512,
+        }
+    }
+
+    fn key(&self, record: &Record) -> Vec<u8> {
+        match self.key {
+            Key::None => vec![],
+            Key::Target => record.target().as_bytes().to_vec(),
+            Key::Mdc(ref key) => log_mdc::get(key, |value| value.unwrap_or("").as_bytes().to_vec()),
+        }
+    }
+}
+
+impl Append for KafkaAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let mut value = SimpleWriter(vec![]);
+        self.encoder.encode(&mut value, record)?;
+        let key = self.key(record);
+
+        let sender = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        let token = self.tracker.track();
+        let sent = match *sender {
+            Some(ref sender) => sender.send(Message::Item(token, (key, value.0))),
+            None => false,
+        };
+        if !sent {
+            self.tracker.complete(token);
+            anyhow::bail!("Kafka publishing has stopped");
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {
+        self.flush_to(FlushToken::issue());
+    }
+
+    fn flush_to(&self, token: FlushToken) {
+        if let Some(ref sender) = *self.sender.lock().unwrap_or_else(|e| e.into_inner()) {
+            sender.send(Message::Flush);
+        }
+        if let Some(ref worker) = self.worker {
+            worker.wait(|| self.tracker.wait(token));
+        }
+    }
+
+    fn health(&self) -> AppendHealth {
+        AppendHealth {
+            connected: self
+                .connected
+                .as_ref()
+                .map(|connected| connected.load(Ordering::Relaxed)),
+            queue_depth: Some(self.tracker.pending()),
+        }
+    }
+}
+
+impl Drop for KafkaAppender {
+    fn drop(&mut self) {
+        // Publishes what is still queued before the worker exits.
+        self.sender
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(worker) = self.worker.take() {
+            worker.join();
+        }
+    }
+}
+
+/// A builder for `KafkaAppender`s.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct KafkaAppenderBuilder {
+    key: Key,
+    encoder: Option<Box<dyn Encode>>,
+    acks: Acks,
+    ack_timeout: Duration,
+    batch_interval: Duration,
+    max_batch: usize,
+}
+
+impl KafkaAppenderBuilder {
+    /// Sets the key records are published with.
+    ///
+    /// Defaults to `Key::None`.
+    pub fn key(mut self, key: Key) -> KafkaAppenderBuilder {
+        self.key = key;
+        self
+    }
+
+    /// Sets the output encoder for the `KafkaAppender`.
+    pub fn encoder(mut self, encoder: Box<dyn Encode>) -> KafkaAppenderBuilder {
+        self.encoder = Some(encoder);
+        self
+    }
+
+    /// Sets the acknowledgement the brokers give for published records.
+    ///
+    /// Defaults to `Acks::One`.
+    pub fn acks(mut self, acks: Acks) -> KafkaAppenderBuilder {
+        self.acks = acks;
+        self
+    }
+
+    /// Sets how long the brokers may take to acknowledge a batch.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn ack_timeout(mut self, ack_timeout: Duration) -> KafkaAppenderBuilder {
+        self.ack_timeout = ack_timeout;
+        self
+    }
+
+    /// Sets the longest a record waits to be published.
+    ///
+    /// Defaults to 100 milliseconds.
+    pub fn batch_interval(mut self, batch_interval: Duration) -> KafkaAppenderBuilder {
+        self.batch_interval = batch_interval;
+        self
+    }
+
+    /// Sets the number of records which are published as soon as they are
+    /// queued, without waiting for the batch interval.
+    ///
+    /// Defaults to 512.
+    pub fn max_batch(mut self, max_batch: usize) -> KafkaAppenderBuilder {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Consumes the `KafkaAppenderBuilder`, producing a `KafkaAppender` which
+    /// publishes to `topic` through the brokers at `brokers`, such as
+    /// `localhost:9092`.
+    pub fn build<S>(self, brokers: &[S], topic: &str) -> anyhow::Result<KafkaAppender>
+    where
+        S: AsRef<str>,
+    {
+        if brokers.is_empty() {
+            anyhow::bail!("no Kafka brokers configured");
+        }
+        let connected = Arc::new(AtomicBool::new(false));
+        let mut publisher = Publisher {
+            brokers: brokers.iter().map(|b| b.as_ref().to_owned()).collect(),
+            topic: topic.to_owned(),
+            acks: self.acks,
+            ack_timeout: self.ack_timeout,
+            producer: None,
+            connected: connected.clone(),
+        };
+        let mut appender = self.start(Box::new(move |batch| publisher.publish(batch)))?;
+        appender.connected = Some(connected);
+        Ok(appender)
+    }
+
+    fn start(self, mut publish: Publish) -> anyhow::Result<KafkaAppender> {
+        let tracker = Arc::new(FlushTracker::new());
+        let (sender, worker) = background::batches(
+            "log4rs kafka",
+            tracker.clone(),
+            self.batch_interval,
+            self.max_batch,
+            move |batch| {
+                if let Err(e) = publish(batch) {
+                    crate::handle_error(&e.context("error publishing records to Kafka"));
+                }
+            },
+        )?;
+
+        Ok(KafkaAppender {
+            sender: Mutex::new(Some(sender)),
+            tracker,
+            worker: Some(worker),
+            connected: None,
+            key: self.key,
+            encoder: self
+                .encoder
+                .unwrap_or_else(|| Box::<PatternEncoder>::default()),
+        })
+    }
+}
+
+/// Publishes batches of records to a topic, connecting to the brokers as
+/// needed.
+struct Publisher {
+    brokers: Vec<String>,
+    topic: String,
+    acks: Acks,
+    ack_timeout: Duration,
+    producer: Option<Producer>,
+    connected: Arc<AtomicBool>,
+}
+
+impl Publisher {
+    fn publish(&mut self, batch: &[KeyValue]) -> anyhow::Result<()> {
+        let producer = match self.producer {
+            Some(ref mut producer) => producer,
+            None => self.producer.get_or_insert(
+                Producer::from_hosts(self.brokers.clone())
+                    .with_required_acks(self.acks.into())
+                    .with_ack_timeout(self.ack_timeout)
+                    .create()?,
+            ),
+        };
+
+        let topic = &self.topic;
+        let records = batch
+            .iter()
+            .map(|(key, value)| KafkaRecord::from_key_value(topic, &key[..], &value[..]))
+            .collect::<Vec<_>>();
+        let confirms = match producer.send_all(&records) {
+            Ok(confirms) => confirms,
+            Err(e) => {
+                // the brokers may have moved; reconnect for the next batch
+                self.producer = None;
+                self.connected.store(false, Ordering::Relaxed);
+                return Err(e.into());
+            }
+        };
+        self.connected.store(true, Ordering::Relaxed);
+
+        let errors = confirms
+            .iter()
+            .flat_map(|confirm| &confirm.partition_confirms)
+            .filter_map(|confirm| {
+                confirm
+                    .offset
+                    .err()
+                    .map(|code| format!("partition {}: {:?}", confirm.partition, code))
+            })
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "brokers rejected records for topic `{}` ({})",
+                self.topic,
+                errors.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A deserializer for the `KafkaAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: kafka
+///
+/// # The brokers the producer connects to, over plaintext. Required.
+/// brokers:
+///   - kafka-1:9092
+///   - kafka-2:9092
+///
+/// # The topic records are published to. Required.
+/// topic: logs
+///
+/// # The key records are published with: "none", "target", or the value of an
+/// # MDC key given as `mdc: <key>`. Defaults to "none".
+/// key:
+///   mdc: request_id
+///
+/// # The acknowledgement the brokers give for published records: "none",
+/// # "one" or "all". Defaults to "one".
+/// acks: all
+///
+/// # How long the brokers may take to acknowledge a batch. Defaults to 30
+/// # seconds.
+/// ack_timeout: 30 seconds
+///
+/// # The longest a record waits to be published. Defaults to 100
+/// # milliseconds.
+/// batch_interval: 100 milliseconds
+///
+/// # The number of queued records which are published without waiting for
+/// # the batch interval. Defaults to 512.
+/// max_batch: 512
+///
+/// # The encoder to use to format output. Defaults to `kind: pattern`.
+/// encoder:
+///   kind: json
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct KafkaAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for KafkaAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = KafkaAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: KafkaAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut builder = KafkaAppender::builder();
+        if let Some(key) = config.key {
+            builder = builder.key(key);
+        }
+        if let Some(encoder) = config.encoder {
+            builder = builder.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
+        }
+        if let Some(acks) = config.acks {
+            builder = builder.acks(acks);
+        }
+        if let Some(ack_timeout) = config.ack_timeout {
+            builder = builder.ack_timeout(ack_timeout);
+        }
+        if let Some(batch_interval) = config.batch_interval {
+            builder = builder.batch_interval(batch_interval);
+        }
+        if let Some(max_batch) = config.max_batch {
+            builder = builder.max_batch(max_batch);
+        }
+        Ok(Box::new(builder.build(&config.brokers, &config.topic)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn publish() {
+        let published = Arc::new(Mutex::new(vec![]));
+        let publish = {
+            let published = published.clone();
+            Box::new(move |batch: &[(Vec<u8>, Vec<u8>)]| {
+                published.lock().unwrap().push(batch.to_vec());
+                Ok(())
+            })
+        };
+        let appender = KafkaAppender::builder()
+            .key(Key::Mdc("request_id".to_owned()))
+            .encoder(Box::new(PatternEncoder::new("{t} {m}")))
+            .batch_interval(Duration::from_secs(60))
+            .start(publish)
+            .unwrap();
+
+        let append = |message| {
+            appender
+                .append(
+                    &Record::builder()
+                        .target("checkout")
+                        .args(format_args!("{}", message))
+                        .build(),
+                )
+                .unwrap()
+        };
+        log_mdc::insert("request_id", "r-1");
+        append("one");
+        log_mdc::remove("request_id");
+        append("two");
+        assert_eq!(appender.health().queue_depth, Some(2));
+        appender.flush();
+        assert_eq!(appender.health().queue_depth, Some(0));
+
+        let published = published.lock().unwrap();
+        assert_eq!(
+            *published,
+            [vec![
+                (b"r-1".to_vec(), b"checkout one".to_vec()),
+                (vec![], b"checkout two".to_vec()),
+            ]]
+        );
+    }
+
+    #[test]
+    fn no_brokers() {
+        assert!(KafkaAppender::builder().build::<&str>(&[], "logs").is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "config_parsing", feature = "yaml_format"))]
+    fn key_config() {
+        // appender configs are deserialized from the parsed config file
+        let parse = |yaml| {
+            ::serde_yaml::from_str::<serde_value::Value>(yaml)
+                .unwrap()
+                .deserialize_into::<KafkaAppenderConfig>()
+                .unwrap()
+                .key
+        };
+        assert_eq!(
+            parse("{brokers: [b], topic: t, key: {mdc: request_id}}"),
+            Some(Key::Mdc("request_id".to_owned()))
+        );
+        assert_eq!(
+            parse("{brokers: [b], topic: t, key: target}"),
+            Some(Key::Target)
+        );
+    }
+}
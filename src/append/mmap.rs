@@ -0,0 +1,202 @@
+//! The memory mapped backend of the file appenders.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Write},
+    os::unix::{fs::FileExt, io::AsRawFd},
+    ptr, slice,
+};
+
+use super::sync::SyncData;
+
+/// The length of the trailer at the end of a mapped region, which holds
+/// `MAGIC` followed by the number of bytes written as a little endian
+/// `u64`.
+const TRAILER_LEN: u64 = 16;
+
+const MAGIC: [u8; 8] = *b"log4rsMM";
+
+/// Truncates the padding and trailer which a mapping that was never
+/// unmapped left at the end of `file`, returning the number of bytes
+/// written to it.
+///
+/// A file which does not end in a trailer is left as it is, so that
+/// output which really ends in zeroes is kept.
+pub fn trim_padding(file: &File) -> io::Result<u64> {
+    let len = file.metadata()?.len();
+    if len < TRAILER_LEN {
+        return Ok(len);
+    }
+    let mut trailer = [0; TRAILER_LEN as usize];
+    file.read_exact_at(&mut trailer, len - TRAILER_LEN)?;
+    if trailer[..8] != MAGIC {
+        return Ok(len);
+    }
+    let mut written = [0; 8];
+    written.copy_from_slice(&trailer[8..]);
+    let written = u64::from_le_bytes(written);
+    if written > len - TRAILER_LEN {
+        return Ok(len);
+    }
+    file.set_len(written)?;
+    Ok(written)
+}
+
+/// Writes to a log file through a shared memory mapping of a region at its
+/// end, so that writing a record is a copy rather than a system call.
+///
+/// The file is extended a region at a time and truncated to the bytes
+/// written when the writer is dropped. Until then, the mapped region ends
+/// in a trailer recording the bytes written, so that the padding can be
+/// told apart from the data if the process crashes.
+pub struct MmapWriter {
+    file: File,
+    region: u64,
+    map: *mut u8,
+    // The offset in the file at which `map` starts, and its length.
+    map_offset: u64,
+    map_len: usize,
+    // The bytes written to the file.
+    len: u64,
+}
+
+impl fmt::Debug for MmapWriter {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MmapWriter")
+            .field("file", &self.file)
+            .field("region", &self.region)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+// The mapping is only accessed through `&mut self`, or read by `msync`.
+unsafe impl Send for MmapWriter {}
+
+impl MmapWriter {
+    /// Creates a writer appending to `file`, which must be open for
+    /// reading and writing and holds `len` bytes.
+    pub fn new(file: File, len: u64, region: u64) -> MmapWriter {
+        MmapWriter {
+            file,
+            region: region.max(1),
+            map: ptr::null_mut(),
+            map_offset: 0,
+            map_len: 0,
+            len,
+        }
+    }
+
+    /// Returns the number of bytes written to the file, which may be
+    /// shorter than the file itself.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Maps a region of the file which holds at least `need` bytes past
+    /// the end of the data written.
+    fn remap(&mut self, need: u64) -> io::Result<()> {
+        self.unmap()?;
+
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        let offset = self.len / page * page;
+        let need = need + TRAILER_LEN;
+        let map_len = ((self.len - offset + self.region.max(need) + page - 1) / page) * page;
+        if self.file.metadata()?.len() < offset + map_len {
+            self.file.set_len(offset + map_len)?;
+        }
+
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                map_len as libc::size_t,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                self.file.as_raw_fd(),
+                offset as libc::off_t,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        self.map = map as *mut u8;
+        self.map_offset = offset;
+        self.map_len = map_len as usize;
+        self.write_trailer();
+        Ok(())
+    }
+
+    fn write_trailer(&mut self) {
+        let map = unsafe { slice::from_raw_parts_mut(self.map, self.map_len) };
+        let at = self.map_len - TRAILER_LEN as usize;
+        map[at..at + MAGIC.len()].copy_from_slice(&MAGIC);
+        map[at + MAGIC.len()..].copy_from_slice(&self.len.to_le_bytes());
+    }
+
+    fn unmap(&mut self) -> io::Result<()> {
+        if self.map.is_null() {
+            return Ok(());
+        }
+        let ret = unsafe { libc::munmap(self.map as *mut libc::c_void, self.map_len) };
+        self.map = ptr::null_mut();
+        self.map_len = 0;
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+impl Write for MmapWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let end = self.map_offset + self.map_len as u64;
+        if self.map.is_null() || self.len + buf.len() as u64 + TRAILER_LEN > end {
+            self.remap(buf.len() as u64)?;
+        }
+        let start = (self.len - self.map_offset) as usize;
+        let map = unsafe { slice::from_raw_parts_mut(self.map, self.map_len) };
+        map[start..start + buf.len()].copy_from_slice(buf);
+        self.len += buf.len() as u64;
+        self.write_trailer();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SyncData for MmapWriter {
+    fn file(&self) -> &File {
+        &self.file
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        if !self.map.is_null()
+            && unsafe { libc::msync(self.map as *mut libc::c_void, self.map_len, libc::MS_SYNC) }
+                != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        self.file.sync_data()
+    }
+}
+
+impl Drop for MmapWriter {
+    fn drop(&mut self) {
+        let result = self.unmap().and_then(|()| {
+            // The file may have been truncated by another program since.
+            if self.file.metadata()?.len() > self.len {
+                self.file.set_len(self.len)?;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            crate::handle_error(
+                &anyhow::Error::new(e).context("error truncating memory mapped log file"),
+            );
+        }
+    }
+}
@@ -7,23 +7,76 @@ use serde::{de, Deserialize, Deserializer};
 use serde_value::Value;
 #[cfg(feature = "config_parsing")]
 use std::collections::BTreeMap;
-use std::fmt;
+use std::{fmt, io, time::Duration};
 
 #[cfg(feature = "config_parsing")]
-use crate::config::Deserializable;
+use crate::config::{Deserializable, Sanitize};
 #[cfg(feature = "config_parsing")]
 use crate::filter::FilterConfig;
+#[cfg(feature = "config_parsing")]
+use crate::processor::ProcessorConfig;
+
+use self::flush::FlushToken;
 
+#[cfg(feature = "alert_appender")]
+pub mod alert;
+#[cfg(feature = "test_support")]
+pub mod capturing;
 #[cfg(feature = "console_appender")]
 pub mod console;
+#[cfg(feature = "failover_appender")]
+pub mod failover;
 #[cfg(feature = "file_appender")]
 pub mod file;
+pub mod flush;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "ring_buffer_appender")]
+pub mod ring_buffer;
 #[cfg(feature = "rolling_file_appender")]
 pub mod rolling_file;
+#[cfg(feature = "routing_appender")]
+pub mod routing;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "tee_appender")]
+pub mod tee;
+#[cfg(all(unix, feature = "unix_socket_appender"))]
+pub mod unix_socket;
+
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+mod atomic;
+#[cfg(any(feature = "console_appender", feature = "file_appender"))]
+mod count;
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+mod dir;
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+mod encode_buf;
+#[cfg(all(
+    unix,
+    any(feature = "file_appender", feature = "rolling_file_appender")
+))]
+mod mmap;
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+mod output;
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+mod perm;
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+mod sync;
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+mod tail;
+#[cfg(all(
+    target_os = "linux",
+    feature = "io_uring",
+    any(feature = "file_appender", feature = "rolling_file_appender")
+))]
+mod uring;
 
 #[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
 mod env_util {
-    use std::borrow::Cow;
+    use std::{borrow::Cow, process};
 
     const ENV_PREFIX: &str = "$ENV{";
     const ENV_PREFIX_LEN: usize = ENV_PREFIX.len();
@@ -81,6 +134,22 @@ mod env_util {
         }
         outpath
     }
+
+    /// Expands the environment variables in a path or pattern, and the
+    /// `{hostname}` and `{pid}` placeholders, so that several instances can
+    /// share a log directory without writing to the same files.
+    pub fn expand_path<'str, Str>(path: Str) -> Cow<'str, str>
+    where
+        Str: Into<Cow<'str, str>>,
+    {
+        let path = expand_env_vars(path);
+        if !path.contains("{hostname}") && !path.contains("{pid}") {
+            return path;
+        }
+        path.replace("{hostname}", crate::encode::hostname())
+            .replace("{pid}", &process::id().to_string())
+            .into()
+    }
 }
 
 /// A trait implemented by log4rs appenders.
@@ -93,6 +162,229 @@ pub trait Append: fmt::Debug + Send + Sync + 'static {
 
     /// Flushes all in-flight records.
     fn flush(&self);
+
+    /// Flushes the records appended before `token` was issued, returning once
+    /// they are durable.
+    ///
+    /// This is what log4rs calls when flushing, including on
+    /// `Handle::shutdown`. Appenders which buffer records, for example to
+    /// write them on a background thread, should implement it with a
+    /// `flush::FlushTracker`. Defaults to calling `flush`.
+    fn flush_to(&self, _token: FlushToken) {
+        self.flush()
+    }
+
+    /// Reads back the last portion of the appender's output, for example to
+    /// include it in a support bundle.
+    ///
+    /// Returns `None` if the appender doesn't support reading back its output.
+    fn tail(&self, _tail: Tail) -> Option<io::Result<Vec<u8>>> {
+        None
+    }
+
+    /// Rotates the appender's output immediately, rather than waiting for the
+    /// next record to trigger it.
+    ///
+    /// Returns `None` if the appender doesn't rotate its output.
+    fn roll_now(&self) -> Option<anyhow::Result<()>> {
+        None
+    }
+
+    /// Writes out the records the appender holds back in memory, for example
+    /// to another appender.
+    ///
+    /// Returns `None` if the appender doesn't hold back records.
+    fn dump(&self) -> Option<anyhow::Result<()>> {
+        None
+    }
+
+    /// Returns the counters kept by the appender itself, which are reported
+    /// by `Handle::stats`.
+    ///
+    /// Counters the appender doesn't keep are `None`.
+    fn stats(&self) -> AppendStats {
+        AppendStats::default()
+    }
+
+    /// Returns the state of the appender's connection and queue, which is
+    /// reported by `Handle::health`.
+    ///
+    /// Parts of the state the appender doesn't have are `None`.
+    fn health(&self) -> AppendHealth {
+        AppendHealth::default()
+    }
+}
+
+/// Counters kept by an appender, returned by `Append::stats`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub struct AppendStats {
+    /// The number of bytes written.
+    pub bytes_written: Option<u64>,
+    /// The number of times the output was rotated.
+    pub rotations: Option<u64>,
+    /// The number of times the output was synced to disk.
+    pub syncs: Option<u64>,
+    /// The total time spent syncing the output to disk.
+    pub sync_time: Option<Duration>,
+    /// The number of records the appender dropped itself, for example
+    /// because its buffer was full.
+    pub dropped: Option<u64>,
+}
+
+/// The state of an appender's connection and queue, returned by
+/// `Append::health`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub struct AppendHealth {
+    /// Whether the appender is connected to the service it sends records to.
+    pub connected: Option<bool>,
+    /// The number of records queued to be written in the background.
+    pub queue_depth: Option<usize>,
+}
+
+/// How durably a file appender writes records.
+///
+/// Records are always handed to the operating system once written, so they
+/// survive the process crashing. Syncing them to disk as well makes them
+/// survive the machine crashing or losing power, at a cost in throughput
+/// which is reported by `Handle::stats`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Durability {
+    /// Records are not synced to disk, other than by the operating system.
+    #[default]
+    None,
+    /// The file is synced only when the appender is flushed, for example by
+    /// its flush interval.
+    #[cfg_attr(feature = "config_parsing", serde(rename = "on_flush"))]
+    OnFlush,
+    /// The file is synced when a record is written at least the appender's
+    /// sync interval, a second by default, after the previous sync, and when
+    /// the appender is flushed.
+    Batched,
+    /// The file is synced after every record, which returns once the record
+    /// is on disk.
+    Strict,
+}
+
+/// How a file appender writes records to its log file.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Backend {
+    /// Records are written with a write call each.
+    #[default]
+    Write,
+    /// Records are copied into a memory mapping of a region at the end of
+    /// the file, which the operating system writes out in the background, so
+    /// that logging a record makes no system call until the region fills up.
+    ///
+    /// The file is extended a region at a time, so it ends in zeroes and a
+    /// 16 byte trailer recording the length written until the appender is
+    /// dropped or rolls it and truncates it. If the process crashes, they
+    /// are left until the appender next opens the file and removes them;
+    /// output which itself ends in zeroes is kept. Only supported on
+    /// Unix, and not with atomic writes, reopening the file when it is
+    /// rotated, or by rolling file appenders shared by several processes.
+    ///
+    /// Another program truncating the mapped file, for example logrotate's
+    /// `copytruncate` or a shell's `> app.log`, makes the next record written
+    /// raise `SIGBUS`, killing the process. Files which are rotated that way
+    /// must use another backend.
+    Mmap,
+    /// Records are handed to a background thread, which submits them to an
+    /// io_uring instance and waits for the writes to complete, so that the
+    /// logging thread does not wait for the file system.
+    ///
+    /// Records are written out in order, soon after they are logged. Flushing
+    /// the appender waits for the pending writes. At most 1024 flushed
+    /// batches of records are queued for the thread; once the queue is full,
+    /// logging blocks until it has room. The number queued is reported by
+    /// `Append::health`. Only supported on Linux 5.6
+    /// or later, and not with atomic writes or by rolling file appenders
+    /// shared by several processes. Requires the `io_uring` feature.
+    #[cfg(feature = "io_uring")]
+    #[cfg_attr(feature = "config_parsing", serde(rename = "io_uring"))]
+    IoUring,
+}
+
+/// How a file appender writing each record with a single write call handles
+/// a record longer than its atomic write limit.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Oversized {
+    /// The record is written in pieces no longer than the limit, each with a
+    /// write call of its own. Records written by other appenders or processes
+    /// may come between the pieces, but never within one.
+    #[default]
+    Split,
+    /// The record is cut short at the limit, keeping its final newline.
+    Truncate,
+}
+
+/// How an appender handles a record its encoder fails to encode, for example
+/// because a field cannot be serialized.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum EncodeFailure {
+    /// The record is not written, and the error is reported to the error
+    /// handler along with the record's message.
+    #[default]
+    Report,
+    /// A plain line with the record's level, target and message, followed by
+    /// the error, is written in place of the record.
+    Plain,
+}
+
+impl EncodeFailure {
+    /// Handles the failure of an encoder to encode `record`.
+    ///
+    /// Anything the encoder wrote before failing should be discarded, as the
+    /// plain line is written to `w` in its place.
+    #[cfg(any(
+        feature = "console_appender",
+        feature = "file_appender",
+        feature = "rolling_file_appender"
+    ))]
+    fn recover(
+        self,
+        error: anyhow::Error,
+        record: &Record,
+        w: &mut dyn io::Write,
+    ) -> anyhow::Result<()> {
+        match self {
+            EncodeFailure::Report => {
+                Err(error.context(format!("error encoding record `{}`", record.args())))
+            }
+            EncodeFailure::Plain => {
+                writeln!(
+                    w,
+                    "{} {} - {} (error encoding record: {:#})",
+                    record.level(),
+                    record.target(),
+                    record.args(),
+                    error
+                )?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The portion of an appender's output returned by `Append::tail`.
+///
+/// File appenders return at most the last 16 MiB of the file, so a tail of
+/// more bytes, or of lines longer in total, is cut short at its start.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Tail {
+    /// The last `n` bytes.
+    Bytes(u64),
+    /// The last `n` lines.
+    Lines(usize),
 }
 
 #[cfg(feature = "config_parsing")]
@@ -114,17 +406,47 @@ impl<T: Log + fmt::Debug + 'static> Append for T {
 }
 
 /// Configuration for an appender.
+///
+/// Further options may be added in minor releases, so it is built with `new`
+/// rather than a struct literal.
 #[cfg(feature = "config_parsing")]
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
 pub struct AppenderConfig {
     /// The appender kind.
     pub kind: String,
     /// The filters attached to the appender.
     pub filters: Vec<FilterConfig>,
+    /// The processors attached to the appender.
+    pub processors: Vec<ProcessorConfig>,
+    /// How control characters in messages are handled, if overridden.
+    pub sanitize: Option<Sanitize>,
+    /// The interval at which the appender is flushed on a schedule, if set.
+    pub flush_interval: Option<Duration>,
+    /// The largest backwards step of the clock hidden from the appender's
+    /// timestamps, if set.
+    pub monotonic_time: Option<Duration>,
     /// The appender configuration.
     pub config: Value,
 }
 
+#[cfg(feature = "config_parsing")]
+impl AppenderConfig {
+    /// Creates a new `AppenderConfig` of the specified kind, with no filters
+    /// or processors and the default options.
+    pub fn new(kind: String, config: Value) -> AppenderConfig {
+        AppenderConfig {
+            kind,
+            filters: vec![],
+            processors: vec![],
+            sanitize: None,
+            flush_interval: None,
+            monotonic_time: None,
+            config,
+        }
+    }
+}
+
 #[cfg(feature = "config_parsing")]
 impl<'de> Deserialize<'de> for AppenderConfig {
     fn deserialize<D>(d: D) -> Result<AppenderConfig, D::Error>
@@ -143,9 +465,37 @@ impl<'de> Deserialize<'de> for AppenderConfig {
             None => vec![],
         };
 
+        let processors = match map.remove(&Value::String("processors".to_owned())) {
+            Some(processors) => processors.deserialize_into().map_err(|e| e.into_error())?,
+            None => vec![],
+        };
+
+        let sanitize = match map.remove(&Value::String("sanitize".to_owned())) {
+            Some(sanitize) => Some(sanitize.deserialize_into().map_err(|e| e.into_error())?),
+            None => None,
+        };
+
+        let flush_interval = match map.remove(&Value::String("flush_interval".to_owned())) {
+            Some(flush_interval) => {
+                crate::config::de_duration(flush_interval).map_err(|e| e.into_error())?
+            }
+            None => None,
+        };
+
+        let monotonic_time = match map.remove(&Value::String("monotonic_time".to_owned())) {
+            Some(monotonic_time) => {
+                crate::config::de_duration(monotonic_time).map_err(|e| e.into_error())?
+            }
+            None => None,
+        };
+
         Ok(AppenderConfig {
             kind,
             filters,
+            processors,
+            sanitize,
+            flush_interval,
+            monotonic_time,
             config: Value::Map(map),
         })
     }
@@ -250,4 +600,19 @@ mod test {
             assert_eq!(res, expected)
         }
     }
+
+    #[test]
+    #[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+    fn expand_path() {
+        set_var("LOG4RS_TEST_DIR", "logs");
+        assert_eq!(
+            super::env_util::expand_path("$ENV{LOG4RS_TEST_DIR}/{hostname}-{pid}.{}.log"),
+            format!(
+                "logs/{}-{}.{{}}.log",
+                crate::encode::hostname(),
+                std::process::id()
+            )
+        );
+        assert_eq!(super::env_util::expand_path("{host}.log"), "{host}.log");
+    }
 }
@@ -0,0 +1,627 @@
+//! The OpenTelemetry appender.
+//!
+//! Requires the `otel` feature.
+
+use log::{
+    kv::{self, VisitSource},
+    Level, Record,
+};
+use serde_json::{json, Value as Json};
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::{
+    append::{
+        flush::{FlushToken, FlushTracker},
+        Append, AppendHealth,
+    },
+    background::{self, Message, Task},
+    encode,
+};
+
+/// The OpenTelemetry appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtelAppenderConfig {
+    endpoint: String,
+    #[serde(default)]
+    resource: BTreeMap<String, String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    batch_interval: Option<Duration>,
+    max_batch: Option<usize>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    timeout: Option<Duration>,
+    trace_id_key: Option<String>,
+    span_id_key: Option<String>,
+}
+
+/// An appender which exports records to an OpenTelemetry collector as OTLP
+/// log records.
+///
+/// Records are converted as they are appended and exported in batches in the
+/// background, over OTLP/HTTP with JSON encoding. gRPC and HTTPS
+/// endpoints are not supported; export through a local collector to reach
+/// those. Export failures are reported to stderr and the batch is dropped.
+///
+/// The trace and span IDs of a record are taken from the MDC of the thread it
+/// is logged on, and its key-value fields become attributes of the log
+/// record, along with its target, module path, file and line.
+#[derive(Debug)]
+pub struct OtelAppender {
+    sender: Mutex<Option<background::Sender<Json>>>,
+    tracker: Arc<FlushTracker>,
+    worker: Option<Task>,
+    trace_id_key: String,
+    span_id_key: String,
+}
+
+impl OtelAppender {
+    /// Creates a new `OtelAppender` builder.
+    pub fn builder() -> OtelAppenderBuilder {
+        OtelAppenderBuilder {
+            resource: vec![],
+            headers: vec![],
+            batch_interval: Duration::from_secs(1),
+            max_batch: 512,
+            timeout: Duration::from_secs(10),
+            trace_id_key: "trace_id".to_owned(),
+            span_id_key: "span_id".to_owned(),
+        }
+    }
+
+    fn log_record(&self, record: &Record) -> Json {
+        struct Attributes<'a>(&'a mut Vec<Json>);
+
+        impl<'kvs> VisitSource<'kvs> for Attributes<'_> {
+            fn visit_pair(
+                &mut self,
+                key: kv::Key<'kvs>,
+                value: kv::Value<'kvs>,
+            ) -> Result<(), kv::Error> {
+                self.0.push(attribute(key.as_str(), any_value(&value)));
+                Ok(())
+            }
+        }
+
+        let mut attributes = vec![attribute(
+            "log.target",
+            json!({ "stringValue": record.target() }),
+        )];
+        if let Some(module_path) = record.module_path() {
+            attributes.push(attribute(
+                "code.namespace",
+                json!({ "stringValue": module_path }),
+            ));
+        }
+        if let Some(file) = record.file() {
+            attributes.push(attribute("code.filepath", json!({ "stringValue": file })));
+        }
+        if let Some(line) = record.line() {
+            attributes.push(attribute(
+                "code.lineno",
+                json!({ "intValue": line.to_string() }),
+            ));
+        }
+        let _ = record.key_values().visit(&mut Attributes(&mut attributes));
+
+        let observed = SystemTime::now();
+        let mut log_record = json!({
+            "timeUnixNano": unix_nanos(encode::record_time()),
+            "observedTimeUnixNano": unix_nanos(observed),
+            "severityNumber": severity_number(record.level()),
+            "severityText": record.level().as_str(),
+            "body": { "stringValue": record.args().to_string() },
+            "attributes": attributes,
+        });
+        if let Some(trace_id) = mdc_id(&self.trace_id_key, 32) {
+            log_record["traceId"] = Json::String(trace_id);
+        }
+        if let Some(span_id) = mdc_id(&self.span_id_key, 16) {
+            log_record["spanId"] = Json::String(span_id);
+        }
+        log_record
+    }
+}
+
+impl Append for OtelAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let log_record = self.log_record(record);
+
+        let sender = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        let token = self.tracker.track();
+        let sent = match *sender {
+            Some(ref sender) => sender.send(Message::Item(token, log_record)),
+            None => false,
+        };
+        if !sent {
+            self.tracker.complete(token);
+            anyhow::bail!("OpenTelemetry export has stopped");
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {
+        self.flush_to(FlushToken::issue());
+    }
+
+    fn flush_to(&self, token: FlushToken) {
+        if let Some(ref sender) = *self.sender.lock().unwrap_or_else(|e| e.into_inner()) {
+            sender.send(Message::Flush);
+        }
+        if let Some(ref worker) = self.worker {
+            worker.wait(|| self.tracker.wait(token));
+        }
+    }
+
+    fn health(&self) -> AppendHealth {
+        AppendHealth {
+            queue_depth: Some(self.tracker.pending()),
+            ..AppendHealth::default()
+        }
+    }
+}
+
+impl Drop for OtelAppender {
+    fn drop(&mut self) {
+        // Exports what is still queued before the worker exits.
+        self.sender
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(worker) = self.worker.take() {
+            worker.join();
+        }
+    }
+}
+
+/// A builder for `OtelAppender`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct OtelAppenderBuilder {
+    resource: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    batch_interval: Duration,
+    max_batch: usize,
+    timeout: Duration,
+    trace_id_key: String,
+    span_id_key: String,
+}
+
+impl OtelAppenderBuilder {
+    /// Adds an attribute of the resource the records are exported for, such
+    /// as `service.name`.
+    pub fn resource_attribute<K, V>(mut self, key: K, value: V) -> OtelAppenderBuilder
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.resource.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds a header sent with every export request, for example to
+    /// authenticate with the collector.
+    pub fn header<K, V>(mut self, name: K, value: V) -> OtelAppenderBuilder
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the longest a record waits to be exported.
+    ///
+    /// Defaults to 1 second.
+    pub fn batch_interval(mut self, batch_interval: Duration) -> OtelAppenderBuilder {
+        self.batch_interval = batch_interval;
+        self
+    }
+
+    /// Sets the number of records which are exported as soon as they are
+    /// queued, without waiting for the batch interval.
+    ///
+    /// Defaults to 512.
+    pub fn max_batch(mut self, max_batch: usize) -> OtelAppenderBuilder {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Sets the timeout of connecting to the collector and of each export
+    /// request.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> OtelAppenderBuilder {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the MDC key holding the trace ID of a record, as 32 hex digits.
+    ///
+    /// Defaults to `trace_id`.
+    pub fn trace_id_key<K>(mut self, key: K) -> OtelAppenderBuilder
+    where
+        K: Into<String>,
+    {
+        self.trace_id_key = key.into();
+        self
+    }
+
+    /// Sets the MDC key holding the span ID of a record, as 16 hex digits.
+    ///
+    /// Defaults to `span_id`.
+    pub fn span_id_key<K>(mut self, key: K) -> OtelAppenderBuilder
+    where
+        K: Into<String>,
+    {
+        self.span_id_key = key.into();
+        self
+    }
+
+    /// Consumes the `OtelAppenderBuilder`, producing an `OtelAppender` which
+    /// exports to `endpoint`, such as `http://localhost:4318/v1/logs`.
+    ///
+    /// The path defaults to `/v1/logs` if the endpoint has none.
+    pub fn build(self, endpoint: &str) -> anyhow::Result<OtelAppender> {
+        let exporter = Exporter::new(endpoint, self.headers, self.resource, self.timeout)?;
+        let tracker = Arc::new(FlushTracker::new());
+        let (sender, worker) = background::batches(
+            "log4rs otel",
+            tracker.clone(),
+            self.batch_interval,
+            self.max_batch,
+            move |batch| {
+                if let Err(e) = exporter.export(batch) {
+                    crate::handle_error(&e.context("error exporting records to OpenTelemetry"));
+                }
+            },
+        )?;
+
+        Ok(OtelAppender {
+            sender: Mutex::new(Some(sender)),
+            tracker,
+            worker: Some(worker),
+            trace_id_key: self.trace_id_key,
+            span_id_key: self.span_id_key,
+        })
+    }
+}
+
+/// Sends batches of log records to an OTLP/HTTP endpoint.
+#[derive(Debug)]
+struct Exporter {
+    host: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    resource: Json,
+    timeout: Duration,
+}
+
+impl Exporter {
+    fn new(
+        endpoint: &str,
+        headers: Vec<(String, String)>,
+        resource: Vec<(String, String)>,
+        timeout: Duration,
+    ) -> anyhow::Result<Exporter> {
+        let rest = match endpoint.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => anyhow::bail!(
+                "unsupported OpenTelemetry endpoint `{}`: only http:// endpoints are supported",
+                endpoint
+            ),
+        };
+        let (host, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, "/v1/logs"),
+        };
+        if host.is_empty() {
+            anyhow::bail!("OpenTelemetry endpoint `{}` has no host", endpoint);
+        }
+        let host = if host.contains(':') {
+            host.to_owned()
+        } else {
+            format!("{}:80", host)
+        };
+
+        let resource = resource
+            .iter()
+            .map(|(key, value)| attribute(key, json!({ "stringValue": value })))
+            .collect::<Vec<_>>();
+
+        Ok(Exporter {
+            host,
+            path: path.to_owned(),
+            headers,
+            resource: json!({ "attributes": resource }),
+            timeout,
+        })
+    }
+
+    fn export(&self, log_records: &[Json]) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(&json!({
+            "resourceLogs": [{
+                "resource": self.resource,
+                "scopeLogs": [{
+                    "scope": { "name": "log4rs", "version": env!("CARGO_PKG_VERSION") },
+                    "logRecords": log_records,
+                }],
+            }],
+        }))?;
+
+        let addr = self
+            .host
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("`{}` did not resolve to an address", self.host))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.path,
+            self.host,
+            body.len()
+        );
+        for (name, value) in &self.headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(&body)?;
+        stream.flush()?;
+
+        let mut response = vec![];
+        match stream.read_to_end(&mut response) {
+            Ok(_) => {}
+            // The status line is all that is needed if the collector keeps
+            // the connection open regardless.
+            Err(ref e)
+                if !response.is_empty()
+                    && matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let status_line = response.split(|&b| b == b'\r').next().unwrap_or_default();
+        let status_line = String::from_utf8_lossy(status_line);
+        match status_line.split(' ').nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => anyhow::bail!("OpenTelemetry collector responded `{}`", status_line),
+        }
+    }
+}
+
+fn attribute(key: &str, value: Json) -> Json {
+    json!({ "key": key, "value": value })
+}
+
+fn any_value(value: &kv::Value) -> Json {
+    if let Some(b) = value.to_bool() {
+        json!({ "boolValue": b })
+    } else if let Some(i) = value.to_i64() {
+        json!({ "intValue": i.to_string() })
+    } else if let Some(f) = value.to_f64() {
+        json!({ "doubleValue": f })
+    } else {
+        json!({ "stringValue": value.to_string() })
+    }
+}
+
+fn severity_number(level: Level) -> u8 {
+    match level {
+        Level::Trace => 1,
+        Level::Debug => 5,
+        Level::Info => 9,
+        Level::Warn => 13,
+        Level::Error => 17,
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+        .to_string()
+}
+
+/// Returns the MDC value of `key` if it is an ID of `len` hex digits.
+fn mdc_id(key: &str, len: usize) -> Option<String> {
+    log_mdc::get(key, |value| {
+        value
+            .filter(|id| id.len() == len && id.bytes().all(|b| b.is_ascii_hexdigit()))
+            .map(str::to_ascii_lowercase)
+    })
+}
+
+/// A deserializer for the `OtelAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: otel
+///
+/// # The OTLP/HTTP endpoint records are exported to, with JSON encoding. Only
+/// # http:// endpoints are supported. The path defaults to /v1/logs. Required.
+/// endpoint: http://localhost:4318/v1/logs
+///
+/// # Attributes of the resource the records are exported for. Defaults to
+/// # none.
+/// resource:
+///   service.name: checkout
+///   deployment.environment: production
+///
+/// # Headers sent with every export request. Defaults to none.
+/// headers:
+///   authorization: Bearer secret
+///
+/// # The longest a record waits to be exported. Defaults to 1 second.
+/// batch_interval: 1 second
+///
+/// # The number of queued records which are exported without waiting for the
+/// # batch interval. Defaults to 512.
+/// max_batch: 512
+///
+/// # The timeout of connecting to the collector and of each export request.
+/// # Defaults to 10 seconds.
+/// timeout: 10 seconds
+///
+/// # The MDC keys holding the trace and span IDs of a record, as 32 and 16 hex
+/// # digits. Default to trace_id and span_id.
+/// trace_id_key: trace_id
+/// span_id_key: span_id
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct OtelAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for OtelAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = OtelAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: OtelAppenderConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut builder = OtelAppender::builder();
+        for (key, value) in config.resource {
+            builder = builder.resource_attribute(key, value);
+        }
+        for (name, value) in config.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(batch_interval) = config.batch_interval {
+            builder = builder.batch_interval(batch_interval);
+        }
+        if let Some(max_batch) = config.max_batch {
+            builder = builder.max_batch(max_batch);
+        }
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(trace_id_key) = config.trace_id_key {
+            builder = builder.trace_id_key(trace_id_key);
+        }
+        if let Some(span_id_key) = config.span_id_key {
+            builder = builder.span_id_key(span_id_key);
+        }
+        Ok(Box::new(builder.build(&config.endpoint)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    use super::*;
+
+    /// Accepts one export request, returning its head and body.
+    fn collector() -> (String, JoinHandle<(String, Json)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/logs", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buf = [0; 4096];
+            let (head, body_len) = loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).into_owned();
+                if let Some(idx) = text.find("\r\n\r\n") {
+                    let head = text[..idx].to_owned();
+                    let len = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("Content-Length: "))
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap();
+                    break (head, idx + 4 + len);
+                }
+            };
+            while request.len() < body_len {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            let body = &request[head.len() + 4..body_len];
+            (head, serde_json::from_slice(body).unwrap())
+        });
+        (endpoint, handle)
+    }
+
+    #[test]
+    fn export() {
+        let (endpoint, collector) = collector();
+        let appender = OtelAppender::builder()
+            .resource_attribute("service.name", "checkout")
+            .header("authorization", "Bearer secret")
+            .batch_interval(Duration::from_secs(60))
+            .build(&endpoint)
+            .unwrap();
+
+        log_mdc::insert("trace_id", "4BF92F3577B34DA6A3CE929D0E0E4736");
+        log_mdc::insert("span_id", "00f067aa0ba902b7");
+        let kvs = [
+            ("user", kv::Value::from("alice")),
+            ("items", kv::Value::from(3)),
+        ];
+        appender
+            .append(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("checkout")
+                    .args(format_args!("payment retried"))
+                    .key_values(&kvs)
+                    .build(),
+            )
+            .unwrap();
+        log_mdc::remove("trace_id");
+        log_mdc::remove("span_id");
+        appender.flush();
+
+        let (head, body) = collector.join().unwrap();
+        assert!(head.starts_with("POST /v1/logs HTTP/1.1\r\n"));
+        assert!(head.contains("\r\nauthorization: Bearer secret"));
+
+        let resource_logs = &body["resourceLogs"][0];
+        assert_eq!(
+            resource_logs["resource"]["attributes"][0],
+            json!({ "key": "service.name", "value": { "stringValue": "checkout" } })
+        );
+        let log_record = &resource_logs["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(log_record["severityNumber"], 13);
+        assert_eq!(log_record["body"]["stringValue"], "payment retried");
+        assert_eq!(log_record["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(log_record["spanId"], "00f067aa0ba902b7");
+        let attributes = log_record["attributes"].as_array().unwrap();
+        assert!(attributes.contains(&json!({ "key": "user", "value": { "stringValue": "alice" } })));
+        assert!(attributes.contains(&json!({ "key": "items", "value": { "intValue": "3" } })));
+    }
+
+    #[test]
+    fn unsupported_endpoint() {
+        assert!(OtelAppender::builder()
+            .build("https://collector:4318/v1/logs")
+            .is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! The writers behind the file appenders, one for each backend.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+#[cfg(unix)]
+use super::mmap::{self, MmapWriter};
+use super::sync::SyncData;
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+use super::uring::UringWriter;
+use super::Backend;
+
+/// The default length of the regions of the log file the mmap backend
+/// maps at a time.
+pub const DEFAULT_REGION: u64 = 16 * 1024 * 1024;
+
+/// The writer behind a file appender, as chosen by its `Backend`.
+#[derive(Debug)]
+pub enum Output {
+    Write(BufWriter<File>),
+    #[cfg(unix)]
+    Mmap(MmapWriter),
+    #[cfg(all(target_os = "linux", feature = "io_uring"))]
+    IoUring(UringWriter),
+}
+
+/// Returns the number of bytes written to `file`, a log file about to be
+/// written by `backend`.
+///
+/// A file written by the mmap backend of a process which crashed ends in
+/// the zeroes and trailer of the region it had mapped, which are removed
+/// first.
+pub fn file_len(backend: Backend, file: &File) -> io::Result<u64> {
+    match backend {
+        #[cfg(unix)]
+        Backend::Mmap => mmap::trim_padding(file),
+        _ => Ok(file.metadata()?.len()),
+    }
+}
+
+impl Output {
+    /// Creates the writer for `backend` writing to `file`, which holds
+    /// `len` bytes. `region` is the length of the regions mapped by the
+    /// mmap backend.
+    pub fn new(backend: Backend, file: File, len: u64, region: u64) -> io::Result<Output> {
+        let _ = (len, region);
+        match backend {
+            Backend::Write => Ok(Output::Write(BufWriter::with_capacity(1024, file))),
+            #[cfg(unix)]
+            Backend::Mmap => Ok(Output::Mmap(MmapWriter::new(file, len, region))),
+            #[cfg(all(target_os = "linux", feature = "io_uring"))]
+            Backend::IoUring => Ok(Output::IoUring(UringWriter::new(file)?)),
+            #[allow(unreachable_patterns)]
+            backend => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("the {:?} backend is not supported here", backend),
+            )),
+        }
+    }
+
+    /// Returns the number of writes queued in the background, if the
+    /// backend queues them.
+    pub fn queue_depth(&self) -> Option<usize> {
+        match self {
+            #[cfg(all(target_os = "linux", feature = "io_uring"))]
+            Output::IoUring(w) => Some(w.queue_depth()),
+            _ => None,
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Write(w) => w.write(buf),
+            #[cfg(unix)]
+            Output::Mmap(w) => w.write(buf),
+            #[cfg(all(target_os = "linux", feature = "io_uring"))]
+            Output::IoUring(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Write(w) => w.flush(),
+            #[cfg(unix)]
+            Output::Mmap(w) => w.flush(),
+            #[cfg(all(target_os = "linux", feature = "io_uring"))]
+            Output::IoUring(w) => w.flush(),
+        }
+    }
+}
+
+impl SyncData for Output {
+    fn file(&self) -> &File {
+        match self {
+            Output::Write(w) => w.get_ref(),
+            #[cfg(unix)]
+            Output::Mmap(w) => w.file(),
+            #[cfg(all(target_os = "linux", feature = "io_uring"))]
+            Output::IoUring(w) => w.file(),
+        }
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        match self {
+            Output::Write(w) => w.get_ref().sync_data(),
+            #[cfg(unix)]
+            Output::Mmap(w) => w.sync_data(),
+            #[cfg(all(target_os = "linux", feature = "io_uring"))]
+            Output::IoUring(w) => w.sync_data(),
+        }
+    }
+
+    fn complete(&self) -> io::Result<()> {
+        match self {
+            #[cfg(all(target_os = "linux", feature = "io_uring"))]
+            Output::IoUring(w) => w.complete(),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! The permissions and ownership of the files appenders create.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+};
+
+/// The permissions and ownership given to the files an appender creates.
+///
+/// Only applied on Unix.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Permissions {
+    pub mode: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+}
+
+impl Permissions {
+    /// Opens a file, applying the permissions if it is created.
+    pub fn open(&self, options: &mut OpenOptions, path: &Path) -> io::Result<File> {
+        let created = !path.exists();
+        #[cfg(unix)]
+        if let (true, Some(mode)) = (created, self.mode) {
+            use std::os::unix::fs::OpenOptionsExt;
+
+            // never broader than the mode, even before it is applied
+            options.mode(mode);
+        }
+        let file = options.open(path)?;
+        if created {
+            self.apply(&file)?;
+        }
+        Ok(file)
+    }
+
+    /// Applies the permissions to a file, whatever the process umask.
+    pub fn apply(&self, file: &File) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::{fs::PermissionsExt, io::AsRawFd};
+
+            if let Some(mode) = self.mode {
+                file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+            }
+            if self.owner.is_some() || self.group.is_some() {
+                // -1 leaves the owner or group unchanged
+                let uid = match self.owner {
+                    Some(ref owner) => imp::uid(owner)?,
+                    None => libc::uid_t::MAX,
+                };
+                let gid = match self.group {
+                    Some(ref group) => imp::gid(group)?,
+                    None => libc::gid_t::MAX,
+                };
+                if unsafe { libc::fchown(file.as_raw_fd(), uid, gid) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = file;
+        Ok(())
+    }
+
+    /// Applies the permissions to the file at a path, if there is one.
+    #[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+    pub fn apply_path(&self, path: &Path) -> io::Result<()> {
+        if *self == Permissions::default() {
+            return Ok(());
+        }
+        match File::open(path) {
+            Ok(file) => self.apply(&file),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{ffi::CString, io, mem, ptr};
+
+    /// Resolves a user name or numeric ID.
+    pub fn uid(owner: &str) -> io::Result<libc::uid_t> {
+        if let Ok(uid) = owner.parse() {
+            return Ok(uid);
+        }
+        let name = CString::new(owner)?;
+        lookup(owner, "user", |buf, result: &mut *mut libc::passwd| {
+            let mut passwd = unsafe { mem::zeroed::<libc::passwd>() };
+            let rc = unsafe {
+                libc::getpwnam_r(
+                    name.as_ptr(),
+                    &mut passwd,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    result,
+                )
+            };
+            (rc, passwd.pw_uid)
+        })
+    }
+
+    /// Resolves a group name or numeric ID.
+    pub fn gid(group: &str) -> io::Result<libc::gid_t> {
+        if let Ok(gid) = group.parse() {
+            return Ok(gid);
+        }
+        let name = CString::new(group)?;
+        lookup(group, "group", |buf, result: &mut *mut libc::group| {
+            let mut entry = unsafe { mem::zeroed::<libc::group>() };
+            let rc = unsafe {
+                libc::getgrnam_r(
+                    name.as_ptr(),
+                    &mut entry,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    result,
+                )
+            };
+            (rc, entry.gr_gid)
+        })
+    }
+
+    /// Calls a `get*nam_r` function, growing its buffer as needed.
+    fn lookup<T, I, F>(name: &str, kind: &str, mut f: F) -> io::Result<I>
+    where
+        F: FnMut(&mut [libc::c_char], &mut *mut T) -> (libc::c_int, I),
+    {
+        let mut buf = vec![0; 1024];
+        loop {
+            let mut result = ptr::null_mut();
+            let (rc, id) = f(&mut buf, &mut result);
+            match rc {
+                0 if !result.is_null() => return Ok(id),
+                0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("unknown {} `{}`", kind, name),
+                    ))
+                }
+                libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+                rc => return Err(io::Error::from_raw_os_error(rc)),
+            }
+        }
+    }
+}
+
+/// Deserializes a file mode, written in octal as a string such as `"0640"`
+/// or as a number such as `640` whose digits are read as octal.
+#[cfg(feature = "config_parsing")]
+pub fn de_mode<'de, D>(d: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Deserialize, Error};
+
+    let mode = match serde_value::Value::deserialize(d)? {
+        serde_value::Value::String(mode) => mode,
+        value => match value.deserialize_into::<u32>() {
+            Ok(mode) => mode.to_string(),
+            Err(_) => return Err(Error::custom("expected a file mode such as \"0640\"")),
+        },
+    };
+    let digits = mode.trim_start_matches("0o");
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(Some(mode)),
+        _ => Err(Error::custom(format!("invalid file mode `{}`", mode))),
+    }
+}
+
+/// Deserializes a user or group, given as a name or a numeric ID.
+#[cfg(feature = "config_parsing")]
+pub fn de_id<'de, D>(d: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Deserialize, Error};
+
+    match serde_value::Value::deserialize(d)? {
+        serde_value::Value::String(name) => Ok(Some(name)),
+        value => value
+            .deserialize_into::<u32>()
+            .map(|id| Some(id.to_string()))
+            .map_err(|_| Error::custom("expected a name or a numeric ID")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let permissions = Permissions {
+            mode: Some(0o640),
+            ..Permissions::default()
+        };
+        let file = permissions
+            .open(OpenOptions::new().write(true).create(true), &path)
+            .unwrap();
+        assert_eq!(
+            file.metadata().unwrap().permissions().mode() & 0o7777,
+            0o640
+        );
+
+        // existing files are left alone
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .unwrap();
+        permissions
+            .open(OpenOptions::new().write(true).create(true), &path)
+            .unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o600);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn owner() {
+        assert_eq!(imp::uid("0").unwrap(), 0);
+        assert_eq!(imp::uid("root").unwrap(), 0);
+        assert!(imp::gid("log4rs-no-such-group").is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "config_parsing", feature = "yaml_format"))]
+    fn de() {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "de_mode", default)]
+            mode: Option<u32>,
+        }
+
+        let mode = |yaml: &str| {
+            serde_yaml::from_str::<Config>(yaml)
+                .map(|config| config.mode)
+                .ok()
+                .flatten()
+        };
+        assert_eq!(mode("mode: \"0640\""), Some(0o640));
+        assert_eq!(mode("mode: 640"), Some(0o640));
+        assert_eq!(mode("mode: 0640"), Some(0o640));
+        assert_eq!(mode("mode: \"0o600\""), Some(0o600));
+        assert_eq!(mode("mode: 648"), None);
+    }
+}
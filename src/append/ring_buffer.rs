@@ -0,0 +1,303 @@
+//! The ring buffer appender.
+//!
+//! Requires the `ring_buffer_appender` feature.
+
+use log::{LevelFilter, Record};
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::{collections::VecDeque, sync::Mutex, time::SystemTime};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::{
+    append::{flush::FlushToken, Append},
+    history::format_time,
+    record::OwnedRecord,
+};
+
+/// The ring buffer appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RingBufferAppenderConfig {
+    capacity: Option<usize>,
+    dump_level: Option<LevelFilter>,
+    target: Inner,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct Inner {
+    kind: String,
+    config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Inner {
+    fn deserialize<D>(d: D) -> Result<Inner, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(Inner {
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
+
+/// An appender which keeps the most recent records in memory and writes them
+/// to a target appender only when something goes wrong, as a flight recorder.
+///
+/// Once the buffer holds `capacity` records, the oldest is discarded for each
+/// new one. A record at or above the dump level, or a call to
+/// `Handle::dump_buffer`, writes the buffered records to the target, oldest
+/// first, and empties the buffer. Records are passed to the target as they
+/// were logged, with the time they were logged at attached as the
+/// `logged_at` key-value field.
+#[derive(Debug)]
+pub struct RingBufferAppender {
+    records: Mutex<VecDeque<(SystemTime, OwnedRecord)>>,
+    capacity: usize,
+    dump_level: LevelFilter,
+    target: Box<dyn Append>,
+}
+
+impl RingBufferAppender {
+    /// Creates a new `RingBufferAppender` builder.
+    pub fn builder() -> RingBufferAppenderBuilder {
+        RingBufferAppenderBuilder {
+            capacity: 1000,
+            dump_level: LevelFilter::Error,
+        }
+    }
+
+    /// Writes the buffered records to the target appender and empties the
+    /// buffer.
+    ///
+    /// Every record is tried; the first error is returned and any further
+    /// errors are reported to stderr.
+    pub fn dump(&self) -> anyhow::Result<()> {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let mut result = Ok(());
+        for (logged, record) in records.drain(..) {
+            let logged = format_time(logged);
+            let fields = [("logged_at", logged.as_str())];
+            if let Err(e) = record.with_record_fields(&fields, |record| self.target.append(record))
+            {
+                if result.is_ok() {
+                    result = Err(e);
+                } else {
+                    crate::handle_error(&e);
+                }
+            }
+        }
+        self.target.flush();
+        result
+    }
+
+    /// Returns the number of records currently buffered.
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Returns true if no records are buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Append for RingBufferAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        if self.capacity > 0 {
+            let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+            if records.len() >= self.capacity {
+                records.pop_front();
+            }
+            records.push_back((SystemTime::now(), OwnedRecord::new(record)));
+        }
+
+        if record.level() <= self.dump_level {
+            self.dump()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {
+        self.target.flush();
+    }
+
+    fn flush_to(&self, token: FlushToken) {
+        self.target.flush_to(token);
+    }
+
+    fn dump(&self) -> Option<anyhow::Result<()>> {
+        Some(RingBufferAppender::dump(self))
+    }
+}
+
+/// A builder for `RingBufferAppender`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RingBufferAppenderBuilder {
+    capacity: usize,
+    dump_level: LevelFilter,
+}
+
+impl RingBufferAppenderBuilder {
+    /// Sets the maximum number of records kept.
+    ///
+    /// Defaults to 1000.
+    pub fn capacity(mut self, capacity: usize) -> RingBufferAppenderBuilder {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the level at or above which a record dumps the buffer to the
+    /// target, including that record. `LevelFilter::Off` only dumps the
+    /// buffer on request.
+    ///
+    /// Defaults to `LevelFilter::Error`.
+    pub fn dump_level(mut self, dump_level: LevelFilter) -> RingBufferAppenderBuilder {
+        self.dump_level = dump_level;
+        self
+    }
+
+    /// Consumes the `RingBufferAppenderBuilder`, producing a
+    /// `RingBufferAppender` which dumps its records to `target`.
+    pub fn build(self, target: Box<dyn Append>) -> RingBufferAppender {
+        RingBufferAppender {
+            records: Mutex::new(VecDeque::with_capacity(self.capacity.min(1024))),
+            capacity: self.capacity,
+            dump_level: self.dump_level,
+            target,
+        }
+    }
+}
+
+/// A deserializer for the `RingBufferAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: ring_buffer
+///
+/// # The maximum number of records kept in memory. Defaults to 1000.
+/// capacity: 1000
+///
+/// # The level at or above which a record dumps the buffer to the target,
+/// # including that record. "off" only dumps the buffer on request. Defaults
+/// # to "error".
+/// dump_level: error
+///
+/// # The appender the buffered records are dumped to. Required.
+/// target:
+///
+///   # Identifies which appender is to be used. Required.
+///   kind: file
+///
+///   # The remainder of the configuration is passed to the appender's
+///   # deserializer, and will vary based on the kind of appender.
+///   path: log/flight_recorder.log
+///   encoder:
+///     pattern: "{K(logged_at)} {l} {t} - {m}{n}"
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct RingBufferAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for RingBufferAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = RingBufferAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: RingBufferAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let target = deserializers.deserialize(&config.target.kind, config.target.config)?;
+        let mut builder = RingBufferAppender::builder();
+        if let Some(capacity) = config.capacity {
+            builder = builder.capacity(capacity);
+        }
+        if let Some(dump_level) = config.dump_level {
+            builder = builder.dump_level(dump_level);
+        }
+        Ok(Box::new(builder.build(target)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestAppender(Arc<Mutex<Vec<String>>>);
+
+    impl Append for TestAppender {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(record.args().to_string());
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn append(appender: &RingBufferAppender, level: Level, message: &str) {
+        appender
+            .append(
+                &Record::builder()
+                    .level(level)
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn dump_on_error() {
+        let records = Arc::new(Mutex::new(vec![]));
+        let appender = RingBufferAppender::builder()
+            .capacity(2)
+            .build(Box::new(TestAppender(records.clone())));
+
+        append(&appender, Level::Debug, "one");
+        append(&appender, Level::Info, "two");
+        append(&appender, Level::Warn, "three");
+        assert!(records.lock().unwrap().is_empty());
+        assert_eq!(appender.len(), 2);
+
+        append(&appender, Level::Error, "four");
+        assert_eq!(*records.lock().unwrap(), ["three", "four"]);
+        assert!(appender.is_empty());
+    }
+
+    #[test]
+    fn dump_on_request() {
+        let records = Arc::new(Mutex::new(vec![]));
+        let appender = RingBufferAppender::builder()
+            .dump_level(LevelFilter::Off)
+            .build(Box::new(TestAppender(records.clone())));
+
+        append(&appender, Level::Error, "one");
+        assert!(records.lock().unwrap().is_empty());
+
+        Append::dump(&appender).unwrap().unwrap();
+        assert_eq!(*records.lock().unwrap(), ["one"]);
+        assert!(appender.is_empty());
+    }
+}
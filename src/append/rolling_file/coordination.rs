@@ -0,0 +1,223 @@
+//! Coordination of rolls between processes sharing a log file.
+//!
+//! Next to the log file, `<file>.lock` is locked by the process rolling the
+//! file until the roll is done, and `<file>.generation` counts the rolls so
+//! far. A process which finds the counter has moved on since it last looked
+//! knows its handle refers to an archive, and reopens the log file instead of
+//! rolling it again.
+//!
+//! The lock is an advisory lock on the file, which the operating system
+//! releases if the process holding it dies, so the file itself is never
+//! removed.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// How often writing a record checks whether another process rolled the log
+/// file.
+const POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub(super) struct Coordination {
+    lock_path: PathBuf,
+    generation_path: PathBuf,
+    generation: AtomicU64,
+    last_poll: Mutex<Instant>,
+}
+
+impl Coordination {
+    pub(super) fn new(path: &Path) -> io::Result<Coordination> {
+        let coordination = Coordination {
+            lock_path: sibling(path, "lock"),
+            generation_path: sibling(path, "generation"),
+            generation: AtomicU64::new(0),
+            last_poll: Mutex::new(Instant::now()),
+        };
+        coordination
+            .generation
+            .store(coordination.read()?, Ordering::SeqCst);
+        Ok(coordination)
+    }
+
+    /// Returns true if another process has rolled the log file since this
+    /// one last looked.
+    pub(super) fn changed(&self) -> io::Result<bool> {
+        let generation = self.read()?;
+        Ok(self.generation.swap(generation, Ordering::SeqCst) != generation)
+    }
+
+    /// Like `changed`, but only looks at most once every `POLL`, for checking
+    /// before each record is written.
+    pub(super) fn poll(&self) -> io::Result<bool> {
+        {
+            let mut last_poll = self.last_poll.lock().unwrap_or_else(|e| e.into_inner());
+            if last_poll.elapsed() < POLL {
+                return Ok(false);
+            }
+            *last_poll = Instant::now();
+        }
+        self.changed()
+    }
+
+    /// Waits for the lock, returning `None` if another process rolled the
+    /// log file while this one was not looking.
+    pub(super) fn lock(&self) -> io::Result<Option<RollLock>> {
+        let lock = self.acquire()?;
+        if self.changed()? {
+            return Ok(None);
+        }
+        Ok(Some(lock))
+    }
+
+    /// Records a completed roll and releases the lock.
+    pub(super) fn finish(&self, lock: RollLock) -> io::Result<()> {
+        let generation = self.generation.load(Ordering::SeqCst) + 1;
+        let tmp = sibling(&self.generation_path, "tmp");
+        fs::write(&tmp, generation.to_string())?;
+        fs::rename(&tmp, &self.generation_path)?;
+        self.generation.store(generation, Ordering::SeqCst);
+        drop(lock);
+        Ok(())
+    }
+
+    fn acquire(&self) -> io::Result<RollLock> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.lock_path)?;
+        lock_exclusive(&file)?;
+        Ok(RollLock { _file: file })
+    }
+
+    fn read(&self) -> io::Result<u64> {
+        match fs::read_to_string(&self.generation_path) {
+            Ok(generation) => generation.trim().parse().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "invalid roll generation in {}",
+                        self.generation_path.display()
+                    ),
+                )
+            }),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The lock on rolling a shared log file, released when dropped, which
+/// closes the file.
+#[derive(Debug)]
+pub(super) struct RollLock {
+    _file: File,
+}
+
+/// Blocks until this process holds an exclusive advisory lock on `file`.
+#[cfg(unix)]
+fn lock_exclusive(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// Blocks until this process holds an exclusive lock on `file`.
+#[cfg(windows)]
+fn lock_exclusive(file: &File) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::{
+        fileapi::LockFileEx,
+        minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, OVERLAPPED},
+    };
+
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    let locked = unsafe {
+        LockFileEx(
+            file.as_raw_handle() as _,
+            LOCKFILE_EXCLUSIVE_LOCK,
+            0,
+            !0,
+            !0,
+            &mut overlapped,
+        )
+    };
+    if locked == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock_exclusive(_: &File) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "locking shared log files is not supported on this platform",
+    ))
+}
+
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    path.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shared.log");
+        let a = Coordination::new(&path).unwrap();
+        let b = Coordination::new(&path).unwrap();
+
+        let lock = a.lock().unwrap().unwrap();
+        assert!(dir.path().join("shared.log.lock").exists());
+        a.finish(lock).unwrap();
+        assert!(!a.changed().unwrap());
+
+        // b has not seen a's roll, so must not roll again.
+        assert!(b.lock().unwrap().is_none());
+        assert!(!b.changed().unwrap());
+        assert!(b.lock().unwrap().is_some());
+    }
+
+    #[test]
+    fn lock_excludes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shared.log");
+        let a = Coordination::new(&path).unwrap();
+        let b = Coordination::new(&path).unwrap();
+
+        let lock = a.acquire().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let waiter = std::thread::spawn(move || {
+            let lock = b.acquire().unwrap();
+            tx.send(()).unwrap();
+            drop(lock);
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(lock);
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        waiter.join().unwrap();
+    }
+}
@@ -17,12 +17,17 @@
 //! Requires the `rolling_file_appender` feature.
 
 use derivative::Derivative;
-use log::Record;
+use log::{kv, Level, Record};
 use parking_lot::Mutex;
 use std::{
-    fs::{self, File, OpenOptions},
-    io::{self, BufWriter, Write},
+    fs::{self, OpenOptions},
+    io::{self, Write},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 #[cfg(feature = "config_parsing")]
@@ -31,7 +36,17 @@ use serde_value::Value;
 use std::collections::BTreeMap;
 
 use crate::{
-    append::Append,
+    append::{
+        atomic::{self, AtomicWriter},
+        dir::Directories,
+        encode_buf::with_encoded,
+        output::{file_len, Output, DEFAULT_REGION},
+        perm::Permissions,
+        sync::{self, SyncData, Syncer},
+        tail::{open_tail, read_tail},
+        Append, AppendHealth, AppendStats, Backend, Durability, EncodeFailure, Oversized, Tail,
+    },
+    background,
     encode::{self, pattern::PatternEncoder, Encode},
 };
 
@@ -40,7 +55,13 @@ use crate::config::{Deserialize, Deserializers};
 #[cfg(feature = "config_parsing")]
 use crate::encode::EncoderConfig;
 
+#[cfg(feature = "config_parsing")]
+use self::rotation::RotationConfig;
+use self::rotation::{Mode, Rotation};
+
+mod coordination;
 pub mod policy;
+pub mod rotation;
 
 /// Configuration for the rolling file appender.
 #[cfg(feature = "config_parsing")]
@@ -49,7 +70,36 @@ pub mod policy;
 pub struct RollingFileAppenderConfig {
     path: String,
     append: Option<bool>,
+    clean_up: Option<bool>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    check_interval: Option<Duration>,
+    header: Option<String>,
+    footer: Option<String>,
+    durability: Option<Durability>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    sync_interval: Option<Duration>,
+    preallocate: Option<u64>,
+    backend: Option<Backend>,
+    region_size: Option<u64>,
+    startup_report: Option<bool>,
+    multi_process: Option<bool>,
+    atomic_writes: Option<bool>,
+    atomic_write_limit: Option<usize>,
+    oversized_records: Option<Oversized>,
+    mode: Option<Mode>,
+    rotation: Option<RotationConfig>,
+    #[serde(deserialize_with = "crate::append::perm::de_mode", default)]
+    file_mode: Option<u32>,
+    #[serde(deserialize_with = "crate::append::perm::de_id", default)]
+    owner: Option<String>,
+    #[serde(deserialize_with = "crate::append::perm::de_id", default)]
+    group: Option<String>,
+    create_dirs: Option<bool>,
+    max_dir_depth: Option<usize>,
+    fallback_path: Option<String>,
+    lazy: Option<bool>,
     encoder: Option<EncoderConfig>,
+    encode_failure: Option<EncodeFailure>,
     policy: Policy,
 }
 
@@ -82,7 +132,7 @@ impl<'de> serde::Deserialize<'de> for Policy {
 
 #[derive(Debug)]
 struct LogWriter {
-    file: BufWriter<File>,
+    file: Output,
     len: u64,
 }
 
@@ -101,12 +151,25 @@ impl io::Write for LogWriter {
 
 impl encode::Write for LogWriter {}
 
+impl LogWriter {
+    fn write_footer(&mut self, footer: &str) -> io::Result<()> {
+        self.write_all(footer.as_bytes())?;
+        self.flush()
+    }
+}
+
 /// Information about the active log file.
 #[derive(Debug)]
 pub struct LogFile<'a> {
     writer: &'a mut Option<LogWriter>,
     path: &'a Path,
     len: u64,
+    level: Option<Level>,
+    footer: Option<&'a str>,
+    coordination: Option<&'a coordination::Coordination>,
+    lock: Option<coordination::RollLock>,
+    superseded: bool,
+    rotation: &'a Rotation,
 }
 
 #[allow(clippy::len_without_is_empty)]
@@ -137,6 +200,13 @@ impl<'a> LogFile<'a> {
         self.len
     }
 
+    /// Returns the level of the record which was just written, or `None` if
+    /// the policy is being run for another reason, such as by the appender's
+    /// check interval or `roll_now`.
+    pub fn record_level(&self) -> Option<Level> {
+        self.level
+    }
+
     /// Triggers the log file to roll over.
     ///
     /// A policy must call this method when it wishes to roll the log. The
@@ -146,7 +216,55 @@ impl<'a> LogFile<'a> {
     /// If this method is called, the log file must no longer be present on
     /// disk when the policy returns.
     pub fn roll(&mut self) {
-        *self.writer = None;
+        if let Some(mut writer) = self.writer.take() {
+            if let Some(footer) = self.footer {
+                if let Err(e) = writer.write_footer(footer) {
+                    crate::handle_error(&anyhow::Error::new(e).context("error writing log footer"));
+                }
+            }
+        }
+    }
+
+    /// Triggers the log file to roll over unless another process sharing it
+    /// has just done so, returning whether the policy should go on to archive
+    /// the file.
+    ///
+    /// Policies should call this method rather than `roll`. When the appender
+    /// shares the log file with other processes, it waits until no other
+    /// process is rolling the file, and returns `false` if one rolled it since
+    /// this appender last wrote to it, in which case the file on disk is
+    /// already a new one and must be left alone. Otherwise this is the same as
+    /// `roll`.
+    pub fn claim_roll(&mut self) -> anyhow::Result<bool> {
+        if let Some(coordination) = self.coordination {
+            if self.lock.is_none() {
+                match coordination.lock()? {
+                    Some(lock) => self.lock = Some(lock),
+                    None => {
+                        // Our handle refers to an archive now; reopen the file.
+                        *self.writer = None;
+                        self.superseded = true;
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        self.roll();
+        Ok(true)
+    }
+
+    /// Archives the log file with `f` once it has been rolled, following the
+    /// appender's `Rotation`.
+    ///
+    /// `f` is passed the path of the file to archive, which is not the log
+    /// file itself if it is copied and truncated, and must leave no file
+    /// behind at that path. It is called again if it fails because another
+    /// program holds the file open and the rotation allows retries.
+    pub fn archive<F>(&self, f: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&Path) -> anyhow::Result<()>,
+    {
+        self.rotation.archive(self.path, f)
     }
 }
 
@@ -154,38 +272,135 @@ impl<'a> LogFile<'a> {
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct RollingFileAppender {
+    shared: Arc<Shared>,
+    encoder: Box<dyn Encode>,
+    encode_failure: EncodeFailure,
+    bytes_written: AtomicU64,
+    report: StartupReport,
+    // Dropped along with the appender, which stops the timer.
+    #[derivative(Debug = "ignore")]
+    _timer: Option<background::Timer>,
+}
+
+/// What a `RollingFileAppender` found when it was built.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub struct StartupReport {
+    /// The size of the log file in bytes, or `None` if it did not exist.
+    pub file_len: Option<u64>,
+    /// The time since the log file was last modified, or `None` if it did not
+    /// exist or its modification time is unavailable.
+    pub file_age: Option<Duration>,
+    /// The number of archives of the log file, or `None` if the policy cannot
+    /// count them.
+    pub archives: Option<usize>,
+    /// Whether files left behind by interrupted rolls were cleaned up.
+    pub cleaned_up: bool,
+}
+
+impl StartupReport {
+    fn log(&self, appender: &RollingFileAppender) -> anyhow::Result<()> {
+        let mut key_values = vec![];
+        if let Some(file_len) = self.file_len {
+            key_values.push(("file_len", kv::Value::from(file_len)));
+        }
+        if let Some(file_age) = self.file_age {
+            key_values.push(("file_age_secs", kv::Value::from(file_age.as_secs())));
+        }
+        if let Some(archives) = self.archives {
+            key_values.push(("archives", kv::Value::from(archives)));
+        }
+        key_values.push(("cleaned_up", kv::Value::from(self.cleaned_up)));
+
+        appender.append(
+            &Record::builder()
+                .level(Level::Info)
+                .target(module_path!())
+                .module_path_static(Some(module_path!()))
+                .args(format_args!("rolling file appender started"))
+                .key_values(&key_values)
+                .build(),
+        )
+    }
+}
+
+/// The state shared with the timer thread.
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct Shared {
     #[derivative(Debug = "ignore")]
     writer: Mutex<Option<LogWriter>>,
     path: PathBuf,
     append: bool,
-    encoder: Box<dyn Encode>,
+    header: Option<String>,
+    footer: Option<String>,
+    syncer: Syncer,
+    backend: Backend,
+    region_size: u64,
     policy: Box<dyn policy::Policy>,
+    rotations: AtomicU64,
+    coordination: Option<coordination::Coordination>,
+    atomic: Option<AtomicWriter>,
+    rotation: Rotation,
+    permissions: Permissions,
+    directories: Directories,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        if let (Some(writer), Some(footer)) = (self.writer.get_mut(), &self.footer) {
+            if let Err(e) = writer.write_footer(footer) {
+                crate::handle_error(&anyhow::Error::new(e).context("error writing log footer"));
+            }
+        }
+    }
 }
 
 impl Append for RollingFileAppender {
     fn append(&self, record: &Record) -> anyhow::Result<()> {
-        // TODO(eas): Perhaps this is better as a concurrent queue?
-        let mut writer = self.writer.lock();
+        with_encoded(&*self.encoder, self.encode_failure, record, |encoded| {
+            self.write(record.level(), encoded)
+        })
+    }
 
-        let len = {
-            let writer = self.get_writer(&mut writer)?;
-            self.encoder.encode(writer, record)?;
-            writer.flush()?;
-            writer.len
-        };
+    fn flush(&self) {
+        if let Some(ref writer) = *self.shared.writer.lock() {
+            if let Err(e) = self.shared.syncer.flush(&writer.file) {
+                crate::handle_error(&anyhow::Error::new(e).context("error syncing log file"));
+            }
+        }
+    }
 
-        let mut file = LogFile {
-            writer: &mut writer,
-            path: &self.path,
-            len,
-        };
+    fn tail(&self, tail: Tail) -> Option<io::Result<Vec<u8>>> {
+        Some(RollingFileAppender::tail(self, tail))
+    }
 
-        // TODO(eas): Idea: make this optionally return a future, and if so, we initialize a queue for
-        // data that comes in while we are processing the file rotation.
-        self.policy.process(&mut file)
+    fn roll_now(&self) -> Option<anyhow::Result<()>> {
+        Some(RollingFileAppender::roll_now(self))
     }
 
-    fn flush(&self) {}
+    fn stats(&self) -> AppendStats {
+        let (syncs, sync_time) = self.shared.syncer.stats();
+        AppendStats {
+            bytes_written: Some(self.bytes_written.load(Ordering::Relaxed)),
+            rotations: Some(self.shared.rotations.load(Ordering::Relaxed)),
+            syncs,
+            sync_time,
+            ..AppendStats::default()
+        }
+    }
+
+    fn health(&self) -> AppendHealth {
+        AppendHealth {
+            queue_depth: self
+                .shared
+                .writer
+                .lock()
+                .as_ref()
+                .and_then(|writer| writer.file.queue_depth()),
+            ..AppendHealth::default()
+        }
+    }
 }
 
 impl RollingFileAppender {
@@ -193,38 +408,254 @@ impl RollingFileAppender {
     pub fn builder() -> RollingFileAppenderBuilder {
         RollingFileAppenderBuilder {
             append: true,
+            clean_up: false,
+            check_interval: None,
+            header: None,
+            footer: None,
+            durability: Durability::None,
+            sync_interval: sync::DEFAULT_INTERVAL,
+            preallocate: None,
+            backend: Backend::Write,
+            region_size: DEFAULT_REGION,
+            startup_report: false,
+            multi_process: false,
+            atomic_writes: false,
+            atomic_write_limit: atomic::DEFAULT_LIMIT,
+            oversized_records: Oversized::Split,
+            mode: Mode::Rename,
+            rotation: Rotation::default(),
+            permissions: Permissions::default(),
+            directories: Directories::default(),
+            fallback_path: None,
+            lazy: false,
             encoder: None,
+            encode_failure: EncodeFailure::Report,
         }
     }
 
+    /// Returns what the appender found when it was built.
+    pub fn startup_report(&self) -> &StartupReport {
+        &self.report
+    }
+
+    /// Reads the last portion of the active log file.
+    ///
+    /// Writes and rolls are only blocked while the records written so far
+    /// are flushed and the file is opened. The file is then read up to its
+    /// length at that point, so the result always comes from a single file
+    /// and never ends partway through a record. If the log has just been
+    /// rolled and no record has been written since, the result is empty.
+    pub fn tail(&self, tail: Tail) -> io::Result<Vec<u8>> {
+        let opened = {
+            let mut writer = self.shared.writer.lock();
+            let written = match *writer {
+                Some(ref mut writer) => {
+                    writer.flush()?;
+                    writer.file.complete()?;
+                    match writer.file {
+                        // Only the start of the mapped region holds records.
+                        #[cfg(unix)]
+                        Output::Mmap(ref w) => Some(w.len()),
+                        _ => None,
+                    }
+                }
+                None => None,
+            };
+            open_tail(&self.shared.path, written)
+        };
+        match opened {
+            Ok((file, len)) => read_tail(file, len, tail),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Rolls the log file immediately and reopens it, without waiting for the
+    /// next record.
+    ///
+    /// Writes are blocked while the policy rolls the file. Returns an error if
+    /// the policy does not support rolling on demand.
+    pub fn roll_now(&self) -> anyhow::Result<()> {
+        let mut writer = self.shared.writer.lock();
+        let len = match *writer {
+            Some(ref mut writer) => {
+                writer.flush()?;
+                self.shared.len(writer)?
+            }
+            None => 0,
+        };
+
+        self.shared.run_policy(&mut writer, len, None, |file| {
+            self.shared.policy.roll_now(file)
+        })?;
+        self.shared.get_writer(&mut writer)?;
+        Ok(())
+    }
+
+    /// Writes an encoded record to the log file and runs the policy.
+    fn write(&self, level: Level, encoded: &[u8]) -> anyhow::Result<()> {
+        let mut writer = self.shared.writer.lock();
+        if let Some(ref coordination) = self.shared.coordination {
+            if coordination.poll()? {
+                // Another process rolled the file, so reopen it.
+                *writer = None;
+            }
+        }
+
+        let len = {
+            let writer = self.shared.get_writer(&mut writer)?;
+            let start = writer.len;
+            let result = match self.shared.atomic {
+                Some(atomic) => atomic
+                    .write(writer.file.file(), encoded)
+                    .map(|bytes| writer.len += bytes),
+                None => writer.write_all(encoded).and_then(|()| writer.flush()),
+            };
+            self.bytes_written
+                .fetch_add(writer.len - start, Ordering::Relaxed);
+            result?;
+            self.shared
+                .syncer
+                .written(&writer.file, writer.len - start)?;
+            self.shared.len(writer)?
+        };
+
+        // TODO(eas): Idea: make this optionally return a future, and if so, we initialize a queue for
+        // data that comes in while we are processing the file rotation.
+        self.shared
+            .run_policy(&mut writer, len, Some(level), |file| {
+                self.shared.policy.process(file)
+            })
+    }
+}
+
+impl Shared {
     fn get_writer<'a>(&self, writer: &'a mut Option<LogWriter>) -> io::Result<&'a mut LogWriter> {
         if writer.is_none() {
-            let file = OpenOptions::new()
-                .write(true)
-                .append(self.append)
-                .truncate(!self.append)
-                .create(true)
-                .open(&self.path)?;
+            self.directories.create_for(&self.path)?;
+            let file = self.permissions.open(
+                OpenOptions::new()
+                    // mapping the file for writing requires it to be readable
+                    .read(self.backend == Backend::Mmap)
+                    .write(true)
+                    .append(self.append)
+                    .truncate(!self.append)
+                    .create(true),
+                &self.path,
+            )?;
             let len = if self.append {
-                file.metadata()?.len()
+                file_len(self.backend, &file)?
             } else {
                 0
             };
-            *writer = Some(LogWriter {
-                file: BufWriter::with_capacity(1024, file),
+            let mut log_writer = LogWriter {
+                file: Output::new(self.backend, file, len, self.region_size)?,
                 len,
-            });
+            };
+            if let (0, Some(header)) = (len, &self.header) {
+                log_writer.write_all(header.as_bytes())?;
+                log_writer.flush()?;
+            }
+            *writer = Some(log_writer);
+            self.syncer.opened();
         }
 
         // :( unwrap
         Ok(writer.as_mut().unwrap())
     }
+
+    /// Passes the log file to a method of the policy, counting the rotation
+    /// if it rolls the file.
+    fn run_policy<F>(
+        &self,
+        writer: &mut Option<LogWriter>,
+        len: u64,
+        level: Option<Level>,
+        f: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut LogFile) -> anyhow::Result<()>,
+    {
+        let mut file = LogFile {
+            writer,
+            path: &self.path,
+            len,
+            level,
+            footer: self.footer.as_deref(),
+            coordination: self.coordination.as_ref(),
+            lock: None,
+            superseded: false,
+            rotation: &self.rotation,
+        };
+        let mut result = f(&mut file);
+        let (lock, superseded) = (file.lock.take(), file.superseded);
+        if let (Some(coordination), Some(lock)) = (&self.coordination, lock) {
+            if result.is_ok() {
+                result = coordination
+                    .finish(lock)
+                    .map_err(|e| anyhow::Error::new(e).context("error recording log roll"));
+            }
+        }
+        if writer.is_none() && !superseded {
+            self.rotations.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Returns the size of the log file, which other processes may also be
+    /// writing to.
+    fn len(&self, writer: &LogWriter) -> io::Result<u64> {
+        if self.coordination.is_some() {
+            Ok(writer.file.file().metadata()?.len())
+        } else {
+            Ok(writer.len)
+        }
+    }
+
+    /// Lets the policy roll the log file while no records are being written,
+    /// reopening the file if it does.
+    fn check(&self) -> anyhow::Result<()> {
+        let mut writer = self.writer.lock();
+        let len = match *writer {
+            Some(ref mut writer) => {
+                writer.flush()?;
+                self.len(writer)?
+            }
+            // The file was rolled, and is reopened by the next record.
+            None => return Ok(()),
+        };
+
+        self.run_policy(&mut writer, len, None, |file| self.policy.process(file))?;
+        self.get_writer(&mut writer)?;
+        Ok(())
+    }
 }
 
 /// A builder for the `RollingFileAppender`.
 pub struct RollingFileAppenderBuilder {
     append: bool,
+    clean_up: bool,
+    check_interval: Option<Duration>,
+    header: Option<String>,
+    footer: Option<String>,
+    durability: Durability,
+    sync_interval: Duration,
+    preallocate: Option<u64>,
+    backend: Backend,
+    region_size: u64,
+    startup_report: bool,
+    multi_process: bool,
+    atomic_writes: bool,
+    atomic_write_limit: usize,
+    oversized_records: Oversized,
+    mode: Mode,
+    rotation: Rotation,
+    permissions: Permissions,
+    directories: Directories,
+    fallback_path: Option<PathBuf>,
+    lazy: bool,
     encoder: Option<Box<dyn Encode>>,
+    encode_failure: EncodeFailure,
 }
 
 impl RollingFileAppenderBuilder {
@@ -236,6 +667,248 @@ impl RollingFileAppenderBuilder {
         self
     }
 
+    /// Determines if temporary and partial files left behind by interrupted
+    /// rolls, such as half-written compressed archives, are removed when the
+    /// appender is built.
+    ///
+    /// Log files moved aside for rotation in the background but never
+    /// archived are archived instead of being removed.
+    ///
+    /// Defaults to `false`.
+    pub fn clean_up(mut self, clean_up: bool) -> RollingFileAppenderBuilder {
+        self.clean_up = clean_up;
+        self
+    }
+
+    /// Sets how often a background thread passes the log file to the policy
+    /// while no records are written, so that triggers based on time roll the
+    /// file on schedule even when the application is idle.
+    ///
+    /// By default the policy only sees the file after a record is written.
+    pub fn check_interval(mut self, check_interval: Duration) -> RollingFileAppenderBuilder {
+        self.check_interval = Some(check_interval);
+        self
+    }
+
+    /// Sets text written at the start of each new log file, such as the
+    /// column names of a CSV log.
+    ///
+    /// The header is written as is when an empty log file is opened, so it
+    /// should usually end with a newline.
+    pub fn header(mut self, header: &str) -> RollingFileAppenderBuilder {
+        self.header = Some(header.to_owned());
+        self
+    }
+
+    /// Sets text written at the end of each log file, before it is rolled or
+    /// when the appender is dropped.
+    pub fn footer(mut self, footer: &str) -> RollingFileAppenderBuilder {
+        self.footer = Some(footer.to_owned());
+        self
+    }
+
+    /// Sets how durably records are written.
+    ///
+    /// Defaults to `Durability::None`.
+    pub fn durability(mut self, durability: Durability) -> RollingFileAppenderBuilder {
+        self.durability = durability;
+        self
+    }
+
+    /// Sets how long after a sync `Durability::Batched` waits before syncing
+    /// the file again.
+    ///
+    /// Defaults to 1 second.
+    pub fn sync_interval(mut self, sync_interval: Duration) -> RollingFileAppenderBuilder {
+        self.sync_interval = sync_interval;
+        self
+    }
+
+    /// Reserves disk space for each log file ahead of the records written to
+    /// it, `preallocate` bytes at a time, so that writing a record rarely has
+    /// to allocate blocks and running out of disk space is noticed early.
+    ///
+    /// The reserved space does not count towards the file's length, so it
+    /// does not affect size triggers. Only supported on Linux, and ignored
+    /// elsewhere and by file systems which do not support it. By default no
+    /// space is reserved.
+    pub fn preallocate(mut self, preallocate: u64) -> RollingFileAppenderBuilder {
+        self.preallocate = Some(preallocate);
+        self
+    }
+
+    /// Sets how records are written to the log files.
+    ///
+    /// Defaults to `Backend::Write`.
+    pub fn backend(mut self, backend: Backend) -> RollingFileAppenderBuilder {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets the length in bytes of the regions of the log file the
+    /// `Backend::Mmap` backend maps at a time.
+    ///
+    /// Defaults to 16 MiB.
+    pub fn region_size(mut self, region_size: u64) -> RollingFileAppenderBuilder {
+        self.region_size = region_size;
+        self
+    }
+
+    /// Determines if the appender logs its `StartupReport` to the log file
+    /// when it is built, as an `Info` record with the target
+    /// `log4rs::append::rolling_file` and the report as key-values.
+    ///
+    /// Defaults to `false`.
+    pub fn startup_report(mut self, startup_report: bool) -> RollingFileAppenderBuilder {
+        self.startup_report = startup_report;
+        self
+    }
+
+    /// Determines if the log file is shared with other processes, which
+    /// coordinate so that exactly one of them rolls it when it is due.
+    ///
+    /// The process rolling the file holds a lock on `<file>.lock` while it
+    /// does so, and counts the roll in `<file>.generation`; the others reopen
+    /// the file when they see the count change, rather than rolling it again.
+    /// They look at most every 100 milliseconds while writing, so records
+    /// written in that time after another process rolls the file can go to
+    /// the archive. The size of
+    /// the log file is read from disk, so size triggers see what every process
+    /// wrote, and the file is always appended to rather than truncated. Every
+    /// process sharing the file must enable this and use the same policy.
+    ///
+    /// Defaults to `false`.
+    pub fn multi_process(mut self, multi_process: bool) -> RollingFileAppenderBuilder {
+        self.multi_process = multi_process;
+        self
+    }
+
+    /// Determines if each record is written with a single write call to the
+    /// file opened for appending, so that records written by other appenders
+    /// or processes to the same file never land in the middle of it.
+    ///
+    /// The file is always appended to rather than truncated.
+    ///
+    /// Defaults to `false`.
+    pub fn atomic_writes(mut self, atomic_writes: bool) -> RollingFileAppenderBuilder {
+        self.atomic_writes = atomic_writes;
+        self
+    }
+
+    /// Sets the length in bytes of the longest record written with a single
+    /// write call when `atomic_writes` is enabled.
+    ///
+    /// Defaults to 64 KiB.
+    pub fn atomic_write_limit(mut self, atomic_write_limit: usize) -> RollingFileAppenderBuilder {
+        self.atomic_write_limit = atomic_write_limit;
+        self
+    }
+
+    /// Sets how records longer than the atomic write limit are written when
+    /// `atomic_writes` is enabled.
+    ///
+    /// Defaults to `Oversized::Split`.
+    pub fn oversized_records(mut self, oversized_records: Oversized) -> RollingFileAppenderBuilder {
+        self.oversized_records = oversized_records;
+        self
+    }
+
+    /// Sets how the log file is archived when it is rolled.
+    ///
+    /// With `Mode::CopyTruncate` the log file is copied to the archive and
+    /// truncated in place, so that programs following it through an open
+    /// handle, such as `tail -f`, keep seeing new records. Records written by
+    /// other processes between the copy and the truncation are lost.
+    ///
+    /// Defaults to `Mode::Rename`.
+    pub fn mode(mut self, mode: Mode) -> RollingFileAppenderBuilder {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets how the log file is archived when other programs hold it open,
+    /// such as virus scanners or log tailers on Windows.
+    ///
+    /// Defaults to archiving the file once, without retrying.
+    pub fn rotation(mut self, rotation: Rotation) -> RollingFileAppenderBuilder {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Sets the permissions of each log file the appender creates, such as
+    /// `0o640`, regardless of the process umask.
+    ///
+    /// The permissions of archives are set on the roller. Only applies on
+    /// Unix. Defaults to the permissions allowed by the umask.
+    pub fn file_mode(mut self, mode: u32) -> RollingFileAppenderBuilder {
+        self.permissions.mode = Some(mode);
+        self
+    }
+
+    /// Sets the user owning each log file the appender creates, as a name or
+    /// a numeric ID.
+    ///
+    /// Only applies on Unix, and changing the owner usually requires root.
+    /// Defaults to the user running the process.
+    pub fn owner(mut self, owner: &str) -> RollingFileAppenderBuilder {
+        self.permissions.owner = Some(owner.to_owned());
+        self
+    }
+
+    /// Sets the group owning each log file the appender creates, as a name or
+    /// a numeric ID.
+    ///
+    /// Only applies on Unix. The process must belong to the group unless it
+    /// runs as root. Defaults to the group the operating system chooses.
+    pub fn group(mut self, group: &str) -> RollingFileAppenderBuilder {
+        self.permissions.group = Some(group.to_owned());
+        self
+    }
+
+    /// Determines if missing directories leading to the log file are created
+    /// when the appender is built.
+    ///
+    /// Directories for archives are created as the roller allows. Defaults to
+    /// `true`.
+    pub fn create_dirs(mut self, create_dirs: bool) -> RollingFileAppenderBuilder {
+        self.directories.create = create_dirs;
+        self
+    }
+
+    /// Sets the most directories leading to the log file the appender
+    /// creates, so that a mistyped path fails rather than creating a tree of
+    /// directories in an unexpected place.
+    ///
+    /// Defaults to no limit.
+    pub fn max_dir_depth(mut self, max_dir_depth: usize) -> RollingFileAppenderBuilder {
+        self.directories.max_depth = Some(max_dir_depth);
+        self
+    }
+
+    /// Sets a path the appender logs to instead if the log file cannot be
+    /// opened at its path, for example because its directory cannot be
+    /// created.
+    ///
+    /// The failure is reported to the error handler, and the policy rolls
+    /// the fallback log file in place of the log file. The fallback path can
+    /// contain environment variables like the path passed to `build`. By
+    /// default building the appender fails instead.
+    pub fn fallback_path<P: AsRef<Path>>(mut self, fallback_path: P) -> RollingFileAppenderBuilder {
+        self.fallback_path = Some(fallback_path.as_ref().into());
+        self
+    }
+
+    /// Determines if the log file is only created when the first record is
+    /// written to it, rather than when the appender is built.
+    ///
+    /// Missing directories are created at the same time, and a failure to
+    /// open the file is reported as an error appending that record. A lazily
+    /// created log file cannot have a fallback path. Defaults to `false`.
+    pub fn lazy(mut self, lazy: bool) -> RollingFileAppenderBuilder {
+        self.lazy = lazy;
+        self
+    }
+
     /// Sets the encoder used by the appender.
     ///
     /// Defaults to a `PatternEncoder` with the default pattern.
@@ -244,11 +917,20 @@ impl RollingFileAppenderBuilder {
         self
     }
 
+    /// Sets how a record the encoder fails to encode is handled.
+    ///
+    /// Defaults to `EncodeFailure::Report`.
+    pub fn encode_failure(mut self, encode_failure: EncodeFailure) -> RollingFileAppenderBuilder {
+        self.encode_failure = encode_failure;
+        self
+    }
+
     /// Constructs a `RollingFileAppender`.
     /// The path argument can contain environment variables of the form $ENV{name_here},
     /// where 'name_here' will be the name of the environment variable that
     /// will be resolved. Note that if the variable fails to resolve,
-    /// $ENV{name_here} will NOT be replaced in the path.
+    /// $ENV{name_here} will NOT be replaced in the path. `{hostname}` and
+    /// `{pid}` are replaced with the name of the host and the ID of the process.
     pub fn build<P>(
         self,
         path: P,
@@ -257,26 +939,151 @@ impl RollingFileAppenderBuilder {
     where
         P: AsRef<Path>,
     {
-        let path = super::env_util::expand_env_vars(path.as_ref().to_string_lossy());
-        let appender = RollingFileAppender {
+        if self.lazy && self.fallback_path.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a fallback path is not supported by lazily created log files",
+            ));
+        }
+        if (self.atomic_writes || self.multi_process) && self.backend != Backend::Write {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "atomic writes and multi process log files are not supported by the {:?} \
+                     backend",
+                    self.backend
+                ),
+            ));
+        }
+
+        let path =
+            PathBuf::from(super::env_util::expand_path(path.as_ref().to_string_lossy()).as_ref());
+        let prepared = if self.lazy {
+            Ok(())
+        } else {
+            self.prepare(&path)
+        };
+        let path = match prepared {
+            Ok(()) => path,
+            Err(e) => match self.fallback_path {
+                Some(ref fallback) => {
+                    let fallback = PathBuf::from(
+                        super::env_util::expand_path(fallback.to_string_lossy()).as_ref(),
+                    );
+                    crate::handle_error(&anyhow::Error::new(e).context(format!(
+                        "error opening log file `{}`, logging to `{}` instead",
+                        path.display(),
+                        fallback.display()
+                    )));
+                    self.prepare(&fallback)?;
+                    fallback
+                }
+                None => return Err(e),
+            },
+        };
+        let path = path.as_path();
+
+        // Mapped regions are allocated as they are mapped.
+        let preallocate = self.preallocate.filter(|_| self.backend != Backend::Mmap);
+        let coordination = if self.multi_process {
+            Some(coordination::Coordination::new(path)?)
+        } else {
+            None
+        };
+        let shared = Arc::new(Shared {
             writer: Mutex::new(None),
-            path: path.as_ref().into(),
-            append: self.append,
+            path: path.into(),
+            append: self.append || self.multi_process || self.atomic_writes,
+            header: self.header,
+            footer: self.footer,
+            syncer: Syncer::new(self.durability, self.sync_interval, preallocate),
+            backend: self.backend,
+            region_size: self.region_size,
+            policy,
+            rotations: AtomicU64::new(0),
+            coordination,
+            atomic: if self.atomic_writes {
+                Some(AtomicWriter {
+                    limit: self.atomic_write_limit,
+                    oversized: self.oversized_records,
+                })
+            } else {
+                None
+            },
+            rotation: self.rotation.mode(self.mode),
+            permissions: self.permissions,
+            directories: self.directories,
+        });
+
+        let mut report = StartupReport::default();
+        if let Ok(metadata) = fs::metadata(&shared.path) {
+            report.file_len = Some(metadata.len());
+            report.file_age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+        }
+
+        if self.clean_up {
+            match shared.policy.clean_up(&shared.path) {
+                Ok(()) => report.cleaned_up = true,
+                Err(e) => crate::handle_error(&e.context("error cleaning up partial log files")),
+            }
+        }
+
+        match shared.policy.archives(&shared.path) {
+            Ok(archives) => report.archives = archives,
+            Err(e) => crate::handle_error(&e.context("error counting log archives")),
+        }
+
+        // open the log file immediately, unless it is created lazily
+        if !self.lazy {
+            shared.get_writer(&mut shared.writer.lock())?;
+        }
+
+        let timer = match self.check_interval {
+            Some(interval) => {
+                let shared = shared.clone();
+                let timer =
+                    background::schedule("log4rs rolling file timer", interval, move || {
+                        if let Err(e) = shared.check() {
+                            crate::handle_error(&e.context("error rolling log file"));
+                        }
+                        Some(interval)
+                    })?;
+                Some(timer)
+            }
+            None => None,
+        };
+
+        let appender = RollingFileAppender {
+            shared,
             encoder: self
                 .encoder
                 .unwrap_or_else(|| Box::<PatternEncoder>::default()),
-            policy,
+            encode_failure: self.encode_failure,
+            bytes_written: AtomicU64::new(0),
+            report,
+            _timer: timer,
         };
 
-        if let Some(parent) = appender.path.parent() {
-            fs::create_dir_all(parent)?;
+        if self.startup_report {
+            if let Err(e) = appender.report.log(&appender) {
+                crate::handle_error(&e.context("error logging startup report"));
+            }
         }
 
-        // open the log file immediately
-        appender.get_writer(&mut appender.writer.lock())?;
-
         Ok(appender)
     }
+
+    /// Creates the directories leading to the log file and checks that it
+    /// can be opened.
+    fn prepare(&self, path: &Path) -> io::Result<()> {
+        self.directories.create_for(path)?;
+        self.permissions
+            .open(OpenOptions::new().append(true).create(true), path)?;
+        Ok(())
+    }
 }
 
 /// A deserializer for the `RollingFileAppender`.
@@ -290,17 +1097,142 @@ impl RollingFileAppenderBuilder {
 /// # The path can contain environment variables of the form $ENV{name_here},
 /// # where 'name_here' will be the name of the environment variable that
 /// # will be resolved. Note that if the variable fails to resolve,
-/// # $ENV{name_here} will NOT be replaced in the path.
+/// # $ENV{name_here} will NOT be replaced in the path. `{hostname}` and `{pid}`
+/// # are replaced with the name of the host and the ID of the process.
 /// path: log/foo.log
 ///
 /// # Specifies if the appender should append to or truncate the log file if it
 /// # already exists. Defaults to `true`.
 /// append: true
 ///
+/// # Specifies if temporary and partial files left behind by interrupted rolls, such as
+/// # half-written compressed archives, should be removed on startup. Log files moved
+/// # aside for background rotation are archived instead. Defaults to `false`.
+/// clean_up: false
+///
+/// # How often the policy is checked while no records are written, so that
+/// # triggers based on time roll the file even when the application is idle.
+/// # By default the policy is only checked after a record is written.
+/// check_interval: 1 minute
+///
+/// # Text written at the start of each new log file, and at the end of each
+/// # log file before it is rolled or the appender is dropped. Both are written
+/// # as is, and are not written by default.
+/// header: "time,level,message\n"
+/// footer: "log continues in the next file\n"
+///
+/// # How durably records are written. "none" leaves syncing the file to disk
+/// # to the operating system, "on_flush" syncs it when the appender is
+/// # flushed, "batched" syncs it at most once per sync interval and when the
+/// # appender is flushed, and "strict" syncs it after every record. Defaults
+/// # to "none".
+/// durability: batched
+///
+/// # How long "batched" durability waits after a sync before syncing again.
+/// # Defaults to 1 second.
+/// sync_interval: 5 seconds
+///
+/// # If set, disk space is reserved for each log file ahead of the records
+/// # written to it, this many bytes at a time. Only supported on Linux. By
+/// # default no space is reserved.
+/// preallocate: 67108864
+///
+/// # How records are written to the log files. "write" writes each record with
+/// # a write call, "mmap" copies records into a memory mapping of a region at
+/// # the end of the file, which is extended a region at a time and truncated
+/// # to the records written when it is rolled or the appender is dropped, and
+/// # "io_uring" hands records to a background thread which writes them with
+/// # io_uring. "mmap" is only supported on Unix, and "io_uring" only on Linux
+/// # with the `io_uring` feature. Neither is supported with atomic writes or
+/// # multi process log files. Defaults to "write".
+/// backend: io_uring
+///
+/// # The length in bytes of the regions the "mmap" backend maps at a time.
+/// # Defaults to 16777216.
+/// region_size: 67108864
+///
+/// # Specifies if the appender logs a report of what it found on startup to
+/// # the log file: the size and age of the log file, the number of archives,
+/// # and whether partial files were cleaned up. Defaults to `false`.
+/// startup_report: false
+///
+/// # Specifies if the log file is shared with other processes, which then
+/// # coordinate through `<path>.lock` and `<path>.generation` files so that
+/// # exactly one of them rolls it when it is due. Defaults to `false`.
+/// multi_process: false
+///
+/// # Specifies if each record should be written with a single write call to
+/// # the file opened for appending, so that records from other appenders or
+/// # processes writing to the same file cannot land in the middle of it. The
+/// # file is always appended to if enabled. Defaults to `false`.
+/// atomic_writes: true
+///
+/// # The length in bytes of the longest record written with a single write
+/// # call. Defaults to 65536.
+/// atomic_write_limit: 16384
+///
+/// # How records longer than the limit are written. "split" writes them in
+/// # pieces no longer than the limit, and "truncate" cuts them short, keeping
+/// # the final newline. Defaults to "split".
+/// oversized_records: split
+///
+/// # How the log file is archived. "rename" moves it to the archive and opens
+/// # a new log file, and "copy_truncate" copies it to the archive and truncates
+/// # it in place, so that programs following it through an open handle, such
+/// # as `tail -f`, keep seeing new records. Defaults to "rename".
+/// mode: copy_truncate
+///
+/// # How the log file is archived while other programs hold it open, such as
+/// # virus scanners or log tailers on Windows. Archiving which fails with a
+/// # sharing violation is retried up to `retries` times, waiting `backoff`
+/// # before the first retry and twice as long before each further one.
+/// # `copy_truncate` may be "never", or "fallback" to copy the log file and
+/// # truncate it in place once the retries are exhausted. Defaults to no
+/// # retries and "never".
+/// rotation:
+///   retries: 5
+///   backoff: 100 ms
+///   copy_truncate: fallback
+///
+/// # The permissions of each log file the appender creates, written in octal,
+/// # and the user and group owning it, as names or numeric IDs. Only applied
+/// # on Unix; the permissions of archives are set on the roller. By default
+/// # files are created as the process umask allows, owned by the user running
+/// # the process.
+/// file_mode: "0640"
+/// owner: app
+/// group: adm
+///
+/// # Specifies if missing directories leading to the log file are created.
+/// # Defaults to `true`.
+/// create_dirs: true
+///
+/// # The most directories leading to the log file that are created. Defaults
+/// # to no limit.
+/// max_dir_depth: 2
+///
+/// # A path logged to instead if the log file cannot be opened at its path,
+/// # for example because its directory cannot be created. The failure is
+/// # reported to the error handler. By default the appender fails to build
+/// # instead.
+/// fallback_path: /tmp/foo.log
+///
+/// # If set to `true`, the log file and the directories leading to it are
+/// # only created when the first record is written, rather than when the
+/// # appender is built. Cannot be combined with `fallback_path`. Defaults to
+/// # `false`.
+/// lazy: false
+///
 /// # The encoder to use to format output. Defaults to `kind: pattern`.
 /// encoder:
 ///   kind: pattern
 ///
+/// # How a record the encoder fails to encode is handled. "report" reports the
+/// # error and the record's message to the error handler, and "plain" writes a
+/// # plain line with the record's level, target and message and the error in
+/// # its place. Defaults to "report".
+/// encode_failure: plain
+///
 /// # The policy which handles rotation of the log file. Required.
 /// policy:
 ///   # Identifies which policy is to be used. If no kind is specified, it will
@@ -335,10 +1267,82 @@ impl Deserialize for RollingFileAppenderDeserializer {
         if let Some(append) = config.append {
             builder = builder.append(append);
         }
+        if let Some(clean_up) = config.clean_up {
+            builder = builder.clean_up(clean_up);
+        }
+        if let Some(check_interval) = config.check_interval {
+            builder = builder.check_interval(check_interval);
+        }
+        if let Some(header) = config.header {
+            builder = builder.header(&header);
+        }
+        if let Some(footer) = config.footer {
+            builder = builder.footer(&footer);
+        }
+        if let Some(durability) = config.durability {
+            builder = builder.durability(durability);
+        }
+        if let Some(sync_interval) = config.sync_interval {
+            builder = builder.sync_interval(sync_interval);
+        }
+        if let Some(preallocate) = config.preallocate {
+            builder = builder.preallocate(preallocate);
+        }
+        if let Some(backend) = config.backend {
+            builder = builder.backend(backend);
+        }
+        if let Some(region_size) = config.region_size {
+            builder = builder.region_size(region_size);
+        }
+        if let Some(startup_report) = config.startup_report {
+            builder = builder.startup_report(startup_report);
+        }
+        if let Some(multi_process) = config.multi_process {
+            builder = builder.multi_process(multi_process);
+        }
+        if let Some(atomic_writes) = config.atomic_writes {
+            builder = builder.atomic_writes(atomic_writes);
+        }
+        if let Some(atomic_write_limit) = config.atomic_write_limit {
+            builder = builder.atomic_write_limit(atomic_write_limit);
+        }
+        if let Some(oversized_records) = config.oversized_records {
+            builder = builder.oversized_records(oversized_records);
+        }
+        if let Some(mode) = config.mode {
+            builder = builder.mode(mode);
+        }
+        if let Some(rotation) = config.rotation {
+            builder = builder.rotation(rotation.into());
+        }
+        if let Some(file_mode) = config.file_mode {
+            builder = builder.file_mode(file_mode);
+        }
+        if let Some(owner) = config.owner {
+            builder = builder.owner(&owner);
+        }
+        if let Some(group) = config.group {
+            builder = builder.group(&group);
+        }
+        if let Some(create_dirs) = config.create_dirs {
+            builder = builder.create_dirs(create_dirs);
+        }
+        if let Some(max_dir_depth) = config.max_dir_depth {
+            builder = builder.max_dir_depth(max_dir_depth);
+        }
+        if let Some(fallback_path) = config.fallback_path {
+            builder = builder.fallback_path(fallback_path);
+        }
+        if let Some(lazy) = config.lazy {
+            builder = builder.lazy(lazy);
+        }
         if let Some(encoder) = config.encoder {
             let encoder = deserializers.deserialize(&encoder.kind, encoder.config)?;
             builder = builder.encoder(encoder);
         }
+        if let Some(encode_failure) = config.encode_failure {
+            builder = builder.encode_failure(encode_failure);
+        }
 
         let policy = deserializers.deserialize(&config.policy.kind, config.policy.config)?;
         let appender = builder.build(config.path, policy)?;
@@ -430,6 +1434,329 @@ appenders:
         assert_eq!(contents, b"hello");
     }
 
+    #[derive(Debug)]
+    struct RollPolicy;
+
+    impl Policy for RollPolicy {
+        fn process(&self, log: &mut LogFile) -> anyhow::Result<()> {
+            fs::remove_file(log.path())?;
+            log.roll();
+            Ok(())
+        }
+    }
+
+    // Rolls the log file to `<name>.0.log` once.
+    #[derive(Debug)]
+    struct ArchiveOncePolicy;
+
+    impl Policy for ArchiveOncePolicy {
+        fn process(&self, log: &mut LogFile) -> anyhow::Result<()> {
+            let archive = log.path().with_extension("0.log");
+            if !archive.exists() && log.claim_roll()? {
+                log.archive(|path| fs::rename(path, &archive).map_err(Into::into))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn copy_truncate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .mode(Mode::CopyTruncate)
+            .build(&path, Box::new(ArchiveOncePolicy))
+            .unwrap();
+
+        // a tailer following the log file through an open handle
+        let mut tail = File::open(&path).unwrap();
+        for message in ["one", "two"] {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+
+        // the tailer sees the record written after the roll, not the archive
+        let mut contents = String::new();
+        tail.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "two\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("app.0.log")).unwrap(),
+            "one\n"
+        );
+        assert!(!dir.path().join("app.log.copy").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn backends() {
+        #[allow(unused_mut)]
+        let mut backends = vec![Backend::Mmap];
+        #[cfg(all(target_os = "linux", feature = "io_uring"))]
+        backends.push(Backend::IoUring);
+
+        for backend in backends {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("app.log");
+            let appender = RollingFileAppender::builder()
+                .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+                .backend(backend)
+                .region_size(4096)
+                .build(&path, Box::new(ArchiveOncePolicy))
+                .unwrap();
+            for message in ["one", "two", "three"] {
+                appender
+                    .append(&Record::builder().args(format_args!("{}", message)).build())
+                    .unwrap();
+            }
+            assert_eq!(appender.tail(Tail::Lines(5)).unwrap(), b"two\nthree\n");
+
+            // the archive was written out in full before it was rolled
+            assert_eq!(
+                fs::read_to_string(dir.path().join("app.0.log")).unwrap(),
+                "one\n"
+            );
+            drop(appender);
+            assert_eq!(fs::read_to_string(&path).unwrap(), "two\nthree\n");
+
+            assert!(RollingFileAppender::builder()
+                .backend(backend)
+                .multi_process(true)
+                .build(&path, Box::new(ArchiveOncePolicy))
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .build(dir.path().join("stats.log"), Box::new(RollPolicy))
+            .unwrap();
+        for message in ["one", "two"] {
+            appender
+                .append(&Record::builder().args(format_args!("{}", message)).build())
+                .unwrap();
+        }
+
+        let stats = appender.stats();
+        assert_eq!(stats.bytes_written, Some(8));
+        assert_eq!(stats.rotations, Some(2));
+    }
+
+    #[derive(Debug)]
+    struct ArchivePolicy;
+
+    impl Policy for ArchivePolicy {
+        fn process(&self, _: &mut LogFile) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn roll_now(&self, log: &mut LogFile) -> anyhow::Result<()> {
+            log.roll();
+            fs::rename(log.path(), log.path().with_extension("1"))?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn roll_now() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("roll_now.log");
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .build(&path, Box::new(ArchivePolicy))
+            .unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+
+        appender.roll_now().unwrap();
+        assert_eq!(fs::read(path.with_extension("1")).unwrap(), b"one\n");
+        assert_eq!(fs::read(&path).unwrap(), b"");
+        assert_eq!(appender.stats().rotations, Some(1));
+
+        let appender = RollingFileAppender::builder()
+            .build(dir.path().join("nop.log"), Box::new(NopPolicy))
+            .unwrap();
+        assert!(appender.roll_now().is_err());
+    }
+
+    #[derive(Debug)]
+    struct ClaimPolicy;
+
+    impl Policy for ClaimPolicy {
+        fn process(&self, _: &mut LogFile) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn roll_now(&self, log: &mut LogFile) -> anyhow::Result<()> {
+            if log.claim_roll()? {
+                fs::rename(log.path(), log.path().with_extension("1"))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn multi_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("multi_process.log");
+        let build = || {
+            RollingFileAppender::builder()
+                .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+                .multi_process(true)
+                .build(&path, Box::new(ClaimPolicy))
+                .unwrap()
+        };
+        let (a, b) = (build(), build());
+        a.append(&Record::builder().args(format_args!("a")).build())
+            .unwrap();
+        b.append(&Record::builder().args(format_args!("b")).build())
+            .unwrap();
+
+        a.roll_now().unwrap();
+        // b sees that a rolled the file, and does not roll the new one.
+        b.roll_now().unwrap();
+        assert_eq!(fs::read(path.with_extension("1")).unwrap(), b"a\nb\n");
+        assert_eq!(a.stats().rotations, Some(1));
+        assert_eq!(b.stats().rotations, Some(0));
+
+        b.append(&Record::builder().args(format_args!("c")).build())
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"c\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("multi_process.log.generation")).unwrap(),
+            "1"
+        );
+    }
+
+    #[derive(Debug)]
+    struct FlagPolicy {
+        due: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl Policy for FlagPolicy {
+        fn process(&self, log: &mut LogFile) -> anyhow::Result<()> {
+            if self.due.swap(false, Ordering::SeqCst) {
+                log.roll();
+                fs::rename(log.path(), log.path().with_extension("1"))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn check_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("check_interval.log");
+        let due = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .check_interval(Duration::from_millis(10))
+            .build(&path, Box::new(FlagPolicy { due: due.clone() }))
+            .unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+
+        due.store(true, Ordering::SeqCst);
+        for _ in 0..500 {
+            if appender.stats().rotations == Some(1) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(appender.stats().rotations, Some(1));
+        assert_eq!(fs::read(path.with_extension("1")).unwrap(), b"one\n");
+        // The timer reopens the log file after rolling it.
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn header_footer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("header_footer.log");
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .header("header\n")
+            .footer("footer\n")
+            .build(&path, Box::new(ArchivePolicy))
+            .unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+        appender.roll_now().unwrap();
+        assert_eq!(
+            fs::read(path.with_extension("1")).unwrap(),
+            b"header\none\nfooter\n"
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"header\n");
+
+        drop(appender);
+        assert_eq!(fs::read(&path).unwrap(), b"header\nfooter\n");
+    }
+
+    #[test]
+    fn lazy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("lazy.log");
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .header("header\n")
+            .footer("footer\n")
+            .lazy(true)
+            .build(&path, Box::new(NopPolicy))
+            .unwrap();
+        drop(appender);
+        assert!(!path.parent().unwrap().exists());
+
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .header("header\n")
+            .lazy(true)
+            .build(&path, Box::new(NopPolicy))
+            .unwrap();
+        assert!(!path.exists());
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"header\none\n");
+
+        assert!(RollingFileAppender::builder()
+            .lazy(true)
+            .fallback_path(dir.path().join("fallback.log"))
+            .build(&path, Box::new(NopPolicy))
+            .is_err());
+    }
+
+    #[test]
+    fn startup_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("startup_report.log");
+        fs::write(&path, b"hello\n").unwrap();
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new(
+                "{t} {m} {K(file_len)} {K(cleaned_up)}{n}",
+            )))
+            .clean_up(true)
+            .startup_report(true)
+            .build(&path, Box::new(NopPolicy))
+            .unwrap();
+
+        let report = appender.startup_report();
+        assert_eq!(report.file_len, Some(6));
+        assert!(report.file_age.is_some());
+        assert_eq!(report.archives, None);
+        assert!(report.cleaned_up);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "hello\nlog4rs::append::rolling_file rolling file appender started 6 true\n"
+        );
+    }
+
     #[test]
     fn truncate() {
         let dir = tempfile::tempdir().unwrap();
@@ -7,6 +7,7 @@ use serde::{self, de};
 use serde_value::Value;
 #[cfg(feature = "config_parsing")]
 use std::collections::BTreeMap;
+use std::path::Path;
 
 use crate::append::rolling_file::{
     policy::{compound::roll::Roll, Policy},
@@ -25,6 +26,8 @@ pub mod trigger;
 pub struct CompoundPolicyConfig {
     trigger: Trigger,
     roller: Roller,
+    #[serde(default)]
+    skip_if_empty: bool,
 }
 
 #[cfg(feature = "config_parsing")]
@@ -90,23 +93,56 @@ impl<'de> serde::Deserialize<'de> for Roller {
 pub struct CompoundPolicy {
     trigger: Box<dyn trigger::Trigger>,
     roller: Box<dyn Roll>,
+    skip_if_empty: bool,
 }
 
 impl CompoundPolicy {
     /// Creates a new `CompoundPolicy`.
     pub fn new(trigger: Box<dyn trigger::Trigger>, roller: Box<dyn Roll>) -> CompoundPolicy {
-        CompoundPolicy { trigger, roller }
+        CompoundPolicy {
+            trigger,
+            roller,
+            skip_if_empty: false,
+        }
+    }
+
+    /// Sets whether rolls of an empty log file are skipped, so that quiet
+    /// services rolled on a schedule do not pile up empty archives.
+    ///
+    /// This applies to rolls requested by the trigger and by
+    /// `RollingFileAppender::roll_now` alike. A file holding only the
+    /// appender's header is not empty. Defaults to `false`.
+    pub fn skip_if_empty(mut self, skip_if_empty: bool) -> CompoundPolicy {
+        self.skip_if_empty = skip_if_empty;
+        self
     }
 }
 
 impl Policy for CompoundPolicy {
     fn process(&self, log: &mut LogFile) -> anyhow::Result<()> {
         if self.trigger.trigger(log)? {
-            log.roll();
-            self.roller.roll(log.path())?;
+            self.roll_now(log)?;
+        }
+        Ok(())
+    }
+
+    fn roll_now(&self, log: &mut LogFile) -> anyhow::Result<()> {
+        if self.skip_if_empty && log.len_estimate() == 0 {
+            return Ok(());
+        }
+        if log.claim_roll()? {
+            log.archive(|path| self.roller.roll(path))?;
         }
         Ok(())
     }
+
+    fn clean_up(&self, path: &Path) -> anyhow::Result<()> {
+        self.roller.clean_up(path)
+    }
+
+    fn archives(&self, path: &Path) -> anyhow::Result<Option<usize>> {
+        self.roller.archives(path)
+    }
 }
 
 /// A deserializer for the `CompoundPolicyDeserializer`.
@@ -134,6 +170,9 @@ impl Policy for CompoundPolicy {
 ///
 ///   # The remainder of the configuration is passed to the roller's
 ///   # deserializer, and will vary based on the kind of roller.
+///
+/// # Whether rolls of an empty log file are skipped. Defaults to false.
+/// skip_if_empty: true
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -152,6 +191,65 @@ impl Deserialize for CompoundPolicyDeserializer {
     ) -> anyhow::Result<Box<dyn Policy>> {
         let trigger = deserializers.deserialize(&config.trigger.kind, config.trigger.config)?;
         let roller = deserializers.deserialize(&config.roller.kind, config.roller.config)?;
-        Ok(Box::new(CompoundPolicy::new(trigger, roller)))
+        Ok(Box::new(
+            CompoundPolicy::new(trigger, roller).skip_if_empty(config.skip_if_empty),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Record;
+    use std::{
+        fs,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+    use crate::append::{rolling_file::RollingFileAppender, Append};
+
+    #[derive(Debug)]
+    struct Never;
+
+    impl trigger::Trigger for Never {
+        fn trigger(&self, _: &LogFile) -> anyhow::Result<bool> {
+            Ok(false)
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Counting(Arc<AtomicUsize>);
+
+    impl Roll for Counting {
+        fn roll(&self, file: &Path) -> anyhow::Result<()> {
+            let n = self.0.fetch_add(1, Ordering::SeqCst);
+            fs::rename(file, file.with_extension(n.to_string()))?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn skip_if_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let rolls = Arc::new(AtomicUsize::new(0));
+        let policy = CompoundPolicy::new(Box::new(Never), Box::new(Counting(rolls.clone())))
+            .skip_if_empty(true);
+        let appender = RollingFileAppender::builder()
+            .build(dir.path().join("quiet.log"), Box::new(policy))
+            .unwrap();
+
+        appender.roll_now().unwrap();
+        assert_eq!(rolls.load(Ordering::SeqCst), 0);
+
+        appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .unwrap();
+        appender.roll_now().unwrap();
+        appender.roll_now().unwrap();
+        assert_eq!(rolls.load(Ordering::SeqCst), 1);
+        assert!(dir.path().join("quiet.0").exists());
     }
 }
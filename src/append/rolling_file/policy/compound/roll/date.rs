@@ -0,0 +1,864 @@
+//! The date roller.
+//!
+//! Requires the `date_roller` feature.
+
+use anyhow::bail;
+use chrono::{
+    format::{self, Item, Parsed, StrftimeItems},
+    DateTime, Local,
+};
+use std::{
+    fs,
+    hash::Hasher,
+    io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use crate::append::dir::Directories;
+use crate::append::env_util::expand_path;
+use crate::append::perm::Permissions;
+use crate::append::rolling_file::policy::compound::roll::{
+    archive_pattern, check_manifest, partial_file_name, record_in_manifest, remove_if_exists,
+    Compression, Roll,
+};
+use crate::compress::Compressor;
+#[cfg(feature = "config_parsing")]
+use crate::compress::CompressorConfig;
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::encrypt::Encryptor;
+#[cfg(feature = "config_parsing")]
+use crate::encrypt::EncryptorConfig;
+
+/// Configuration for the date roller.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DateRollerConfig {
+    pattern: String,
+    count: Option<u32>,
+    overflow: Option<u32>,
+    max_files_per_dir: Option<usize>,
+    #[serde(default)]
+    shard: Shard,
+    compressor: Option<CompressorConfig>,
+    encrypt: Option<EncryptorConfig>,
+    manifest: Option<bool>,
+    #[serde(deserialize_with = "crate::append::perm::de_mode", default)]
+    mode: Option<u32>,
+    #[serde(deserialize_with = "crate::append::perm::de_id", default)]
+    owner: Option<String>,
+    #[serde(deserialize_with = "crate::append::perm::de_id", default)]
+    group: Option<String>,
+    create_dirs: Option<bool>,
+    max_dir_depth: Option<usize>,
+    archive_dir: Option<String>,
+}
+
+/// How archives are split into subdirectories once a directory is full.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Shard {
+    /// Subdirectories named by two hex digits of a hash of the archive's file
+    /// name, giving up to 256 subdirectories.
+    #[default]
+    Hash,
+    /// Subdirectories named by the date of the roll, as `YYYY-MM-DD`.
+    Date,
+}
+
+/// A roller which archives log files under a name derived from the time of
+/// the roll.
+///
+/// The filename pattern is formatted with the local time using `strftime`
+/// style specifiers, and may place archives in nested date directories, which
+/// are created on demand. For example, with the pattern
+/// `archive/%Y/%m/%d/foo.%H%M%S.log` a log file rolled at 14:30 on the 1st of
+/// May 2024 is archived as `archive/2024/05/01/foo.143000.log`. Rolling fails
+/// if an archive with the resulting name already exists, unless overflow
+/// suffixes are allowed: then the archive is named with the lowest free
+/// suffix `.2`, `.3` and so on before its extension, such as
+/// `app.2024-05-01.2.log.gz` when a daily pattern is rolled twice in a day.
+///
+/// If a count is set, only that many archives are kept: after each roll the
+/// oldest files matching the pattern are deleted, along with any date
+/// directories left empty.
+///
+/// If a maximum number of files per directory is set, archives which would be
+/// placed in a directory that already holds that many entries are placed in
+/// a shard subdirectory of it instead.
+///
+/// Archive files are compressed with the roller's `Compressor` if one is set.
+/// Otherwise, if the file extension of the pattern is `.gz` and the `gzip`
+/// Cargo feature is enabled, the archive files will be gzip-compressed.
+/// Archive files are then encrypted with the roller's `Encryptor` if one is
+/// set.
+#[derive(Clone, Debug)]
+pub struct DateRoller {
+    pattern: String,
+    compression: Compression,
+    count: Option<u32>,
+    overflow: Option<u32>,
+    max_files_per_dir: Option<(usize, Shard)>,
+    manifest: bool,
+    directories: Directories,
+}
+
+impl DateRoller {
+    /// Returns a new builder for the `DateRoller`.
+    pub fn builder() -> DateRollerBuilder {
+        DateRollerBuilder::default()
+    }
+
+    fn roll_at(&self, file: &Path, now: DateTime<Local>) -> anyhow::Result<()> {
+        let mut dst =
+            PathBuf::from(expand_path(now.format(&self.pattern).to_string()).into_owned());
+        self.directories.create_for(&dst)?;
+        if let Some((max, shard)) = self.max_files_per_dir {
+            dst = sharded(dst, max, shard, now)?;
+        }
+
+        if dst.exists() {
+            let limit = match self.overflow {
+                Some(limit) => limit,
+                None => bail!("archive `{}` already exists", dst.display()),
+            };
+            dst = match (2..=limit)
+                .map(|n| with_suffix(&dst, n))
+                .find(|dst| !dst.exists())
+            {
+                Some(dst) => dst,
+                None => bail!("archive `{}` already exists {} times", dst.display(), limit),
+            };
+        }
+        self.directories.create_for(&dst)?;
+        self.compression.compress(file, &dst.to_string_lossy())?;
+        if self.manifest {
+            record_in_manifest(&dst)?;
+        }
+
+        if let Some(count) = self.count {
+            self.prune(count as usize)?;
+        }
+        Ok(())
+    }
+
+    /// Splits the pattern into the deepest directory which doesn't depend on
+    /// the date, below which all archives live, and the components of the
+    /// rest of the pattern.
+    fn split_pattern(&self) -> (PathBuf, Vec<String>) {
+        let pattern = expand_path(&self.pattern);
+
+        let mut root = PathBuf::new();
+        let mut rest = vec![];
+        for component in Path::new(&*pattern).components() {
+            let text = component.as_os_str().to_string_lossy();
+            if rest.is_empty() && !text.contains('%') {
+                root.push(component);
+            } else if let Component::Normal(_) = component {
+                rest.push(text.into_owned());
+            }
+        }
+        if root.as_os_str().is_empty() {
+            root.push(".");
+        }
+        (root, rest)
+    }
+
+    /// Deletes all but the newest `count` archives.
+    fn prune(&self, count: usize) -> io::Result<()> {
+        let (root, rest) = self.split_pattern();
+        if rest.is_empty() {
+            // The pattern doesn't depend on the date at all, so there is at
+            // most one archive.
+            return Ok(());
+        }
+
+        let mut archives = vec![];
+        find_archives(&root, &rest, &mut vec![], &mut archives)?;
+        archives.sort_by(|a, b| b.cmp(a));
+
+        for (_, path) in archives.into_iter().skip(count) {
+            fs::remove_file(&path)?;
+            let mut dir = path.parent();
+            while let Some(d) = dir {
+                if d == root || fs::remove_dir(d).is_err() {
+                    break;
+                }
+                dir = d.parent();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Moves `dst` into a shard subdirectory if its directory is full.
+fn sharded(dst: PathBuf, max: usize, shard: Shard, now: DateTime<Local>) -> io::Result<PathBuf> {
+    let (parent, name) = match (dst.parent(), dst.file_name()) {
+        (Some(parent), Some(name)) => (parent, name),
+        _ => return Ok(dst),
+    };
+    let dir = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+
+    if fs::read_dir(dir)?.count() < max {
+        return Ok(dst);
+    }
+
+    let subdir = match shard {
+        Shard::Hash => {
+            let mut hasher = fnv::FnvHasher::default();
+            hasher.write(name.to_string_lossy().as_bytes());
+            format!("{:02x}", hasher.finish() & 0xff)
+        }
+        Shard::Date => now.format("%Y-%m-%d").to_string(),
+    };
+    Ok(parent.join(subdir).join(name))
+}
+
+/// Compression and encryption extensions, which stay last when an overflow
+/// suffix is added.
+const COMPRESSED: &[&str] = &["gz", "zst", "lz4", "bz2", "xz", "age"];
+
+/// Returns `dst` with the overflow suffix `.n` inserted before its extension.
+fn with_suffix(dst: &Path, n: u32) -> PathBuf {
+    let name = match dst.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return dst.to_owned(),
+    };
+    let mut end = name.len();
+    while let Some(i) = name[..end].rfind('.') {
+        let extension = &name[i + 1..end];
+        end = i;
+        if !COMPRESSED.contains(&extension) {
+            break;
+        }
+    }
+    dst.with_file_name(format!("{}.{}{}", &name[..end], n, &name[end..]))
+}
+
+/// Collects the files below `dir` whose path relative to the archive root
+/// matches the date-dependent `rest` of the pattern.
+fn find_archives(
+    dir: &Path,
+    rest: &[String],
+    prefix: &mut Vec<String>,
+    archives: &mut Vec<(SystemTime, PathBuf)>,
+) -> io::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let last = prefix.len() + 1 == rest.len();
+    for entry in entries {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        prefix.push(entry.file_name().to_string_lossy().into_owned());
+
+        if last && file_type.is_file() {
+            if matches(rest, prefix) {
+                archives.push((entry.metadata()?.modified()?, entry.path()));
+            }
+        } else if last && file_type.is_dir() {
+            // a shard subdirectory
+            for shard_entry in fs::read_dir(entry.path())? {
+                let shard_entry = shard_entry?;
+                if !shard_entry.file_type()?.is_file() {
+                    continue;
+                }
+                prefix.pop();
+                prefix.push(shard_entry.file_name().to_string_lossy().into_owned());
+                if matches(rest, prefix) {
+                    archives.push((shard_entry.metadata()?.modified()?, shard_entry.path()));
+                }
+            }
+        } else if !last && file_type.is_dir() {
+            find_archives(&entry.path(), rest, prefix, archives)?;
+        }
+
+        prefix.pop();
+    }
+    Ok(())
+}
+
+fn matches(rest: &[String], relative: &[String]) -> bool {
+    let format = rest.join("/");
+    let parses = |relative: &str| {
+        let mut parsed = Parsed::new();
+        format::parse(&mut parsed, relative, StrftimeItems::new(&format)).is_ok()
+    };
+
+    let relative = relative.join("/");
+    if parses(&relative) {
+        return true;
+    }
+
+    // Try each `.N` segment of the file name as an overflow suffix.
+    let name_start = relative.rfind('/').map_or(0, |i| i + 1);
+    let mut start = name_start;
+    while let Some(i) = relative[start..].find('.') {
+        let dot = start + i;
+        let digits = relative[dot + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(relative.len(), |j| dot + 1 + j);
+        if digits > dot + 1 && relative[digits..].starts_with('.') {
+            let unsuffixed = format!("{}{}", &relative[..dot], &relative[digits..]);
+            if parses(&unsuffixed) {
+                return true;
+            }
+        }
+        start = dot + 1;
+    }
+    false
+}
+
+impl Roll for DateRoller {
+    fn roll(&self, file: &Path) -> anyhow::Result<()> {
+        self.roll_at(file, Local::now())
+    }
+
+    fn clean_up(&self, _: &Path) -> anyhow::Result<()> {
+        let (root, mut rest) = self.split_pattern();
+        match rest.last_mut() {
+            Some(last) => *last = partial_file_name(last),
+            None => {
+                let partial = partial_file_name(&root.to_string_lossy());
+                return remove_if_exists(Path::new(&partial)).map_err(Into::into);
+            }
+        }
+
+        let mut partials = vec![];
+        find_archives(&root, &rest, &mut vec![], &mut partials)?;
+        for (_, path) in partials {
+            remove_if_exists(&path)?;
+        }
+        Ok(())
+    }
+
+    fn archives(&self, _: &Path) -> anyhow::Result<Option<usize>> {
+        let (root, rest) = self.split_pattern();
+        if rest.is_empty() {
+            return Ok(Some(root.is_file() as usize));
+        }
+
+        let mut archives = vec![];
+        find_archives(&root, &rest, &mut vec![], &mut archives)?;
+        Ok(Some(archives.len()))
+    }
+}
+
+/// A builder for the `DateRoller`.
+#[derive(Clone, Debug, Default)]
+pub struct DateRollerBuilder {
+    count: Option<u32>,
+    overflow: Option<u32>,
+    max_files_per_dir: Option<(usize, Shard)>,
+    compressor: Option<Arc<dyn Compressor>>,
+    encryptor: Option<Arc<dyn Encryptor>>,
+    manifest: bool,
+    permissions: Permissions,
+    directories: Directories,
+    archive_dir: Option<String>,
+}
+
+impl DateRollerBuilder {
+    /// Sets the maximum number of archived logs to maintain.
+    ///
+    /// Defaults to keeping every archive.
+    pub fn count(mut self, count: u32) -> DateRollerBuilder {
+        self.count = Some(count);
+        self
+    }
+
+    /// Sets the maximum number of archives which may share a name, such as
+    /// the archives of one day with a daily pattern.
+    ///
+    /// When the name of a new archive is taken, it is given the lowest free
+    /// suffix from `.2` up to `.limit`, inserted before the file extension
+    /// (and before a compression extension such as `.gz`). Rolling fails once
+    /// every suffix is taken.
+    ///
+    /// Defaults to failing as soon as the name is taken.
+    pub fn overflow(mut self, limit: u32) -> DateRollerBuilder {
+        self.overflow = Some(limit);
+        self
+    }
+
+    /// Sets the maximum number of entries in an archive directory, beyond
+    /// which archives are placed in subdirectories chosen by `shard`.
+    ///
+    /// Defaults to no limit.
+    pub fn max_files_per_dir(mut self, max: usize, shard: Shard) -> DateRollerBuilder {
+        self.max_files_per_dir = Some((max, shard));
+        self
+    }
+
+    /// Sets the compressor applied to archived log files.
+    ///
+    /// Defaults to choosing the compression from the file extension of the
+    /// pattern.
+    pub fn compressor(mut self, compressor: Box<dyn Compressor>) -> DateRollerBuilder {
+        self.compressor = Some(Arc::from(compressor));
+        self
+    }
+
+    /// Sets the encryptor applied to archived log files, after they are
+    /// compressed.
+    ///
+    /// With an encryptor set, an `.age` extension of the pattern is skipped
+    /// when choosing the compression from its file extension, so a pattern
+    /// ending in `.log.gz.age` is gzip-compressed and then encrypted.
+    ///
+    /// Defaults to leaving archives unencrypted.
+    pub fn encryptor(mut self, encryptor: Box<dyn Encryptor>) -> DateRollerBuilder {
+        self.encryptor = Some(Arc::from(encryptor));
+        self
+    }
+
+    /// Determines if each archive is recorded in `manifest.jsonl` in its
+    /// directory, as a line of JSON holding its file name, size, SHA-256
+    /// digest and the time it was archived.
+    ///
+    /// Requires the `archive_manifest` feature. Defaults to `false`.
+    pub fn manifest(mut self, manifest: bool) -> DateRollerBuilder {
+        self.manifest = manifest;
+        self
+    }
+
+    /// Sets the permissions of archives, such as `0o640`, regardless of the
+    /// process umask.
+    ///
+    /// Only applies on Unix. Defaults to the permissions of the log file for
+    /// archives moved into place, and those allowed by the umask for archives
+    /// which are compressed or encrypted.
+    pub fn mode(mut self, mode: u32) -> DateRollerBuilder {
+        self.permissions.mode = Some(mode);
+        self
+    }
+
+    /// Sets the user owning archives, as a name or a numeric ID.
+    ///
+    /// Only applies on Unix, and changing the owner usually requires root.
+    pub fn owner(mut self, owner: &str) -> DateRollerBuilder {
+        self.permissions.owner = Some(owner.to_owned());
+        self
+    }
+
+    /// Sets the group owning archives, as a name or a numeric ID.
+    ///
+    /// Only applies on Unix. The process must belong to the group unless it
+    /// runs as root.
+    pub fn group(mut self, group: &str) -> DateRollerBuilder {
+        self.permissions.group = Some(group.to_owned());
+        self
+    }
+
+    /// Determines if missing directories leading to archives, such as the
+    /// date directories of the pattern, are created when the log is rolled.
+    ///
+    /// Defaults to `true`.
+    pub fn create_dirs(mut self, create_dirs: bool) -> DateRollerBuilder {
+        self.directories.create = create_dirs;
+        self
+    }
+
+    /// Sets the most directories leading to an archive the roller creates,
+    /// counting shard subdirectories separately.
+    ///
+    /// Defaults to no limit.
+    pub fn max_dir_depth(mut self, max_dir_depth: usize) -> DateRollerBuilder {
+        self.directories.max_depth = Some(max_dir_depth);
+        self
+    }
+
+    /// Sets the directory a relative pattern is resolved against, so that
+    /// archives can be kept apart from the live log, for example on cheaper
+    /// storage.
+    ///
+    /// Archives are moved there by a copy and delete if it is on another
+    /// filesystem. Defaults to the `cwd` of your application.
+    pub fn archive_dir(mut self, archive_dir: &str) -> DateRollerBuilder {
+        self.archive_dir = Some(archive_dir.to_owned());
+        self
+    }
+
+    /// Constructs a new `DateRoller`.
+    ///
+    /// `pattern` is either an absolute path or lacking a leading `/`, relative
+    /// to the archive directory, or else the `cwd` of your application. It is
+    /// formatted with the time of the roll using `strftime` style specifiers such as `%Y`, `%m` and `%d`.
+    /// It may contain environment variables and the `{hostname}` and `{pid}`
+    /// placeholders, as the path of a file appender may.
+    ///
+    /// If no compressor is set, the file extension of the pattern is `.gz` and
+    /// the `gzip` Cargo feature is enabled, the archive files will be
+    /// gzip-compressed. If the extension is `.gz` and the `gzip` feature is
+    /// *not* enabled, an error will be returned.
+    pub fn build(self, pattern: &str) -> anyhow::Result<DateRoller> {
+        // `%` in the directory must not be taken for a date specifier
+        let archive_dir = self.archive_dir.as_ref().map(|dir| dir.replace('%', "%%"));
+        let pattern = &*archive_pattern(pattern, archive_dir.as_deref());
+        if StrftimeItems::new(pattern).any(|item| item == Item::Error) {
+            bail!("invalid date format in pattern `{}`", pattern);
+        }
+        check_manifest(self.manifest)?;
+
+        Ok(DateRoller {
+            pattern: pattern.to_owned(),
+            compression: Compression::new(pattern, self.compressor, self.encryptor)?
+                .permissions(self.permissions),
+            count: self.count,
+            overflow: self.overflow,
+            max_files_per_dir: self.max_files_per_dir,
+            manifest: self.manifest,
+            directories: self.directories,
+        })
+    }
+}
+
+/// A deserializer for the `DateRoller`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: date
+///
+/// # The filename pattern for archived logs. This is either an absolute path
+/// # or if lacking a leading `/`, relative to the archive directory, or else
+/// # the `cwd` of your application. The pattern is formatted with the time of
+/// # the roll using `strftime` style specifiers, and date directories are
+/// # created as needed. Environment variables and `{hostname}` and `{pid}` are
+/// # replaced as in the path of the rolling file appender.
+/// # If the file extension of the pattern is `.gz` and the `gzip` Cargo feature
+/// # is enabled, the archive files will be gzip-compressed.
+/// # Required.
+/// pattern: archive/%Y/%m/%d/foo.%H%M%S.log
+///
+/// # The maximum number of archived logs to maintain. Empty date directories are removed along with
+/// # the oldest archives. Defaults to keeping every archive.
+/// count: 30
+///
+/// # The maximum number of archives which may share a name, such as the archives of one day with
+/// # a daily pattern. Later archives with a taken name get the suffix `.2`, `.3` and so on before
+/// # their extension, e.g. `app.2024-05-01.2.log.gz`. By default rolling fails if the name is taken.
+/// overflow: 10
+///
+/// # The maximum number of entries in an archive directory. Once reached, new archives are placed in
+/// # a subdirectory of it chosen by `shard`. Defaults to no limit.
+/// max_files_per_dir: 10000
+///
+/// # How archives are sharded: `hash` uses up to 256 subdirectories named by a hash of the file name,
+/// # `date` uses a subdirectory per day named `YYYY-MM-DD`. Defaults to `hash`.
+/// shard: hash
+///
+/// # The compressor applied to archived logs. By default, it is chosen from the
+/// # file extension of the pattern.
+/// compressor:
+///   kind: gzip
+///   level: 9
+///
+/// # The encryptor applied to archived logs after they are compressed. When set, an `.age`
+/// # extension of the pattern is skipped when choosing the compression. Defaults to none.
+/// encrypt:
+///   kind: age
+///   recipient: age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p
+///
+/// # Specifies if each archive is recorded in `manifest.jsonl` in its directory, as a line of JSON
+/// # holding its file name, size, SHA-256 digest and the time it was archived. Requires the
+/// # `archive_manifest` Cargo feature. Defaults to `false`.
+/// manifest: true
+///
+/// # The permissions of archives, written in octal, and the user and group owning them, as names or
+/// # numeric IDs. Only applied on Unix. By default archives keep the permissions of the log file,
+/// # or are created as the process umask allows when compressed or encrypted.
+/// mode: "0640"
+/// owner: app
+/// group: adm
+///
+/// # Specifies if missing directories leading to archives, such as date directories, are created,
+/// # and the most that are created for an archive. Defaults to `true` and no limit.
+/// create_dirs: true
+/// max_dir_depth: 3
+///
+/// # The directory a relative pattern is resolved against, to keep archives
+/// # apart from the live log. Archives are copied and deleted if it is on
+/// # another filesystem. Defaults to the `cwd` of your application.
+/// archive_dir: /mnt/archive/app
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct DateRollerDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for DateRollerDeserializer {
+    type Trait = dyn Roll;
+
+    type Config = DateRollerConfig;
+
+    fn deserialize(
+        &self,
+        config: DateRollerConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Roll>> {
+        let mut builder = DateRoller::builder();
+        if let Some(count) = config.count {
+            builder = builder.count(count);
+        }
+        if let Some(overflow) = config.overflow {
+            builder = builder.overflow(overflow);
+        }
+        if let Some(max) = config.max_files_per_dir {
+            builder = builder.max_files_per_dir(max, config.shard);
+        }
+        if let Some(compressor) = config.compressor {
+            let compressor = deserializers.deserialize(&compressor.kind, compressor.config)?;
+            builder = builder.compressor(compressor);
+        }
+        if let Some(encryptor) = config.encrypt {
+            let encryptor = deserializers.deserialize(&encryptor.kind, encryptor.config)?;
+            builder = builder.encryptor(encryptor);
+        }
+        if let Some(manifest) = config.manifest {
+            builder = builder.manifest(manifest);
+        }
+        if let Some(mode) = config.mode {
+            builder = builder.mode(mode);
+        }
+        if let Some(owner) = config.owner {
+            builder = builder.owner(&owner);
+        }
+        if let Some(group) = config.group {
+            builder = builder.group(&group);
+        }
+        if let Some(create_dirs) = config.create_dirs {
+            builder = builder.create_dirs(create_dirs);
+        }
+        if let Some(max_dir_depth) = config.max_dir_depth {
+            builder = builder.max_dir_depth(max_dir_depth);
+        }
+        if let Some(archive_dir) = config.archive_dir {
+            builder = builder.archive_dir(&archive_dir);
+        }
+
+        Ok(Box::new(builder.build(&config.pattern)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+    use std::{fs::File, io::Write};
+
+    use super::*;
+
+    fn roll(roller: &DateRoller, file: &Path, contents: &[u8], time: DateTime<Local>) {
+        File::create(file).unwrap().write_all(contents).unwrap();
+        roller.roll_at(file, time).unwrap();
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn date_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let roller = DateRoller::builder()
+            .build(&format!(
+                "{}/archive/%Y/%m/%d/foo.%H%M.log",
+                dir.path().display()
+            ))
+            .unwrap();
+        let file = dir.path().join("foo.log");
+
+        let time = Local.with_ymd_and_hms(2024, 5, 1, 14, 30, 0).unwrap();
+        roll(&roller, &file, b"file1", time);
+        let archive = dir.path().join("archive/2024/05/01/foo.1430.log");
+        assert_eq!(fs::read(&archive).unwrap(), b"file1");
+
+        File::create(&file).unwrap();
+        assert!(roller.roll_at(&file, time).is_err());
+        assert_eq!(fs::read(&archive).unwrap(), b"file1");
+    }
+
+    #[test]
+    fn archive_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("100%");
+        let roller = DateRoller::builder()
+            .archive_dir(archive.to_str().unwrap())
+            .build("%Y/foo.%H%M.log")
+            .unwrap();
+        let file = dir.path().join("foo.log");
+
+        let time = Local.with_ymd_and_hms(2024, 5, 1, 14, 30, 0).unwrap();
+        roll(&roller, &file, b"file1", time);
+        assert_eq!(
+            fs::read(archive.join("2024/foo.1430.log")).unwrap(),
+            b"file1"
+        );
+    }
+
+    #[test]
+    fn max_dir_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let roller = DateRoller::builder()
+            .max_dir_depth(1)
+            .build(&format!(
+                "{}/archive/%Y/%m/foo.%d.log",
+                dir.path().display()
+            ))
+            .unwrap();
+        let file = dir.path().join("foo.log");
+
+        // archive/2024/05 is more than one directory deep
+        File::create(&file).unwrap();
+        let time = Local.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        assert!(roller.roll_at(&file, time).is_err());
+        assert!(!dir.path().join("archive").exists());
+
+        // once the year exists, each month only needs its own directory
+        fs::create_dir_all(dir.path().join("archive/2024")).unwrap();
+        roll(&roller, &file, b"file1", time);
+        let time = Local.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        roll(&roller, &file, b"file2", time);
+        assert_eq!(
+            fs::read(dir.path().join("archive/2024/06/foo.01.log")).unwrap(),
+            b"file2"
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let dir = tempfile::tempdir().unwrap();
+        let roller = DateRoller::builder()
+            .count(3)
+            .overflow(3)
+            .build(&format!("{}/app.%Y-%m-%d.log", dir.path().display()))
+            .unwrap();
+        let file = dir.path().join("app.log");
+
+        let time = Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        for contents in [b"one", b"two", b"thr"] {
+            roll(&roller, &file, contents, time);
+        }
+        assert_eq!(
+            fs::read(dir.path().join("app.2024-05-01.log")).unwrap(),
+            b"one"
+        );
+        assert_eq!(
+            fs::read(dir.path().join("app.2024-05-01.2.log")).unwrap(),
+            b"two"
+        );
+        assert_eq!(
+            fs::read(dir.path().join("app.2024-05-01.3.log")).unwrap(),
+            b"thr"
+        );
+        assert_eq!(roller.archives(&file).unwrap(), Some(3));
+
+        File::create(&file).unwrap();
+        assert!(roller.roll_at(&file, time).is_err());
+
+        assert_eq!(
+            with_suffix(Path::new("a/app.2024-05-01.log.gz"), 2),
+            Path::new("a/app.2024-05-01.2.log.gz")
+        );
+        assert_eq!(
+            with_suffix(Path::new("app.2024-05-01.log.gz.age"), 2),
+            Path::new("app.2024-05-01.2.log.gz.age")
+        );
+        assert_eq!(with_suffix(Path::new("app"), 4), Path::new("app.4"));
+    }
+
+    #[test]
+    fn retention_prunes_empty_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let roller = DateRoller::builder()
+            .count(2)
+            .build(&format!(
+                "{}/archive/%Y/%m/%d/foo.log",
+                dir.path().display()
+            ))
+            .unwrap();
+        let file = dir.path().join("foo.log");
+
+        for day in 1..=3 {
+            let time = Local.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap();
+            roll(&roller, &file, b"file", time);
+        }
+
+        let archive = dir.path().join("archive/2024/05");
+        assert!(!archive.join("01").exists());
+        assert!(archive.join("02/foo.log").exists());
+        assert!(archive.join("03/foo.log").exists());
+
+        // unrelated files are left alone
+        fs::write(archive.join("notes.txt"), b"").unwrap();
+        let time = Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        roll(&roller, &file, b"file", time);
+        assert!(!archive.join("02").exists());
+        assert!(archive.join("notes.txt").exists());
+        assert!(dir.path().join("archive/2024/06/01/foo.log").exists());
+    }
+
+    #[test]
+    fn clean_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let roller = DateRoller::builder()
+            .build(&format!(
+                "{}/archive/%Y/%m/%d/foo.%H%M.log",
+                dir.path().display()
+            ))
+            .unwrap();
+        let file = dir.path().join("foo.log");
+
+        let day = dir.path().join("archive/2024/05/01");
+        fs::create_dir_all(day.join("3f")).unwrap();
+        for name in &[
+            "foo.1430.log",
+            "foo.1430.log.partial",
+            "3f/foo.1500.log.partial",
+            "notes.txt.partial",
+        ] {
+            File::create(day.join(name)).unwrap();
+        }
+
+        roller.clean_up(&file).unwrap();
+
+        assert!(day.join("foo.1430.log").exists());
+        assert!(!day.join("foo.1430.log.partial").exists());
+        assert!(!day.join("3f/foo.1500.log.partial").exists());
+        assert!(day.join("notes.txt.partial").exists());
+    }
+
+    #[test]
+    fn shards_full_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let roller = DateRoller::builder()
+            .count(3)
+            .max_files_per_dir(2, Shard::Date)
+            .build(&format!("{}/archive/foo.%d%H.log", dir.path().display()))
+            .unwrap();
+        let file = dir.path().join("foo.log");
+
+        for hour in 1..=3 {
+            let time = Local.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap();
+            roll(&roller, &file, b"file", time);
+        }
+        let archive = dir.path().join("archive");
+        assert!(archive.join("foo.0101.log").exists());
+        assert!(archive.join("foo.0102.log").exists());
+        assert!(archive.join("2024-05-01/foo.0103.log").exists());
+
+        // retention sees sharded archives as well
+        let time = Local.with_ymd_and_hms(2024, 5, 1, 4, 0, 0).unwrap();
+        roll(&roller, &file, b"file", time);
+        assert!(!archive.join("foo.0101.log").exists());
+        assert!(archive.join("2024-05-01/foo.0104.log").exists());
+    }
+}
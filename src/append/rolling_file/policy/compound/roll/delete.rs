@@ -25,6 +25,10 @@ impl Roll for DeleteRoller {
     fn roll(&self, file: &Path) -> anyhow::Result<()> {
         fs::remove_file(file).map_err(Into::into)
     }
+
+    fn archives(&self, _: &Path) -> anyhow::Result<Option<usize>> {
+        Ok(Some(0))
+    }
 }
 
 impl DeleteRoller {
@@ -5,17 +5,30 @@
 use anyhow::bail;
 #[cfg(feature = "background_rotation")]
 use parking_lot::{Condvar, Mutex};
-#[cfg(feature = "background_rotation")]
-use std::sync::Arc;
 use std::{
+    borrow::Cow,
     fs, io,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
-use crate::append::env_util::expand_env_vars;
-use crate::append::rolling_file::policy::compound::roll::Roll;
+use crate::append::dir::Directories;
+use crate::append::env_util::expand_path;
+use crate::append::perm::Permissions;
+use crate::append::rolling_file::policy::compound::roll::{
+    archive_pattern, check_manifest, move_file, partial_file_name, record_in_manifest,
+    remove_if_exists, Compression, Roll,
+};
+#[cfg(feature = "background_rotation")]
+use crate::background;
+use crate::compress::Compressor;
+#[cfg(feature = "config_parsing")]
+use crate::compress::CompressorConfig;
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
+use crate::encrypt::Encryptor;
+#[cfg(feature = "config_parsing")]
+use crate::encrypt::EncryptorConfig;
 
 /// Configuration for the fixed window roller.
 #[cfg(feature = "config_parsing")]
@@ -25,38 +38,18 @@ pub struct FixedWindowRollerConfig {
     pattern: String,
     base: Option<u32>,
     count: u32,
-}
-
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
-enum Compression {
-    None,
-    #[cfg(feature = "gzip")]
-    Gzip,
-}
-
-impl Compression {
-    fn compress(&self, src: &Path, dst: &str) -> io::Result<()> {
-        match *self {
-            Compression::None => move_file(src, dst),
-            #[cfg(feature = "gzip")]
-            Compression::Gzip => {
-                #[cfg(feature = "flate2")]
-                use flate2::write::GzEncoder;
-                use std::fs::File;
-
-                let mut i = File::open(src)?;
-
-                let o = File::create(dst)?;
-                let mut o = GzEncoder::new(o, flate2::Compression::default());
-
-                io::copy(&mut i, &mut o)?;
-                drop(o.finish()?);
-                drop(i); // needs to happen before remove_file call on Windows
-
-                fs::remove_file(src)
-            }
-        }
-    }
+    compressor: Option<CompressorConfig>,
+    encrypt: Option<EncryptorConfig>,
+    manifest: Option<bool>,
+    #[serde(deserialize_with = "crate::append::perm::de_mode", default)]
+    mode: Option<u32>,
+    #[serde(deserialize_with = "crate::append::perm::de_id", default)]
+    owner: Option<String>,
+    #[serde(deserialize_with = "crate::append::perm::de_id", default)]
+    group: Option<String>,
+    create_dirs: Option<bool>,
+    max_dir_depth: Option<usize>,
+    archive_dir: Option<String>,
 }
 
 /// A roller which maintains a fixed window of archived log files.
@@ -76,26 +69,55 @@ impl Compression {
 /// `archive/foo.1.log`, and the new log file will be renamed to
 /// `archive/foo.0.log`.
 ///
-/// If the file extension of the pattern is `.gz` and the `gzip` Cargo feature
-/// is enabled, the archive files will be gzip-compressed.
+/// Archive files are compressed with the roller's `Compressor` if one is set.
+/// Otherwise, if the file extension of the pattern is `.gz` and the `gzip`
+/// Cargo feature is enabled, the archive files will be gzip-compressed.
+/// Archive files are then encrypted with the roller's `Encryptor` if one is
+/// set.
 ///
 /// Note that this roller will have to rename every archived file every time the
 /// log rolls over. Performance may be negatively impacted by specifying a large
 /// count.
 #[derive(Clone, Debug)]
 pub struct FixedWindowRoller {
-    pattern: String,
+    pattern: Pattern,
     compression: Compression,
     base: u32,
     count: u32,
+    manifest: bool,
+    directories: Directories,
     #[cfg(feature = "background_rotation")]
     cond_pair: Arc<(Mutex<bool>, Condvar)>,
 }
 
+/// An archive filename pattern resolved against the archive directory, whose
+/// `{}` are only replaced with the index after the directory.
+#[derive(Clone, Debug)]
+struct Pattern {
+    pattern: String,
+    dir_len: usize,
+}
+
+impl Pattern {
+    fn new(pattern: &str, archive_dir: Option<&str>) -> Pattern {
+        let resolved = archive_pattern(pattern, archive_dir);
+        Pattern {
+            dir_len: resolved.len() - pattern.len(),
+            pattern: resolved,
+        }
+    }
+
+    /// Returns the path of the archive with index `i`.
+    fn path(&self, i: u32) -> Cow<'static, str> {
+        let (dir, pattern) = self.pattern.split_at(self.dir_len);
+        expand_path(format!("{}{}", dir, pattern.replace("{}", &i.to_string())))
+    }
+}
+
 impl FixedWindowRoller {
     /// Returns a new builder for the `FixedWindowRoller`.
     pub fn builder() -> FixedWindowRollerBuilder {
-        FixedWindowRollerBuilder { base: 0 }
+        FixedWindowRollerBuilder::default()
     }
 }
 
@@ -108,9 +130,11 @@ impl Roll for FixedWindowRoller {
 
         rotate(
             self.pattern.clone(),
-            self.compression,
+            self.compression.clone(),
             self.base,
             self.count,
+            self.manifest,
+            self.directories,
             file.to_path_buf(),
         )?;
 
@@ -137,41 +161,75 @@ impl Roll for FixedWindowRoller {
         drop(ready);
 
         let pattern = self.pattern.clone();
-        let compression = self.compression;
+        let compression = self.compression.clone();
         let base = self.base;
         let count = self.count;
+        let manifest = self.manifest;
+        let directories = self.directories;
         let cond_pair = self.cond_pair.clone();
-        // rotate in the separate thread
-        std::thread::spawn(move || {
+        // rotate in the background
+        let spawned = background::spawn("log4rs rotation", move || {
             let (lock, cvar) = &*cond_pair;
             let mut ready = lock.lock();
 
-            if let Err(e) = rotate(pattern, compression, base, count, temp) {
+            if let Err(e) = rotate(
+                pattern,
+                compression,
+                base,
+                count,
+                manifest,
+                directories,
+                temp,
+            ) {
                 use std::io::Write;
                 let _ = writeln!(io::stderr(), "log4rs, error rotating: {}", e);
             }
             *ready = true;
             cvar.notify_one();
         });
+        if let Err(e) = spawned {
+            *lock.lock() = true;
+            cvar.notify_one();
+            return Err(e.into());
+        }
 
         Ok(())
     }
-}
 
-fn move_file<P, Q>(src: P, dst: Q) -> io::Result<()>
-where
-    P: AsRef<Path>,
-    Q: AsRef<Path>,
-{
-    // first try a rename
-    match fs::rename(src.as_ref(), dst.as_ref()) {
-        Ok(()) => return Ok(()),
-        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
-        Err(_) => {}
+    fn clean_up(&self, file: &Path) -> anyhow::Result<()> {
+        for i in self.base..self.base + self.count {
+            let dst = self.pattern.path(i);
+            remove_if_exists(Path::new(&partial_file_name(&dst)))?;
+        }
+        // Log files moved aside for background rotation hold records which
+        // were never archived, so finish rotating them, oldest first.
+        for temp in temp_files(file)? {
+            if self.count == 0 {
+                fs::remove_file(&temp)?;
+                continue;
+            }
+            rotate(
+                self.pattern.clone(),
+                self.compression.clone(),
+                self.base,
+                self.count,
+                self.manifest,
+                self.directories,
+                temp,
+            )?;
+        }
+        Ok(())
     }
 
-    // fall back to a copy and delete if src and dst are on different mounts
-    fs::copy(src.as_ref(), dst.as_ref()).and_then(|_| fs::remove_file(src.as_ref()))
+    fn archives(&self, _: &Path) -> anyhow::Result<Option<usize>> {
+        let archives = (self.base..self.base + self.count)
+            .filter(|i| {
+                let dst = self.pattern.path(*i);
+                Path::new(&*dst).exists()
+            })
+            .count();
+        Ok(Some(archives))
+    }
 }
 
 #[cfg(feature = "background_rotation")]
@@ -183,47 +241,83 @@ where
         .duration_since(std::time::SystemTime::UNIX_EPOCH)
         .unwrap_or_else(|_| std::time::Duration::from_secs(0))
         .as_secs();
-    let mut temp = file.as_ref().to_path_buf();
-    temp.set_extension(format!("{}", n));
+    let temp_name = |n| {
+        let mut name = file.as_ref().as_os_str().to_owned();
+        name.push(format!(".{}.tmp", n));
+        PathBuf::from(name)
+    };
+    let mut temp = temp_name(n);
     while temp.exists() {
         n += 1;
-        temp.set_extension(format!("{}", n));
+        temp = temp_name(n);
     }
     temp
 }
 
+/// Returns the log files moved aside for background rotation which were
+/// never rotated into place, oldest first.
+fn temp_files(file: &Path) -> io::Result<Vec<PathBuf>> {
+    let name = match file.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return Ok(vec![]),
+    };
+    let dir = match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut temps = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let entry_name = entry.file_name();
+        let n = entry_name
+            .to_string_lossy()
+            .strip_prefix(&*name)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .and_then(|rest| rest.strip_suffix(".tmp"))
+            .filter(|n| n.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(n) = n {
+            if entry.file_type()?.is_file() {
+                temps.push((n, entry.path()));
+            }
+        }
+    }
+    // The temporary files are numbered by the time they were moved aside.
+    temps.sort();
+    Ok(temps.into_iter().map(|(_, path)| path).collect())
+}
+
 // TODO(eas): compress to tmp file then move into place once prev task is done
 fn rotate(
-    pattern: String,
+    pattern: Pattern,
     compression: Compression,
     base: u32,
     count: u32,
+    manifest: bool,
+    directories: Directories,
     file: PathBuf,
-) -> io::Result<()> {
-    let dst_0 = expand_env_vars(pattern.replace("{}", &base.to_string()));
+) -> anyhow::Result<()> {
+    let dst_0 = pattern.path(base);
 
-    if let Some(parent) = Path::new(dst_0.as_ref()).parent() {
-        fs::create_dir_all(parent)?;
-    }
+    directories.create_for(Path::new(dst_0.as_ref()))?;
 
     // In the common case, all of the archived files will be in the same
     // directory, so avoid extra filesystem calls in that case.
     let parent_varies = match (
         Path::new(dst_0.as_ref()).parent(),
-        Path::new(expand_env_vars(&pattern).as_ref()).parent(),
+        Path::new(expand_path(&*pattern.pattern).as_ref()).parent(),
     ) {
         (Some(a), Some(b)) => a != b,
         _ => false, // Only case that can actually happen is (None, None)
     };
 
     for i in (base..base + count - 1).rev() {
-        let src = expand_env_vars(pattern.replace("{}", &i.to_string()));
-        let dst = expand_env_vars(pattern.replace("{}", &(i + 1).to_string()));
+        let src = pattern.path(i);
+        let dst = pattern.path(i + 1);
 
         if parent_varies {
-            if let Some(parent) = Path::new(dst.as_ref()).parent() {
-                fs::create_dir_all(parent)?;
-            }
+            directories.create_for(Path::new(dst.as_ref()))?;
         }
 
         move_file(src.as_ref(), dst.as_ref())?;
@@ -233,13 +327,22 @@ fn rotate(
         println!("err compressing: {:?}, dst: {:?}", file, dst_0);
         e
     })?;
+    if manifest {
+        record_in_manifest(Path::new(dst_0.as_ref()))?;
+    }
     Ok(())
 }
 
 /// A builder for the `FixedWindowRoller`.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct FixedWindowRollerBuilder {
     base: u32,
+    compressor: Option<Arc<dyn Compressor>>,
+    encryptor: Option<Arc<dyn Encryptor>>,
+    manifest: bool,
+    permissions: Permissions,
+    directories: Directories,
+    archive_dir: Option<String>,
 }
 
 impl FixedWindowRollerBuilder {
@@ -251,15 +354,111 @@ impl FixedWindowRollerBuilder {
         self
     }
 
+    /// Sets the compressor applied to archived log files.
+    ///
+    /// Defaults to choosing the compression from the file extension of the
+    /// pattern.
+    pub fn compressor(mut self, compressor: Box<dyn Compressor>) -> FixedWindowRollerBuilder {
+        self.compressor = Some(Arc::from(compressor));
+        self
+    }
+
+    /// Sets the encryptor applied to archived log files, after they are
+    /// compressed.
+    ///
+    /// With an encryptor set, an `.age` extension of the pattern is skipped
+    /// when choosing the compression from its file extension, so a pattern
+    /// ending in `.log.gz.age` is gzip-compressed and then encrypted.
+    ///
+    /// Defaults to leaving archives unencrypted.
+    pub fn encryptor(mut self, encryptor: Box<dyn Encryptor>) -> FixedWindowRollerBuilder {
+        self.encryptor = Some(Arc::from(encryptor));
+        self
+    }
+
+    /// Determines if each archive is recorded in `manifest.jsonl` in its
+    /// directory, as a line of JSON holding its file name, size, SHA-256
+    /// digest and the time it was archived.
+    ///
+    /// The name recorded is the one the archive is first written under;
+    /// later rolls rename it to higher indices.
+    ///
+    /// Requires the `archive_manifest` feature. Defaults to `false`.
+    pub fn manifest(mut self, manifest: bool) -> FixedWindowRollerBuilder {
+        self.manifest = manifest;
+        self
+    }
+
+    /// Sets the permissions of archives, such as `0o640`, regardless of the
+    /// process umask.
+    ///
+    /// Only applies on Unix. Defaults to the permissions of the log file for
+    /// archives moved into place, and those allowed by the umask for archives
+    /// which are compressed or encrypted.
+    pub fn mode(mut self, mode: u32) -> FixedWindowRollerBuilder {
+        self.permissions.mode = Some(mode);
+        self
+    }
+
+    /// Sets the user owning archives, as a name or a numeric ID.
+    ///
+    /// Only applies on Unix, and changing the owner usually requires root.
+    pub fn owner(mut self, owner: &str) -> FixedWindowRollerBuilder {
+        self.permissions.owner = Some(owner.to_owned());
+        self
+    }
+
+    /// Sets the group owning archives, as a name or a numeric ID.
+    ///
+    /// Only applies on Unix. The process must belong to the group unless it
+    /// runs as root.
+    pub fn group(mut self, group: &str) -> FixedWindowRollerBuilder {
+        self.permissions.group = Some(group.to_owned());
+        self
+    }
+
+    /// Determines if missing directories leading to archives are created when
+    /// the log is rolled.
+    ///
+    /// Defaults to `true`.
+    pub fn create_dirs(mut self, create_dirs: bool) -> FixedWindowRollerBuilder {
+        self.directories.create = create_dirs;
+        self
+    }
+
+    /// Sets the most directories leading to an archive the roller creates.
+    ///
+    /// Defaults to no limit.
+    pub fn max_dir_depth(mut self, max_dir_depth: usize) -> FixedWindowRollerBuilder {
+        self.directories.max_depth = Some(max_dir_depth);
+        self
+    }
+
+    /// Sets the directory a relative pattern is resolved against, so that
+    /// archives can be kept apart from the live log, for example on cheaper
+    /// storage.
+    ///
+    /// Archives are moved there by a copy and delete if it is on another
+    /// filesystem. Unlike in the pattern, `{}` in the directory is kept as it
+    /// is. Defaults to the `cwd` of your application.
+    pub fn archive_dir(mut self, archive_dir: &str) -> FixedWindowRollerBuilder {
+        self.archive_dir = Some(archive_dir.to_owned());
+        self
+    }
+
     /// Constructs a new `FixedWindowRoller`.
     ///
     /// `pattern` is either an absolute path or lacking a leading `/`, relative
-    /// to the `cwd` of your application. The pattern must contain at least one
+    /// to the archive directory, or else the `cwd` of your application. The
+    /// pattern must contain at least one
     /// instance of `{}`, all of which will be replaced with an archived log file's index.
+    /// It may contain environment variables and the `{hostname}` and `{pid}`
+    /// placeholders, as the path of a file appender may.
     ///
-    /// If the file extension of the pattern is `.gz` and the `gzip` Cargo
-    /// feature is enabled, the archive files will be gzip-compressed.
-    /// If the extension is `.gz` and the `gzip` feature is *not* enabled, an error will be returned.
+    /// If no compressor is set, the file extension of the pattern is `.gz` and
+    /// the `gzip` Cargo feature is enabled, the archive files will be
+    /// gzip-compressed. If the extension is `.gz` and the `gzip` feature is
+    /// *not* enabled, an error will be returned.
     ///
     /// `count` is the maximum number of archived logs to maintain.
     pub fn build(self, pattern: &str, count: u32) -> anyhow::Result<FixedWindowRoller> {
@@ -269,21 +468,18 @@ impl FixedWindowRollerBuilder {
             bail!(msg);
         }
 
-        let compression = match Path::new(pattern).extension() {
-            #[cfg(feature = "gzip")]
-            Some(e) if e == "gz" => Compression::Gzip,
-            #[cfg(not(feature = "gzip"))]
-            Some(e) if e == "gz" => {
-                bail!("gzip compression requires the `gzip` feature");
-            }
-            _ => Compression::None,
-        };
+        let pattern = Pattern::new(pattern, self.archive_dir.as_deref());
+        let compression = Compression::new(&pattern.pattern, self.compressor, self.encryptor)?
+            .permissions(self.permissions);
+        check_manifest(self.manifest)?;
 
         Ok(FixedWindowRoller {
-            pattern: pattern.to_owned(),
+            pattern,
             compression,
             base: self.base,
             count,
+            manifest: self.manifest,
+            directories: self.directories,
             #[cfg(feature = "background_rotation")]
             cond_pair: Arc::new((Mutex::new(true), Condvar::new())),
         })
@@ -297,9 +493,12 @@ impl FixedWindowRollerBuilder {
 /// ```yaml
 /// kind: fixed_window
 ///
-/// # The filename pattern for archived logs. This is either an absolute path or if lacking a leading `/`,
-/// # relative to the `cwd` of your application. The pattern must contain at least one
-/// # instance of `{}`, all of which will be replaced with an archived log file's index.
+/// # The filename pattern for archived logs. This is either an absolute path
+/// # or if lacking a leading `/`, relative to the archive directory, or else
+/// # the `cwd` of your application. The pattern must contain at least one
+/// # instance of `{}`, all of which will be replaced with an archived log
+/// # file's index. Environment variables and `{hostname}` and `{pid}` are
+/// # replaced as in the path of the rolling file appender.
 /// # If the file extension of the pattern is `.gz` and the `gzip` Cargo feature
 /// # is enabled, the archive files will be gzip-compressed.
 /// # Required.
@@ -310,6 +509,41 @@ impl FixedWindowRollerBuilder {
 ///
 /// # The base value for archived log indices. Defaults to 0.
 /// base: 1
+///
+/// # The compressor applied to archived logs. By default, it is chosen from the
+/// # file extension of the pattern.
+/// compressor:
+///   kind: gzip
+///   level: 9
+///
+/// # The encryptor applied to archived logs after they are compressed. When set, an `.age`
+/// # extension of the pattern is skipped when choosing the compression. Defaults to none.
+/// encrypt:
+///   kind: age
+///   recipient: age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p
+///
+/// # Specifies if each archive is recorded in `manifest.jsonl` in its directory, as a line of JSON
+/// # holding its file name, size, SHA-256 digest and the time it was archived. Requires the
+/// # `archive_manifest` Cargo feature. Defaults to `false`.
+/// manifest: true
+///
+/// # The permissions of archives, written in octal, and the user and group owning them, as names or
+/// # numeric IDs. Only applied on Unix. By default archives keep the permissions of the log file,
+/// # or are created as the process umask allows when compressed or encrypted.
+/// mode: "0640"
+/// owner: app
+/// group: adm
+///
+/// # Specifies if missing directories leading to archives are created, and the most that are
+/// # created for an archive. Defaults to `true` and no limit.
+/// create_dirs: true
+/// max_dir_depth: 2
+///
+/// # The directory a relative pattern is resolved against, to keep archives
+/// # apart from the live log. Archives are copied and deleted if it is on
+/// # another filesystem. Any `{}` in it is kept as it is. Defaults to the
+/// # `cwd` of your application.
+/// archive_dir: /mnt/archive/app
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -324,12 +558,41 @@ impl Deserialize for FixedWindowRollerDeserializer {
     fn deserialize(
         &self,
         config: FixedWindowRollerConfig,
-        _: &Deserializers,
+        deserializers: &Deserializers,
     ) -> anyhow::Result<Box<dyn Roll>> {
         let mut builder = FixedWindowRoller::builder();
         if let Some(base) = config.base {
             builder = builder.base(base);
         }
+        if let Some(compressor) = config.compressor {
+            let compressor = deserializers.deserialize(&compressor.kind, compressor.config)?;
+            builder = builder.compressor(compressor);
+        }
+        if let Some(encryptor) = config.encrypt {
+            let encryptor = deserializers.deserialize(&encryptor.kind, encryptor.config)?;
+            builder = builder.encryptor(encryptor);
+        }
+        if let Some(manifest) = config.manifest {
+            builder = builder.manifest(manifest);
+        }
+        if let Some(mode) = config.mode {
+            builder = builder.mode(mode);
+        }
+        if let Some(owner) = config.owner {
+            builder = builder.owner(&owner);
+        }
+        if let Some(group) = config.group {
+            builder = builder.group(&group);
+        }
+        if let Some(create_dirs) = config.create_dirs {
+            builder = builder.create_dirs(create_dirs);
+        }
+        if let Some(max_dir_depth) = config.max_dir_depth {
+            builder = builder.max_dir_depth(max_dir_depth);
+        }
+        if let Some(archive_dir) = config.archive_dir {
+            builder = builder.archive_dir(&archive_dir);
+        }
 
         Ok(Box::new(builder.build(&config.pattern, config.count)?))
     }
@@ -364,11 +627,13 @@ mod test {
             .unwrap();
 
         let file = dir.path().join("foo.log");
+        assert_eq!(roller.archives(&file).unwrap(), Some(0));
         File::create(&file).unwrap().write_all(b"file1").unwrap();
 
         roller.roll(&file).unwrap();
         wait_for_roller(&roller);
         assert!(!file.exists());
+        assert_eq!(roller.archives(&file).unwrap(), Some(1));
         let mut contents = vec![];
         File::open(dir.path().join("foo.log.0"))
             .unwrap()
@@ -401,6 +666,7 @@ mod test {
         assert!(!file.exists());
         contents.clear();
         assert!(!dir.path().join("foo.log.2").exists());
+        assert_eq!(roller.archives(&file).unwrap(), Some(2));
         File::open(dir.path().join("foo.log.1"))
             .unwrap()
             .read_to_end(&mut contents)
@@ -452,7 +718,7 @@ mod test {
         wait_for_roller(&roller);
         assert!(!file.exists());
         contents.clear();
-        File::open(dir.path().join(&format!("{}.{}", fname, base + 1)))
+        File::open(dir.path().join(format!("{}.{}", fname, base + 1)))
             .unwrap()
             .read_to_end(&mut contents)
             .unwrap();
@@ -487,6 +753,51 @@ mod test {
         assert!(base.join("foo.1.log").exists());
     }
 
+    #[test]
+    fn archive_dir() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let archive = dir.path().join("archive");
+        let roller = FixedWindowRoller::builder()
+            .archive_dir(archive.to_str().unwrap())
+            .build("foo.{}.log", 2)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        File::create(&file).unwrap().write_all(b"file").unwrap();
+
+        roller.roll(&file).unwrap();
+        wait_for_roller(&roller);
+
+        assert!(!file.exists());
+        let mut contents = String::new();
+        File::open(archive.join("foo.0.log"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "file");
+    }
+
+    #[test]
+    fn archive_dir_with_braces() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let archive = dir.path().join("a{}");
+        let roller = FixedWindowRoller::builder()
+            .archive_dir(archive.to_str().unwrap())
+            .build("foo.{}.log", 2)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        File::create(&file).unwrap().write_all(b"file").unwrap();
+
+        roller.roll(&file).unwrap();
+        wait_for_roller(&roller);
+
+        assert!(archive.join("foo.0.log").exists());
+        assert!(!dir.path().join("a0").exists());
+    }
+
     #[test]
     fn create_archive_varied() {
         let dir = tempfile::tempdir().unwrap();
@@ -515,6 +826,66 @@ mod test {
         assert!(base.join("1").join("foo.log").exists());
     }
 
+    #[test]
+    fn clean_up() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let roller = FixedWindowRoller::builder()
+            .build(&format!("{}/foo.{{}}.log", dir.path().display()), 2)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        for name in &[
+            "foo.log",
+            "foo.log.1700000000.tmp",
+            "foo.0.log",
+            "foo.1.log.partial",
+            "foo.2.log.partial",
+            "bar.log.1700000000.tmp",
+        ] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        roller.clean_up(&file).unwrap();
+
+        assert!(file.exists());
+        assert!(!dir.path().join("foo.log.1700000000.tmp").exists());
+        assert!(dir.path().join("foo.0.log").exists());
+        // the moved aside log file was rotated into the window
+        assert!(dir.path().join("foo.1.log").exists());
+        assert!(!dir.path().join("foo.1.log.partial").exists());
+        // outside of the window, so not written by this roller
+        assert!(dir.path().join("foo.2.log.partial").exists());
+        assert!(dir.path().join("bar.log.1700000000.tmp").exists());
+    }
+
+    #[test]
+    fn clean_up_interrupted_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let roller = FixedWindowRoller::builder()
+            .build(&format!("{}/foo.{{}}.log", dir.path().display()), 3)
+            .unwrap();
+
+        // Two rolls moved the log file aside, and the process crashed before
+        // either was archived.
+        let file = dir.path().join("foo.log");
+        fs::write(dir.path().join("foo.0.log"), "oldest").unwrap();
+        fs::write(dir.path().join("foo.log.1700000000.tmp"), "older").unwrap();
+        fs::write(dir.path().join("foo.log.1700000001.tmp"), "newer").unwrap();
+        fs::write(&file, "current").unwrap();
+
+        roller.clean_up(&file).unwrap();
+
+        let read = |name| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("foo.log"), "current");
+        assert_eq!(read("foo.0.log"), "newer");
+        assert_eq!(read("foo.1.log"), "older");
+        assert_eq!(read("foo.2.log"), "oldest");
+        assert!(!dir.path().join("foo.log.1700000000.tmp").exists());
+        assert!(!dir.path().join("foo.log.1700000001.tmp").exists());
+    }
+
     #[test]
     #[cfg_attr(feature = "gzip", ignore)]
     fn unsupported_gzip() {
@@ -561,6 +932,123 @@ mod test {
         assert_eq!(contents, actual);
     }
 
+    #[test]
+    fn custom_compressor() {
+        #[derive(Debug)]
+        struct Upper;
+
+        impl Compressor for Upper {
+            fn compress(
+                &self,
+                src: &mut dyn io::Read,
+                dst: &mut dyn io::Write,
+            ) -> anyhow::Result<()> {
+                let mut contents = vec![];
+                src.read_to_end(&mut contents)?;
+                dst.write_all(&contents.to_ascii_uppercase())?;
+                Ok(())
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = dir.path().join("foo.log.{}");
+        let roller = FixedWindowRoller::builder()
+            .compressor(Box::new(Upper))
+            .build(pattern.to_str().unwrap(), 2)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        File::create(&file).unwrap().write_all(b"file1").unwrap();
+        roller.roll(&file).unwrap();
+        wait_for_roller(&roller);
+
+        assert!(!file.exists());
+        assert_eq!(fs::read(dir.path().join("foo.log.0")).unwrap(), b"FILE1");
+        assert!(!dir.path().join("foo.log.0.partial").exists());
+    }
+
+    #[test]
+    fn custom_encryptor() {
+        use crate::encrypt::Encrypt;
+
+        // flips every bit, and marks the end of the stream
+        #[derive(Debug)]
+        struct Flip;
+
+        struct FlipWriter<'a>(&'a mut dyn io::Write);
+
+        impl io::Write for FlipWriter<'_> {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let flipped = buf.iter().map(|b| !b).collect::<Vec<_>>();
+                self.0.write_all(&flipped)?;
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.flush()
+            }
+        }
+
+        impl Encrypt for FlipWriter<'_> {
+            fn finish(self: Box<Self>) -> anyhow::Result<()> {
+                self.0.write_all(b"$")?;
+                Ok(())
+            }
+        }
+
+        impl Encryptor for Flip {
+            fn encrypt<'a>(
+                &self,
+                dst: &'a mut dyn io::Write,
+            ) -> anyhow::Result<Box<dyn Encrypt + 'a>> {
+                Ok(Box::new(FlipWriter(dst)))
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = dir.path().join("foo.log.{}.age");
+        let roller = FixedWindowRoller::builder()
+            .encryptor(Box::new(Flip))
+            .build(pattern.to_str().unwrap(), 2)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        File::create(&file).unwrap().write_all(b"file1").unwrap();
+        roller.roll(&file).unwrap();
+        wait_for_roller(&roller);
+
+        let mut expected = b"file1".iter().map(|b| !b).collect::<Vec<_>>();
+        expected.push(b'$');
+        assert!(!file.exists());
+        assert_eq!(
+            fs::read(dir.path().join("foo.log.0.age")).unwrap(),
+            expected
+        );
+        assert!(!dir.path().join("foo.log.0.age.partial").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = dir.path().join("foo.log.{}");
+        let roller = FixedWindowRoller::builder()
+            .mode(0o600)
+            .build(pattern.to_str().unwrap(), 2)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        File::create(&file).unwrap().write_all(b"file1").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+        roller.roll(&file).unwrap();
+        wait_for_roller(&roller);
+
+        let archive = fs::metadata(dir.path().join("foo.log.0")).unwrap();
+        assert_eq!(archive.permissions().mode() & 0o7777, 0o600);
+    }
+
     #[test]
     fn roll_with_env_var() {
         std::env::set_var("LOG_DIR", "test_log_dir");
@@ -0,0 +1,83 @@
+//! Manifests of archived log files.
+//!
+//! Each archive is recorded as a line of JSON in `manifest.jsonl` in the
+//! directory it is written to, holding its file name, size in bytes,
+//! SHA-256 digest and the time it was archived, so that the archives can
+//! later be checked against the manifest for tampering.
+//!
+//! Requires the `archive_manifest` feature.
+
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+/// The name of the manifest file in an archive directory.
+pub(crate) const MANIFEST: &str = "manifest.jsonl";
+
+/// Appends an entry for the archive at `path` to the manifest of its
+/// directory.
+pub(crate) fn record(path: &Path) -> anyhow::Result<()> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut File::open(path)?, &mut hasher)?;
+    let mut sha256 = String::with_capacity(64);
+    for b in hasher.finalize() {
+        let _ = write!(sha256, "{:02x}", b);
+    }
+
+    let entry = serde_json::json!({
+        "file": path.file_name().map(|name| name.to_string_lossy()),
+        "size": size,
+        "sha256": sha256,
+        "rotated": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+    });
+    let mut line = serde_json::to_vec(&entry)?;
+    line.push(b'\n');
+
+    let manifest = match path.parent() {
+        Some(dir) => dir.join(MANIFEST),
+        None => MANIFEST.into(),
+    };
+    // A single write to a file opened for appending, so that entries are not
+    // torn when several processes archive into the same directory.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(manifest)?
+        .write_all(&line)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn record() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("foo.1.log");
+        fs::write(&archive, b"abc").unwrap();
+
+        super::record(&archive).unwrap();
+        super::record(&archive).unwrap();
+
+        let manifest = fs::read_to_string(dir.path().join(MANIFEST)).unwrap();
+        let entries = manifest
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["file"], "foo.1.log");
+        assert_eq!(entries[0]["size"], 3);
+        assert_eq!(
+            entries[0]["sha256"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(entries[0]["rotated"].as_str().unwrap().ends_with('Z'));
+    }
+}
@@ -1,14 +1,30 @@
 //! Rollers
 
 use std::{fmt, path::Path};
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+use std::{fs, io, sync::Arc};
+
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+use crate::{
+    append::{env_util::expand_path, perm::Permissions},
+    compress::Compressor,
+    encrypt::Encryptor,
+};
 
 #[cfg(feature = "config_parsing")]
 use crate::config::Deserializable;
 
+#[cfg(feature = "date_roller")]
+pub mod date;
 #[cfg(feature = "delete_roller")]
 pub mod delete;
 #[cfg(feature = "fixed_window_roller")]
 pub mod fixed_window;
+#[cfg(all(
+    feature = "archive_manifest",
+    any(feature = "fixed_window_roller", feature = "date_roller")
+))]
+mod manifest;
 
 /// A trait which processes log files after they have been rolled over.
 pub trait Roll: fmt::Debug + Send + Sync + 'static {
@@ -20,6 +36,25 @@ pub trait Roll: fmt::Debug + Send + Sync + 'static {
     /// If this method returns successfully, there *must* no longer be a file
     /// at the specified location.
     fn roll(&self, file: &Path) -> anyhow::Result<()>;
+
+    /// Removes temporary and partial files left behind by rolls of the log
+    /// file which were interrupted, for example by a crash, finishing the
+    /// rolls of files which still hold records.
+    ///
+    /// The default implementation does nothing.
+    fn clean_up(&self, file: &Path) -> anyhow::Result<()> {
+        let _ = file;
+        Ok(())
+    }
+
+    /// Counts the archives of the log file which currently exist.
+    ///
+    /// The default implementation returns `None`, as the roller does not know
+    /// where its archives are.
+    fn archives(&self, file: &Path) -> anyhow::Result<Option<usize>> {
+        let _ = file;
+        Ok(None)
+    }
 }
 
 #[cfg(feature = "config_parsing")]
@@ -28,3 +63,178 @@ impl Deserializable for dyn Roll {
         "roller"
     }
 }
+
+/// The compression and encryption applied to archived log files.
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+#[derive(Clone, Debug)]
+pub(crate) struct Compression {
+    compressor: Option<Arc<dyn Compressor>>,
+    encryptor: Option<Arc<dyn Encryptor>>,
+    permissions: Permissions,
+}
+
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+impl Compression {
+    pub(crate) fn compress(&self, src: &Path, dst: &str) -> anyhow::Result<()> {
+        use std::fs::File;
+
+        if self.compressor.is_none() && self.encryptor.is_none() {
+            move_file(src, dst)?;
+            return self
+                .permissions
+                .apply_path(Path::new(dst))
+                .map_err(Into::into);
+        }
+
+        let mut i = File::open(src)?;
+
+        // compress next to the archive and move it into place once complete,
+        // so an interrupted roll never leaves a truncated archive behind
+        let partial = partial_file_name(dst);
+        let o = File::create(&partial)?;
+        self.permissions.apply(&o)?;
+        let mut o = io::BufWriter::new(o);
+        match self.encryptor {
+            Some(ref encryptor) => {
+                let mut e = encryptor.encrypt(&mut o)?;
+                self.write(&mut i, &mut e)?;
+                e.finish()?;
+            }
+            None => self.write(&mut i, &mut o)?,
+        }
+        drop(o.into_inner().map_err(|e| e.into_error())?);
+        fs::rename(&partial, dst)?;
+        drop(i); // needs to happen before remove_file call on Windows
+
+        fs::remove_file(src).map_err(Into::into)
+    }
+
+    fn write(&self, src: &mut dyn io::Read, dst: &mut dyn io::Write) -> anyhow::Result<()> {
+        match self.compressor {
+            Some(ref compressor) => compressor.compress(src, dst),
+            None => io::copy(src, dst).map(drop).map_err(Into::into),
+        }
+    }
+
+    /// Determines the compression of archives, from the compressor if one is
+    /// set and otherwise from the file extension of the archive pattern.
+    ///
+    /// Archives are encrypted after compression if an encryptor is set.
+    pub(crate) fn new(
+        pattern: &str,
+        compressor: Option<Arc<dyn Compressor>>,
+        encryptor: Option<Arc<dyn Encryptor>>,
+    ) -> anyhow::Result<Compression> {
+        let compressor = match compressor {
+            Some(compressor) => Some(compressor),
+            None => default_compressor(pattern, encryptor.is_some())?,
+        };
+        Ok(Compression {
+            compressor,
+            encryptor,
+            permissions: Permissions::default(),
+        })
+    }
+
+    /// Sets the permissions given to archives.
+    pub(crate) fn permissions(mut self, permissions: Permissions) -> Compression {
+        self.permissions = permissions;
+        self
+    }
+}
+
+/// Chooses the compressor from the file extension of the archive pattern,
+/// looking past an `.age` extension when archives are encrypted.
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+fn default_compressor(
+    pattern: &str,
+    encrypted: bool,
+) -> anyhow::Result<Option<Arc<dyn Compressor>>> {
+    let mut pattern = Path::new(pattern);
+    if encrypted && pattern.extension().map_or(false, |e| e == "age") {
+        pattern = Path::new(pattern.file_stem().unwrap_or_default());
+    }
+
+    match pattern.extension() {
+        #[cfg(feature = "gzip")]
+        Some(e) if e == "gz" => Ok(Some(Arc::new(crate::compress::gzip::GzipCompressor::new()))),
+        #[cfg(not(feature = "gzip"))]
+        Some(e) if e == "gz" => {
+            anyhow::bail!("gzip compression requires the `gzip` feature");
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Records the archive `dst` in the manifest of its directory.
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+pub(crate) fn record_in_manifest(dst: &Path) -> anyhow::Result<()> {
+    #[cfg(feature = "archive_manifest")]
+    {
+        manifest::record(dst)
+    }
+    #[cfg(not(feature = "archive_manifest"))]
+    {
+        let _ = dst;
+        anyhow::bail!("archive manifests require the `archive_manifest` feature")
+    }
+}
+
+/// Returns an error if archive manifests are requested but not available.
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+pub(crate) fn check_manifest(manifest: bool) -> anyhow::Result<()> {
+    if manifest && !cfg!(feature = "archive_manifest") {
+        anyhow::bail!("archive manifests require the `archive_manifest` feature");
+    }
+    Ok(())
+}
+
+/// Returns the name under which the archive `dst` is written while it is
+/// being compressed.
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+pub(crate) fn partial_file_name(dst: &str) -> String {
+    format!("{}.partial", dst)
+}
+
+/// Removes `path` if it exists.
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+pub(crate) fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        r => r,
+    }
+}
+
+/// Moves a file, falling back to a copy and delete across mounts.
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+pub(crate) fn move_file<P, Q>(src: P, dst: Q) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    // first try a rename
+    match fs::rename(src.as_ref(), dst.as_ref()) {
+        Ok(()) => return Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(_) => {}
+    }
+
+    // fall back to a copy and delete if src and dst are on different mounts,
+    // copying next to dst first so an interrupted copy is never mistaken for
+    // a complete archive
+    let partial = partial_file_name(&dst.as_ref().to_string_lossy());
+    fs::copy(src.as_ref(), &partial)?;
+    fs::rename(&partial, dst.as_ref())?;
+    fs::remove_file(src.as_ref())
+}
+
+/// Resolves a relative archive `pattern` against `archive_dir`, if set.
+#[cfg(any(feature = "fixed_window_roller", feature = "date_roller"))]
+pub(crate) fn archive_pattern(pattern: &str, archive_dir: Option<&str>) -> String {
+    match archive_dir {
+        Some(dir) if !Path::new(&*expand_path(pattern)).is_absolute() => {
+            Path::new(dir).join(pattern).to_string_lossy().into_owned()
+        }
+        _ => pattern.to_owned(),
+    }
+}
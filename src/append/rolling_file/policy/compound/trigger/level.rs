@@ -0,0 +1,157 @@
+//! The level trigger.
+//!
+//! Requires the `level_trigger` feature.
+
+use log::Level;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::append::rolling_file::{policy::compound::trigger::Trigger, LogFile};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+
+/// Configuration for the level trigger.
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelTriggerConfig {
+    level: Level,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    cooldown: Option<Duration>,
+}
+
+/// A trigger which rolls the log once a record at or above a certain level
+/// has been written to it.
+///
+/// The record is the last one in the rolled file, so an incident can be
+/// shipped as soon as it is logged while the records following it start a
+/// fresh file.
+#[derive(Debug)]
+pub struct LevelTrigger {
+    level: Level,
+    cooldown: Option<Duration>,
+    last_fired: Mutex<Option<Instant>>,
+}
+
+impl LevelTrigger {
+    /// Returns a new trigger which rolls the log once a record at or above
+    /// the specified level has been written to it.
+    pub fn new(level: Level) -> LevelTrigger {
+        LevelTrigger {
+            level,
+            cooldown: None,
+            last_fired: Mutex::new(None),
+        }
+    }
+
+    /// Sets a period after the trigger fires during which records at the
+    /// level do not fire it again, so that a burst of errors ends up in one
+    /// file rather than a file each.
+    pub fn cooldown(mut self, cooldown: Duration) -> LevelTrigger {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Returns the level at or above which the log is rolled.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+}
+
+impl Trigger for LevelTrigger {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        match file.record_level() {
+            Some(level) if level <= self.level => {}
+            _ => return Ok(false),
+        }
+
+        let mut last_fired = self.last_fired.lock().unwrap_or_else(|e| e.into_inner());
+        if let (Some(cooldown), Some(last_fired)) = (self.cooldown, *last_fired) {
+            if last_fired.elapsed() < cooldown {
+                return Ok(false);
+            }
+        }
+        *last_fired = Some(Instant::now());
+        Ok(true)
+    }
+}
+
+/// A deserializer for the `LevelTrigger`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: level
+///
+/// # The level at or above which a record rolls the log once it has been
+/// # written. Required.
+/// level: error
+///
+/// # A period after the log is rolled during which records at the level do
+/// # not roll it again. Defaults to no cooldown.
+/// cooldown: 1 minute
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct LevelTriggerDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for LevelTriggerDeserializer {
+    type Trait = dyn Trigger;
+
+    type Config = LevelTriggerConfig;
+
+    fn deserialize(
+        &self,
+        config: LevelTriggerConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Trigger>> {
+        let mut trigger = LevelTrigger::new(config.level);
+        if let Some(cooldown) = config.cooldown {
+            trigger = trigger.cooldown(cooldown);
+        }
+        Ok(Box::new(trigger))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    fn fires(trigger: &LevelTrigger, level: Option<Level>) -> bool {
+        let mut writer = None;
+        let file = LogFile {
+            writer: &mut writer,
+            path: Path::new("log"),
+            len: 0,
+            level,
+            footer: None,
+            coordination: None,
+            lock: None,
+            superseded: false,
+            rotation: &Default::default(),
+        };
+        trigger.trigger(&file).unwrap()
+    }
+
+    #[test]
+    fn levels() {
+        let trigger = LevelTrigger::new(Level::Warn);
+        assert!(!fires(&trigger, None));
+        assert!(!fires(&trigger, Some(Level::Info)));
+        assert!(fires(&trigger, Some(Level::Warn)));
+        assert!(fires(&trigger, Some(Level::Error)));
+    }
+
+    #[test]
+    fn cooldown() {
+        let trigger = LevelTrigger::new(Level::Error).cooldown(Duration::from_secs(60));
+        assert!(fires(&trigger, Some(Level::Error)));
+        assert!(!fires(&trigger, Some(Level::Error)));
+    }
+}
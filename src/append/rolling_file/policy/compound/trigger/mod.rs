@@ -6,6 +6,8 @@ use crate::append::rolling_file::LogFile;
 #[cfg(feature = "config_parsing")]
 use crate::config::Deserializable;
 
+#[cfg(feature = "level_trigger")]
+pub mod level;
 #[cfg(feature = "size_trigger")]
 pub mod size;
 
@@ -60,45 +60,50 @@ where
         where
             E: de::Error,
         {
-            let (number, unit) = match v.find(|c: char| !c.is_ascii_digit()) {
-                Some(n) => (v[..n].trim(), Some(v[n..].trim())),
-                None => (v.trim(), None),
-            };
-
-            let number = match number.parse::<u64>() {
-                Ok(n) => n,
-                Err(_) => return Err(E::invalid_value(de::Unexpected::Str(number), &"a number")),
-            };
-
-            let unit = match unit {
-                Some(u) => u,
-                None => return Ok(number),
-            };
-
-            let number = if unit.eq_ignore_ascii_case("b") {
-                Some(number)
-            } else if unit.eq_ignore_ascii_case("kb") || unit.eq_ignore_ascii_case("kib") {
-                number.checked_mul(1024)
-            } else if unit.eq_ignore_ascii_case("mb") || unit.eq_ignore_ascii_case("mib") {
-                number.checked_mul(1024 * 1024)
-            } else if unit.eq_ignore_ascii_case("gb") || unit.eq_ignore_ascii_case("gib") {
-                number.checked_mul(1024 * 1024 * 1024)
-            } else if unit.eq_ignore_ascii_case("tb") || unit.eq_ignore_ascii_case("tib") {
-                number.checked_mul(1024 * 1024 * 1024 * 1024)
-            } else {
-                return Err(E::invalid_value(de::Unexpected::Str(unit), &"a valid unit"));
-            };
-
-            match number {
-                Some(n) => Ok(n),
-                None => Err(E::invalid_value(de::Unexpected::Str(v), &"a byte size")),
-            }
+            parse_limit(v).map_err(E::custom)
         }
     }
 
     d.deserialize_any(V)
 }
 
+/// Parses a size such as "100 MB" or "1.5 GiB" into bytes.
+fn parse_limit(v: &str) -> anyhow::Result<u64> {
+    let (number, unit) = match v.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(n) => (v[..n].trim(), v[n..].trim()),
+        None => (v.trim(), ""),
+    };
+
+    let multiplier: u64 = match &*unit.to_ascii_lowercase() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000 * 1000,
+        "gb" => 1000 * 1000 * 1000,
+        "tb" => 1000 * 1000 * 1000 * 1000,
+        "kib" => 1024,
+        "mib" => 1024 * 1024,
+        "gib" => 1024 * 1024 * 1024,
+        "tib" => 1024 * 1024 * 1024 * 1024,
+        _ => anyhow::bail!("invalid size unit `{}`", unit),
+    };
+
+    let bytes = match number.parse::<u64>() {
+        Ok(number) => number.checked_mul(multiplier),
+        Err(_) => match number.parse::<f64>() {
+            Ok(number) if number.is_finite() && number >= 0. => {
+                let bytes = (number * multiplier as f64).round();
+                if bytes < u64::MAX as f64 {
+                    Some(bytes as u64)
+                } else {
+                    None
+                }
+            }
+            _ => anyhow::bail!("invalid size `{}`", v),
+        },
+    };
+    bytes.ok_or_else(|| anyhow::anyhow!("size `{}` is too large", v))
+}
+
 /// A trigger which rolls the log once it has passed a certain size.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub struct SizeTrigger {
@@ -111,6 +116,22 @@ impl SizeTrigger {
     pub fn new(limit: u64) -> SizeTrigger {
         SizeTrigger { limit }
     }
+
+    /// Returns a new trigger which rolls the log once it has passed the
+    /// specified size, such as `"100 MB"` or `"50 KiB"`.
+    ///
+    /// The units are those accepted in config files: "b", the SI units "kb",
+    /// "mb", "gb" and "tb", which are powers of 1000, and the binary units
+    /// "kib", "mib", "gib" and "tib", which are powers of 1024, in any case.
+    /// The number may have a fractional part.
+    pub fn parse(limit: &str) -> anyhow::Result<SizeTrigger> {
+        Ok(SizeTrigger::new(parse_limit(limit)?))
+    }
+
+    /// Returns the size in bytes past which the log is rolled.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
 }
 
 impl Trigger for SizeTrigger {
@@ -126,10 +147,12 @@ impl Trigger for SizeTrigger {
 /// ```yaml
 /// kind: size
 ///
-/// # The size limit in bytes. The following units are supported (case insensitive):
-/// # "b", "kb", "kib", "mb", "mib", "gb", "gib", "tb", "tib". The unit defaults to
-/// # bytes if not specified. Required.
-/// limit: 10 mb
+/// # The size limit in bytes. The following units are supported (case
+/// # insensitive): "b", the SI units "kb", "mb", "gb" and "tb", which are
+/// # powers of 1000, and the binary units "kib", "mib", "gib" and "tib", which
+/// # are powers of 1024. The number may have a fractional part. The unit
+/// # defaults to bytes if not specified. Required.
+/// limit: 10 mib
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -149,3 +172,34 @@ impl Deserialize for SizeTriggerDeserializer {
         Ok(Box::new(SizeTrigger::new(config.limit)))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let tests = [
+            ("1024", Some(1024)),
+            ("10 b", Some(10)),
+            ("100 MB", Some(100_000_000)),
+            ("50 KiB", Some(51_200)),
+            ("50kib", Some(51_200)),
+            ("1.5 GiB", Some(1_610_612_736)),
+            ("2 tb", Some(2_000_000_000_000)),
+            ("10 parsecs", None),
+            ("1.2.3 mb", None),
+            ("mb", None),
+            ("20000000 tib", None),
+        ];
+
+        for &(limit, expected) in &tests {
+            assert_eq!(
+                SizeTrigger::parse(limit).ok().map(|t| t.limit()),
+                expected,
+                "{}",
+                limit
+            );
+        }
+    }
+}
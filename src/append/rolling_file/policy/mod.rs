@@ -1,5 +1,5 @@
 //! Policies.
-use std::fmt;
+use std::{fmt, path::Path};
 
 use crate::append::rolling_file::LogFile;
 
@@ -16,6 +16,36 @@ pub trait Policy: Sync + Send + 'static + fmt::Debug {
     /// This method is called after each log event. It is provided a reference
     /// to the current log file.
     fn process(&self, log: &mut LogFile) -> anyhow::Result<()>;
+
+    /// Rolls the current log file unconditionally.
+    ///
+    /// This method is called by `RollingFileAppender::roll_now`. The default
+    /// implementation returns an error, as the policy does not know how to
+    /// roll the file.
+    fn roll_now(&self, log: &mut LogFile) -> anyhow::Result<()> {
+        let _ = log;
+        anyhow::bail!("policy does not support rolling on demand")
+    }
+
+    /// Removes temporary and partial files left behind by rolls of the log
+    /// file at `path` which were interrupted, for example by a crash.
+    ///
+    /// This is called when a `RollingFileAppender` which cleans up partial
+    /// files is built. The default implementation does nothing.
+    fn clean_up(&self, path: &Path) -> anyhow::Result<()> {
+        let _ = path;
+        Ok(())
+    }
+
+    /// Counts the archives of the log file at `path` which currently exist.
+    ///
+    /// This is used in the report of a `RollingFileAppender` when it is built.
+    /// The default implementation returns `None`, as the policy does not know
+    /// where its archives are.
+    fn archives(&self, path: &Path) -> anyhow::Result<Option<usize>> {
+        let _ = path;
+        Ok(None)
+    }
 }
 
 #[cfg(feature = "config_parsing")]
@@ -0,0 +1,336 @@
+//! How a log file is rotated while other programs hold it open.
+//!
+//! On Windows a file cannot be renamed or deleted while another program,
+//! such as a virus scanner or a log tailer, has it open without sharing
+//! access, so archiving it fails with a sharing violation. A `Rotation`
+//! retries such failures with a backoff, and can fall back to copying the log
+//! file and truncating it in place, which only needs the file to be readable
+//! and writable.
+//!
+//! Tools which follow the log file through an open handle, such as `tail -f`
+//! and some log shippers, keep reading the archive once the log file is moved
+//! away. `Mode::CopyTruncate` always copies and truncates the log file
+//! instead, so the file they follow stays in place.
+
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Configuration for the rotation of a log file.
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RotationConfig {
+    retries: Option<u32>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    backoff: Option<Duration>,
+    copy_truncate: Option<CopyTruncate>,
+}
+
+#[cfg(feature = "config_parsing")]
+impl From<RotationConfig> for Rotation {
+    fn from(config: RotationConfig) -> Rotation {
+        let mut rotation = Rotation::new();
+        if let Some(retries) = config.retries {
+            rotation = rotation.retries(retries);
+        }
+        if let Some(backoff) = config.backoff {
+            rotation = rotation.backoff(backoff);
+        }
+        if let Some(copy_truncate) = config.copy_truncate {
+            rotation = rotation.copy_truncate(copy_truncate);
+        }
+        rotation
+    }
+}
+
+/// How the log file is archived when it is rolled.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+pub enum Mode {
+    /// The log file is moved to the archive and a new log file is opened,
+    /// copying and truncating it only as the `Rotation` allows.
+    #[default]
+    Rename,
+    /// The log file is copied to the archive and truncated in place, so that
+    /// open handles to it keep following the log.
+    CopyTruncate,
+}
+
+/// When the log file is archived by copying it and truncating it in place
+/// because moving it failed.
+///
+/// Use `Mode::CopyTruncate` to always copy and truncate it.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum CopyTruncate {
+    /// The log file is always moved.
+    #[default]
+    Never,
+    /// The log file is copied and truncated once moving it has failed with a
+    /// sharing violation on every retry.
+    Fallback,
+}
+
+/// The strategy for archiving a log file which other programs may hold open.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Rotation {
+    retries: u32,
+    backoff: Duration,
+    copy_truncate: CopyTruncate,
+    mode: Mode,
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation {
+            retries: 0,
+            backoff: Duration::from_millis(100),
+            copy_truncate: CopyTruncate::Never,
+            mode: Mode::Rename,
+        }
+    }
+}
+
+impl Rotation {
+    /// Creates a new `Rotation` which archives the log file once, without
+    /// retrying.
+    pub fn new() -> Rotation {
+        Rotation::default()
+    }
+
+    /// Sets how many times archiving the log file is retried after it fails
+    /// with a sharing violation.
+    ///
+    /// Records are not written while the log file is archived, so retries
+    /// hold up logging. Defaults to 0.
+    pub fn retries(mut self, retries: u32) -> Rotation {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how long to wait before the first retry. The wait doubles before
+    /// each further retry.
+    ///
+    /// Defaults to 100 milliseconds.
+    pub fn backoff(mut self, backoff: Duration) -> Rotation {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets whether the log file is copied and truncated in place once moving
+    /// it has failed.
+    ///
+    /// The copy is archived in place of the log file. Records written to the
+    /// log file by other processes between the copy and the truncation are
+    /// lost.
+    ///
+    /// Defaults to `CopyTruncate::Never`.
+    pub fn copy_truncate(mut self, copy_truncate: CopyTruncate) -> Rotation {
+        self.copy_truncate = copy_truncate;
+        self
+    }
+
+    // Sets the mode of the appender, which takes precedence over
+    // `copy_truncate`.
+    pub(super) fn mode(mut self, mode: Mode) -> Rotation {
+        self.mode = mode;
+        self
+    }
+
+    /// Archives the log file at `path` with `archive`, which must leave no
+    /// file behind at the path it is given.
+    pub(super) fn archive<F>(&self, path: &Path, mut archive: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&Path) -> anyhow::Result<()>,
+    {
+        if self.mode == Mode::CopyTruncate {
+            return copy_truncate(path, archive);
+        }
+
+        let mut backoff = self.backoff;
+        let mut retries = self.retries;
+        loop {
+            match archive(path) {
+                Err(e) if is_sharing_violation(&e) => {
+                    if retries > 0 {
+                        retries -= 1;
+                        thread::sleep(backoff);
+                        backoff = backoff.saturating_mul(2);
+                    } else if self.copy_truncate == CopyTruncate::Fallback {
+                        return copy_truncate(path, archive);
+                    } else {
+                        return Err(e);
+                    }
+                }
+                r => return r,
+            }
+        }
+    }
+}
+
+/// Copies the log file next to it, truncates it, and archives the copy.
+///
+/// If archiving the copy fails, the copy is left behind so that its records
+/// are not lost, and is archived before the log file is copied again.
+fn copy_truncate<F>(path: &Path, mut archive: F) -> anyhow::Result<()>
+where
+    F: FnMut(&Path) -> anyhow::Result<()>,
+{
+    let copy = copy_file_name(path);
+    if copy.exists() {
+        archive(&copy)?;
+    }
+    fs::copy(path, &copy)?;
+    OpenOptions::new().write(true).open(path)?.set_len(0)?;
+    archive(&copy)
+}
+
+fn copy_file_name(path: &Path) -> PathBuf {
+    let mut copy = path.as_os_str().to_owned();
+    copy.push(".copy");
+    copy.into()
+}
+
+/// Determines if archiving failed because another program holds the log file
+/// open, which only happens on Windows.
+fn is_sharing_violation(e: &anyhow::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    const SHARING_VIOLATIONS: &[i32] = &[32, 33];
+
+    cfg!(windows)
+        && e.chain()
+            .filter_map(|e| e.downcast_ref::<io::Error>())
+            .any(|e| {
+                e.kind() == io::ErrorKind::PermissionDenied
+                    || e.raw_os_error()
+                        .map_or(false, |code| SHARING_VIOLATIONS.contains(&code))
+            })
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn denied() -> anyhow::Error {
+        io::Error::from(io::ErrorKind::PermissionDenied).into()
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "records").unwrap();
+
+        let attempts = Cell::new(0);
+        let archive = |path: &Path| {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                return Err(denied());
+            }
+            fs::remove_file(path).map_err(Into::into)
+        };
+
+        let rotation = Rotation::new().backoff(Duration::from_millis(1));
+        assert!(rotation.archive(&path, archive).is_err());
+        assert_eq!(attempts.get(), 1);
+
+        attempts.set(0);
+        rotation.retries(2).archive(&path, archive).unwrap();
+        assert_eq!(attempts.get(), 3);
+        assert!(!path.exists());
+
+        // other errors are not retried
+        attempts.set(0);
+        let result = rotation.retries(2).archive(&path, |_| {
+            attempts.set(attempts.get() + 1);
+            Err(io::Error::from(io::ErrorKind::NotFound).into())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn no_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+
+        let attempts = Cell::new(0);
+        let rotation = Rotation::new()
+            .retries(2)
+            .backoff(Duration::from_millis(1))
+            .copy_truncate(CopyTruncate::Fallback);
+        let result = rotation.archive(&path, |_| {
+            attempts.set(attempts.get() + 1);
+            Err(denied())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn copy_truncate_leftover_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "records").unwrap();
+        fs::write(copy_file_name(&path), "earlier records").unwrap();
+
+        let archived = Cell::new(0);
+        let rotation = Rotation::new().mode(Mode::CopyTruncate);
+        rotation
+            .archive(&path, |src| {
+                let archive = dir.path().join(format!("app.{}.log", archived.get()));
+                archived.set(archived.get() + 1);
+                fs::rename(src, archive).map_err(Into::into)
+            })
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("app.0.log")).unwrap(),
+            "earlier records"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("app.1.log")).unwrap(),
+            "records"
+        );
+        assert!(!copy_file_name(&path).exists());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn copy_truncate_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let archive = dir.path().join("app.0.log");
+        fs::write(&path, "records").unwrap();
+
+        // the log file itself is held open, but its copy is not
+        let rotation = Rotation::new()
+            .retries(1)
+            .backoff(Duration::from_millis(1))
+            .copy_truncate(CopyTruncate::Fallback);
+        rotation
+            .archive(&path, |src| {
+                if src == path {
+                    return Err(denied());
+                }
+                fs::rename(src, &archive).map_err(Into::into)
+            })
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        assert_eq!(fs::read_to_string(&archive).unwrap(), "records");
+        assert!(!copy_file_name(&path).exists());
+    }
+}
@@ -0,0 +1,482 @@
+//! The routing appender.
+//!
+//! Requires the `routing_appender` feature.
+
+use log::Record;
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use crate::append::{flush::FlushToken, Append};
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+
+/// The routing appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingAppenderConfig {
+    route: Route,
+    default_key: Option<String>,
+    max_routes: Option<usize>,
+    appender: Inner,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct Inner {
+    kind: String,
+    config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Inner {
+    fn deserialize<D>(d: D) -> Result<Inner, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(Inner {
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
+
+/// The part of a record a `RoutingAppender` routes it by.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(
+    feature = "config_parsing",
+    serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)
+)]
+pub enum Route {
+    /// The target of the record, cut down to its first `depth` components if
+    /// set.
+    Target {
+        /// The number of `::` separated components of the target kept.
+        #[cfg_attr(feature = "config_parsing", serde(default))]
+        depth: Option<usize>,
+    },
+    /// The level of the record, in lower case.
+    Level,
+    /// A value in the MDC of the thread logging the record.
+    Mdc {
+        /// The MDC key.
+        key: String,
+    },
+}
+
+type Factory = dyn Fn(&str) -> anyhow::Result<Box<dyn Append>> + Send + Sync;
+
+/// An appender which routes each record to one of a set of appenders, chosen
+/// by a key derived from the record.
+///
+/// The appender for a key is created from a template the first time a record
+/// with that key is appended, for example a file appender writing to
+/// `logs/{key}.log`, so that each module can log to its own file without an
+/// appender being configured for each.
+///
+/// Keys are made safe for use in file names: `::` is replaced with `.`, and
+/// characters other than ASCII letters, digits, `-`, `_` and `.` with `_`.
+pub struct RoutingAppender {
+    route: Route,
+    default_key: String,
+    max_routes: usize,
+    factory: Box<Factory>,
+    routes: RwLock<HashMap<String, Arc<dyn Append>>>,
+}
+
+impl fmt::Debug for RoutingAppender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoutingAppender")
+            .field("route", &self.route)
+            .field("default_key", &self.default_key)
+            .field("max_routes", &self.max_routes)
+            .field("routes", &self.routes)
+            .finish()
+    }
+}
+
+impl RoutingAppender {
+    /// Creates a new `RoutingAppender` builder.
+    pub fn builder() -> RoutingAppenderBuilder {
+        RoutingAppenderBuilder {
+            default_key: "default".to_owned(),
+            max_routes: 64,
+        }
+    }
+
+    /// Returns the keys of the appenders created so far.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = self
+            .routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    fn key(&self, record: &Record) -> String {
+        let key = match self.route {
+            Route::Target { depth } => {
+                let target = record.target();
+                match depth {
+                    Some(depth) => target
+                        .split("::")
+                        .take(depth)
+                        .collect::<Vec<_>>()
+                        .join("::"),
+                    None => target.to_owned(),
+                }
+            }
+            Route::Level => record.level().as_str().to_lowercase(),
+            Route::Mdc { ref key } => log_mdc::get(key, |value| value.map(ToOwned::to_owned))
+                .unwrap_or_else(|| self.default_key.clone()),
+        };
+
+        if key.is_empty() {
+            return self.default_key.clone();
+        }
+        sanitize(&key)
+    }
+
+    fn appender(&self, key: String) -> anyhow::Result<Arc<dyn Append>> {
+        if let Some(appender) = self
+            .routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+        {
+            return Ok(appender.clone());
+        }
+
+        let mut routes = self.routes.write().unwrap_or_else(|e| e.into_inner());
+        // Keys beyond the limit share the appender of the default key.
+        let key = if routes.len() >= self.max_routes && !routes.contains_key(&key) {
+            self.default_key.clone()
+        } else {
+            key
+        };
+        if let Some(appender) = routes.get(&key) {
+            return Ok(appender.clone());
+        }
+        let appender: Arc<dyn Append> = Arc::from((self.factory)(&key)?);
+        routes.insert(key, appender.clone());
+        Ok(appender)
+    }
+
+    fn all(&self) -> Vec<Arc<dyn Append>> {
+        self.routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+impl Append for RoutingAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        self.appender(self.key(record))?.append(record)
+    }
+
+    fn flush(&self) {
+        for appender in self.all() {
+            appender.flush();
+        }
+    }
+
+    fn flush_to(&self, token: FlushToken) {
+        for appender in self.all() {
+            appender.flush_to(token);
+        }
+    }
+}
+
+fn sanitize(key: &str) -> String {
+    key.replace("::", ".")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A builder for `RoutingAppender`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RoutingAppenderBuilder {
+    default_key: String,
+    max_routes: usize,
+}
+
+impl RoutingAppenderBuilder {
+    /// Sets the key of records the route yields no key for, such as those
+    /// logged without the MDC value routed by.
+    ///
+    /// Defaults to `default`.
+    pub fn default_key(mut self, default_key: &str) -> RoutingAppenderBuilder {
+        self.default_key = sanitize(default_key);
+        self
+    }
+
+    /// Sets the maximum number of appenders created. Records with new keys
+    /// beyond the limit go to the appender of the default key.
+    ///
+    /// Defaults to 64.
+    pub fn max_routes(mut self, max_routes: usize) -> RoutingAppenderBuilder {
+        self.max_routes = max_routes;
+        self
+    }
+
+    /// Consumes the `RoutingAppenderBuilder`, producing a `RoutingAppender`
+    /// which routes records by `route` to appenders created by `factory`
+    /// from their key.
+    pub fn build<F>(self, route: Route, factory: F) -> RoutingAppender
+    where
+        F: Fn(&str) -> anyhow::Result<Box<dyn Append>> + Send + Sync + 'static,
+    {
+        RoutingAppender {
+            route,
+            default_key: self.default_key,
+            max_routes: self.max_routes,
+            factory: Box::new(factory),
+            routes: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+/// Replaces `{key}` in the strings of an appender's configuration.
+#[cfg(feature = "config_parsing")]
+fn substitute(value: &Value, key: &str) -> Value {
+    match *value {
+        Value::String(ref s) => Value::String(s.replace("{key}", key)),
+        Value::Option(Some(ref value)) => Value::Option(Some(Box::new(substitute(value, key)))),
+        Value::Newtype(ref value) => Value::Newtype(Box::new(substitute(value, key))),
+        Value::Seq(ref values) => Value::Seq(values.iter().map(|v| substitute(v, key)).collect()),
+        Value::Map(ref map) => Value::Map(
+            map.iter()
+                .map(|(k, v)| (k.clone(), substitute(v, key)))
+                .collect(),
+        ),
+        ref value => value.clone(),
+    }
+}
+
+/// A deserializer for the `RoutingAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: routing
+///
+/// # The part of the record records are routed by. Required.
+/// route:
+///
+///   # "target", "level" or "mdc". Required.
+///   kind: target
+///
+///   # For "target", the number of "::" separated components of the target
+///   # kept. Defaults to the whole target.
+///   depth: 2
+///
+///   # For "mdc", the MDC key whose value records are routed by.
+///   # key: tenant
+///
+/// # The key of records the route yields no key for, such as those logged
+/// # without the MDC value. Defaults to "default".
+/// default_key: other
+///
+/// # The maximum number of appenders created. Records with new keys beyond the
+/// # limit go to the appender of the default key. Defaults to 64.
+/// max_routes: 64
+///
+/// # The template of the appenders records are routed to, with "{key}" in its
+/// # strings replaced by the key of the records. "::" in keys is replaced with
+/// # ".", and characters other than ASCII letters, digits, "-", "_" and "."
+/// # with "_". Required.
+/// appender:
+///   kind: file
+///   path: "logs/{key}.log"
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct RoutingAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for RoutingAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = RoutingAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: RoutingAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut builder = RoutingAppender::builder();
+        if let Some(default_key) = config.default_key {
+            builder = builder.default_key(&default_key);
+        }
+        if let Some(max_routes) = config.max_routes {
+            builder = builder.max_routes(max_routes);
+        }
+
+        let deserializers = deserializers.clone();
+        let Inner {
+            kind,
+            config: template,
+        } = config.appender;
+        Ok(Box::new(builder.build(config.route, move |key| {
+            deserializers.deserialize(&kind, substitute(&template, key))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    type Records = Arc<Mutex<Vec<(String, String)>>>;
+
+    #[derive(Debug)]
+    struct TestAppender(String, Records);
+
+    impl Append for TestAppender {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            self.1
+                .lock()
+                .unwrap()
+                .push((self.0.clone(), record.args().to_string()));
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn routing(route: Route, max_routes: usize) -> (RoutingAppender, Records) {
+        let records = Arc::new(Mutex::new(vec![]));
+        let appender = {
+            let records = records.clone();
+            RoutingAppender::builder()
+                .max_routes(max_routes)
+                .build(route, move |key| {
+                    Ok(Box::new(TestAppender(key.to_owned(), records.clone())))
+                })
+        };
+        (appender, records)
+    }
+
+    #[test]
+    fn route() {
+        let (appender, records) = routing(Route::Target { depth: Some(2) }, 2);
+        for (target, message) in [
+            ("app::db::pool", "one"),
+            ("app::db", "two"),
+            ("app::http", "three"),
+            ("app::auth", "four"),
+        ] {
+            appender
+                .append(
+                    &Record::builder()
+                        .target(target)
+                        .args(format_args!("{}", message))
+                        .build(),
+                )
+                .unwrap();
+        }
+        assert_eq!(appender.keys(), ["app.db", "app.http", "default"]);
+        assert_eq!(
+            *records.lock().unwrap(),
+            [
+                ("app.db".to_owned(), "one".to_owned()),
+                ("app.db".to_owned(), "two".to_owned()),
+                ("app.http".to_owned(), "three".to_owned()),
+                ("default".to_owned(), "four".to_owned()),
+            ]
+        );
+
+        let (appender, _) = routing(Route::Level, 64);
+        appender
+            .append(&Record::builder().level(Level::Warn).build())
+            .unwrap();
+        assert_eq!(appender.keys(), ["warn"]);
+
+        let (appender, _) = routing(
+            Route::Mdc {
+                key: "tenant".to_owned(),
+            },
+            64,
+        );
+        appender.append(&Record::builder().build()).unwrap();
+        log_mdc::insert("tenant", "acme/eu");
+        appender.append(&Record::builder().build()).unwrap();
+        log_mdc::remove("tenant");
+        assert_eq!(appender.keys(), ["acme_eu", "default"]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "file_appender"))]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = format!(
+            "
+appenders:
+  modules:
+    kind: routing
+    route:
+      kind: target
+      depth: 1
+    appender:
+      kind: file
+      path: {}/{{key}}.log
+      encoder:
+        pattern: \"{{m}}\"
+",
+            dir.path().display()
+        );
+        let config = ::serde_yaml::from_str::<RawConfig>(&config).unwrap();
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::default());
+        assert!(errors.is_empty());
+
+        appenders[0]
+            .appender()
+            .append(
+                &Record::builder()
+                    .target("db::pool")
+                    .args(format_args!("one"))
+                    .build(),
+            )
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("db.log")).unwrap(),
+            "one"
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! The SQLite appender.
+//!
+//! Requires the `sqlite` feature.
+
+use derivative::Derivative;
+use log::{
+    kv::{self, VisitSource},
+    Record,
+};
+use rusqlite::{params, Connection};
+use serde_json::{Map, Value as Json};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::{
+    append::{
+        flush::{FlushToken, FlushTracker},
+        Append, AppendHealth,
+    },
+    background::{self, Message, Task},
+    encode,
+    history::format_time,
+};
+
+/// The SQLite appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SqliteAppenderConfig {
+    path: String,
+    table: Option<String>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    batch_interval: Option<Duration>,
+    max_batch: Option<usize>,
+}
+
+struct Row {
+    time: String,
+    level: &'static str,
+    target: String,
+    message: String,
+    fields: Option<String>,
+}
+
+/// An appender which inserts records into a table of an SQLite database, for
+/// logs which can be queried without a log collector.
+///
+/// The table is created if it does not exist, with the columns `time`, an
+/// RFC 3339 UTC timestamp, `level`, `target`, `message`, and `fields`, the
+/// record's key-value pairs as a JSON object, or `NULL` if it has none.
+///
+/// Records are inserted in batches in the background, each batch in a single
+/// transaction. A batch which cannot be inserted is reported to the
+/// error handler and dropped. The database is switched to write-ahead logging
+/// so that it can be queried while records are inserted.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct SqliteAppender {
+    #[derivative(Debug = "ignore")]
+    sender: Mutex<Option<background::Sender<Row>>>,
+    tracker: Arc<FlushTracker>,
+    worker: Option<Task>,
+}
+
+impl SqliteAppender {
+    /// Creates a new `SqliteAppender` builder.
+    pub fn builder() -> SqliteAppenderBuilder {
+        SqliteAppenderBuilder {
+            table: "logs".to_owned(),
+            batch_interval: Duration::from_millis(500),
+            max_batch: 256,
+        }
+    }
+}
+
+impl Append for SqliteAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let row = Row {
+            time: format_time(encode::record_time()),
+            level: record.level().as_str(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            fields: fields(record.key_values()),
+        };
+
+        let sender = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        let token = self.tracker.track();
+        let sent = match *sender {
+            Some(ref sender) => sender.send(Message::Item(token, row)),
+            None => false,
+        };
+        if !sent {
+            self.tracker.complete(token);
+            anyhow::bail!("SQLite writer has stopped");
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {
+        self.flush_to(FlushToken::issue());
+    }
+
+    fn flush_to(&self, token: FlushToken) {
+        if let Some(ref sender) = *self.sender.lock().unwrap_or_else(|e| e.into_inner()) {
+            sender.send(Message::Flush);
+        }
+        if let Some(ref worker) = self.worker {
+            worker.wait(|| self.tracker.wait(token));
+        }
+    }
+
+    fn health(&self) -> AppendHealth {
+        AppendHealth {
+            queue_depth: Some(self.tracker.pending()),
+            ..AppendHealth::default()
+        }
+    }
+}
+
+impl Drop for SqliteAppender {
+    fn drop(&mut self) {
+        // Inserts what is still queued before the worker exits.
+        self.sender
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(worker) = self.worker.take() {
+            worker.join();
+        }
+    }
+}
+
+/// A builder for `SqliteAppender`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SqliteAppenderBuilder {
+    table: String,
+    batch_interval: Duration,
+    max_batch: usize,
+}
+
+impl SqliteAppenderBuilder {
+    /// Sets the name of the table records are inserted into.
+    ///
+    /// The name may only contain ASCII letters, digits and underscores.
+    /// Defaults to `logs`.
+    pub fn table<T>(mut self, table: T) -> SqliteAppenderBuilder
+    where
+        T: Into<String>,
+    {
+        self.table = table.into();
+        self
+    }
+
+    /// Sets the longest a record waits to be inserted.
+    ///
+    /// Defaults to 500 milliseconds.
+    pub fn batch_interval(mut self, batch_interval: Duration) -> SqliteAppenderBuilder {
+        self.batch_interval = batch_interval;
+        self
+    }
+
+    /// Sets the number of records which are inserted as soon as they are
+    /// queued, without waiting for the batch interval.
+    ///
+    /// Defaults to 256.
+    pub fn max_batch(mut self, max_batch: usize) -> SqliteAppenderBuilder {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Consumes the `SqliteAppenderBuilder`, producing a `SqliteAppender`
+    /// which inserts records into the database at `path`.
+    ///
+    /// The database and its table are created if they do not exist.
+    pub fn build<P: AsRef<Path>>(self, path: P) -> anyhow::Result<SqliteAppender> {
+        let valid = !self.table.is_empty()
+            && !self.table.starts_with(|c: char| c.is_ascii_digit())
+            && self
+                .table
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            anyhow::bail!("invalid SQLite table name `{}`", self.table);
+        }
+
+        let mut connection = Connection::open(path)?;
+        connection.busy_timeout(Duration::from_secs(5))?;
+        connection.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        connection.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    time TEXT NOT NULL,
+                    level TEXT NOT NULL,
+                    target TEXT NOT NULL,
+                    message TEXT NOT NULL,
+                    fields TEXT
+                )",
+                self.table
+            ),
+            [],
+        )?;
+
+        let tracker = Arc::new(FlushTracker::new());
+        let insert = format!(
+            "INSERT INTO {} (time, level, target, message, fields) VALUES (?1, ?2, ?3, ?4, ?5)",
+            self.table
+        );
+        let (sender, worker) = background::batches(
+            "log4rs sqlite",
+            tracker.clone(),
+            self.batch_interval,
+            self.max_batch,
+            move |batch| {
+                if let Err(e) = insert_batch(&mut connection, &insert, batch) {
+                    crate::handle_error(
+                        &anyhow::Error::new(e).context("error inserting records into SQLite"),
+                    );
+                }
+            },
+        )?;
+
+        Ok(SqliteAppender {
+            sender: Mutex::new(Some(sender)),
+            tracker,
+            worker: Some(worker),
+        })
+    }
+}
+
+fn insert_batch(connection: &mut Connection, insert: &str, batch: &[Row]) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    {
+        let mut statement = transaction.prepare_cached(insert)?;
+        for row in batch {
+            statement.execute(params![
+                row.time,
+                row.level,
+                row.target,
+                row.message,
+                row.fields
+            ])?;
+        }
+    }
+    transaction.commit()
+}
+
+/// Returns the key-value pairs of a record as a JSON object, if it has any.
+fn fields(source: &dyn kv::Source) -> Option<String> {
+    struct Fields(Map<String, Json>);
+
+    impl<'kvs> VisitSource<'kvs> for Fields {
+        fn visit_pair(
+            &mut self,
+            key: kv::Key<'kvs>,
+            value: kv::Value<'kvs>,
+        ) -> Result<(), kv::Error> {
+            self.0
+                .insert(key.as_str().to_owned(), Json::String(value.to_string()));
+            Ok(())
+        }
+    }
+
+    if source.count() == 0 {
+        return None;
+    }
+    let mut fields = Fields(Map::new());
+    let _ = source.visit(&mut fields);
+    Some(Json::Object(fields.0).to_string())
+}
+
+/// A deserializer for the `SqliteAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: sqlite
+///
+/// # The path of the database. Required.
+/// path: log/app.db
+///
+/// # The table records are inserted into, which is created if it does not
+/// # exist. Defaults to `logs`.
+/// table: logs
+///
+/// # The longest a record waits to be inserted. Defaults to 500 milliseconds.
+/// batch_interval: 500 milliseconds
+///
+/// # The number of queued records which are inserted without waiting for the
+/// # batch interval. Defaults to 256.
+/// max_batch: 256
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SqliteAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for SqliteAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = SqliteAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: SqliteAppenderConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut builder = SqliteAppender::builder();
+        if let Some(table) = config.table {
+            builder = builder.table(table);
+        }
+        if let Some(batch_interval) = config.batch_interval {
+            builder = builder.batch_interval(batch_interval);
+        }
+        if let Some(max_batch) = config.max_batch {
+            builder = builder.max_batch(max_batch);
+        }
+        Ok(Box::new(builder.build(&config.path)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn insert() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.db");
+        let appender = SqliteAppender::builder()
+            .table("events")
+            .batch_interval(Duration::from_secs(60))
+            .build(&path)
+            .unwrap();
+
+        let kvs = [("user", "alice")];
+        appender
+            .append(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("checkout")
+                    .args(format_args!("payment retried"))
+                    .key_values(&kvs)
+                    .build(),
+            )
+            .unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("done")).build())
+            .unwrap();
+        appender.flush();
+
+        let connection = Connection::open(&path).unwrap();
+        let mut statement = connection
+            .prepare("SELECT level, target, message, fields FROM events ORDER BY rowid")
+            .unwrap();
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "WARN".to_owned(),
+                    "checkout".to_owned(),
+                    "payment retried".to_owned(),
+                    Some(r#"{"user":"alice"}"#.to_owned()),
+                ),
+                ("INFO".to_owned(), "".to_owned(), "done".to_owned(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_table() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(SqliteAppender::builder()
+            .table("logs; DROP TABLE users")
+            .build(dir.path().join("app.db"))
+            .is_err());
+    }
+}
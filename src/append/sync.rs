@@ -0,0 +1,175 @@
+//! Syncing the log files of the file appenders to disk.
+
+use std::{
+    fs::File,
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use super::Durability;
+
+/// How often `Durability::Batched` syncs the file by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A log file which can be synced to disk.
+pub trait SyncData {
+    /// Returns the file written to.
+    fn file(&self) -> &File;
+
+    /// Syncs the data written to the file to disk.
+    fn sync_data(&self) -> io::Result<()> {
+        self.file().sync_data()
+    }
+
+    /// Waits for the writes handed to the operating system
+    /// asynchronously to complete.
+    fn complete(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SyncData for File {
+    fn file(&self) -> &File {
+        self
+    }
+}
+
+/// Syncs a log file to disk as its `Durability` requires, timing the
+/// syncs, and preallocates space for it.
+#[derive(Debug)]
+pub struct Syncer {
+    durability: Durability,
+    interval: Duration,
+    preallocate: Option<u64>,
+    // The bytes left of the space last preallocated, as far as is known.
+    preallocated: AtomicU64,
+    start: Instant,
+    // In nanoseconds since `start`.
+    last_sync: AtomicU64,
+    syncs: AtomicU64,
+    sync_nanos: AtomicU64,
+}
+
+impl Syncer {
+    pub fn new(durability: Durability, interval: Duration, preallocate: Option<u64>) -> Syncer {
+        Syncer {
+            durability,
+            interval,
+            preallocate,
+            preallocated: AtomicU64::new(0),
+            start: Instant::now(),
+            last_sync: AtomicU64::new(0),
+            syncs: AtomicU64::new(0),
+            sync_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Called when a new log file has been opened.
+    pub fn opened(&self) {
+        self.preallocated.store(0, Ordering::Relaxed);
+    }
+
+    /// Called once `bytes` bytes of a record have been written and
+    /// flushed to `file`.
+    pub fn written<F>(&self, file: &F, bytes: u64) -> io::Result<()>
+    where
+        F: SyncData + ?Sized,
+    {
+        if let Some(preallocate) = self.preallocate {
+            let left = self.preallocated.load(Ordering::Relaxed);
+            if left <= bytes {
+                self::preallocate(file.file(), preallocate)?;
+                self.preallocated.store(preallocate, Ordering::Relaxed);
+            } else {
+                self.preallocated.store(left - bytes, Ordering::Relaxed);
+            }
+        }
+
+        match self.durability {
+            Durability::None | Durability::OnFlush => Ok(()),
+            Durability::Batched => {
+                let since_sync =
+                    self.start.elapsed().as_nanos() as u64 - self.last_sync.load(Ordering::Relaxed);
+                if since_sync >= self.interval.as_nanos() as u64 {
+                    self.sync(file)
+                } else {
+                    Ok(())
+                }
+            }
+            Durability::Strict => self.sync(file),
+        }
+    }
+
+    /// Called when the appender is flushed, once `file` has been flushed.
+    pub fn flush<F>(&self, file: &F) -> io::Result<()>
+    where
+        F: SyncData + ?Sized,
+    {
+        file.complete()?;
+        match self.durability {
+            Durability::None => Ok(()),
+            Durability::OnFlush | Durability::Batched | Durability::Strict => self.sync(file),
+        }
+    }
+
+    fn sync<F>(&self, file: &F) -> io::Result<()>
+    where
+        F: SyncData + ?Sized,
+    {
+        let start = Instant::now();
+        file.sync_data()?;
+        self.syncs.fetch_add(1, Ordering::Relaxed);
+        self.sync_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.last_sync
+            .store(self.start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the number of syncs and the time spent in them, if the
+    /// file is synced at all.
+    pub fn stats(&self) -> (Option<u64>, Option<Duration>) {
+        match self.durability {
+            Durability::None => (None, None),
+            Durability::OnFlush | Durability::Batched | Durability::Strict => (
+                Some(self.syncs.load(Ordering::Relaxed)),
+                Some(Duration::from_nanos(
+                    self.sync_nanos.load(Ordering::Relaxed),
+                )),
+            ),
+        }
+    }
+}
+
+/// Reserves disk space for the next `len` bytes appended to `file`
+/// without changing its length, so that appending to it does not have to
+/// allocate blocks. Does nothing where this is not supported.
+#[cfg(target_os = "linux")]
+fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let end = file.metadata()?.len();
+    let ret = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_KEEP_SIZE,
+            end as libc::off_t,
+            len as libc::off_t,
+        )
+    };
+    if ret == 0 {
+        return Ok(());
+    }
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        // the file system cannot preallocate
+        Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => Ok(()),
+        _ => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn preallocate(_: &File, _: u64) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! Reading back the end of the log files of the file appenders.
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use super::Tail;
+
+const CHUNK: u64 = 8 * 1024;
+
+/// The most bytes read from the end of a file.
+pub const MAX_TAIL: u64 = 16 * 1024 * 1024;
+
+/// Opens the file at `path` to read its tail, returning it with the
+/// number of bytes it holds: `written` if the writer knows it, or else
+/// the file's length.
+///
+/// The length is captured when the file is opened, so that the tail can
+/// be read without blocking writes and never ends partway through a
+/// record written after it was opened.
+pub fn open_tail(path: &Path, written: Option<u64>) -> io::Result<(File, u64)> {
+    let file = File::open(path)?;
+    let len = match written {
+        Some(len) => len,
+        None => file.metadata()?.len(),
+    };
+    Ok((file, len))
+}
+
+/// Reads the last portion of the first `len` bytes of `file`, and at most
+/// its last `MAX_TAIL` bytes.
+pub fn read_tail(mut file: File, len: u64, tail: Tail) -> io::Result<Vec<u8>> {
+    let floor = len.saturating_sub(MAX_TAIL);
+    let lines = match tail {
+        Tail::Bytes(n) => {
+            let mut buf = vec![];
+            file.seek(SeekFrom::Start(len.saturating_sub(n).max(floor)))?;
+            file.take(n.min(len - floor)).read_to_end(&mut buf)?;
+            return Ok(buf);
+        }
+        Tail::Lines(0) => return Ok(vec![]),
+        Tail::Lines(n) => n,
+    };
+
+    // The chunks read so far, from the end of the file backwards, each
+    // scanned for newlines once.
+    let mut chunks = vec![];
+    let mut pos = len;
+    let mut start = floor;
+    let mut newlines = 0;
+    while pos > floor {
+        let size = CHUNK.min(pos - floor);
+        pos -= size;
+        let mut chunk = vec![0; size as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut chunk)?;
+
+        // A newline terminating the file doesn't start another line.
+        let end = if pos + size == len && chunk.last() == Some(&b'\n') {
+            chunk.len() - 1
+        } else {
+            chunk.len()
+        };
+        let mut found = None;
+        for (i, _) in chunk[..end]
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, &b)| b == b'\n')
+        {
+            newlines += 1;
+            if newlines == lines {
+                found = Some(i);
+                break;
+            }
+        }
+        chunks.push(chunk);
+        if let Some(i) = found {
+            start = pos + i as u64 + 1;
+            break;
+        }
+    }
+
+    let mut buf = Vec::with_capacity((len - pos) as usize);
+    for chunk in chunks.iter().rev() {
+        buf.extend_from_slice(chunk);
+    }
+    buf.drain(..(start - pos) as usize);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    fn tail_of(path: &Path, tail: Tail) -> Vec<u8> {
+        let (file, len) = open_tail(path, None).unwrap();
+        read_tail(file, len, tail).unwrap()
+    }
+
+    #[test]
+    fn lines_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let lines = (0..5000).map(|i| format!("{}\n", i)).collect::<Vec<_>>();
+        fs::write(&path, lines.concat()).unwrap();
+
+        assert_eq!(tail_of(&path, Tail::Lines(1)), b"4999\n");
+        assert_eq!(
+            tail_of(&path, Tail::Lines(3000)),
+            lines[2000..].concat().into_bytes()
+        );
+        assert_eq!(
+            tail_of(&path, Tail::Lines(6000)),
+            lines.concat().into_bytes()
+        );
+    }
+
+    #[test]
+    fn capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let line = [b'x'; 1023]
+            .iter()
+            .chain(b"\n")
+            .copied()
+            .collect::<Vec<_>>();
+        fs::write(&path, line.repeat(MAX_TAIL as usize / 1024 + 4)).unwrap();
+
+        assert_eq!(tail_of(&path, Tail::Bytes(u64::MAX)).len() as u64, MAX_TAIL);
+        assert_eq!(
+            tail_of(&path, Tail::Lines(usize::MAX)).len() as u64,
+            MAX_TAIL
+        );
+        assert_eq!(tail_of(&path, Tail::Bytes(5)), b"xxxx\n");
+    }
+}
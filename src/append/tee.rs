@@ -0,0 +1,436 @@
+//! The tee appender.
+//!
+//! Requires the `tee_appender` feature.
+
+use log::Record;
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::{
+    append::{flush::FlushToken, Append},
+    background,
+    filter::{summary, Filter, Response},
+};
+#[cfg(feature = "config_parsing")]
+use crate::{
+    config::{Deserialize, Deserializers},
+    filter::FilterConfig,
+};
+
+/// The tee appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeeAppenderConfig {
+    appenders: Vec<ChildConfig>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct ChildConfig {
+    kind: String,
+    filters: Vec<FilterConfig>,
+    config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for ChildConfig {
+    fn deserialize<D>(d: D) -> Result<ChildConfig, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        let filters = match map.remove(&Value::String("filters".to_owned())) {
+            Some(filters) => filters.deserialize_into().map_err(|e| e.to_error())?,
+            None => vec![],
+        };
+
+        Ok(ChildConfig {
+            kind,
+            filters,
+            config: Value::Map(map),
+        })
+    }
+}
+
+/// An appender which writes each record to several child appenders, each
+/// with its own filters.
+///
+/// A record is written to every child whose filters accept it, in the order
+/// the children were added. A child which fails does not stop the record from
+/// reaching the others; the first error is returned once every child has been
+/// tried, and any further errors are reported to stderr.
+///
+/// Children with different encoders write the same records in several
+/// formats, such as readable lines to the console and JSON to a file, while
+/// the loggers and filters refer to the tee alone.
+#[derive(Debug)]
+pub struct TeeAppender {
+    children: Arc<Vec<Child>>,
+    // Sweep the filters of the children until dropped.
+    _timers: Vec<background::Timer>,
+}
+
+#[derive(Debug)]
+struct Child {
+    filters: Vec<Box<dyn Filter>>,
+    appender: Box<dyn Append>,
+}
+
+impl Child {
+    // Sweeps one of the filters, writing the summaries it adds.
+    fn sweep(&self, filter: usize) {
+        let ((), summaries) = summary::collect(|| self.filters[filter].sweep());
+        for summary in summaries {
+            if let Err(e) = summary.with_record(|summary| self.appender.append(summary)) {
+                crate::handle_error(&e);
+            }
+        }
+    }
+}
+
+impl TeeAppender {
+    /// Creates a new `TeeAppender` builder.
+    pub fn builder() -> TeeAppenderBuilder {
+        TeeAppenderBuilder { children: vec![] }
+    }
+}
+
+impl Append for TeeAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for child in self.children.iter() {
+            let (rejected, summaries) = summary::collect(|| {
+                for filter in &child.filters {
+                    match filter.filter(record) {
+                        Response::Accept => break,
+                        Response::Neutral => {}
+                        Response::Reject => return true,
+                    }
+                }
+                false
+            });
+
+            let mut report = |appended: anyhow::Result<()>| {
+                if let Err(e) = appended {
+                    if result.is_ok() {
+                        result = Err(e);
+                    } else {
+                        crate::handle_error(&e);
+                    }
+                }
+            };
+            for summary in summaries {
+                report(summary.with_record(|summary| child.appender.append(summary)));
+            }
+            if !rejected {
+                report(child.appender.append(record));
+            }
+        }
+        result
+    }
+
+    fn flush(&self) {
+        for child in self.children.iter() {
+            child.appender.flush();
+        }
+    }
+
+    fn flush_to(&self, token: FlushToken) {
+        for child in self.children.iter() {
+            child.appender.flush_to(token);
+        }
+    }
+}
+
+/// A builder for `TeeAppender`s.
+#[derive(Debug)]
+pub struct TeeAppenderBuilder {
+    children: Vec<Child>,
+}
+
+impl TeeAppenderBuilder {
+    /// Adds a child appender which is written every record.
+    pub fn appender(self, appender: Box<dyn Append>) -> TeeAppenderBuilder {
+        self.filtered_appender(vec![], appender)
+    }
+
+    /// Adds a child appender which is written the records its filters accept.
+    pub fn filtered_appender(
+        mut self,
+        filters: Vec<Box<dyn Filter>>,
+        appender: Box<dyn Append>,
+    ) -> TeeAppenderBuilder {
+        self.children.push(Child { filters, appender });
+        self
+    }
+
+    /// Consumes the `TeeAppenderBuilder`, producing a `TeeAppender`.
+    pub fn build(self) -> TeeAppender {
+        let children = Arc::new(self.children);
+        let mut timers = vec![];
+        for idx in 0..children.len() {
+            for filter in 0..children[idx].filters.len() {
+                let interval = match children[idx].filters[filter].sweep_interval() {
+                    Some(interval) => interval,
+                    None => continue,
+                };
+                let weak = Arc::downgrade(&children);
+                let scheduled = background::schedule("log4rs sweep", interval, move || {
+                    weak.upgrade()?[idx].sweep(filter);
+                    Some(interval)
+                });
+                match scheduled {
+                    Ok(timer) => timers.push(timer),
+                    Err(e) => crate::handle_error(&e.into()),
+                }
+            }
+        }
+
+        TeeAppender {
+            children,
+            _timers: timers,
+        }
+    }
+}
+
+/// A deserializer for the `TeeAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: tee
+///
+/// # The appenders each record is written to. Required.
+/// appenders:
+///
+///     # Identifies which appender is to be used. Required.
+///   - kind: file
+///
+///     # The filters the child applies before a record is written to it,
+///     # configured like the filters of a top level appender. Defaults to no
+///     # filters.
+///     filters:
+///       - kind: threshold
+///         level: warn
+///
+///     # The remainder of the configuration is passed to the appender's
+///     # deserializer, and will vary based on the kind of appender.
+///     path: log/errors.log
+///     encoder:
+///       pattern: "{d} {l} {t} - {m}{n}"
+///
+///   - kind: console
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct TeeAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for TeeAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = TeeAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: TeeAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut builder = TeeAppender::builder();
+        for child in config.appenders {
+            let filters = child
+                .filters
+                .into_iter()
+                .map(|filter| deserializers.deserialize(&filter.kind, filter.config))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let appender = deserializers.deserialize(&child.kind, child.config)?;
+            builder = builder.filtered_appender(filters, appender);
+        }
+        Ok(Box::new(builder.build()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestAppender(Arc<Mutex<Vec<String>>>, bool);
+
+    impl Append for TestAppender {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            if self.1 {
+                anyhow::bail!("broken");
+            }
+            self.0.lock().unwrap().push(record.args().to_string());
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[derive(Debug)]
+    struct MinLevel(Level);
+
+    impl Filter for MinLevel {
+        fn filter(&self, record: &Record) -> Response {
+            if record.level() <= self.0 {
+                Response::Neutral
+            } else {
+                Response::Reject
+            }
+        }
+    }
+
+    #[test]
+    fn tee() {
+        let all = Arc::new(Mutex::new(vec![]));
+        let warnings = Arc::new(Mutex::new(vec![]));
+        let appender = TeeAppender::builder()
+            .appender(Box::new(TestAppender(all.clone(), false)))
+            .filtered_appender(
+                vec![Box::new(MinLevel(Level::Warn))],
+                Box::new(TestAppender(warnings.clone(), false)),
+            )
+            .build();
+
+        for (level, message) in [(Level::Info, "one"), (Level::Error, "two")] {
+            appender
+                .append(
+                    &Record::builder()
+                        .level(level)
+                        .args(format_args!("{}", message))
+                        .build(),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(*all.lock().unwrap(), ["one", "two"]);
+        assert_eq!(*warnings.lock().unwrap(), ["two"]);
+    }
+
+    #[test]
+    fn failing_child() {
+        let records = Arc::new(Mutex::new(vec![]));
+        let appender = TeeAppender::builder()
+            .appender(Box::new(TestAppender(Arc::default(), true)))
+            .appender(Box::new(TestAppender(records.clone(), false)))
+            .build();
+
+        assert!(appender
+            .append(&Record::builder().args(format_args!("one")).build())
+            .is_err());
+        assert_eq!(*records.lock().unwrap(), ["one"]);
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "yaml_format",
+        feature = "file_appender",
+        feature = "threshold_filter"
+    ))]
+    fn deserialize() {
+        use crate::config::RawConfig;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = format!(
+            "
+appenders:
+  tee:
+    kind: tee
+    appenders:
+      - kind: file
+        path: {dir}/all.log
+        encoder:
+          pattern: \"{{m}}\"
+      - kind: file
+        path: {dir}/errors.log
+        encoder:
+          pattern: \"{{l}} {{m}}\"
+        filters:
+          - kind: threshold
+            level: error
+",
+            dir = dir.path().display()
+        );
+        let config = ::serde_yaml::from_str::<RawConfig>(&config).unwrap();
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::default());
+        assert!(errors.is_empty());
+
+        for (level, message) in [(Level::Info, "one"), (Level::Error, "two")] {
+            appenders[0]
+                .appender()
+                .append(
+                    &Record::builder()
+                        .level(level)
+                        .args(format_args!("{}", message))
+                        .build(),
+                )
+                .unwrap();
+        }
+        let read = |name| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("all.log"), "onetwo");
+        assert_eq!(read("errors.log"), "ERROR two");
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "file_appender",
+        feature = "pattern_encoder",
+        feature = "json_encoder"
+    ))]
+    fn formats() {
+        use crate::{
+            append::file::FileAppender,
+            encode::{json::JsonEncoder, pattern::PatternEncoder},
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let appender = TeeAppender::builder()
+            .appender(Box::new(
+                FileAppender::builder()
+                    .encoder(Box::new(PatternEncoder::new("{l} {m}{n}")))
+                    .build(dir.path().join("app.log"))
+                    .unwrap(),
+            ))
+            .appender(Box::new(
+                FileAppender::builder()
+                    .encoder(Box::new(JsonEncoder::new()))
+                    .build(dir.path().join("app.json"))
+                    .unwrap(),
+            ))
+            .build();
+
+        appender
+            .append(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .args(format_args!("disk low"))
+                    .build(),
+            )
+            .unwrap();
+        appender.flush();
+
+        let read = |name| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("app.log"), "WARN disk low\n");
+        let json = read("app.json");
+        let object = serde_json::from_str::<serde_json::Value>(json.trim()).unwrap();
+        assert_eq!(object["message"], "disk low");
+        assert_eq!(object["level"], "WARN");
+    }
+}
@@ -0,0 +1,331 @@
+//! The Unix socket appender.
+//!
+//! Requires the `unix_socket_appender` feature, and is only available on Unix.
+
+use derivative::Derivative;
+use log::Record;
+use std::{
+    io::{self, Write},
+    os::unix::net::{UnixDatagram, UnixStream},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+#[cfg(feature = "config_parsing")]
+use crate::encode::EncoderConfig;
+use crate::{
+    append::{Append, AppendHealth},
+    encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+};
+
+/// The Unix socket appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnixSocketAppenderConfig {
+    path: PathBuf,
+    mode: Option<Mode>,
+    encoder: Option<EncoderConfig>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    reconnect_delay: Option<Duration>,
+}
+
+/// The type of socket records are written to.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Mode {
+    /// A stream socket, over which the encoded records are written one after
+    /// another.
+    #[default]
+    Stream,
+    /// A datagram socket, such as `/dev/log`, to which each encoded record is
+    /// sent as a datagram of its own.
+    Datagram,
+}
+
+enum Socket {
+    Stream(UnixStream),
+    Datagram(UnixDatagram),
+}
+
+impl Socket {
+    fn connect(path: &Path, mode: Mode) -> io::Result<Socket> {
+        match mode {
+            Mode::Stream => UnixStream::connect(path).map(Socket::Stream),
+            Mode::Datagram => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(path)?;
+                Ok(Socket::Datagram(socket))
+            }
+        }
+    }
+
+    fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        match *self {
+            Socket::Stream(ref mut stream) => stream.write_all(buf),
+            Socket::Datagram(ref socket) => socket.send(buf).map(|_| ()),
+        }
+    }
+}
+
+struct State {
+    socket: Option<Socket>,
+    // When connecting last failed, if the socket is not connected.
+    failed_at: Option<Instant>,
+}
+
+/// An appender which writes encoded records to a Unix domain socket, for
+/// collectors listening on a local socket.
+///
+/// The socket is connected when the first record is appended. If writing to
+/// it fails, the appender reconnects and writes the record again once. While
+/// the socket cannot be connected, records are dropped with an error, and
+/// connecting is only tried again once the reconnect delay has passed since
+/// the last attempt.
+///
+/// Records are written exactly as the encoder produces them; a JSON encoder
+/// gives a stream of newline delimited JSON objects.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct UnixSocketAppender {
+    path: PathBuf,
+    mode: Mode,
+    encoder: Box<dyn Encode>,
+    reconnect_delay: Duration,
+    #[derivative(Debug = "ignore")]
+    state: Mutex<State>,
+}
+
+impl Append for UnixSocketAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let mut buf = SimpleWriter(vec![]);
+        self.encoder.encode(&mut buf, record)?;
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref mut socket) = state.socket {
+            if socket.send(&buf.0).is_ok() {
+                return Ok(());
+            }
+            // the collector went away; try a fresh connection right away
+            state.socket = None;
+            state.failed_at = None;
+        }
+
+        if let Some(failed_at) = state.failed_at {
+            if failed_at.elapsed() < self.reconnect_delay {
+                anyhow::bail!("not connected to log socket `{}`", self.path.display());
+            }
+        }
+        match Socket::connect(&self.path, self.mode).and_then(|mut socket| {
+            socket.send(&buf.0)?;
+            Ok(socket)
+        }) {
+            Ok(socket) => {
+                state.socket = Some(socket);
+                state.failed_at = None;
+                Ok(())
+            }
+            Err(e) => {
+                state.failed_at = Some(Instant::now());
+                Err(anyhow::Error::new(e).context(format!(
+                    "error writing to log socket `{}`",
+                    self.path.display()
+                )))
+            }
+        }
+    }
+
+    fn flush(&self) {}
+
+    fn health(&self) -> AppendHealth {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        AppendHealth {
+            connected: Some(state.socket.is_some()),
+            ..AppendHealth::default()
+        }
+    }
+}
+
+impl UnixSocketAppender {
+    /// Creates a new `UnixSocketAppender` builder.
+    pub fn builder() -> UnixSocketAppenderBuilder {
+        UnixSocketAppenderBuilder {
+            mode: Mode::Stream,
+            encoder: None,
+            reconnect_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A builder for `UnixSocketAppender`s.
+pub struct UnixSocketAppenderBuilder {
+    mode: Mode,
+    encoder: Option<Box<dyn Encode>>,
+    reconnect_delay: Duration,
+}
+
+impl UnixSocketAppenderBuilder {
+    /// Sets the type of the socket.
+    ///
+    /// Defaults to `Mode::Stream`.
+    pub fn mode(mut self, mode: Mode) -> UnixSocketAppenderBuilder {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the output encoder for the `UnixSocketAppender`.
+    pub fn encoder(mut self, encoder: Box<dyn Encode>) -> UnixSocketAppenderBuilder {
+        self.encoder = Some(encoder);
+        self
+    }
+
+    /// Sets how long the appender waits after failing to connect before it
+    /// tries again.
+    ///
+    /// Defaults to 1 second.
+    pub fn reconnect_delay(mut self, reconnect_delay: Duration) -> UnixSocketAppenderBuilder {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    /// Consumes the `UnixSocketAppenderBuilder`, producing a
+    /// `UnixSocketAppender` writing to the socket at `path`.
+    ///
+    /// The socket does not need to exist yet.
+    pub fn build<P: AsRef<Path>>(self, path: P) -> UnixSocketAppender {
+        UnixSocketAppender {
+            path: path.as_ref().to_owned(),
+            mode: self.mode,
+            encoder: self
+                .encoder
+                .unwrap_or_else(|| Box::<PatternEncoder>::default()),
+            reconnect_delay: self.reconnect_delay,
+            state: Mutex::new(State {
+                socket: None,
+                failed_at: None,
+            }),
+        }
+    }
+}
+
+/// A deserializer for the `UnixSocketAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: unix_socket
+///
+/// # The path of the socket. Required.
+/// path: /run/collector.sock
+///
+/// # The type of the socket, "stream" or "datagram". Defaults to "stream".
+/// mode: stream
+///
+/// # How long to wait after failing to connect before trying again. Defaults
+/// # to 1 second.
+/// reconnect_delay: 5 seconds
+///
+/// # The encoder to use to format output. Defaults to `kind: pattern`.
+/// encoder:
+///   kind: json
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct UnixSocketAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for UnixSocketAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = UnixSocketAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: UnixSocketAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut appender = UnixSocketAppender::builder();
+        if let Some(mode) = config.mode {
+            appender = appender.mode(mode);
+        }
+        if let Some(encoder) = config.encoder {
+            appender = appender.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
+        }
+        if let Some(reconnect_delay) = config.reconnect_delay {
+            appender = appender.reconnect_delay(reconnect_delay);
+        }
+        Ok(Box::new(appender.build(config.path)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader},
+        os::unix::net::UnixListener,
+    };
+
+    use super::*;
+
+    fn record(message: &str, f: impl FnOnce(&Record)) {
+        f(&Record::builder().args(format_args!("{}", message)).build())
+    }
+
+    #[test]
+    fn stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let appender = UnixSocketAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+            .reconnect_delay(Duration::from_secs(60))
+            .build(&path);
+
+        // nothing is listening yet, and the delay holds off reconnecting
+        record("lost", |r| assert!(appender.append(r).is_err()));
+        assert_eq!(appender.health().connected, Some(false));
+        let listener = UnixListener::bind(&path).unwrap();
+        record("held off", |r| assert!(appender.append(r).is_err()));
+        appender.state.lock().unwrap().failed_at = None;
+
+        record("one", |r| appender.append(r).unwrap());
+        record("two", |r| appender.append(r).unwrap());
+        assert_eq!(appender.health().connected, Some(true));
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "one");
+        assert_eq!(lines.next().unwrap().unwrap(), "two");
+
+        // the collector restarts; the appender reconnects for the next record
+        drop(lines);
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+        let listener = UnixListener::bind(&path).unwrap();
+        record("three", |r| appender.append(r).unwrap());
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "three");
+    }
+
+    #[test]
+    fn datagram() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let appender = UnixSocketAppender::builder()
+            .mode(Mode::Datagram)
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(&path);
+
+        record("one", |r| appender.append(r).unwrap());
+        record("two", |r| appender.append(r).unwrap());
+        let mut buf = [0; 16];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"one");
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"two");
+    }
+}
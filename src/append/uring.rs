@@ -0,0 +1,243 @@
+//! The io_uring backend of the file appenders.
+
+use io_uring::{opcode, squeue, types, IoUring};
+use parking_lot::{Condvar, Mutex};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Write},
+    mem,
+    os::unix::io::AsRawFd,
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+use super::sync::SyncData;
+
+/// The most writes submitted to the ring at once.
+const ENTRIES: usize = 64;
+
+/// The most buffers handed to the thread and not yet taken by it. Once
+/// the queue is full, flushing blocks until the thread catches up.
+const QUEUE: usize = 16 * ENTRIES;
+
+/// Writes to a log file asynchronously, handing the records flushed to it
+/// to a background thread which submits them to an io_uring instance.
+///
+/// The writes of a batch are linked, so they complete in order.
+pub struct UringWriter {
+    file: Arc<File>,
+    buf: Vec<u8>,
+    shared: Arc<Shared>,
+    tx: Option<mpsc::SyncSender<Vec<u8>>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl fmt::Debug for UringWriter {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("UringWriter")
+            .field("file", &self.file)
+            .field("pending", &self.shared.state.lock().pending)
+            .finish()
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    completed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    // Buffers handed to the thread and not yet written.
+    pending: usize,
+    // The first error the thread ran into, not yet reported.
+    error: Option<io::Error>,
+    // Written buffers, kept for reuse.
+    free: Vec<Vec<u8>>,
+}
+
+impl UringWriter {
+    pub fn new(file: File) -> io::Result<UringWriter> {
+        let ring = IoUring::new(ENTRIES as u32)?;
+        let file = Arc::new(file);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            completed: Condvar::new(),
+        });
+        let (tx, rx) = mpsc::sync_channel(QUEUE);
+        let thread = thread::Builder::new()
+            .name("log4rs-io-uring".to_owned())
+            .spawn({
+                let file = file.clone();
+                let shared = shared.clone();
+                move || run(ring, &file, &rx, &shared)
+            })?;
+        Ok(UringWriter {
+            file,
+            buf: vec![],
+            shared,
+            tx: Some(tx),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the number of buffers handed to the thread and not yet
+    /// written.
+    pub fn queue_depth(&self) -> usize {
+        self.shared.state.lock().pending
+    }
+}
+
+impl Write for UringWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Hands the records written since the last flush to the background
+    /// thread, without waiting for them to be written, and returns the
+    /// error any earlier write failed with. Blocks while the thread's
+    /// queue is full.
+    fn flush(&mut self) -> io::Result<()> {
+        let mut state = self.shared.state.lock();
+        if let Some(e) = state.error.take() {
+            self.buf.clear();
+            return Err(e);
+        }
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let buf = mem::replace(&mut self.buf, state.free.pop().unwrap_or_default());
+        state.pending += 1;
+        drop(state);
+        match self.tx {
+            Some(ref tx) if tx.send(buf).is_ok() => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the io_uring thread has stopped",
+            )),
+        }
+    }
+}
+
+impl SyncData for UringWriter {
+    fn file(&self) -> &File {
+        &self.file
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        self.complete()?;
+        self.file.sync_data()
+    }
+
+    fn complete(&self) -> io::Result<()> {
+        let mut state = self.shared.state.lock();
+        // Checking on the thread keeps a panic in it from hanging the caller.
+        while state.pending > 0 && self.thread.as_ref().map_or(false, |t| !t.is_finished()) {
+            self.shared
+                .completed
+                .wait_for(&mut state, Duration::from_millis(100));
+        }
+        match state.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for UringWriter {
+    fn drop(&mut self) {
+        let mut result = self.flush();
+        // The thread writes out what it was handed before stopping.
+        self.tx = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if let Some(e) = self.shared.state.lock().error.take() {
+            result = result.and(Err(e));
+        }
+        if let Err(e) = result {
+            crate::handle_error(&anyhow::Error::new(e).context("error writing log file"));
+        }
+    }
+}
+
+fn run(mut ring: IoUring, file: &File, rx: &mpsc::Receiver<Vec<u8>>, shared: &Shared) {
+    while let Ok(buf) = rx.recv() {
+        let mut batch = vec![buf];
+        while batch.len() < ENTRIES {
+            match rx.try_recv() {
+                Ok(buf) => batch.push(buf),
+                Err(_) => break,
+            }
+        }
+
+        let len = batch.len();
+        let result = write_batch(&mut ring, file, &mut batch);
+        let mut state = shared.state.lock();
+        state.pending -= len;
+        if let Err(e) = result {
+            state.error.get_or_insert(e);
+        }
+        for mut buf in batch {
+            buf.clear();
+            if state.free.len() < ENTRIES {
+                state.free.push(buf);
+            }
+        }
+        drop(state);
+        shared.completed.notify_all();
+    }
+}
+
+/// Writes the buffers in `batch` to the end of `file`, in order.
+fn write_batch(ring: &mut IoUring, file: &File, batch: &mut Vec<Vec<u8>>) -> io::Result<()> {
+    let fd = types::Fd(file.as_raw_fd());
+    {
+        let mut sq = ring.submission();
+        for (i, buf) in batch.iter().enumerate() {
+            // An offset of -1 writes at the file's position, or its end
+            // if it is open for appending.
+            let mut entry = opcode::Write::new(fd, buf.as_ptr(), buf.len() as u32)
+                .offset(u64::MAX)
+                .build()
+                .user_data(i as u64);
+            // If a write fails or is short, the writes after it are
+            // cancelled rather than landing before the rest of it.
+            if i + 1 < batch.len() {
+                entry = entry.flags(squeue::Flags::IO_LINK);
+            }
+            unsafe { sq.push(&entry) }.expect("batch larger than the ring");
+        }
+    }
+
+    let mut written = vec![0; batch.len()];
+    let mut completed = 0;
+    while completed < batch.len() {
+        match ring.submit_and_wait(1) {
+            Ok(_) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                // The kernel may still be reading the buffers.
+                mem::forget(mem::take(batch));
+                return Err(e);
+            }
+        }
+        for cqe in ring.completion() {
+            written[cqe.user_data() as usize] = cqe.result().max(0) as usize;
+            completed += 1;
+        }
+    }
+
+    // Finish the writes which failed, were short or were cancelled, so
+    // that errors are reported as the write call reports them.
+    let mut file = file;
+    for (buf, written) in batch.iter().zip(written) {
+        if written < buf.len() {
+            file.write_all(&buf[written..])?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,868 @@
+//! Background work.
+//!
+//! log4rs does some of its work off the logging threads: publishing batches
+//! of records to Kafka, OpenTelemetry, SQLite or an alerting endpoint,
+//! rolling and compressing log files in the background, flushing appenders
+//! on an interval, heartbeats and exporting counters. By default each piece
+//! of work runs on a thread of its own.
+//!
+//! With the `tokio` feature, the work can instead run on an existing tokio
+//! runtime, by calling [`use_tokio`] before the appenders are built. Records
+//! queued for a batch or an alert are received on the runtime, and only the
+//! blocking parts of the work, such as writing to a file or sending a batch,
+//! take a thread of the runtime's blocking thread pool while they run.
+//! Dropping the runtime therefore never waits for log4rs, but stops its
+//! background work, after which records queued for a batch are no longer
+//! published. The runtime must have its time driver enabled, and should be
+//! multi-threaded: the thread of a current-thread runtime cannot wait for
+//! work on that runtime, so appenders flushed or dropped there report an
+//! error rather than waiting for the records they have queued.
+//!
+//! Work started before `use_tokio` is called keeps its threads, as do the
+//! non-blocking console writer, the io_uring writer, process capture and the
+//! reloading of config files, which wait on the operating system rather than
+//! on timers and queues.
+
+use std::{
+    fmt, io,
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "tokio")]
+use std::cell::Cell;
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+use crate::append::flush::{FlushToken, FlushTracker};
+
+#[cfg(feature = "tokio")]
+static RUNTIME: std::sync::Mutex<Option<tokio::runtime::Handle>> = std::sync::Mutex::new(None);
+
+/// Runs log4rs's background work started from now on on the tokio runtime
+/// behind `handle`, rather than on threads of its own.
+///
+/// Requires the `tokio` feature.
+#[cfg(feature = "tokio")]
+pub fn use_tokio(handle: tokio::runtime::Handle) {
+    *RUNTIME.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle);
+}
+
+/// Runs log4rs's background work started from now on on threads of its own,
+/// which is the default.
+///
+/// Requires the `tokio` feature.
+#[cfg(feature = "tokio")]
+pub fn use_threads() {
+    *RUNTIME.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Where background work runs.
+#[derive(Clone, Debug)]
+pub(crate) enum Executor {
+    Threads,
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::runtime::Handle),
+}
+
+impl Executor {
+    /// Returns the executor selected by `use_tokio` or `use_threads`.
+    pub fn current() -> Executor {
+        #[cfg(feature = "tokio")]
+        if let Some(ref handle) = *RUNTIME.lock().unwrap_or_else(|e| e.into_inner()) {
+            return Executor::Tokio(handle.clone());
+        }
+        Executor::Threads
+    }
+
+    /// Runs `f` once.
+    pub fn spawn<F>(&self, name: &str, f: F) -> io::Result<Task>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match self {
+            Executor::Threads => {
+                let (done, task) = Task::new();
+                thread::Builder::new()
+                    .name(name.to_owned())
+                    .spawn(move || {
+                        let _done = done;
+                        f()
+                    })?;
+                Ok(task)
+            }
+            #[cfg(feature = "tokio")]
+            Executor::Tokio(handle) => {
+                let (done, mut task) = Task::new();
+                task.on_runtime = true;
+                handle.spawn_blocking(move || {
+                    let _done = done;
+                    in_pool();
+                    f()
+                });
+                Ok(task)
+            }
+        }
+    }
+
+    /// Passes the items sent to the returned `Sender` to `worker`, running it
+    /// when it is due, until every `Sender` is dropped.
+    ///
+    /// On a tokio runtime, the items are received on the runtime, and the
+    /// worker only takes a thread of the blocking thread pool while it runs.
+    #[cfg(any(
+        feature = "alert_appender",
+        feature = "kafka",
+        feature = "otel",
+        feature = "sqlite"
+    ))]
+    pub fn serve<T, W>(
+        &self,
+        name: &str,
+        tracker: Arc<FlushTracker>,
+        mut worker: W,
+    ) -> io::Result<(Sender<T>, Task)>
+    where
+        T: Send + 'static,
+        W: Worker<T>,
+    {
+        match self {
+            Executor::Threads => {
+                let (sender, receiver) = mpsc::channel();
+                let mut queue = Queue::new(receiver, tracker);
+                let task = self.spawn(name, move || loop {
+                    let received = match worker.due() {
+                        Some(due) => match queue
+                            .receiver
+                            .recv_timeout(due.saturating_duration_since(Instant::now()))
+                        {
+                            Ok(message) => Received::Message(message),
+                            Err(mpsc::RecvTimeoutError::Timeout) => Received::Timeout,
+                            Err(mpsc::RecvTimeoutError::Disconnected) => Received::Disconnected,
+                        },
+                        None => queue
+                            .receiver
+                            .recv()
+                            .map_or(Received::Disconnected, Received::Message),
+                    };
+                    let step = queue.take(received, &mut worker);
+                    if let Step::Wait = step {
+                        continue;
+                    }
+                    worker.run();
+                    queue.ran();
+                    if let Step::Stop = step {
+                        break;
+                    }
+                })?;
+                Ok((Sender(Sending::Thread(sender)), task))
+            }
+            #[cfg(feature = "tokio")]
+            Executor::Tokio(handle) => {
+                let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+                let mut queue = Queue::new(receiver, tracker);
+                let (done, mut task) = Task::new();
+                task.on_runtime = true;
+                handle.spawn(async move {
+                    let _done = done;
+                    loop {
+                        let received = match worker.due() {
+                            Some(due) => {
+                                match tokio::time::timeout_at(due.into(), queue.receiver.recv())
+                                    .await
+                                {
+                                    Ok(message) => {
+                                        message.map_or(Received::Disconnected, Received::Message)
+                                    }
+                                    Err(_) => Received::Timeout,
+                                }
+                            }
+                            None => queue
+                                .receiver
+                                .recv()
+                                .await
+                                .map_or(Received::Disconnected, Received::Message),
+                        };
+                        let step = queue.take(received, &mut worker);
+                        if let Step::Wait = step {
+                            continue;
+                        }
+                        let ran = tokio::task::spawn_blocking(move || {
+                            in_pool();
+                            worker.run();
+                            worker
+                        });
+                        worker = match ran.await {
+                            Ok(worker) => worker,
+                            Err(_) => break,
+                        };
+                        queue.ran();
+                        if let Step::Stop = step {
+                            break;
+                        }
+                    }
+                });
+                Ok((Sender(Sending::Tokio(sender)), task))
+            }
+        }
+    }
+
+    /// Runs `f` after `delay`, and again after each delay it returns, until it
+    /// returns `None` or the returned `Timer` is dropped.
+    pub fn schedule<F>(&self, name: &str, delay: Duration, mut f: F) -> io::Result<Timer>
+    where
+        F: FnMut() -> Option<Duration> + Send + 'static,
+    {
+        match self {
+            Executor::Threads => {
+                let (stop, stopped) = mpsc::channel::<()>();
+                self.spawn(name, move || {
+                    let mut deadline = Instant::now() + delay;
+                    let mut detached = false;
+                    loop {
+                        let delay = deadline.saturating_duration_since(Instant::now());
+                        if detached {
+                            thread::sleep(delay);
+                        } else {
+                            match stopped.recv_timeout(delay) {
+                                Ok(()) => {
+                                    detached = true;
+                                    continue;
+                                }
+                                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                            }
+                        }
+                        match f() {
+                            Some(next) => deadline = Instant::now() + next,
+                            None => break,
+                        }
+                    }
+                })?;
+                Ok(Timer(Some(Stop::Thread(stop))))
+            }
+            #[cfg(feature = "tokio")]
+            Executor::Tokio(handle) => {
+                let task = handle.spawn(async move {
+                    let mut delay = delay;
+                    loop {
+                        tokio::time::sleep(delay).await;
+                        let ran = tokio::task::spawn_blocking(move || {
+                            in_pool();
+                            let next = f();
+                            (f, next)
+                        });
+                        match ran.await {
+                            Ok((next_f, Some(next))) => {
+                                f = next_f;
+                                delay = next;
+                            }
+                            _ => break,
+                        }
+                    }
+                });
+                Ok(Timer(Some(Stop::Tokio(task))))
+            }
+        }
+    }
+}
+
+/// Work on the items sent to it, started by `serve`.
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+pub(crate) trait Worker<T>: Send + 'static {
+    /// Takes an item, returning true if the work is due at once.
+    fn push(&mut self, item: T) -> bool;
+
+    /// Returns the time the work is due at, if it is waiting for one.
+    fn due(&self) -> Option<Instant>;
+
+    /// Works on the items taken so far, blocking.
+    ///
+    /// Called when the work is due, when a flush is requested and once every
+    /// sender has been dropped. The flush tokens of the items are completed
+    /// once it returns.
+    fn run(&mut self);
+}
+
+/// Collects items into batches and passes them to a function.
+///
+/// A batch is passed on once it holds `max_batch` items, once its first item
+/// has waited for `interval`, or when a flush is requested.
+#[cfg(any(feature = "kafka", feature = "otel", feature = "sqlite"))]
+pub(crate) struct Batches<T, F> {
+    batch: Vec<T>,
+    due: Option<Instant>,
+    interval: Duration,
+    max_batch: usize,
+    publish: F,
+}
+
+#[cfg(any(feature = "kafka", feature = "otel", feature = "sqlite"))]
+impl<T, F> Batches<T, F> {
+    pub fn new(interval: Duration, max_batch: usize, publish: F) -> Batches<T, F> {
+        Batches {
+            batch: vec![],
+            due: None,
+            interval,
+            max_batch,
+            publish,
+        }
+    }
+}
+
+#[cfg(any(feature = "kafka", feature = "otel", feature = "sqlite"))]
+impl<T, F> Worker<T> for Batches<T, F>
+where
+    T: Send + 'static,
+    F: FnMut(&[T]) + Send + 'static,
+{
+    fn push(&mut self, item: T) -> bool {
+        if self.batch.is_empty() {
+            self.due = Some(Instant::now() + self.interval);
+        }
+        self.batch.push(item);
+        self.batch.len() >= self.max_batch
+    }
+
+    fn due(&self) -> Option<Instant> {
+        self.due
+    }
+
+    fn run(&mut self) {
+        if !self.batch.is_empty() {
+            (self.publish)(&self.batch);
+            self.batch.clear();
+        }
+        self.due = None;
+    }
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+/// A message to work started by `serve`.
+pub(crate) enum Message<T> {
+    /// An item to work on, along with the token to complete once it has
+    /// been.
+    Item(FlushToken, T),
+    /// Runs the work on the items received so far now.
+    Flush,
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+/// The sending end of the messages to work started by `serve`.
+pub(crate) struct Sender<T>(Sending<T>);
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+enum Sending<T> {
+    Thread(mpsc::Sender<Message<T>>),
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::sync::mpsc::UnboundedSender<Message<T>>),
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+impl<T> Sender<T> {
+    /// Sends a message, returning false if the work has stopped.
+    pub fn send(&self, message: Message<T>) -> bool {
+        match self.0 {
+            Sending::Thread(ref sender) => sender.send(message).is_ok(),
+            #[cfg(feature = "tokio")]
+            Sending::Tokio(ref sender) => sender.send(message).is_ok(),
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Sender").finish()
+    }
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+/// The receiving end of the messages to work started by `serve`.
+struct Queue<R> {
+    receiver: R,
+    tracker: Arc<FlushTracker>,
+    // The token of the last item taken, until the work has run on it.
+    last: Option<FlushToken>,
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+impl<R> Queue<R> {
+    fn new(receiver: R, tracker: Arc<FlushTracker>) -> Queue<R> {
+        Queue {
+            receiver,
+            tracker,
+            last: None,
+        }
+    }
+
+    // Passes what was received on to the worker, returning what to do next.
+    fn take<T, W>(&mut self, received: Received<T>, worker: &mut W) -> Step
+    where
+        W: Worker<T>,
+    {
+        match received {
+            Received::Message(Message::Item(token, item)) => {
+                self.last = Some(token);
+                if worker.push(item) {
+                    Step::Run
+                } else {
+                    Step::Wait
+                }
+            }
+            Received::Message(Message::Flush) | Received::Timeout => Step::Run,
+            Received::Disconnected => Step::Stop,
+        }
+    }
+
+    // Completes the tokens of the items the worker has run on.
+    fn ran(&mut self) {
+        if let Some(token) = self.last.take() {
+            self.tracker.complete(token);
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+impl<R> Drop for Queue<R> {
+    // If the work is stopped early, by the shutdown of its runtime, the items
+    // it still holds are lost, but flushes must not wait for them.
+    fn drop(&mut self) {
+        self.tracker.complete(FlushToken::issue());
+    }
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+enum Received<T> {
+    Message(Message<T>),
+    Timeout,
+    Disconnected,
+}
+
+#[cfg(any(
+    feature = "alert_appender",
+    feature = "kafka",
+    feature = "otel",
+    feature = "sqlite"
+))]
+enum Step {
+    Wait,
+    Run,
+    Stop,
+}
+
+/// Passes the items sent to the returned `Sender` to `worker` on the current
+/// executor.
+#[cfg(feature = "alert_appender")]
+pub(crate) fn serve<T, W>(
+    name: &str,
+    tracker: Arc<FlushTracker>,
+    worker: W,
+) -> io::Result<(Sender<T>, Task)>
+where
+    T: Send + 'static,
+    W: Worker<T>,
+{
+    Executor::current().serve(name, tracker, worker)
+}
+
+/// Collects the items sent to the returned `Sender` into batches and passes
+/// them to `publish` on the current executor.
+#[cfg(any(feature = "kafka", feature = "otel", feature = "sqlite"))]
+pub(crate) fn batches<T, F>(
+    name: &str,
+    tracker: Arc<FlushTracker>,
+    interval: Duration,
+    max_batch: usize,
+    publish: F,
+) -> io::Result<(Sender<T>, Task)>
+where
+    T: Send + 'static,
+    F: FnMut(&[T]) + Send + 'static,
+{
+    Executor::current().serve(name, tracker, Batches::new(interval, max_batch, publish))
+}
+
+#[cfg(feature = "tokio")]
+thread_local! {
+    // Whether the current thread is in the blocking thread pool of a tokio
+    // runtime, rather than one of the threads running its tasks, as far as
+    // log4rs knows.
+    static POOL_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+// Marks the current thread as one of the blocking thread pool.
+#[cfg(feature = "tokio")]
+fn in_pool() {
+    POOL_THREAD.with(|pool_thread| pool_thread.set(true));
+}
+
+/// Runs `f` once on the current executor.
+pub(crate) fn spawn<F>(name: &str, f: F) -> io::Result<Task>
+where
+    F: FnOnce() + Send + 'static,
+{
+    Executor::current().spawn(name, f)
+}
+
+/// Runs `f` after `delay`, and again after each delay it returns, on the
+/// current executor.
+pub(crate) fn schedule<F>(name: &str, delay: Duration, f: F) -> io::Result<Timer>
+where
+    F: FnMut() -> Option<Duration> + Send + 'static,
+{
+    Executor::current().schedule(name, delay, f)
+}
+
+/// Background work which can be waited for.
+///
+/// Dropping the `Task` leaves the work running, and its clones wait for the
+/// same work.
+#[derive(Clone, Debug)]
+pub(crate) struct Task {
+    done: Arc<(Mutex<bool>, Condvar)>,
+    // Whether the work runs on a tokio runtime.
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(
+        not(any(
+            feature = "alert_appender",
+            feature = "kafka",
+            feature = "otel",
+            feature = "sqlite"
+        )),
+        allow(dead_code)
+    )]
+    on_runtime: bool,
+}
+
+impl Task {
+    // The work holds the `Done` until it finishes or panics.
+    fn new() -> (Done, Task) {
+        let done = Arc::new((Mutex::new(false), Condvar::new()));
+        let task = Task {
+            done: done.clone(),
+            #[cfg(feature = "tokio")]
+            on_runtime: false,
+        };
+        (Done(done), task)
+    }
+
+    /// Waits for the work to finish.
+    ///
+    /// See `wait` for where it cannot be waited for.
+    #[cfg(any(
+        feature = "alert_appender",
+        feature = "kafka",
+        feature = "otel",
+        feature = "sqlite"
+    ))]
+    pub fn join(self) {
+        self.wait(|| {
+            let (ref lock, ref cvar) = *self.done;
+            let done = lock.lock().unwrap_or_else(|e| e.into_inner());
+            drop(cvar.wait_while(done, |done| !*done));
+        });
+    }
+
+    /// Calls `f`, which blocks until the work has made progress.
+    ///
+    /// On a multi-threaded tokio runtime, the runtime is told that the
+    /// thread blocks. The thread of a current-thread runtime cannot block on
+    /// work running on it, so there `f` is not called, and the error handler
+    /// is told instead.
+    #[cfg(any(
+        feature = "alert_appender",
+        feature = "kafka",
+        feature = "otel",
+        feature = "sqlite"
+    ))]
+    pub fn wait<F>(&self, f: F)
+    where
+        F: FnOnce(),
+    {
+        #[cfg(feature = "tokio")]
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            if !POOL_THREAD.with(Cell::get) {
+                match handle.runtime_flavor() {
+                    tokio::runtime::RuntimeFlavor::CurrentThread => {
+                        if self.on_runtime {
+                            crate::handle_error(&anyhow::anyhow!(
+                                "cannot wait for background work from the thread of the \
+                                 current-thread tokio runtime it runs on"
+                            ));
+                            return;
+                        }
+                    }
+                    _ => return tokio::task::block_in_place(f),
+                }
+            }
+        }
+        f()
+    }
+
+    /// Returns whether the work has finished.
+    pub fn is_finished(&self) -> bool {
+        let (ref lock, _) = *self.done;
+        *lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Waits for the work to finish for at most `timeout`, returning false
+    /// if it has not.
+    pub fn join_timeout(self, timeout: Duration) -> bool {
+        let (ref lock, ref cvar) = *self.done;
+        let done = lock.lock().unwrap_or_else(|e| e.into_inner());
+        match cvar.wait_timeout_while(done, timeout, |done| !*done) {
+            Ok((done, _)) => *done,
+            Err(e) => *e.into_inner().0,
+        }
+    }
+}
+
+// Marks a `Task` as finished when dropped.
+struct Done(Arc<(Mutex<bool>, Condvar)>);
+
+impl Drop for Done {
+    fn drop(&mut self) {
+        let (ref lock, ref cvar) = *self.0;
+        *lock.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        cvar.notify_all();
+    }
+}
+
+/// Work started by `schedule`, which stops when this is dropped.
+pub(crate) struct Timer(Option<Stop>);
+
+enum Stop {
+    Thread(mpsc::Sender<()>),
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::task::JoinHandle<()>),
+}
+
+impl Timer {
+    /// Leaves the work running until its function returns `None`.
+    pub fn detach(mut self) {
+        match self.0.take() {
+            Some(Stop::Thread(stop)) => {
+                let _ = stop.send(());
+            }
+            #[cfg(feature = "tokio")]
+            Some(Stop::Tokio(_)) => {}
+            None => {}
+        }
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Timer").finish()
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        match self.0.take() {
+            Some(Stop::Thread(stop)) => drop(stop),
+            #[cfg(feature = "tokio")]
+            Some(Stop::Tokio(task)) => task.abort(),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[cfg(feature = "tokio")]
+    type Runtime = Option<tokio::runtime::Runtime>;
+    #[cfg(not(feature = "tokio"))]
+    type Runtime = Option<()>;
+
+    // The executors to test, along with the runtime to drive.
+    fn executors() -> Vec<(Executor, Runtime)> {
+        #[allow(unused_mut)]
+        let mut executors = vec![(Executor::Threads, None)];
+        #[cfg(feature = "tokio")]
+        {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .unwrap();
+            executors.push((Executor::Tokio(runtime.handle().clone()), Some(runtime)));
+        }
+        executors
+    }
+
+    // Gives the work time to run, driving the runtime if there is one.
+    fn wait(runtime: &Runtime, duration: Duration) {
+        match runtime {
+            #[cfg(feature = "tokio")]
+            Some(runtime) => runtime.block_on(async { tokio::time::sleep(duration).await }),
+            _ => thread::sleep(duration),
+        }
+    }
+
+    #[test]
+    fn spawn() {
+        for (executor, _runtime) in executors() {
+            let count = Arc::new(AtomicUsize::new(0));
+            let task = executor
+                .spawn("test", {
+                    let count = count.clone();
+                    move || {
+                        count.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+                .unwrap();
+            assert!(task.join_timeout(Duration::from_secs(5)));
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    #[test]
+    fn schedule() {
+        for (executor, runtime) in executors() {
+            let count = Arc::new(AtomicUsize::new(0));
+            let timer = executor
+                .schedule("test", Duration::from_millis(5), {
+                    let count = count.clone();
+                    move || {
+                        count.fetch_add(1, Ordering::SeqCst);
+                        Some(Duration::from_millis(5))
+                    }
+                })
+                .unwrap();
+            wait(&runtime, Duration::from_millis(100));
+            drop(timer);
+            wait(&runtime, Duration::from_millis(20));
+            let ticks = count.load(Ordering::SeqCst);
+            assert!(ticks >= 2);
+
+            // dropping the timer stopped it
+            wait(&runtime, Duration::from_millis(50));
+            assert_eq!(count.load(Ordering::SeqCst), ticks);
+
+            // a detached timer runs until it is done
+            let timer = executor
+                .schedule("test", Duration::from_millis(5), {
+                    let count = count.clone();
+                    move || {
+                        count.fetch_add(1, Ordering::SeqCst);
+                        None
+                    }
+                })
+                .unwrap();
+            timer.detach();
+            wait(&runtime, Duration::from_millis(50));
+            assert_eq!(count.load(Ordering::SeqCst), ticks + 1);
+        }
+    }
+
+    #[test]
+    #[cfg(any(feature = "kafka", feature = "otel", feature = "sqlite"))]
+    fn batches() {
+        for (executor, runtime) in executors() {
+            let tracker = Arc::new(FlushTracker::new());
+            let (published, batches) = mpsc::channel();
+            let batcher = Batches::new(Duration::from_secs(60), 2, move |batch: &[u32]| {
+                published.send(batch.to_vec()).unwrap()
+            });
+            let (sender, task) = executor.serve("test", tracker.clone(), batcher).unwrap();
+
+            for i in 0..3 {
+                assert!(sender.send(Message::Item(tracker.track(), i)));
+            }
+            assert!(sender.send(Message::Flush));
+            wait(&runtime, Duration::from_millis(50));
+            assert_eq!(batches.try_recv().unwrap(), [0, 1]);
+            assert_eq!(batches.try_recv().unwrap(), [2]);
+
+            assert!(sender.send(Message::Item(tracker.track(), 3)));
+            drop(sender);
+            wait(&runtime, Duration::from_millis(50));
+            task.join();
+            assert_eq!(batches.try_recv().unwrap(), [3]);
+            assert!(tracker.wait_timeout(FlushToken::issue(), Duration::ZERO));
+        }
+    }
+
+    #[test]
+    #[cfg(any(feature = "kafka", feature = "otel", feature = "sqlite"))]
+    fn batch_interval() {
+        for (executor, runtime) in executors() {
+            let tracker = Arc::new(FlushTracker::new());
+            let (published, batches) = mpsc::channel();
+            let batcher = Batches::new(Duration::from_millis(20), 10, move |batch: &[u32]| {
+                published.send(batch.to_vec()).unwrap()
+            });
+            let (sender, task) = executor.serve("test", tracker.clone(), batcher).unwrap();
+
+            assert!(sender.send(Message::Item(tracker.track(), 0)));
+            wait(&runtime, Duration::from_millis(100));
+            assert_eq!(batches.try_recv().unwrap(), [0]);
+            assert!(tracker.wait_timeout(FlushToken::issue(), Duration::ZERO));
+
+            drop(sender);
+            wait(&runtime, Duration::from_millis(50));
+            task.join();
+            assert!(batches.try_recv().is_err());
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! Prints the JSON Schema of log4rs config files, for editors to validate and
+//! complete them with.
+//!
+//! Requires the `config_schema` feature.
+
+use log4rs::config::Deserializers;
+
+fn main() {
+    let schema = Deserializers::default().json_schema();
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema).expect("a schema is serializable")
+    );
+}
@@ -0,0 +1,281 @@
+//! Capture of the output of child processes.
+//!
+//! Requires the `process_capture` feature.
+//!
+//! A `Capture` reads the stdout and stderr of a child process on background
+//! threads and logs each line they write as a record, with the child's pid
+//! and the name of the stream as key-values:
+//!
+//! ```no_run
+//! # fn f() -> std::io::Result<()> {
+//! use std::process::Command;
+//!
+//! use log::Level;
+//! use log4rs::capture::Capture;
+//!
+//! let (mut child, capture) = Capture::builder()
+//!     .target("tool")
+//!     .stderr_level(Level::Error)
+//!     .spawn(&mut Command::new("tool"))?;
+//! child.wait()?;
+//! capture.join();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read},
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use log::{kv::Value, Level, Log, Record};
+
+/// The threads capturing the output of a child process.
+#[derive(Debug)]
+pub struct Capture {
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Capture {
+    /// Creates a new `CaptureBuilder`.
+    pub fn builder() -> CaptureBuilder {
+        CaptureBuilder {
+            target: "child".to_owned(),
+            stdout_level: Level::Info,
+            stderr_level: Level::Warn,
+            logger: None,
+        }
+    }
+
+    /// Blocks until the child has closed its output and every line it wrote
+    /// has been logged.
+    pub fn join(self) {
+        for thread in self.threads {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A builder for `Capture`s.
+#[derive(Clone)]
+pub struct CaptureBuilder {
+    target: String,
+    stdout_level: Level,
+    stderr_level: Level,
+    logger: Option<Arc<dyn Log>>,
+}
+
+impl fmt::Debug for CaptureBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CaptureBuilder")
+            .field("target", &self.target)
+            .field("stdout_level", &self.stdout_level)
+            .field("stderr_level", &self.stderr_level)
+            .finish()
+    }
+}
+
+impl CaptureBuilder {
+    /// Sets the target of the records.
+    ///
+    /// Defaults to `child`.
+    pub fn target(mut self, target: &str) -> CaptureBuilder {
+        self.target = target.to_owned();
+        self
+    }
+
+    /// Sets the level of the lines written to stdout.
+    ///
+    /// Defaults to `Level::Info`.
+    pub fn stdout_level(mut self, level: Level) -> CaptureBuilder {
+        self.stdout_level = level;
+        self
+    }
+
+    /// Sets the level of the lines written to stderr.
+    ///
+    /// Defaults to `Level::Warn`.
+    pub fn stderr_level(mut self, level: Level) -> CaptureBuilder {
+        self.stderr_level = level;
+        self
+    }
+
+    /// Sets the logger the records are sent to.
+    ///
+    /// Defaults to the global logger.
+    pub fn logger(mut self, logger: Arc<dyn Log>) -> CaptureBuilder {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Spawns `command` with its stdout and stderr piped, and captures them.
+    pub fn spawn(&self, command: &mut Command) -> io::Result<(Child, Capture)> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let capture = self.adopt(&mut child)?;
+        Ok((child, capture))
+    }
+
+    /// Captures the pipes of a child which was spawned with its stdout or
+    /// stderr piped.
+    ///
+    /// The pipes are taken from the child. Streams which were not piped are
+    /// ignored.
+    pub fn adopt(&self, child: &mut Child) -> io::Result<Capture> {
+        let pid = child.id();
+        let mut threads = vec![];
+        if let Some(stdout) = child.stdout.take() {
+            threads.push(self.read(pid, "stdout", self.stdout_level, stdout)?);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            threads.push(self.read(pid, "stderr", self.stderr_level, stderr)?);
+        }
+        Ok(Capture { threads })
+    }
+
+    fn read<R>(
+        &self,
+        pid: u32,
+        stream: &'static str,
+        level: Level,
+        pipe: R,
+    ) -> io::Result<JoinHandle<()>>
+    where
+        R: Read + Send + 'static,
+    {
+        let target = self.target.clone();
+        let logger = self.logger.clone();
+        thread::Builder::new()
+            .name(format!("log4rs capture {}", stream))
+            .spawn(move || {
+                let logger: &dyn Log = match logger {
+                    Some(ref logger) => &**logger,
+                    None => log::logger(),
+                };
+                let key_values = [("pid", Value::from(pid)), ("stream", Value::from(stream))];
+
+                let mut pipe = BufReader::new(pipe);
+                let mut line = vec![];
+                loop {
+                    line.clear();
+                    match pipe.read_until(b'\n', &mut line) {
+                        Ok(0) => break,
+                        Ok(_) => {}
+                        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            crate::handle_error(
+                                &anyhow::Error::new(e)
+                                    .context(format!("error reading the {} of {}", stream, pid)),
+                            );
+                            break;
+                        }
+                    }
+                    if line.ends_with(b"\n") {
+                        line.pop();
+                        if line.ends_with(b"\r") {
+                            line.pop();
+                        }
+                    }
+
+                    logger.log(
+                        &Record::builder()
+                            .level(level)
+                            .target(&target)
+                            .args(format_args!("{}", String::from_utf8_lossy(&line)))
+                            .key_values(&key_values)
+                            .build(),
+                    );
+                }
+                logger.flush();
+            })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::sync::Mutex;
+
+    use log::{
+        kv::{Key, VisitSource},
+        Metadata,
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<(Level, String, String, String)>>);
+
+    impl Log for Collect {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            struct Stream<'a>(&'a mut String);
+
+            impl<'a, 'kvs> VisitSource<'kvs> for Stream<'a> {
+                fn visit_pair(
+                    &mut self,
+                    key: Key<'kvs>,
+                    value: Value<'kvs>,
+                ) -> Result<(), log::kv::Error> {
+                    if key.as_str() == "stream" {
+                        *self.0 = value.to_string();
+                    }
+                    Ok(())
+                }
+            }
+
+            let mut stream = String::new();
+            record.key_values().visit(&mut Stream(&mut stream)).unwrap();
+            self.0.lock().unwrap().push((
+                record.level(),
+                record.target().to_owned(),
+                stream,
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn spawn() {
+        let collect = Arc::new(Collect::default());
+        let (mut child, capture) = Capture::builder()
+            .target("sh")
+            .logger(collect.clone())
+            .spawn(
+                Command::new("sh")
+                    .arg("-c")
+                    .arg("echo one; echo two >&2; printf three"),
+            )
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        capture.join();
+
+        let mut records = collect.0.lock().unwrap().clone();
+        records.sort_by(|a, b| a.3.cmp(&b.3));
+        let record = |level, stream: &str, message: &str| {
+            (
+                level,
+                "sh".to_owned(),
+                stream.to_owned(),
+                message.to_owned(),
+            )
+        };
+        assert_eq!(
+            records,
+            [
+                record(Level::Info, "stdout", "one"),
+                record(Level::Info, "stdout", "three"),
+                record(Level::Warn, "stderr", "two"),
+            ]
+        );
+    }
+}
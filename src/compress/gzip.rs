@@ -0,0 +1,113 @@
+//! The gzip compressor.
+//!
+//! Requires the `gzip` feature.
+
+use flate2::{write::GzEncoder, Compression};
+use std::io::{self, Read, Write};
+
+use crate::compress::Compressor;
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+
+/// Configuration for the gzip compressor.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GzipCompressorConfig {
+    level: Option<u32>,
+}
+
+/// A `Compressor` writing the gzip format.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GzipCompressor {
+    level: u32,
+}
+
+impl Default for GzipCompressor {
+    fn default() -> GzipCompressor {
+        GzipCompressor { level: 6 }
+    }
+}
+
+impl GzipCompressor {
+    /// Creates a new `GzipCompressor` with the default compression level of 6.
+    pub fn new() -> GzipCompressor {
+        GzipCompressor::default()
+    }
+
+    /// Sets the compression level, from 0 for none to 9 for the smallest
+    /// output.
+    ///
+    /// Levels above 9 are treated as 9.
+    pub fn level(mut self, level: u32) -> GzipCompressor {
+        self.level = level.min(9);
+        self
+    }
+}
+
+impl Compressor for GzipCompressor {
+    fn compress(&self, src: &mut dyn Read, dst: &mut dyn Write) -> anyhow::Result<()> {
+        let mut encoder = GzEncoder::new(dst, Compression::new(self.level));
+        io::copy(src, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
+
+/// A deserializer for the `GzipCompressor`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: gzip
+///
+/// # The compression level, from 0 for none to 9 for the smallest output.
+/// # Defaults to 6.
+/// level: 9
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct GzipCompressorDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for GzipCompressorDeserializer {
+    type Trait = dyn Compressor;
+
+    type Config = GzipCompressorConfig;
+
+    fn deserialize(
+        &self,
+        config: GzipCompressorConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Compressor>> {
+        let mut compressor = GzipCompressor::new();
+        if let Some(level) = config.level {
+            compressor = compressor.level(level);
+        }
+        Ok(Box::new(compressor))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for level in [0, 6, 9] {
+            let mut compressed = vec![];
+            GzipCompressor::new()
+                .level(level)
+                .compress(&mut &b"hello hello hello"[..], &mut compressed)
+                .unwrap();
+
+            let mut decompressed = String::new();
+            GzDecoder::new(&compressed[..])
+                .read_to_string(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, "hello hello hello");
+        }
+    }
+}
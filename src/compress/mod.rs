@@ -0,0 +1,70 @@
+//! Compressors
+//!
+//! A `Compressor` implements a compression codec shared by the components
+//! which compress their output, such as the rollers compressing archived log
+//! files. Custom codecs can be registered with `Deserializers` under the
+//! `compressor` trait.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+};
+
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::Deserializable;
+
+#[cfg(feature = "gzip")]
+pub mod gzip;
+
+/// A trait implemented by compression codecs.
+pub trait Compressor: fmt::Debug + Send + Sync + 'static {
+    /// Compresses everything read from `src`, writing the result to `dst`.
+    ///
+    /// The compressed stream must be complete when this method returns.
+    fn compress(&self, src: &mut dyn Read, dst: &mut dyn Write) -> anyhow::Result<()>;
+}
+
+#[cfg(feature = "config_parsing")]
+impl Deserializable for dyn Compressor {
+    fn name() -> &'static str {
+        "compressor"
+    }
+}
+
+/// Configuration for a compressor.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CompressorConfig {
+    /// The compressor's kind.
+    pub kind: String,
+
+    /// The compressor's configuration.
+    pub config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> de::Deserialize<'de> for CompressorConfig {
+    fn deserialize<D>(d: D) -> Result<CompressorConfig, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(CompressorConfig {
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
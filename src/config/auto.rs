@@ -0,0 +1,180 @@
+//! Configuration chosen from the environment the program runs in.
+
+use log::LevelFilter;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    append::{
+        console::{ConsoleAppender, Target},
+        rolling_file::{
+            policy::compound::{
+                roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
+            },
+            RollingFileAppender,
+        },
+        Append,
+    },
+    config::{Appender, Config, Root},
+    encode::{json::JsonEncoder, pattern::PatternEncoder, writer::console::ConsoleWriter},
+    Handle,
+};
+
+/// The environment variable overriding the level of the root logger, such as
+/// `LOG4RS_LEVEL=debug`.
+pub const LEVEL_VAR: &str = "LOG4RS_LEVEL";
+
+const APPENDER: &str = "auto";
+const PATTERN: &str = "{d(%H:%M:%S%.3f)} {h({l:<5})} {t} - {m}{n}";
+const FILE_LIMIT: u64 = 10 * 1024 * 1024;
+const FILE_COUNT: u32 = 5;
+
+/// The kind of environment a program runs in, which decides where
+/// `auto_init` sends its logs.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Environment {
+    /// A container, such as one run by Docker or Kubernetes, whose standard
+    /// output is collected: logs are written there as JSON, one object per
+    /// line.
+    Container,
+    /// A terminal: logs are written to standard error with colored levels.
+    Terminal,
+    /// Neither, such as a command line tool run by cron: logs are written to
+    /// a rolling file in the user's state directory, `$XDG_STATE_HOME/<program>/`
+    /// or `~/.local/state/<program>/`.
+    Background,
+}
+
+impl Environment {
+    /// Detects the environment of the running program.
+    ///
+    /// A container is recognized by `/.dockerenv`, `/run/.containerenv` or the
+    /// `KUBERNETES_SERVICE_HOST` and `container` environment variables, and a
+    /// terminal by standard output or standard error being one.
+    pub fn detect() -> Environment {
+        let container = Path::new("/.dockerenv").exists()
+            || Path::new("/run/.containerenv").exists()
+            || env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+            || env::var_os("container").is_some();
+        if container {
+            Environment::Container
+        } else if ConsoleWriter::stdout().is_some() || ConsoleWriter::stderr().is_some() {
+            Environment::Terminal
+        } else {
+            Environment::Background
+        }
+    }
+}
+
+/// Initializes the global logger with defaults chosen for the environment the
+/// program runs in, as detected by `Environment::detect`.
+///
+/// The root logger logs at `info` and above, unless the `LOG4RS_LEVEL`
+/// environment variable names another level. Programs needing more control
+/// should use a config file or build a `Config` instead.
+///
+/// Requires the `console_appender`, `rolling_file_appender`, `compound_policy`,
+/// `size_trigger`, `fixed_window_roller` and `json_encoder` features.
+pub fn auto_init() -> anyhow::Result<Handle> {
+    let config = auto_config(Environment::detect())?;
+    Ok(crate::init_config(config)?)
+}
+
+/// Returns the config `auto_init` uses in the specified environment.
+pub fn auto_config(environment: Environment) -> anyhow::Result<Config> {
+    config(environment, state_dir())
+}
+
+/// Returns the config for the environment, writing log files under
+/// `state_dir` in the background.
+fn config(environment: Environment, state_dir: Option<PathBuf>) -> anyhow::Result<Config> {
+    let level = match env::var(LEVEL_VAR) {
+        Ok(level) => level
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid level `{}` in {}", level, LEVEL_VAR))?,
+        Err(_) => LevelFilter::Info,
+    };
+
+    let appender: Box<dyn Append> = match environment {
+        Environment::Container => Box::new(
+            ConsoleAppender::builder()
+                .encoder(Box::new(JsonEncoder::new()))
+                .build(),
+        ),
+        Environment::Terminal => Box::new(
+            ConsoleAppender::builder()
+                .encoder(Box::new(PatternEncoder::new(PATTERN)))
+                .target(Target::Stderr)
+                .build(),
+        ),
+        Environment::Background => {
+            let dir = state_dir
+                .ok_or_else(|| anyhow::anyhow!("no state directory for log files"))?
+                .join(program());
+            // The directory is passed separately so that braces in it are not
+            // taken for the index.
+            let roller = FixedWindowRoller::builder()
+                .archive_dir(&dir.to_string_lossy())
+                .build(&format!("{}.{{}}.log", program()), FILE_COUNT)?;
+            let policy =
+                CompoundPolicy::new(Box::new(SizeTrigger::new(FILE_LIMIT)), Box::new(roller));
+            Box::new(
+                RollingFileAppender::builder()
+                    .build(dir.join(format!("{}.log", program())), Box::new(policy))?,
+            )
+        }
+    };
+
+    Ok(Config::builder()
+        .appender(Appender::builder().build(APPENDER, appender))
+        .build(Root::builder().appender(APPENDER).build(level))?)
+}
+
+/// Returns the directory for state such as logs, following the XDG base
+/// directory specification.
+fn state_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if Path::new(&dir).is_absolute() => Some(dir.into()),
+        _ => env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .map(|home| Path::new(&home).join(".local").join("state"))
+            .or_else(|| env::var_os("LOCALAPPDATA").map(Into::into)),
+    }
+}
+
+/// Returns the name of the running program.
+fn program() -> String {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "log4rs".to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn background() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join("state{}");
+        let config = config(Environment::Background, Some(state_dir.clone())).unwrap();
+
+        assert_eq!(config.root().level(), LevelFilter::Info);
+        assert_eq!(config.appenders()[0].name(), APPENDER);
+        let log = state_dir.join(program()).join(format!("{}.log", program()));
+        assert!(log.exists());
+    }
+
+    #[test]
+    fn terminal() {
+        let config = auto_config(Environment::Terminal).unwrap();
+        assert_eq!(config.root().appenders(), [APPENDER]);
+    }
+}
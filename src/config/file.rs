@@ -1,19 +1,28 @@
+use anyhow::{anyhow, Context};
+use serde_value::Value;
+#[cfg(feature = "notify")]
+use std::sync::mpsc;
 use std::{
-    fs,
+    collections::BTreeMap,
+    env, fmt, fs,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
     thread,
     time::{Duration, SystemTime},
 };
 
 use thiserror::Error;
 
-use super::{init_config, Config, Deserializers, Handle, RawConfig};
+use super::{
+    init_config, raw::merge, runtime::ConfigError, Config, Deserializers, Handle, RawConfig,
+};
 use crate::handle_error;
 
 /// Initializes the global logger as a log4rs logger configured via a file.
 ///
 /// Configuration is read from a file located at the provided path on the
 /// filesystem and components are created from the provided `Deserializers`.
+/// The format of the file is determined by its extension.
 ///
 /// Any nonfatal errors encountered when processing the configuration are
 /// reported to stderr.
@@ -23,28 +32,34 @@ pub fn init_file<P>(path: P, deserializers: Deserializers) -> anyhow::Result<()>
 where
     P: AsRef<Path>,
 {
+    let format = Format::from_path(path.as_ref())?;
+    init_file_with_format(path, format, deserializers)
+}
+
+/// Initializes the global logger as a log4rs logger configured via a file in
+/// the specified format.
+///
+/// Unlike `init_file`, the format does not depend on the file's extension.
+pub fn init_file_with_format<P>(
+    path: P,
+    format: Format,
+    deserializers: Deserializers,
+) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    format.ensure_supported()?;
     let path = path.as_ref().to_path_buf();
-    let format = Format::from_path(&path)?;
-    let source = read_config(&path)?;
-    // An Err here could come because mtime isn't available, so don't bail
-    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
-    let config = format.parse(&source)?;
+    let source = Source::read(&path, format)?;
+    let config = raw_config(source.value.clone())?;
 
-    let refresh_rate = config.refresh_rate();
+    let mode = ReloadMode::of(&config);
     let config = deserialize(&config, &deserializers);
 
     match init_config(config) {
         Ok(handle) => {
-            if let Some(refresh_rate) = refresh_rate {
-                ConfigReloader::start(
-                    path,
-                    format,
-                    refresh_rate,
-                    source,
-                    modified,
-                    deserializers,
-                    handle,
-                );
+            if let Some(mode) = mode {
+                ConfigReloader::start(path, format, mode, source, deserializers, handle);
             }
             Ok(())
         }
@@ -60,14 +75,332 @@ pub fn load_config_file<P>(path: P, deserializers: Deserializers) -> anyhow::Res
 where
     P: AsRef<Path>,
 {
-    let path = path.as_ref();
-    let format = Format::from_path(path)?;
-    let source = read_config(path)?;
-    let config = format.parse(&source)?;
+    let format = Format::from_path(path.as_ref())?;
+    load_config_file_with_format(path, format, deserializers)
+}
+
+/// Loads a log4rs logger configuration from a file in the specified format.
+///
+/// Unlike `load_config_file`, the format does not depend on the file's
+/// extension.
+pub fn load_config_file_with_format<P>(
+    path: P,
+    format: Format,
+    deserializers: Deserializers,
+) -> anyhow::Result<Config>
+where
+    P: AsRef<Path>,
+{
+    format.ensure_supported()?;
+    let source = Source::read(path.as_ref(), format)?;
+    let config = raw_config(source.value)?;
 
     Ok(deserialize(&config, &deserializers))
 }
 
+/// Loads a log4rs logger configuration from a string in the specified
+/// format.
+///
+/// Files it includes are resolved relative to the current directory.
+pub(crate) fn load_config_str(
+    source: &str,
+    format: Format,
+    deserializers: &Deserializers,
+) -> anyhow::Result<Config> {
+    format.ensure_supported()?;
+    let value = format.parse(source)?;
+    let value = resolve_includes(value, Path::new(""), format, &mut vec![], &mut vec![])?;
+    let config = raw_config(value)?;
+
+    Ok(deserialize(&config, deserializers))
+}
+
+/// Checks a log4rs configuration file without initializing the logger.
+///
+/// The file and the files it includes are parsed and every component is
+/// constructed, exactly as `init_file` would, but rather than being reported
+/// to stderr and skipped, all errors are returned along with the location
+/// they were found at when known. Note that constructing components has
+/// side effects such as creating log files.
+///
+/// On success, returns a summary of the configuration.
+pub fn validate_file<P>(
+    path: P,
+    deserializers: &Deserializers,
+) -> Result<ConfigReport, ValidationErrors>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let format = Format::from_path(path)
+        .map_err(|e| ValidationErrors::single(ValidationError::new(Some(path), None, e.into())))?;
+
+    let source = Source::read(path, format).map_err(|e| {
+        let (file, location) = match e.chain().find_map(|e| e.downcast_ref::<ParseError>()) {
+            Some(parse) => (parse.path.clone(), parse.location),
+            None => (path.to_owned(), None),
+        };
+        ValidationErrors::single(ValidationError::new(Some(&file), location, e))
+    })?;
+    let texts = source
+        .files
+        .iter()
+        .filter_map(|(file, _)| Some((file.clone(), fs::read_to_string(file).ok()?)))
+        .collect::<Vec<_>>();
+
+    let config = raw_config(source.value).map_err(|e| {
+        // Deserializing the text directly recovers the location, unless the
+        // error came from an included file
+        let location = match *texts {
+            [(_, ref text)] => format.locate_config_error(text),
+            _ => None,
+        };
+        ValidationErrors::single(ValidationError::new(Some(path), location, e))
+    })?;
+
+    let mut errors = vec![];
+    let (appenders, appender_errors) = config.appenders_lossy(deserializers);
+    let mut failed = vec![];
+    for error in appender_errors.into_inner() {
+        let name = error.appender().to_owned();
+        let (file, location) = locate_key(&texts, &name);
+        errors.push(ValidationError::new(file, location, error.into()));
+        failed.push(name);
+    }
+
+    let mut report = ConfigReport {
+        files: texts.iter().map(|(file, _)| file.clone()).collect(),
+        appenders: appenders.iter().map(|a| a.name().to_owned()).collect(),
+        loggers: config
+            .loggers()
+            .iter()
+            .map(|l| l.name().to_owned())
+            .collect(),
+        refresh_rate: config.refresh_rate(),
+    };
+    report.appenders.sort();
+    report.loggers.sort();
+
+    let mut builder = Config::builder()
+        .appenders(appenders)
+        .loggers(config.loggers())
+        .heartbeats(config.heartbeats())
+        .escalations(config.escalations())
+        .kv_limits(config.kv_limits())
+        .fields(config.fields())
+        .derived_fields(config.derived_fields())
+        .sanitize(config.sanitize())
+        .shutdown_summary(config.shutdown_summary());
+    if let Some(history) = config.history() {
+        builder = builder.history(history);
+    }
+    let build = builder.build(config.root());
+    if let Err(config_errors) = build {
+        for error in config_errors.into_inner() {
+            let (file, location) = match error {
+                // Already reported as a failure to deserialize the appender
+                ConfigError::NonexistentAppender(ref name) if failed.contains(name) => continue,
+                ConfigError::NonexistentAppender(ref name) => locate_reference(&texts, name),
+                ConfigError::DuplicateAppenderName(ref name)
+                | ConfigError::DuplicateLoggerName(ref name)
+                | ConfigError::InvalidLoggerName(ref name) => locate_key(&texts, name),
+                ConfigError::ZeroHeartbeatInterval | ConfigError::__Extensible => (None, None),
+            };
+            errors.push(ValidationError::new(file, location, error.into()));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(report)
+    } else {
+        Err(ValidationErrors(errors))
+    }
+}
+
+/// Finds the line defining `key` as a map key in the first file containing
+/// one.
+fn locate_key<'a>(
+    texts: &'a [(PathBuf, String)],
+    key: &str,
+) -> (Option<&'a Path>, Option<(usize, usize)>) {
+    let keys = [key.to_owned(), format!("\"{}\"", key), format!("'{}'", key)];
+    locate(texts, |line| {
+        // TOML table headers such as `[appenders.foo]`
+        let line = line.trim_start_matches('[');
+        let line = line
+            .strip_prefix("appenders.")
+            .or_else(|| line.strip_prefix("loggers."))
+            .unwrap_or(line);
+        keys.iter().any(|key| {
+            line.strip_prefix(key.as_str()).map_or(false, |rest| {
+                let rest = rest.trim_start();
+                rest.starts_with(':') || rest.starts_with('=') || rest.starts_with(']')
+            })
+        })
+    })
+}
+
+/// Finds the line of a list item referring to `name` in the first file
+/// containing one.
+fn locate_reference<'a>(
+    texts: &'a [(PathBuf, String)],
+    name: &str,
+) -> (Option<&'a Path>, Option<(usize, usize)>) {
+    locate(texts, |line| {
+        let line = line.strip_prefix('-').unwrap_or(line);
+        line.split(['[', ']', ','])
+            .any(|item| item.trim().trim_matches(|c| c == '"' || c == '\'') == name)
+    })
+}
+
+fn locate<F>(texts: &[(PathBuf, String)], matches: F) -> (Option<&Path>, Option<(usize, usize)>)
+where
+    F: Fn(&str) -> bool,
+{
+    for (file, text) in texts {
+        for (idx, line) in text.lines().enumerate() {
+            let trimmed = line.trim_start();
+            if matches(trimmed) {
+                let column = line.len() - trimmed.len() + 1;
+                return (Some(file), Some((idx + 1, column)));
+            }
+        }
+    }
+    (None, None)
+}
+
+/// Converts a byte offset into a one-based line and column.
+#[cfg(feature = "toml_format")]
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
+}
+
+/// A summary of a configuration file checked by `validate_file`.
+#[derive(Clone, Debug)]
+pub struct ConfigReport {
+    files: Vec<PathBuf>,
+    appenders: Vec<String>,
+    loggers: Vec<String>,
+    refresh_rate: Option<Duration>,
+}
+
+impl ConfigReport {
+    /// Returns the files read, starting with the one validated and followed
+    /// by those it includes.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Returns the names of the configured appenders, in sorted order.
+    pub fn appenders(&self) -> &[String] {
+        &self.appenders
+    }
+
+    /// Returns the names of the configured loggers, in sorted order.
+    pub fn loggers(&self) -> &[String] {
+        &self.loggers
+    }
+
+    /// Returns the rate at which the file would be checked for changes.
+    pub fn refresh_rate(&self) -> Option<Duration> {
+        self.refresh_rate
+    }
+}
+
+/// An error found by `validate_file`.
+#[derive(Debug)]
+pub struct ValidationError {
+    path: Option<PathBuf>,
+    location: Option<(usize, usize)>,
+    error: anyhow::Error,
+}
+
+impl ValidationError {
+    fn new(path: Option<&Path>, location: Option<(usize, usize)>, error: anyhow::Error) -> Self {
+        ValidationError {
+            path: path.map(Path::to_owned),
+            location,
+            error,
+        }
+    }
+
+    /// Returns the file the error was found in, if known.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the one-based line the error was found at, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.location.map(|(line, _)| line)
+    }
+
+    /// Returns the one-based column the error was found at, if known.
+    pub fn column(&self) -> Option<usize> {
+        self.location.map(|(_, column)| column)
+    }
+
+    /// Returns the underlying error.
+    pub fn error(&self) -> &anyhow::Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref path) = self.path {
+            write!(fmt, "{}:", path.display())?;
+            if let Some((line, column)) = self.location {
+                write!(fmt, "{}:{}:", line, column)?;
+            }
+            fmt.write_str(" ")?;
+        }
+        write!(fmt, "{:#}", self.error)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// The errors found by `validate_file`.
+#[derive(Debug)]
+pub struct ValidationErrors(Vec<ValidationError>);
+
+impl ValidationErrors {
+    fn single(error: ValidationError) -> Self {
+        ValidationErrors(vec![error])
+    }
+
+    /// Returns a slice of the errors.
+    pub fn errors(&self) -> &[ValidationError] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                fmt.write_str("\n")?;
+            }
+            write!(fmt, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// An error parsing a config file, along with where it was found.
+#[derive(Debug, Error)]
+#[error("{error}")]
+struct ParseError {
+    path: PathBuf,
+    location: Option<(usize, usize)>,
+    error: anyhow::Error,
+}
+
 /// The various types of formatting errors that can be generated.
 #[derive(Debug, Error)]
 pub enum FormatError {
@@ -92,50 +425,292 @@ pub enum FormatError {
     UnknownFormat,
 }
 
-#[derive(Debug)]
-enum Format {
-    #[cfg(feature = "yaml_format")]
+/// A config file format.
+///
+/// All formats share the same structure, and each requires a Cargo feature to
+/// be enabled.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Format {
+    /// YAML, with the extension `yaml` or `yml`. Requires the `yaml_format`
+    /// feature.
     Yaml,
-    #[cfg(feature = "json_format")]
+    /// JSON, with the extension `json`. Requires the `json_format` feature.
     Json,
-    #[cfg(feature = "toml_format")]
+    /// TOML, with the extension `toml`. Requires the `toml_format` feature.
     Toml,
 }
 
 impl Format {
-    fn from_path(path: &Path) -> anyhow::Result<Format> {
-        match path.extension().and_then(|s| s.to_str()) {
+    /// Determines the format of a config file from its extension.
+    ///
+    /// Returns an error if the extension is not recognized or the feature
+    /// required for the format is not enabled.
+    pub fn from_path(path: &Path) -> Result<Format, FormatError> {
+        let format = match path.extension().and_then(|s| s.to_str()) {
+            Some("yaml") | Some("yml") => Format::Yaml,
+            Some("json") => Format::Json,
+            Some("toml") => Format::Toml,
+            Some(f) => return Err(FormatError::UnsupportedFormat(f.to_string())),
+            None => return Err(FormatError::UnknownFormat),
+        };
+        format.ensure_supported()?;
+        Ok(format)
+    }
+
+    fn ensure_supported(self) -> Result<(), FormatError> {
+        match self {
             #[cfg(feature = "yaml_format")]
-            Some("yaml") | Some("yml") => Ok(Format::Yaml),
+            Format::Yaml => Ok(()),
             #[cfg(not(feature = "yaml_format"))]
-            Some("yaml") | Some("yml") => Err(FormatError::YamlFeatureFlagRequired.into()),
+            Format::Yaml => Err(FormatError::YamlFeatureFlagRequired),
 
             #[cfg(feature = "json_format")]
-            Some("json") => Ok(Format::Json),
+            Format::Json => Ok(()),
             #[cfg(not(feature = "json_format"))]
-            Some("json") => Err(FormatError::JsonFeatureFlagRequired.into()),
+            Format::Json => Err(FormatError::JsonFeatureFlagRequired),
 
             #[cfg(feature = "toml_format")]
-            Some("toml") => Ok(Format::Toml),
+            Format::Toml => Ok(()),
             #[cfg(not(feature = "toml_format"))]
-            Some("toml") => Err(FormatError::TomlFeatureFlagRequired.into()),
-
-            Some(f) => Err(FormatError::UnsupportedFormat(f.to_string()).into()),
-            None => Err(FormatError::UnknownFormat.into()),
+            Format::Toml => Err(FormatError::TomlFeatureFlagRequired),
         }
     }
 
+    #[allow(unused_variables, unreachable_code)]
+    fn parse(&self, source: &str) -> anyhow::Result<Value> {
+        let value = match *self {
+            #[cfg(feature = "yaml_format")]
+            Format::Yaml => ::serde_yaml::from_str(source)?,
+            #[cfg(not(feature = "yaml_format"))]
+            Format::Yaml => return Err(FormatError::YamlFeatureFlagRequired.into()),
+
+            #[cfg(feature = "json_format")]
+            Format::Json => ::serde_json::from_str(source)?,
+            #[cfg(not(feature = "json_format"))]
+            Format::Json => return Err(FormatError::JsonFeatureFlagRequired.into()),
+
+            #[cfg(feature = "toml_format")]
+            Format::Toml => ::toml::from_str(source)?,
+            #[cfg(not(feature = "toml_format"))]
+            Format::Toml => return Err(FormatError::TomlFeatureFlagRequired.into()),
+        };
+        Ok(value)
+    }
+
+    /// Returns the one-based line and column of an error returned by `parse`,
+    /// if the format's parser reports one.
     #[allow(unused_variables)]
-    fn parse(&self, source: &str) -> anyhow::Result<RawConfig> {
+    fn locate(&self, error: &anyhow::Error, source: &str) -> Option<(usize, usize)> {
         match *self {
             #[cfg(feature = "yaml_format")]
-            Format::Yaml => ::serde_yaml::from_str(source).map_err(Into::into),
+            Format::Yaml => {
+                let location = error.downcast_ref::<::serde_yaml::Error>()?.location()?;
+                Some((location.line(), location.column()))
+            }
+            #[cfg(not(feature = "yaml_format"))]
+            Format::Yaml => None,
+
             #[cfg(feature = "json_format")]
-            Format::Json => ::serde_json::from_str(source).map_err(Into::into),
+            Format::Json => {
+                let error = error.downcast_ref::<::serde_json::Error>()?;
+                Some((error.line(), error.column()))
+            }
+            #[cfg(not(feature = "json_format"))]
+            Format::Json => None,
+
             #[cfg(feature = "toml_format")]
-            Format::Toml => ::toml::from_str(source).map_err(Into::into),
+            Format::Toml => {
+                let span = error.downcast_ref::<::toml::de::Error>()?.span()?;
+                Some(line_column(source, span.start))
+            }
+            #[cfg(not(feature = "toml_format"))]
+            Format::Toml => None,
         }
     }
+
+    /// Returns the location of the error deserializing `source` straight into
+    /// a `RawConfig`, if any.
+    #[allow(unused_variables, unreachable_code)]
+    fn locate_config_error(&self, source: &str) -> Option<(usize, usize)> {
+        let error: anyhow::Error = match *self {
+            #[cfg(feature = "yaml_format")]
+            Format::Yaml => ::serde_yaml::from_str::<RawConfig>(source).err()?.into(),
+            #[cfg(not(feature = "yaml_format"))]
+            Format::Yaml => return None,
+
+            #[cfg(feature = "json_format")]
+            Format::Json => ::serde_json::from_str::<RawConfig>(source).err()?.into(),
+            #[cfg(not(feature = "json_format"))]
+            Format::Json => return None,
+
+            #[cfg(feature = "toml_format")]
+            Format::Toml => ::toml::from_str::<RawConfig>(source).err()?.into(),
+            #[cfg(not(feature = "toml_format"))]
+            Format::Toml => return None,
+        };
+        self.locate(&error, source)
+    }
+}
+
+/// A config file read from disk, along with the files it includes.
+struct Source {
+    /// The config, with the included files merged in.
+    value: Value,
+    /// Every file read, along with its modification time if available.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Source {
+    fn read(path: &Path, format: Format) -> anyhow::Result<Source> {
+        let mut files = vec![];
+        let value = read_value(path, format, &mut vec![], &mut files)?;
+        Ok(Source { value, files })
+    }
+
+    /// Returns whether any of the files may have changed since they were
+    /// read.
+    fn modified(&self) -> bool {
+        self.files.iter().any(|(path, modified)| match modified {
+            Some(modified) => fs::metadata(path).and_then(|m| m.modified()).ok() != Some(*modified),
+            None => true,
+        })
+    }
+}
+
+/// Reads the config file at `path`, overlaying it on the files it includes.
+///
+/// `stack` holds the files currently being read, and `files` collects every
+/// file read.
+fn read_value(
+    path: &Path,
+    format: Format,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<(PathBuf, Option<SystemTime>)>,
+) -> anyhow::Result<Value> {
+    if stack.iter().any(|p| p == path) {
+        return Err(anyhow!("config file `{}` includes itself", path.display()));
+    }
+
+    // An Err here could come because mtime isn't available, so don't bail
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    let source = read_config(path)?;
+    files.push((path.to_owned(), modified));
+    let value = format.parse(&source).map_err(|error| ParseError {
+        path: path.to_owned(),
+        location: format.locate(&error, &source),
+        error,
+    })?;
+
+    stack.push(path.to_owned());
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let value = resolve_includes(value, dir, format, stack, files)?;
+    stack.pop();
+
+    Ok(value)
+}
+
+/// Overlays a parsed config on the files it includes, which are resolved
+/// relative to `dir`.
+fn resolve_includes(
+    mut value: Value,
+    dir: &Path,
+    format: Format,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<(PathBuf, Option<SystemTime>)>,
+) -> anyhow::Result<Value> {
+    let includes = match value {
+        Value::Map(ref mut map) => match map.remove(&Value::String("include".to_owned())) {
+            Some(Value::String(include)) => vec![include],
+            Some(includes) => includes
+                .deserialize_into::<Vec<String>>()
+                .map_err(|_| anyhow!("`include` must be a path or a list of paths"))?,
+            None => vec![],
+        },
+        _ => vec![],
+    };
+    if includes.is_empty() {
+        return Ok(value);
+    }
+
+    let mut base = Value::Map(BTreeMap::new());
+    for include in includes {
+        let include = dir.join(include);
+        // Fall back to the including file's format for unknown extensions
+        let format = match Format::from_path(&include) {
+            Ok(format) => format,
+            Err(FormatError::UnsupportedFormat(_)) | Err(FormatError::UnknownFormat) => format,
+            Err(e) => return Err(e.into()),
+        };
+        let included = read_value(&include, format, stack, files)
+            .with_context(|| format!("error including `{}`", include.display()))?;
+        base = merge(base, included);
+    }
+
+    Ok(merge(base, value))
+}
+
+/// Deserializes a config read from a file, substituting environment
+/// variables.
+fn raw_config(value: Value) -> anyhow::Result<RawConfig> {
+    substitute_env_vars(value)
+        .deserialize_into()
+        .map_err(Into::into)
+}
+
+/// Replaces references to environment variables in all string values.
+///
+/// `${NAME}` is replaced with the value of the variable `NAME`, and
+/// `${NAME:-default}` with `default` if it is unset or empty. References to
+/// unset variables without a default, and unterminated references, are left
+/// as they are. `$${` is replaced with a literal `${`.
+fn substitute_env_vars(value: Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(substitute_str(&s)),
+        Value::Option(Some(value)) => Value::Option(Some(Box::new(substitute_env_vars(*value)))),
+        Value::Newtype(value) => Value::Newtype(Box::new(substitute_env_vars(*value))),
+        Value::Seq(values) => Value::Seq(values.into_iter().map(substitute_env_vars).collect()),
+        Value::Map(map) => Value::Map(
+            map.into_iter()
+                .map(|(key, value)| (key, substitute_env_vars(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn substitute_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = rest.find('$') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        if let Some(tail) = rest.strip_prefix("$${") {
+            out.push_str("${");
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("${") {
+            let end = match tail.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            let (name, default) = match tail[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&tail[..end], None),
+            };
+            match (env::var(name), default) {
+                (Ok(value), Some(default)) if value.is_empty() => out.push_str(default),
+                (Ok(value), _) => out.push_str(&value),
+                (Err(_), Some(default)) => out.push_str(default),
+                (Err(_), None) => out.push_str(&rest[..end + 3]),
+            }
+            rest = &tail[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
 }
 
 fn read_config(path: &Path) -> anyhow::Result<String> {
@@ -147,86 +722,605 @@ fn deserialize(config: &RawConfig, deserializers: &Deserializers) -> Config {
     let (appenders, mut errors) = config.appenders_lossy(deserializers);
     errors.handle();
 
-    let (config, mut errors) = Config::builder()
+    let mut builder = Config::builder()
         .appenders(appenders)
         .loggers(config.loggers())
-        .build_lossy(config.root());
+        .heartbeats(config.heartbeats())
+        .escalations(config.escalations())
+        .kv_limits(config.kv_limits())
+        .fields(config.fields())
+        .derived_fields(config.derived_fields())
+        .sanitize(config.sanitize())
+        .shutdown_summary(config.shutdown_summary());
+    if let Some(history) = config.history() {
+        builder = builder.history(history);
+    }
+    let (config, mut errors) = builder.build_lossy(config.root());
 
     errors.handle();
 
     config
 }
 
+type ReloadCallback = dyn Fn(Result<(), &anyhow::Error>) + Send + Sync;
+
+static ON_RELOAD: Mutex<Option<Arc<ReloadCallback>>> = Mutex::new(None);
+
+/// Sets a callback invoked whenever a configuration file initialized with
+/// `init_file` changes and is reloaded.
+///
+/// The callback receives `Ok(())` once the new configuration is in place,
+/// and the error if the changed file could not be read or parsed, in which
+/// case the running configuration is kept. Replaces any previous callback.
+pub fn on_reload<F>(callback: F)
+where
+    F: Fn(Result<(), &anyhow::Error>) + Send + Sync + 'static,
+{
+    *ON_RELOAD.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(callback));
+}
+
+fn notify_reload(result: Result<(), &anyhow::Error>) {
+    let callback = ON_RELOAD.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(callback) = callback {
+        callback(result);
+    }
+}
+
+/// How a config file is checked for changes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ReloadMode {
+    /// Scan the files at a fixed rate.
+    Poll(Duration),
+    /// Wait for file system notifications, reloading once none has arrived
+    /// for the debounce time.
+    #[cfg(feature = "notify")]
+    Watch(Duration),
+}
+
+impl ReloadMode {
+    fn of(config: &RawConfig) -> Option<ReloadMode> {
+        #[cfg(feature = "notify")]
+        if let Some(debounce) = config.watch() {
+            return Some(ReloadMode::Watch(debounce));
+        }
+        #[cfg(not(feature = "notify"))]
+        if config.watch().is_some() {
+            handle_error(&anyhow!(
+                "the `notify` feature is required to watch config files, using `refresh_rate`"
+            ));
+        }
+        config.refresh_rate().map(ReloadMode::Poll)
+    }
+}
+
 struct ConfigReloader {
     path: PathBuf,
     format: Format,
-    source: String,
-    modified: Option<SystemTime>,
+    mode: Option<ReloadMode>,
+    source: Source,
     deserializers: Deserializers,
     handle: Handle,
+    #[cfg(feature = "notify")]
+    watcher: Option<Watcher>,
 }
 
 impl ConfigReloader {
     fn start(
         path: PathBuf,
         format: Format,
-        rate: Duration,
-        source: String,
-        modified: Option<SystemTime>,
+        mode: ReloadMode,
+        source: Source,
         deserializers: Deserializers,
         handle: Handle,
     ) {
         let mut reloader = ConfigReloader {
             path,
             format,
+            mode: Some(mode),
             source,
-            modified,
             deserializers,
             handle,
+            #[cfg(feature = "notify")]
+            watcher: None,
         };
 
         thread::Builder::new()
             .name("log4rs refresh".to_owned())
-            .spawn(move || reloader.run(rate))
+            .spawn(move || reloader.run())
             .unwrap();
     }
 
-    fn run(&mut self, mut rate: Duration) {
-        loop {
-            thread::sleep(rate);
+    fn run(&mut self) {
+        while let Some(mode) = self.mode {
+            let changed = self.wait(mode);
 
-            match self.run_once(rate) {
-                Ok(Some(r)) => rate = r,
-                Ok(None) => break,
-                Err(e) => handle_error(&e),
+            match self.run_once(changed) {
+                Ok(true) => notify_reload(Ok(())),
+                Ok(false) => {}
+                Err(e) => {
+                    handle_error(&e);
+                    notify_reload(Err(&e));
+                }
             }
         }
     }
 
-    fn run_once(&mut self, rate: Duration) -> anyhow::Result<Option<Duration>> {
-        if let Some(last_modified) = self.modified {
-            let modified = fs::metadata(&self.path).and_then(|m| m.modified())?;
-            if last_modified == modified {
-                return Ok(Some(rate));
+    /// Blocks until it's time to check the files, returning whether they are
+    /// known to have changed.
+    fn wait(&mut self, mode: ReloadMode) -> bool {
+        match mode {
+            ReloadMode::Poll(rate) => {
+                thread::sleep(rate);
+                false
+            }
+            #[cfg(feature = "notify")]
+            ReloadMode::Watch(debounce) => {
+                let files = self.source.files.iter().map(|(path, _)| path.clone());
+                let result = match self.watcher {
+                    Some(ref watcher) => watcher.wait(debounce),
+                    None => Watcher::new(files)
+                        .and_then(|watcher| self.watcher.insert(watcher).wait(debounce)),
+                };
+                match result {
+                    Ok(()) => true,
+                    Err(e) => {
+                        // Fall back to scanning the files
+                        handle_error(&e.context("error watching config files"));
+                        self.watcher = None;
+                        thread::sleep(debounce);
+                        false
+                    }
+                }
             }
-
-            self.modified = Some(modified);
         }
+    }
 
-        let source = read_config(&self.path)?;
-
-        if source == self.source {
-            return Ok(Some(rate));
+    /// Reloads the config if it has changed, returning whether it was
+    /// reloaded.
+    fn run_once(&mut self, changed: bool) -> anyhow::Result<bool> {
+        if !changed && !self.source.modified() {
+            return Ok(false);
         }
 
+        let source = Source::read(&self.path, self.format)?;
+        #[cfg(feature = "notify")]
+        if source.files.iter().map(|(path, _)| path).ne(self
+            .source
+            .files
+            .iter()
+            .map(|(path, _)| path))
+        {
+            // The includes changed, so different files need watching
+            self.watcher = None;
+        }
+        let unchanged = source.value == self.source.value;
         self.source = source;
+        if unchanged {
+            return Ok(false);
+        }
 
-        let config = self.format.parse(&self.source)?;
-        let rate = config.refresh_rate();
+        let config = raw_config(self.source.value.clone())?;
+        self.mode = ReloadMode::of(&config);
+        let quiesce = config.quiesce();
         let config = deserialize(&config, &self.deserializers);
 
-        self.handle.set_config(config);
+        match quiesce {
+            Some(max_wait) => {
+                if !self.handle.set_config_quiesced(config, max_wait) {
+                    crate::handle_error(&anyhow::anyhow!(
+                        "records were still being logged under the previous configuration {:?} \
+                         after the reload began",
+                        max_wait
+                    ));
+                }
+            }
+            None => self.handle.set_config(config),
+        }
+
+        Ok(true)
+    }
+}
+
+/// Watches config files for changes using file system notifications.
+#[cfg(feature = "notify")]
+struct Watcher {
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    files: Vec<PathBuf>,
+}
+
+#[cfg(feature = "notify")]
+impl Watcher {
+    fn new<I>(files: I) -> anyhow::Result<Watcher>
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        use notify::Watcher as _;
+
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        let mut dirs = vec![];
+        let mut watched = vec![];
+        for file in files {
+            // Watch the directory rather than the file, so that the file is
+            // still watched after being replaced by a rename
+            let dir = match file.parent() {
+                Some(dir) if dir != Path::new("") => dir,
+                _ => Path::new("."),
+            };
+            let dir = fs::canonicalize(dir)?;
+            if !dirs.contains(&dir) {
+                watcher.watch(&dir, notify::RecursiveMode::NonRecursive)?;
+                dirs.push(dir.clone());
+            }
+            if let Some(name) = file.file_name() {
+                watched.push(dir.join(name));
+            }
+        }
+
+        Ok(Watcher {
+            _watcher: watcher,
+            events,
+            files: watched,
+        })
+    }
+
+    /// Blocks until one of the files changes and no further change to them
+    /// has been seen for `debounce`. Events for other files in the same
+    /// directories, such as a log being written, are ignored.
+    fn wait(&self, debounce: Duration) -> anyhow::Result<()> {
+        loop {
+            let event = self.events.recv()?;
+            if self.is_change(&event?) {
+                break;
+            }
+        }
+        let mut deadline = std::time::Instant::now() + debounce;
+        loop {
+            let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+            match self.events.recv_timeout(timeout) {
+                Ok(event) => {
+                    if self.is_change(&event?) {
+                        deadline = std::time::Instant::now() + debounce;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn is_change(&self, event: &notify::Event) -> bool {
+        !event.kind.is_access() && event.paths.iter().any(|path| self.files.contains(path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn substitute() {
+        env::set_var("LOG4RS_TEST_SUBSTITUTE", "/var/log");
+        env::set_var("LOG4RS_TEST_SUBSTITUTE_EMPTY", "");
+        env::remove_var("LOG4RS_TEST_SUBSTITUTE_UNSET");
+
+        let cases = [
+            ("${LOG4RS_TEST_SUBSTITUTE}/app.log", "/var/log/app.log"),
+            (
+                "${LOG4RS_TEST_SUBSTITUTE:-/tmp}/app.log",
+                "/var/log/app.log",
+            ),
+            ("${LOG4RS_TEST_SUBSTITUTE_UNSET:-info}", "info"),
+            ("${LOG4RS_TEST_SUBSTITUTE_EMPTY:-info}", "info"),
+            ("${LOG4RS_TEST_SUBSTITUTE_UNSET:-}", ""),
+            ("$${LOG4RS_TEST_SUBSTITUTE}", "${LOG4RS_TEST_SUBSTITUTE}"),
+            ("$ENV{HOME}/cost $5", "$ENV{HOME}/cost $5"),
+            (
+                "${LOG4RS_TEST_SUBSTITUTE_UNSET}/app.log",
+                "${LOG4RS_TEST_SUBSTITUTE_UNSET}/app.log",
+            ),
+            ("${LOG4RS_TEST_SUBSTITUTE", "${LOG4RS_TEST_SUBSTITUTE"),
+            (
+                "${LOG4RS_TEST_SUBSTITUTE}/${LOG4RS_TEST_SUBSTITUTE",
+                "/var/log/${LOG4RS_TEST_SUBSTITUTE",
+            ),
+        ];
+        for (input, expected) in &cases {
+            assert_eq!(substitute_str(input), *expected);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "json_format", feature = "toml_format"))]
+    fn parse_json_and_toml() {
+        let json = Format::Json
+            .parse(r#"{"root": {"level": "warn"}, "refresh_rate": "30 seconds"}"#)
+            .and_then(raw_config)
+            .unwrap();
+        let toml = Format::Toml
+            .parse(
+                r#"
+refresh_rate = "30 seconds"
+
+[root]
+level = "warn"
+"#,
+            )
+            .and_then(raw_config)
+            .unwrap();
+        for config in &[json, toml] {
+            assert_eq!(config.refresh_rate(), Some(Duration::from_secs(30)));
+            assert_eq!(config.root().level(), log::LevelFilter::Warn);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "toml_format")]
+    fn explicit_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.conf");
+        fs::write(&path, "[root]\nlevel = \"error\"\n").unwrap();
+
+        assert!(load_config_file(&path, Deserializers::default()).is_err());
+        let config =
+            load_config_file_with_format(&path, Format::Toml, Deserializers::default()).unwrap();
+        assert_eq!(config.root().level(), log::LevelFilter::Error);
+    }
+
+    #[test]
+    fn from_path() {
+        assert!(matches!(
+            Format::from_path(Path::new("log4rs.ini")),
+            Err(FormatError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            Format::from_path(Path::new("log4rs")),
+            Err(FormatError::UnknownFormat)
+        ));
+        #[cfg(feature = "yaml_format")]
+        assert_eq!(
+            Format::from_path(Path::new("log4rs.yml")).unwrap(),
+            Format::Yaml
+        );
+        #[cfg(not(feature = "json_format"))]
+        assert!(matches!(
+            Format::from_path(Path::new("log4rs.json")),
+            Err(FormatError::JsonFeatureFlagRequired)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn parse_with_substitution() {
+        env::set_var("LOG4RS_TEST_PARSE_LEVEL", "warn");
+
+        let config = Format::Yaml
+            .parse(
+                "
+refresh_rate: ${LOG4RS_TEST_PARSE_UNSET:-30 seconds}
+root:
+  level: ${LOG4RS_TEST_PARSE_LEVEL}
+",
+            )
+            .and_then(raw_config)
+            .unwrap();
+        assert_eq!(config.refresh_rate(), Some(Duration::from_secs(30)));
+        assert_eq!(config.root().level(), log::LevelFilter::Warn);
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn include() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("common")).unwrap();
+        fs::write(
+            dir.path().join("common/base.yml"),
+            "
+refresh_rate: 30 seconds
+root:
+  level: info
+  appenders: [console]
+loggers:
+  app::db:
+    level: warn
+",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("prod.yml"),
+            "
+include: common/base.yml
+root:
+  level: warn
+loggers:
+  app::net:
+    level: debug
+",
+        )
+        .unwrap();
+
+        let source = Source::read(&dir.path().join("prod.yml"), Format::Yaml).unwrap();
+        assert_eq!(source.files.len(), 2);
+        let config = raw_config(source.value).unwrap();
+        assert_eq!(config.refresh_rate(), Some(Duration::from_secs(30)));
+        assert_eq!(config.root().level(), log::LevelFilter::Warn);
+        assert_eq!(config.root().appenders(), ["console"]);
+        let mut loggers = config
+            .loggers()
+            .iter()
+            .map(|logger| (logger.name().to_owned(), logger.level()))
+            .collect::<Vec<_>>();
+        loggers.sort();
+        assert_eq!(
+            loggers,
+            [
+                ("app::db".to_owned(), log::LevelFilter::Warn),
+                ("app::net".to_owned(), log::LevelFilter::Debug)
+            ]
+        );
+
+        fs::write(dir.path().join("a.yml"), "include: [b.yml]").unwrap();
+        fs::write(dir.path().join("b.yml"), "include: a.yml").unwrap();
+        assert!(Source::read(&dir.path().join("a.yml"), Format::Yaml).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "console_appender"))]
+    fn validate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yml");
+
+        fs::write(
+            &path,
+            "
+refresh_rate: 30 seconds
+appenders:
+  stdout:
+    kind: console
+root:
+  appenders: [stdout]
+loggers:
+  app::db:
+    level: warn
+",
+        )
+        .unwrap();
+        let report = validate_file(&path, &Deserializers::default()).unwrap();
+        assert_eq!(report.files(), std::slice::from_ref(&path));
+        assert_eq!(report.appenders(), ["stdout"]);
+        assert_eq!(report.loggers(), ["app::db"]);
+        assert_eq!(report.refresh_rate(), Some(Duration::from_secs(30)));
+
+        fs::write(&path, "root:\n  level: [warn\n").unwrap();
+        let errors = validate_file(&path, &Deserializers::default()).unwrap_err();
+        assert_eq!(errors.errors().len(), 1);
+        assert_eq!(errors.errors()[0].path(), Some(&*path));
+        assert!(errors.errors()[0].line().is_some());
+
+        fs::write(&path, "root:\n  level: warn\nappenders: {}\nfoo: bar\n").unwrap();
+        let errors = validate_file(&path, &Deserializers::default()).unwrap_err();
+        assert_eq!(errors.errors().len(), 1);
+        assert_eq!(errors.errors()[0].line(), Some(4));
+
+        fs::write(
+            &path,
+            "
+appenders:
+  stdout:
+    kind: console
+  broken:
+    kind: nonexistent
+root:
+  appenders:
+    - broken
+    - missing
+",
+        )
+        .unwrap();
+        let errors = validate_file(&path, &Deserializers::default()).unwrap_err();
+        let locations = errors
+            .errors()
+            .iter()
+            .map(|e| (e.line(), e.column()))
+            .collect::<Vec<_>>();
+        assert_eq!(locations, [(Some(5), Some(3)), (Some(10), Some(5))]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "notify", feature = "yaml_format"))]
+    fn watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yml");
+        fs::write(&path, "watch: 50ms\nroot:\n  level: info\n").unwrap();
+
+        let source = Source::read(&path, Format::Yaml).unwrap();
+        let config = raw_config(source.value.clone()).unwrap();
+        let mode = ReloadMode::of(&config).unwrap();
+        assert_eq!(mode, ReloadMode::Watch(Duration::from_millis(50)));
+        let logger = crate::Logger::new(deserialize(&config, &Deserializers::default()));
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        on_reload(move |result| {
+            let _ = tx.lock().unwrap().send(result.is_ok());
+        });
+        ConfigReloader::start(
+            path.clone(),
+            Format::Yaml,
+            mode,
+            source,
+            Deserializers::default(),
+            handle,
+        );
+        // Give the watcher time to start
+        thread::sleep(Duration::from_millis(200));
+
+        fs::write(&path, "watch: 50ms\nroot:\n  level: warn\n").unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(true));
+        assert_eq!(logger.max_log_level(), log::LevelFilter::Warn);
+
+        fs::write(&path, "watch: 50ms\nroot: [\n").unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(false));
+        assert_eq!(logger.max_log_level(), log::LevelFilter::Warn);
+    }
+
+    #[test]
+    #[cfg(feature = "notify")]
+    fn watch_ignores_siblings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log4rs.yml");
+        let sibling = dir.path().join("app.log");
+        fs::write(&path, "").unwrap();
+        let watcher = Watcher::new(vec![path.clone()]).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            fs::write(&path, "root:\n  level: warn\n").unwrap();
+            // Keep writing a sibling well past the debounce, as a log would
+            for i in 0..40 {
+                fs::write(&sibling, i.to_string()).unwrap();
+                if rx.try_recv().is_ok() {
+                    return true;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            false
+        });
+
+        watcher.wait(Duration::from_millis(300)).unwrap();
+        tx.send(()).unwrap();
+        assert!(writer.join().unwrap());
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "json_format"))]
+    fn set_config_from_str() {
+        let config = Config::builder()
+            .build(crate::config::Root::builder().build(log::LevelFilter::Info))
+            .unwrap();
+        let logger = crate::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        handle
+            .set_config_from_str("root:\n  level: warn\n", Format::Yaml)
+            .unwrap();
+        assert_eq!(logger.max_log_level(), log::LevelFilter::Warn);
+
+        handle
+            .set_config_from_reader(&br#"{"root": {"level": "debug"}}"#[..], Format::Json)
+            .unwrap();
+        assert_eq!(logger.max_log_level(), log::LevelFilter::Debug);
 
-        Ok(rate)
+        assert!(handle
+            .set_config_from_str("root: [\n", Format::Yaml)
+            .is_err());
+        assert_eq!(logger.max_log_level(), log::LevelFilter::Debug);
     }
 }
@@ -0,0 +1,341 @@
+//! Golden-output tests of logging configurations.
+//!
+//! A `Harness` runs a configuration against a fixed sequence of records and
+//! returns the bytes each appender produced, so a project can keep the
+//! expected output of its logging configuration next to it and have CI catch
+//! changes to patterns, filters and logger wiring.
+//!
+//! The console, file and rolling file appenders are replaced by in-memory
+//! appenders which encode records with the configured encoder; the rest of
+//! their configuration is ignored and nothing is written to the console or
+//! the file system. Other kinds of appenders are reported as errors.
+//! Heartbeats are not run.
+//!
+//! Records are timestamped by a mock clock which starts at
+//! 2024-01-01T00:00:00Z and advances by one second per record. Encoders
+//! which format times in the local time zone, or write the thread name or
+//! ID, produce output which depends on where the harness runs; use
+//! `{d(...)(utc)}` in patterns under test.
+//!
+//! Requires the `test_support` feature.
+//!
+//! ```
+//! # #[cfg(feature = "yaml_format")]
+//! # fn f() {
+//! use log::Level;
+//! use log4rs::config::{
+//!     harness::{CannedRecord, Harness},
+//!     RawConfig,
+//! };
+//!
+//! let config: RawConfig = serde_yaml::from_str(
+//!     "
+//! appenders:
+//!   requests:
+//!     kind: file
+//!     path: log/requests.log
+//!     encoder:
+//!       pattern: \"{d(%H:%M:%S)(utc)} {l} {m}{n}\"
+//! root:
+//!   level: info
+//!   appenders:
+//!     - requests
+//! ",
+//! )
+//! .unwrap();
+//!
+//! let output = Harness::new(config)
+//!     .record(CannedRecord::new(Level::Info, "app", "started"))
+//!     .record(CannedRecord::new(Level::Debug, "app", "hidden"))
+//!     .run()
+//!     .unwrap();
+//!
+//! assert_eq!(output["requests"], b"00:00:00 INFO started\n");
+//! # }
+//! # fn main() {}
+//! ```
+
+use derivative::Derivative;
+use log::{Level, Log, Record};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    append::Append,
+    config::{Config, Deserializers, RawConfig},
+    encode::{self, pattern::PatternEncoder, writer::simple::SimpleWriter, Encode, EncoderConfig},
+};
+
+/// A record fed to the configuration under test.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CannedRecord {
+    level: Level,
+    target: String,
+    message: String,
+    key_values: Vec<(String, String)>,
+    time: Option<SystemTime>,
+}
+
+impl CannedRecord {
+    /// Creates a record with the specified level, target and message.
+    pub fn new<T, M>(level: Level, target: T, message: M) -> CannedRecord
+    where
+        T: Into<String>,
+        M: Into<String>,
+    {
+        CannedRecord {
+            level,
+            target: target.into(),
+            message: message.into(),
+            key_values: vec![],
+            time: None,
+        }
+    }
+
+    /// Adds a key-value field.
+    pub fn key_value<K, V>(mut self, key: K, value: V) -> CannedRecord
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.key_values.push((key.into(), value.into()));
+        self
+    }
+
+    /// Sets the time the record is stamped with, instead of the mock clock's.
+    ///
+    /// The mock clock carries on from this time for the following records.
+    pub fn time(mut self, time: SystemTime) -> CannedRecord {
+        self.time = Some(time);
+        self
+    }
+}
+
+/// Runs a configuration against a sequence of records.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Harness {
+    config: RawConfig,
+    #[derivative(Debug = "ignore")]
+    deserializers: Deserializers,
+    records: Vec<CannedRecord>,
+}
+
+impl Harness {
+    /// Creates a harness for the specified configuration, using the default
+    /// deserializers.
+    pub fn new(config: RawConfig) -> Harness {
+        Harness {
+            config,
+            deserializers: Deserializers::default(),
+            records: vec![],
+        }
+    }
+
+    /// Sets the deserializers used for the configuration's encoders and
+    /// filters.
+    pub fn deserializers(mut self, deserializers: Deserializers) -> Harness {
+        self.deserializers = deserializers;
+        self
+    }
+
+    /// Adds a record to the sequence.
+    pub fn record(mut self, record: CannedRecord) -> Harness {
+        self.records.push(record);
+        self
+    }
+
+    /// Adds records to the sequence.
+    pub fn records<I>(mut self, records: I) -> Harness
+    where
+        I: IntoIterator<Item = CannedRecord>,
+    {
+        self.records.extend(records);
+        self
+    }
+
+    /// Logs the records through the configuration, returning the bytes each
+    /// appender produced by name.
+    ///
+    /// Fails if the configuration is invalid or has an appender of a kind the
+    /// harness cannot run.
+    pub fn run(self) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+        let outputs = Mutex::new(BTreeMap::new());
+        let (appenders, errors) =
+            self.config
+                .appenders_with(&self.deserializers, |name, appender, deserializers| {
+                    let encoder: Box<dyn Encode> = match &*appender.kind {
+                        "console" | "file" | "rolling_file" => {
+                            match encoder_config(&appender.config)? {
+                                Some(encoder) => {
+                                    deserializers.deserialize(&encoder.kind, encoder.config)?
+                                }
+                                None => Box::<PatternEncoder>::default(),
+                            }
+                        }
+                        kind => anyhow::bail!("appenders of kind `{}` are not supported", kind),
+                    };
+                    let output = Arc::new(Mutex::new(vec![]));
+                    outputs
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert(name.to_owned(), output.clone());
+                    Ok(Box::new(MemoryAppender { encoder, output }))
+                });
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        let mut builder = Config::builder()
+            .appenders(appenders)
+            .loggers(self.config.loggers())
+            .escalations(self.config.escalations())
+            .kv_limits(self.config.kv_limits())
+            .fields(self.config.fields())
+            .derived_fields(self.config.derived_fields())
+            .sanitize(self.config.sanitize());
+        if let Some(history) = self.config.history() {
+            builder = builder.history(history);
+        }
+        let logger = crate::Logger::new(builder.build(self.config.root())?);
+
+        let mut time = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        for record in &self.records {
+            if let Some(t) = record.time {
+                time = t;
+            }
+            let key_values = record
+                .key_values
+                .iter()
+                .map(|(k, v)| (&**k, &**v))
+                .collect::<Vec<_>>();
+            encode::with_record_time(time, || {
+                logger.log(
+                    &Record::builder()
+                        .level(record.level)
+                        .target(&record.target)
+                        .args(format_args!("{}", record.message))
+                        .key_values(&key_values)
+                        .build(),
+                )
+            });
+            time += Duration::from_secs(1);
+        }
+        drop(logger);
+
+        Ok(outputs
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .map(|(name, output)| {
+                let output = output.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                (name, output)
+            })
+            .collect())
+    }
+}
+
+/// Returns the encoder in an appender's configuration, if any.
+fn encoder_config(config: &serde_value::Value) -> anyhow::Result<Option<EncoderConfig>> {
+    if let serde_value::Value::Map(map) = config {
+        if let Some(encoder) = map.get(&serde_value::Value::String("encoder".to_owned())) {
+            return Ok(Some(encoder.clone().deserialize_into()?));
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Debug)]
+struct MemoryAppender {
+    encoder: Box<dyn Encode>,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Append for MemoryAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        self.encoder.encode(&mut SimpleWriter(&mut *output), record)
+    }
+
+    fn flush(&self) {
+        let _ = self
+            .output
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .flush();
+    }
+}
+
+#[cfg(all(test, feature = "yaml_format"))]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "threshold_filter")]
+    fn golden_output() {
+        let config = ::serde_yaml::from_str::<RawConfig>(
+            "
+appenders:
+  all:
+    kind: console
+    encoder:
+      pattern: \"{d(%Y-%m-%dT%H:%M:%S)(utc)} {l} {t} {K(user)} - {m}{n}\"
+  errors:
+    kind: file
+    path: /nonexistent/errors.log
+    filters:
+      - kind: threshold
+        level: error
+    encoder:
+      pattern: \"{m}{n}\"
+root:
+  level: info
+  appenders:
+    - all
+loggers:
+  app::db:
+    level: error
+    appenders:
+      - errors
+",
+        )
+        .unwrap();
+
+        let output = Harness::new(config)
+            .record(CannedRecord::new(Level::Info, "app", "started").key_value("user", "alice"))
+            .record(CannedRecord::new(Level::Warn, "app::db", "slow query"))
+            .record(CannedRecord::new(
+                Level::Error,
+                "app::db",
+                "connection lost",
+            ))
+            .run()
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(&output["all"]),
+            "2024-01-01T00:00:00 INFO app alice - started\n\
+             2024-01-01T00:00:02 ERROR app::db  - connection lost\n"
+        );
+        assert_eq!(output["errors"], b"connection lost\n");
+    }
+
+    #[test]
+    fn unsupported_kind() {
+        let config = ::serde_yaml::from_str::<RawConfig>(
+            "
+appenders:
+  tee:
+    kind: tee
+    appenders: []
+",
+        )
+        .unwrap();
+
+        assert!(Harness::new(config).run().is_err());
+    }
+}
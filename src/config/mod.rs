@@ -4,19 +4,54 @@
 use log::SetLoggerError;
 use thiserror::Error;
 
+#[cfg(feature = "config_parsing")]
 use crate::Handle;
 
 pub mod runtime;
 
+#[cfg(all(
+    feature = "console_appender",
+    feature = "rolling_file_appender",
+    feature = "compound_policy",
+    feature = "size_trigger",
+    feature = "fixed_window_roller",
+    feature = "json_encoder"
+))]
+mod auto;
 #[cfg(feature = "config_parsing")]
 mod file;
+#[cfg(all(feature = "config_parsing", feature = "test_support"))]
+pub mod harness;
+#[cfg(feature = "config_parsing")]
+pub mod plugin;
 #[cfg(feature = "config_parsing")]
 mod raw;
+#[cfg(feature = "config_schema")]
+mod schema;
 
-pub use runtime::{Appender, Config, Logger, Root};
+pub use runtime::{
+    Appender, Config, DerivedField, Escalation, Expression, Heartbeat, History, KvLimits, Logger,
+    Root, Sanitize,
+};
 
+#[cfg(all(
+    feature = "console_appender",
+    feature = "rolling_file_appender",
+    feature = "compound_policy",
+    feature = "size_trigger",
+    feature = "fixed_window_roller",
+    feature = "json_encoder"
+))]
+pub use self::auto::{auto_config, auto_init, Environment, LEVEL_VAR};
+#[cfg(feature = "config_parsing")]
+pub(crate) use self::file::load_config_str;
 #[cfg(feature = "config_parsing")]
-pub use self::file::{init_file, load_config_file, FormatError};
+pub use self::file::{
+    init_file, init_file_with_format, load_config_file, load_config_file_with_format, on_reload,
+    validate_file, ConfigReport, Format, FormatError, ValidationError, ValidationErrors,
+};
+#[cfg(feature = "config_parsing")]
+pub(crate) use self::raw::de_duration;
 #[cfg(feature = "config_parsing")]
 pub use self::raw::{Deserializable, Deserialize, Deserializers, RawConfig};
 
@@ -25,12 +60,7 @@ pub use self::raw::{Deserializable, Deserialize, Deserializers, RawConfig};
 /// A `Handle` object is returned which can be used to adjust the logging
 /// configuration.
 pub fn init_config(config: runtime::Config) -> Result<crate::Handle, SetLoggerError> {
-    let logger = crate::Logger::new(config);
-    log::set_max_level(logger.max_log_level());
-    let handle = Handle {
-        shared: logger.0.clone(),
-    };
-    log::set_boxed_logger(Box::new(logger)).map(|()| handle)
+    crate::Logger::new(config).install()
 }
 
 /// Initializes the global logger as a log4rs logger with the provided config and error handler.
@@ -41,12 +71,7 @@ pub fn init_config_with_err_handler(
     config: runtime::Config,
     err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
 ) -> Result<crate::Handle, SetLoggerError> {
-    let logger = crate::Logger::new_with_err_handler(config, err_handler);
-    log::set_max_level(logger.max_log_level());
-    let handle = Handle {
-        shared: logger.0.clone(),
-    };
-    log::set_boxed_logger(Box::new(logger)).map(|()| handle)
+    crate::Logger::new_with_err_handler(config, err_handler).install()
 }
 
 /// Initializes the global logger as a log4rs logger using the provided raw config.
@@ -58,14 +83,22 @@ pub fn init_raw_config(config: RawConfig) -> Result<(), InitError> {
     if !errors.is_empty() {
         return Err(InitError::Deserializing(errors));
     }
-    let config = Config::builder()
+    let mut builder = Config::builder()
         .appenders(appenders)
         .loggers(config.loggers())
-        .build(config.root())?;
+        .heartbeats(config.heartbeats())
+        .escalations(config.escalations())
+        .kv_limits(config.kv_limits())
+        .fields(config.fields())
+        .derived_fields(config.derived_fields())
+        .sanitize(config.sanitize())
+        .shutdown_summary(config.shutdown_summary());
+    if let Some(history) = config.history() {
+        builder = builder.history(history);
+    }
+    let config = builder.build(config.root())?;
 
-    let logger = crate::Logger::new(config);
-    log::set_max_level(logger.max_log_level());
-    log::set_boxed_logger(Box::new(logger))?;
+    crate::Logger::new(config).install()?;
     Ok(())
 }
 
@@ -0,0 +1,214 @@
+//! Components from other crates usable in config files.
+//!
+//! A component is usually made available to config files by implementing
+//! `Deserialize` for a deserializer type and inserting it into the
+//! `Deserializers` passed to `init_file`. Implementing `Plugin` for the
+//! component's config instead does without the deserializer type:
+//!
+//! ```
+//! # #[cfg(feature = "console_appender")]
+//! # fn f() {
+//! use log4rs::{
+//!     append::{console::ConsoleAppender, Append},
+//!     config::{plugin::Plugin, Deserializers},
+//! };
+//!
+//! #[derive(serde::Deserialize)]
+//! #[serde(deny_unknown_fields)]
+//! struct StderrAppenderConfig {}
+//!
+//! impl Plugin for StderrAppenderConfig {
+//!     type Trait = dyn Append;
+//!
+//!     fn build(self, _: &Deserializers) -> anyhow::Result<Box<dyn Append>> {
+//!         Ok(Box::new(
+//!             ConsoleAppender::builder()
+//!                 .target(log4rs::append::console::Target::Stderr)
+//!                 .build(),
+//!         ))
+//!     }
+//! }
+//!
+//! let mut deserializers = Deserializers::default();
+//! deserializers.insert_plugin::<StderrAppenderConfig>("stderr");
+//! # }
+//! ```
+//!
+//! With the `plugins` feature, a single line wherever the plugin is defined,
+//! `log4rs::plugin!("stderr", StderrAppenderConfig);`, registers it instead.
+//! `Deserializers::default` includes every plugin registered in the program,
+//! so that applications pick up the components of the crates they depend on
+//! without any code of their own.
+
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+
+use crate::config::{Deserializable, Deserialize, Deserializers};
+
+/// A trait implemented by the configs of components which build the
+/// component themselves.
+pub trait Plugin: DeserializeOwned + 'static {
+    /// The trait of the component, such as `dyn Append`.
+    type Trait: ?Sized + Deserializable;
+
+    /// Builds the component.
+    ///
+    /// `deserializers` deserializes the components nested in its config, such
+    /// as an appender's encoder.
+    fn build(self, deserializers: &Deserializers) -> anyhow::Result<Box<Self::Trait>>;
+}
+
+/// The deserializer of the components built by the plugin `P`.
+pub struct PluginDeserializer<P>(PhantomData<fn() -> P>);
+
+impl<P> Default for PluginDeserializer<P> {
+    fn default() -> PluginDeserializer<P> {
+        PluginDeserializer(PhantomData)
+    }
+}
+
+impl<P: Plugin> Deserialize for PluginDeserializer<P> {
+    type Trait = P::Trait;
+
+    type Config = P;
+
+    fn deserialize(
+        &self,
+        config: P,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<P::Trait>> {
+        config.build(deserializers)
+    }
+}
+
+impl Deserializers {
+    /// Adds a mapping from the specified `kind` to the plugin `P`.
+    pub fn insert_plugin<P: Plugin>(&mut self, kind: &str) {
+        self.insert(kind, PluginDeserializer::<P>::default());
+    }
+}
+
+/// Registers a plugin under a kind, so that `Deserializers::default`
+/// includes it.
+///
+/// Requires the `plugins` feature.
+///
+/// ```
+/// # #[cfg(feature = "threshold_filter")]
+/// # mod quiet {
+/// use log::LevelFilter;
+/// use log4rs::{
+///     config::{plugin::Plugin, Deserializers},
+///     filter::{threshold::ThresholdFilter, Filter},
+/// };
+///
+/// #[derive(serde::Deserialize)]
+/// struct QuietFilterConfig {}
+///
+/// impl Plugin for QuietFilterConfig {
+///     type Trait = dyn Filter;
+///
+///     fn build(self, _: &Deserializers) -> anyhow::Result<Box<dyn Filter>> {
+///         Ok(Box::new(ThresholdFilter::new(LevelFilter::Error)))
+///     }
+/// }
+///
+/// log4rs::plugin!("quiet", QuietFilterConfig);
+/// # }
+/// ```
+#[cfg(feature = "plugins")]
+#[macro_export]
+macro_rules! plugin {
+    ($kind:expr, $plugin:ty) => {
+        const _: () = {
+            fn register(deserializers: &mut $crate::config::Deserializers) {
+                deserializers.insert_plugin::<$plugin>($kind);
+            }
+
+            $crate::config::plugin::__private::inventory::submit! {
+                $crate::config::plugin::__private::Registration::new(register)
+            }
+        };
+    };
+}
+
+#[cfg(feature = "plugins")]
+#[doc(hidden)]
+pub mod __private {
+    pub use inventory;
+
+    use crate::config::Deserializers;
+
+    pub struct Registration {
+        register: fn(&mut Deserializers),
+    }
+
+    impl Registration {
+        pub const fn new(register: fn(&mut Deserializers)) -> Registration {
+            Registration { register }
+        }
+    }
+
+    inventory::collect!(Registration);
+
+    /// Inserts every registered plugin into `deserializers`.
+    pub(crate) fn register_all(deserializers: &mut Deserializers) {
+        for registration in inventory::iter::<Registration> {
+            (registration.register)(deserializers);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "threshold_filter")]
+mod test {
+    use log::LevelFilter;
+    use serde_value::Value;
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::filter::{threshold::ThresholdFilter, Filter};
+
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct QuietFilterConfig {}
+
+    impl Plugin for QuietFilterConfig {
+        type Trait = dyn Filter;
+
+        fn build(self, _: &Deserializers) -> anyhow::Result<Box<dyn Filter>> {
+            Ok(Box::new(ThresholdFilter::new(LevelFilter::Error)))
+        }
+    }
+
+    #[cfg(feature = "plugins")]
+    crate::plugin!("quiet", QuietFilterConfig);
+
+    #[test]
+    fn insert_plugin() {
+        let mut deserializers = Deserializers::empty();
+        deserializers.insert_plugin::<QuietFilterConfig>("quiet");
+        let config = Value::Map(BTreeMap::new());
+        assert!(deserializers
+            .deserialize::<dyn Filter>("quiet", config)
+            .is_ok());
+
+        let config = Value::Map(
+            vec![(Value::String("level".to_owned()), Value::Unit)]
+                .into_iter()
+                .collect(),
+        );
+        assert!(deserializers
+            .deserialize::<dyn Filter>("quiet", config)
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "plugins")]
+    fn registered() {
+        let config = Value::Map(BTreeMap::new());
+        assert!(Deserializers::default()
+            .deserialize::<dyn Filter>("quiet", config)
+            .is_ok());
+    }
+}
@@ -10,11 +10,29 @@
 //! of the YAML format.
 //!
 //! ```yaml
+//! # Config files to overlay this file on, as a path or a list of paths relative
+//! # to this file. They are merged in order, then this file is merged on top:
+//! # maps are merged key by key, with later values winning, while lists and
+//! # components of a different kind are replaced.
+//! include: base.yml
+//!
 //! # If set, log4rs will scan the file at the specified rate for changes and
 //! # automatically reconfigure the logger. The input string is parsed by the
 //! # humantime crate.
 //! refresh_rate: 30 seconds
 //!
+//! # If set, log4rs will instead watch the file and the files it includes for
+//! # changes using file system notifications, reconfiguring the logger once no
+//! # further change has been seen for the specified time. Requires the
+//! # `notify` feature; without it, "refresh_rate" is used.
+//! watch: 500ms
+//!
+//! # If set, a reload holds back new records, waits up to the specified time
+//! # for records being logged under the running configuration to finish, and
+//! # drains the queues of its appenders before applying the new one, so that
+//! # every record is written entirely under one configuration.
+//! quiesce: 1s
+//!
 //! # The "appenders" map contains the set of appenders, indexed by their names.
 //! appenders:
 //!
@@ -37,6 +55,37 @@
 //!         # filter's builder, and will vary based on the kind of filter.
 //!         level: error
 //!
+//!     # Processors attached to an appender modify the records which pass its
+//!     # filters, in order, before they are encoded. They are specified inside
+//!     # the "processors" array and, like filters, identified by their "kind".
+//!     processors:
+//!
+//!       - kind: fields
+//!         fields:
+//!           service: billing
+//!
+//!     # How control characters in messages are handled by this appender. One of
+//!     # "off", "strip" or "escape".
+//!     #
+//!     # Default: the top-level "sanitize" setting
+//!     sanitize: escape
+//!
+//!     # If set, the appender is flushed by a background thread at this
+//!     # interval, aligned to the wall clock so that "1 minute" flushes on the
+//!     # minute. The input string is parsed by the humantime crate.
+//!     #
+//!     # Default: flushed only on request
+//!     flush_interval: 1 minute
+//!
+//!     # If set, backwards steps of the clock up to this size, such as NTP
+//!     # adjustments, are hidden from the timestamps of the records this
+//!     # appender writes: until the clock catches up, records are stamped with
+//!     # the time of the appender's latest record and carry a "time_clamped_us"
+//!     # field. The input string is parsed by the humantime crate.
+//!     #
+//!     # Default: timestamps follow the clock
+//!     monotonic_time: 1 second
+//!
 //!     # The remainder of the configuration is passed along to the appender's
 //!     # builder, and will vary based on the kind of appender.
 //!     # Appenders will commonly be associated with an encoder.
@@ -51,6 +100,24 @@
 //!       # encoder's builder, and will vary based on the kind of encoder.
 //!       pattern: "{d} [{t}] {m}{n}"
 //!
+//!   bar:
+//!
+//!     # An appender can extend a template from the "appender_templates" map,
+//!     # overriding individual fields of it. Nested maps such as the encoder
+//!     # are merged field by field unless their "kind" differs.
+//!     extends: file_template
+//!     path: log/bar.log
+//!
+//! # The "appender_templates" map contains partial appender configurations
+//! # which appenders can extend. Templates can extend other templates.
+//! appender_templates:
+//!
+//!   file_template:
+//!     kind: file
+//!     path: log/default.log
+//!     encoder:
+//!       pattern: "{d} [{t}] {m}{n}"
+//!
 //! # The root logger is configured by the "root" map.
 //! root:
 //!
@@ -87,31 +154,220 @@
 //!     #
 //!     Default: true
 //!     additive: false
+//!
+//! # The "heartbeats" array contains synthetic records written at a fixed
+//! # interval, so that a quiet application can be told apart from a dead log
+//! # stream.
+//! heartbeats:
+//!
+//!   -
+//!     # The interval between heartbeat records. Required.
+//!     interval: 1 minute
+//!
+//!     # The level of the heartbeat record.
+//!     #
+//!     # Default: info
+//!     level: info
+//!
+//!     # The target of the heartbeat record.
+//!     #
+//!     # Default: log4rs::heartbeat
+//!     target: heartbeat
+//!
+//!     # The message of the heartbeat record.
+//!     #
+//!     # Default: heartbeat
+//!     message: still alive
+//!
+//!     # Key-value fields attached to the heartbeat record.
+//!     #
+//!     # Default: empty map
+//!     fields:
+//!       service: api
+//!
+//!     # The list of appenders the heartbeat record is written to.
+//!     #
+//!     # Default: all appenders
+//!     appenders:
+//!       - foo
+//!
+//! # The "escalations" array temporarily raises the levels of loggers when
+//! # records at or above a trigger level are logged in a burst. Once the
+//! # escalation ends, the configured levels apply again.
+//! escalations:
+//!
+//!   -
+//!     # How long the levels are raised for. Required.
+//!     duration: 5 minutes
+//!
+//!     # The level at or above which records are counted.
+//!     #
+//!     # Default: error
+//!     trigger: error
+//!
+//!     # The number of records within the window which starts the escalation.
+//!     #
+//!     # Default: 10
+//!     threshold: 10
+//!
+//!     # The window the records are counted within.
+//!     #
+//!     # Default: 1 minute
+//!     window: 1 minute
+//!
+//!     # The level the loggers are raised to.
+//!     #
+//!     # Default: debug
+//!     level: debug
+//!
+//!     # The loggers raised, along with the loggers below them.
+//!     #
+//!     # Default: the root logger
+//!     targets:
+//!       - foo::bar
+//!
+//!     # How long after ending the escalation cannot start again.
+//!     #
+//!     # Default: 0 seconds
+//!     cooldown: 10 minutes
+//!
+//!     # The number of times the escalation may start.
+//!     #
+//!     # Default: unlimited
+//!     max_activations: 3
+//!
+//! # The "history" map keeps recent records in memory, including those below
+//! # the levels of their loggers, so they can be written out on demand with
+//! # `Handle::dump_recent`.
+//! history:
+//!
+//!   # How long records are kept for. Required.
+//!   duration: 5 minutes
+//!
+//!   # The level at or above which records are kept.
+//!   #
+//!   # Default: debug
+//!   level: debug
+//!
+//!   # The maximum number of records kept.
+//!   #
+//!   # Default: 10000
+//!   capacity: 10000
+//!
+//! # Limits on the key-value fields attached to records. Records exceeding them
+//! # have excess fields dropped and long keys and values truncated, and gain a
+//! # "_truncated_fields" field counting the fields affected.
+//! kv_limits:
+//!
+//!   # The maximum number of fields of a record.
+//!   #
+//!   # Default: unlimited
+//!   max_fields: 32
+//!
+//!   # The maximum length of a key in bytes.
+//!   #
+//!   # Default: unlimited
+//!   max_key_len: 64
+//!
+//!   # The maximum length of a value in bytes.
+//!   #
+//!   # Default: unlimited
+//!   max_value_len: 1024
+//!
+//! # Key-value fields added to every record, such as the name and version of
+//! # the service. Records which already have a field with the same key keep
+//! # their value.
+//! fields:
+//!   service: billing
+//!   version: ${APP_VERSION:-dev}
+//!
+//! # Key-value fields added to every record, computed from the record by an
+//! # expression. Expressions can refer to the level, target, module_path, file,
+//! # line and message of the record and to its key-value fields as "kv.<key>",
+//! # and can call the functions "map", "truncate", "lower", "upper" and
+//! # "concat". Fields are added before the kv_limits are applied.
+//! derived_fields:
+//!   severity_num: "map(level, {error: 3, warn: 4, info: 6}, 7)"
+//!   short_target: "truncate(target, 20)"
+//!
+//! # How control characters in messages are handled by appenders. "strip"
+//! # removes them along with the rest of any ANSI escape sequences they start,
+//! # while "escape" replaces them with escapes such as "\r". Newlines and tabs
+//! # are always kept.
+//! #
+//! # Default: off
+//! sanitize: strip
+//!
+//! # If set, the records logged at each level are counted, and Handle::shutdown
+//! # writes a summary record with the counts to every appender.
+//! #
+//! # Default: false
+//! shutdown_summary: true
+//!
+//! # If set, only components of these kinds may be used, such as appenders,
+//! # encoders and filters. Any other kind fails to load, whatever the
+//! # application has registered.
+//! allowed_kinds:
+//!   - console
+//!   - rolling_file
+//!   - pattern
 //! ```
 #![allow(deprecated)]
 
 use std::{
-    borrow::ToOwned, collections::HashMap, fmt, marker::PhantomData, sync::Arc, time::Duration,
+    borrow::{Cow, ToOwned},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    marker::PhantomData,
+    sync::Arc,
+    time::Duration,
 };
 
 use anyhow::anyhow;
 use derivative::Derivative;
-use log::LevelFilter;
+use log::{Level, LevelFilter};
 use serde::de::{self, Deserialize as SerdeDeserialize, DeserializeOwned};
 use serde_value::Value;
 use thiserror::Error;
 use typemap_ors::{Key, ShareCloneMap};
 
-use crate::{append::AppenderConfig, config};
+use crate::{
+    append::{Append, AppenderConfig},
+    config,
+};
 
 #[allow(unused_imports)]
 use crate::append;
 
-#[cfg(any(feature = "json_encoder", feature = "pattern_encoder"))]
+#[cfg(feature = "gzip")]
+use crate::compress;
+
+#[cfg(feature = "age_encryption")]
+use crate::encrypt;
+
+#[cfg(any(
+    feature = "json_encoder",
+    feature = "pattern_encoder",
+    feature = "wrap_encoder"
+))]
 use crate::encode;
 
-#[cfg(feature = "threshold_filter")]
+#[cfg(any(
+    feature = "threshold_filter",
+    feature = "level_range_filter",
+    feature = "regex_filter",
+    feature = "rate_limit_filter",
+    feature = "dedup_filter",
+    feature = "sample_filter",
+    feature = "time_window_filter"
+))]
 use crate::filter;
+#[cfg(any(
+    feature = "fields_processor",
+    feature = "redact_processor",
+    feature = "target_processor"
+))]
+use crate::processor;
 
 /// A trait implemented by traits which are deserializable.
 pub trait Deserializable: 'static {
@@ -171,9 +427,20 @@ impl<T: ?Sized + 'static> Key for KeyAdaptor<T> {
     type Value = HashMap<String, Arc<dyn ErasedDeserialize<Trait = T>>>;
 }
 
+// The fields of a component's config, if it is a struct.
+#[cfg(feature = "config_schema")]
+type Fields = Option<&'static [&'static str]>;
+
 /// A container of `Deserialize`rs.
 #[derive(Clone)]
-pub struct Deserializers(ShareCloneMap);
+pub struct Deserializers {
+    map: ShareCloneMap,
+    // The kinds which may be deserialized, if restricted.
+    allowed: Option<Arc<HashSet<String>>>,
+    // The fields of each kind's config, by the name of the trait it creates.
+    #[cfg(feature = "config_schema")]
+    kinds: BTreeMap<&'static str, BTreeMap<String, Fields>>,
+}
 
 impl Default for Deserializers {
     fn default() -> Deserializers {
@@ -192,6 +459,39 @@ impl Default for Deserializers {
             append::rolling_file::RollingFileAppenderDeserializer,
         );
 
+        #[cfg(feature = "failover_appender")]
+        d.insert("failover", append::failover::FailoverAppenderDeserializer);
+
+        #[cfg(feature = "alert_appender")]
+        d.insert("alert", append::alert::AlertAppenderDeserializer);
+
+        #[cfg(feature = "kafka")]
+        d.insert("kafka", append::kafka::KafkaAppenderDeserializer);
+
+        #[cfg(feature = "otel")]
+        d.insert("otel", append::otel::OtelAppenderDeserializer);
+
+        #[cfg(feature = "ring_buffer_appender")]
+        d.insert(
+            "ring_buffer",
+            append::ring_buffer::RingBufferAppenderDeserializer,
+        );
+
+        #[cfg(feature = "routing_appender")]
+        d.insert("routing", append::routing::RoutingAppenderDeserializer);
+
+        #[cfg(feature = "sqlite")]
+        d.insert("sqlite", append::sqlite::SqliteAppenderDeserializer);
+
+        #[cfg(feature = "tee_appender")]
+        d.insert("tee", append::tee::TeeAppenderDeserializer);
+
+        #[cfg(all(unix, feature = "unix_socket_appender"))]
+        d.insert(
+            "unix_socket",
+            append::unix_socket::UnixSocketAppenderDeserializer,
+        );
+
         #[cfg(feature = "compound_policy")]
         d.insert(
             "compound",
@@ -210,21 +510,81 @@ impl Default for Deserializers {
             append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRollerDeserializer,
         );
 
+        #[cfg(feature = "date_roller")]
+        d.insert(
+            "date",
+            append::rolling_file::policy::compound::roll::date::DateRollerDeserializer,
+        );
+
         #[cfg(feature = "size_trigger")]
         d.insert(
             "size",
             append::rolling_file::policy::compound::trigger::size::SizeTriggerDeserializer,
         );
 
+        #[cfg(feature = "level_trigger")]
+        d.insert(
+            "level",
+            append::rolling_file::policy::compound::trigger::level::LevelTriggerDeserializer,
+        );
+
+        #[cfg(feature = "gzip")]
+        d.insert("gzip", compress::gzip::GzipCompressorDeserializer);
+
+        #[cfg(feature = "age_encryption")]
+        d.insert("age", encrypt::age::AgeEncryptorDeserializer);
+
         #[cfg(feature = "json_encoder")]
         d.insert("json", encode::json::JsonEncoderDeserializer);
 
         #[cfg(feature = "pattern_encoder")]
         d.insert("pattern", encode::pattern::PatternEncoderDeserializer);
 
+        #[cfg(feature = "wrap_encoder")]
+        d.insert("wrap", encode::wrap::WrapEncoderDeserializer);
+
         #[cfg(feature = "threshold_filter")]
         d.insert("threshold", filter::threshold::ThresholdFilterDeserializer);
 
+        #[cfg(feature = "level_range_filter")]
+        d.insert(
+            "level_range",
+            filter::level_range::LevelRangeFilterDeserializer,
+        );
+
+        #[cfg(feature = "regex_filter")]
+        d.insert("regex", filter::regex::RegexFilterDeserializer);
+
+        #[cfg(feature = "rate_limit_filter")]
+        d.insert(
+            "rate_limit",
+            filter::rate_limit::RateLimitFilterDeserializer,
+        );
+
+        #[cfg(feature = "dedup_filter")]
+        d.insert("dedup", filter::dedup::DedupFilterDeserializer);
+
+        #[cfg(feature = "sample_filter")]
+        d.insert("sample", filter::sample::SampleFilterDeserializer);
+
+        #[cfg(feature = "time_window_filter")]
+        d.insert(
+            "time_window",
+            filter::time_window::TimeWindowFilterDeserializer,
+        );
+
+        #[cfg(feature = "fields_processor")]
+        d.insert("fields", processor::fields::FieldsProcessorDeserializer);
+
+        #[cfg(feature = "redact_processor")]
+        d.insert("redact", processor::redact::RedactProcessorDeserializer);
+
+        #[cfg(feature = "target_processor")]
+        d.insert("target", processor::target::TargetProcessorDeserializer);
+
+        #[cfg(feature = "plugins")]
+        config::plugin::__private::register_all(&mut d);
+
         d
     }
 }
@@ -241,14 +601,53 @@ impl Deserializers {
     ///         * Requires the `file_appender` feature.
     ///     * "rolling_file" -> `RollingFileAppenderDeserializer`
     ///         * Requires the `rolling_file_appender` feature.
+    ///     * "failover" -> `FailoverAppenderDeserializer`
+    ///         * Requires the `failover_appender` feature.
+    ///     * "alert" -> `AlertAppenderDeserializer`
+    ///         * Requires the `alert_appender` feature.
+    ///     * "kafka" -> `KafkaAppenderDeserializer`
+    ///         * Requires the `kafka` feature.
+    ///     * "otel" -> `OtelAppenderDeserializer`
+    ///         * Requires the `otel` feature.
+    ///     * "ring_buffer" -> `RingBufferAppenderDeserializer`
+    ///         * Requires the `ring_buffer_appender` feature.
+    ///     * "routing" -> `RoutingAppenderDeserializer`
+    ///         * Requires the `routing_appender` feature.
+    ///     * "sqlite" -> `SqliteAppenderDeserializer`
+    ///         * Requires the `sqlite` feature.
+    ///     * "tee" -> `TeeAppenderDeserializer`
+    ///         * Requires the `tee_appender` feature.
+    ///     * "unix_socket" -> `UnixSocketAppenderDeserializer`
+    ///         * Requires the `unix_socket_appender` feature, and Unix.
     /// * Encoders
     ///     * "pattern" -> `PatternEncoderDeserializer`
     ///         * Requires the `pattern_encoder` feature.
     ///     * "json" -> `JsonEncoderDeserializer`
     ///         * Requires the `json_encoder` feature.
+    ///     * "wrap" -> `WrapEncoderDeserializer`
+    ///         * Requires the `wrap_encoder` feature.
     /// * Filters
     ///     * "threshold" -> `ThresholdFilterDeserializer`
     ///         * Requires the `threshold_filter` feature.
+    ///     * "level_range" -> `LevelRangeFilterDeserializer`
+    ///         * Requires the `level_range_filter` feature.
+    ///     * "regex" -> `RegexFilterDeserializer`
+    ///         * Requires the `regex_filter` feature.
+    ///     * "rate_limit" -> `RateLimitFilterDeserializer`
+    ///         * Requires the `rate_limit_filter` feature.
+    ///     * "dedup" -> `DedupFilterDeserializer`
+    ///         * Requires the `dedup_filter` feature.
+    ///     * "sample" -> `SampleFilterDeserializer`
+    ///         * Requires the `sample_filter` feature.
+    ///     * "time_window" -> `TimeWindowFilterDeserializer`
+    ///         * Requires the `time_window_filter` feature.
+    /// * Processors
+    ///     * "fields" -> `FieldsProcessorDeserializer`
+    ///         * Requires the `fields_processor` feature.
+    ///     * "redact" -> `RedactProcessorDeserializer`
+    ///         * Requires the `redact_processor` feature.
+    ///     * "target" -> `TargetProcessorDeserializer`
+    ///         * Requires the `target_processor` feature.
     /// * Policies
     ///     *  "compound" -> `CompoundPolicyDeserializer`
     ///         * Requires the `compound_policy` feature.
@@ -257,16 +656,33 @@ impl Deserializers {
     ///         * Requires the `delete_roller` feature.
     ///     * "fixed_window" -> `FixedWindowRollerDeserializer`
     ///         * Requires the `fixed_window_roller` feature.
+    ///     * "date" -> `DateRollerDeserializer`
+    ///         * Requires the `date_roller` feature.
     /// * Triggers
     ///     * "size" -> `SizeTriggerDeserializer`
     ///         * Requires the `size_trigger` feature.
+    ///     * "level" -> `LevelTriggerDeserializer`
+    ///         * Requires the `level_trigger` feature.
+    /// * Compressors
+    ///     * "gzip" -> `GzipCompressorDeserializer`
+    ///         * Requires the `gzip` feature.
+    /// * Encryptors
+    ///     * "age" -> `AgeEncryptorDeserializer`
+    ///         * Requires the `age_encryption` feature.
+    /// * Plugins registered with `log4rs::plugin!`
+    ///     * Requires the `plugins` feature.
     pub fn new() -> Deserializers {
         Deserializers::default()
     }
 
     /// Creates a new `Deserializers` with no mappings.
     pub fn empty() -> Deserializers {
-        Deserializers(ShareCloneMap::custom())
+        Deserializers {
+            map: ShareCloneMap::custom(),
+            allowed: None,
+            #[cfg(feature = "config_schema")]
+            kinds: BTreeMap::new(),
+        }
     }
 
     /// Adds a mapping from the specified `kind` to a deserializer.
@@ -274,18 +690,83 @@ impl Deserializers {
     where
         T: Deserialize,
     {
-        self.0
+        self.map
             .entry::<KeyAdaptor<T::Trait>>()
             .or_insert_with(HashMap::new)
             .insert(kind.to_owned(), Arc::new(DeserializeEraser(deserializer)));
+        #[cfg(feature = "config_schema")]
+        self.kinds
+            .entry(T::Trait::name())
+            .or_default()
+            .insert(kind.to_owned(), config::schema::fields::<T::Config>());
+    }
+
+    /// Removes the mapping for the specified `kind` of component of type `T`,
+    /// such as one of the defaults, so that it cannot be deserialized.
+    pub fn remove<T>(&mut self, kind: &str)
+    where
+        T: ?Sized + Deserializable,
+    {
+        if let Some(map) = self.map.get_mut::<KeyAdaptor<T>>() {
+            map.remove(kind);
+        }
+        #[cfg(feature = "config_schema")]
+        if let Some(kinds) = self.kinds.get_mut(T::name()) {
+            kinds.remove(kind);
+        }
+    }
+
+    /// Restricts the kinds of component which may be deserialized, of any
+    /// type, to `kinds`.
+    ///
+    /// Deserializing any other kind fails, including components nested in
+    /// others such as encoders and filters, so an application can guarantee
+    /// that no config file makes it, for example, open sockets. Further calls
+    /// narrow the restriction rather than widen it.
+    pub fn allow_only<I, S>(&mut self, kinds: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut kinds = kinds.into_iter().map(Into::into).collect::<HashSet<_>>();
+        if let Some(ref allowed) = self.allowed {
+            kinds.retain(|kind| allowed.contains(kind));
+        }
+        self.allowed = Some(Arc::new(kinds));
+    }
+
+    /// Returns whether components of the specified `kind` may be
+    /// deserialized.
+    pub fn is_allowed(&self, kind: &str) -> bool {
+        self.allowed
+            .as_ref()
+            .map_or(true, |allowed| allowed.contains(kind))
+    }
+
+    /// Returns the kinds which may be deserialized and the fields of their
+    /// configs, by the name of the trait they create.
+    #[cfg(feature = "config_schema")]
+    pub(crate) fn kinds(&self) -> impl Iterator<Item = (&'static str, Vec<(&str, Fields)>)> + '_ {
+        self.kinds.iter().map(move |(&name, kinds)| {
+            let kinds = kinds
+                .iter()
+                .filter(|(kind, _)| self.is_allowed(kind))
+                .map(|(kind, fields)| (&**kind, *fields))
+                .collect();
+            (name, kinds)
+        })
     }
 
     /// Deserializes a value of a specific type and kind.
-    pub fn deserialize<T: ?Sized>(&self, kind: &str, config: Value) -> anyhow::Result<Box<T>>
+    pub fn deserialize<T>(&self, kind: &str, config: Value) -> anyhow::Result<Box<T>>
     where
-        T: Deserializable,
+        T: ?Sized + Deserializable,
     {
-        match self.0.get::<KeyAdaptor<T>>().and_then(|m| m.get(kind)) {
+        if !self.is_allowed(kind) {
+            return Err(anyhow!("{} kind `{}` is not allowed", T::name(), kind));
+        }
+
+        match self.map.get::<KeyAdaptor<T>>().and_then(|m| m.get(kind)) {
             Some(b) => b.deserialize(config, self),
             None => Err(anyhow!(
                 "no {} deserializer for kind `{}` registered",
@@ -302,6 +783,47 @@ pub enum DeserializingConfigError {
     Appender(String, anyhow::Error),
     #[error("error deserializing filter attached to appender {0}: {1}")]
     Filter(String, anyhow::Error),
+    #[error("error deserializing processor attached to appender {0}: {1}")]
+    Processor(String, anyhow::Error),
+}
+
+impl DeserializingConfigError {
+    pub(crate) fn appender(&self) -> &str {
+        match *self {
+            DeserializingConfigError::Appender(ref name, _)
+            | DeserializingConfigError::Filter(ref name, _)
+            | DeserializingConfigError::Processor(ref name, _) => name,
+        }
+    }
+}
+
+/// An appender's configuration, parsed once the templates it extends, if
+/// any, are applied.
+#[derive(Clone, Debug)]
+enum RawAppender {
+    Config(AppenderConfig),
+    Extends(Value),
+}
+
+impl<'de> SerdeDeserialize<'de> for RawAppender {
+    fn deserialize<D>(d: D) -> Result<RawAppender, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let config = Value::deserialize(d)?;
+        let extends = match config {
+            Value::Map(ref map) => map.contains_key(&Value::String("extends".to_owned())),
+            _ => false,
+        };
+        if extends {
+            Ok(RawAppender::Extends(config))
+        } else {
+            config
+                .deserialize_into()
+                .map(RawAppender::Config)
+                .map_err(|e| e.into_error())
+        }
+    }
 }
 
 /// A raw deserializable log4rs configuration.
@@ -311,14 +833,50 @@ pub struct RawConfig {
     #[serde(deserialize_with = "de_duration", default)]
     refresh_rate: Option<Duration>,
 
+    #[serde(deserialize_with = "de_duration", default)]
+    watch: Option<Duration>,
+
+    #[serde(deserialize_with = "de_duration", default)]
+    quiesce: Option<Duration>,
+
     #[serde(default)]
     root: Root,
 
     #[serde(default)]
-    appenders: HashMap<String, AppenderConfig>,
+    appenders: HashMap<String, RawAppender>,
+
+    #[serde(default)]
+    appender_templates: HashMap<String, Value>,
 
     #[serde(default)]
     loggers: HashMap<String, Logger>,
+
+    #[serde(default)]
+    heartbeats: Vec<Heartbeat>,
+
+    #[serde(default)]
+    escalations: Vec<Escalation>,
+
+    #[serde(default)]
+    history: Option<History>,
+
+    #[serde(default)]
+    kv_limits: KvLimits,
+
+    #[serde(default)]
+    fields: BTreeMap<String, String>,
+
+    #[serde(default)]
+    derived_fields: BTreeMap<String, config::Expression>,
+
+    #[serde(default)]
+    sanitize: config::Sanitize,
+
+    #[serde(default)]
+    shutdown_summary: bool,
+
+    #[serde(default)]
+    allowed_kinds: Option<Vec<String>>,
 }
 
 #[derive(Debug, Error)]
@@ -334,6 +892,9 @@ impl AppenderErrors {
             crate::handle_error(&error.into());
         }
     }
+    pub(crate) fn into_inner(self) -> Vec<DeserializingConfigError> {
+        self.0
+    }
 }
 
 impl RawConfig {
@@ -357,6 +918,112 @@ impl RawConfig {
             .collect()
     }
 
+    /// Returns the heartbeats.
+    pub fn heartbeats(&self) -> Vec<config::Heartbeat> {
+        self.heartbeats
+            .iter()
+            .map(|heartbeat| {
+                let mut builder = config::Heartbeat::builder()
+                    .level(heartbeat.level)
+                    .appenders(heartbeat.appenders.clone());
+                if let Some(ref target) = heartbeat.target {
+                    builder = builder.target(target.clone());
+                }
+                if let Some(ref message) = heartbeat.message {
+                    builder = builder.message(message.clone());
+                }
+                for (key, value) in &heartbeat.fields {
+                    builder = builder.field(key.clone(), value.clone());
+                }
+                builder.build(heartbeat.interval)
+            })
+            .collect()
+    }
+
+    /// Returns the escalations.
+    pub fn escalations(&self) -> Vec<config::Escalation> {
+        self.escalations
+            .iter()
+            .map(|escalation| {
+                let mut builder = config::Escalation::builder().targets(escalation.targets.clone());
+                if let Some(trigger) = escalation.trigger {
+                    builder = builder.trigger(trigger);
+                }
+                if let Some(threshold) = escalation.threshold {
+                    builder = builder.threshold(threshold);
+                }
+                if let Some(window) = escalation.window {
+                    builder = builder.window(window);
+                }
+                if let Some(level) = escalation.level {
+                    builder = builder.level(level);
+                }
+                if let Some(cooldown) = escalation.cooldown {
+                    builder = builder.cooldown(cooldown);
+                }
+                if let Some(max_activations) = escalation.max_activations {
+                    builder = builder.max_activations(max_activations);
+                }
+                builder.build(escalation.duration.unwrap_or(Duration::from_secs(300)))
+            })
+            .collect()
+    }
+
+    /// Returns the history of recent records, if configured.
+    pub fn history(&self) -> Option<config::History> {
+        self.history.as_ref().map(|history| {
+            let mut builder = config::History::builder();
+            if let Some(level) = history.level {
+                builder = builder.level(level);
+            }
+            if let Some(capacity) = history.capacity {
+                builder = builder.capacity(capacity);
+            }
+            builder.build(history.duration.unwrap_or(Duration::from_secs(300)))
+        })
+    }
+
+    /// Returns the limits on key-value fields.
+    pub fn kv_limits(&self) -> config::KvLimits {
+        let mut builder = config::KvLimits::builder();
+        if let Some(max_fields) = self.kv_limits.max_fields {
+            builder = builder.max_fields(max_fields);
+        }
+        if let Some(max_key_len) = self.kv_limits.max_key_len {
+            builder = builder.max_key_len(max_key_len);
+        }
+        if let Some(max_value_len) = self.kv_limits.max_value_len {
+            builder = builder.max_value_len(max_value_len);
+        }
+        builder.build()
+    }
+
+    /// Returns the key-value fields added to every record.
+    pub fn fields(&self) -> Vec<(String, String)> {
+        self.fields
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Returns the derived fields.
+    pub fn derived_fields(&self) -> Vec<config::DerivedField> {
+        self.derived_fields
+            .iter()
+            .map(|(name, expression)| config::DerivedField::new(name.clone(), expression.clone()))
+            .collect()
+    }
+
+    /// Returns how control characters in messages are handled.
+    pub fn sanitize(&self) -> config::Sanitize {
+        self.sanitize
+    }
+
+    /// Returns whether `Handle::shutdown` writes a summary record.
+    pub fn shutdown_summary(&self) -> bool {
+        self.shutdown_summary
+    }
+
     /// Returns the appenders.
     ///
     /// Any components which fail to be deserialized will be ignored.
@@ -364,18 +1031,82 @@ impl RawConfig {
         &self,
         deserializers: &Deserializers,
     ) -> (Vec<config::Appender>, AppenderErrors) {
+        self.appenders_with(deserializers, |_, appender, deserializers| {
+            deserializers.deserialize(&appender.kind, appender.config.clone())
+        })
+    }
+
+    /// Returns the kinds of component the configuration restricts itself to,
+    /// if any.
+    pub fn allowed_kinds(&self) -> Option<&[String]> {
+        self.allowed_kinds.as_deref()
+    }
+
+    /// Returns the appenders, with the `Append` implementation of each built
+    /// by `build` from its resolved configuration and the deserializers to
+    /// use for its components.
+    pub(crate) fn appenders_with<F>(
+        &self,
+        deserializers: &Deserializers,
+        mut build: F,
+    ) -> (Vec<config::Appender>, AppenderErrors)
+    where
+        F: FnMut(&str, &AppenderConfig, &Deserializers) -> anyhow::Result<Box<dyn Append>>,
+    {
         let mut appenders = vec![];
         let mut errors = vec![];
 
+        let restricted;
+        let deserializers = match self.allowed_kinds {
+            Some(ref kinds) => {
+                let mut d = deserializers.clone();
+                d.allow_only(kinds.iter().cloned());
+                restricted = d;
+                &restricted
+            }
+            None => deserializers,
+        };
+
         for (name, appender) in &self.appenders {
-            let mut builder = config::Appender::builder();
+            let appender = match self.resolve_appender(appender) {
+                Ok(appender) => appender,
+                Err(e) => {
+                    errors.push(DeserializingConfigError::Appender(name.clone(), e));
+                    continue;
+                }
+            };
+
+            let mut builder =
+                config::Appender::builder().fingerprint(config::runtime::stable_hash(&appender));
+            if let Some(sanitize) = appender.sanitize {
+                builder = builder.sanitize(sanitize);
+            }
+            if let Some(flush_interval) = appender.flush_interval {
+                builder = builder.flush_interval(flush_interval);
+            }
+            if let Some(monotonic_time) = appender.monotonic_time {
+                builder = builder.monotonic_time(monotonic_time);
+            }
             for filter in &appender.filters {
                 match deserializers.deserialize(&filter.kind, filter.config.clone()) {
                     Ok(filter) => builder = builder.filter(filter),
                     Err(e) => errors.push(DeserializingConfigError::Filter(name.clone(), e)),
                 }
             }
-            match deserializers.deserialize(&appender.kind, appender.config.clone()) {
+            for processor in &appender.processors {
+                match deserializers.deserialize(&processor.kind, processor.config.clone()) {
+                    Ok(processor) => builder = builder.processor(processor),
+                    Err(e) => errors.push(DeserializingConfigError::Processor(name.clone(), e)),
+                }
+            }
+            if !deserializers.is_allowed(&appender.kind) {
+                errors.push(DeserializingConfigError::Appender(
+                    name.clone(),
+                    anyhow!("appender kind `{}` is not allowed", appender.kind),
+                ));
+                continue;
+            }
+            match build(name, &appender, deserializers) {
                 Ok(appender) => appenders.push(builder.build(name.clone(), appender)),
                 Err(e) => errors.push(DeserializingConfigError::Appender(name.clone(), e)),
             }
@@ -384,13 +1115,65 @@ impl RawConfig {
         (appenders, AppenderErrors(errors))
     }
 
+    /// Applies the appender templates an appender extends to its
+    /// configuration.
+    fn resolve_appender<'a>(
+        &self,
+        appender: &'a RawAppender,
+    ) -> anyhow::Result<Cow<'a, AppenderConfig>> {
+        match *appender {
+            RawAppender::Config(ref config) => Ok(Cow::Borrowed(config)),
+            RawAppender::Extends(ref config) => {
+                let config = self.extend(config.clone(), &mut vec![])?;
+                Ok(Cow::Owned(config.deserialize_into()?))
+            }
+        }
+    }
+
+    fn extend(&self, config: Value, seen: &mut Vec<String>) -> anyhow::Result<Value> {
+        let mut map = match config {
+            Value::Map(map) => map,
+            config => return Ok(config),
+        };
+
+        let template = match map.remove(&Value::String("extends".to_owned())) {
+            Some(Value::String(template)) => template,
+            Some(_) => return Err(anyhow!("`extends` must be the name of a template")),
+            None => return Ok(Value::Map(map)),
+        };
+        if seen.contains(&template) {
+            return Err(anyhow!("appender template `{}` extends itself", template));
+        }
+
+        let base = self
+            .appender_templates
+            .get(&template)
+            .ok_or_else(|| anyhow!("reference to nonexistent appender template `{}`", template))?
+            .clone();
+        seen.push(template);
+        let base = self.extend(base, seen)?;
+
+        Ok(merge(base, Value::Map(map)))
+    }
+
     /// Returns the requested refresh rate.
     pub fn refresh_rate(&self) -> Option<Duration> {
         self.refresh_rate
     }
+
+    /// Returns the requested debounce time of file watching.
+    pub fn watch(&self) -> Option<Duration> {
+        self.watch
+    }
+
+    /// Returns how long a reload waits for records being logged under the
+    /// running configuration to finish, if reloads quiesce it.
+    pub fn quiesce(&self) -> Option<Duration> {
+        self.quiesce
+    }
 }
 
-fn de_duration<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
+pub(crate) fn de_duration<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
 where
     D: de::Deserializer<'de>,
 {
@@ -425,10 +1208,17 @@ where
     Option::<S>::deserialize(d).map(|r| r.map(|s| s.0))
 }
 
+fn de_required_duration<'de, D>(d: D) -> Result<Duration, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    de_duration(d)?.ok_or_else(|| de::Error::custom("a duration is required"))
+}
+
 #[derive(Clone, Debug, Derivative, serde::Deserialize)]
 #[derivative(Default)]
 #[serde(deny_unknown_fields)]
-struct Root {
+pub(super) struct Root {
     #[serde(default = "root_level_default")]
     #[derivative(Default(value = "root_level_default()"))]
     level: LevelFilter,
@@ -442,7 +1232,7 @@ fn root_level_default() -> LevelFilter {
 
 #[derive(serde::Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
-struct Logger {
+pub(super) struct Logger {
     level: LevelFilter,
     #[serde(default)]
     appenders: Vec<String>,
@@ -454,13 +1244,102 @@ fn logger_additive_default() -> bool {
     true
 }
 
+/// Overlays `config` on `base`, merging maps key by key. Maps of components
+/// of different kinds are not merged.
+pub(crate) fn merge(base: Value, config: Value) -> Value {
+    let kind = Value::String("kind".to_owned());
+    match (base, config) {
+        (Value::Map(base), Value::Map(config))
+            if config.contains_key(&kind) && base.get(&kind) != config.get(&kind) =>
+        {
+            Value::Map(config)
+        }
+        (Value::Map(mut base), Value::Map(config)) => {
+            for (key, value) in config {
+                let value = match base.remove(&key) {
+                    Some(base) => merge(base, value),
+                    None => value,
+                };
+                base.insert(key, value);
+            }
+            Value::Map(base)
+        }
+        (_, config) => config,
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Heartbeat {
+    #[serde(deserialize_with = "de_required_duration")]
+    interval: Duration,
+    #[serde(default = "heartbeat_level_default")]
+    level: Level,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    fields: BTreeMap<String, String>,
+    #[serde(default)]
+    appenders: Vec<String>,
+}
+
+fn heartbeat_level_default() -> Level {
+    Level::Info
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Escalation {
+    #[serde(deserialize_with = "de_duration")]
+    duration: Option<Duration>,
+    #[serde(default)]
+    trigger: Option<Level>,
+    #[serde(default)]
+    threshold: Option<usize>,
+    #[serde(default, deserialize_with = "de_duration")]
+    window: Option<Duration>,
+    #[serde(default)]
+    level: Option<LevelFilter>,
+    #[serde(default)]
+    targets: Vec<String>,
+    #[serde(default, deserialize_with = "de_duration")]
+    cooldown: Option<Duration>,
+    #[serde(default)]
+    max_activations: Option<u64>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(super) struct History {
+    #[serde(deserialize_with = "de_duration")]
+    duration: Option<Duration>,
+    #[serde(default)]
+    level: Option<LevelFilter>,
+    #[serde(default)]
+    capacity: Option<usize>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(super) struct KvLimits {
+    max_fields: Option<usize>,
+    max_key_len: Option<usize>,
+    max_value_len: Option<usize>,
+}
+
 #[cfg(test)]
 #[allow(unused_imports)]
 mod test {
     use super::*;
 
     #[test]
-    #[cfg(all(feature = "yaml_format", feature = "threshold_filter"))]
+    #[cfg(all(
+        feature = "yaml_format",
+        feature = "threshold_filter",
+        feature = "fields_processor"
+    ))]
     fn full_deserialize() {
         let cfg = r#"
 refresh_rate: 60 seconds
@@ -471,9 +1350,15 @@ appenders:
     filters:
       - kind: threshold
         level: debug
+    processors:
+      - kind: fields
+        fields:
+          service: api
   baz:
     kind: file
     path: /tmp/baz.log
+    flush_interval: 1 minute
+    monotonic_time: 500ms
     encoder:
       pattern: "%m"
 
@@ -488,11 +1373,206 @@ loggers:
     appenders:
       - baz
     additive: false
+
+heartbeats:
+  - interval: 30 seconds
+    message: still alive
+    fields:
+      service: api
+    appenders:
+      - baz
+
+kv_limits:
+  max_fields: 32
+  max_value_len: 1024
+
+fields:
+  service: billing
+
+derived_fields:
+  short_target: "truncate(target, 20)"
+
+sanitize: strip
 "#;
         let config = ::serde_yaml::from_str::<RawConfig>(cfg).unwrap();
-        let errors = config.appenders_lossy(&Deserializers::new()).1;
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::new());
         println!("{:?}", errors);
         assert!(errors.is_empty());
+        let console = appenders.iter().find(|a| a.name() == "console").unwrap();
+        assert_eq!(console.processors().len(), 1);
+        let baz = appenders.iter().find(|a| a.name() == "baz").unwrap();
+        assert_eq!(baz.flush_interval(), Some(Duration::from_secs(60)));
+        assert_eq!(baz.monotonic_time(), Some(Duration::from_millis(500)));
+        assert_eq!(
+            config.kv_limits(),
+            config::KvLimits::builder()
+                .max_fields(32)
+                .max_value_len(1024)
+                .build()
+        );
+        assert_eq!(
+            config.fields(),
+            [("service".to_owned(), "billing".to_owned())]
+        );
+        assert_eq!(
+            config.derived_fields(),
+            [config::DerivedField::new(
+                "short_target",
+                "truncate(target, 20)".parse().unwrap()
+            )]
+        );
+        assert_eq!(config.sanitize(), config::Sanitize::Strip);
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn invalid_derived_field() {
+        let cfg = r#"
+derived_fields:
+  severity_num: "map(level, {error: 3"
+"#;
+        assert!(::serde_yaml::from_str::<RawConfig>(cfg).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn heartbeat_without_interval() {
+        let cfg = r#"
+heartbeats:
+  - message: still alive
+"#;
+        assert!(::serde_yaml::from_str::<RawConfig>(cfg).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "file_appender"))]
+    fn appender_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = format!(
+            r#"
+appender_templates:
+  base_file:
+    kind: file
+    path: {0}/base.log
+    encoder:
+      pattern: "{{m}}"
+  subsystem_file:
+    extends: base_file
+    append: false
+
+appenders:
+  db:
+    extends: subsystem_file
+    path: {0}/db.log
+  net:
+    extends: base_file
+    path: {0}/net.log
+    encoder:
+      kind: json
+  broken:
+    extends: missing
+"#,
+            dir.path().display()
+        );
+        let config = ::serde_yaml::from_str::<RawConfig>(&cfg).unwrap();
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::new());
+
+        assert_eq!(appenders.len(), 2);
+        assert_eq!(errors.0.len(), 1);
+        assert!(dir.path().join("db.log").exists());
+        assert!(dir.path().join("net.log").exists());
+        assert!(!dir.path().join("base.log").exists());
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "file_appender"))]
+    fn appender_fingerprint() {
+        let dir = tempfile::tempdir().unwrap();
+        let fingerprints = |name: &str| {
+            let cfg = format!(
+                r#"
+appenders:
+  file:
+    kind: file
+    path: {}/{}
+"#,
+                dir.path().display(),
+                name
+            );
+            let config = ::serde_yaml::from_str::<RawConfig>(&cfg).unwrap();
+            config.appenders_lossy(&Deserializers::new()).0[0].fingerprint()
+        };
+
+        assert!(fingerprints("a.log").is_some());
+        assert_eq!(fingerprints("a.log"), fingerprints("a.log"));
+        assert_ne!(fingerprints("a.log"), fingerprints("b.log"));
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "yaml_format",
+        feature = "console_appender",
+        feature = "file_appender",
+        feature = "json_encoder"
+    ))]
+    fn allowed_kinds() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = format!(
+            r#"
+appenders:
+  stdout:
+    kind: console
+  json:
+    kind: console
+    encoder:
+      kind: json
+  file:
+    kind: file
+    path: {}/file.log
+"#,
+            dir.path().display()
+        );
+        let config = ::serde_yaml::from_str::<RawConfig>(&cfg).unwrap();
+        let errors = |deserializers: &Deserializers| {
+            let mut errors = config
+                .appenders_lossy(deserializers)
+                .1
+                .into_inner()
+                .iter()
+                .map(|e| e.appender().to_owned())
+                .collect::<Vec<_>>();
+            errors.sort();
+            errors
+        };
+
+        let mut deserializers = Deserializers::new();
+        assert!(errors(&deserializers).is_empty());
+        deserializers.allow_only(["console", "pattern"]);
+        assert_eq!(errors(&deserializers), ["file", "json"]);
+        deserializers.allow_only(["file", "pattern"]);
+        assert_eq!(errors(&deserializers), ["file", "json", "stdout"]);
+
+        let mut deserializers = Deserializers::new();
+        deserializers.remove::<dyn Append>("file");
+        assert_eq!(errors(&deserializers), ["file"]);
+
+        let config =
+            ::serde_yaml::from_str::<RawConfig>(&format!("{}\nallowed_kinds: [file, json]\n", cfg))
+                .unwrap();
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::new());
+        assert_eq!(appenders.len(), 1);
+        assert_eq!(errors.into_inner().len(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn malformed_appender() {
+        let cfg = r#"
+appenders:
+  console:
+    filters: console
+"#;
+        assert!(::serde_yaml::from_str::<RawConfig>(cfg).is_err());
     }
 
     #[test]
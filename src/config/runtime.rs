@@ -1,10 +1,21 @@
 //! log4rs configuration
 
-use log::LevelFilter;
-use std::{collections::HashSet, iter::IntoIterator};
+use fnv::FnvHasher;
+use log::{Level, LevelFilter};
+use std::{
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::IntoIterator,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 use thiserror::Error;
 
-use crate::{append::Append, filter::Filter};
+use crate::{
+    append::Append, derived, error_handler::ErrorHandler, filter::Filter, processor::Processor,
+};
 
 /// A log4rs configuration.
 #[derive(Debug)]
@@ -12,6 +23,15 @@ pub struct Config {
     appenders: Vec<Appender>,
     root: Root,
     loggers: Vec<Logger>,
+    heartbeats: Vec<Heartbeat>,
+    escalations: Vec<Escalation>,
+    history: Option<History>,
+    kv_limits: KvLimits,
+    fields: Vec<(String, String)>,
+    derived_fields: Vec<DerivedField>,
+    sanitize: Sanitize,
+    shutdown_summary: bool,
+    error_handler: Option<Arc<dyn ErrorHandler>>,
 }
 
 impl Config {
@@ -20,6 +40,15 @@ impl Config {
         ConfigBuilder {
             appenders: vec![],
             loggers: vec![],
+            heartbeats: vec![],
+            escalations: vec![],
+            history: None,
+            kv_limits: KvLimits::default(),
+            fields: vec![],
+            derived_fields: vec![],
+            sanitize: Sanitize::default(),
+            shutdown_summary: false,
+            error_handler: None,
         }
     }
 
@@ -43,13 +72,134 @@ impl Config {
         &self.loggers
     }
 
-    pub(crate) fn unpack(self) -> (Vec<Appender>, Root, Vec<Logger>) {
+    /// Returns the `Heartbeat`s associated with the `Config`.
+    pub fn heartbeats(&self) -> &[Heartbeat] {
+        &self.heartbeats
+    }
+
+    /// Returns the `Escalation`s associated with the `Config`.
+    pub fn escalations(&self) -> &[Escalation] {
+        &self.escalations
+    }
+
+    /// Returns the `History` associated with the `Config`, if any.
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// Returns the limits on the key-value fields of records associated with
+    /// the `Config`.
+    pub fn kv_limits(&self) -> &KvLimits {
+        &self.kv_limits
+    }
+
+    /// Returns the key-value fields added to every record.
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    /// Returns the `DerivedField`s associated with the `Config`.
+    pub fn derived_fields(&self) -> &[DerivedField] {
+        &self.derived_fields
+    }
+
+    /// Returns how control characters in messages are handled by appenders
+    /// which don't specify it themselves.
+    pub fn sanitize(&self) -> Sanitize {
+        self.sanitize
+    }
+
+    /// Returns whether `Handle::shutdown` writes a summary record.
+    pub fn shutdown_summary(&self) -> bool {
+        self.shutdown_summary
+    }
+
+    /// Returns the handler of errors encountered while logging, if set.
+    pub fn error_handler(&self) -> Option<&Arc<dyn ErrorHandler>> {
+        self.error_handler.as_ref()
+    }
+
+    /// Returns a hash of the configuration, for example to check that every
+    /// instance of a service runs the intended logging configuration.
+    ///
+    /// The hash is the same in every process and on every host for equal
+    /// configurations with the same version of log4rs, regardless of the
+    /// order appenders and loggers were added in. Appenders and filters are
+    /// opaque, so appenders built in code are only identified by their name
+    /// and `AppenderBuilder::fingerprint`; those deserialized from a config
+    /// file are fingerprinted from their configuration. The error handler is
+    /// not included.
+    pub fn fingerprint(&self) -> u64 {
+        let mut appenders = self
+            .appenders
+            .iter()
+            .map(|appender| (&appender.name, appender.fingerprint, appender.sanitize))
+            .collect::<Vec<_>>();
+        appenders.sort_by(|a, b| a.0.cmp(b.0));
+        let mut loggers = self.loggers.iter().collect::<Vec<_>>();
+        loggers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        stable_hash(&(
+            appenders,
+            self.root.level,
+            &self.root.appenders,
+            loggers,
+            &self.heartbeats,
+            &self.escalations,
+            &self.history,
+            &self.kv_limits,
+            &self.fields,
+            &self.derived_fields,
+            self.sanitize,
+            self.shutdown_summary,
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn unpack(
+        self,
+    ) -> (
+        Vec<Appender>,
+        Root,
+        Vec<Logger>,
+        Vec<Heartbeat>,
+        Vec<Escalation>,
+        Option<History>,
+        KvLimits,
+        Vec<(String, String)>,
+        Vec<DerivedField>,
+        Sanitize,
+        bool,
+        Option<Arc<dyn ErrorHandler>>,
+    ) {
         let Config {
             appenders,
             root,
             loggers,
+            heartbeats,
+            escalations,
+            history,
+            kv_limits,
+            fields,
+            derived_fields,
+            sanitize,
+            shutdown_summary,
+            error_handler,
         } = self;
-        (appenders, root, loggers)
+        (
+            appenders,
+            root,
+            loggers,
+            heartbeats,
+            escalations,
+            history,
+            kv_limits,
+            fields,
+            derived_fields,
+            sanitize,
+            shutdown_summary,
+            error_handler,
+        )
     }
 }
 
@@ -58,6 +208,15 @@ impl Config {
 pub struct ConfigBuilder {
     appenders: Vec<Appender>,
     loggers: Vec<Logger>,
+    heartbeats: Vec<Heartbeat>,
+    escalations: Vec<Escalation>,
+    history: Option<History>,
+    kv_limits: KvLimits,
+    fields: Vec<(String, String)>,
+    derived_fields: Vec<DerivedField>,
+    sanitize: Sanitize,
+    shutdown_summary: bool,
+    error_handler: Option<Arc<dyn ErrorHandler>>,
 }
 
 impl ConfigBuilder {
@@ -91,6 +250,120 @@ impl ConfigBuilder {
         self
     }
 
+    /// Adds a heartbeat.
+    pub fn heartbeat(mut self, heartbeat: Heartbeat) -> ConfigBuilder {
+        self.heartbeats.push(heartbeat);
+        self
+    }
+
+    /// Adds heartbeats.
+    pub fn heartbeats<I>(mut self, heartbeats: I) -> ConfigBuilder
+    where
+        I: IntoIterator<Item = Heartbeat>,
+    {
+        self.heartbeats.extend(heartbeats);
+        self
+    }
+
+    /// Adds an escalation.
+    pub fn escalation(mut self, escalation: Escalation) -> ConfigBuilder {
+        self.escalations.push(escalation);
+        self
+    }
+
+    /// Adds escalations.
+    pub fn escalations<I>(mut self, escalations: I) -> ConfigBuilder
+    where
+        I: IntoIterator<Item = Escalation>,
+    {
+        self.escalations.extend(escalations);
+        self
+    }
+
+    /// Sets the history of recent records.
+    ///
+    /// Defaults to no history.
+    pub fn history(mut self, history: History) -> ConfigBuilder {
+        self.history = Some(history);
+        self
+    }
+
+    /// Sets the limits on the key-value fields of records.
+    ///
+    /// Defaults to no limits.
+    pub fn kv_limits(mut self, kv_limits: KvLimits) -> ConfigBuilder {
+        self.kv_limits = kv_limits;
+        self
+    }
+
+    /// Adds a key-value field to every record, such as the name or version
+    /// of the service.
+    ///
+    /// Records which already have a field with the same key keep their value.
+    pub fn field<K, V>(mut self, key: K, value: V) -> ConfigBuilder
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds key-value fields to every record.
+    pub fn fields<I, K, V>(mut self, fields: I) -> ConfigBuilder
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.fields
+            .extend(fields.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Adds a derived field.
+    pub fn derived_field(mut self, derived_field: DerivedField) -> ConfigBuilder {
+        self.derived_fields.push(derived_field);
+        self
+    }
+
+    /// Adds derived fields.
+    pub fn derived_fields<I>(mut self, derived_fields: I) -> ConfigBuilder
+    where
+        I: IntoIterator<Item = DerivedField>,
+    {
+        self.derived_fields.extend(derived_fields);
+        self
+    }
+
+    /// Sets how control characters in messages are handled by appenders
+    /// which don't specify it themselves.
+    ///
+    /// Defaults to `Sanitize::Off`.
+    pub fn sanitize(mut self, sanitize: Sanitize) -> ConfigBuilder {
+        self.sanitize = sanitize;
+        self
+    }
+
+    /// Sets whether `Handle::shutdown` writes a summary record, which
+    /// requires the records logged at each level to be counted.
+    ///
+    /// Defaults to `false`.
+    pub fn shutdown_summary(mut self, shutdown_summary: bool) -> ConfigBuilder {
+        self.shutdown_summary = shutdown_summary;
+        self
+    }
+
+    /// Sets the handler of errors encountered while logging, such as an
+    /// appender failing to write a record.
+    ///
+    /// Defaults to printing them to stderr, or when the configuration replaces
+    /// another, to the handler already in use.
+    pub fn error_handler(mut self, error_handler: Arc<dyn ErrorHandler>) -> ConfigBuilder {
+        self.error_handler = Some(error_handler);
+        self
+    }
+
     /// Consumes the `ConfigBuilder`, returning the `Config`.
     ///
     /// Unlike `build`, this method will always return a `Config` by stripping
@@ -98,7 +371,19 @@ impl ConfigBuilder {
     pub fn build_lossy(self, mut root: Root) -> (Config, ConfigErrors) {
         let mut errors: Vec<ConfigError> = vec![];
 
-        let ConfigBuilder { appenders, loggers } = self;
+        let ConfigBuilder {
+            appenders,
+            loggers,
+            heartbeats,
+            escalations,
+            history,
+            kv_limits,
+            fields,
+            derived_fields,
+            sanitize,
+            shutdown_summary,
+            error_handler,
+        } = self;
 
         let mut ok_appenders = vec![];
         let mut appender_names = HashSet::new();
@@ -146,10 +431,59 @@ impl ConfigBuilder {
             ok_loggers.push(logger);
         }
 
+        let mut ok_heartbeats = vec![];
+        for mut heartbeat in heartbeats {
+            if heartbeat.interval.is_zero() {
+                errors.push(ConfigError::ZeroHeartbeatInterval);
+                continue;
+            }
+
+            // No appenders means every appender, so a heartbeat whose
+            // appenders all don't exist is dropped instead.
+            let listed = !heartbeat.appenders.is_empty();
+            let mut ok_heartbeat_appenders = vec![];
+            for appender in heartbeat.appenders {
+                if appender_names.contains(&appender) {
+                    ok_heartbeat_appenders.push(appender);
+                } else {
+                    errors.push(ConfigError::NonexistentAppender(appender));
+                }
+            }
+            if listed && ok_heartbeat_appenders.is_empty() {
+                continue;
+            }
+            heartbeat.appenders = ok_heartbeat_appenders;
+
+            ok_heartbeats.push(heartbeat);
+        }
+
+        let mut ok_escalations = vec![];
+        for mut escalation in escalations {
+            let mut ok_escalation_targets = vec![];
+            for target in escalation.targets {
+                match check_logger_name(&target) {
+                    Ok(()) => ok_escalation_targets.push(target),
+                    Err(err) => errors.push(err),
+                }
+            }
+            escalation.targets = ok_escalation_targets;
+
+            ok_escalations.push(escalation);
+        }
+
         let config = Config {
             appenders: ok_appenders,
             root,
             loggers: ok_loggers,
+            heartbeats: ok_heartbeats,
+            escalations: ok_escalations,
+            history,
+            kv_limits,
+            fields,
+            derived_fields,
+            sanitize,
+            shutdown_summary,
+            error_handler,
         };
 
         (config, ConfigErrors(errors))
@@ -230,18 +564,73 @@ impl RootBuilder {
     }
 }
 
+/// Hashes a value identically on every host, unlike the standard hashers
+/// which are randomly seeded and hash integers in native byte order.
+pub(crate) fn stable_hash<T>(value: &T) -> u64
+where
+    T: Hash + ?Sized,
+{
+    struct StableHasher(FnvHasher);
+
+    impl Hasher for StableHasher {
+        fn finish(&self) -> u64 {
+            self.0.finish()
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.write(bytes);
+        }
+
+        fn write_u16(&mut self, i: u16) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u32(&mut self, i: u32) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u64(&mut self, i: u64) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u128(&mut self, i: u128) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_usize(&mut self, i: usize) {
+            self.write_u64(i as u64);
+        }
+    }
+
+    let mut hasher = StableHasher(FnvHasher::default());
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// Configuration for an appender.
 #[derive(Debug)]
 pub struct Appender {
     name: String,
     appender: Box<dyn Append>,
     filters: Vec<Box<dyn Filter>>,
+    processors: Vec<Box<dyn Processor>>,
+    sanitize: Option<Sanitize>,
+    flush_interval: Option<Duration>,
+    monotonic_time: Option<Duration>,
+    fingerprint: Option<u64>,
 }
 
 impl Appender {
     /// Creates a new `AppenderBuilder` with the specified name and `Append` trait object.
     pub fn builder() -> AppenderBuilder {
-        AppenderBuilder { filters: vec![] }
+        AppenderBuilder {
+            filters: vec![],
+            processors: vec![],
+            sanitize: None,
+            flush_interval: None,
+            monotonic_time: None,
+            fingerprint: None,
+        }
     }
 
     /// Returns the name of the appender.
@@ -259,13 +648,66 @@ impl Appender {
         &self.filters
     }
 
-    pub(crate) fn unpack(self) -> (String, Box<dyn Append>, Vec<Box<dyn Filter>>) {
+    /// Returns the processors attached to the appender.
+    pub fn processors(&self) -> &[Box<dyn Processor>] {
+        &self.processors
+    }
+
+    /// Returns how control characters in messages are handled by the
+    /// appender, if it overrides the `Config`'s setting.
+    pub fn sanitize(&self) -> Option<Sanitize> {
+        self.sanitize
+    }
+
+    /// Returns the interval at which the appender is flushed on a schedule,
+    /// if set.
+    pub fn flush_interval(&self) -> Option<Duration> {
+        self.flush_interval
+    }
+
+    /// Returns the largest backwards step of the clock the appender hides
+    /// from its records' timestamps, if set.
+    pub fn monotonic_time(&self) -> Option<Duration> {
+        self.monotonic_time
+    }
+
+    /// Returns the value identifying the appender's configuration in
+    /// `Config::fingerprint`, if set.
+    pub fn fingerprint(&self) -> Option<u64> {
+        self.fingerprint
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn unpack(
+        self,
+    ) -> (
+        String,
+        Box<dyn Append>,
+        Vec<Box<dyn Filter>>,
+        Vec<Box<dyn Processor>>,
+        Option<Sanitize>,
+        Option<Duration>,
+        Option<Duration>,
+    ) {
         let Appender {
             name,
             appender,
             filters,
+            processors,
+            sanitize,
+            flush_interval,
+            monotonic_time,
+            fingerprint: _,
         } = self;
-        (name, appender, filters)
+        (
+            name,
+            appender,
+            filters,
+            processors,
+            sanitize,
+            flush_interval,
+            monotonic_time,
+        )
     }
 }
 
@@ -273,6 +715,11 @@ impl Appender {
 #[derive(Debug)]
 pub struct AppenderBuilder {
     filters: Vec<Box<dyn Filter>>,
+    processors: Vec<Box<dyn Processor>>,
+    sanitize: Option<Sanitize>,
+    flush_interval: Option<Duration>,
+    monotonic_time: Option<Duration>,
+    fingerprint: Option<u64>,
 }
 
 impl AppenderBuilder {
@@ -291,6 +738,70 @@ impl AppenderBuilder {
         self
     }
 
+    /// Adds a processor.
+    ///
+    /// Processors modify the records which pass the appender's filters, in
+    /// the order they were added, before the appender writes them.
+    pub fn processor(mut self, processor: Box<dyn Processor>) -> AppenderBuilder {
+        self.processors.push(processor);
+        self
+    }
+
+    /// Adds processors.
+    pub fn processors<I>(mut self, processors: I) -> AppenderBuilder
+    where
+        I: IntoIterator<Item = Box<dyn Processor>>,
+    {
+        self.processors.extend(processors);
+        self
+    }
+
+    /// Sets how control characters in messages are handled by the appender.
+    ///
+    /// Defaults to the `Config`'s setting.
+    pub fn sanitize(mut self, sanitize: Sanitize) -> AppenderBuilder {
+        self.sanitize = Some(sanitize);
+        self
+    }
+
+    /// Sets an interval at which the appender is flushed by a background
+    /// thread.
+    ///
+    /// Flushes are aligned to the wall clock: they happen at multiples of the
+    /// interval since the Unix epoch, so with an interval of a minute the
+    /// appender is flushed on the minute. By default the appender is only
+    /// flushed on request.
+    pub fn flush_interval(mut self, flush_interval: Duration) -> AppenderBuilder {
+        self.flush_interval = Some(flush_interval);
+        self
+    }
+
+    /// Keeps the timestamps of the records the appender writes from going
+    /// backwards when the clock steps back by up to `max_step`, as it may
+    /// after an NTP adjustment.
+    ///
+    /// Until the clock catches up, records are stamped with the time of the
+    /// appender's latest record and carry how far the clock stepped back, in
+    /// microseconds, as their `time_clamped_us` field. Larger steps are taken
+    /// to be deliberate corrections and show up in the timestamps. Only the
+    /// timestamps written by log4rs's encoders, which use
+    /// `encode::record_time`, are affected. By default timestamps follow the
+    /// clock.
+    pub fn monotonic_time(mut self, max_step: Duration) -> AppenderBuilder {
+        self.monotonic_time = Some(max_step);
+        self
+    }
+
+    /// Sets a value identifying the appender's configuration, such as a hash
+    /// of its settings, which `Config::fingerprint` includes.
+    ///
+    /// Appenders deserialized from a config file are given a hash of their
+    /// configuration.
+    pub fn fingerprint(mut self, fingerprint: u64) -> AppenderBuilder {
+        self.fingerprint = Some(fingerprint);
+        self
+    }
+
     /// Consumes the `AppenderBuilder`, returning the `Appender`.
     pub fn build<T>(self, name: T, appender: Box<dyn Append>) -> Appender
     where
@@ -300,6 +811,11 @@ impl AppenderBuilder {
             name: name.into(),
             appender,
             filters: self.filters,
+            processors: self.processors,
+            sanitize: self.sanitize,
+            flush_interval: self.flush_interval,
+            monotonic_time: self.monotonic_time,
+            fingerprint: self.fingerprint,
         }
     }
 }
@@ -392,6 +908,572 @@ impl LoggerBuilder {
     }
 }
 
+/// Configuration for a heartbeat.
+///
+/// A heartbeat is a synthetic record written at a fixed interval, allowing
+/// downstream consumers to tell a quiet application from a dead log stream.
+/// Heartbeat records are passed through the filters of their appenders but
+/// bypass loggers entirely.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Heartbeat {
+    interval: Duration,
+    level: Level,
+    target: String,
+    message: String,
+    fields: Vec<(String, String)>,
+    appenders: Vec<String>,
+}
+
+impl Heartbeat {
+    /// Creates a new `HeartbeatBuilder`.
+    ///
+    /// By default heartbeats are written at the `Info` level to the
+    /// `log4rs::heartbeat` target with the message `heartbeat`.
+    pub fn builder() -> HeartbeatBuilder {
+        HeartbeatBuilder {
+            level: Level::Info,
+            target: "log4rs::heartbeat".to_owned(),
+            message: "heartbeat".to_owned(),
+            fields: vec![],
+            appenders: vec![],
+        }
+    }
+
+    /// Returns the interval between heartbeat records.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns the level of heartbeat records.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns the target of heartbeat records.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the message of heartbeat records.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the key-value fields attached to heartbeat records.
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    /// Returns the list of names of appenders heartbeat records are written
+    /// to. If empty, they are written to every appender.
+    pub fn appenders(&self) -> &[String] {
+        &self.appenders
+    }
+}
+
+/// A builder for `Heartbeat`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct HeartbeatBuilder {
+    level: Level,
+    target: String,
+    message: String,
+    fields: Vec<(String, String)>,
+    appenders: Vec<String>,
+}
+
+impl HeartbeatBuilder {
+    /// Sets the level of heartbeat records.
+    pub fn level(mut self, level: Level) -> HeartbeatBuilder {
+        self.level = level;
+        self
+    }
+
+    /// Sets the target of heartbeat records.
+    pub fn target<T>(mut self, target: T) -> HeartbeatBuilder
+    where
+        T: Into<String>,
+    {
+        self.target = target.into();
+        self
+    }
+
+    /// Sets the message of heartbeat records.
+    pub fn message<T>(mut self, message: T) -> HeartbeatBuilder
+    where
+        T: Into<String>,
+    {
+        self.message = message.into();
+        self
+    }
+
+    /// Adds a key-value field to heartbeat records.
+    pub fn field<K, V>(mut self, key: K, value: V) -> HeartbeatBuilder
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds an appender.
+    pub fn appender<T>(mut self, appender: T) -> HeartbeatBuilder
+    where
+        T: Into<String>,
+    {
+        self.appenders.push(appender.into());
+        self
+    }
+
+    /// Adds appenders.
+    pub fn appenders<I>(mut self, appenders: I) -> HeartbeatBuilder
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.appenders.extend(appenders.into_iter().map(Into::into));
+        self
+    }
+
+    /// Consumes the `HeartbeatBuilder`, returning a `Heartbeat` written every
+    /// `interval`.
+    pub fn build(self, interval: Duration) -> Heartbeat {
+        Heartbeat {
+            interval,
+            level: self.level,
+            target: self.target,
+            message: self.message,
+            fields: self.fields,
+            appenders: self.appenders,
+        }
+    }
+}
+
+/// Configuration for an escalation.
+///
+/// An escalation counts the records logged at or above its trigger level.
+/// When `threshold` of them are logged within its window, the levels of its
+/// target loggers are raised for its duration and then restored. After that,
+/// it does not trigger again until its cooldown has passed, and not at all
+/// once it has triggered `max_activations` times.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Escalation {
+    duration: Duration,
+    trigger: Level,
+    threshold: usize,
+    window: Duration,
+    level: LevelFilter,
+    targets: Vec<String>,
+    cooldown: Duration,
+    max_activations: Option<u64>,
+}
+
+impl Escalation {
+    /// Creates a new `EscalationBuilder`.
+    ///
+    /// By default escalations raise the root logger to the `Debug` level when
+    /// 10 errors are logged within a minute, and may trigger again as soon as
+    /// they end.
+    pub fn builder() -> EscalationBuilder {
+        EscalationBuilder {
+            trigger: Level::Error,
+            threshold: 10,
+            window: Duration::from_secs(60),
+            level: LevelFilter::Debug,
+            targets: vec![],
+            cooldown: Duration::from_secs(0),
+            max_activations: None,
+        }
+    }
+
+    /// Returns how long the levels are raised for.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the level at or above which records are counted.
+    pub fn trigger(&self) -> Level {
+        self.trigger
+    }
+
+    /// Returns the number of records which trigger the escalation.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns the window the records are counted within.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns the level the target loggers are raised to.
+    pub fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    /// Returns the names of the loggers raised. If empty, the root logger is
+    /// raised.
+    pub fn targets(&self) -> &[String] {
+        &self.targets
+    }
+
+    /// Returns how long after ending the escalation cannot trigger again.
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Returns the number of times the escalation may trigger, if limited.
+    pub fn max_activations(&self) -> Option<u64> {
+        self.max_activations
+    }
+}
+
+/// A builder for `Escalation`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct EscalationBuilder {
+    trigger: Level,
+    threshold: usize,
+    window: Duration,
+    level: LevelFilter,
+    targets: Vec<String>,
+    cooldown: Duration,
+    max_activations: Option<u64>,
+}
+
+impl EscalationBuilder {
+    /// Sets the level at or above which records are counted.
+    pub fn trigger(mut self, trigger: Level) -> EscalationBuilder {
+        self.trigger = trigger;
+        self
+    }
+
+    /// Sets the number of records which trigger the escalation.
+    pub fn threshold(mut self, threshold: usize) -> EscalationBuilder {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the window the records are counted within.
+    pub fn window(mut self, window: Duration) -> EscalationBuilder {
+        self.window = window;
+        self
+    }
+
+    /// Sets the level the target loggers are raised to.
+    pub fn level(mut self, level: LevelFilter) -> EscalationBuilder {
+        self.level = level;
+        self
+    }
+
+    /// Adds a target logger.
+    pub fn target<T>(mut self, target: T) -> EscalationBuilder
+    where
+        T: Into<String>,
+    {
+        self.targets.push(target.into());
+        self
+    }
+
+    /// Adds target loggers.
+    pub fn targets<I>(mut self, targets: I) -> EscalationBuilder
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.targets.extend(targets.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets how long after ending the escalation cannot trigger again.
+    pub fn cooldown(mut self, cooldown: Duration) -> EscalationBuilder {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Sets the number of times the escalation may trigger.
+    pub fn max_activations(mut self, max_activations: u64) -> EscalationBuilder {
+        self.max_activations = Some(max_activations);
+        self
+    }
+
+    /// Consumes the `EscalationBuilder`, returning an `Escalation` which
+    /// raises levels for `duration`.
+    pub fn build(self, duration: Duration) -> Escalation {
+        Escalation {
+            duration,
+            trigger: self.trigger,
+            threshold: self.threshold,
+            window: self.window,
+            level: self.level,
+            targets: self.targets,
+            cooldown: self.cooldown,
+            max_activations: self.max_activations,
+        }
+    }
+}
+
+/// Configuration for the history of recent records.
+///
+/// The history keeps the records logged at or above its level in memory for
+/// its duration, including those filtered out by the levels of their loggers,
+/// so that `Handle::dump_recent` can write them out when something goes
+/// wrong. At most `capacity` records are kept.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct History {
+    duration: Duration,
+    level: LevelFilter,
+    capacity: usize,
+}
+
+impl History {
+    /// Creates a new `HistoryBuilder`.
+    ///
+    /// By default the history keeps up to 10,000 records at the `Debug` level
+    /// and above.
+    pub fn builder() -> HistoryBuilder {
+        HistoryBuilder {
+            level: LevelFilter::Debug,
+            capacity: 10_000,
+        }
+    }
+
+    /// Returns how long records are kept for.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the level at or above which records are kept.
+    pub fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    /// Returns the maximum number of records kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// A builder for `History`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct HistoryBuilder {
+    level: LevelFilter,
+    capacity: usize,
+}
+
+impl HistoryBuilder {
+    /// Sets the level at or above which records are kept.
+    pub fn level(mut self, level: LevelFilter) -> HistoryBuilder {
+        self.level = level;
+        self
+    }
+
+    /// Sets the maximum number of records kept.
+    pub fn capacity(mut self, capacity: usize) -> HistoryBuilder {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Consumes the `HistoryBuilder`, returning a `History` which keeps
+    /// records for `duration`.
+    pub fn build(self, duration: Duration) -> History {
+        History {
+            duration,
+            level: self.level,
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// Limits on the key-value fields attached to records.
+///
+/// Records exceeding the limits have fields beyond the maximum count dropped
+/// and overlong keys and values truncated before they reach any appender. A
+/// `_truncated_fields` field holding the number of fields dropped or
+/// truncated is added to such records, and their remaining values are passed
+/// on as strings.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct KvLimits {
+    max_fields: Option<usize>,
+    max_key_len: Option<usize>,
+    max_value_len: Option<usize>,
+}
+
+impl KvLimits {
+    /// Creates a new `KvLimitsBuilder`.
+    ///
+    /// By default nothing is limited.
+    pub fn builder() -> KvLimitsBuilder {
+        KvLimitsBuilder::default()
+    }
+
+    /// Returns the maximum number of fields of a record.
+    pub fn max_fields(&self) -> Option<usize> {
+        self.max_fields
+    }
+
+    /// Returns the maximum length of a key, in bytes.
+    pub fn max_key_len(&self) -> Option<usize> {
+        self.max_key_len
+    }
+
+    /// Returns the maximum length of a value when formatted, in bytes.
+    pub fn max_value_len(&self) -> Option<usize> {
+        self.max_value_len
+    }
+}
+
+/// A builder for `KvLimits`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct KvLimitsBuilder {
+    limits: KvLimits,
+}
+
+impl KvLimitsBuilder {
+    /// Sets the maximum number of fields of a record.
+    pub fn max_fields(mut self, max_fields: usize) -> KvLimitsBuilder {
+        self.limits.max_fields = Some(max_fields);
+        self
+    }
+
+    /// Sets the maximum length of a key, in bytes.
+    pub fn max_key_len(mut self, max_key_len: usize) -> KvLimitsBuilder {
+        self.limits.max_key_len = Some(max_key_len);
+        self
+    }
+
+    /// Sets the maximum length of a value when formatted, in bytes.
+    pub fn max_value_len(mut self, max_value_len: usize) -> KvLimitsBuilder {
+        self.limits.max_value_len = Some(max_value_len);
+        self
+    }
+
+    /// Consumes the `KvLimitsBuilder`, returning the `KvLimits`.
+    pub fn build(self) -> KvLimits {
+        self.limits
+    }
+}
+
+/// A key-value field added to every record, computed from the record by an
+/// `Expression`.
+///
+/// Fields are added before the `KvLimits` are applied. A field is left out of
+/// records its expression can't be evaluated for, such as those without a
+/// key-value field it refers to.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DerivedField {
+    name: String,
+    expression: Expression,
+}
+
+impl DerivedField {
+    /// Creates a new `DerivedField`.
+    pub fn new<T>(name: T, expression: Expression) -> DerivedField
+    where
+        T: Into<String>,
+    {
+        DerivedField {
+            name: name.into(),
+            expression,
+        }
+    }
+
+    /// Returns the name of the field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the expression computing the field.
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+}
+
+/// An expression computing a `DerivedField`, parsed from a string.
+///
+/// An expression is a quoted string, an integer, a field of the record, or a
+/// call to a function. The fields are `level`, `target`, `module_path`,
+/// `file`, `line`, `message`, and `kv.<key>` for a key-value field. The
+/// functions are:
+///
+/// * `map(expr, {key: value, ...})` looks up the value of `expr` among the
+///   keys, ignoring ASCII case, and evaluates to the matching value. An
+///   optional third argument is the value when no key matches.
+/// * `truncate(expr, len)` keeps the first `len` characters of `expr`.
+/// * `lower(expr)` and `upper(expr)` change the case of `expr`.
+/// * `concat(expr, ...)` joins its arguments together.
+///
+/// For example, `map(level, {error: 3, warn: 4, info: 6}, 7)` or
+/// `truncate(target, 20)`.
+#[derive(Clone, Debug)]
+pub struct Expression {
+    source: String,
+    pub(crate) expr: derived::Expr,
+}
+
+impl FromStr for Expression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Expression> {
+        let expr = derived::Expr::parse(s)
+            .map_err(|e| e.context(format!("invalid expression `{}`", s)))?;
+        Ok(Expression {
+            source: s.to_owned(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Expression) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Expression {}
+
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Expression {
+    fn deserialize<D>(d: D) -> Result<Expression, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(d)?;
+        s.parse()
+            .map_err(|e: anyhow::Error| <D::Error as serde::de::Error>::custom(format!("{:#}", e)))
+    }
+}
+
+/// How control characters in messages are handled before they are written.
+///
+/// Sanitizing messages keeps user controlled strings from injecting escape
+/// sequences into terminals or confusing downstream parsers. Newlines and tabs
+/// are always left as they are.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum Sanitize {
+    /// Messages are written as they are.
+    #[default]
+    Off,
+    /// Control characters are removed, along with the rest of any ANSI escape
+    /// sequences they start.
+    Strip,
+    /// Control characters are replaced by escapes such as `\r` and `\u{1b}`.
+    Escape,
+}
+
 fn check_logger_name(name: &str) -> Result<(), ConfigError> {
     if name.is_empty() {
         return Err(ConfigError::InvalidLoggerName(name.to_owned()));
@@ -439,6 +1521,11 @@ impl ConfigErrors {
             crate::handle_error(&e.into());
         }
     }
+
+    #[cfg(feature = "config_parsing")]
+    pub(crate) fn into_inner(self) -> Vec<ConfigError> {
+        self.0
+    }
 }
 
 /// An error validating a log4rs `Config`.
@@ -460,6 +1547,10 @@ pub enum ConfigError {
     #[error("Invalid logger name `{0}`")]
     InvalidLoggerName(String),
 
+    /// A heartbeat was to be written at a zero interval.
+    #[error("Heartbeat interval must not be zero")]
+    ZeroHeartbeatInterval,
+
     #[doc(hidden)]
     #[error("Reserved for future use")]
     __Extensible,
@@ -467,6 +1558,86 @@ pub enum ConfigError {
 
 #[cfg(test)]
 mod test {
+    use log::Record;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct NopAppender;
+
+    impl Append for NopAppender {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn fingerprint() {
+        let config = |names: [&str; 2], level| {
+            Config::builder()
+                .appenders(
+                    names
+                        .iter()
+                        .map(|name| Appender::builder().build(*name, Box::new(NopAppender))),
+                )
+                .loggers(
+                    names
+                        .iter()
+                        .map(|name| Logger::builder().build(*name, LevelFilter::Info)),
+                )
+                .build(Root::builder().appender("a").build(level))
+                .unwrap()
+        };
+
+        let fingerprint = config(["a", "b"], LevelFilter::Warn).fingerprint();
+        assert_eq!(
+            fingerprint,
+            config(["b", "a"], LevelFilter::Warn).fingerprint()
+        );
+        assert_ne!(
+            fingerprint,
+            config(["a", "b"], LevelFilter::Info).fingerprint()
+        );
+        assert_ne!(
+            fingerprint,
+            config(["a", "c"], LevelFilter::Warn).fingerprint()
+        );
+
+        // The hash must not change between hosts or releases by accident.
+        assert_eq!(stable_hash(&(1usize, "a")), 0x519d_8dc8_9e05_3dc0);
+    }
+
+    #[test]
+    fn heartbeats() {
+        let (config, errors) = Config::builder()
+            .appender(Appender::builder().build("a", Box::new(NopAppender)))
+            .heartbeat(Heartbeat::builder().build(Duration::from_secs(60)))
+            .heartbeat(
+                Heartbeat::builder()
+                    .appenders(["a", "b"])
+                    .build(Duration::from_secs(60)),
+            )
+            .heartbeat(
+                Heartbeat::builder()
+                    .appender("b")
+                    .build(Duration::from_secs(60)),
+            )
+            .heartbeat(Heartbeat::builder().build(Duration::ZERO))
+            .build_lossy(Root::builder().build(LevelFilter::Info));
+
+        let appenders = config
+            .heartbeats()
+            .iter()
+            .map(|heartbeat| heartbeat.appenders().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(appenders, [vec![], vec!["a".to_owned()]]);
+        let errors = errors.errors();
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(errors[2], ConfigError::ZeroHeartbeatInterval));
+    }
+
     #[test]
     fn check_logger_name() {
         let tests = [
@@ -0,0 +1,273 @@
+//! Export of the config file format as a JSON Schema.
+//!
+//! Requires the `config_schema` feature.
+
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde_json::{json, Map, Value};
+use std::{error, fmt};
+
+use crate::config::{
+    raw::{Escalation, Heartbeat, History, KvLimits, Logger, Root},
+    Deserializers, RawConfig,
+};
+
+/// Fields of component configs holding other components, and the name of the
+/// trait of the components they hold.
+const NESTED: &[(&str, &str)] = &[
+    ("encoder", "encoder"),
+    ("policy", "policy"),
+    ("trigger", "trigger"),
+    ("roller", "roller"),
+    ("compressor", "compressor"),
+    ("encrypt", "encryptor"),
+];
+
+/// The fields every appender accepts, alongside those of its kind.
+const APPENDER_FIELDS: &[&str] = &[
+    "filters",
+    "processors",
+    "sanitize",
+    "flush_interval",
+    "monotonic_time",
+    "extends",
+];
+
+impl Deserializers {
+    /// Returns a JSON Schema describing config files which can be
+    /// deserialized with these `Deserializers`, for editors to validate and
+    /// complete config files with.
+    ///
+    /// Each component may be of any kind registered for its trait, and may
+    /// only have the fields the config of that kind accepts. The types of the
+    /// fields are not described.
+    ///
+    /// Requires the `config_schema` feature. The `log4rs-schema` binary prints
+    /// the schema of the default `Deserializers`.
+    pub fn json_schema(&self) -> Value {
+        let components = self
+            .kinds()
+            .filter(|(_, kinds)| !kinds.is_empty())
+            .collect::<Vec<_>>();
+        let names = components.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+        let schema = Schema { names: &names };
+
+        let mut definitions = Map::new();
+        for (name, kinds) in components {
+            let mut variants = kinds
+                .into_iter()
+                .map(|(kind, fields)| {
+                    let mut properties = Map::new();
+                    properties.insert("kind".to_owned(), json!({ "const": kind }));
+                    if name == "appender" {
+                        properties.extend(schema.properties(APPENDER_FIELDS));
+                    }
+                    properties.extend(schema.properties(fields.unwrap_or_default()));
+                    schema.object(fields, properties)
+                })
+                .collect::<Vec<_>>();
+            if name == "appender" {
+                // appenders extending a template may leave out their kind
+                variants.push(json!({ "type": "object", "required": ["extends"] }));
+            }
+            definitions.insert(name.to_owned(), json!({ "anyOf": variants }));
+        }
+
+        let mut properties = Map::new();
+        properties.insert(
+            "include".to_owned(),
+            json!({
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } },
+                ]
+            }),
+        );
+        for &field in fields::<RawConfig>().unwrap_or_default() {
+            let property = match field {
+                "root" => schema.object(fields::<Root>(), Map::new()),
+                "appenders" => json!({
+                    "type": "object",
+                    "additionalProperties": schema.component("appender"),
+                }),
+                "appender_templates" => json!({
+                    "type": "object",
+                    "additionalProperties": { "type": "object" },
+                }),
+                "loggers" => json!({
+                    "type": "object",
+                    "additionalProperties": schema.object(fields::<Logger>(), Map::new()),
+                }),
+                "heartbeats" => json!({
+                    "type": "array",
+                    "items": schema.object(fields::<Heartbeat>(), Map::new()),
+                }),
+                "escalations" => json!({
+                    "type": "array",
+                    "items": schema.object(fields::<Escalation>(), Map::new()),
+                }),
+                "history" => schema.object(fields::<History>(), Map::new()),
+                "kv_limits" => schema.object(fields::<KvLimits>(), Map::new()),
+                _ => json!({}),
+            };
+            properties.insert(field.to_owned(), property);
+        }
+
+        let mut config = schema.object(fields::<RawConfig>(), properties);
+        config["$schema"] = json!("http://json-schema.org/draft-07/schema#");
+        config["title"] = json!("log4rs config");
+        config["definitions"] = Value::Object(definitions);
+        config
+    }
+}
+
+struct Schema<'a> {
+    // The names of the traits with registered kinds.
+    names: &'a [&'static str],
+}
+
+impl<'a> Schema<'a> {
+    /// Returns the schema of an object with the specified fields, or with any
+    /// fields if they are unknown. Fields without a schema in `properties`
+    /// may have any value.
+    fn object(&self, fields: Option<&[&str]>, mut properties: Map<String, Value>) -> Value {
+        for &field in fields.unwrap_or_default() {
+            properties.entry(field).or_insert_with(|| json!({}));
+        }
+        json!({
+            "type": "object",
+            "properties": properties,
+            "additionalProperties": fields.is_none(),
+        })
+    }
+
+    /// Returns the schemas of the fields of a component's config, which hold
+    /// other components if they are named after them.
+    fn properties(&self, fields: &[&str]) -> Map<String, Value> {
+        fields
+            .iter()
+            .filter(|&&field| field != "kind")
+            .map(|&field| {
+                let property = match NESTED.iter().find(|&&(nested, _)| nested == field) {
+                    Some(&(_, name)) => self.component(name),
+                    None if field == "filters" => {
+                        json!({ "type": "array", "items": self.component("filter") })
+                    }
+                    None if field == "processors" => {
+                        json!({ "type": "array", "items": self.component("processor") })
+                    }
+                    None => json!({}),
+                };
+                (field.to_owned(), property)
+            })
+            .collect()
+    }
+
+    /// Returns the schema of a component with the specified trait name.
+    fn component(&self, name: &str) -> Value {
+        if self.names.contains(&name) {
+            json!({ "$ref": format!("#/definitions/{}", name) })
+        } else {
+            json!({ "type": "object" })
+        }
+    }
+}
+
+/// Returns the fields of the struct `T` is deserialized from, or `None` if it
+/// is deserialized from something else, such as a map with arbitrary keys.
+pub(crate) fn fields<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    match T::deserialize(Probe) {
+        Ok(_) => None,
+        Err(Probed(fields)) => fields,
+    }
+}
+
+/// A deserializer which fails as soon as it is asked for a value, reporting
+/// the fields of a struct if it was asked for one.
+struct Probe;
+
+#[derive(Debug)]
+struct Probed(Option<&'static [&'static str]>);
+
+impl fmt::Display for Probed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("config probed")
+    }
+}
+
+impl error::Error for Probed {}
+
+impl de::Error for Probed {
+    fn custom<T: fmt::Display>(_: T) -> Probed {
+        Probed(None)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Probe {
+    type Error = Probed;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Probed> {
+        Err(Probed(None))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Probed> {
+        Err(Probed(Some(fields)))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn probe() {
+        assert_eq!(
+            fields::<Logger>(),
+            Some(&["level", "appenders", "additive"][..])
+        );
+        assert_eq!(fields::<std::collections::BTreeMap<String, String>>(), None);
+    }
+
+    #[test]
+    #[cfg(all(feature = "file_appender", feature = "pattern_encoder"))]
+    fn json_schema() {
+        let mut deserializers = Deserializers::default();
+        let schema = deserializers.json_schema();
+        assert_eq!(
+            schema["properties"]["appenders"]["additionalProperties"]["$ref"],
+            "#/definitions/appender"
+        );
+        assert_eq!(schema["additionalProperties"], false);
+
+        let file = schema["definitions"]["appender"]["anyOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|variant| variant["properties"]["kind"]["const"] == "file")
+            .unwrap();
+        assert_eq!(file["additionalProperties"], false);
+        assert!(file["properties"]["path"].is_object());
+        assert!(file["properties"]["filters"].is_object());
+        assert_eq!(
+            file["properties"]["encoder"]["$ref"],
+            "#/definitions/encoder"
+        );
+
+        deserializers.allow_only(["file"]);
+        let schema = deserializers.json_schema();
+        let appender = &schema["definitions"]["appender"]["anyOf"];
+        assert_eq!(appender[0]["properties"]["kind"]["const"], "file");
+        assert_eq!(appender.as_array().unwrap().len(), 2);
+        assert!(schema["definitions"].get("encoder").is_none());
+    }
+}
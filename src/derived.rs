@@ -0,0 +1,440 @@
+use log::{
+    kv::{self, Source},
+    Record,
+};
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+use crate::config::DerivedField;
+
+/// Evaluates `fields` for `record`, whose key-value fields are `key_values`,
+/// returning the name and value of those which could be evaluated.
+pub(crate) fn derive<'a>(
+    fields: &'a [DerivedField],
+    record: &Record,
+    key_values: &dyn Source,
+) -> Vec<(&'a str, Value)> {
+    fields
+        .iter()
+        .filter_map(|field| {
+            field
+                .expression()
+                .expr
+                .eval(record, key_values)
+                .map(|value| (field.name(), value))
+        })
+        .collect()
+}
+
+/// The value of an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Str(String),
+    Int(i64),
+}
+
+impl Value {
+    pub(crate) fn to_kv(&self) -> kv::Value<'_> {
+        match *self {
+            Value::Str(ref s) => kv::Value::from(&**s),
+            Value::Int(i) => kv::Value::from(i),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Value::Str(ref s) => f.write_str(s),
+            Value::Int(i) => write!(f, "{}", i),
+        }
+    }
+}
+
+/// A parsed derived field expression.
+#[derive(Clone, Debug)]
+pub(crate) enum Expr {
+    Level,
+    Target,
+    ModulePath,
+    File,
+    Line,
+    Message,
+    Kv(String),
+    Str(String),
+    Int(i64),
+    Map(Box<Expr>, Vec<(String, Expr)>, Option<Box<Expr>>),
+    Truncate(Box<Expr>, usize),
+    Lower(Box<Expr>),
+    Upper(Box<Expr>),
+    Concat(Vec<Expr>),
+}
+
+impl Expr {
+    /// Evaluates the expression, returning `None` if a value it depends on
+    /// is missing from the record or its key-value fields.
+    fn eval(&self, record: &Record, key_values: &dyn Source) -> Option<Value> {
+        let value = match *self {
+            Expr::Level => Value::Str(record.level().as_str().to_owned()),
+            Expr::Target => Value::Str(record.target().to_owned()),
+            Expr::ModulePath => Value::Str(record.module_path()?.to_owned()),
+            Expr::File => Value::Str(record.file()?.to_owned()),
+            Expr::Line => Value::Int(record.line()?.into()),
+            Expr::Message => Value::Str(record.args().to_string()),
+            Expr::Kv(ref key) => {
+                let value = key_values.get(kv::Key::from_str(key))?;
+                match value.to_i64() {
+                    Some(i) => Value::Int(i),
+                    None => Value::Str(value.to_string()),
+                }
+            }
+            Expr::Str(ref s) => Value::Str(s.clone()),
+            Expr::Int(i) => Value::Int(i),
+            Expr::Map(ref expr, ref map, ref default) => {
+                let key = expr.eval(record, key_values)?.to_string();
+                match map.iter().find(|(k, _)| k.eq_ignore_ascii_case(&key)) {
+                    Some((_, value)) => value.eval(record, key_values)?,
+                    None => default.as_ref()?.eval(record, key_values)?,
+                }
+            }
+            Expr::Truncate(ref expr, len) => Value::Str(
+                expr.eval(record, key_values)?
+                    .to_string()
+                    .chars()
+                    .take(len)
+                    .collect(),
+            ),
+            Expr::Lower(ref expr) => {
+                Value::Str(expr.eval(record, key_values)?.to_string().to_lowercase())
+            }
+            Expr::Upper(ref expr) => {
+                Value::Str(expr.eval(record, key_values)?.to_string().to_uppercase())
+            }
+            Expr::Concat(ref exprs) => {
+                let mut s = String::new();
+                for expr in exprs {
+                    s.push_str(&expr.eval(record, key_values)?.to_string());
+                }
+                Value::Str(s)
+            }
+        };
+        Some(value)
+    }
+
+    /// Parses an expression.
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Expr> {
+        let mut parser = Parser {
+            s,
+            chars: s.char_indices().peekable(),
+        };
+        let expr = parser.expr()?;
+        match parser.next()? {
+            None => Ok(expr),
+            Some(token) => Err(anyhow::anyhow!("unexpected {}", token)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Str(String),
+    Int(i64),
+    Punct(char),
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Token::Ident(ident) => write!(f, "`{}`", ident),
+            Token::Str(ref s) => write!(f, "{:?}", s),
+            Token::Int(i) => write!(f, "`{}`", i),
+            Token::Punct(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+enum Arg {
+    Expr(Expr),
+    Map(Vec<(String, Expr)>),
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> anyhow::Result<Option<Token<'a>>> {
+        while self.chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {}
+
+        let (start, c) = match self.chars.next() {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        let token = match c {
+            '(' | ')' | '{' | '}' | ',' | ':' => Token::Punct(c),
+            '"' | '\'' => {
+                let mut s = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some((_, '\\')) => match self.chars.next() {
+                            Some((_, c)) => s.push(c),
+                            None => break,
+                        },
+                        Some((_, end)) if end == c => return Ok(Some(Token::Str(s))),
+                        Some((_, c)) => s.push(c),
+                        None => break,
+                    }
+                }
+                anyhow::bail!("unterminated string");
+            }
+            '-' | '0'..='9' => {
+                let end = self.take_while(start, |c| c.is_ascii_digit());
+                let int = &self.s[start..end];
+                Token::Int(
+                    int.parse()
+                        .map_err(|_| anyhow::anyhow!("invalid integer `{}`", int))?,
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let end = self.take_while(start, |c| c.is_alphanumeric() || c == '_' || c == '.');
+                Token::Ident(&self.s[start..end])
+            }
+            c => anyhow::bail!("unexpected `{}`", c),
+        };
+        Ok(Some(token))
+    }
+
+    /// Consumes the characters matching `f` following the one at `start`,
+    /// returning the end of the token.
+    fn take_while<F>(&mut self, start: usize, f: F) -> usize
+    where
+        F: Fn(char) -> bool,
+    {
+        let mut end = start + self.s[start..].chars().next().map_or(0, char::len_utf8);
+        while let Some((i, c)) = self.chars.next_if(|&(_, c)| f(c)) {
+            end = i + c.len_utf8();
+        }
+        end
+    }
+
+    fn expect(&mut self, punct: char) -> anyhow::Result<()> {
+        match self.next()? {
+            Some(Token::Punct(c)) if c == punct => Ok(()),
+            Some(token) => Err(anyhow::anyhow!("expected `{}`, found {}", punct, token)),
+            None => Err(anyhow::anyhow!("expected `{}`", punct)),
+        }
+    }
+
+    fn expr(&mut self) -> anyhow::Result<Expr> {
+        match self.next()? {
+            Some(token) => self.expr_from(token),
+            None => Err(anyhow::anyhow!("expected an expression")),
+        }
+    }
+
+    fn expr_from(&mut self, token: Token<'a>) -> anyhow::Result<Expr> {
+        let ident = match token {
+            Token::Str(s) => return Ok(Expr::Str(s)),
+            Token::Int(i) => return Ok(Expr::Int(i)),
+            Token::Ident(ident) => ident,
+            token => anyhow::bail!("expected an expression, found {}", token),
+        };
+
+        if self.chars.peek().map(|&(_, c)| c) == Some('(') {
+            self.chars.next();
+            let args = self.args()?;
+            return call(ident, args);
+        }
+
+        let expr = match ident {
+            "level" => Expr::Level,
+            "target" => Expr::Target,
+            "module_path" => Expr::ModulePath,
+            "file" => Expr::File,
+            "line" => Expr::Line,
+            "message" => Expr::Message,
+            _ => match ident.strip_prefix("kv.") {
+                Some(key) if !key.is_empty() => Expr::Kv(key.to_owned()),
+                _ => anyhow::bail!("unknown field `{}`", ident),
+            },
+        };
+        Ok(expr)
+    }
+
+    fn args(&mut self) -> anyhow::Result<Vec<Arg>> {
+        let mut args = vec![];
+        loop {
+            let arg = match self.next()? {
+                Some(Token::Punct(')')) if args.is_empty() => return Ok(args),
+                Some(Token::Punct('{')) => Arg::Map(self.map()?),
+                Some(token) => Arg::Expr(self.expr_from(token)?),
+                None => anyhow::bail!("expected `)`"),
+            };
+            args.push(arg);
+            match self.next()? {
+                Some(Token::Punct(',')) => {}
+                Some(Token::Punct(')')) => return Ok(args),
+                Some(token) => anyhow::bail!("expected `,` or `)`, found {}", token),
+                None => anyhow::bail!("expected `)`"),
+            }
+        }
+    }
+
+    fn map(&mut self) -> anyhow::Result<Vec<(String, Expr)>> {
+        let mut map = vec![];
+        loop {
+            let key = match self.next()? {
+                Some(Token::Punct('}')) if map.is_empty() => return Ok(map),
+                Some(Token::Ident(ident)) => ident.to_owned(),
+                Some(Token::Str(s)) => s,
+                Some(Token::Int(i)) => i.to_string(),
+                Some(token) => anyhow::bail!("expected a key, found {}", token),
+                None => anyhow::bail!("expected `}}`"),
+            };
+            self.expect(':')?;
+            map.push((key, self.expr()?));
+            match self.next()? {
+                Some(Token::Punct(',')) => {}
+                Some(Token::Punct('}')) => return Ok(map),
+                Some(token) => anyhow::bail!("expected `,` or `}}`, found {}", token),
+                None => anyhow::bail!("expected `}}`"),
+            }
+        }
+    }
+}
+
+fn call(name: &str, args: Vec<Arg>) -> anyhow::Result<Expr> {
+    let mut args = args.into_iter();
+    let expr = match name {
+        "map" => {
+            let arg = expr_arg(name, &mut args)?;
+            let map = match args.next() {
+                Some(Arg::Map(map)) => map,
+                _ => anyhow::bail!("expected a map as the second argument to `map`"),
+            };
+            let default = match args.next() {
+                Some(Arg::Expr(default)) => Some(Box::new(default)),
+                Some(Arg::Map(_)) => anyhow::bail!("unexpected map in `map`"),
+                None => None,
+            };
+            Expr::Map(arg, map, default)
+        }
+        "truncate" => {
+            let arg = expr_arg(name, &mut args)?;
+            match args.next() {
+                Some(Arg::Expr(Expr::Int(len))) if len >= 0 => Expr::Truncate(arg, len as usize),
+                _ => anyhow::bail!("expected a length as the second argument to `truncate`"),
+            }
+        }
+        "lower" => Expr::Lower(expr_arg(name, &mut args)?),
+        "upper" => Expr::Upper(expr_arg(name, &mut args)?),
+        "concat" => {
+            let mut exprs = vec![*expr_arg(name, &mut args)?];
+            for _ in 0..args.len() {
+                exprs.push(*expr_arg(name, &mut args)?);
+            }
+            Expr::Concat(exprs)
+        }
+        _ => anyhow::bail!("unknown function `{}`", name),
+    };
+    if args.next().is_some() {
+        anyhow::bail!("too many arguments to `{}`", name);
+    }
+    Ok(expr)
+}
+
+fn expr_arg<I>(name: &str, args: &mut I) -> anyhow::Result<Box<Expr>>
+where
+    I: Iterator<Item = Arg>,
+{
+    match args.next() {
+        Some(Arg::Expr(expr)) => Ok(Box::new(expr)),
+        Some(Arg::Map(_)) => Err(anyhow::anyhow!("unexpected map in `{}`", name)),
+        None => Err(anyhow::anyhow!("missing argument to `{}`", name)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+
+    use super::*;
+
+    fn eval(expr: &str, record: &Record) -> Option<Value> {
+        Expr::parse(expr).unwrap().eval(record, record.key_values())
+    }
+
+    #[test]
+    fn expressions() {
+        let kvs = [("user", kv::Value::from("Ann")), ("status", 404.into())];
+        let record = Record::builder()
+            .level(Level::Warn)
+            .target("app::server::http")
+            .args(format_args!("not found"))
+            .line(Some(12))
+            .key_values(&kvs)
+            .build();
+
+        let severity = "map(level, {error: 3, warn: 4, info: 6}, 7)";
+        assert_eq!(eval(severity, &record), Some(Value::Int(4)));
+        assert_eq!(
+            eval("truncate(target, 10)", &record),
+            Some(Value::Str("app::serve".to_owned()))
+        );
+        assert_eq!(
+            eval("upper(kv.user)", &record),
+            Some(Value::Str("ANN".to_owned()))
+        );
+        assert_eq!(
+            eval("concat(message, ' (', kv.status, \")\")", &record),
+            Some(Value::Str("not found (404)".to_owned()))
+        );
+        assert_eq!(eval("line", &record), Some(Value::Int(12)));
+        assert_eq!(eval("map(level, {'error': 'E'})", &record), None);
+        assert_eq!(eval("lower(kv.missing)", &record), None);
+        assert_eq!(eval("file", &record), None);
+    }
+
+    #[test]
+    fn errors() {
+        for expr in [
+            "",
+            "levle",
+            "kv.",
+            "lower(level",
+            "lower(level, target)",
+            "truncate(target)",
+            "truncate(target, -1)",
+            "map(level, 3)",
+            "map(level, {error 3})",
+            "frobnicate(level)",
+            "'unterminated",
+            "level target",
+        ] {
+            assert!(Expr::parse(expr).is_err(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn derived_fields() {
+        let fields = [DerivedField::new(
+            "short_target",
+            "truncate(target, 3)".parse().unwrap(),
+        )];
+        let kvs = [("a", "1")];
+        let record = Record::builder()
+            .target("app::server")
+            .key_values(&kvs)
+            .build();
+
+        let derived = derive(&fields, &record, record.key_values());
+        assert_eq!(derived, [("short_target", Value::Str("app".to_owned()))]);
+
+        // key-value fields are looked up in the given source
+        let fields = [DerivedField::new("user", "kv.user".parse().unwrap())];
+        let kvs = [("user", "ann")];
+        let derived = derive(&fields, &record, &kvs);
+        assert_eq!(derived, [("user", Value::Str("ann".to_owned()))]);
+    }
+}
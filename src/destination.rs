@@ -0,0 +1,76 @@
+use log::{
+    kv::{self, Source, VisitSource},
+    Record,
+};
+
+/// The key-value key which names an appender a record is written to in
+/// addition to its logger's appenders.
+pub(crate) const KEY: &str = "log4rs.appender";
+
+/// Calls `f` with `record` and the appender it names with the destination
+/// key, if any. The key is stripped from the record passed to `f`.
+pub(crate) fn with_destination<F, R>(record: &Record, f: F) -> R
+where
+    F: FnOnce(&Record, Option<&str>) -> R,
+{
+    let destination = match record.key_values().get(kv::Key::from(KEY)) {
+        Some(destination) => destination.to_string(),
+        None => return f(record, None),
+    };
+
+    let key_values = Without(record.key_values());
+    f(
+        &Record::builder()
+            .metadata(record.metadata().clone())
+            .args(*record.args())
+            .module_path(record.module_path())
+            .file(record.file())
+            .line(record.line())
+            .key_values(&key_values)
+            .build(),
+        Some(&destination),
+    )
+}
+
+/// The key-value fields of a record, without the destination key.
+struct Without<'a>(&'a dyn Source);
+
+impl Source for Without<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        struct Skip<'a, 'kvs>(&'a mut dyn VisitSource<'kvs>);
+
+        impl<'kvs> VisitSource<'kvs> for Skip<'_, 'kvs> {
+            fn visit_pair(
+                &mut self,
+                key: kv::Key<'kvs>,
+                value: kv::Value<'kvs>,
+            ) -> Result<(), kv::Error> {
+                if key.as_str() == KEY {
+                    Ok(())
+                } else {
+                    self.0.visit_pair(key, value)
+                }
+            }
+        }
+
+        self.0.visit(&mut Skip(visitor))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strips_key() {
+        let kvs = [("user", "alice"), (KEY, "audit")];
+        let record = Record::builder().key_values(&kvs).build();
+
+        with_destination(&record, |record, destination| {
+            assert_eq!(destination, Some("audit"));
+            assert_eq!(record.key_values().count(), 1);
+            assert!(record.key_values().get(kv::Key::from(KEY)).is_none());
+            assert!(record.key_values().get(kv::Key::from("user")).is_some());
+        });
+    }
+}
@@ -0,0 +1,121 @@
+//! Framing of encoded records on a byte stream.
+//!
+//! Encoders produce the bytes of a single record; how records are delimited
+//! from each other on the wire is a property of the transport instead. An
+//! appender which sends records over a stream, such as TCP or a syslog
+//! connection, takes a `Framing` alongside its encoder so that any encoder
+//! can be used with the framing the receiver expects.
+//!
+//! Requires the `simple_writer` feature.
+
+use log::Record;
+use std::{convert::TryFrom, io};
+
+use crate::encode::{writer::simple::SimpleWriter, Encode};
+
+/// How records are delimited on a byte stream.
+///
+/// Trailing line breaks written by the encoder are removed before a record
+/// is framed, so encoders which end records with a newline, such as the JSON
+/// encoder, can be used with every framing.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+pub enum Framing {
+    /// Each record is followed by a newline, as in newline-delimited JSON.
+    ///
+    /// The encoder must not write line breaks within a record.
+    #[default]
+    Newline,
+    /// Each record is preceded by its length in bytes, as a 4 byte big-endian
+    /// integer.
+    LengthPrefix,
+    /// Each record is preceded by its length in bytes, in ASCII decimal, and
+    /// a space, as in the octet counting framing of RFC 6587.
+    OctetCounting,
+}
+
+impl Framing {
+    /// Encodes `record` with `encoder` and writes it to `w`, framed.
+    pub fn encode(
+        self,
+        encoder: &dyn Encode,
+        record: &Record,
+        w: &mut dyn io::Write,
+    ) -> anyhow::Result<()> {
+        let mut buf = vec![];
+        encoder.encode(&mut SimpleWriter(&mut buf), record)?;
+        self.frame(&buf, w)?;
+        Ok(())
+    }
+
+    /// Writes the bytes of an encoded record to `w`, framed.
+    pub fn frame(self, record: &[u8], w: &mut dyn io::Write) -> io::Result<()> {
+        let mut record = record;
+        while let Some((b'\n' | b'\r', rest)) = record.split_last() {
+            record = rest;
+        }
+
+        match self {
+            Framing::Newline => {
+                w.write_all(record)?;
+                w.write_all(b"\n")
+            }
+            Framing::LengthPrefix => {
+                let len = u32::try_from(record.len()).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "record too long to frame")
+                })?;
+                w.write_all(&len.to_be_bytes())?;
+                w.write_all(record)
+            }
+            Framing::OctetCounting => {
+                write!(w, "{} ", record.len())?;
+                w.write_all(record)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frame(framing: Framing, record: &[u8]) -> Vec<u8> {
+        let mut buf = vec![];
+        framing.frame(record, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn frames() {
+        assert_eq!(frame(Framing::Newline, b"{\"a\":1}\n"), b"{\"a\":1}\n");
+        assert_eq!(frame(Framing::Newline, b"abc"), b"abc\n");
+        assert_eq!(frame(Framing::LengthPrefix, b"abc\r\n"), b"\0\0\0\x03abc");
+        assert_eq!(
+            frame(Framing::OctetCounting, b"<13>hello\n"),
+            b"9 <13>hello"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "pattern_encoder")]
+    fn encode() {
+        use crate::encode::pattern::PatternEncoder;
+
+        let encoder = PatternEncoder::new("{l} {m}{n}");
+        let mut buf = vec![];
+        for message in ["one", "two"] {
+            Framing::OctetCounting
+                .encode(
+                    &encoder,
+                    &Record::builder()
+                        .level(log::Level::Info)
+                        .args(format_args!("{}", message))
+                        .build(),
+                    &mut buf,
+                )
+                .unwrap();
+        }
+        assert_eq!(buf, b"8 INFO one8 INFO two");
+    }
+}
@@ -19,23 +19,60 @@
 //!     "target": "foo::bar",
 //!     "thread": "main",
 //!     "thread_id": 123,
+//!     "tid": 4013,
+//!     "seq": 1337,
+//!     "pid": 4012,
+//!     "host": "web-1",
+//!     "process_start": "2016-03-20T08:00:01.123456789+00:00",
 //!     "mdc": {
 //!         "request_id": "123e4567-e89b-12d3-a456-426655440000"
+//!     },
+//!     "attributes": {
+//!         "service": "api"
+//!     },
+//!     "error": {
+//!         "message": "failed to load config",
+//!         "causes": ["No such file or directory (os error 2)"]
 //!     }
 //! }
 //! ```
+//!
+//! `tid` is the operating system's ID of the thread which logged the record,
+//! as shown by tools such as `top -H` on Linux, and `thread` is the name of
+//! the thread, or its `tid` if it is unnamed.
+//!
+//! `seq` is the sequence number of the record, which increases by one for each
+//! record numbered in the process, as for the pattern encoder's `{seq}`, and
+//! `host`, `pid` and `process_start`
+//! identify the instance of the service which logged it.
+//!
+//! The `attributes` object holds the record's key-value pairs, and is omitted
+//! if there are none.
+//!
+//! The `error` object holds the error attached to the record under the `err`
+//! key, if there is one, as described in [`error_chain`]: its `message`, the
+//! messages of the errors which caused it in `causes`, and the lines of its
+//! `backtrace` if one was captured.
+//!
+//! [`error_chain`]: crate::error_chain
 
 use chrono::{
     format::{DelayedFormat, Fixed, Item},
-    DateTime, Local,
+    DateTime, Local, Utc,
+};
+use log::{
+    kv::{self, VisitSource},
+    Level, Record,
 };
-use log::{Level, Record};
 use serde::ser::{self, Serialize, SerializeMap};
-use std::{fmt, option, thread};
+use std::{borrow::Cow, fmt, option, process, thread};
 
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
-use crate::encode::{Encode, Write, NEWLINE};
+use crate::{
+    encode::{self, Encode, Write, NEWLINE},
+    error_chain::ErrorChain,
+};
 
 /// The JSON encoder's configuration
 #[cfg(feature = "config_parsing")]
@@ -65,6 +102,8 @@ impl JsonEncoder {
         record: &Record,
     ) -> anyhow::Result<()> {
         let thread = thread::current();
+        let tid = encode::os_thread_id();
+        let error = ErrorChain::attached(record);
         let message = Message {
             time: time.format_with_items(Some(Item::Fixed(Fixed::RFC3339)).into_iter()),
             message: record.args(),
@@ -73,9 +112,19 @@ impl JsonEncoder {
             file: record.file(),
             line: record.line(),
             target: record.target(),
-            thread: thread.name(),
+            thread: match thread.name() {
+                Some(name) => Cow::Borrowed(name),
+                None => Cow::Owned(tid.to_string()),
+            },
             thread_id: thread_id::get(),
+            tid,
+            seq: encode::record_seq(),
+            pid: process::id(),
+            host: encode::hostname(),
+            process_start: DateTime::<Utc>::from(encode::process_start()).to_rfc3339(),
             mdc: Mdc,
+            attributes: Kv(record.key_values()),
+            error: error.as_ref().map(JsonError::new),
         };
         message.serialize(&mut serde_json::Serializer::new(&mut *w))?;
         w.write_all(NEWLINE.as_bytes())?;
@@ -85,7 +134,7 @@ impl JsonEncoder {
 
 impl Encode for JsonEncoder {
     fn encode(&self, w: &mut dyn Write, record: &Record) -> anyhow::Result<()> {
-        self.encode_inner(w, Local::now(), record)
+        self.encode_inner(w, DateTime::from(encode::record_time()), record)
     }
 }
 
@@ -103,9 +152,40 @@ struct Message<'a> {
     line: Option<u32>,
     level: Level,
     target: &'a str,
-    thread: Option<&'a str>,
+    thread: Cow<'a, str>,
     thread_id: usize,
+    tid: u64,
+    seq: u64,
+    pid: u32,
+    host: &'a str,
+    process_start: String,
     mdc: Mdc,
+    #[serde(skip_serializing_if = "Kv::is_empty")]
+    attributes: Kv<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonError<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonError<'a> {
+    message: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    causes: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backtrace: Option<Vec<&'a str>>,
+}
+
+impl<'a> JsonError<'a> {
+    fn new(chain: &'a ErrorChain) -> JsonError<'a> {
+        let (message, causes) = chain.messages().split_first().expect("an error");
+        JsonError {
+            message,
+            causes,
+            backtrace: chain
+                .backtrace()
+                .map(|backtrace| backtrace.lines().collect()),
+        }
+    }
 }
 
 fn ser_display<T, S>(v: &T, s: S) -> Result<S::Ok, S::Error>
@@ -137,6 +217,39 @@ impl ser::Serialize for Mdc {
     }
 }
 
+struct Kv<'a>(&'a dyn kv::Source);
+
+impl<'a> Kv<'a> {
+    fn is_empty(&self) -> bool {
+        self.0.count() == 0
+    }
+}
+
+impl<'a> ser::Serialize for Kv<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        struct Visitor<M: SerializeMap>(M);
+
+        impl<'kvs, M: SerializeMap> VisitSource<'kvs> for Visitor<M> {
+            fn visit_pair(
+                &mut self,
+                key: kv::Key<'kvs>,
+                value: kv::Value<'kvs>,
+            ) -> Result<(), kv::Error> {
+                self.0
+                    .serialize_entry(key.as_str(), &format_args!("{}", value))
+                    .map_err(|e| kv::Error::boxed(e.to_string()))
+            }
+        }
+
+        let mut visitor = Visitor(serializer.serialize_map(Some(self.0.count()))?);
+        self.0.visit(&mut visitor).map_err(ser::Error::custom)?;
+        visitor.0.end()
+    }
+}
+
 /// A deserializer for the `JsonEncoder`.
 ///
 /// # Configuration
@@ -190,25 +303,28 @@ mod test {
         let encoder = JsonEncoder::new();
 
         let mut buf = vec![];
-        encoder
-            .encode_inner(
-                &mut SimpleWriter(&mut buf),
-                time,
-                &Record::builder()
-                    .level(level)
-                    .target(target)
-                    .module_path(Some(module_path))
-                    .file(Some(file))
-                    .line(Some(line))
-                    .args(format_args!("{}", message))
-                    .build(),
-            )
-            .unwrap();
+        encode::with_record_seq(7, || {
+            encoder
+                .encode_inner(
+                    &mut SimpleWriter(&mut buf),
+                    time,
+                    &Record::builder()
+                        .level(level)
+                        .target(target)
+                        .module_path(Some(module_path))
+                        .file(Some(file))
+                        .line(Some(line))
+                        .args(format_args!("{}", message))
+                        .build(),
+                )
+                .unwrap()
+        });
 
         let expected = format!(
             "{{\"time\":\"{}\",\"message\":\"{}\",\"module_path\":\"{}\",\
              \"file\":\"{}\",\"line\":{},\"level\":\"{}\",\"target\":\"{}\",\
-             \"thread\":\"{}\",\"thread_id\":{},\"mdc\":{{\"foo\":\"bar\"}}}}",
+             \"thread\":\"{}\",\"thread_id\":{},\"tid\":{},\"seq\":7,\"pid\":{},\"host\":\"{}\",\
+             \"process_start\":\"{}\",\"mdc\":{{\"foo\":\"bar\"}}}}",
             time.to_rfc3339(),
             message,
             module_path,
@@ -218,7 +334,69 @@ mod test {
             target,
             thread,
             thread_id::get(),
+            encode::os_thread_id(),
+            process::id(),
+            encode::hostname(),
+            DateTime::<Utc>::from(encode::process_start()).to_rfc3339(),
         );
         assert_eq!(expected, String::from_utf8(buf).unwrap().trim());
     }
+
+    #[test]
+    fn attributes() {
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let kvs = [("service", "api"), ("attempt", "2")];
+
+        let mut buf = vec![];
+        JsonEncoder::new()
+            .encode_inner(
+                &mut SimpleWriter(&mut buf),
+                time,
+                &Record::builder()
+                    .args(format_args!("message"))
+                    .key_values(&kvs)
+                    .build(),
+            )
+            .unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(
+            value["attributes"],
+            serde_json::json!({"service": "api", "attempt": "2"})
+        );
+    }
+
+    #[test]
+    fn error() {
+        let time = DateTime::parse_from_rfc3339("2016-03-20T14:22:20.644420340-08:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let error = anyhow::anyhow!("no such file").context("failed to load config");
+        let error = ErrorChain::from(&error);
+        let kvs = [(
+            crate::error_chain::ERROR_KEY,
+            kv::Value::from_dyn_error(&error),
+        )];
+
+        let mut buf = vec![];
+        JsonEncoder::new()
+            .encode_inner(
+                &mut SimpleWriter(&mut buf),
+                time,
+                &Record::builder()
+                    .args(format_args!("message"))
+                    .key_values(&kvs)
+                    .build(),
+            )
+            .unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(value["error"]["message"], "failed to load config");
+        assert_eq!(
+            value["error"]["causes"],
+            serde_json::json!(["no such file"])
+        );
+    }
 }
@@ -0,0 +1,236 @@
+//! Localization of encoder output.
+//!
+//! A `Localize` provider lets encoders render level names, month and weekday
+//! names, and numbers for a particular locale. The `PatternEncoder` uses one
+//! when configured with `PatternEncoder::locale`.
+
+use log::Level;
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+#[cfg(feature = "config_parsing")]
+use anyhow::anyhow;
+#[cfg(feature = "config_parsing")]
+use std::{collections::BTreeMap, convert::TryInto};
+
+/// A trait implemented by providers of localized text.
+///
+/// Every method has a default implementation producing the unlocalized text,
+/// so providers only need to override what their locale changes.
+pub trait Localize: fmt::Debug + Send + Sync + 'static {
+    /// Returns the name of a level.
+    fn level(&self, level: Level) -> Cow<'_, str> {
+        Cow::Borrowed(level.as_str())
+    }
+
+    /// Returns the name of a month, numbered from 1 for January, or `None` to
+    /// use the English name.
+    fn month(&self, month: u32, abbreviated: bool) -> Option<Cow<'_, str>> {
+        let _ = (month, abbreviated);
+        None
+    }
+
+    /// Returns the name of a day of the week, numbered from 0 for Monday, or
+    /// `None` to use the English name.
+    fn weekday(&self, weekday: u32, abbreviated: bool) -> Option<Cow<'_, str>> {
+        let _ = (weekday, abbreviated);
+        None
+    }
+
+    /// Formats a number such as a line number or process ID.
+    fn number(&self, number: u64) -> String {
+        number.to_string()
+    }
+}
+
+/// A `Localize` provider backed by tables of names.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Locale {
+    levels: HashMap<Level, String>,
+    months: Option<[String; 12]>,
+    months_abbreviated: Option<[String; 12]>,
+    weekdays: Option<[String; 7]>,
+    weekdays_abbreviated: Option<[String; 7]>,
+    thousands_separator: Option<char>,
+}
+
+impl Locale {
+    /// Creates a new `Locale` builder.
+    pub fn builder() -> LocaleBuilder {
+        LocaleBuilder {
+            locale: Locale::default(),
+        }
+    }
+}
+
+impl Localize for Locale {
+    fn level(&self, level: Level) -> Cow<'_, str> {
+        match self.levels.get(&level) {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Borrowed(level.as_str()),
+        }
+    }
+
+    fn month(&self, month: u32, abbreviated: bool) -> Option<Cow<'_, str>> {
+        let months = if abbreviated {
+            self.months_abbreviated.as_ref()
+        } else {
+            self.months.as_ref()
+        };
+        let name = months?.get(month.checked_sub(1)? as usize)?;
+        Some(Cow::Borrowed(name))
+    }
+
+    fn weekday(&self, weekday: u32, abbreviated: bool) -> Option<Cow<'_, str>> {
+        let weekdays = if abbreviated {
+            self.weekdays_abbreviated.as_ref()
+        } else {
+            self.weekdays.as_ref()
+        };
+        Some(Cow::Borrowed(weekdays?.get(weekday as usize)?))
+    }
+
+    fn number(&self, number: u64) -> String {
+        let digits = number.to_string();
+        let separator = match self.thousands_separator {
+            Some(separator) => separator,
+            None => return digits,
+        };
+
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                out.push(separator);
+            }
+            out.push(digit);
+        }
+        out
+    }
+}
+
+/// A builder for `Locale`s.
+#[derive(Clone, Debug)]
+pub struct LocaleBuilder {
+    locale: Locale,
+}
+
+impl LocaleBuilder {
+    /// Sets the name of a level.
+    pub fn level(mut self, level: Level, name: String) -> LocaleBuilder {
+        self.locale.levels.insert(level, name);
+        self
+    }
+
+    /// Sets the names of the months, starting with January.
+    pub fn months(mut self, months: [String; 12]) -> LocaleBuilder {
+        self.locale.months = Some(months);
+        self
+    }
+
+    /// Sets the abbreviated names of the months, starting with January.
+    pub fn months_abbreviated(mut self, months: [String; 12]) -> LocaleBuilder {
+        self.locale.months_abbreviated = Some(months);
+        self
+    }
+
+    /// Sets the names of the days of the week, starting with Monday.
+    pub fn weekdays(mut self, weekdays: [String; 7]) -> LocaleBuilder {
+        self.locale.weekdays = Some(weekdays);
+        self
+    }
+
+    /// Sets the abbreviated names of the days of the week, starting with
+    /// Monday.
+    pub fn weekdays_abbreviated(mut self, weekdays: [String; 7]) -> LocaleBuilder {
+        self.locale.weekdays_abbreviated = Some(weekdays);
+        self
+    }
+
+    /// Sets the character separating groups of three digits in numbers.
+    ///
+    /// Defaults to no separator.
+    pub fn thousands_separator(mut self, separator: char) -> LocaleBuilder {
+        self.locale.thousands_separator = Some(separator);
+        self
+    }
+
+    /// Consumes the `LocaleBuilder`, producing a `Locale`.
+    pub fn build(self) -> Locale {
+        self.locale
+    }
+}
+
+/// The configuration of a `Locale`.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocaleConfig {
+    #[serde(default)]
+    levels: BTreeMap<Level, String>,
+    months: Option<Vec<String>>,
+    months_abbreviated: Option<Vec<String>>,
+    weekdays: Option<Vec<String>>,
+    weekdays_abbreviated: Option<Vec<String>>,
+    thousands_separator: Option<char>,
+}
+
+#[cfg(feature = "config_parsing")]
+impl LocaleConfig {
+    /// Builds the `Locale` described by the configuration.
+    pub fn build(self) -> anyhow::Result<Locale> {
+        fn names<const N: usize>(field: &str, names: Vec<String>) -> anyhow::Result<[String; N]> {
+            let len = names.len();
+            names
+                .try_into()
+                .map_err(|_| anyhow!("`{}` must list {} names, not {}", field, N, len))
+        }
+
+        let mut builder = Locale::builder();
+        for (level, name) in self.levels {
+            builder = builder.level(level, name);
+        }
+        if let Some(months) = self.months {
+            builder = builder.months(names("months", months)?);
+        }
+        if let Some(months) = self.months_abbreviated {
+            builder = builder.months_abbreviated(names("months_abbreviated", months)?);
+        }
+        if let Some(weekdays) = self.weekdays {
+            builder = builder.weekdays(names("weekdays", weekdays)?);
+        }
+        if let Some(weekdays) = self.weekdays_abbreviated {
+            builder = builder.weekdays_abbreviated(names("weekdays_abbreviated", weekdays)?);
+        }
+        if let Some(separator) = self.thousands_separator {
+            builder = builder.thousands_separator(separator);
+        }
+        Ok(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        let locale = Locale::builder().thousands_separator('.').build();
+        assert_eq!(locale.number(7), "7");
+        assert_eq!(locale.number(123), "123");
+        assert_eq!(locale.number(1234), "1.234");
+        assert_eq!(locale.number(1234567), "1.234.567");
+        assert_eq!(Locale::default().number(1234), "1234");
+    }
+
+    #[test]
+    fn names() {
+        let locale = Locale::builder()
+            .level(Level::Warn, "WARNUNG".to_owned())
+            .weekdays_abbreviated(["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"].map(str::to_owned))
+            .build();
+        assert_eq!(locale.level(Level::Warn), "WARNUNG");
+        assert_eq!(locale.level(Level::Info), "INFO");
+        assert_eq!(locale.weekday(2, true).as_deref(), Some("Mi"));
+        assert_eq!(locale.weekday(2, false), None);
+        assert_eq!(locale.month(1, false), None);
+    }
+}
@@ -2,7 +2,15 @@
 
 use derivative::Derivative;
 use log::Record;
-use std::{fmt, io};
+use std::{
+    cell::Cell,
+    env, fmt, fs, io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
 
 #[cfg(feature = "config_parsing")]
 use serde::de;
@@ -14,12 +22,169 @@ use std::collections::BTreeMap;
 #[cfg(feature = "config_parsing")]
 use crate::config::Deserializable;
 
+#[cfg(feature = "simple_writer")]
+pub mod framing;
 #[cfg(feature = "json_encoder")]
 pub mod json;
+pub mod locale;
 #[cfg(feature = "pattern_encoder")]
 pub mod pattern;
+#[cfg(feature = "wrap_encoder")]
+pub mod wrap;
 pub mod writer;
 
+thread_local! {
+    static RECORD_TIME: Cell<Option<SystemTime>> = const { Cell::new(None) };
+    static RECORD_SEQ: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+static HOSTNAME: Mutex<Option<&'static str>> = Mutex::new(None);
+static PROCESS_START: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+/// Returns the time to stamp the record being encoded with.
+///
+/// This is the current time, unless the record is written by an appender
+/// which keeps its timestamps from going backwards, in which case it may be
+/// the time of the appender's previous record. Encoders should call this
+/// rather than reading the clock themselves.
+pub fn record_time() -> SystemTime {
+    RECORD_TIME
+        .try_with(Cell::get)
+        .ok()
+        .flatten()
+        .unwrap_or_else(SystemTime::now)
+}
+
+/// Calls `f` with `record_time` returning `time` on the current thread.
+pub(crate) fn with_record_time<F, R>(time: SystemTime, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    struct Reset(Option<SystemTime>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let _ = RECORD_TIME.try_with(|t| t.set(self.0));
+        }
+    }
+
+    let _reset = Reset(RECORD_TIME.with(|t| t.replace(Some(time))));
+    f()
+}
+
+/// Returns the sequence number of the record being encoded.
+///
+/// A record logged through a log4rs logger is numbered the first time this
+/// is called while it is written, starting from 1, so every appender writing
+/// the record sees the same number and records no encoder asks the number of
+/// are not numbered. A record encoded outside of a logger is given the next
+/// number on each call.
+pub fn record_seq() -> u64 {
+    RECORD_SEQ
+        .try_with(|seq| match seq.get() {
+            Some(UNNUMBERED) => {
+                let next = next_seq();
+                seq.set(Some(next));
+                next
+            }
+            Some(current) => current,
+            None => next_seq(),
+        })
+        .unwrap_or_else(|_| next_seq())
+}
+
+/// The sequence number of a record which is not numbered yet.
+const UNNUMBERED: u64 = 0;
+
+fn next_seq() -> u64 {
+    NEXT_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Calls `f` with `record_seq` numbering the record logged on the current
+/// thread on its first call.
+pub(crate) fn with_unnumbered_record<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    with_record_seq(UNNUMBERED, f)
+}
+
+/// Calls `f` with `record_seq` returning `seq` on the current thread.
+pub(crate) fn with_record_seq<F, R>(seq: u64, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    struct Reset(Option<u64>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let _ = RECORD_SEQ.try_with(|s| s.set(self.0));
+        }
+    }
+
+    let _reset = Reset(RECORD_SEQ.with(|s| s.replace(Some(seq))));
+    f()
+}
+
+/// Returns the operating system's ID of the current thread, as shown by
+/// tools such as `top -H` or `ps -L` on Linux.
+///
+/// This is the kernel thread ID on Linux, and the ID given by the
+/// `thread-id` crate elsewhere.
+#[cfg(any(feature = "pattern_encoder", feature = "json_encoder"))]
+pub(crate) fn os_thread_id() -> u64 {
+    thread_local!(static OS_THREAD_ID: u64 = read_os_thread_id());
+
+    OS_THREAD_ID.with(|id| *id)
+}
+
+#[cfg(any(feature = "pattern_encoder", feature = "json_encoder"))]
+fn read_os_thread_id() -> u64 {
+    #[cfg(target_os = "linux")]
+    {
+        // `/proc/thread-self` links to `<pid>/task/<tid>`.
+        if let Some(id) = fs::read_link("/proc/thread-self")
+            .ok()
+            .and_then(|link| link.file_name()?.to_str()?.parse().ok())
+        {
+            return id;
+        }
+    }
+    thread_id::get() as u64
+}
+
+/// Returns the name of the host the process runs on, or `unknown` if it
+/// cannot be determined.
+///
+/// The name is taken from the `HOSTNAME` or `COMPUTERNAME` environment
+/// variables, or on Linux from the kernel, and is looked up once.
+pub fn hostname() -> &'static str {
+    let mut hostname = HOSTNAME.lock().unwrap_or_else(|e| e.into_inner());
+    hostname.get_or_insert_with(|| {
+        let name = env::var("HOSTNAME")
+            .or_else(|_| env::var("COMPUTERNAME"))
+            .ok()
+            .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+            .map(|name| name.trim().to_owned())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "unknown".to_owned());
+        Box::leak(name.into_boxed_str())
+    })
+}
+
+/// Returns the time the process started logging.
+///
+/// This is the time the first log4rs logger was created, or the time this
+/// function was first called if that was earlier, and stands in for the
+/// process's start time, which the standard library does not expose.
+pub fn process_start() -> SystemTime {
+    *PROCESS_START
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(SystemTime::now)
+}
+
 #[allow(dead_code)]
 #[cfg(windows)]
 const NEWLINE: &'static str = "\r\n";
@@ -79,6 +244,8 @@ impl<'de> de::Deserialize<'de> for EncoderConfig {
 /// A text or background color.
 #[allow(missing_docs)]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
 pub enum Color {
     Black,
     Red,
@@ -149,7 +316,7 @@ pub trait Write: io::Write {
     }
 }
 
-impl<'a, W: Write + ?Sized> Write for &'a mut W {
+impl<W: Write + ?Sized> Write for &mut W {
     fn set_style(&mut self, style: &Style) -> io::Result<()> {
         <W as Write>::set_style(*self, style)
     }
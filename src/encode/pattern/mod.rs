@@ -6,13 +6,14 @@
 //! consists of raw text interspersed with format arguments. The grammar is:
 //!
 //! ```not_rust
-//! format_string := <text> [ format <text> ] *
+//! format_string := <text> [ ( format | conditional ) <text> ] *
 //! format := '{' formatter [ ':' format_spec ] '}'
+//! conditional := '{' '?' formatter ':' format_string '}'
 //! formatter := [ name ] [ '(' argument ')' ] *
 //! name := identifier
 //! argument := format_string
 //!
-//! format_spec := [ [ fill ] align ] [ min_width ] [ '.' max_width ]
+//! format_spec := [ [ fill ] align ] [ min_width ] [ '.' [ '-' ] max_width ]
 //! fill := character
 //! align := '<' | '>'
 //! min_width := number
@@ -40,40 +41,97 @@
 //! a formatter does not accept any argument.
 //!
 //! * `d`, `date` - The current time. By default, the ISO 8601 format is used.
-//!     A custom format may be provided in the syntax accepted by `chrono`.
-//!     The timezone defaults to local, but can be specified explicitly by
-//!     passing a second argument of `utc` for UTC or `local` for local time.
-//!     * `{d}` - `2016-03-20T14:22:20.644420340-08:00`
-//!     * `{d(%Y-%m-%d %H:%M:%S)}` - `2016-03-20 14:22:20`
-//!     * `{d(%Y-%m-%d %H:%M:%S %Z)(utc)}` - `2016-03-20 22:22:20 UTC`
+//!   A custom format may be provided in the syntax accepted by `chrono`.
+//!   The timezone defaults to local, but can be specified explicitly by
+//!   passing a second argument of `utc` for UTC or `local` for local time.
+//!   * `{d}` - `2016-03-20T14:22:20.644420340-08:00`
+//!   * `{d(%Y-%m-%d %H:%M:%S)}` - `2016-03-20 14:22:20`
+//!   * `{d(%Y-%m-%d %H:%M:%S %Z)(utc)}` - `2016-03-20 22:22:20 UTC`
 //! * `f`, `file` - The source file that the log message came from, or `???` if
-//!     not provided.
+//!   not provided. An argument abbreviates the path, as for `M`.
+//!   * `{f(1)}` - `main.rs`
 //! * `h`, `highlight` - Styles its argument according to the log level. The
-//!     style is intense red for errors, red for warnings, blue for info, and
-//!     the default style for all other levels.
-//!     * `{h(the level is {l})}` -
-//!         <code style="color: red; font-weight: bold">the level is ERROR</code>
+//!   style is intense red for errors, red for warnings, blue for info, and
+//!   the default style for all other levels.
+//!   * `{h(the level is {l})}` -
+//!     <code style="color: red; font-weight: bold">the level is ERROR</code>
 //! * `l`, `level` - The log level.
 //! * `L`, `line` - The line that the log message came from, or `???` if not
-//!     provided.
+//!   provided.
 //! * `m`, `message` - The log message.
 //! * `M`, `module` - The module that the log message came from, or `???` if not
-//!     provided.
+//!   provided. An optional argument abbreviates the module path: a number
+//!   keeps only that many of its last segments, and a number followed by `.`
+//!   cuts every segment but the last to that many characters.
+//!   * `{M(1)}` - `Foo` for `alpha::beta::Foo`
+//!   * `{M(1.)}` - `a::b::Foo` for `alpha::beta::Foo`
 //! * `P`, `pid` - The current process id.
-//! * `i`, `tid` - The current system-wide unique thread ID.
+//! * `seq` - The sequence number of the record, which increases by one for
+//!   each record numbered in the process. Records are numbered when their
+//!   number is first written, so records written without it don't use up
+//!   numbers. Every appender writing a record sees the same number.
+//! * `host` - The name of the host the process runs on.
+//! * `start` - The time the process started logging, in the ISO 8601 format
+//!   in UTC. Together with `host` and `pid` it identifies an instance of a
+//!   service, even when process ids are reused.
+//!   * `{host}:{pid}:{seq}` - `web-1:4012:1337`
+//! * `i`, `tid` - The operating system's ID of the current thread, which is
+//!   unique system-wide and matches the thread IDs shown by tools such as
+//!   `top -H` on Linux.
 //! * `n` - A platform-specific newline.
-//! * `t`, `target` - The target of the log message.
-//! * `T`, `thread` - The name of the current thread.
+//! * `t`, `target` - The target of the log message. An argument abbreviates
+//!   the target, as for `M`.
+//! * `T`, `thread` - The name of the current thread, or its `tid` if it is
+//!   unnamed.
 //! * `I`, `thread_id` - The pthread ID of the current thread.
 //! * `X`, `mdc` - A value from the [MDC][MDC]. The first argument specifies
-//!     the key, and the second argument specifies the default value if the
-//!     key is not present in the MDC. The second argument is optional, and
-//!     defaults to the empty string.
-//!     * `{X(user_id)}` - `123e4567-e89b-12d3-a456-426655440000`
-//!     * `{X(nonexistent_key)(no mapping)}` - `no mapping`
+//!   the key, and the second argument specifies the default value if the
+//!   key is not present in the MDC. The second argument is optional, and
+//!   defaults to the empty string.
+//!   * `{X(user_id)}` - `123e4567-e89b-12d3-a456-426655440000`
+//!   * `{X(nonexistent_key)(no mapping)}` - `no mapping`
+//! * `K`, `key_value` - A value from the record's key-value pairs. The
+//!   arguments are the same as for `X`.
+//! * `backtrace` - A backtrace of the code logging the record, if its level is
+//!   at or above the first argument, `error` by default, and capturing
+//!   backtraces is enabled with the `RUST_LIB_BACKTRACE` or `RUST_BACKTRACE`
+//!   environment variable; otherwise nothing. Capturing a backtrace is slow,
+//!   so it is only done when both allow it. The backtrace is written on lines
+//!   of its own, starting with `backtrace:` and indented by four spaces or by
+//!   the second argument, and its first frames are those of the logger
+//!   itself.
+//!   * `{m}{backtrace(warn)}{n}`
+//! * `err` - The error attached to the record under the `err` key, as
+//!   described in [`error_chain`], or nothing if there is none. Each error
+//!   which caused it, and the backtrace if one was captured, follow on lines
+//!   of their own, indented by four spaces or by the argument, so that line
+//!   oriented tools can tell them from new records.
+//!   * `{m}{?K(err):: {err}}` - `startup failed: no config`
+//!     <br>`    caused by: No such file or directory (os error 2)`
+//!   * `{K(service)}` - `api`
+//!   * `{K(nonexistent_key)(none)}` - `none`
 //! * An "unnamed" formatter simply formats its argument, applying the format
-//!     specification.
-//!     * `{({l} {m})}` - `INFO hello`
+//!   specification.
+//!   * `{({l} {m})}` - `INFO hello`
+//!
+//! # Conditional sections
+//!
+//! A conditional section writes its body only if a formatter, its condition,
+//! writes anything, so that optional context does not leave dangling labels
+//! and separators in every line. `X` and `K` write nothing for a missing key
+//! unless given a default.
+//!
+//! * `{?X(request_id):rid={X(request_id)} }{m}` - `rid=42 hello`, or `hello`
+//!   if the MDC has no `request_id`
+//!
+//! # Localization
+//!
+//! An encoder given a [`Localize`] provider with `PatternEncoder::locale`
+//! renders level names, the month and weekday names of dates (the `%B`, `%b`,
+//! `%h`, `%A` and `%a` specifiers), and line numbers and IDs through it.
+//!
+//! [`Localize`]: crate::encode::locale::Localize
+//! [`error_chain`]: crate::error_chain
 //!
 //! # Format Specification
 //!
@@ -90,7 +148,7 @@
 //!
 //! * `<` - Left align by appending the fill character to the formatter output
 //! * `>` - Right align by prepending the fill character to the formatter
-//!     output.
+//!   output.
 //!
 //! ## Width
 //!
@@ -99,6 +157,12 @@
 //! configured. Any output over the maximum length will be truncated, and
 //! output under the minimum length will be padded (see above).
 //!
+//! Output over the maximum length is cut from its end by default. A `-`
+//! before the maximum length cuts it from the start instead, keeping the end
+//! of the output, which suits module paths and targets: `{t:>20.-20}` aligns
+//! targets in a column 20 characters wide, showing `b::c::very_long_name`
+//! for the target `a::b::c::very_long_name`.
+//!
 //! # Examples
 //!
 //! The default pattern is `{d} {l} {t} - {m}{n}` which produces output like
@@ -119,33 +183,40 @@
 //!
 //! [MDC]: https://crates.io/crates/log-mdc
 
-use chrono::{Local, Utc};
+use chrono::{DateTime, Datelike, Local, Utc};
 use derivative::Derivative;
-use log::{Level, Record};
-use std::{default::Default, io, process, thread};
+use log::{kv, Level, Record};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    borrow::Cow,
+    default::Default,
+    io, process,
+    sync::Arc,
+    thread,
+};
 
+#[cfg(feature = "config_parsing")]
+use crate::encode::locale::LocaleConfig;
 use crate::encode::{
     self,
-    pattern::parser::{Alignment, Parameters, Parser, Piece},
+    locale::Localize,
+    pattern::parser::{Alignment, Parameters, Parser, Piece, Truncate},
     Color, Encode, Style, NEWLINE,
 };
+use crate::error_chain::ErrorChain;
 
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
 
 mod parser;
 
-thread_local!(
-    /// Thread-locally cached thread ID.
-    static TID: usize = thread_id::get()
-);
-
 /// The pattern encoder's configuration.
 #[cfg(feature = "config_parsing")]
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct PatternEncoderConfig {
     pattern: Option<String>,
+    locale: Option<LocaleConfig>,
 }
 
 fn is_char_boundary(b: u8) -> bool {
@@ -271,19 +342,73 @@ impl<W: encode::Write> RightAlignWriter<W> {
     }
 }
 
+fn buffer_data(out: &mut Vec<BufferedOutput>, buf: &[u8]) {
+    if let Some(&mut BufferedOutput::Data(ref mut data)) = out.last_mut() {
+        data.extend_from_slice(buf);
+    } else {
+        out.push(BufferedOutput::Data(buf.to_owned()));
+    }
+}
+
 impl<W: encode::Write> io::Write for RightAlignWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.to_fill = self.to_fill.saturating_sub(char_starts(buf));
+        buffer_data(&mut self.buf, buf);
+        Ok(buf.len())
+    }
 
-        let mut pushed = false;
-        if let Some(&mut BufferedOutput::Data(ref mut data)) = self.buf.last_mut() {
-            data.extend_from_slice(buf);
-            pushed = true;
-        };
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: encode::Write> encode::Write for RightAlignWriter<W> {
+    fn set_style(&mut self, style: &Style) -> io::Result<()> {
+        self.buf.push(BufferedOutput::Style(style.clone()));
+        Ok(())
+    }
+}
 
-        if !pushed {
-            self.buf.push(BufferedOutput::Data(buf.to_owned()));
+/// Buffers output so that only its last characters are written, keeping
+/// every change of style.
+#[derive(Default)]
+struct LeftTruncateWriter {
+    buf: Vec<BufferedOutput>,
+}
+
+impl LeftTruncateWriter {
+    fn finish(self, max_width: usize, w: &mut dyn encode::Write) -> io::Result<()> {
+        let len: usize = self
+            .buf
+            .iter()
+            .map(|out| match *out {
+                BufferedOutput::Data(ref data) => char_starts(data),
+                BufferedOutput::Style(_) => 0,
+            })
+            .sum();
+        let mut skip = len.saturating_sub(max_width);
+        for out in self.buf {
+            match out {
+                BufferedOutput::Data(ref data) => {
+                    let start = data
+                        .iter()
+                        .enumerate()
+                        .filter(|&(_, &b)| is_char_boundary(b))
+                        .nth(skip)
+                        .map_or(data.len(), |(idx, _)| idx);
+                    skip -= char_starts(&data[..start]);
+                    w.write_all(&data[start..])?;
+                }
+                BufferedOutput::Style(ref style) => w.set_style(style)?,
+            }
         }
+        Ok(())
+    }
+}
+
+impl io::Write for LeftTruncateWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        buffer_data(&mut self.buf, buf);
         Ok(buf.len())
     }
 
@@ -292,13 +417,87 @@ impl<W: encode::Write> io::Write for RightAlignWriter<W> {
     }
 }
 
-impl<W: encode::Write> encode::Write for RightAlignWriter<W> {
+impl encode::Write for LeftTruncateWriter {
     fn set_style(&mut self, style: &Style) -> io::Result<()> {
         self.buf.push(BufferedOutput::Style(style.clone()));
         Ok(())
     }
 }
 
+/// Discards output, noting whether there was any.
+struct ProbeWriter(bool);
+
+impl io::Write for ProbeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 |= !buf.is_empty();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl encode::Write for ProbeWriter {}
+
+/// Writes the output of `f` padded and truncated as the parameters specify.
+fn encode_aligned<F>(w: &mut dyn encode::Write, params: &Parameters, f: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn encode::Write) -> io::Result<()>,
+{
+    match (params.min_width, params.max_width, params.align) {
+        (None, None, _) => f(w),
+        (None, Some(max_width), _) => f(&mut MaxWidthWriter {
+            remaining: max_width,
+            w,
+        }),
+        (Some(min_width), None, Alignment::Left) => {
+            let mut w = LeftAlignWriter {
+                to_fill: min_width,
+                fill: params.fill,
+                w,
+            };
+            f(&mut w)?;
+            w.finish()
+        }
+        (Some(min_width), None, Alignment::Right) => {
+            let mut w = RightAlignWriter {
+                to_fill: min_width,
+                fill: params.fill,
+                w,
+                buf: vec![],
+            };
+            f(&mut w)?;
+            w.finish()
+        }
+        (Some(min_width), Some(max_width), Alignment::Left) => {
+            let mut w = LeftAlignWriter {
+                to_fill: min_width,
+                fill: params.fill,
+                w: MaxWidthWriter {
+                    remaining: max_width,
+                    w,
+                },
+            };
+            f(&mut w)?;
+            w.finish()
+        }
+        (Some(min_width), Some(max_width), Alignment::Right) => {
+            let mut w = RightAlignWriter {
+                to_fill: min_width,
+                fill: params.fill,
+                w: MaxWidthWriter {
+                    remaining: max_width,
+                    w,
+                },
+                buf: vec![],
+            };
+            f(&mut w)?;
+            w.finish()
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 enum Chunk {
     Text(String),
@@ -306,70 +505,50 @@ enum Chunk {
         chunk: FormattedChunk,
         params: Parameters,
     },
+    Conditional {
+        condition: Box<Chunk>,
+        body: Vec<Chunk>,
+    },
     Error(String),
 }
 
 impl Chunk {
-    fn encode(&self, w: &mut dyn encode::Write, record: &Record) -> io::Result<()> {
+    fn encode(
+        &self,
+        w: &mut dyn encode::Write,
+        record: &Record,
+        locale: Option<&dyn Localize>,
+    ) -> io::Result<()> {
         match *self {
             Chunk::Text(ref s) => w.write_all(s.as_bytes()),
             Chunk::Formatted {
                 ref chunk,
                 ref params,
-            } => match (params.min_width, params.max_width, params.align) {
-                (None, None, _) => chunk.encode(w, record),
-                (None, Some(max_width), _) => {
-                    let mut w = MaxWidthWriter {
-                        remaining: max_width,
-                        w,
-                    };
-                    chunk.encode(&mut w, record)
-                }
-                (Some(min_width), None, Alignment::Left) => {
-                    let mut w = LeftAlignWriter {
-                        to_fill: min_width,
-                        fill: params.fill,
-                        w,
-                    };
-                    chunk.encode(&mut w, record)?;
-                    w.finish()
-                }
-                (Some(min_width), None, Alignment::Right) => {
-                    let mut w = RightAlignWriter {
-                        to_fill: min_width,
-                        fill: params.fill,
-                        w,
-                        buf: vec![],
+            } => match (params.truncate, params.max_width) {
+                (Truncate::Left, Some(max_width)) => {
+                    let mut buf = LeftTruncateWriter::default();
+                    chunk.encode(&mut buf, record, locale)?;
+                    let params = Parameters {
+                        max_width: None,
+                        ..params.clone()
                     };
-                    chunk.encode(&mut w, record)?;
-                    w.finish()
-                }
-                (Some(min_width), Some(max_width), Alignment::Left) => {
-                    let mut w = LeftAlignWriter {
-                        to_fill: min_width,
-                        fill: params.fill,
-                        w: MaxWidthWriter {
-                            remaining: max_width,
-                            w,
-                        },
-                    };
-                    chunk.encode(&mut w, record)?;
-                    w.finish()
-                }
-                (Some(min_width), Some(max_width), Alignment::Right) => {
-                    let mut w = RightAlignWriter {
-                        to_fill: min_width,
-                        fill: params.fill,
-                        w: MaxWidthWriter {
-                            remaining: max_width,
-                            w,
-                        },
-                        buf: vec![],
-                    };
-                    chunk.encode(&mut w, record)?;
-                    w.finish()
+                    encode_aligned(w, &params, |w| buf.finish(max_width, w))
                 }
+                _ => encode_aligned(w, params, |w| chunk.encode(w, record, locale)),
             },
+            Chunk::Conditional {
+                ref condition,
+                ref body,
+            } => {
+                let mut probe = ProbeWriter(false);
+                condition.encode(&mut probe, record, locale)?;
+                if probe.0 {
+                    for chunk in body {
+                        chunk.encode(w, record, locale)?;
+                    }
+                }
+                Ok(())
+            }
             Chunk::Error(ref s) => write!(w, "{{ERROR: {}}}", s),
         }
     }
@@ -388,13 +567,13 @@ impl<'a> From<Piece<'a>> for Chunk {
                         return Chunk::Error("expected at most two arguments".to_owned());
                     }
 
-                    let format = match formatter.args.get(0) {
+                    let format = match formatter.args.first() {
                         Some(arg) => {
                             let mut format = String::new();
                             for piece in arg {
                                 match *piece {
                                     Piece::Text(text) => format.push_str(text),
-                                    Piece::Argument { .. } => {
+                                    Piece::Argument { .. } | Piece::Conditional { .. } => {
                                         format.push_str("{ERROR: unexpected formatter}");
                                     }
                                     Piece::Error(ref err) => {
@@ -411,7 +590,7 @@ impl<'a> From<Piece<'a>> for Chunk {
 
                     let timezone = match formatter.args.get(1) {
                         Some(arg) => {
-                            if let Some(arg) = arg.get(0) {
+                            if let Some(arg) = arg.first() {
                                 match *arg {
                                     Piece::Text("utc") => Timezone::Utc,
                                     Piece::Text("local") => Timezone::Local,
@@ -451,55 +630,68 @@ impl<'a> From<Piece<'a>> for Chunk {
                 }
                 "l" | "level" => no_args(&formatter.args, parameters, FormattedChunk::Level),
                 "m" | "message" => no_args(&formatter.args, parameters, FormattedChunk::Message),
-                "M" | "module" => no_args(&formatter.args, parameters, FormattedChunk::Module),
+                "M" | "module" => abbreviated(&formatter.args, parameters, FormattedChunk::Module),
                 "n" => no_args(&formatter.args, parameters, FormattedChunk::Newline),
-                "f" | "file" => no_args(&formatter.args, parameters, FormattedChunk::File),
+                "f" | "file" => abbreviated(&formatter.args, parameters, FormattedChunk::File),
                 "L" | "line" => no_args(&formatter.args, parameters, FormattedChunk::Line),
                 "T" | "thread" => no_args(&formatter.args, parameters, FormattedChunk::Thread),
                 "I" | "thread_id" => no_args(&formatter.args, parameters, FormattedChunk::ThreadId),
                 "P" | "pid" => no_args(&formatter.args, parameters, FormattedChunk::ProcessId),
+                "seq" => no_args(&formatter.args, parameters, FormattedChunk::Seq),
+                "host" => no_args(&formatter.args, parameters, FormattedChunk::Host),
+                "start" => no_args(&formatter.args, parameters, FormattedChunk::ProcessStart),
                 "i" | "tid" => no_args(&formatter.args, parameters, FormattedChunk::SystemThreadId),
-                "t" | "target" => no_args(&formatter.args, parameters, FormattedChunk::Target),
-                "X" | "mdc" => {
+                "t" | "target" => abbreviated(&formatter.args, parameters, FormattedChunk::Target),
+                "backtrace" => {
                     if formatter.args.len() > 2 {
                         return Chunk::Error("expected at most two arguments".to_owned());
                     }
-
-                    let key = match formatter.args.get(0) {
-                        Some(arg) => {
-                            if let Some(arg) = arg.get(0) {
-                                match arg {
-                                    Piece::Text(key) => key.to_owned(),
-                                    Piece::Error(ref e) => return Chunk::Error(e.clone()),
-                                    _ => return Chunk::Error("invalid MDC key".to_owned()),
-                                }
-                            } else {
-                                return Chunk::Error("invalid MDC key".to_owned());
-                            }
-                        }
-                        None => return Chunk::Error("missing MDC key".to_owned()),
+                    let level = match formatter.args.first().map(Vec::as_slice) {
+                        None => Level::Error,
+                        Some([Piece::Text(level)]) => match level.parse() {
+                            Ok(level) => level,
+                            Err(_) => return Chunk::Error(format!("invalid level `{}`", level)),
+                        },
+                        Some(_) => return Chunk::Error("invalid level".to_owned()),
                     };
-
-                    let default = match formatter.args.get(1) {
-                        Some(arg) => {
-                            if let Some(arg) = arg.get(0) {
-                                match arg {
-                                    Piece::Text(key) => key.to_owned(),
-                                    Piece::Error(ref e) => return Chunk::Error(e.clone()),
-                                    _ => return Chunk::Error("invalid MDC default".to_owned()),
-                                }
-                            } else {
-                                return Chunk::Error("invalid MDC default".to_owned());
-                            }
-                        }
-                        None => "",
+                    let indent = match formatter.args.get(1).map(Vec::as_slice) {
+                        None => "    ".to_owned(),
+                        Some([Piece::Text(indent)]) => (*indent).to_owned(),
+                        Some(_) => return Chunk::Error("invalid indent".to_owned()),
                     };
-
                     Chunk::Formatted {
-                        chunk: FormattedChunk::Mdc(key.into(), default.into()),
+                        chunk: FormattedChunk::Backtrace(level, indent),
                         params: parameters,
                     }
                 }
+                "err" => {
+                    let indent = match formatter.args.as_slice() {
+                        [] => "    ".to_owned(),
+                        [arg] => match arg.as_slice() {
+                            [Piece::Text(indent)] => (*indent).to_owned(),
+                            _ => return Chunk::Error("invalid indent".to_owned()),
+                        },
+                        _ => return Chunk::Error("expected at most one argument".to_owned()),
+                    };
+                    Chunk::Formatted {
+                        chunk: FormattedChunk::Error(indent),
+                        params: parameters,
+                    }
+                }
+                "X" | "mdc" => match key_and_default(&formatter.args, "MDC") {
+                    Ok((key, default)) => Chunk::Formatted {
+                        chunk: FormattedChunk::Mdc(key, default),
+                        params: parameters,
+                    },
+                    Err(e) => Chunk::Error(e),
+                },
+                "K" | "key_value" => match key_and_default(&formatter.args, "key-value") {
+                    Ok((key, default)) => Chunk::Formatted {
+                        chunk: FormattedChunk::KeyValue(key, default),
+                        params: parameters,
+                    },
+                    Err(e) => Chunk::Error(e),
+                },
                 "" => {
                     if formatter.args.len() != 1 {
                         return Chunk::Error("expected exactly one argument".to_owned());
@@ -519,6 +711,19 @@ impl<'a> From<Piece<'a>> for Chunk {
                 }
                 name => Chunk::Error(format!("unknown formatter `{}`", name)),
             },
+            Piece::Conditional { condition, body } => {
+                let condition = Chunk::from(Piece::Argument {
+                    formatter: condition,
+                    parameters: Parameters::default(),
+                });
+                if let Chunk::Error(err) = condition {
+                    return Chunk::Error(err);
+                }
+                Chunk::Conditional {
+                    condition: Box::new(condition),
+                    body: body.into_iter().map(From::from).collect(),
+                }
+            }
             Piece::Error(err) => Chunk::Error(err),
         }
     }
@@ -532,59 +737,227 @@ fn no_args(arg: &[Vec<Piece>], params: Parameters, chunk: FormattedChunk) -> Chu
     }
 }
 
+fn abbreviated(
+    args: &[Vec<Piece>],
+    params: Parameters,
+    chunk: fn(Option<Abbreviation>) -> FormattedChunk,
+) -> Chunk {
+    let abbreviation = match args {
+        [] => None,
+        [arg] => match arg.as_slice() {
+            [Piece::Text(text)] => match Abbreviation::parse(text) {
+                Some(abbreviation) => Some(abbreviation),
+                None => return Chunk::Error(format!("invalid abbreviation `{}`", text)),
+            },
+            [Piece::Error(ref e)] => return Chunk::Error(e.clone()),
+            _ => return Chunk::Error("invalid abbreviation".to_owned()),
+        },
+        _ => return Chunk::Error("expected at most one argument".to_owned()),
+    };
+    Chunk::Formatted {
+        chunk: chunk(abbreviation),
+        params,
+    }
+}
+
+fn key_and_default(args: &[Vec<Piece>], what: &str) -> Result<(String, String), String> {
+    if args.len() > 2 {
+        return Err("expected at most two arguments".to_owned());
+    }
+
+    let key = match args.first() {
+        Some(arg) => match arg.first() {
+            Some(Piece::Text(key)) => (*key).to_owned(),
+            Some(Piece::Error(ref e)) => return Err(e.clone()),
+            _ => return Err(format!("invalid {} key", what)),
+        },
+        None => return Err(format!("missing {} key", what)),
+    };
+
+    let default = match args.get(1) {
+        Some(arg) => match arg.first() {
+            Some(Piece::Text(default)) => (*default).to_owned(),
+            Some(Piece::Error(ref e)) => return Err(e.clone()),
+            _ => return Err(format!("invalid {} default", what)),
+        },
+        None => String::new(),
+    };
+
+    Ok((key, default))
+}
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 enum Timezone {
     Utc,
     Local,
 }
 
+/// How a module path, target or file path is shortened.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+enum Abbreviation {
+    /// Only the last segments are kept, such as `Foo` for `a::b::Foo`
+    /// with 1.
+    Last(usize),
+    /// Every segment but the last is cut to its first characters, such as
+    /// `a::b::Foo` for `alpha::beta::Foo` with 1.
+    Shorten(usize),
+}
+
+impl Abbreviation {
+    fn parse(text: &str) -> Option<Abbreviation> {
+        match text.strip_suffix('.') {
+            Some(len) => len.parse().ok().map(Abbreviation::Shorten),
+            None => text.parse().ok().filter(|&n| n > 0).map(Abbreviation::Last),
+        }
+    }
+
+    /// Abbreviates a path whose segments are separated by `separators`, as
+    /// byte offsets and lengths.
+    fn apply<'a>(self, path: &'a str, separators: &[(usize, usize)]) -> Cow<'a, str> {
+        match self {
+            Abbreviation::Last(n) => match separators.len().checked_sub(n) {
+                Some(i) => {
+                    let (start, len) = separators[i];
+                    Cow::Borrowed(&path[start + len..])
+                }
+                None => Cow::Borrowed(path),
+            },
+            Abbreviation::Shorten(n) => {
+                let mut out = String::with_capacity(path.len());
+                let mut start = 0;
+                for &(sep, len) in separators {
+                    out.extend(path[start..sep].chars().take(n));
+                    out.push_str(&path[sep..sep + len]);
+                    start = sep + len;
+                }
+                out.push_str(&path[start..]);
+                Cow::Owned(out)
+            }
+        }
+    }
+
+    fn module_path(abbreviation: Option<Abbreviation>, path: &str) -> Cow<'_, str> {
+        match abbreviation {
+            Some(abbreviation) => {
+                let separators = path
+                    .match_indices("::")
+                    .map(|(idx, sep)| (idx, sep.len()))
+                    .collect::<Vec<_>>();
+                abbreviation.apply(path, &separators)
+            }
+            None => Cow::Borrowed(path),
+        }
+    }
+
+    fn file_path(abbreviation: Option<Abbreviation>, path: &str) -> Cow<'_, str> {
+        match abbreviation {
+            Some(abbreviation) => {
+                let separators = path
+                    .match_indices(['/', '\\'])
+                    .map(|(idx, sep)| (idx, sep.len()))
+                    .collect::<Vec<_>>();
+                abbreviation.apply(path, &separators)
+            }
+            None => Cow::Borrowed(path),
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 enum FormattedChunk {
     Time(String, Timezone),
     Level,
     Message,
-    Module,
-    File,
+    Module(Option<Abbreviation>),
+    File(Option<Abbreviation>),
     Line,
     Thread,
     ThreadId,
     ProcessId,
+    Seq,
+    Host,
+    ProcessStart,
     SystemThreadId,
-    Target,
+    Target(Option<Abbreviation>),
     Newline,
     Align(Vec<Chunk>),
     Highlight(Vec<Chunk>),
     Mdc(String, String),
+    KeyValue(String, String),
+    Error(String),
+    Backtrace(Level, String),
 }
 
 impl FormattedChunk {
-    fn encode(&self, w: &mut dyn encode::Write, record: &Record) -> io::Result<()> {
+    fn encode(
+        &self,
+        w: &mut dyn encode::Write,
+        record: &Record,
+        locale: Option<&dyn Localize>,
+    ) -> io::Result<()> {
         match *self {
-            FormattedChunk::Time(ref fmt, Timezone::Utc) => write!(w, "{}", Utc::now().format(fmt)),
+            FormattedChunk::Time(ref fmt, Timezone::Utc) => {
+                let now = DateTime::<Utc>::from(encode::record_time());
+                write!(
+                    w,
+                    "{}",
+                    now.format(&localize_time_format(fmt, &now, locale))
+                )
+            }
             FormattedChunk::Time(ref fmt, Timezone::Local) => {
-                write!(w, "{}", Local::now().format(fmt))
+                let now = DateTime::<Local>::from(encode::record_time());
+                write!(
+                    w,
+                    "{}",
+                    now.format(&localize_time_format(fmt, &now, locale))
+                )
             }
-            FormattedChunk::Level => write!(w, "{}", record.level()),
+            FormattedChunk::Level => match locale {
+                Some(locale) => w.write_all(locale.level(record.level()).as_bytes()),
+                None => write!(w, "{}", record.level()),
+            },
             FormattedChunk::Message => w.write_fmt(*record.args()),
-            FormattedChunk::Module => w.write_all(record.module_path().unwrap_or("???").as_bytes()),
-            FormattedChunk::File => w.write_all(record.file().unwrap_or("???").as_bytes()),
+            FormattedChunk::Module(abbreviation) => match record.module_path() {
+                Some(path) => w.write_all(Abbreviation::module_path(abbreviation, path).as_bytes()),
+                None => w.write_all(b"???"),
+            },
+            FormattedChunk::File(abbreviation) => match record.file() {
+                Some(path) => w.write_all(Abbreviation::file_path(abbreviation, path).as_bytes()),
+                None => w.write_all(b"???"),
+            },
             FormattedChunk::Line => match record.line() {
-                Some(line) => write!(w, "{}", line),
+                Some(line) => w.write_all(format_number(line.into(), locale).as_bytes()),
                 None => w.write_all(b"???"),
             },
-            FormattedChunk::Thread => {
-                w.write_all(thread::current().name().unwrap_or("unnamed").as_bytes())
+            FormattedChunk::Thread => match thread::current().name() {
+                Some(name) => w.write_all(name.as_bytes()),
+                None => w.write_all(format_number(encode::os_thread_id(), locale).as_bytes()),
+            },
+            FormattedChunk::ThreadId => {
+                w.write_all(format_number(thread_id::get() as u64, locale).as_bytes())
             }
-            FormattedChunk::ThreadId => w.write_all(thread_id::get().to_string().as_bytes()),
-            FormattedChunk::ProcessId => w.write_all(process::id().to_string().as_bytes()),
+            FormattedChunk::ProcessId => {
+                w.write_all(format_number(process::id().into(), locale).as_bytes())
+            }
+            FormattedChunk::Seq => {
+                w.write_all(format_number(encode::record_seq(), locale).as_bytes())
+            }
+            FormattedChunk::Host => w.write_all(encode::hostname().as_bytes()),
+            FormattedChunk::ProcessStart => write!(
+                w,
+                "{}",
+                DateTime::<Utc>::from(encode::process_start()).to_rfc3339()
+            ),
             FormattedChunk::SystemThreadId => {
-                TID.with(|tid| w.write_all(tid.to_string().as_bytes()))
+                w.write_all(format_number(encode::os_thread_id(), locale).as_bytes())
+            }
+            FormattedChunk::Target(abbreviation) => {
+                w.write_all(Abbreviation::module_path(abbreviation, record.target()).as_bytes())
             }
-            FormattedChunk::Target => w.write_all(record.target().as_bytes()),
             FormattedChunk::Newline => w.write_all(NEWLINE.as_bytes()),
             FormattedChunk::Align(ref chunks) => {
                 for chunk in chunks {
-                    chunk.encode(w, record)?;
+                    chunk.encode(w, record, locale)?;
                 }
                 Ok(())
             }
@@ -599,7 +972,7 @@ impl FormattedChunk {
                     _ => {}
                 }
                 for chunk in chunks {
-                    chunk.encode(w, record)?;
+                    chunk.encode(w, record, locale)?;
                 }
                 match record.level() {
                     Level::Error | Level::Warn | Level::Info | Level::Trace => {
@@ -612,18 +985,130 @@ impl FormattedChunk {
             FormattedChunk::Mdc(ref key, ref default) => {
                 log_mdc::get(key, |v| write!(w, "{}", v.unwrap_or(default)))
             }
+            FormattedChunk::KeyValue(ref key, ref default) => {
+                match record.key_values().get(kv::Key::from_str(key)) {
+                    Some(value) => write!(w, "{}", value),
+                    None => w.write_all(default.as_bytes()),
+                }
+            }
+            FormattedChunk::Error(ref indent) => {
+                let chain = match ErrorChain::attached(record) {
+                    Some(chain) => chain,
+                    None => return Ok(()),
+                };
+                let mut lines = vec![];
+                for (i, message) in chain.messages().iter().enumerate() {
+                    match i {
+                        0 => lines.extend(message.lines().map(Cow::Borrowed)),
+                        _ => lines.extend(message.lines().enumerate().map(|(j, line)| match j {
+                            0 => Cow::Owned(format!("caused by: {}", line)),
+                            _ => Cow::Borrowed(line),
+                        })),
+                    }
+                }
+                if let Some(backtrace) = chain.backtrace() {
+                    lines.push(Cow::Borrowed("backtrace:"));
+                    lines.extend(backtrace.lines().map(Cow::Borrowed));
+                }
+                write_indented(w, lines.iter().map(|line| &**line), indent)
+            }
+            FormattedChunk::Backtrace(level, ref indent) => {
+                if record.level() > level {
+                    return Ok(());
+                }
+                let backtrace = Backtrace::capture();
+                if backtrace.status() != BacktraceStatus::Captured {
+                    return Ok(());
+                }
+                let backtrace = backtrace.to_string();
+                write_indented(
+                    w,
+                    ["", "backtrace:"].iter().copied().chain(backtrace.lines()),
+                    indent,
+                )
+            }
+        }
+    }
+}
+
+/// Writes lines, indenting every line but the first.
+fn write_indented<'a, I>(w: &mut dyn encode::Write, lines: I, indent: &str) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    for (i, line) in lines.into_iter().enumerate() {
+        if i > 0 {
+            w.write_all(NEWLINE.as_bytes())?;
+            w.write_all(indent.as_bytes())?;
         }
+        w.write_all(line.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn format_number(number: u64, locale: Option<&dyn Localize>) -> String {
+    match locale {
+        Some(locale) => locale.number(number),
+        None => number.to_string(),
     }
 }
 
+/// Replaces the month and weekday name specifiers of a chrono format string
+/// with the names given by the locale.
+fn localize_time_format<'a, D: Datelike>(
+    fmt: &'a str,
+    date: &D,
+    locale: Option<&dyn Localize>,
+) -> Cow<'a, str> {
+    let locale = match locale {
+        Some(locale) => locale,
+        None => return Cow::Borrowed(fmt),
+    };
+
+    let mut out = String::with_capacity(fmt.len());
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let spec = chars.next();
+        let name = match spec {
+            Some('B') => locale.month(date.month(), false),
+            Some('b') | Some('h') => locale.month(date.month(), true),
+            Some('A') => locale.weekday(date.weekday().num_days_from_monday(), false),
+            Some('a') => locale.weekday(date.weekday().num_days_from_monday(), true),
+            _ => None,
+        };
+        match (name, spec) {
+            (Some(name), _) => out.push_str(&name.replace('%', "%%")),
+            (None, Some(spec)) => {
+                out.push('%');
+                out.push(spec);
+            }
+            (None, None) => out.push('%'),
+        }
+    }
+    Cow::Owned(out)
+}
+
 /// An `Encode`r configured via a format string.
 #[derive(Derivative)]
-#[derivative(Debug)]
-#[derive(Clone, Eq, PartialEq, Hash)]
+#[derivative(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PatternEncoder {
     #[derivative(Debug = "ignore")]
     chunks: Vec<Chunk>,
     pattern: String,
+    #[derivative(PartialEq(compare_with = "same_locale"), Hash = "ignore")]
+    locale: Option<Arc<dyn Localize>>,
+}
+
+fn same_locale(a: &Option<Arc<dyn Localize>>, b: &Option<Arc<dyn Localize>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
 }
 
 /// Returns a `PatternEncoder` using the default pattern of `{d} {l} {t} - {m}{n}`.
@@ -636,7 +1121,7 @@ impl Default for PatternEncoder {
 impl Encode for PatternEncoder {
     fn encode(&self, w: &mut dyn encode::Write, record: &Record) -> anyhow::Result<()> {
         for chunk in &self.chunks {
-            chunk.encode(w, record)?;
+            chunk.encode(w, record, self.locale.as_deref())?;
         }
         Ok(())
     }
@@ -650,8 +1135,16 @@ impl PatternEncoder {
         PatternEncoder {
             chunks: Parser::new(pattern).map(From::from).collect(),
             pattern: pattern.to_owned(),
+            locale: None,
         }
     }
+
+    /// Sets the provider used to localize level names, month and weekday
+    /// names in dates, and line numbers and IDs.
+    pub fn locale(mut self, locale: Arc<dyn Localize>) -> PatternEncoder {
+        self.locale = Some(locale);
+        self
+    }
 }
 
 /// A deserializer for the `PatternEncoder`.
@@ -664,6 +1157,19 @@ impl PatternEncoder {
 /// # The pattern to follow when formatting logs. Defaults to
 /// # "{d} {l} {t} - {m}{n}".
 /// pattern: "{d} {l} {t} - {m}{n}"
+///
+/// # Localized text, replacing the English names of levels, months and
+/// # weekdays, and grouping the digits of numbers. Every field is optional.
+/// # Month lists start with January and weekday lists with Monday.
+/// locale:
+///   levels:
+///     error: FEHLER
+///     warn: WARNUNG
+///   months: [Januar, Februar, März, April, Mai, Juni, Juli, August, September, Oktober, November, Dezember]
+///   months_abbreviated: [Jan, Feb, Mär, Apr, Mai, Jun, Jul, Aug, Sep, Okt, Nov, Dez]
+///   weekdays: [Montag, Dienstag, Mittwoch, Donnerstag, Freitag, Samstag, Sonntag]
+///   weekdays_abbreviated: [Mo, Di, Mi, Do, Fr, Sa, So]
+///   thousands_separator: "."
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -680,10 +1186,13 @@ impl Deserialize for PatternEncoderDeserializer {
         config: PatternEncoderConfig,
         _: &Deserializers,
     ) -> anyhow::Result<Box<dyn Encode>> {
-        let encoder = match config.pattern {
+        let mut encoder = match config.pattern {
             Some(pattern) => PatternEncoder::new(&pattern),
             None => PatternEncoder::default(),
         };
+        if let Some(locale) = config.locale {
+            encoder = encoder.locale(Arc::new(locale.build()?));
+        }
         Ok(Box::new(encoder))
     }
 }
@@ -691,7 +1200,9 @@ impl Deserialize for PatternEncoderDeserializer {
 #[cfg(test)]
 mod tests {
     #[cfg(feature = "simple_writer")]
-    use log::{Level, Record};
+    use log::{kv, Level, Record};
+    #[cfg(feature = "simple_writer")]
+    use std::backtrace::{Backtrace, BacktraceStatus};
     #[cfg(feature = "simple_writer")]
     use std::process;
     #[cfg(feature = "simple_writer")]
@@ -701,13 +1212,15 @@ mod tests {
     #[cfg(feature = "simple_writer")]
     use crate::encode::writer::simple::SimpleWriter;
     #[cfg(feature = "simple_writer")]
-    use crate::encode::Encode;
+    use crate::encode::{Encode, NEWLINE};
+    #[cfg(feature = "simple_writer")]
+    use crate::error_chain::ErrorChain;
 
     fn error_free(encoder: &PatternEncoder) -> bool {
-        encoder.chunks.iter().all(|c| match *c {
-            Chunk::Error(_) => false,
-            _ => true,
-        })
+        encoder
+            .chunks
+            .iter()
+            .all(|c| !matches!(*c, Chunk::Error(_)))
     }
 
     #[test]
@@ -740,6 +1253,54 @@ mod tests {
         assert_eq!(buf, &b"DEBUG the message at path in file:132"[..]);
     }
 
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn localized() {
+        use crate::encode::locale::Locale;
+        use std::sync::Arc;
+
+        let locale = Locale::builder()
+            .level(Level::Warn, "WARNUNG".to_owned())
+            .months(
+                [
+                    "Januar",
+                    "Februar",
+                    "März",
+                    "April",
+                    "Mai",
+                    "Juni",
+                    "Juli",
+                    "August",
+                    "September",
+                    "Oktober",
+                    "November",
+                    "Dezember",
+                ]
+                .map(str::to_owned),
+            )
+            .thousands_separator('.')
+            .build();
+        let pw = PatternEncoder::new("{l} {L}").locale(Arc::new(locale));
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .level(Level::Warn)
+                .line(Some(1234))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, &b"WARNUNG 1.234"[..]);
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let locale = pw.locale.as_deref();
+        assert_eq!(
+            super::localize_time_format("%d. %B %Y (%b, %%B)", &date, locale),
+            "%d. März %Y (%b, %%B)"
+        );
+        assert_eq!(super::localize_time_format("%B", &date, None), "%B");
+    }
+
     #[test]
     #[cfg(feature = "simple_writer")]
     fn unnamed_thread() {
@@ -748,7 +1309,7 @@ mod tests {
             let mut buf = vec![];
             pw.encode(&mut SimpleWriter(&mut buf), &Record::builder().build())
                 .unwrap();
-            assert_eq!(buf, b"unnamed");
+            assert_eq!(buf, crate::encode::os_thread_id().to_string().as_bytes());
         })
         .join()
         .unwrap();
@@ -806,7 +1367,10 @@ mod tests {
         pw.encode(&mut SimpleWriter(&mut buf), &Record::builder().build())
             .unwrap();
 
-        assert_eq!(buf, thread_id::get().to_string().as_bytes());
+        let tid = crate::encode::os_thread_id();
+        assert_eq!(buf, tid.to_string().as_bytes());
+        #[cfg(target_os = "linux")]
+        assert!(std::path::Path::new(&format!("/proc/self/task/{}", tid)).exists());
     }
 
     #[test]
@@ -893,6 +1457,56 @@ mod tests {
         assert_eq!(buf, b"   INFO foobar!");
     }
 
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn truncate_left() {
+        let pw = PatternEncoder::new("{t:.>12.-12}|{({h(x)}{m}):.-3}");
+
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .target("alpha::beta::Foo")
+                .args(format_args!("hello"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"a::beta::Foo|llo");
+
+        buf.clear();
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .target("Foo")
+                .args(format_args!("hi"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b".........Foo|xhi");
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn abbreviations() {
+        let pw = PatternEncoder::new("{M(1)} {M(1.)} {t(2)} {f(1)} {M(5)}");
+
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .module_path(Some("alpha::beta::Foo"))
+                .target("alpha::beta::Foo")
+                .file(Some("src/alpha/beta.rs"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"Foo a::b::Foo beta::Foo beta.rs alpha::beta::Foo");
+
+        assert!(!error_free(&PatternEncoder::new("{M(0)}")));
+        assert!(!error_free(&PatternEncoder::new("{t(x.)}")));
+        assert!(!error_free(&PatternEncoder::new("{f(1)(2)}")));
+    }
+
     #[test]
     fn custom_date_format() {
         assert!(error_free(&PatternEncoder::new(
@@ -907,6 +1521,22 @@ mod tests {
         assert!(!error_free(&PatternEncoder::new("{d(%+)(foo)}")));
     }
 
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn instance_fields() {
+        let pw = PatternEncoder::new("{host} {pid} {seq}");
+        let mut buf = vec![];
+        crate::encode::with_record_seq(42, || {
+            pw.encode(&mut SimpleWriter(&mut buf), &Record::builder().build())
+                .unwrap()
+        });
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            format!("{} {} 42", crate::encode::hostname(), process::id())
+        );
+        assert!(error_free(&PatternEncoder::new("{start}")));
+    }
+
     #[test]
     fn unescaped_parens() {
         assert!(!error_free(&PatternEncoder::new("(hi)")));
@@ -976,4 +1606,126 @@ mod tests {
 
         assert_eq!(buf, b"missing value");
     }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn key_value() {
+        let pw = PatternEncoder::new("{K(service)} {K(missing)(none)}");
+        let kvs = [("service", "api")];
+
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder().key_values(&kvs).build(),
+        )
+        .unwrap();
+
+        assert_eq!(buf, b"api none");
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn conditional() {
+        let pw = PatternEncoder::new(
+            "{?K(request_id):rid={K(request_id)} }{?K(user):[user {K(user)}] }{m}",
+        );
+
+        let mut buf = vec![];
+        let kvs = [("request_id", "42")];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .key_values(&kvs)
+                .args(format_args!("hello"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"rid=42 hello");
+
+        buf.clear();
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder().args(format_args!("hello")).build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"hello");
+
+        assert!(!error_free(&PatternEncoder::new("{?K(user)}")));
+        assert!(!error_free(&PatternEncoder::new("{?nope:x}")));
+        assert!(!error_free(&PatternEncoder::new("{?K(user):x")));
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn error_chain() {
+        let pw = PatternEncoder::new("{m}{?K(err):: {err}}");
+        let error = anyhow::anyhow!("no such\nfile").context("failed to load config");
+        let error = ErrorChain::new(&*error);
+        let kvs = [(
+            crate::error_chain::ERROR_KEY,
+            kv::Value::from_dyn_error(&error),
+        )];
+
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .args(format_args!("startup failed"))
+                .key_values(&kvs)
+                .build(),
+        )
+        .unwrap();
+        let expected = format!(
+            "startup failed: failed to load config{0}    caused by: no such{0}    file",
+            NEWLINE
+        );
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+
+        buf.clear();
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder().args(format_args!("ok")).build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"ok");
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn backtrace() {
+        let pw = PatternEncoder::new("{m}{backtrace(warn)(> )}");
+        assert!(error_free(&pw));
+        assert!(!error_free(&PatternEncoder::new("{backtrace(loud)}")));
+
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("hello"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"hello");
+
+        buf.clear();
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .level(Level::Warn)
+                .args(format_args!("hello"))
+                .build(),
+        )
+        .unwrap();
+        let output = String::from_utf8_lossy(&buf);
+        match Backtrace::capture().status() {
+            BacktraceStatus::Captured => {
+                let mut lines = output.lines();
+                assert_eq!(lines.next(), Some("hello"));
+                assert_eq!(lines.next(), Some("> backtrace:"));
+                assert!(lines.all(|line| line.starts_with("> ")));
+            }
+            _ => assert_eq!(output, "hello"),
+        }
+    }
 }
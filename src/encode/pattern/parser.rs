@@ -8,6 +8,10 @@ pub enum Piece<'a> {
         formatter: Formatter<'a>,
         parameters: Parameters,
     },
+    Conditional {
+        condition: Formatter<'a>,
+        body: Vec<Piece<'a>>,
+    },
     Error(String),
 }
 
@@ -23,6 +27,19 @@ pub struct Parameters {
     pub align: Alignment,
     pub min_width: Option<usize>,
     pub max_width: Option<usize>,
+    pub truncate: Truncate,
+}
+
+impl Default for Parameters {
+    fn default() -> Parameters {
+        Parameters {
+            fill: ' ',
+            align: Alignment::Left,
+            min_width: None,
+            max_width: None,
+            truncate: Truncate::Right,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -31,6 +48,13 @@ pub enum Alignment {
     Right,
 }
 
+/// The side output over the maximum width is cut from.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Truncate {
+    Left,
+    Right,
+}
+
 #[derive(Clone, Debug)]
 pub struct Parser<'a> {
     pattern: &'a str,
@@ -67,6 +91,25 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses a conditional section, `{?condition:body}`, after its `?`.
+    fn conditional(&mut self) -> Piece<'a> {
+        let condition = match self.formatter() {
+            Ok(formatter) => formatter,
+            Err(err) => return Piece::Error(err),
+        };
+        if !self.consume(':') {
+            return Piece::Error("expected ':'".to_owned());
+        }
+
+        let mut body = vec![];
+        loop {
+            match self.it.peek() {
+                Some(&(_, '}')) | None => return Piece::Conditional { condition, body },
+                _ => body.extend(self.next()),
+            }
+        }
+    }
+
     fn formatter(&mut self) -> Result<Formatter<'a>, String> {
         Ok(Formatter {
             name: self.name(),
@@ -121,12 +164,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parameters(&mut self) -> Parameters {
-        let mut params = Parameters {
-            fill: ' ',
-            align: Alignment::Left,
-            min_width: None,
-            max_width: None,
-        };
+        let mut params = Parameters::default();
 
         if !self.consume(':') {
             return params;
@@ -153,6 +191,9 @@ impl<'a> Parser<'a> {
         }
 
         if self.consume('.') {
+            if self.consume('-') {
+                params.truncate = Truncate::Left;
+            }
             if let Some(max_width) = self.integer() {
                 params.max_width = Some(max_width);
             }
@@ -204,7 +245,11 @@ impl<'a> Iterator for Parser<'a> {
                 if self.consume('{') {
                     Some(Piece::Text("{"))
                 } else {
-                    let piece = self.argument();
+                    let piece = if self.consume('?') {
+                        self.conditional()
+                    } else {
+                        self.argument()
+                    };
                     if self.consume('}') {
                         Some(piece)
                     } else {
@@ -0,0 +1,390 @@
+//! An encoder which hard-wraps the lines written by another encoder.
+//!
+//! Requires the `wrap_encoder` feature.
+
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::{io, str};
+
+use log::Record;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::encode::{self, Encode, Style};
+
+/// The wrap encoder's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WrapEncoderConfig {
+    encoder: Inner,
+    width: Option<usize>,
+    continuation: Option<String>,
+    charset: Option<Charset>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct Inner {
+    kind: String,
+    config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Inner {
+    fn deserialize<D>(d: D) -> Result<Inner, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(Inner {
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
+
+/// The characters a `WrapEncoder` may write.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+pub enum Charset {
+    /// Any character, encoded as UTF-8.
+    #[cfg_attr(feature = "config_parsing", serde(rename = "utf-8"))]
+    Utf8,
+    /// Only ASCII characters. Others, along with invalid UTF-8, are replaced
+    /// with `?`.
+    #[cfg_attr(feature = "config_parsing", serde(rename = "ascii"))]
+    Ascii,
+}
+
+/// An `Encode`r which breaks the lines written by another encoder so that
+/// none is longer than a maximum width, for sinks such as serial consoles.
+///
+/// Widths are counted in characters. Each line continuing a broken one starts
+/// with a continuation marker, which counts towards its width. Styles set by
+/// the inner encoder are passed through.
+#[derive(Debug)]
+pub struct WrapEncoder {
+    encoder: Box<dyn Encode>,
+    width: usize,
+    continuation: String,
+    charset: Charset,
+}
+
+impl WrapEncoder {
+    /// Creates a new `WrapEncoder` builder.
+    pub fn builder() -> WrapEncoderBuilder {
+        WrapEncoderBuilder {
+            width: 80,
+            continuation: "> ".to_owned(),
+            charset: Charset::Utf8,
+        }
+    }
+}
+
+impl Encode for WrapEncoder {
+    fn encode(&self, w: &mut dyn encode::Write, record: &Record) -> anyhow::Result<()> {
+        let mut writer = WrapWriter {
+            w,
+            encoder: self,
+            column: 0,
+            pending: vec![],
+        };
+        self.encoder.encode(&mut writer, record)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// A builder for `WrapEncoder`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct WrapEncoderBuilder {
+    width: usize,
+    continuation: String,
+    charset: Charset,
+}
+
+impl WrapEncoderBuilder {
+    /// Sets the maximum width of a line, in characters.
+    ///
+    /// Defaults to 80.
+    pub fn width(mut self, width: usize) -> WrapEncoderBuilder {
+        self.width = width;
+        self
+    }
+
+    /// Sets the marker starting each line which continues a broken one.
+    ///
+    /// Defaults to `"> "`.
+    pub fn continuation(mut self, continuation: &str) -> WrapEncoderBuilder {
+        self.continuation = continuation.to_owned();
+        self
+    }
+
+    /// Sets the characters the encoder may write.
+    ///
+    /// Defaults to `Charset::Utf8`.
+    pub fn charset(mut self, charset: Charset) -> WrapEncoderBuilder {
+        self.charset = charset;
+        self
+    }
+
+    /// Consumes the `WrapEncoderBuilder`, producing a `WrapEncoder` which
+    /// wraps the output of `encoder`.
+    pub fn build(self, encoder: Box<dyn Encode>) -> WrapEncoder {
+        let continuation = match self.charset {
+            Charset::Utf8 => self.continuation,
+            Charset::Ascii => self
+                .continuation
+                .chars()
+                .map(|c| if c.is_ascii() { c } else { '?' })
+                .collect(),
+        };
+        WrapEncoder {
+            encoder,
+            width: self.width,
+            continuation,
+            charset: self.charset,
+        }
+    }
+}
+
+struct WrapWriter<'a> {
+    w: &'a mut dyn encode::Write,
+    encoder: &'a WrapEncoder,
+    // The width of the current line so far.
+    column: usize,
+    // The start of a character split across writes.
+    pending: Vec<u8>,
+}
+
+impl<'a> WrapWriter<'a> {
+    fn write_char(&mut self, c: char) -> io::Result<()> {
+        match c {
+            '\n' | '\r' => self.column = 0,
+            _ => {
+                // Every line holds at least one character after the
+                // continuation marker, even if the marker fills the width.
+                if self.column >= self.encoder.width {
+                    self.w.write_all(encode::NEWLINE.as_bytes())?;
+                    self.w.write_all(self.encoder.continuation.as_bytes())?;
+                    self.column = self.encoder.continuation.chars().count();
+                }
+                self.column += 1;
+            }
+        }
+
+        let c = match self.encoder.charset {
+            Charset::Ascii if !c.is_ascii() => '?',
+            _ => c,
+        };
+        self.w.write_all(c.encode_utf8(&mut [0; 4]).as_bytes())
+    }
+
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        for c in s.chars() {
+            self.write_char(c)?;
+        }
+        Ok(())
+    }
+
+    /// Writes out a partial character left at the end of the record.
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.write_char(char::REPLACEMENT_CHARACTER)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> io::Write for WrapWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(buf);
+
+        let mut rest = &bytes[..];
+        loop {
+            match str::from_utf8(rest) {
+                Ok(s) => {
+                    self.write_str(s)?;
+                    break;
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    // Safe to unwrap, as `valid_up_to` ends at a character.
+                    self.write_str(str::from_utf8(valid).unwrap())?;
+                    match e.error_len() {
+                        Some(len) => {
+                            self.write_char(char::REPLACEMENT_CHARACTER)?;
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            self.pending = invalid.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+impl<'a> encode::Write for WrapWriter<'a> {
+    fn set_style(&mut self, style: &Style) -> io::Result<()> {
+        self.w.set_style(style)
+    }
+}
+
+/// A deserializer for the `WrapEncoder`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: wrap
+///
+/// # The encoder whose output is wrapped. Required.
+/// encoder:
+///
+///   # Identifies which encoder is to be used. Required.
+///   kind: pattern
+///
+///   # The remainder of the configuration is passed to the encoder's
+///   # deserializer, and will vary based on the kind of encoder.
+///   pattern: "{d} {l} {t} - {m}{n}"
+///
+/// # The maximum width of a line, in characters. Defaults to 80.
+/// width: 80
+///
+/// # The marker starting each line which continues a broken one. Defaults to
+/// # "> ".
+/// continuation: "> "
+///
+/// # The characters which may be written, either "utf-8" or "ascii". With
+/// # "ascii", other characters are replaced with "?". Defaults to "utf-8".
+/// charset: ascii
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct WrapEncoderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for WrapEncoderDeserializer {
+    type Trait = dyn Encode;
+
+    type Config = WrapEncoderConfig;
+
+    fn deserialize(
+        &self,
+        config: WrapEncoderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Encode>> {
+        let encoder = deserializers.deserialize(&config.encoder.kind, config.encoder.config)?;
+        let mut builder = WrapEncoder::builder();
+        if let Some(width) = config.width {
+            builder = builder.width(width);
+        }
+        if let Some(continuation) = config.continuation {
+            builder = builder.continuation(&continuation);
+        }
+        if let Some(charset) = config.charset {
+            builder = builder.charset(charset);
+        }
+        Ok(Box::new(builder.build(encoder)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug)]
+    struct MessageEncoder;
+
+    impl Encode for MessageEncoder {
+        fn encode(&self, w: &mut dyn encode::Write, record: &Record) -> anyhow::Result<()> {
+            // Written a byte at a time to split characters across writes.
+            for byte in record.args().to_string().bytes() {
+                w.write_all(&[byte])?;
+            }
+            Ok(())
+        }
+    }
+
+    struct Buffer(Vec<u8>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl encode::Write for Buffer {}
+
+    fn encode(encoder: &WrapEncoder, message: &str) -> String {
+        let mut buf = Buffer(vec![]);
+        encoder
+            .encode(
+                &mut buf,
+                &Record::builder().args(format_args!("{}", message)).build(),
+            )
+            .unwrap();
+        String::from_utf8(buf.0).unwrap()
+    }
+
+    #[test]
+    fn wrap() {
+        let encoder = WrapEncoder::builder()
+            .width(6)
+            .build(Box::new(MessageEncoder));
+        assert_eq!(encode(&encoder, "short\n"), "short\n");
+        assert_eq!(
+            encode(&encoder, "0123456789\nabcdéfghijk"),
+            format!(
+                "012345{n}> 6789\nabcdéf{n}> ghij{n}> k",
+                n = encode::NEWLINE
+            )
+        );
+
+        let encoder = WrapEncoder::builder()
+            .width(2)
+            .continuation("... ")
+            .build(Box::new(MessageEncoder));
+        assert_eq!(
+            encode(&encoder, "abcd"),
+            format!("ab{n}... c{n}... d", n = encode::NEWLINE)
+        );
+    }
+
+    #[test]
+    fn ascii() {
+        let encoder = WrapEncoder::builder()
+            .width(4)
+            .continuation("» ")
+            .charset(Charset::Ascii)
+            .build(Box::new(MessageEncoder));
+        assert_eq!(
+            encode(&encoder, "día über"),
+            format!("d?a {n}? ?b{n}? er", n = encode::NEWLINE)
+        );
+    }
+}
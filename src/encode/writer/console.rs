@@ -27,7 +27,7 @@ impl ConsoleWriter {
     }
 
     /// Locks the console, preventing other threads from writing concurrently.
-    pub fn lock(&self) -> ConsoleWriterLock {
+    pub fn lock(&self) -> ConsoleWriterLock<'_> {
         ConsoleWriterLock(self.0.lock())
     }
 }
@@ -111,7 +111,7 @@ mod imp {
             Some(Writer(AnsiWriter(StdWriter::stderr())))
         }
 
-        pub fn lock(&self) -> WriterLock {
+        pub fn lock(&self) -> WriterLock<'_> {
             WriterLock(AnsiWriter((self.0).0.lock()))
         }
     }
@@ -0,0 +1,132 @@
+//! The age encryptor.
+//!
+//! Requires the `age_encryption` feature.
+
+use std::{fmt, io::Write};
+
+use ::age::{stream::StreamWriter, x25519::Recipient};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::encrypt::{Encrypt, Encryptor};
+
+/// Configuration for the age encryptor.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgeEncryptorConfig {
+    recipient: String,
+}
+
+/// An `Encryptor` writing the [age](https://age-encryption.org) format,
+/// encrypted to an X25519 recipient.
+///
+/// Only the public key of the recipient is needed to encrypt, so a
+/// compromised machine cannot decrypt the archives it has written. They are
+/// decrypted elsewhere with the recipient's identity, for example with
+/// `age --decrypt -i key.txt app.0.log.gz.age`.
+#[derive(Clone)]
+pub struct AgeEncryptor {
+    recipient: Recipient,
+}
+
+impl fmt::Debug for AgeEncryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AgeEncryptor")
+            .field("recipient", &self.recipient.to_string())
+            .finish()
+    }
+}
+
+impl AgeEncryptor {
+    /// Creates a new `AgeEncryptor` encrypting to the recipient with the
+    /// specified public key, such as `age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p`.
+    pub fn new(recipient: &str) -> anyhow::Result<AgeEncryptor> {
+        let recipient = recipient
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid age recipient `{}`: {}", recipient, e))?;
+        Ok(AgeEncryptor { recipient })
+    }
+}
+
+impl Encryptor for AgeEncryptor {
+    fn encrypt<'a>(&self, dst: &'a mut dyn Write) -> anyhow::Result<Box<dyn Encrypt + 'a>> {
+        let encryptor = ::age::Encryptor::with_recipients(vec![Box::new(self.recipient.clone())])
+            .expect("a recipient is given");
+        Ok(Box::new(encryptor.wrap_output(dst)?))
+    }
+}
+
+impl<W: Write> Encrypt for StreamWriter<W> {
+    fn finish(self: Box<Self>) -> anyhow::Result<()> {
+        StreamWriter::finish(*self)?;
+        Ok(())
+    }
+}
+
+/// A deserializer for the `AgeEncryptor`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: age
+///
+/// # The public key of the recipient the output is encrypted to. Required.
+/// recipient: age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct AgeEncryptorDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for AgeEncryptorDeserializer {
+    type Trait = dyn Encryptor;
+
+    type Config = AgeEncryptorConfig;
+
+    fn deserialize(
+        &self,
+        config: AgeEncryptorConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Encryptor>> {
+        Ok(Box::new(AgeEncryptor::new(&config.recipient)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use ::age::{x25519::Identity, Decryptor};
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let identity = Identity::generate();
+        let encryptor = AgeEncryptor::new(&identity.to_public().to_string()).unwrap();
+
+        let mut encrypted = vec![];
+        let mut writer = encryptor.encrypt(&mut encrypted).unwrap();
+        writer.write_all(b"secret records").unwrap();
+        writer.finish().unwrap();
+        assert!(!encrypted.windows(b"secret".len()).any(|w| w == b"secret"));
+
+        let decryptor = match Decryptor::new(&encrypted[..]).unwrap() {
+            Decryptor::Recipients(decryptor) => decryptor,
+            _ => panic!("not encrypted to recipients"),
+        };
+        let mut decrypted = vec![];
+        decryptor
+            .decrypt(std::iter::once(&identity as &dyn ::age::Identity))
+            .unwrap()
+            .read_to_end(&mut decrypted)
+            .unwrap();
+        assert_eq!(decrypted, b"secret records");
+    }
+
+    #[test]
+    fn invalid_recipient() {
+        assert!(AgeEncryptor::new("age1nope").is_err());
+    }
+}
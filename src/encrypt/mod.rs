@@ -0,0 +1,75 @@
+//! Encryptors
+//!
+//! An `Encryptor` encrypts the output of a component, such as the archives
+//! written by the rollers, so that logs are encrypted at rest. Custom
+//! encryptors can be registered with `Deserializers` under the `encryptor`
+//! trait.
+
+use std::{fmt, io::Write};
+
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::Deserializable;
+
+#[cfg(feature = "age_encryption")]
+pub mod age;
+
+/// A trait implemented by encryption schemes.
+pub trait Encryptor: fmt::Debug + Send + Sync + 'static {
+    /// Returns a writer which encrypts everything written to it, writing the
+    /// result to `dst`.
+    fn encrypt<'a>(&self, dst: &'a mut dyn Write) -> anyhow::Result<Box<dyn Encrypt + 'a>>;
+}
+
+/// A writer returned by an `Encryptor`.
+pub trait Encrypt: Write {
+    /// Completes the encrypted stream.
+    ///
+    /// The stream is incomplete, and cannot be decrypted, unless this method
+    /// returns successfully.
+    fn finish(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+#[cfg(feature = "config_parsing")]
+impl Deserializable for dyn Encryptor {
+    fn name() -> &'static str {
+        "encryptor"
+    }
+}
+
+/// Configuration for an encryptor.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct EncryptorConfig {
+    /// The encryptor's kind.
+    pub kind: String,
+
+    /// The encryptor's configuration.
+    pub config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> de::Deserialize<'de> for EncryptorConfig {
+    fn deserialize<D>(d: D) -> Result<EncryptorConfig, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(EncryptorConfig {
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
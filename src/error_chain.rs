@@ -0,0 +1,238 @@
+//! Errors attached to records.
+//!
+//! An error attached to a record under the `err` key is rendered with its
+//! full chain of causes by the pattern encoder's `{err}` formatter and the
+//! JSON encoder's `error` field, rather than as the single line its `Display`
+//! implementation gives. The `log_error!` macro logs a record with an error
+//! attached, along with the backtrace of an `anyhow::Error` if it captured
+//! one:
+//!
+//! ```
+//! let err = std::fs::read("missing.toml").unwrap_err();
+//! log4rs::log_error!(err, "failed to read {}", "missing.toml");
+//! ```
+//!
+//! Errors attached with the `log` macros, as in
+//! `log::warn!(err:err = e; "retrying")`, are rendered the same way, without
+//! a backtrace.
+
+use log::{kv, Level, Metadata, Record};
+use std::{backtrace::BacktraceStatus, error::Error, fmt};
+
+/// The key of the key-value field holding an error attached to a record.
+pub const ERROR_KEY: &str = "err";
+
+/// An error, its causes and its backtrace, captured so that they can be
+/// attached to a record.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ErrorChain {
+    messages: Vec<String>,
+    backtrace: Option<String>,
+}
+
+impl ErrorChain {
+    /// Captures an error and the chain of errors which caused it.
+    pub fn new<E: Error + ?Sized>(error: &E) -> ErrorChain {
+        let mut messages = vec![error.to_string()];
+        let mut source = error.source();
+        while let Some(error) = source {
+            messages.push(error.to_string());
+            source = error.source();
+        }
+        ErrorChain {
+            messages,
+            backtrace: None,
+        }
+    }
+
+    /// Returns the messages of the error and each of its causes in turn.
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    /// Returns the backtrace of the error, if one was captured.
+    pub fn backtrace(&self) -> Option<&str> {
+        self.backtrace.as_deref()
+    }
+
+    /// Returns the error attached to a record, if there is one.
+    #[cfg(any(feature = "pattern_encoder", feature = "json_encoder"))]
+    pub(crate) fn attached(record: &Record) -> Option<ErrorChain> {
+        let value = record.key_values().get(kv::Key::from_str(ERROR_KEY))?;
+        Some(match value.to_borrowed_error() {
+            Some(error) => match error.downcast_ref::<ErrorChain>() {
+                Some(chain) => chain.clone(),
+                None => ErrorChain::new(error),
+            },
+            None => ErrorChain {
+                messages: vec![value.to_string()],
+                backtrace: None,
+            },
+        })
+    }
+}
+
+impl From<&anyhow::Error> for ErrorChain {
+    fn from(error: &anyhow::Error) -> ErrorChain {
+        let backtrace = error.backtrace();
+        ErrorChain {
+            messages: error.chain().map(ToString::to_string).collect(),
+            backtrace: match backtrace.status() {
+                BacktraceStatus::Captured => Some(backtrace.to_string()),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ErrorChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.messages[0])
+    }
+}
+
+impl Error for ErrorChain {}
+
+/// Logs a record at the `Error` level with an error attached under the `err`
+/// key.
+///
+/// The error may be an `anyhow::Error`, whose backtrace is attached if it
+/// captured one, or any `std::error::Error`. A target may be given first, as
+/// with the `log` macros.
+///
+/// ```
+/// # fn load() -> anyhow::Result<()> { anyhow::bail!("no config") }
+/// if let Err(err) = load() {
+///     log4rs::log_error!(target: "config", err, "failed to load config");
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_error {
+    (target: $target:expr, $err:expr, $($arg:tt)+) => {{
+        #[allow(unused_imports)]
+        use $crate::error_chain::__private::{AnyhowKind as _, StdKind as _};
+        let target = $target;
+        if $crate::error_chain::__private::enabled(target) {
+            $crate::error_chain::__private::log(
+                &(&$err).error_chain(),
+                target,
+                &(::std::module_path!(), ::std::file!(), ::std::line!()),
+                ::std::format_args!($($arg)+),
+            );
+        }
+    }};
+    ($err:expr, $($arg:tt)+) => {
+        $crate::log_error!(target: ::std::module_path!(), $err, $($arg)+)
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    // `(&err).error_chain()` resolves to `AnyhowKind` for an `anyhow::Error`,
+    // and to `StdKind` through autoref for any other error.
+    pub trait AnyhowKind {
+        fn error_chain(&self) -> ErrorChain;
+    }
+
+    impl AnyhowKind for anyhow::Error {
+        fn error_chain(&self) -> ErrorChain {
+            ErrorChain::from(self)
+        }
+    }
+
+    pub trait StdKind {
+        fn error_chain(&self) -> ErrorChain;
+    }
+
+    impl<E: Error + ?Sized> StdKind for &E {
+        fn error_chain(&self) -> ErrorChain {
+            ErrorChain::new(*self)
+        }
+    }
+
+    pub fn enabled(target: &str) -> bool {
+        Level::Error <= log::max_level()
+            && log::logger().enabled(
+                &Metadata::builder()
+                    .level(Level::Error)
+                    .target(target)
+                    .build(),
+            )
+    }
+
+    pub fn log(
+        error: &ErrorChain,
+        target: &str,
+        &(module_path, file, line): &(&'static str, &'static str, u32),
+        args: fmt::Arguments,
+    ) {
+        let key_values = [(ERROR_KEY, kv::Value::from_dyn_error(error))];
+        log::logger().log(
+            &Record::builder()
+                .level(Level::Error)
+                .target(target)
+                .module_path_static(Some(module_path))
+                .file_static(Some(file))
+                .line(Some(line))
+                .args(args)
+                .key_values(&key_values)
+                .build(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Outer(io::Error);
+
+    impl fmt::Display for Outer {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("failed to load config")
+        }
+    }
+
+    impl Error for Outer {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)] // as `log_error!` calls it
+    fn chains() {
+        use self::__private::{AnyhowKind, StdKind};
+
+        let error = Outer(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        let chain = (&error).error_chain();
+        assert_eq!(chain.messages(), ["failed to load config", "no such file"]);
+
+        let error = anyhow::Error::new(error).context("startup failed");
+        let chain = (&error).error_chain();
+        assert_eq!(
+            chain.messages(),
+            ["startup failed", "failed to load config", "no such file"]
+        );
+    }
+
+    #[test]
+    #[cfg(any(feature = "pattern_encoder", feature = "json_encoder"))]
+    fn attached() {
+        let error = ErrorChain::new(&Outer(io::Error::new(io::ErrorKind::Other, "cause")));
+        let key_values = [(ERROR_KEY, kv::Value::from_dyn_error(&error))];
+        let record = Record::builder().key_values(&key_values).build();
+        assert_eq!(ErrorChain::attached(&record), Some(error));
+
+        let key_values = [(ERROR_KEY, "plain")];
+        let record = Record::builder().key_values(&key_values).build();
+        assert_eq!(ErrorChain::attached(&record).unwrap().messages(), ["plain"]);
+
+        assert_eq!(ErrorChain::attached(&Record::builder().build()), None);
+    }
+}
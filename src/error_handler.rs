@@ -0,0 +1,108 @@
+//! Error handlers.
+//!
+//! An error handler receives the errors log4rs runs into while logging, such
+//! as an appender failing to write a record, which cannot be returned to the
+//! code that logged. By default they are printed to stderr.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+/// A trait implemented by error handlers.
+pub trait ErrorHandler: fmt::Debug + Send + Sync + 'static {
+    /// Handles an error.
+    fn handle(&self, error: &anyhow::Error);
+}
+
+/// An error handler which prints errors to stderr.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct StderrErrorHandler;
+
+impl ErrorHandler for StderrErrorHandler {
+    fn handle(&self, error: &anyhow::Error) {
+        let _ = writeln!(io::stderr(), "log4rs: {}", error);
+    }
+}
+
+/// An error handler which ignores errors.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct IgnoreErrorHandler;
+
+impl ErrorHandler for IgnoreErrorHandler {
+    fn handle(&self, _: &anyhow::Error) {}
+}
+
+/// An error handler which panics on errors.
+///
+/// Useful in tests, where a logging failure should fail the test.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct PanicErrorHandler;
+
+impl ErrorHandler for PanicErrorHandler {
+    fn handle(&self, error: &anyhow::Error) {
+        panic!("log4rs: {}", error);
+    }
+}
+
+/// An error handler which counts errors, for example to export them as a
+/// metric.
+///
+/// Keep a clone of the `Arc` it is installed with to read the count.
+#[derive(Debug, Default)]
+pub struct CountingErrorHandler {
+    count: AtomicU64,
+}
+
+impl CountingErrorHandler {
+    /// Creates a new `CountingErrorHandler`.
+    pub fn new() -> CountingErrorHandler {
+        CountingErrorHandler::default()
+    }
+
+    /// Returns the number of errors handled.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+impl ErrorHandler for CountingErrorHandler {
+    fn handle(&self, _: &anyhow::Error) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Adapts the closures accepted by `Logger::new_with_err_handler`.
+pub(crate) struct FnErrorHandler(pub(crate) Box<dyn Send + Sync + Fn(&anyhow::Error)>);
+
+impl fmt::Debug for FnErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnErrorHandler").finish()
+    }
+}
+
+impl ErrorHandler for FnErrorHandler {
+    fn handle(&self, error: &anyhow::Error) {
+        (self.0)(error)
+    }
+}
+
+// The handler of the global logger, which also receives errors from outside
+// of logging calls such as config file reloads.
+static GLOBAL: RwLock<Option<Arc<dyn ErrorHandler>>> = RwLock::new(None);
+
+pub(crate) fn set_global(handler: Arc<dyn ErrorHandler>) {
+    *GLOBAL.write().unwrap_or_else(|e| e.into_inner()) = Some(handler);
+}
+
+pub(crate) fn handle_global(error: &anyhow::Error) {
+    let handler = GLOBAL.read().unwrap_or_else(|e| e.into_inner()).clone();
+    match handler {
+        Some(handler) => handler.handle(error),
+        None => StderrErrorHandler.handle(error),
+    }
+}
@@ -0,0 +1,167 @@
+use log::{Level, LevelFilter};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use crate::config::Escalation;
+
+/// The runtime state of an `Escalation`.
+#[derive(Debug)]
+pub(crate) struct Escalator {
+    escalation: Escalation,
+    // Mirrors `State::until.is_some()` so the logging path can skip the lock.
+    active: AtomicBool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    events: VecDeque<Instant>,
+    until: Option<Instant>,
+    ready_at: Option<Instant>,
+    activations: u64,
+}
+
+impl Escalator {
+    pub(crate) fn new(escalation: Escalation) -> Escalator {
+        Escalator {
+            escalation,
+            active: AtomicBool::new(false),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Counts a logged record, returning true if it started the escalation.
+    pub(crate) fn observe(&self, level: Level) -> bool {
+        if level > self.escalation.trigger() || self.active.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.until.is_some()
+            || state.ready_at.map_or(false, |ready_at| now < ready_at)
+            || self
+                .escalation
+                .max_activations()
+                .map_or(false, |max| state.activations >= max)
+        {
+            return false;
+        }
+
+        let window = self.escalation.window();
+        while let Some(&event) = state.events.front() {
+            if now.duration_since(event) < window {
+                break;
+            }
+            state.events.pop_front();
+        }
+        state.events.push_back(now);
+        if state.events.len() < self.escalation.threshold() {
+            return false;
+        }
+
+        state.events.clear();
+        state.until = Some(now + self.escalation.duration());
+        state.activations += 1;
+        self.active.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Ends the escalation if its duration has passed, returning true if it
+    /// ended.
+    pub(crate) fn expire(&self) -> bool {
+        if !self.active.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.until {
+            Some(until) if until <= now => {
+                state.until = None;
+                state.ready_at = Some(now + self.escalation.cooldown());
+                self.active.store(false, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if the escalation is active and lets records at `level`
+    /// for `target` through.
+    pub(crate) fn allows(&self, target: &str, level: Level) -> bool {
+        self.active.load(Ordering::Relaxed)
+            && level <= self.escalation.level()
+            && self.covers(target)
+    }
+
+    /// Returns the level the escalation raises loggers to while it is
+    /// active.
+    pub(crate) fn level(&self) -> LevelFilter {
+        if self.active.load(Ordering::Relaxed) {
+            self.escalation.level()
+        } else {
+            LevelFilter::Off
+        }
+    }
+
+    fn covers(&self, target: &str) -> bool {
+        let targets = self.escalation.targets();
+        targets.is_empty()
+            || targets.iter().any(|name| {
+                target
+                    .strip_prefix(name.as_str())
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn escalate() {
+        let escalator = Escalator::new(
+            Escalation::builder()
+                .threshold(2)
+                .target("a::b")
+                .max_activations(2)
+                .build(Duration::from_millis(50)),
+        );
+
+        assert!(!escalator.observe(Level::Warn));
+        assert!(!escalator.observe(Level::Error));
+        assert!(!escalator.allows("a::b", Level::Debug));
+        assert!(escalator.observe(Level::Error));
+        assert!(escalator.allows("a::b", Level::Debug));
+        assert!(escalator.allows("a::b::c", Level::Debug));
+        assert!(!escalator.allows("a::bc", Level::Debug));
+        assert!(!escalator.allows("a", Level::Debug));
+        assert!(!escalator.allows("a::b", Level::Trace));
+        assert_eq!(escalator.level(), LevelFilter::Debug);
+        assert!(!escalator.expire());
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(escalator.expire());
+        assert!(!escalator.allows("a::b", Level::Debug));
+        assert_eq!(escalator.level(), LevelFilter::Off);
+
+        assert!(!escalator.observe(Level::Error));
+        assert!(escalator.observe(Level::Error));
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(escalator.expire());
+        assert!(!escalator.observe(Level::Error));
+        assert!(!escalator.observe(Level::Error));
+        assert_eq!(escalator.level(), LevelFilter::Off);
+    }
+}
@@ -0,0 +1,211 @@
+//! The duplicate suppression filter.
+//!
+//! Requires the `dedup_filter` feature.
+
+use fnv::FnvHasher;
+use std::{
+    fmt::{self, Write},
+    hash::Hasher,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::{Level, Record};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::filter::{summary, Filter, Response};
+
+/// The duplicate suppression filter's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DedupFilterConfig {
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    window: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Last {
+    level: Level,
+    target: String,
+    // A hash of the formatted message.
+    message: u64,
+    at: Instant,
+    repeated: u64,
+}
+
+/// A filter that rejects consecutive records with the same message and target.
+///
+/// A record is considered a duplicate if it arrives within the window of the
+/// last copy of it which was let through. Once a different record arrives, or
+/// the window expires, a "last message repeated N times" record is written
+/// before it, at the level of the repeated record, to the appender the filter
+/// belongs to. Messages are compared by a hash of their text, so that they
+/// are not formatted into strings.
+#[derive(Debug)]
+pub struct DedupFilter {
+    window: Duration,
+    last: Mutex<Option<Last>>,
+}
+
+impl DedupFilter {
+    /// Creates a new `DedupFilter` suppressing duplicates within `window`.
+    pub fn new(window: Duration) -> DedupFilter {
+        DedupFilter {
+            window,
+            last: Mutex::default(),
+        }
+    }
+
+    /// Returns the response for the record, along with the level, target and
+    /// repeat count of the run of duplicates it ended, if any.
+    fn check(&self, record: &Record, now: Instant) -> (Response, Option<(Level, String, u64)>) {
+        let message = hash_message(record.args());
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(ref mut last) = *last {
+            if last.message == message
+                && last.target == record.target()
+                && now.duration_since(last.at) < self.window
+            {
+                last.repeated += 1;
+                return (Response::Reject, None);
+            }
+        }
+
+        let ended = last
+            .take()
+            .filter(|last| last.repeated > 0)
+            .map(|last| (last.level, last.target, last.repeated));
+        *last = Some(Last {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message,
+            at: now,
+            repeated: 0,
+        });
+        (Response::Neutral, ended)
+    }
+}
+
+// Hashes a message as it is formatted.
+fn hash_message(args: &fmt::Arguments) -> u64 {
+    struct HashWriter(FnvHasher);
+
+    impl Write for HashWriter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0.write(s.as_bytes());
+            Ok(())
+        }
+    }
+
+    let mut writer = HashWriter(FnvHasher::default());
+    let _ = writer.write_fmt(*args);
+    writer.0.finish()
+}
+
+impl Filter for DedupFilter {
+    fn filter(&self, record: &Record) -> Response {
+        let (response, ended) = self.check(record, Instant::now());
+        if let Some((level, target, repeated)) = ended {
+            summary::add(
+                level,
+                &target,
+                format_args!("last message repeated {} times", repeated),
+            );
+        }
+        response
+    }
+}
+
+/// A deserializer for the `DedupFilter`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: dedup
+///
+/// # Consecutive copies of a record within this window of the last copy
+/// # written are dropped. Defaults to 30 seconds.
+/// window: 1 minute
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DedupFilterDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for DedupFilterDeserializer {
+    type Trait = dyn Filter;
+
+    type Config = DedupFilterConfig;
+
+    fn deserialize(
+        &self,
+        config: DedupFilterConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Filter>> {
+        let window = config.window.unwrap_or(Duration::from_secs(30));
+        Ok(Box::new(DedupFilter::new(window)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(
+        filter: &DedupFilter,
+        message: &str,
+        now: Instant,
+    ) -> (Response, Option<(Level, String, u64)>) {
+        filter.check(
+            &Record::builder()
+                .level(Level::Error)
+                .target("app")
+                .args(format_args!("{}", message))
+                .build(),
+            now,
+        )
+    }
+
+    #[test]
+    fn suppresses_consecutive_duplicates() {
+        let filter = DedupFilter::new(Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert_eq!(check(&filter, "a", start), (Response::Neutral, None));
+        assert_eq!(check(&filter, "a", start), (Response::Reject, None));
+        assert_eq!(check(&filter, "a", start), (Response::Reject, None));
+        assert_eq!(
+            check(&filter, "b", start),
+            (Response::Neutral, Some((Level::Error, "app".to_owned(), 2)))
+        );
+        assert_eq!(check(&filter, "a", start), (Response::Neutral, None));
+    }
+
+    #[test]
+    fn hashes_formatted_message() {
+        assert_eq!(
+            hash_message(&format_args!("{}{}", "a", 1)),
+            hash_message(&format_args!("a1"))
+        );
+        assert_ne!(
+            hash_message(&format_args!("a1")),
+            hash_message(&format_args!("a2"))
+        );
+    }
+
+    #[test]
+    fn window_expires() {
+        let filter = DedupFilter::new(Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert_eq!(check(&filter, "a", start), (Response::Neutral, None));
+        assert_eq!(check(&filter, "a", start), (Response::Reject, None));
+        assert_eq!(
+            check(&filter, "a", start + Duration::from_secs(1)),
+            (Response::Neutral, Some((Level::Error, "app".to_owned(), 1)))
+        );
+    }
+}
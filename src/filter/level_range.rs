@@ -0,0 +1,133 @@
+//! The level range filter.
+//!
+//! Requires the `level_range_filter` feature.
+
+use log::{Level, Record};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::filter::{Filter, Response};
+
+/// The level range filter's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelRangeFilterConfig {
+    min: Option<Level>,
+    max: Option<Level>,
+}
+
+/// A filter that rejects all events at a level outside of a provided range.
+///
+/// The range is inclusive at both ends and is expressed in terms of severity:
+/// `min` is the least severe level passed on and `max` the most severe, so a
+/// range from `Warn` to `Error` passes on only warnings and errors.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct LevelRangeFilter {
+    min: Level,
+    max: Level,
+}
+
+impl LevelRangeFilter {
+    /// Creates a new `LevelRangeFilter` passing on events from `min` up to
+    /// and including `max`.
+    ///
+    /// If `max` is less severe than `min`, all events are rejected.
+    pub fn new(min: Level, max: Level) -> LevelRangeFilter {
+        LevelRangeFilter { min, max }
+    }
+}
+
+impl Filter for LevelRangeFilter {
+    fn filter(&self, record: &Record) -> Response {
+        // `Level` orders from most severe (`Error`) to least severe (`Trace`).
+        if record.level() > self.min || record.level() < self.max {
+            Response::Reject
+        } else {
+            Response::Neutral
+        }
+    }
+}
+
+/// A deserializer for the `LevelRangeFilter`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: level_range
+///
+/// # The least severe log level to pass on. Defaults to `trace`.
+/// min: warn
+///
+/// # The most severe log level to pass on. Defaults to `error`.
+/// max: error
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct LevelRangeFilterDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for LevelRangeFilterDeserializer {
+    type Trait = dyn Filter;
+
+    type Config = LevelRangeFilterConfig;
+
+    fn deserialize(
+        &self,
+        config: LevelRangeFilterConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Filter>> {
+        let min = config.min.unwrap_or(Level::Trace);
+        let max = config.max.unwrap_or(Level::Error);
+        if max > min {
+            return Err(anyhow::anyhow!(
+                "`max` level {} is less severe than `min` level {}",
+                max,
+                min
+            ));
+        }
+        Ok(Box::new(LevelRangeFilter::new(min, max)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn passed(filter: &LevelRangeFilter) -> Vec<Level> {
+        [
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ]
+        .iter()
+        .copied()
+        .filter(|&level| {
+            let record = Record::builder()
+                .level(level)
+                .args(format_args!("x"))
+                .build();
+            filter.filter(&record) == Response::Neutral
+        })
+        .collect()
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(
+            passed(&LevelRangeFilter::new(Level::Warn, Level::Error)),
+            [Level::Error, Level::Warn]
+        );
+        assert_eq!(
+            passed(&LevelRangeFilter::new(Level::Debug, Level::Debug)),
+            [Level::Debug]
+        );
+        assert_eq!(
+            passed(&LevelRangeFilter::new(Level::Trace, Level::Info)),
+            [Level::Info, Level::Debug, Level::Trace]
+        );
+        assert!(passed(&LevelRangeFilter::new(Level::Error, Level::Warn)).is_empty());
+    }
+}
@@ -7,13 +7,27 @@ use serde::de;
 use serde_value::Value;
 #[cfg(feature = "config_parsing")]
 use std::collections::BTreeMap;
-use std::fmt;
+use std::{fmt, time::Duration};
 
 #[cfg(feature = "config_parsing")]
 use crate::config::Deserializable;
 
+#[cfg(feature = "dedup_filter")]
+pub mod dedup;
+#[cfg(feature = "level_range_filter")]
+pub mod level_range;
+#[cfg(feature = "rate_limit_filter")]
+pub mod rate_limit;
+#[cfg(feature = "regex_filter")]
+pub mod regex;
+#[cfg(feature = "sample_filter")]
+pub mod sample;
 #[cfg(feature = "threshold_filter")]
 pub mod threshold;
+#[cfg(feature = "time_window_filter")]
+pub mod time_window;
+
+pub(crate) mod summary;
 
 /// The trait implemented by log4rs filters.
 ///
@@ -22,6 +36,19 @@ pub mod threshold;
 pub trait Filter: fmt::Debug + Send + Sync + 'static {
     /// Filters a log event.
     fn filter(&self, record: &Record) -> Response;
+
+    /// Returns how often `sweep` is to be called, if ever.
+    ///
+    /// Defaults to never.
+    fn sweep_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Lets the filter close the windows it keeps, even while no log events
+    /// reach it.
+    ///
+    /// Defaults to doing nothing.
+    fn sweep(&self) {}
 }
 
 #[cfg(feature = "config_parsing")]
@@ -32,6 +59,7 @@ impl Deserializable for dyn Filter {
 }
 
 /// The response returned by a filter.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Response {
     /// Accept the log event.
     ///
@@ -0,0 +1,403 @@
+//! The rate limit filter.
+//!
+//! Requires the `rate_limit_filter` feature.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::{Level, Record};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::filter::{summary, Filter, Response};
+
+/// The rate limit filter's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitFilterConfig {
+    limit: u32,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    window: Option<Duration>,
+    #[serde(default)]
+    summary: bool,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    count: u32,
+    suppressed: u64,
+}
+
+#[derive(Debug)]
+struct Windows {
+    // The window of each target, by level.
+    levels: [HashMap<String, Window>; 5],
+    swept: Instant,
+}
+
+/// A filter that allows at most a fixed number of records per time window for
+/// each target and level, rejecting the rest.
+///
+/// If summaries are enabled, a warning of the form "suppressed N messages" is
+/// logged for a target and level once a window in which records were
+/// suppressed closes, even if the target logs nothing further. The summary is
+/// written to the appender the filter belongs to, bypassing its filters.
+///
+/// Closed windows are forgotten once per window, so that targets which stop
+/// logging do not hold on to memory.
+#[derive(Debug)]
+pub struct RateLimitFilter {
+    limit: u32,
+    window: Duration,
+    summary: bool,
+    windows: Mutex<Windows>,
+}
+
+impl RateLimitFilter {
+    /// Creates a new `RateLimitFilter` allowing `limit` records per `window`.
+    pub fn new(limit: u32, window: Duration) -> RateLimitFilter {
+        RateLimitFilter {
+            limit,
+            window,
+            summary: false,
+            windows: Mutex::new(Windows {
+                levels: Default::default(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    /// Sets whether a summary of the suppressed records is logged once a
+    /// window closes.
+    ///
+    /// Defaults to `false`.
+    pub fn summary(mut self, summary: bool) -> RateLimitFilter {
+        self.summary = summary;
+        self
+    }
+
+    /// Returns the response for the record, along with the windows which
+    /// closed having suppressed records.
+    fn check(&self, record: &Record, now: Instant) -> (Response, Vec<Suppressed>) {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let mut closed = vec![];
+        if now.duration_since(windows.swept) >= self.window {
+            closed = self.sweep_windows(&mut windows, now);
+        }
+
+        let targets = &mut windows.levels[record.level() as usize - 1];
+        if !targets.contains_key(record.target()) {
+            let window = Window {
+                start: now,
+                count: 0,
+                suppressed: 0,
+            };
+            targets.insert(record.target().to_owned(), window);
+        }
+        let window = targets.get_mut(record.target()).unwrap();
+
+        if now.duration_since(window.start) >= self.window {
+            if window.suppressed > 0 {
+                closed.push(Suppressed {
+                    level: record.level(),
+                    target: record.target().to_owned(),
+                    count: window.suppressed,
+                });
+            }
+            *window = Window {
+                start: now,
+                count: 0,
+                suppressed: 0,
+            };
+        }
+
+        if window.count < self.limit {
+            window.count += 1;
+            (Response::Neutral, closed)
+        } else {
+            window.suppressed += 1;
+            (Response::Reject, closed)
+        }
+    }
+
+    // Forgets the windows which have closed, returning those which suppressed
+    // records.
+    fn sweep_windows(&self, windows: &mut Windows, now: Instant) -> Vec<Suppressed> {
+        let mut closed = vec![];
+        for (targets, &level) in windows.levels.iter_mut().zip(LEVELS.iter()) {
+            targets.retain(|target, window| {
+                if now.duration_since(window.start) < self.window {
+                    return true;
+                }
+                if window.suppressed > 0 {
+                    closed.push(Suppressed {
+                        level,
+                        target: target.clone(),
+                        count: window.suppressed,
+                    });
+                }
+                false
+            });
+        }
+        windows.swept = now;
+        closed
+    }
+
+    fn summarize(&self, closed: Vec<Suppressed>) {
+        if !self.summary {
+            return;
+        }
+        for suppressed in closed {
+            summary::add(
+                Level::Warn,
+                &suppressed.target,
+                format_args!("suppressed {} messages", suppressed.count),
+            );
+        }
+    }
+}
+
+// The levels of `Windows::levels`.
+const LEVELS: [Level; 5] = [
+    Level::Error,
+    Level::Warn,
+    Level::Info,
+    Level::Debug,
+    Level::Trace,
+];
+
+// The records suppressed by a window which closed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Suppressed {
+    level: Level,
+    target: String,
+    count: u64,
+}
+
+impl Filter for RateLimitFilter {
+    fn filter(&self, record: &Record) -> Response {
+        let (response, closed) = self.check(record, Instant::now());
+        self.summarize(closed);
+        response
+    }
+
+    fn sweep_interval(&self) -> Option<Duration> {
+        Some(self.window)
+    }
+
+    fn sweep(&self) {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let closed = self.sweep_windows(&mut windows, Instant::now());
+        drop(windows);
+        self.summarize(closed);
+    }
+}
+
+/// A deserializer for the `RateLimitFilter`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: rate_limit
+///
+/// # The maximum number of records per window for each target and level.
+/// # Required.
+/// limit: 100
+///
+/// # The length of the window. Defaults to 1 second.
+/// window: 10 seconds
+///
+/// # Whether to log a "suppressed N messages" warning when a window in which
+/// # records were rejected closes. Defaults to false.
+/// summary: true
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RateLimitFilterDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for RateLimitFilterDeserializer {
+    type Trait = dyn Filter;
+
+    type Config = RateLimitFilterConfig;
+
+    fn deserialize(
+        &self,
+        config: RateLimitFilterConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Filter>> {
+        let window = config.window.unwrap_or(Duration::from_secs(1));
+        Ok(Box::new(
+            RateLimitFilter::new(config.limit, window).summary(config.summary),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(filter: &RateLimitFilter, level: Level, now: Instant) -> (Response, Vec<u64>) {
+        let (response, closed) = filter.check(
+            &Record::builder()
+                .level(level)
+                .target("app")
+                .args(format_args!("loop"))
+                .build(),
+            now,
+        );
+        (response, closed.iter().map(|closed| closed.count).collect())
+    }
+
+    #[test]
+    fn limits_per_window_and_level() {
+        let filter = RateLimitFilter::new(2, Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert_eq!(
+            check(&filter, Level::Error, start),
+            (Response::Neutral, vec![])
+        );
+        assert_eq!(
+            check(&filter, Level::Error, start),
+            (Response::Neutral, vec![])
+        );
+        assert_eq!(
+            check(&filter, Level::Error, start),
+            (Response::Reject, vec![])
+        );
+        assert_eq!(
+            check(&filter, Level::Error, start),
+            (Response::Reject, vec![])
+        );
+        assert_eq!(
+            check(&filter, Level::Warn, start),
+            (Response::Neutral, vec![])
+        );
+
+        let later = start + Duration::from_secs(1);
+        assert_eq!(
+            check(&filter, Level::Error, later),
+            (Response::Neutral, vec![2])
+        );
+        assert_eq!(
+            check(&filter, Level::Error, later),
+            (Response::Neutral, vec![])
+        );
+    }
+
+    #[test]
+    fn forgets_closed_windows() {
+        let filter = RateLimitFilter::new(1, Duration::from_secs(1));
+        let start = Instant::now();
+        let windows = || {
+            let windows = filter.windows.lock().unwrap();
+            windows.levels.iter().map(HashMap::len).sum::<usize>()
+        };
+
+        check(&filter, Level::Error, start);
+        check(&filter, Level::Warn, start);
+        check(&filter, Level::Warn, start);
+        assert_eq!(windows(), 2);
+
+        // the warn window is reported as it is forgotten
+        let (_, closed) = filter.check(
+            &Record::builder()
+                .level(Level::Info)
+                .target("other")
+                .args(format_args!("loop"))
+                .build(),
+            start + Duration::from_secs(2),
+        );
+        assert_eq!(
+            closed,
+            [Suppressed {
+                level: Level::Warn,
+                target: "app".to_owned(),
+                count: 1,
+            }]
+        );
+        assert_eq!(windows(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "console_appender")]
+    fn summary_goes_to_the_filtered_appender() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::{
+            append::Append,
+            config::{Appender, Config, Root},
+        };
+
+        #[derive(Debug)]
+        struct Collect(Arc<Mutex<Vec<String>>>);
+
+        impl Append for Collect {
+            fn append(&self, record: &Record) -> anyhow::Result<()> {
+                self.0.lock().unwrap().push(record.args().to_string());
+                Ok(())
+            }
+
+            fn flush(&self) {}
+        }
+
+        let limited = Arc::new(Mutex::new(vec![]));
+        let other = Arc::new(Mutex::new(vec![]));
+        let config = Config::builder()
+            .appender(
+                Appender::builder()
+                    .filter(Box::new(
+                        RateLimitFilter::new(1, Duration::from_millis(50)).summary(true),
+                    ))
+                    .build("limited", Box::new(Collect(limited.clone()))),
+            )
+            .appender(Appender::builder().build("other", Box::new(Collect(other.clone()))))
+            .build(
+                Root::builder()
+                    .appenders(["limited", "other"])
+                    .build(log::LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = crate::Logger::new(config);
+        let log = |message: &str| {
+            log::Log::log(
+                &logger,
+                &Record::builder()
+                    .level(Level::Info)
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+        };
+
+        log("one");
+        log("two");
+        std::thread::sleep(Duration::from_millis(60));
+        log("three");
+
+        assert_eq!(
+            *limited.lock().unwrap(),
+            ["one", "suppressed 1 messages", "three"]
+        );
+        assert_eq!(*other.lock().unwrap(), ["one", "two", "three"]);
+
+        // a burst which stops is summarized once its window closes
+        log("four");
+        log("five");
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(
+            *limited.lock().unwrap(),
+            [
+                "one",
+                "suppressed 1 messages",
+                "three",
+                "suppressed 2 messages"
+            ]
+        );
+    }
+}
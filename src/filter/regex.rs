@@ -0,0 +1,182 @@
+//! The regex filter.
+//!
+//! Requires the `regex_filter` feature.
+
+use log::Record;
+use regex::Regex;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::filter::{Filter, Response};
+
+/// The regex filter's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegexFilterConfig {
+    #[serde(default)]
+    field: Field,
+    pattern: String,
+    #[serde(default)]
+    on_match: OnMatch,
+}
+
+/// The part of a record matched by a `RegexFilter`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "snake_case"))]
+pub enum Field {
+    /// The formatted log message.
+    #[default]
+    Message,
+    /// The record's target.
+    Target,
+    /// The record's module path. Records without a module path never match.
+    ModulePath,
+}
+
+/// What a `RegexFilter` does with records that match its pattern.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "config_parsing", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config_parsing", serde(rename_all = "lowercase"))]
+pub enum OnMatch {
+    /// Accept matching records.
+    #[default]
+    Accept,
+    /// Reject matching records.
+    Reject,
+}
+
+/// A filter that accepts or rejects records whose message, target, or module
+/// path matches a regular expression.
+///
+/// Records that do not match are passed on to the remaining filters.
+#[derive(Clone, Debug)]
+pub struct RegexFilter {
+    field: Field,
+    regex: Regex,
+    on_match: OnMatch,
+}
+
+impl RegexFilter {
+    /// Creates a new `RegexFilter` matching `regex` against the specified
+    /// field of each record.
+    pub fn new(field: Field, regex: Regex, on_match: OnMatch) -> RegexFilter {
+        RegexFilter {
+            field,
+            regex,
+            on_match,
+        }
+    }
+}
+
+impl Filter for RegexFilter {
+    fn filter(&self, record: &Record) -> Response {
+        let matched = match self.field {
+            Field::Message => match record.args().as_str() {
+                Some(message) => self.regex.is_match(message),
+                None => self.regex.is_match(&record.args().to_string()),
+            },
+            Field::Target => self.regex.is_match(record.target()),
+            Field::ModulePath => record
+                .module_path()
+                .map_or(false, |path| self.regex.is_match(path)),
+        };
+
+        match (matched, self.on_match) {
+            (false, _) => Response::Neutral,
+            (true, OnMatch::Accept) => Response::Accept,
+            (true, OnMatch::Reject) => Response::Reject,
+        }
+    }
+}
+
+/// A deserializer for the `RegexFilter`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: regex
+///
+/// # The part of the record to match against. One of `message`, `target` or
+/// # `module_path`. Defaults to `message`.
+/// field: message
+///
+/// # The regular expression to search for. Required.
+/// pattern: "^health check"
+///
+/// # What to do with matching records. One of `accept` or `reject`. Records
+/// # which do not match are passed on to the next filter. Defaults to
+/// # `accept`.
+/// on_match: reject
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RegexFilterDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for RegexFilterDeserializer {
+    type Trait = dyn Filter;
+
+    type Config = RegexFilterConfig;
+
+    fn deserialize(
+        &self,
+        config: RegexFilterConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Filter>> {
+        let regex = Regex::new(&config.pattern)?;
+        Ok(Box::new(RegexFilter::new(
+            config.field,
+            regex,
+            config.on_match,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+
+    use super::*;
+
+    fn filter(filter: &RegexFilter, target: &str, message: &str) -> Response {
+        filter.filter(
+            &Record::builder()
+                .level(Level::Info)
+                .target(target)
+                .module_path(Some("app::db"))
+                .args(format_args!("{}", message))
+                .build(),
+        )
+    }
+
+    #[test]
+    fn message() {
+        let f = RegexFilter::new(
+            Field::Message,
+            Regex::new("^health").unwrap(),
+            OnMatch::Reject,
+        );
+        assert_eq!(filter(&f, "app", "health check ok"), Response::Reject);
+        assert_eq!(filter(&f, "app", "request served"), Response::Neutral);
+    }
+
+    #[test]
+    fn target_and_module_path() {
+        let f = RegexFilter::new(
+            Field::Target,
+            Regex::new("^hyper").unwrap(),
+            OnMatch::Accept,
+        );
+        assert_eq!(filter(&f, "hyper::proto", "x"), Response::Accept);
+        assert_eq!(filter(&f, "app", "x"), Response::Neutral);
+
+        let f = RegexFilter::new(
+            Field::ModulePath,
+            Regex::new("::db$").unwrap(),
+            OnMatch::Reject,
+        );
+        assert_eq!(filter(&f, "app", "x"), Response::Reject);
+    }
+}
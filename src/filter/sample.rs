@@ -0,0 +1,154 @@
+//! The sampling filter.
+//!
+//! Requires the `sample_filter` feature.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::Record;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::filter::{Filter, Response};
+
+/// The sampling filter's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, PartialEq, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SampleFilterConfig {
+    rate: Option<f64>,
+    every: Option<u64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Mode {
+    Rate(f64),
+    Every(u64),
+}
+
+/// A filter that passes on only a sample of records, rejecting the rest.
+///
+/// Records are sampled either at random with a fixed probability, or at a
+/// fixed cadence, passing on every n-th record.
+#[derive(Debug)]
+pub struct SampleFilter {
+    mode: Mode,
+    state: AtomicU64,
+}
+
+impl SampleFilter {
+    /// Creates a new `SampleFilter` passing on each record with probability
+    /// `rate`, which is clamped to the range `0.0..=1.0`.
+    pub fn rate(rate: f64) -> SampleFilter {
+        let seed = RandomState::new().build_hasher().finish();
+        SampleFilter {
+            mode: Mode::Rate(rate.clamp(0.0, 1.0)),
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    /// Creates a new `SampleFilter` passing on the first of every `every`
+    /// records. An `every` of 0 is treated as 1.
+    pub fn every(every: u64) -> SampleFilter {
+        SampleFilter {
+            mode: Mode::Every(every.max(1)),
+            state: AtomicU64::new(0),
+        }
+    }
+
+    fn sample(&self) -> bool {
+        match self.mode {
+            Mode::Rate(rate) => {
+                // splitmix64
+                let mut z = self
+                    .state
+                    .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+                    .wrapping_add(0x9e37_79b9_7f4a_7c15);
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^= z >> 31;
+                ((z >> 11) as f64 / (1u64 << 53) as f64) < rate
+            }
+            Mode::Every(every) => self.state.fetch_add(1, Ordering::Relaxed) % every == 0,
+        }
+    }
+}
+
+impl Filter for SampleFilter {
+    fn filter(&self, _: &Record) -> Response {
+        if self.sample() {
+            Response::Neutral
+        } else {
+            Response::Reject
+        }
+    }
+}
+
+/// A deserializer for the `SampleFilter`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: sample
+///
+/// # The probability with which each record is passed on. Exactly one of
+/// # `rate` and `every` is required.
+/// rate: 0.01
+///
+/// # Pass on the first of every `every` records instead.
+/// # every: 100
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SampleFilterDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for SampleFilterDeserializer {
+    type Trait = dyn Filter;
+
+    type Config = SampleFilterConfig;
+
+    fn deserialize(
+        &self,
+        config: SampleFilterConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Filter>> {
+        match (config.rate, config.every) {
+            (Some(rate), None) => Ok(Box::new(SampleFilter::rate(rate))),
+            (None, Some(every)) => Ok(Box::new(SampleFilter::every(every))),
+            _ => Err(anyhow::anyhow!(
+                "exactly one of `rate` and `every` must be specified"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn passed(filter: &SampleFilter, n: usize) -> usize {
+        let record = Record::builder().args(format_args!("x")).build();
+        (0..n)
+            .filter(|_| matches!(filter.filter(&record), Response::Neutral))
+            .count()
+    }
+
+    #[test]
+    fn every() {
+        assert_eq!(passed(&SampleFilter::every(100), 1000), 10);
+        assert_eq!(passed(&SampleFilter::every(0), 10), 10);
+    }
+
+    #[test]
+    fn rate() {
+        assert_eq!(passed(&SampleFilter::rate(0.0), 1000), 0);
+        assert_eq!(passed(&SampleFilter::rate(1.0), 1000), 1000);
+
+        let n = passed(&SampleFilter::rate(0.1), 10_000);
+        assert!(n > 800 && n < 1200, "{}", n);
+    }
+}
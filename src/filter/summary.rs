@@ -0,0 +1,59 @@
+//! Support for filters which write a summary of the records they rejected.
+
+use std::cell::RefCell;
+#[cfg(any(feature = "rate_limit_filter", feature = "dedup_filter"))]
+use std::fmt;
+
+#[cfg(any(feature = "rate_limit_filter", feature = "dedup_filter"))]
+use log::{Level, Record};
+
+use crate::record::OwnedRecord;
+
+thread_local! {
+    // The summaries added by the filters `collect` runs, while it runs them.
+    static PENDING: RefCell<Option<Vec<OwnedRecord>>> = const { RefCell::new(None) };
+}
+
+/// Calls `f`, which runs the filters of an appender, returning the summaries
+/// they added along with its result.
+///
+/// The summaries are to be written to that appender, bypassing its filters,
+/// ahead of the record being filtered.
+pub(crate) fn collect<F, R>(f: F) -> (R, Vec<OwnedRecord>)
+where
+    F: FnOnce() -> R,
+{
+    struct Reset(Option<Vec<OwnedRecord>>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let outer = self.0.take();
+            PENDING.with(|pending| *pending.borrow_mut() = outer);
+        }
+    }
+
+    let outer = PENDING.with(|pending| pending.replace(Some(vec![])));
+    let reset = Reset(outer);
+    let result = f();
+    let summaries = PENDING.with(|pending| pending.borrow_mut().take());
+    drop(reset);
+    (result, summaries.unwrap_or_default())
+}
+
+/// Adds a summary record for the appender whose filters are running.
+///
+/// The summary is dropped if the filter is not run by an appender.
+#[cfg(any(feature = "rate_limit_filter", feature = "dedup_filter"))]
+pub(crate) fn add(level: Level, target: &str, args: fmt::Arguments) {
+    PENDING.with(|pending| {
+        if let Some(ref mut pending) = *pending.borrow_mut() {
+            pending.push(OwnedRecord::new(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(args)
+                    .build(),
+            ));
+        }
+    });
+}
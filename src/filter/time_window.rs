@@ -0,0 +1,207 @@
+//! The time window filter.
+//!
+//! Requires the `time_window_filter` feature.
+
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
+use log::Record;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::filter::{Filter, Response};
+
+/// The time window filter's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeWindowFilterConfig {
+    windows: Vec<TimeWindowConfig>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TimeWindowConfig {
+    start: String,
+    end: String,
+    #[serde(default)]
+    days: Vec<String>,
+}
+
+/// A period of local time, repeated daily or on selected days of the week.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TimeWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+    days: Vec<Weekday>,
+}
+
+impl TimeWindow {
+    /// Creates a new `TimeWindow` from `start` (inclusive) to `end`
+    /// (exclusive) on every day.
+    ///
+    /// If `end` is before `start`, the window runs past midnight into the
+    /// next day. If they are equal, the window covers the whole day.
+    pub fn new(start: NaiveTime, end: NaiveTime) -> TimeWindow {
+        TimeWindow {
+            start,
+            end,
+            days: vec![],
+        }
+    }
+
+    /// Restricts the window to the specified days of the week.
+    ///
+    /// A window which runs past midnight belongs to the day it starts on.
+    pub fn days<I>(mut self, days: I) -> TimeWindow
+    where
+        I: IntoIterator<Item = Weekday>,
+    {
+        self.days.extend(days);
+        self
+    }
+
+    fn on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    fn contains(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let day = now.weekday();
+        if self.start < self.end {
+            self.on(day) && self.start <= time && time < self.end
+        } else if self.start > self.end {
+            (self.on(day) && self.start <= time) || (self.on(day.pred()) && time < self.end)
+        } else {
+            self.on(day)
+        }
+    }
+}
+
+/// A filter that rejects records logged outside of a set of local time
+/// windows.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TimeWindowFilter {
+    windows: Vec<TimeWindow>,
+}
+
+impl TimeWindowFilter {
+    /// Creates a new `TimeWindowFilter` accepting records logged within any of
+    /// `windows`.
+    pub fn new(windows: Vec<TimeWindow>) -> TimeWindowFilter {
+        TimeWindowFilter { windows }
+    }
+
+    fn filter_at(&self, now: NaiveDateTime) -> Response {
+        if self.windows.iter().any(|window| window.contains(now)) {
+            Response::Neutral
+        } else {
+            Response::Reject
+        }
+    }
+}
+
+impl Filter for TimeWindowFilter {
+    fn filter(&self, _: &Record) -> Response {
+        self.filter_at(Local::now().naive_local())
+    }
+}
+
+/// A deserializer for the `TimeWindowFilter`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: time_window
+///
+/// # The local time windows in which records are passed on. Records logged
+/// # at any other time are rejected. Required.
+/// windows:
+///
+///   -
+///     # The start of the window, as `HH:MM` or `HH:MM:SS`. Required.
+///     start: "22:00"
+///
+///     # The end of the window, exclusive. A window ending before it starts
+///     # runs past midnight. Required.
+///     end: "04:00"
+///
+///     # The days of the week the window starts on. Defaults to every day.
+///     days: [mon, tue, wed, thu, fri]
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TimeWindowFilterDeserializer;
+
+#[cfg(feature = "config_parsing")]
+fn parse_time(time: &str) -> anyhow::Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .map_err(|_| anyhow::anyhow!("invalid time of day `{}`", time))
+}
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for TimeWindowFilterDeserializer {
+    type Trait = dyn Filter;
+
+    type Config = TimeWindowFilterConfig;
+
+    fn deserialize(
+        &self,
+        config: TimeWindowFilterConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Filter>> {
+        let mut windows = vec![];
+        for window in config.windows {
+            let mut days = vec![];
+            for day in &window.days {
+                days.push(
+                    day.parse::<Weekday>()
+                        .map_err(|_| anyhow::anyhow!("invalid day of the week `{}`", day))?,
+                );
+            }
+            windows.push(
+                TimeWindow::new(parse_time(&window.start)?, parse_time(&window.end)?).days(days),
+            );
+        }
+        Ok(Box::new(TimeWindowFilter::new(windows)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        // 2024-05-06 is a Monday
+        NaiveDate::from_ymd_opt(2024, 5, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn time(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn same_day_window() {
+        let filter = TimeWindowFilter::new(vec![TimeWindow::new(time(9), time(17))]);
+        assert_eq!(filter.filter_at(at(6, 8)), Response::Reject);
+        assert_eq!(filter.filter_at(at(6, 9)), Response::Neutral);
+        assert_eq!(filter.filter_at(at(6, 17)), Response::Reject);
+    }
+
+    #[test]
+    fn overnight_window_on_selected_days() {
+        let filter = TimeWindowFilter::new(vec![
+            TimeWindow::new(time(22), time(4)).days(vec![Weekday::Mon])
+        ]);
+        assert_eq!(filter.filter_at(at(6, 23)), Response::Neutral);
+        assert_eq!(filter.filter_at(at(7, 3)), Response::Neutral);
+        assert_eq!(filter.filter_at(at(7, 4)), Response::Reject);
+        assert_eq!(filter.filter_at(at(7, 23)), Response::Reject);
+        assert_eq!(filter.filter_at(at(6, 3)), Response::Reject);
+    }
+}
@@ -0,0 +1,143 @@
+use log::{LevelFilter, Record};
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{config::History, record::OwnedRecord};
+
+/// The recent records kept according to a `History`.
+#[derive(Debug)]
+pub(crate) struct RecordHistory {
+    history: History,
+    records: Mutex<VecDeque<(SystemTime, OwnedRecord)>>,
+}
+
+impl RecordHistory {
+    pub(crate) fn new(history: History) -> RecordHistory {
+        RecordHistory {
+            history,
+            records: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn level(&self) -> LevelFilter {
+        self.history.level()
+    }
+
+    /// Keeps a copy of the record if it is at or above the history's level.
+    pub(crate) fn push(&self, record: &Record) {
+        if record.level() > self.history.level() || self.history.capacity() == 0 {
+            return;
+        }
+
+        let now = SystemTime::now();
+        let record = OwnedRecord::new(record);
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        self.expire(&mut records, now);
+        if records.len() >= self.history.capacity() {
+            records.pop_front();
+        }
+        records.push_back((now, record));
+    }
+
+    /// Returns the records kept, oldest first, along with the times they were
+    /// logged at.
+    pub(crate) fn records(&self) -> Vec<(SystemTime, OwnedRecord)> {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        self.expire(&mut records, SystemTime::now());
+        records.iter().cloned().collect()
+    }
+
+    fn expire(&self, records: &mut VecDeque<(SystemTime, OwnedRecord)>, now: SystemTime) {
+        while let Some(&(logged, _)) = records.front() {
+            match now.duration_since(logged) {
+                Ok(age) if age > self.history.duration() => {
+                    records.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp with millisecond precision.
+pub(crate) fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Converts days since the epoch to a civil date, after Howard Hinnant's
+    // `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn format() {
+        assert_eq!(format_time(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_time(UNIX_EPOCH + Duration::from_millis(951_782_400_123)),
+            "2000-02-29T00:00:00.123Z"
+        );
+        assert_eq!(
+            format_time(UNIX_EPOCH + Duration::from_secs(1_791_980_230)),
+            "2026-10-14T12:17:10.000Z"
+        );
+    }
+
+    #[test]
+    fn capacity() {
+        let history = RecordHistory::new(
+            History::builder()
+                .capacity(2)
+                .build(Duration::from_secs(60)),
+        );
+        for (level, message) in [
+            (Level::Info, "one"),
+            (Level::Trace, "two"),
+            (Level::Debug, "three"),
+            (Level::Warn, "four"),
+        ] {
+            history.push(
+                &Record::builder()
+                    .level(level)
+                    .args(format_args!("{}", message))
+                    .build(),
+            );
+        }
+
+        let messages = history
+            .records()
+            .iter()
+            .map(|(_, record)| record.with_record(|record| record.args().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(messages, ["three", "four"]);
+    }
+}
@@ -0,0 +1,165 @@
+use log::kv::{self, Source, VisitSource};
+use std::fmt::{self, Write};
+
+use crate::config::KvLimits;
+
+/// Returns the key-value fields of `key_values` cut down to `limits`,
+/// followed by the number of fields cut as `_truncated_fields`, or `None` if
+/// they are within the limits.
+pub(crate) fn limit(limits: &KvLimits, key_values: &dyn Source) -> Option<Vec<(String, String)>> {
+    let mut check = Check {
+        limits,
+        count: 0,
+        exceeded: false,
+    };
+    if *limits == KvLimits::default() || key_values.visit(&mut check).is_ok() && !check.exceeded {
+        return None;
+    }
+
+    let mut fields = Fields {
+        limits,
+        fields: vec![],
+        truncated: 0,
+    };
+    let _ = key_values.visit(&mut fields);
+    let truncated = fields.truncated.to_string();
+    fields
+        .fields
+        .push(("_truncated_fields".to_owned(), truncated));
+    Some(fields.fields)
+}
+
+/// Formats `value` into a string of at most `limit` bytes, returning whether
+/// it had to be truncated.
+fn format_bounded(value: &dyn fmt::Display, limit: Option<usize>) -> (String, bool) {
+    let mut bounded = Bounded {
+        buf: String::new(),
+        limit: limit.unwrap_or(usize::MAX),
+        truncated: false,
+    };
+    let _ = write!(bounded, "{}", value);
+    (bounded.buf, bounded.truncated)
+}
+
+struct Bounded {
+    buf: String,
+    limit: usize,
+    truncated: bool,
+}
+
+impl fmt::Write for Bounded {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = self.limit - self.buf.len();
+        if s.len() <= room {
+            self.buf.push_str(s);
+            return Ok(());
+        }
+
+        let mut end = room;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buf.push_str(&s[..end]);
+        self.truncated = true;
+        // stop formatting the rest of the value
+        Err(fmt::Error)
+    }
+}
+
+struct Check<'a> {
+    limits: &'a KvLimits,
+    count: usize,
+    exceeded: bool,
+}
+
+impl<'kvs> VisitSource<'kvs> for Check<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        self.count += 1;
+        self.exceeded = self
+            .limits
+            .max_fields()
+            .map_or(false, |max| self.count > max)
+            || self
+                .limits
+                .max_key_len()
+                .map_or(false, |max| key.as_str().len() > max)
+            || format_bounded(&value, self.limits.max_value_len()).1;
+        if self.exceeded {
+            Err(kv::Error::msg("limit exceeded"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+struct Fields<'a> {
+    limits: &'a KvLimits,
+    fields: Vec<(String, String)>,
+    truncated: usize,
+}
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        if Some(self.fields.len()) == self.limits.max_fields() {
+            self.truncated += 1;
+            return Ok(());
+        }
+
+        let (key, key_truncated) = format_bounded(&key, self.limits.max_key_len());
+        let (value, value_truncated) = format_bounded(&value, self.limits.max_value_len());
+        if key_truncated || value_truncated {
+            self.truncated += 1;
+        }
+        self.fields.push((key, value));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn limited(limits: KvLimits, kvs: &[(&str, &str)]) -> Vec<(String, String)> {
+        limit(&limits, &kvs).unwrap_or_else(|| owned(kvs))
+    }
+
+    fn owned(kvs: &[(&str, &str)]) -> Vec<(String, String)> {
+        kvs.iter()
+            .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn within_limits() {
+        let limits = KvLimits::builder()
+            .max_fields(2)
+            .max_key_len(3)
+            .max_value_len(3)
+            .build();
+        let kvs = [("a", "1"), ("bcd", "234")];
+        assert_eq!(limited(limits, &kvs), owned(&kvs));
+    }
+
+    #[test]
+    fn too_many_fields() {
+        let limits = KvLimits::builder().max_fields(2).build();
+        assert_eq!(
+            limited(limits, &[("a", "1"), ("b", "2"), ("c", "3"), ("d", "4")]),
+            owned(&[("a", "1"), ("b", "2"), ("_truncated_fields", "2")])
+        );
+    }
+
+    #[test]
+    fn long_keys_and_values() {
+        let limits = KvLimits::builder().max_key_len(3).max_value_len(4).build();
+        assert_eq!(
+            limited(limits, &[("abcdef", "1"), ("b", "2"), ("c", "héllo")]),
+            owned(&[
+                ("abc", "1"),
+                ("b", "2"),
+                ("c", "hél"),
+                ("_truncated_fields", "2")
+            ])
+        );
+    }
+}
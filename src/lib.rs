@@ -19,18 +19,52 @@
 //!       - Rollers
 //!         - [delete](append/rolling_file/policy/compound/roll/delete/struct.DeleteRollerDeserializer.html#configuration): requires the `delete_roller` feature
 //!         - [fixed_window](append/rolling_file/policy/compound/roll/fixed_window/struct.FixedWindowRollerDeserializer.html#configuration): requires the `fixed_window_roller` feature
+//!         - [date](append/rolling_file/policy/compound/roll/date/struct.DateRollerDeserializer.html#configuration): requires the `date_roller` feature
 //!       - Triggers
 //!         - [size](append/rolling_file/policy/compound/trigger/size/struct.SizeTriggerDeserializer.html#configuration): requires the `size_trigger` feature
+//!         - [level](append/rolling_file/policy/compound/trigger/level/struct.LevelTriggerDeserializer.html#configuration): requires the `level_trigger` feature
+//!   - [failover](append/failover/struct.FailoverAppenderDeserializer.html#configuration): requires the `failover_appender` feature.
+//!   - [alert](append/alert/struct.AlertAppenderDeserializer.html#configuration): requires the `alert_appender` feature.
+//!   - [kafka](append/kafka/struct.KafkaAppenderDeserializer.html#configuration): requires the `kafka` feature.
+//!   - [otel](append/otel/struct.OtelAppenderDeserializer.html#configuration): requires the `otel` feature.
+//!   - [ring_buffer](append/ring_buffer/struct.RingBufferAppenderDeserializer.html#configuration): requires the `ring_buffer_appender` feature.
+//!   - [routing](append/routing/struct.RoutingAppenderDeserializer.html#configuration): requires the `routing_appender` feature.
+//!   - [sqlite](append/sqlite/struct.SqliteAppenderDeserializer.html#configuration): requires the `sqlite` feature.
+//!   - [tee](append/tee/struct.TeeAppenderDeserializer.html#configuration): requires the `tee_appender` feature.
+//!   - [unix_socket](append/unix_socket/struct.UnixSocketAppenderDeserializer.html#configuration): requires the `unix_socket_appender` feature and Unix.
+//!
+//! ## Compressors
+//!
+//! A compressor compresses the output of a component, such as the archives
+//! written by the rollers.
+//!
+//! Implementations:
+//!   - [gzip](compress/gzip/struct.GzipCompressorDeserializer.html#configuration): requires the `gzip` feature
+//!
+//! ## Encryptors
+//!
+//! An encryptor encrypts the output of a component, such as the archives
+//! written by the rollers.
+//!
+//! Implementations:
+//!   - [age](encrypt/age/struct.AgeEncryptorDeserializer.html#configuration): requires the `age_encryption` feature
 //!
 //! ## Encoders
 //!
 //! An encoder is responsible for taking a log record, transforming it into the
 //! appropriate output format, and writing it out. An appender will normally
-//! use an encoder internally.
+//! use an encoder internally. Appenders which send records over a byte stream
+//! delimit them with a separate [framing](encode/framing/enum.Framing.html),
+//! so any encoder can be combined with the framing the receiver expects.
 //!
 //! Implementations:
 //!   - [pattern](encode/pattern/struct.PatternEncoderDeserializer.html#configuration): requires the `pattern_encoder` feature
 //!   - [json](encode/json/struct.JsonEncoderDeserializer.html#configuration): requires the `json_encoder` feature
+//!   - [wrap](encode/wrap/struct.WrapEncoderDeserializer.html#configuration): requires the `wrap_encoder` feature
+//!
+//! Errors attached to records with [`log_error!`] are rendered with their
+//! full chain of causes and backtrace by both the pattern and json encoders,
+//! as described in [`error_chain`].
 //!
 //! ## Filters
 //!
@@ -39,6 +73,23 @@
 //!
 //! Implementations:
 //!   - [threshold](filter/threshold/struct.ThresholdFilterDeserializer.html#configuration): requires the `threshold_filter` feature
+//!   - [level_range](filter/level_range/struct.LevelRangeFilterDeserializer.html#configuration): requires the `level_range_filter` feature
+//!   - [regex](filter/regex/struct.RegexFilterDeserializer.html#configuration): requires the `regex_filter` feature
+//!   - [rate_limit](filter/rate_limit/struct.RateLimitFilterDeserializer.html#configuration): requires the `rate_limit_filter` feature
+//!   - [dedup](filter/dedup/struct.DedupFilterDeserializer.html#configuration): requires the `dedup_filter` feature
+//!   - [sample](filter/sample/struct.SampleFilterDeserializer.html#configuration): requires the `sample_filter` feature
+//!   - [time_window](filter/time_window/struct.TimeWindowFilterDeserializer.html#configuration): requires the `time_window_filter` feature
+//!
+//! ## Processors
+//!
+//! Processors are associated with appenders and modify the log events which
+//! pass the appender's filters before they are encoded, for example to add
+//! fields, redact values, or rewrite targets.
+//!
+//! Implementations:
+//!   - [fields](processor/fields/struct.FieldsProcessorDeserializer.html#configuration): requires the `fields_processor` feature
+//!   - [redact](processor/redact/struct.RedactProcessorDeserializer.html#configuration): requires the `redact_processor` feature
+//!   - [target](processor/target/struct.TargetProcessorDeserializer.html#configuration): requires the `target_processor` feature
 //!
 //! ## Loggers
 //!
@@ -66,6 +117,15 @@
 //! The "root" logger is the ancestor of all other loggers. Since it has no
 //! ancestors, its additivity cannot be configured.
 //!
+//! A record can also name an appender it must reach with the reserved
+//! `log4rs.appender` key-value field, for example
+//! `log::info!("log4rs.appender" = "audit"; "user {} deleted", id)`. The record
+//! is written to that appender as well as the logger's own, even if the logger
+//! or its level is not wired to it. The appender's threshold and filters still
+//! apply, and the field is removed before the record is encoded. A name which
+//! matches no appender is reported to the error handler. Records at levels the
+//! configuration disables for every logger never reach log4rs.
+//!
 //! # Configuration
 //!
 //! For a detailed breakdown on configuration, refer to the
@@ -83,6 +143,11 @@
 //!
 //! As a convenience, the `all_components` feature activates all logger components.
 //!
+//! Crates providing components of their own make them usable in config files
+//! by implementing [`Plugin`](config/plugin/trait.Plugin.html) for their
+//! configs; with the `plugins` feature, `log4rs::plugin!` registers them with
+//! every `Deserializers::default()`.
+//!
 //! # Examples
 //!
 //! ## Configuration via a YAML file
@@ -172,44 +237,189 @@
 //! # fn main() {}
 //! ```
 //!
+//! ## Zero-config defaults
+//!
+//! Small tools can leave the choice of appender to `auto_init`, which logs
+//! JSON lines to standard output in containers, colored records to standard
+//! error on terminals, and otherwise to a rolling file in the user's state
+//! directory. The `LOG4RS_LEVEL` environment variable overrides the level,
+//! `info` by default.
+//!
+//! ```no_run
+//! # #[cfg(all(feature = "console_appender", feature = "rolling_file_appender",
+//! #           feature = "compound_policy", feature = "size_trigger",
+//! #           feature = "fixed_window_roller", feature = "json_encoder"))]
+//! # fn f() {
+//! log4rs::auto_init().unwrap();
+//! # }
+//! # fn main() {}
+//! ```
+//!
+//! ## Asserting on log output in tests
+//!
+//! With the `test_support` feature, a `CapturingAppender` keeps copies of the
+//! records logged on the test's thread:
+//!
+//! ```
+//! # #[cfg(feature = "test_support")]
+//! # fn f() {
+//! use log::Level;
+//! use log4rs::append::capturing::CapturingAppender;
+//!
+//! let capture = CapturingAppender::new();
+//! let _guard = capture.install().unwrap();
+//!
+//! log::warn!("cache miss for {}", "user:1");
+//!
+//! assert!(capture.contains(Level::Warn, "cache miss"));
+//! # }
+//! # fn main() {}
+//! ```
+//!
+//! ## Measuring throughput
+//!
+//! The criterion benchmarks in `benches/throughput.rs` measure the encoders
+//! and appenders; `cargo bench --all-features --bench throughput` runs them.
+//! With the `load_generator` feature, the [`load`] module drives the same
+//! synthetic load against an application's own appenders and loggers.
+//!
+//! ## Running on a tokio runtime
+//!
+//! Appenders which send records over the network, background rotation and
+//! the logger's timers run on threads of their own. With the `tokio` feature,
+//! async applications can run them on their runtime instead, as described in
+//! the [`background`] module:
+//!
+//! ```no_run
+//! # #[cfg(all(feature = "tokio", feature = "console_appender"))]
+//! # async fn f() {
+//! use log::LevelFilter;
+//! use log4rs::append::console::ConsoleAppender;
+//! use log4rs::config::{Appender, Config, Root};
+//!
+//! // before building the appenders, so that they start their work on it
+//! log4rs::background::use_tokio(tokio::runtime::Handle::current());
+//!
+//! let stdout = ConsoleAppender::builder().build();
+//! let config = Config::builder()
+//!     .appender(Appender::builder().build("stdout", Box::new(stdout)))
+//!     .build(Root::builder().appender("stdout").build(LevelFilter::Warn))
+//!     .unwrap();
+//!
+//! log4rs::init_config(config).unwrap();
+//! # }
+//! # fn main() {}
+//! ```
+//!
 //! For more examples see the [examples](https://github.com/estk/log4rs/tree/master/examples).
 //!
 
-#![allow(where_clauses_object_safety, clippy::manual_non_exhaustive)]
+#![allow(clippy::manual_non_exhaustive)]
 #![warn(missing_docs)]
 
 use std::{
-    cmp, collections::HashMap, fmt, hash::BuildHasherDefault, io, io::prelude::*, sync::Arc,
+    cmp,
+    collections::{HashMap, VecDeque},
+    fmt, fs,
+    hash::BuildHasherDefault,
+    io,
+    io::prelude::*,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use arc_swap::ArcSwap;
 use fnv::FnvHasher;
 use log::{Level, LevelFilter, Metadata, Record};
+use thiserror::Error;
 
+#[cfg(feature = "alloc_audit")]
+pub mod alloc_audit;
 pub mod append;
+pub mod background;
+#[cfg(feature = "process_capture")]
+pub mod capture;
+pub mod compress;
 pub mod config;
+mod derived;
+mod destination;
 pub mod encode;
+pub mod encrypt;
+pub mod error_chain;
+pub mod error_handler;
+mod escalation;
 pub mod filter;
+mod history;
+mod kv_limit;
+#[cfg(feature = "load_generator")]
+pub mod load;
+mod monotonic;
 #[cfg(feature = "console_writer")]
 mod priv_io;
+pub mod processor;
+mod record;
+mod sanitize;
+pub mod span;
+pub mod state;
+#[cfg(feature = "tracing_bridge")]
+pub mod tracing_bridge;
 
 pub use config::{init_config, Config};
 
+#[cfg(all(
+    feature = "console_appender",
+    feature = "rolling_file_appender",
+    feature = "compound_policy",
+    feature = "size_trigger",
+    feature = "fixed_window_roller",
+    feature = "json_encoder"
+))]
+pub use config::auto_init;
+
 #[cfg(feature = "config_parsing")]
-pub use config::{init_file, init_raw_config};
+pub use config::{init_file, init_file_with_format, init_raw_config};
 
-use self::{append::Append, filter::Filter};
+use self::{
+    append::Append,
+    error_handler::{ErrorHandler, StderrErrorHandler},
+    filter::Filter,
+    processor::Processor,
+    record::OwnedRecord,
+};
 
 type FnvHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FnvHasher>>;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct ConfiguredLogger {
     level: LevelFilter,
+    // Whether the level was taken from the parent rather than configured.
+    inherits_level: bool,
+    // The appenders records are written to, including inherited ones.
     appenders: Vec<usize>,
+    // The appenders attached to the logger itself.
+    own_appenders: Vec<usize>,
+    additive: bool,
     children: FnvHashMap<String, ConfiguredLogger>,
 }
 
 impl ConfiguredLogger {
+    /// Returns a child logger with no configuration of its own.
+    fn inheriting(&self) -> ConfiguredLogger {
+        ConfiguredLogger {
+            level: self.level,
+            inherits_level: true,
+            appenders: self.appenders.clone(),
+            own_appenders: vec![],
+            additive: true,
+            children: FnvHashMap::default(),
+        }
+    }
+
     fn add(&mut self, path: &str, mut appenders: Vec<usize>, additive: bool, level: LevelFilter) {
         let (part, rest) = match path.find("::") {
             Some(idx) => (&path[..idx], &path[idx + 2..]),
@@ -222,21 +432,21 @@ impl ConfiguredLogger {
         }
 
         let child = if rest.is_empty() {
+            let own_appenders = appenders.clone();
             if additive {
                 appenders.extend(self.appenders.iter().cloned());
             }
 
             ConfiguredLogger {
                 level,
+                inherits_level: false,
                 appenders,
+                own_appenders,
+                additive,
                 children: FnvHashMap::default(),
             }
         } else {
-            let mut child = ConfiguredLogger {
-                level: self.level,
-                appenders: self.appenders.clone(),
-                children: FnvHashMap::default(),
-            };
+            let mut child = self.inheriting();
             child.add(rest, appenders, additive, level);
             child
         };
@@ -244,6 +454,104 @@ impl ConfiguredLogger {
         self.children.insert(part.to_owned(), child);
     }
 
+    /// Sets the level of the logger at `path`, and of all loggers below it
+    /// which inherit their level.
+    fn set_level(&mut self, path: &str, level: LevelFilter) {
+        let logger = self.logger_mut(path);
+        logger.level = level;
+        logger.inherits_level = false;
+        logger.inherit_level(level);
+    }
+
+    /// Returns the level configured for the logger at `path`, or `None` if it
+    /// takes its level from its parent.
+    fn configured_level(&self, path: &str) -> Option<LevelFilter> {
+        let mut node = self;
+        if !path.is_empty() {
+            for part in path.split("::") {
+                node = node.children.get(part)?;
+            }
+        }
+        if node.inherits_level {
+            None
+        } else {
+            Some(node.level)
+        }
+    }
+
+    /// Makes the logger at `path` take its level from its parent again.
+    fn clear_level(&mut self, path: &str) {
+        let (part, rest) = match path.find("::") {
+            Some(idx) => (&path[..idx], &path[idx + 2..]),
+            None => (path, ""),
+        };
+
+        let level = self.level;
+        match self.children.get_mut(part) {
+            Some(child) if rest.is_empty() => {
+                child.level = level;
+                child.inherits_level = true;
+                child.inherit_level(level);
+            }
+            Some(child) => child.clear_level(rest),
+            None => {}
+        }
+    }
+
+    /// Returns the logger at `path`, creating it and the loggers above it if
+    /// they are not configured.
+    fn logger_mut(&mut self, path: &str) -> &mut ConfiguredLogger {
+        if path.is_empty() {
+            return self;
+        }
+
+        let (part, rest) = match path.find("::") {
+            Some(idx) => (&path[..idx], &path[idx + 2..]),
+            None => (path, ""),
+        };
+
+        if !self.children.contains_key(part) {
+            let child = self.inheriting();
+            self.children.insert(part.to_owned(), child);
+        }
+        self.children
+            .get_mut(part)
+            .expect("inserted above")
+            .logger_mut(rest)
+    }
+
+    /// Changes the logger at `path` with `f`, then recomputes the appenders
+    /// of every logger. Called on the root logger.
+    fn update_appenders<F>(&mut self, path: &str, f: F)
+    where
+        F: FnOnce(&mut ConfiguredLogger),
+    {
+        f(self.logger_mut(path));
+        self.appenders = self.own_appenders.clone();
+        self.inherit_appenders();
+    }
+
+    /// Recomputes the appenders of all loggers below this one after their
+    /// own appenders or additivity changed.
+    fn inherit_appenders(&mut self) {
+        for child in self.children.values_mut() {
+            child.appenders = child.own_appenders.clone();
+            if child.additive {
+                child.appenders.extend(self.appenders.iter().cloned());
+            }
+            child.inherit_appenders();
+        }
+    }
+
+    fn inherit_level(&mut self, level: LevelFilter) {
+        for child in self.children.values_mut() {
+            if child.inherits_level {
+                child.level = level;
+                child.inherit_level(level);
+            }
+        }
+    }
+
     fn max_log_level(&self) -> LevelFilter {
         let mut max = self.level;
         for child in self.children.values() {
@@ -269,13 +577,13 @@ impl ConfiguredLogger {
         self.level >= level
     }
 
+    /// Writes a record to the logger's appenders. The caller checks that the
+    /// record is enabled, which escalations may override.
     fn log(&self, record: &log::Record, appenders: &[Appender]) -> Result<(), Vec<anyhow::Error>> {
         let mut errors = vec![];
-        if self.enabled(record.level()) {
-            for &idx in &self.appenders {
-                if let Err(err) = appenders[idx].append(record) {
-                    errors.push(err);
-                }
+        for &idx in &self.appenders {
+            if let Err(err) = appenders[idx].append(record) {
+                errors.push(err);
             }
         }
 
@@ -289,32 +597,310 @@ impl ConfiguredLogger {
 
 #[derive(Debug)]
 struct Appender {
+    name: String,
     appender: Box<dyn Append>,
     filters: Vec<Box<dyn Filter>>,
+    processors: Vec<Box<dyn Processor>>,
+    sanitize: config::Sanitize,
+    flush_interval: Option<Duration>,
+    monotonic_time: Option<monotonic::MonotonicTime>,
+    threshold: AtomicUsize,
+    paused: AtomicBool,
+    pause: Mutex<PauseState>,
+    // Held while the buffer of a resumed appender is written.
+    replay: Mutex<()>,
+    // The last flush started by `Handle::flush`, which later calls wait for
+    // while it runs.
+    flushing: Mutex<Option<background::Task>>,
+    counters: AppenderCounters,
+    stats: Arc<Stats>,
+}
+
+/// The counters behind `AppenderStats`.
+#[derive(Debug, Default)]
+struct AppenderCounters {
+    written: AtomicU64,
+    errors: AtomicU64,
+    filtered: AtomicU64,
+    dropped: AtomicU64,
+    // Nanoseconds since the epoch, or 0 if nothing was written yet.
+    last_write: AtomicU64,
+    last_error: Mutex<Option<(SystemTime, String)>>,
+}
+
+#[derive(Debug, Default)]
+struct PauseState {
+    mode: Option<PauseMode>,
+    buffer: VecDeque<(SystemTime, OwnedRecord)>,
+    dropped: u64,
 }
 
 impl Appender {
     fn append(&self, record: &Record) -> anyhow::Result<()> {
-        for filter in &self.filters {
-            match filter.filter(record) {
-                filter::Response::Accept => break,
-                filter::Response::Neutral => {}
-                filter::Response::Reject => return Ok(()),
+        if record.level() > self.threshold() {
+            self.counters.filtered.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let (rejected, summaries) = filter::summary::collect(|| {
+            for filter in &self.filters {
+                match filter.filter(record) {
+                    filter::Response::Accept => break,
+                    filter::Response::Neutral => {}
+                    filter::Response::Reject => return true,
+                }
+            }
+            false
+        });
+        for summary in summaries {
+            summary.with_record(|summary| self.process(summary))?;
+        }
+        if rejected {
+            self.counters.filtered.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        self.process(record)
+    }
+
+    /// Sweeps one of the filters, writing the summaries it adds.
+    fn sweep(&self, filter: usize) -> anyhow::Result<()> {
+        let ((), summaries) = filter::summary::collect(|| self.filters[filter].sweep());
+        for summary in summaries {
+            summary.with_record(|summary| self.process(summary))?;
+        }
+        Ok(())
+    }
+
+    /// Passes a record accepted by the filters through the processors to the
+    /// appender, or holds it while the appender is paused.
+    fn process(&self, record: &Record) -> anyhow::Result<()> {
+        processor::with_processed(&self.processors, record, |record| {
+            sanitize::with_sanitized(self.sanitize, record, |record| {
+                if self.paused.load(Ordering::Acquire) && self.hold(record) {
+                    return Ok(());
+                }
+
+                self.write(record)
+            })
+        })
+    }
+
+    /// Passes a record to the appender, bypassing the threshold, filters and
+    /// pause.
+    fn write(&self, record: &Record) -> anyhow::Result<()> {
+        let result = match self.monotonic_time {
+            Some(ref monotonic_time) => {
+                monotonic_time.with_time(record, |record| self.appender.append(record))
+            }
+            None => self.appender.append(record),
+        };
+        match result {
+            Ok(()) => {
+                self.counters.written.fetch_add(1, Ordering::Relaxed);
+                if self.stats.timed.load(Ordering::Relaxed) {
+                    let since_epoch = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    self.counters
+                        .last_write
+                        .store(since_epoch.as_nanos() as u64, Ordering::Relaxed);
+                }
+            }
+            Err(ref e) => {
+                self.counters.errors.fetch_add(1, Ordering::Relaxed);
+                *self
+                    .counters
+                    .last_error
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner()) =
+                    Some((SystemTime::now(), format!("{:#}", e)));
+            }
+        }
+        result
+    }
+
+    /// Buffers or drops a record while the appender is paused, returning false
+    /// if the appender was resumed in the meantime.
+    fn hold(&self, record: &Record) -> bool {
+        let mut state = self.pause.lock().unwrap_or_else(|e| e.into_inner());
+        match state.mode {
+            Some(PauseMode::Buffer(capacity)) => {
+                if capacity == 0 {
+                    self.count_drop(&mut state);
+                    return true;
+                }
+                if state.buffer.len() >= capacity {
+                    state.buffer.pop_front();
+                    self.count_drop(&mut state);
+                }
+                state
+                    .buffer
+                    .push_back((encode::record_time(), OwnedRecord::new(record)));
+                true
             }
+            Some(PauseMode::Drop) => {
+                self.count_drop(&mut state);
+                true
+            }
+            None => false,
         }
+    }
+
+    fn count_drop(&self, state: &mut PauseState) {
+        state.dropped += 1;
+        self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn threshold(&self) -> LevelFilter {
+        LevelFilter::iter()
+            .nth(self.threshold.load(Ordering::Relaxed))
+            .unwrap_or(LevelFilter::Trace)
+    }
+
+    fn set_threshold(&self, level: LevelFilter) {
+        self.threshold.store(level as usize, Ordering::Relaxed);
+    }
 
-        self.appender.append(record)
+    fn pause(&self, mode: PauseMode) {
+        let mut state = self.pause.lock().unwrap_or_else(|e| e.into_inner());
+        state.mode = Some(mode);
+        self.paused.store(true, Ordering::Release);
+    }
+
+    /// Resumes the appender, passing the records it buffered to `replay` in
+    /// order, and returns the number replayed and dropped.
+    ///
+    /// Records keep being held until the buffer is empty, so that none is
+    /// written ahead of those buffered before it.
+    fn resume<F>(&self, mut replay: F) -> (usize, u64)
+    where
+        F: FnMut(SystemTime, OwnedRecord),
+    {
+        let _replay = self.replay.lock().unwrap_or_else(|e| e.into_inner());
+        let mut replayed = 0;
+        loop {
+            let mut state = self.pause.lock().unwrap_or_else(|e| e.into_inner());
+            if state.buffer.is_empty() {
+                self.paused.store(false, Ordering::Release);
+                state.mode = None;
+                return (replayed, std::mem::replace(&mut state.dropped, 0));
+            }
+            let buffer = std::mem::take(&mut state.buffer);
+            drop(state);
+
+            replayed += buffer.len();
+            for (logged, record) in buffer {
+                replay(logged, record);
+            }
+        }
     }
 
     fn flush(&self) {
-        self.appender.flush();
+        self.appender.flush_to(append::flush::FlushToken::issue());
+    }
+
+    fn stats(&self) -> AppenderStats {
+        let append_stats = self.appender.stats();
+        AppenderStats {
+            name: self.name.clone(),
+            written: self.counters.written.load(Ordering::Relaxed),
+            errors: self.counters.errors.load(Ordering::Relaxed),
+            filtered: self.counters.filtered.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed)
+                + append_stats.dropped.unwrap_or(0),
+            bytes_written: append_stats.bytes_written,
+            rotations: append_stats.rotations,
+            syncs: append_stats.syncs,
+            sync_time: append_stats.sync_time,
+        }
+    }
+
+    fn health(&self) -> AppenderHealth {
+        let append_health = self.appender.health();
+        let last_write = match self.counters.last_write.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(UNIX_EPOCH + Duration::from_nanos(nanos)),
+        };
+        AppenderHealth {
+            name: self.name.clone(),
+            paused: self.paused.load(Ordering::Acquire),
+            last_write,
+            last_error: self
+                .counters
+                .last_error
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+            connected: append_health.connected,
+            queue_depth: append_health.queue_depth,
+        }
+    }
+}
+
+/// Counters over the lifetime of a logger, kept across reconfiguration.
+#[derive(Debug)]
+struct Stats {
+    started: Instant,
+    // Indexed by `Level as usize - 1`.
+    records: [AtomicU64; 5],
+    dropped: AtomicU64,
+    // Set by the first call to `Handle::health`, after which writes are
+    // timed.
+    timed: AtomicBool,
+    #[cfg(feature = "alloc_audit")]
+    allocations: alloc_audit::AllocationCounters,
+}
+
+impl Stats {
+    fn new() -> Stats {
+        Stats {
+            started: Instant::now(),
+            records: Default::default(),
+            dropped: AtomicU64::new(0),
+            timed: AtomicBool::new(false),
+            #[cfg(feature = "alloc_audit")]
+            allocations: Default::default(),
+        }
     }
+
+    fn count(&self, level: Level) {
+        self.records[level as usize - 1].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+static EXIT_REASON: Mutex<Option<String>> = Mutex::new(None);
+
+/// Sets the reason the process is exiting, which is included in the summary
+/// record written by `Handle::shutdown`.
+pub fn set_exit_reason<S>(reason: S)
+where
+    S: Into<String>,
+{
+    *EXIT_REASON.lock().unwrap_or_else(|e| e.into_inner()) = Some(reason.into());
 }
 
 struct SharedLogger {
-    root: ConfiguredLogger,
+    root: ArcSwap<ConfiguredLogger>,
     appenders: Vec<Appender>,
-    err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
+    heartbeats: Vec<(config::Heartbeat, Vec<usize>)>,
+    escalators: Vec<escalation::Escalator>,
+    history: Option<history::RecordHistory>,
+    kv_limits: config::KvLimits,
+    fields: Vec<(String, String)>,
+    derived_fields: Vec<config::DerivedField>,
+    // Whether records are counted by level for `Handle::shutdown`.
+    shutdown_summary: bool,
+    fingerprint: u64,
+    stats: Arc<Stats>,
+    // Dropped along with the logger, which stops the heartbeat, flush and
+    // sweep timers.
+    timers: Mutex<Vec<background::Timer>>,
+    err_handler: RwLock<Arc<dyn ErrorHandler>>,
+    // Set while the logger is quiesced for a reload, which holds back new
+    // records until the replacement is swapped in.
+    quiescing: AtomicBool,
 }
 
 impl fmt::Debug for SharedLogger {
@@ -327,34 +913,57 @@ impl fmt::Debug for SharedLogger {
 }
 
 impl SharedLogger {
-    fn new(config: config::Config) -> SharedLogger {
-        Self::new_with_err_handler(
-            config,
-            Box::new(|e: &anyhow::Error| {
-                let _ = writeln!(io::stderr(), "log4rs: {}", e);
-            }),
-        )
-    }
-    fn new_with_err_handler(
+    fn appender(&self, name: &str) -> Result<usize, HandleError> {
+        self.appenders
+            .iter()
+            .position(|appender| appender.name == name)
+            .ok_or_else(|| HandleError::NonexistentAppender(name.to_owned()))
+    }
+
+    /// Creates a logger, using `err_handler` if given, or else the handler of
+    /// the configuration, or else `fallback`.
+    fn new(
         config: config::Config,
-        err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
+        err_handler: Option<Arc<dyn ErrorHandler>>,
+        fallback: Arc<dyn ErrorHandler>,
     ) -> SharedLogger {
-        let (appenders, root, mut loggers) = config.unpack();
+        // Pin the process start time reported to encoders to the first logger.
+        encode::process_start();
+        let fingerprint = config.fingerprint();
+        let (
+            appenders,
+            root,
+            mut loggers,
+            heartbeats,
+            escalations,
+            history,
+            kv_limits,
+            fields,
+            derived_fields,
+            sanitize,
+            shutdown_summary,
+            config_err_handler,
+        ) = config.unpack();
+        let err_handler = err_handler.or(config_err_handler).unwrap_or(fallback);
+
+        let appender_map = appenders
+            .iter()
+            .enumerate()
+            .map(|(i, appender)| (appender.name(), i))
+            .collect::<HashMap<_, _>>();
 
         let root = {
-            let appender_map = appenders
+            let appenders = root
+                .appenders()
                 .iter()
-                .enumerate()
-                .map(|(i, appender)| (appender.name(), i))
-                .collect::<HashMap<_, _>>();
-
+                .map(|appender| appender_map[&**appender])
+                .collect::<Vec<_>>();
             let mut root = ConfiguredLogger {
                 level: root.level(),
-                appenders: root
-                    .appenders()
-                    .iter()
-                    .map(|appender| appender_map[&**appender])
-                    .collect(),
+                inherits_level: false,
+                appenders: appenders.clone(),
+                own_appenders: appenders,
+                additive: false,
                 children: FnvHashMap::default(),
             };
 
@@ -369,25 +978,309 @@ impl SharedLogger {
                 root.add(logger.name(), appenders, logger.additive(), logger.level());
             }
 
-            root
+            ArcSwap::from_pointee(root)
         };
 
+        let heartbeats = heartbeats
+            .into_iter()
+            .map(|heartbeat| {
+                let appenders = if heartbeat.appenders().is_empty() {
+                    (0..appenders.len()).collect()
+                } else {
+                    heartbeat
+                        .appenders()
+                        .iter()
+                        .map(|appender| appender_map[&**appender])
+                        .collect()
+                };
+                (heartbeat, appenders)
+            })
+            .collect();
+
+        let stats = Arc::new(Stats::new());
         let appenders = appenders
             .into_iter()
             .map(|appender| {
-                let (_, appender, filters) = appender.unpack();
-                Appender { appender, filters }
+                let (
+                    name,
+                    appender,
+                    filters,
+                    processors,
+                    appender_sanitize,
+                    flush_interval,
+                    monotonic_time,
+                ) = appender.unpack();
+                Appender {
+                    name,
+                    appender,
+                    filters,
+                    processors,
+                    sanitize: appender_sanitize.unwrap_or(sanitize),
+                    flush_interval,
+                    monotonic_time: monotonic_time.map(monotonic::MonotonicTime::new),
+                    threshold: AtomicUsize::new(LevelFilter::Trace as usize),
+                    paused: AtomicBool::new(false),
+                    pause: Mutex::default(),
+                    replay: Mutex::default(),
+                    flushing: Mutex::default(),
+                    counters: AppenderCounters::default(),
+                    stats: stats.clone(),
+                }
             })
             .collect();
 
         SharedLogger {
             root,
             appenders,
-            err_handler,
+            heartbeats,
+            escalators: escalations
+                .into_iter()
+                .map(escalation::Escalator::new)
+                .collect(),
+            history: history.map(history::RecordHistory::new),
+            kv_limits,
+            fields,
+            derived_fields,
+            shutdown_summary,
+            fingerprint,
+            stats,
+            timers: Mutex::default(),
+            err_handler: RwLock::new(err_handler),
+            quiescing: AtomicBool::new(false),
+        }
+    }
+
+    /// Stops new records from being dispatched by `logger`, the running
+    /// logger, then waits up to `max_wait` for those being dispatched to
+    /// finish and flushes the appenders, which drains their queues. Returns
+    /// false if records were still being dispatched when the wait ended.
+    ///
+    /// `self` must be the only other strong reference to the logger held by
+    /// the caller.
+    fn quiesce(self: &Arc<Self>, logger: &ArcSwap<SharedLogger>, max_wait: Duration) -> bool {
+        self.quiescing.store(true, Ordering::SeqCst);
+        // Records are dispatched through guards of `logger`, which are only
+        // counted as references once the logger is replaced. Replacing it
+        // with itself makes the records being dispatched countable.
+        let previous = logger.compare_and_swap(self, self.clone());
+        let held = if Arc::ptr_eq(&previous, self) { 2 } else { 1 };
+        drop(previous);
+
+        let deadline = Instant::now() + max_wait;
+        let quiesced = loop {
+            if Arc::strong_count(self) <= held {
+                break true;
+            }
+            if Instant::now() >= deadline {
+                break false;
+            }
+            thread::sleep(QUIESCE_POLL);
+        };
+        // Records logged while the appenders are flushed, for example by the
+        // appenders themselves, must not wait for the flush to finish.
+        self.quiescing.store(false, Ordering::SeqCst);
+        for appender in &self.appenders {
+            appender.flush();
+        }
+        quiesced
+    }
+
+    /// Returns the maximum level of any logger, including those raised by
+    /// active escalations, or of the history.
+    fn max_log_level(&self) -> LevelFilter {
+        self.escalators
+            .iter()
+            .map(|escalator| escalator.level())
+            .chain(self.history.as_ref().map(|history| history.level()))
+            .fold(self.root.load().max_log_level(), cmp::max)
+    }
+
+    /// Returns true if records at `level` are kept in the history.
+    fn in_history(&self, level: Level) -> bool {
+        self.history
+            .as_ref()
+            .map_or(false, |history| level <= history.level())
+    }
+
+    /// Returns true if an active escalation lets records at `level` for
+    /// `target` through, ending those whose duration has passed.
+    fn escalated(&self, target: &str, level: Level) -> bool {
+        let mut allowed = false;
+        let mut expired = false;
+        for escalator in &self.escalators {
+            if escalator.expire() {
+                expired = true;
+            } else {
+                allowed |= escalator.allows(target, level);
+            }
+        }
+        if expired {
+            log::set_max_level(self.max_log_level());
+        }
+        allowed
+    }
+
+    /// Counts a logged record towards the escalations, starting those it
+    /// triggers.
+    fn observe(&self, level: Level) {
+        let mut started = false;
+        for escalator in &self.escalators {
+            started |= escalator.observe(level);
+        }
+        if started {
+            log::set_max_level(self.max_log_level());
+        }
+    }
+
+    fn err_handler(&self) -> Arc<dyn ErrorHandler> {
+        self.err_handler
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn handle_error(&self, e: &anyhow::Error) {
+        self.err_handler().handle(e);
+    }
+
+    /// Returns the index of the appender a record names as its destination,
+    /// reporting an error if there is no such appender.
+    fn destination(&self, name: &str) -> Option<usize> {
+        let idx = self.appenders.iter().position(|a| a.name == name);
+        if idx.is_none() {
+            self.handle_error(&anyhow::anyhow!(
+                "record names unknown appender `{}` in `{}`",
+                name,
+                destination::KEY
+            ));
+        }
+        idx
+    }
+
+    /// Replaces the logger's counters with those of the logger it succeeds.
+    fn inherit_stats(&mut self, stats: &Arc<Stats>) {
+        for appender in &mut self.appenders {
+            appender.stats = stats.clone();
+        }
+        self.stats = stats.clone();
+    }
+
+    /// Moves the logger into an `Arc` and starts its heartbeat, flush and
+    /// sweep timers.
+    fn start(self) -> Arc<SharedLogger> {
+        let shared = Arc::new(self);
+
+        let mut timers = shared.timers.lock().unwrap_or_else(|e| e.into_inner());
+        for idx in 0..shared.heartbeats.len() {
+            let interval = shared.heartbeats[idx].0.interval();
+            let weak = Arc::downgrade(&shared);
+            let scheduled = background::schedule("log4rs heartbeat", interval, move || {
+                let shared = weak.upgrade()?;
+                shared.beat(idx);
+                Some(interval)
+            });
+            match scheduled {
+                Ok(timer) => timers.push(timer),
+                Err(e) => shared.handle_error(&e.into()),
+            }
+        }
+        for idx in 0..shared.appenders.len() {
+            let interval = match shared.appenders[idx].flush_interval {
+                Some(interval) => interval,
+                None => continue,
+            };
+            let weak = Arc::downgrade(&shared);
+            let scheduled = background::schedule(
+                "log4rs flush",
+                until_aligned(SystemTime::now(), interval),
+                move || {
+                    let shared = weak.upgrade()?;
+                    shared.appenders[idx].flush();
+                    Some(until_aligned(SystemTime::now(), interval))
+                },
+            );
+            match scheduled {
+                Ok(timer) => timers.push(timer),
+                Err(e) => shared.handle_error(&e.into()),
+            }
+        }
+        for idx in 0..shared.appenders.len() {
+            for filter in 0..shared.appenders[idx].filters.len() {
+                let interval = match shared.appenders[idx].filters[filter].sweep_interval() {
+                    Some(interval) => interval,
+                    None => continue,
+                };
+                let weak = Arc::downgrade(&shared);
+                let scheduled = background::schedule("log4rs sweep", interval, move || {
+                    let shared = weak.upgrade()?;
+                    if let Err(e) = shared.appenders[idx].sweep(filter) {
+                        shared.handle_error(&e);
+                    }
+                    Some(interval)
+                });
+                match scheduled {
+                    Ok(timer) => timers.push(timer),
+                    Err(e) => shared.handle_error(&e.into()),
+                }
+            }
+        }
+        drop(timers);
+
+        shared
+    }
+
+    fn beat(&self, idx: usize) {
+        let (ref heartbeat, ref appenders) = self.heartbeats[idx];
+        let fields = heartbeat
+            .fields()
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        let append = |record: &Record| {
+            for &idx in appenders {
+                if let Err(e) = self.appenders[idx].append(record) {
+                    self.handle_error(&e);
+                }
+            }
+        };
+
+        self.record_fields().with_fields(
+            &Record::builder()
+                .level(heartbeat.level())
+                .target(heartbeat.target())
+                .args(format_args!("{}", heartbeat.message()))
+                .key_values(&fields)
+                .build(),
+            append,
+        );
+    }
+
+    /// Returns the key-value fields added to the records dispatched by the
+    /// logger, and heartbeats.
+    fn record_fields(&self) -> record::RecordFields<'_> {
+        record::RecordFields {
+            defaults: &self.fields,
+            derived: &self.derived_fields,
+            limits: &self.kv_limits,
         }
     }
 }
 
+/// How often dispatch and reloads check on each other while a logger is
+/// quiesced.
+const QUIESCE_POLL: Duration = Duration::from_millis(1);
+
+/// Returns the time from `now` until the next multiple of `interval` since
+/// the Unix epoch.
+fn until_aligned(now: SystemTime, interval: Duration) -> Duration {
+    let interval = interval.as_nanos().max(1);
+    let since_epoch = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    Duration::from_nanos((interval - since_epoch % interval) as u64)
+}
+
 /// The fully configured log4rs Logger which is appropriate
 /// to use with the `log::set_boxed_logger` function.
 #[derive(Debug)]
@@ -396,44 +1289,117 @@ pub struct Logger(Arc<ArcSwap<SharedLogger>>);
 impl Logger {
     /// Create a new `Logger` given a configuration.
     pub fn new(config: config::Config) -> Logger {
-        Logger(Arc::new(ArcSwap::new(Arc::new(SharedLogger::new(config)))))
+        Logger(Arc::new(ArcSwap::new(
+            SharedLogger::new(config, None, Arc::new(StderrErrorHandler)).start(),
+        )))
     }
     /// Create a new `Logger` given a configuration and err handler.
     pub fn new_with_err_handler(
         config: config::Config,
         err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
     ) -> Logger {
-        Logger(Arc::new(ArcSwap::new(Arc::new(
-            SharedLogger::new_with_err_handler(config, err_handler),
-        ))))
+        let err_handler = Arc::new(error_handler::FnErrorHandler(err_handler));
+        Logger(Arc::new(ArcSwap::new(
+            SharedLogger::new(config, Some(err_handler), Arc::new(StderrErrorHandler)).start(),
+        )))
+    }
+
+    /// Installs the logger as the global logger, returning a `Handle` to it.
+    pub(crate) fn install(self) -> Result<Handle, log::SetLoggerError> {
+        log::set_max_level(self.max_log_level());
+        let handle = Handle {
+            shared: self.0.clone(),
+        };
+        let err_handler = self.0.load().err_handler();
+        log::set_boxed_logger(Box::new(self))?;
+        error_handler::set_global(err_handler);
+        Ok(handle)
     }
 
     /// Set the max log level above which everything will be filtered.
     pub fn max_log_level(&self) -> LevelFilter {
-        self.0.load().root.max_log_level()
+        self.0.load().max_log_level()
+    }
+
+    /// Loads the logger to dispatch a record with, waiting while it is
+    /// quiesced for a reload until the replacement is swapped in.
+    ///
+    /// The returned guard is what `SharedLogger::quiesce` waits for.
+    fn dispatcher(&self) -> arc_swap::Guard<Arc<SharedLogger>> {
+        loop {
+            let shared = self.0.load();
+            if !shared.quiescing.load(Ordering::SeqCst) {
+                return shared;
+            }
+            drop(shared);
+            thread::sleep(QUIESCE_POLL);
+        }
     }
 }
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        self.0
-            .load()
+        let shared = self.0.load();
+        shared
             .root
+            .load()
             .find(metadata.target())
             .enabled(metadata.level())
+            || shared.escalated(metadata.target(), metadata.level())
+            || shared.in_history(metadata.level())
     }
 
     fn log(&self, record: &log::Record) {
-        let shared = self.0.load();
-        if let Err(errs) = shared
-            .root
-            .find(record.target())
-            .log(record, &shared.appenders)
-        {
+        #[cfg(feature = "alloc_audit")]
+        let scope = alloc_audit::Scope::start();
+
+        let shared = self.dispatcher();
+        if let Some(ref history) = shared.history {
+            history.push(record);
+        }
+        let root = shared.root.load();
+        let logger = root.find(record.target());
+        let enabled =
+            logger.enabled(record.level()) || shared.escalated(record.target(), record.level());
+        let result = destination::with_destination(record, |record, destination| {
+            let destination = destination.and_then(|name| shared.destination(name));
+            if !enabled && destination.is_none() {
+                return Ok(());
+            }
+
+            if shared.shutdown_summary {
+                shared.stats.count(record.level());
+            }
+            shared.observe(record.level());
+            encode::with_unnumbered_record(|| {
+                shared.record_fields().with_fields(record, |record| {
+                    let mut result = if enabled {
+                        logger.log(record, &shared.appenders)
+                    } else {
+                        Ok(())
+                    };
+                    if let Some(idx) = destination {
+                        if !enabled || !logger.appenders.contains(&idx) {
+                            if let Err(e) = shared.appenders[idx].append(record) {
+                                match result {
+                                    Ok(()) => result = Err(vec![e]),
+                                    Err(ref mut errs) => errs.push(e),
+                                }
+                            }
+                        }
+                    }
+                    result
+                })
+            })
+        });
+        if let Err(errs) = result {
             for e in errs {
-                (shared.err_handler)(&e)
+                shared.handle_error(&e)
             }
         }
+
+        #[cfg(feature = "alloc_audit")]
+        shared.stats.allocations.add(scope);
     }
 
     fn flush(&self) {
@@ -444,7 +1410,7 @@ impl log::Log for Logger {
 }
 
 pub(crate) fn handle_error(e: &anyhow::Error) {
-    let _ = writeln!(io::stderr(), "log4rs: {}", e);
+    error_handler::handle_global(e);
 }
 
 /// A handle to the active logger.
@@ -456,22 +1422,1789 @@ pub struct Handle {
 impl Handle {
     /// Sets the logging configuration.
     pub fn set_config(&self, config: Config) {
-        let shared = SharedLogger::new(config);
-        log::set_max_level(shared.root.max_log_level());
-        self.shared.store(Arc::new(shared));
+        let current = self.shared.load();
+        let mut shared = SharedLogger::new(config, None, current.err_handler());
+        shared.inherit_stats(&current.stats);
+        error_handler::set_global(shared.err_handler());
+        log::set_max_level(shared.max_log_level());
+        self.shared.store(shared.start());
     }
-}
 
-trait ErrorInternals {
-    fn new(message: String) -> Self;
+    /// Sets the logging configuration after quiescing the running one, so
+    /// that every record is written entirely under one configuration.
+    ///
+    /// Records logged from now on are held back while records already being
+    /// dispatched are given up to `max_wait` to finish. Then the running
+    /// configuration's appenders are flushed, which drains the queues of
+    /// background appenders, and only then is the new configuration swapped
+    /// in. Records logged during the flush, for example by the appenders
+    /// themselves, are written under the running configuration rather than
+    /// held back. Returns false if records were still being dispatched under
+    /// the old configuration when `max_wait` ran out; the new configuration
+    /// is applied regardless.
+    pub fn set_config_quiesced(&self, config: Config, max_wait: Duration) -> bool {
+        let current = self.shared.load_full();
+        let mut shared = SharedLogger::new(config, None, current.err_handler());
+        shared.inherit_stats(&current.stats);
+        let shared = shared.start();
+
+        let quiesced = current.quiesce(&self.shared, max_wait);
+        error_handler::set_global(shared.err_handler());
+        log::set_max_level(shared.max_log_level());
+        self.shared.store(shared);
+        quiesced
+    }
+
+    /// Returns the fingerprint of the running configuration, as returned by
+    /// `Config::fingerprint`.
+    pub fn config_fingerprint(&self) -> u64 {
+        self.shared.load().fingerprint
+    }
+
+    /// Sets the handler of errors encountered while logging, such as an
+    /// appender failing to write a record, replacing the one set by the
+    /// configuration.
+    ///
+    /// The handler is kept by later calls to `set_config` unless their
+    /// configuration sets one.
+    pub fn set_error_handler(&self, err_handler: Arc<dyn ErrorHandler>) {
+        *self
+            .shared
+            .load()
+            .err_handler
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = err_handler.clone();
+        error_handler::set_global(err_handler);
+    }
+
+    /// Sets the logging configuration from a string in the specified format,
+    /// such as one received over an admin API.
+    ///
+    /// The string is handled like a config file, except that files it
+    /// includes are resolved relative to the current directory and its
+    /// refresh rate is ignored. Components are created from the default
+    /// `Deserializers`. An error is returned, and the running configuration
+    /// kept, if the string cannot be parsed; errors in individual components
+    /// are reported to stderr and the components skipped.
+    ///
+    /// Requires the `config_parsing` feature.
+    #[cfg(feature = "config_parsing")]
+    pub fn set_config_from_str(&self, source: &str, format: config::Format) -> anyhow::Result<()> {
+        let config = config::load_config_str(source, format, &config::Deserializers::default())?;
+        self.set_config(config);
+        Ok(())
+    }
+
+    /// Sets the logging configuration from a reader in the specified format,
+    /// such as a mounted ConfigMap object.
+    ///
+    /// The reader is read to its end, then handled as by
+    /// `set_config_from_str`.
+    ///
+    /// Requires the `config_parsing` feature.
+    #[cfg(feature = "config_parsing")]
+    pub fn set_config_from_reader<R>(
+        &self,
+        mut reader: R,
+        format: config::Format,
+    ) -> anyhow::Result<()>
+    where
+        R: Read,
+    {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        self.set_config_from_str(&source, format)
+    }
+
+    /// Sets the level of the logger for the specified target, along with all
+    /// loggers below it which do not have a level of their own. An empty
+    /// target sets the level of the root logger.
+    ///
+    /// The logger is created if the configuration does not contain one for
+    /// the target, inheriting its appenders from its parent. The change does
+    /// not survive a call to `set_config`.
+    pub fn set_logger_level(&self, target: &str, level: LevelFilter) {
+        let shared = self.shared.load();
+        shared.root.rcu(|root| {
+            let mut root = ConfiguredLogger::clone(root);
+            root.set_level(target, level);
+            root
+        });
+        log::set_max_level(shared.max_log_level());
+    }
+
+    /// Sets the level of the logger for the specified target, as by
+    /// `set_logger_level`, until the returned guard is dropped.
+    ///
+    /// Dropping the guard restores the level the logger had before, or makes
+    /// it take its level from its parent again if it had none of its own. If
+    /// the configuration has been replaced in the meantime, the guard does
+    /// nothing.
+    pub fn scoped_level(&self, target: &str, level: LevelFilter) -> LevelGuard {
+        let shared = self.shared.load();
+        let previous = shared.root.load().configured_level(target);
+        self.set_logger_level(target, level);
+        LevelGuard {
+            handle: Arc::downgrade(&self.shared),
+            logger: Arc::downgrade(&shared),
+            target: target.to_owned(),
+            previous,
+        }
+    }
+
+    /// Sets the level of the logger for the specified target for `duration`,
+    /// after which it is restored as by dropping the guard returned by
+    /// `scoped_level`, so that verbose logging turned on during an incident
+    /// cannot be left on by mistake.
+    ///
+    /// The level is restored in the background. If that cannot be started,
+    /// the level is restored immediately and the error is returned.
+    pub fn with_level(
+        &self,
+        target: &str,
+        level: LevelFilter,
+        duration: Duration,
+    ) -> io::Result<()> {
+        let mut guard = Some(self.scoped_level(target, level));
+        background::schedule("log4rs level", duration, move || {
+            guard.take();
+            None
+        })?
+        .detach();
+        Ok(())
+    }
+
+    /// Attaches the appender with the specified name to the logger for the
+    /// specified target, so that records logged to it and, if they are
+    /// additive, to the loggers below it are written to the appender too. An
+    /// empty target attaches the appender to the root logger.
+    ///
+    /// The logger is created if the configuration does not contain one for
+    /// the target, as by `set_logger_level`. The change does not survive a
+    /// call to `set_config`, so a debug file can be attached during an
+    /// incident and dropped again by reloading the configuration.
+    pub fn attach_appender(&self, target: &str, appender: &str) -> Result<(), HandleError> {
+        let shared = self.shared.load();
+        let idx = shared.appender(appender)?;
+        shared.root.rcu(|root| {
+            let mut root = ConfiguredLogger::clone(root);
+            root.update_appenders(target, |logger| {
+                if !logger.own_appenders.contains(&idx) {
+                    logger.own_appenders.push(idx);
+                }
+            });
+            root
+        });
+        Ok(())
+    }
+
+    /// Detaches the appender with the specified name from the logger for the
+    /// specified target. An empty target detaches it from the root logger.
+    ///
+    /// Only appenders attached to the logger itself are detached; to stop a
+    /// logger inheriting the appenders of its parent, make it non-additive
+    /// with `set_logger_additive`. The change does not survive a call to
+    /// `set_config`.
+    pub fn detach_appender(&self, target: &str, appender: &str) -> Result<(), HandleError> {
+        let shared = self.shared.load();
+        let idx = shared.appender(appender)?;
+        shared.root.rcu(|root| {
+            let mut root = ConfiguredLogger::clone(root);
+            root.update_appenders(target, |logger| logger.own_appenders.retain(|&i| i != idx));
+            root
+        });
+        Ok(())
+    }
+
+    /// Sets whether the logger for the specified target writes records to the
+    /// appenders of its parent as well as to its own.
+    ///
+    /// The logger is created if the configuration does not contain one for
+    /// the target. The root logger has no parent, so setting its additivity
+    /// has no effect. The change does not survive a call to `set_config`.
+    pub fn set_logger_additive(&self, target: &str, additive: bool) {
+        self.shared.load().root.rcu(|root| {
+            let mut root = ConfiguredLogger::clone(root);
+            root.update_appenders(target, |logger| logger.additive = additive);
+            root
+        });
+    }
+
+    /// Sets the threshold of the appender with the specified name, rejecting
+    /// all records at a level below it before they reach the appender's
+    /// filters.
+    ///
+    /// Records are still subject to the levels of the loggers they are logged
+    /// to, so the threshold can only narrow what the appender receives.
+    /// Setting it to `LevelFilter::Trace` removes it. The threshold does not
+    /// survive a call to `set_config`.
+    pub fn set_appender_threshold(
+        &self,
+        appender: &str,
+        level: LevelFilter,
+    ) -> Result<(), HandleError> {
+        let shared = self.shared.load();
+        let idx = shared.appender(appender)?;
+        shared.appenders[idx].set_threshold(level);
+        Ok(())
+    }
+
+    /// Flushes the appender with the specified name, leaving all other
+    /// appenders untouched.
+    ///
+    /// The flush runs in the background; if it has not completed within
+    /// `timeout`, `HandleError::FlushTimeout` is returned and the flush
+    /// continues. While it does, further calls for the appender wait for it
+    /// rather than starting another, so that a hung appender does not hold
+    /// up more than one flush.
+    pub fn flush(&self, appender: &str, timeout: Duration) -> Result<(), HandleError> {
+        let shared = self.shared.load_full();
+        let idx = shared.appender(appender)?;
+
+        let mut flushing = shared.appenders[idx]
+            .flushing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let task = match *flushing {
+            Some(ref task) if !task.is_finished() => task.clone(),
+            _ => {
+                let shared = shared.clone();
+                let task =
+                    background::spawn("log4rs flush", move || shared.appenders[idx].flush())?;
+                *flushing = Some(task.clone());
+                task
+            }
+        };
+        drop(flushing);
+
+        if task.join_timeout(timeout) {
+            Ok(())
+        } else {
+            Err(HandleError::FlushTimeout(appender.to_owned()))
+        }
+    }
+
+    /// Pauses the appender with the specified name.
+    ///
+    /// Records accepted by the appender's filters are buffered or dropped
+    /// according to `mode` until the appender is resumed. Pausing an already
+    /// paused appender only changes its mode. The pause does not survive a
+    /// call to `set_config`.
+    pub fn pause(&self, appender: &str, mode: PauseMode) -> Result<(), HandleError> {
+        let shared = self.shared.load();
+        let idx = shared.appender(appender)?;
+        shared.appenders[idx].pause(mode);
+        Ok(())
+    }
+
+    /// Resumes the appender with the specified name.
+    ///
+    /// Records buffered while the appender was paused are written to it, in
+    /// order, before this method returns, and records logged meanwhile are
+    /// held until they have been. They keep their key-value fields
+    /// and the time they were logged at, which is also added as a `logged_at`
+    /// field. Errors writing them are reported to the logger's error handler.
+    pub fn resume(&self, appender: &str) -> Result<PauseStats, HandleError> {
+        let shared = self.shared.load();
+        let idx = shared.appender(appender)?;
+        let appender = &shared.appenders[idx];
+
+        let (replayed, dropped) = appender.resume(|logged, record| {
+            let logged_at = history::format_time(logged);
+            let fields = [("logged_at", logged_at.as_str())];
+            let result = encode::with_record_time(logged, || {
+                record.with_record_fields(&fields, |record| appender.write(record))
+            });
+            if let Err(e) = result {
+                shared.handle_error(&e);
+            }
+        });
+
+        Ok(PauseStats { replayed, dropped })
+    }
+
+    /// Writes a summary of the logger's lifetime to every appender if the
+    /// configuration enables `shutdown_summary`, then flushes them. Call this
+    /// as the process exits.
+    ///
+    /// The summary is an info level record with the target `log4rs::summary`
+    /// and the message `shutdown`. Its key-value fields hold the uptime in
+    /// seconds (`uptime`), the number of records logged at each level
+    /// (`error`, `warn`, `info`, `debug` and `trace`), the number of records
+    /// dropped by paused appenders (`dropped`), the fingerprint of the
+    /// configuration in hexadecimal (`config_fingerprint`), and the reason
+    /// given to `set_exit_reason` if any (`exit_reason`). It bypasses the
+    /// loggers, and the thresholds, filters and pauses of the appenders.
+    /// Records are only counted while the running configuration enables the
+    /// summary.
+    pub fn shutdown(&self) {
+        let shared = self.shared.load();
+        if !shared.shutdown_summary {
+            for appender in &shared.appenders {
+                appender.flush();
+            }
+            return;
+        }
+        let stats = &shared.stats;
+
+        let uptime = format!("{:.3}", stats.started.elapsed().as_secs_f64());
+        let counts = Level::iter()
+            .map(|level| {
+                let count = stats.records[level as usize - 1].load(Ordering::Relaxed);
+                (level.as_str().to_lowercase(), count.to_string())
+            })
+            .collect::<Vec<_>>();
+        let dropped = stats.dropped.load(Ordering::Relaxed).to_string();
+        let fingerprint = format!("{:016x}", shared.fingerprint);
+        let exit_reason = EXIT_REASON
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        let mut fields = vec![("uptime", uptime.as_str())];
+        fields.extend(counts.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        fields.push(("dropped", &dropped));
+        fields.push(("config_fingerprint", &fingerprint));
+        if let Some(ref exit_reason) = exit_reason {
+            fields.push(("exit_reason", exit_reason));
+        }
+
+        let record = Record::builder()
+            .level(Level::Info)
+            .target("log4rs::summary")
+            .args(format_args!("shutdown"))
+            .key_values(&fields)
+            .build();
+        for appender in &shared.appenders {
+            if let Err(e) = appender.write(&record) {
+                shared.handle_error(&e);
+            }
+            appender.flush();
+        }
+    }
+
+    /// Writes the records kept in the history to a file or appender, returning
+    /// the number written.
+    ///
+    /// Only records accepted by `filter`, if given, are written. Records are
+    /// passed to an appender as they were logged, bypassing its thresholds,
+    /// filters and pauses, with the time they were logged at attached as the
+    /// `logged_at` key-value field. A file is created or truncated and holds
+    /// one line per record with the time, level, target and message. Nothing
+    /// is written if the configuration has no history.
+    pub fn dump_recent(
+        &self,
+        dump: Dump,
+        filter: Option<&dyn filter::Filter>,
+    ) -> Result<usize, HandleError> {
+        let shared = self.shared.load();
+        let records = match shared.history {
+            Some(ref history) => history.records(),
+            None => return Ok(0),
+        };
+        let records = records
+            .iter()
+            .filter(|(_, record)| match filter {
+                Some(filter) => {
+                    record.with_record(|record| filter.filter(record) != filter::Response::Reject)
+                }
+                None => true,
+            })
+            .map(|(logged, record)| (history::format_time(*logged), record));
+
+        let mut count = 0;
+        match dump {
+            Dump::Path(path) => {
+                let mut file = io::BufWriter::new(fs::File::create(path)?);
+                for (logged, record) in records {
+                    record.with_record(|record| {
+                        writeln!(
+                            file,
+                            "{} {:<5} {} - {}",
+                            logged,
+                            record.level(),
+                            record.target(),
+                            record.args()
+                        )
+                    })?;
+                    count += 1;
+                }
+                file.flush()?;
+            }
+            Dump::Appender(name) => {
+                let appender = &shared.appenders[shared.appender(&name)?];
+                for (logged, record) in records {
+                    let fields = [("logged_at", logged.as_str())];
+                    if let Err(e) =
+                        record.with_record_fields(&fields, |record| appender.write(record))
+                    {
+                        shared.handle_error(&e);
+                    }
+                    count += 1;
+                }
+                appender.flush();
+            }
+        }
+        Ok(count)
+    }
+
+    /// Returns statistics about the heap allocations made while logging
+    /// records.
+    ///
+    /// Allocations are only counted while `alloc_audit::CountingAllocator` is
+    /// the global allocator. The statistics survive calls to `set_config`.
+    ///
+    /// Requires the `alloc_audit` feature.
+    #[cfg(feature = "alloc_audit")]
+    pub fn allocation_stats(&self) -> alloc_audit::AllocationStats {
+        self.shared.load().stats.allocations.stats()
+    }
+
+    /// Resets the statistics returned by `allocation_stats`.
+    ///
+    /// Requires the `alloc_audit` feature.
+    #[cfg(feature = "alloc_audit")]
+    pub fn reset_allocation_stats(&self) {
+        self.shared.load().stats.allocations.reset();
+    }
+
+    /// Returns the counters of each appender, in the order they are
+    /// configured.
+    ///
+    /// Records written by `resume`, `shutdown` and `dump_recent` are counted
+    /// as well. The counters start over when `set_config` is called.
+    pub fn stats(&self) -> Vec<AppenderStats> {
+        self.shared
+            .load()
+            .appenders
+            .iter()
+            .map(Appender::stats)
+            .collect()
+    }
+
+    /// Returns the state of each appender, in the order they are configured,
+    /// for example to report on a readiness endpoint whether logging works.
+    ///
+    /// Like the counters of `stats`, the times of the last write and error
+    /// start over when `set_config` is called. Writes are only timed once
+    /// this has been called, so that loggers nobody checks on don't read the
+    /// clock for every record, and the first call reports no last write.
+    pub fn health(&self) -> Vec<AppenderHealth> {
+        let shared = self.shared.load();
+        shared.stats.timed.store(true, Ordering::Relaxed);
+        shared.appenders.iter().map(Appender::health).collect()
+    }
+
+    /// Passes the result of `stats` to `export` every `interval`, for example
+    /// to publish the counters as metrics.
+    ///
+    /// The export runs in the background until the returned `StatsExport` is
+    /// dropped.
+    pub fn export_stats<F>(&self, interval: Duration, export: F) -> io::Result<StatsExport>
+    where
+        F: Fn(&[AppenderStats]) + Send + 'static,
+    {
+        let weak = Arc::downgrade(&self.shared);
+        let timer = background::schedule("log4rs stats", interval, move || {
+            let shared = weak.upgrade()?;
+            export(&Handle { shared }.stats());
+            Some(interval)
+        })?;
+        Ok(StatsExport { _timer: timer })
+    }
+
+    /// Rotates the output of the appender with the specified name
+    /// immediately, rather than when the next record triggers it.
+    ///
+    /// Returns `HandleError::Unsupported` if the appender does not rotate its
+    /// output. The rolling file appender supports this, with the compound
+    /// policy.
+    pub fn roll_now(&self, appender: &str) -> Result<(), HandleError> {
+        let shared = self.shared.load();
+        let idx = shared.appender(appender)?;
+        match shared.appenders[idx].appender.roll_now() {
+            Some(r) => r.map_err(|e| HandleError::Appender(appender.to_owned(), e)),
+            None => Err(HandleError::Unsupported(appender.to_owned())),
+        }
+    }
+
+    /// Writes out the records held back in memory by the appender with the
+    /// specified name.
+    ///
+    /// Returns `HandleError::Unsupported` if the appender does not hold back
+    /// records. The ring buffer appender supports this, writing its records to
+    /// its target appender.
+    pub fn dump_buffer(&self, appender: &str) -> Result<(), HandleError> {
+        let shared = self.shared.load();
+        let idx = shared.appender(appender)?;
+        match shared.appenders[idx].appender.dump() {
+            Some(r) => r.map_err(|e| HandleError::Appender(appender.to_owned(), e)),
+            None => Err(HandleError::Unsupported(appender.to_owned())),
+        }
+    }
+
+    /// Reads back the last portion of the output of the appender with the
+    /// specified name.
+    ///
+    /// Returns `HandleError::Unsupported` if the appender cannot read back its
+    /// output. The file appenders support this.
+    pub fn tail(&self, appender: &str, tail: append::Tail) -> Result<Vec<u8>, HandleError> {
+        let shared = self.shared.load();
+        let idx = shared.appender(appender)?;
+        match shared.appenders[idx].appender.tail(tail) {
+            Some(r) => Ok(r?),
+            None => Err(HandleError::Unsupported(appender.to_owned())),
+        }
+    }
+}
+
+/// What a paused appender does with the records it receives.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum PauseMode {
+    /// Buffer up to the specified number of records, discarding the oldest
+    /// records once the buffer is full.
+    Buffer(usize),
+    /// Drop all records.
+    Drop,
+}
+
+/// Where `Handle::dump_recent` writes the history.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Dump {
+    /// A file at the specified path.
+    Path(PathBuf),
+    /// The appender with the specified name.
+    Appender(String),
+}
+
+/// Statistics about a pause, returned when the appender is resumed.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub struct PauseStats {
+    /// The number of buffered records written to the appender on resume.
+    pub replayed: usize,
+    /// The number of records dropped while the appender was paused.
+    pub dropped: u64,
+}
+
+/// The counters of an appender, returned by `Handle::stats`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub struct AppenderStats {
+    /// The name of the appender.
+    pub name: String,
+    /// The number of records written to the appender.
+    pub written: u64,
+    /// The number of records the appender returned an error for.
+    pub errors: u64,
+    /// The number of records rejected by the appender's threshold or filters.
+    pub filtered: u64,
+    /// The number of records dropped while the appender was paused, or by
+    /// the appender itself, for example because its buffer was full.
+    pub dropped: u64,
+    /// The number of bytes written by the appender, if it counts them.
+    pub bytes_written: Option<u64>,
+    /// The number of times the appender rotated its output, if it does.
+    pub rotations: Option<u64>,
+    /// The number of times the appender synced its output to disk, if it
+    /// does.
+    pub syncs: Option<u64>,
+    /// The total time the appender spent syncing its output to disk, if it
+    /// does.
+    pub sync_time: Option<Duration>,
+}
+
+/// The state of an appender, returned by `Handle::health`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub struct AppenderHealth {
+    /// The name of the appender.
+    pub name: String,
+    /// Whether the appender is paused.
+    pub paused: bool,
+    /// When the appender last wrote a record, if it has written one since
+    /// `Handle::health` was first called.
+    pub last_write: Option<SystemTime>,
+    /// When the appender last returned an error for a record, and the error.
+    pub last_error: Option<(SystemTime, String)>,
+    /// Whether the appender is connected to the service it sends records to,
+    /// if it sends them to one. Appenders which connect when they first send
+    /// a record are not connected until then.
+    pub connected: Option<bool>,
+    /// The number of records the appender has queued to be written in the
+    /// background, if it queues them.
+    pub queue_depth: Option<usize>,
+}
+
+impl AppenderHealth {
+    /// Returns true if the last record the appender was given failed, rather
+    /// than being written.
+    pub fn is_failing(&self) -> bool {
+        match (&self.last_error, self.last_write) {
+            (Some((failed, _)), Some(written)) => *failed >= written,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// A temporary level of a logger set by `Handle::scoped_level`.
+///
+/// The level is restored when this is dropped.
+#[derive(Debug)]
+pub struct LevelGuard {
+    handle: Weak<ArcSwap<SharedLogger>>,
+    // The configuration the level was set in.
+    logger: Weak<SharedLogger>,
+    target: String,
+    previous: Option<LevelFilter>,
+}
+
+impl Drop for LevelGuard {
+    fn drop(&mut self) {
+        let handle = match self.handle.upgrade() {
+            Some(handle) => handle,
+            None => return,
+        };
+        let shared = handle.load();
+        if Arc::as_ptr(&shared) != self.logger.as_ptr() {
+            return;
+        }
+
+        shared.root.rcu(|root| {
+            let mut root = ConfiguredLogger::clone(root);
+            match self.previous {
+                Some(level) => root.set_level(&self.target, level),
+                None => root.clear_level(&self.target),
+            }
+            root
+        });
+        log::set_max_level(shared.max_log_level());
+    }
+}
+
+/// A periodic export of appender counters started by `Handle::export_stats`.
+///
+/// The export stops when this is dropped.
+#[derive(Debug)]
+pub struct StatsExport {
+    _timer: background::Timer,
+}
+
+/// Errors returned by operations on a `Handle`.
+#[derive(Debug, Error)]
+pub enum HandleError {
+    /// No appender with the specified name is configured.
+    #[error("Reference to nonexistent appender: `{0}`")]
+    NonexistentAppender(String),
+
+    /// The appender did not finish flushing in time.
+    #[error("Timed out flushing appender `{0}`")]
+    FlushTimeout(String),
+
+    /// The appender does not support the operation.
+    #[error("Appender `{0}` does not support this operation")]
+    Unsupported(String),
+
+    /// The appender returned an error.
+    #[error("Appender `{0}` failed: {1}")]
+    Appender(String, anyhow::Error),
+
+    /// An I/O error.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[doc(hidden)]
+    #[error("Reserved for future use")]
+    __Extensible,
 }
 
 #[cfg(test)]
 mod test {
     use log::{Level, LevelFilter, Log};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    };
 
     use super::*;
 
+    #[derive(Debug, Default)]
+    struct FlushCounter(Arc<AtomicUsize>);
+
+    impl Append for FlushCounter {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct MessageCollector(Arc<Mutex<Vec<String>>>);
+
+    impl Append for MessageCollector {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(record.args().to_string());
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn log_message(logger: &super::Logger, message: &str) {
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .build("a", Box::new(MessageCollector(messages.clone()))),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        handle.pause("a", PauseMode::Buffer(2)).unwrap();
+        log_message(&logger, "one");
+        log_message(&logger, "two");
+        log_message(&logger, "three");
+        assert!(messages.lock().unwrap().is_empty());
+
+        let stats = handle.resume("a").unwrap();
+        assert_eq!(stats.replayed, 2);
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(*messages.lock().unwrap(), ["two", "three"]);
+
+        handle.pause("a", PauseMode::Drop).unwrap();
+        log_message(&logger, "four");
+        let stats = handle.resume("a").unwrap();
+        assert_eq!(stats.replayed, 0);
+        assert_eq!(stats.dropped, 1);
+
+        log_message(&logger, "five");
+        assert_eq!(*messages.lock().unwrap(), ["two", "three", "five"]);
+    }
+
+    #[test]
+    fn resume_in_order() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .build("a", Box::new(MessageCollector(messages.clone()))),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = Arc::new(super::Logger::new(config));
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        handle.pause("a", PauseMode::Buffer(100_000)).unwrap();
+        for i in 0..10_000 {
+            log_message(&logger, &i.to_string());
+        }
+
+        let resumed = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let logger = logger.clone();
+            let resumed = resumed.clone();
+            thread::spawn(move || {
+                let mut i = 10_000;
+                while !resumed.load(Ordering::SeqCst) {
+                    log_message(&logger, &i.to_string());
+                    i += 1;
+                }
+            })
+        };
+        handle.resume("a").unwrap();
+        resumed.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+
+        let messages = messages.lock().unwrap();
+        assert!(messages.len() >= 10_000);
+        for (i, message) in messages.iter().enumerate() {
+            assert_eq!(*message, i.to_string());
+        }
+    }
+
+    #[derive(Debug)]
+    struct FieldCollector(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl Append for FieldCollector {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            struct Visitor<'a>(&'a mut Vec<(String, String)>);
+
+            impl<'kvs> log::kv::VisitSource<'kvs> for Visitor<'_> {
+                fn visit_pair(
+                    &mut self,
+                    key: log::kv::Key<'kvs>,
+                    value: log::kv::Value<'kvs>,
+                ) -> Result<(), log::kv::Error> {
+                    self.0.push((key.to_string(), value.to_string()));
+                    Ok(())
+                }
+            }
+
+            let mut fields = self.0.lock().unwrap();
+            fields.clear();
+            record.key_values().visit(&mut Visitor(&mut fields))?;
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn resume_keeps_fields_and_time() {
+        let fields = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder().build("a", Box::new(FieldCollector(fields.clone()))),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        handle.pause("a", PauseMode::Buffer(1)).unwrap();
+        let logged = UNIX_EPOCH + Duration::from_millis(1_500);
+        encode::with_record_time(logged, || {
+            logger.log(
+                &Record::builder()
+                    .level(Level::Info)
+                    .key_values(&[("user", "alice")])
+                    .build(),
+            )
+        });
+        handle.resume("a").unwrap();
+
+        assert_eq!(
+            *fields.lock().unwrap(),
+            [
+                ("user".to_owned(), "alice".to_owned()),
+                (
+                    "logged_at".to_owned(),
+                    "1970-01-01T00:00:01.500Z".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn shutdown_summary() {
+        let fields = Arc::new(Mutex::new(vec![]));
+        let config = || {
+            config::Config::builder()
+                .appender(
+                    config::Appender::builder()
+                        .build("a", Box::new(FieldCollector(fields.clone()))),
+                )
+                .shutdown_summary(true)
+                .build(
+                    config::Root::builder()
+                        .appender("a")
+                        .build(LevelFilter::Info),
+                )
+                .unwrap()
+        };
+        let logger = super::Logger::new(config());
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        log_message(&logger, "one");
+        handle.pause("a", PauseMode::Drop).unwrap();
+        log_message(&logger, "two");
+        handle.resume("a").unwrap();
+        handle.set_config(config());
+        logger.log(&Record::builder().level(Level::Error).build());
+        logger.log(&Record::builder().level(Level::Debug).build());
+        set_exit_reason("done");
+        handle.shutdown();
+
+        let fields = fields.lock().unwrap();
+        let field = |key: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        assert!(field("uptime").is_some());
+        assert_eq!(field("error"), Some("1"));
+        assert_eq!(field("info"), Some("2"));
+        assert_eq!(field("debug"), Some("0"));
+        assert_eq!(field("dropped"), Some("1"));
+        assert_eq!(
+            field("config_fingerprint"),
+            Some(format!("{:016x}", config().fingerprint()).as_str())
+        );
+        assert_eq!(field("exit_reason"), Some("done"));
+    }
+
+    #[test]
+    fn no_shutdown_summary() {
+        let fields = Arc::new(Mutex::new(vec![("unset".to_owned(), String::new())]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder().build("a", Box::new(FieldCollector(fields.clone()))),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        log_message(&logger, "one");
+        assert_eq!(
+            logger.0.load().stats.records[Level::Info as usize - 1].load(Ordering::Relaxed),
+            0
+        );
+        *fields.lock().unwrap() = vec![("unset".to_owned(), String::new())];
+        handle.shutdown();
+        assert_eq!(fields.lock().unwrap()[0].0, "unset");
+    }
+
+    #[derive(Debug)]
+    struct SeqCollector(Arc<Mutex<Vec<(u64, u64)>>>);
+
+    impl Append for SeqCollector {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            let seqs = (encode::record_seq(), encode::record_seq());
+            self.0.lock().unwrap().push(seqs);
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn seq_numbered_on_demand() {
+        let seqs = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("a", Box::new(SeqCollector(seqs.clone()))))
+            .appender(config::Appender::builder().build("b", Box::new(SeqCollector(seqs.clone()))))
+            .build(
+                config::Root::builder()
+                    .appenders(["a", "b"])
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+
+        log_message(&logger, "one");
+        log_message(&logger, "two");
+
+        // every appender sees the number given on the first call
+        let seqs = seqs.lock().unwrap();
+        assert_eq!(seqs.len(), 4);
+        assert!(seqs.iter().all(|&(a, b)| a == b));
+        assert_eq!(seqs[0], seqs[1]);
+        assert_eq!(seqs[2], seqs[3]);
+        assert!(seqs[2].0 > seqs[0].0);
+    }
+
+    #[test]
+    fn runtime_levels() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .build("a", Box::new(MessageCollector(messages.clone()))),
+            )
+            .logger(config::Logger::builder().build("foo::bar", LevelFilter::Error))
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+        let enabled = |level, target| {
+            logger.enabled(&Metadata::builder().level(level).target(target).build())
+        };
+
+        handle.set_logger_level("foo", LevelFilter::Trace);
+        assert!(enabled(Level::Trace, "foo"));
+        assert!(enabled(Level::Trace, "foo::baz"));
+        assert!(!enabled(Level::Warn, "foo::bar"));
+        assert!(!enabled(Level::Debug, "qux"));
+        assert_eq!(logger.max_log_level(), LevelFilter::Trace);
+
+        handle.set_logger_level("", LevelFilter::Warn);
+        assert!(!enabled(Level::Info, "qux"));
+        assert!(enabled(Level::Trace, "foo"));
+
+        handle
+            .set_appender_threshold("a", LevelFilter::Warn)
+            .unwrap();
+        for level in [Level::Info, Level::Warn] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("foo")
+                    .args(format_args!("{}", level))
+                    .build(),
+            );
+        }
+        assert_eq!(*messages.lock().unwrap(), ["WARN"]);
+
+        assert!(matches!(
+            handle.set_appender_threshold("b", LevelFilter::Warn),
+            Err(HandleError::NonexistentAppender(_))
+        ));
+    }
+
+    #[test]
+    fn scoped_levels() {
+        let config = config::Config::builder()
+            .logger(config::Logger::builder().build("foo::bar", LevelFilter::Error))
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+        let enabled = |level, target| {
+            logger.enabled(&Metadata::builder().level(level).target(target).build())
+        };
+
+        let guard = handle.scoped_level("foo", LevelFilter::Trace);
+        let bar = handle.scoped_level("foo::bar", LevelFilter::Debug);
+        assert!(enabled(Level::Trace, "foo"));
+        assert!(enabled(Level::Debug, "foo::bar"));
+        drop(guard);
+        drop(bar);
+        assert!(!enabled(Level::Debug, "foo"));
+        assert!(!enabled(Level::Warn, "foo::bar"));
+
+        // the logger follows its parent again
+        handle.set_logger_level("", LevelFilter::Warn);
+        assert!(!enabled(Level::Info, "foo"));
+
+        handle
+            .with_level("foo", LevelFilter::Trace, Duration::from_millis(10))
+            .unwrap();
+        assert!(enabled(Level::Trace, "foo"));
+        let start = Instant::now();
+        while enabled(Level::Trace, "foo") {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(enabled(Level::Warn, "foo"));
+    }
+
+    #[test]
+    fn runtime_appenders() {
+        let (a, b) = (Arc::new(Mutex::new(vec![])), Arc::new(Mutex::new(vec![])));
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("a", Box::new(MessageCollector(a.clone()))))
+            .appender(config::Appender::builder().build("b", Box::new(MessageCollector(b.clone()))))
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+        let log = |target, message| {
+            logger.log(
+                &Record::builder()
+                    .level(Level::Info)
+                    .target(target)
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+        };
+
+        handle.attach_appender("foo", "b").unwrap();
+        log("foo::bar", "one");
+        log("qux", "two");
+        handle.set_logger_additive("foo", false);
+        log("foo::bar", "three");
+        handle.detach_appender("foo", "b").unwrap();
+        handle.set_logger_additive("foo", true);
+        log("foo::bar", "four");
+        handle.attach_appender("", "b").unwrap();
+        log("qux", "five");
+
+        assert_eq!(*a.lock().unwrap(), ["one", "two", "four", "five"]);
+        assert_eq!(*b.lock().unwrap(), ["one", "three", "five"]);
+
+        assert!(matches!(
+            handle.attach_appender("foo", "c"),
+            Err(HandleError::NonexistentAppender(_))
+        ));
+    }
+
+    #[cfg(feature = "alloc_audit")]
+    #[global_allocator]
+    static ALLOCATOR: alloc_audit::CountingAllocator = alloc_audit::CountingAllocator;
+
+    #[test]
+    #[cfg(feature = "alloc_audit")]
+    fn allocation_audit() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("a", Box::<FlushCounter>::default()))
+            .appender(
+                config::Appender::builder()
+                    .build("b", Box::new(MessageCollector(messages.clone()))),
+            )
+            .logger(
+                config::Logger::builder()
+                    .appender("b")
+                    .build("collected", LevelFilter::Info),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+        let log = |level, target| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(format_args!("{} {}", level, target))
+                    .build(),
+            );
+        };
+
+        // The first call on a thread sets up arc-swap's thread local state
+        log(Level::Info, "fast");
+        handle.reset_allocation_stats();
+
+        // The fast path must not allocate
+        log(Level::Info, "fast");
+        log(Level::Debug, "fast");
+        let stats = handle.allocation_stats();
+        assert_eq!(stats.records, 2);
+        assert_eq!(stats.allocations, 0);
+        assert_eq!(stats.max_allocations, 0);
+
+        handle.reset_allocation_stats();
+        log(Level::Info, "collected");
+        let stats = handle.allocation_stats();
+        assert_eq!(stats.records, 1);
+        assert!(stats.allocations > 0);
+        assert!(stats.bytes > 0);
+        assert_eq!(stats.max_allocations, stats.allocations);
+    }
+
+    #[derive(Debug)]
+    struct TargetFilter(&'static str);
+
+    impl filter::Filter for TargetFilter {
+        fn filter(&self, record: &Record) -> filter::Response {
+            if record.target() == self.0 {
+                filter::Response::Neutral
+            } else {
+                filter::Response::Reject
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct FailingAppender;
+
+    impl Append for FailingAppender {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            anyhow::bail!("failed")
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn error_handler() {
+        let config = |err_handler: Option<Arc<dyn ErrorHandler>>| {
+            let mut builder = config::Config::builder()
+                .appender(config::Appender::builder().build("a", Box::new(FailingAppender)));
+            if let Some(err_handler) = err_handler {
+                builder = builder.error_handler(err_handler);
+            }
+            builder
+                .build(
+                    config::Root::builder()
+                        .appender("a")
+                        .build(LevelFilter::Info),
+                )
+                .unwrap()
+        };
+        let first = Arc::new(error_handler::CountingErrorHandler::new());
+        let logger = super::Logger::new(config(Some(first.clone())));
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        log_message(&logger, "one");
+        assert_eq!(first.count(), 1);
+
+        handle.set_config(config(None));
+        log_message(&logger, "two");
+        assert_eq!(first.count(), 2);
+
+        let second = Arc::new(error_handler::CountingErrorHandler::new());
+        handle.set_error_handler(second.clone());
+        log_message(&logger, "three");
+        assert_eq!(first.count(), 2);
+        assert_eq!(second.count(), 1);
+
+        handle.set_error_handler(Arc::new(error_handler::PanicErrorHandler));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            log_message(&logger, "four")
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn appender_stats() {
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("a", Box::new(MessageCollector::default())))
+            .appender(config::Appender::builder().build("b", Box::new(FailingAppender)))
+            .build(
+                config::Root::builder()
+                    .appenders(["a", "b"])
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+        handle.set_error_handler(Arc::new(error_handler::IgnoreErrorHandler));
+
+        log_message(&logger, "one");
+        handle.pause("a", PauseMode::Drop).unwrap();
+        log_message(&logger, "two");
+        handle.resume("a").unwrap();
+        handle
+            .set_appender_threshold("a", LevelFilter::Warn)
+            .unwrap();
+        log_message(&logger, "three");
+
+        let stats = handle.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].name, "a");
+        assert_eq!(
+            (
+                stats[0].written,
+                stats[0].errors,
+                stats[0].filtered,
+                stats[0].dropped
+            ),
+            (1, 0, 1, 1)
+        );
+        assert_eq!(stats[0].bytes_written, None);
+        assert_eq!(stats[1].name, "b");
+        assert_eq!((stats[1].written, stats[1].errors), (0, 3));
+
+        let (tx, rx) = mpsc::channel();
+        let export = handle
+            .export_stats(Duration::from_millis(10), move |stats| {
+                let _ = tx.send(stats[1].errors);
+            })
+            .unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(3));
+        drop(export);
+        while rx.recv_timeout(Duration::from_secs(5)).is_ok() {}
+    }
+
+    #[test]
+    fn appender_health() {
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("a", Box::new(MessageCollector::default())))
+            .appender(config::Appender::builder().build("b", Box::new(FailingAppender)))
+            .build(
+                config::Root::builder()
+                    .appenders(["a", "b"])
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+        handle.set_error_handler(Arc::new(error_handler::IgnoreErrorHandler));
+
+        let health = handle.health();
+        assert_eq!(health[0].last_write, None);
+        assert!(!health[1].is_failing());
+
+        let before = SystemTime::now();
+        log_message(&logger, "one");
+        handle.pause("a", PauseMode::Drop).unwrap();
+
+        let health = handle.health();
+        assert_eq!(health.len(), 2);
+        assert_eq!(health[0].name, "a");
+        assert!(health[0].paused);
+        assert!(health[0].last_write.unwrap() >= before);
+        assert_eq!(health[0].last_error, None);
+        assert!(!health[0].is_failing());
+        assert_eq!((health[0].connected, health[0].queue_depth), (None, None));
+        assert_eq!(health[1].name, "b");
+        assert!(!health[1].paused);
+        assert_eq!(health[1].last_write, None);
+        assert_eq!(health[1].last_error.as_ref().unwrap().1, "failed");
+        assert!(health[1].is_failing());
+    }
+
+    #[derive(Debug)]
+    struct BackgroundAppender {
+        queue: Mutex<mpsc::Sender<(append::flush::FlushToken, String)>>,
+        tracker: Arc<append::flush::FlushTracker>,
+    }
+
+    impl BackgroundAppender {
+        fn new(messages: Arc<Mutex<Vec<String>>>) -> BackgroundAppender {
+            let (tx, rx) = mpsc::channel::<(append::flush::FlushToken, String)>();
+            let tracker = Arc::new(append::flush::FlushTracker::new());
+            let worker = tracker.clone();
+            thread::spawn(move || {
+                for (token, message) in rx {
+                    thread::sleep(Duration::from_millis(10));
+                    messages.lock().unwrap().push(message);
+                    worker.complete(token);
+                }
+            });
+            BackgroundAppender {
+                queue: Mutex::new(tx),
+                tracker,
+            }
+        }
+    }
+
+    impl Append for BackgroundAppender {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            let queue = self.queue.lock().unwrap();
+            let token = self.tracker.track();
+            queue.send((token, record.args().to_string()))?;
+            Ok(())
+        }
+
+        fn flush(&self) {}
+
+        fn flush_to(&self, token: append::flush::FlushToken) {
+            self.tracker.wait(token);
+        }
+    }
+
+    #[test]
+    fn flush_to() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .build("a", Box::new(BackgroundAppender::new(messages.clone()))),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+
+        log_message(&logger, "one");
+        log_message(&logger, "two");
+        log_message(&logger, "three");
+        log::Log::flush(&logger);
+        assert_eq!(*messages.lock().unwrap(), ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn set_config_quiesced() {
+        let config = |messages: &Arc<Mutex<Vec<String>>>| {
+            config::Config::builder()
+                .appender(
+                    config::Appender::builder()
+                        .build("a", Box::new(BackgroundAppender::new(messages.clone()))),
+                )
+                .build(
+                    config::Root::builder()
+                        .appender("a")
+                        .build(LevelFilter::Info),
+                )
+                .unwrap()
+        };
+        let (old, new) = (Arc::default(), Arc::default());
+        let logger = super::Logger::new(config(&old));
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        log_message(&logger, "one");
+        assert!(handle.set_config_quiesced(config(&new), Duration::from_secs(5)));
+        // The old configuration's queue was drained before the swap.
+        assert_eq!(*old.lock().unwrap(), ["one"]);
+
+        log_message(&logger, "two");
+        log::Log::flush(&logger);
+        assert_eq!(*old.lock().unwrap(), ["one"]);
+        assert_eq!(*new.lock().unwrap(), ["two"]);
+    }
+
+    #[derive(Debug)]
+    struct BlockingAppender(Mutex<(mpsc::Sender<()>, mpsc::Receiver<()>)>);
+
+    impl Append for BlockingAppender {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            let channel = self.0.lock().unwrap();
+            let _ = channel.0.send(());
+            let _ = channel.1.recv();
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn set_config_quiesced_timeout() {
+        let (tx, rx) = mpsc::channel();
+        let (entered_tx, entered) = mpsc::channel();
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build(
+                "a",
+                Box::new(BlockingAppender(Mutex::new((entered_tx, rx)))),
+            ))
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = Arc::new(super::Logger::new(config));
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        let blocked = thread::spawn({
+            let logger = logger.clone();
+            move || log_message(&logger, "blocked")
+        });
+        entered.recv().unwrap();
+
+        let config = config::Config::builder()
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        assert!(!handle.set_config_quiesced(config, Duration::from_millis(10)));
+        tx.send(()).unwrap();
+        blocked.join().unwrap();
+    }
+
+    #[derive(Debug)]
+    struct LoggingFlushAppender {
+        logger: Arc<Mutex<Weak<super::Logger>>>,
+        messages: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Append for LoggingFlushAppender {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            self.messages
+                .lock()
+                .unwrap()
+                .push(record.args().to_string());
+            Ok(())
+        }
+
+        fn flush(&self) {
+            let logger = self.logger.lock().unwrap().upgrade();
+            if let Some(logger) = logger {
+                log_message(&logger, "flushing");
+            }
+        }
+    }
+
+    #[test]
+    fn set_config_quiesced_logging_flush() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let weak = Arc::new(Mutex::new(Weak::new()));
+        let config = || {
+            let appender = LoggingFlushAppender {
+                logger: weak.clone(),
+                messages: messages.clone(),
+            };
+            config::Config::builder()
+                .appender(config::Appender::builder().build("a", Box::new(appender)))
+                .build(
+                    config::Root::builder()
+                        .appender("a")
+                        .build(LevelFilter::Info),
+                )
+                .unwrap()
+        };
+        let logger = Arc::new(super::Logger::new(config()));
+        *weak.lock().unwrap() = Arc::downgrade(&logger);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        log_message(&logger, "one");
+        // The record logged by the flush is written rather than held back.
+        assert!(handle.set_config_quiesced(config(), Duration::from_secs(5)));
+        assert_eq!(*messages.lock().unwrap(), ["one", "flushing"]);
+    }
+
+    #[test]
+    fn dump_recent() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .build("a", Box::new(MessageCollector(messages.clone()))),
+            )
+            .history(config::History::builder().build(Duration::from_secs(60)))
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Warn),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+        assert_eq!(logger.max_log_level(), LevelFilter::Debug);
+        assert!(logger.enabled(&Metadata::builder().level(Level::Debug).build()));
+
+        for (level, target) in [
+            (Level::Debug, "foo"),
+            (Level::Warn, "bar"),
+            (Level::Trace, "foo"),
+        ] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(format_args!("{} {}", level, target))
+                    .build(),
+            );
+        }
+        assert_eq!(*messages.lock().unwrap(), ["WARN bar"]);
+
+        let count = handle
+            .dump_recent(Dump::Appender("a".to_owned()), None)
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            *messages.lock().unwrap(),
+            ["WARN bar", "DEBUG foo", "WARN bar"]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.log");
+        let count = handle
+            .dump_recent(Dump::Path(path.clone()), Some(&TargetFilter("foo")))
+            .unwrap();
+        assert_eq!(count, 1);
+        let dump = fs::read_to_string(&path).unwrap();
+        assert!(dump.ends_with("Z DEBUG foo - DEBUG foo\n"), "{}", dump);
+    }
+
+    #[test]
+    fn escalation() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .build("a", Box::new(MessageCollector(messages.clone()))),
+            )
+            .escalation(
+                config::Escalation::builder()
+                    .threshold(2)
+                    .target("foo")
+                    .build(Duration::from_millis(50)),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("a")
+                    .build(LevelFilter::Warn),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let log = |level, target| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(format_args!("{} {}", level, target))
+                    .build(),
+            );
+        };
+
+        log(Level::Debug, "foo");
+        log(Level::Error, "bar");
+        log(Level::Error, "bar");
+        log(Level::Debug, "foo::baz");
+        log(Level::Trace, "foo");
+        log(Level::Debug, "bar");
+        assert_eq!(logger.max_log_level(), LevelFilter::Debug);
+
+        thread::sleep(Duration::from_millis(60));
+        log(Level::Debug, "foo");
+        assert_eq!(logger.max_log_level(), LevelFilter::Warn);
+        assert_eq!(
+            *messages.lock().unwrap(),
+            ["ERROR bar", "ERROR bar", "DEBUG foo::baz"]
+        );
+    }
+
+    #[test]
+    fn flush_single_appender() {
+        let a = Arc::new(AtomicUsize::new(0));
+        let b = Arc::new(AtomicUsize::new(0));
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("a", Box::new(FlushCounter(a.clone()))))
+            .appender(config::Appender::builder().build("b", Box::new(FlushCounter(b.clone()))))
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let handle = Handle {
+            shared: super::Logger::new(config).0,
+        };
+
+        handle.flush("a", Duration::from_secs(10)).unwrap();
+        assert_eq!(a.load(Ordering::SeqCst), 1);
+        assert_eq!(b.load(Ordering::SeqCst), 0);
+
+        assert!(matches!(
+            handle.flush("c", Duration::from_secs(10)),
+            Err(HandleError::NonexistentAppender(_))
+        ));
+    }
+
+    #[test]
+    fn flush_hung_appender() {
+        #[derive(Debug)]
+        struct Hung {
+            flushes: Arc<AtomicUsize>,
+            release: Mutex<mpsc::Receiver<()>>,
+        }
+
+        impl Append for Hung {
+            fn append(&self, _: &Record) -> anyhow::Result<()> {
+                Ok(())
+            }
+
+            fn flush(&self) {
+                self.flushes.fetch_add(1, Ordering::SeqCst);
+                let _ = self.release.lock().unwrap().recv();
+            }
+        }
+
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let (release, rx) = mpsc::channel();
+        let appender = Hung {
+            flushes: flushes.clone(),
+            release: Mutex::new(rx),
+        };
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("a", Box::new(appender)))
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let handle = Handle {
+            shared: super::Logger::new(config).0,
+        };
+
+        for _ in 0..3 {
+            assert!(matches!(
+                handle.flush("a", Duration::from_millis(10)),
+                Err(HandleError::FlushTimeout(_))
+            ));
+        }
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+
+        // once the flush is done, the next call starts another
+        drop(release);
+        handle.flush("a", Duration::from_secs(10)).unwrap();
+        let started = flushes.load(Ordering::SeqCst);
+        handle.flush("a", Duration::from_secs(10)).unwrap();
+        assert_eq!(flushes.load(Ordering::SeqCst), started + 1);
+    }
+
+    #[test]
+    #[cfg(feature = "file_appender")]
+    fn tail_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = append::file::FileAppender::builder()
+            .encoder(Box::new(encode::pattern::PatternEncoder::new("{m}\n")))
+            .build(dir.path().join("tail.log"))
+            .unwrap();
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("file", Box::new(file)))
+            .appender(config::Appender::builder().build("other", Box::<FlushCounter>::default()))
+            .build(
+                config::Root::builder()
+                    .appender("file")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = Handle {
+            shared: logger.0.clone(),
+        };
+
+        log_message(&logger, "one");
+        log_message(&logger, "two");
+        assert_eq!(
+            handle.tail("file", append::Tail::Lines(1)).unwrap(),
+            b"two\n"
+        );
+        assert!(matches!(
+            handle.tail("other", append::Tail::Lines(1)),
+            Err(HandleError::Unsupported(_))
+        ));
+        assert!(matches!(
+            handle.roll_now("file"),
+            Err(HandleError::Unsupported(_))
+        ));
+    }
+
     #[test]
     #[cfg(all(feature = "config_parsing", feature = "json_format"))]
     fn init_from_raw_config() {
@@ -579,4 +3312,145 @@ mod test {
                 .build()
         ));
     }
+
+    #[test]
+    fn heartbeat() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .build("a", Box::new(MessageCollector(messages.clone()))),
+            )
+            .appender(config::Appender::builder().build("b", Box::<FlushCounter>::default()))
+            .heartbeat(
+                config::Heartbeat::builder()
+                    .message("alive")
+                    .field("service", "test")
+                    .appender("a")
+                    .build(Duration::from_millis(10)),
+            )
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let logger = super::Logger::new(config);
+
+        thread::sleep(Duration::from_millis(200));
+        assert!(messages.lock().unwrap().iter().any(|m| m == "alive"));
+
+        drop(logger);
+        thread::sleep(Duration::from_millis(50));
+        let count = messages.lock().unwrap().len();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(messages.lock().unwrap().len(), count);
+    }
+
+    #[test]
+    fn heartbeat_fields() {
+        let fields = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder().build("a", Box::new(FieldCollector(fields.clone()))),
+            )
+            .derived_field(config::DerivedField::new(
+                "team",
+                "upper(kv.service)".parse().unwrap(),
+            ))
+            .kv_limits(config::KvLimits::builder().max_value_len(3).build())
+            .heartbeat(
+                config::Heartbeat::builder()
+                    .field("service", "billing")
+                    .build(Duration::from_millis(10)),
+            )
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let _logger = super::Logger::new(config);
+
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(
+            *fields.lock().unwrap(),
+            [
+                ("service".to_owned(), "bil".to_owned()),
+                ("team".to_owned(), "BIL".to_owned()),
+                ("_truncated_fields".to_owned(), "2".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn until_aligned() {
+        let at = |millis| UNIX_EPOCH + Duration::from_millis(millis);
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            super::until_aligned(at(125_000), minute),
+            Duration::from_secs(55)
+        );
+        assert_eq!(super::until_aligned(at(120_000), minute), minute);
+        assert_eq!(
+            super::until_aligned(at(1_250), Duration::from_millis(500)),
+            Duration::from_millis(250)
+        );
+    }
+
+    #[test]
+    fn flush_interval() {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .flush_interval(Duration::from_millis(10))
+                    .build("a", Box::new(FlushCounter(flushes.clone()))),
+            )
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let logger = super::Logger::new(config);
+
+        thread::sleep(Duration::from_millis(200));
+        assert!(flushes.load(Ordering::SeqCst) > 0);
+
+        drop(logger);
+        thread::sleep(Duration::from_millis(50));
+        let count = flushes.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(flushes.load(Ordering::SeqCst), count);
+    }
+
+    #[test]
+    fn destination_hint() {
+        let app = Arc::new(Mutex::new(vec![]));
+        let audit = Arc::new(Mutex::new(vec![]));
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder().build("app", Box::new(MessageCollector(app.clone()))),
+            )
+            .appender(
+                config::Appender::builder()
+                    .build("audit", Box::new(MessageCollector(audit.clone()))),
+            )
+            .logger(config::Logger::builder().build("quiet", LevelFilter::Off))
+            .build(
+                config::Root::builder()
+                    .appender("app")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let log = |target, destination, message| {
+            let kvs = [(destination::KEY, destination)];
+            logger.log(
+                &Record::builder()
+                    .level(Level::Info)
+                    .target(target)
+                    .args(format_args!("{}", message))
+                    .key_values(&kvs)
+                    .build(),
+            );
+        };
+
+        log("app", "audit", "one");
+        log("quiet", "audit", "two");
+        log("app", "app", "three");
+        log("app", "missing", "four");
+
+        assert_eq!(*app.lock().unwrap(), ["one", "three", "four"]);
+        assert_eq!(*audit.lock().unwrap(), ["one", "two"]);
+    }
 }
@@ -0,0 +1,323 @@
+//! Synthetic load, for measuring the throughput of appenders and loggers.
+//!
+//! Requires the `load_generator` feature.
+//!
+//! A `Load` describes a stream of records logged concurrently by a number of
+//! threads. Running it against an appender or a logger reports how fast the
+//! records were accepted and how long individual calls took, so that numbers
+//! from the benchmarks in the repository can be reproduced against an
+//! application's own configuration:
+//!
+//! ```
+//! # #[cfg(feature = "file_appender")]
+//! # fn f() {
+//! use log4rs::{append::file::FileAppender, load::Load};
+//!
+//! let dir = tempfile::tempdir().unwrap();
+//! let appender = FileAppender::builder()
+//!     .build(dir.path().join("load.log"))
+//!     .unwrap();
+//! let report = Load::builder()
+//!     .threads(4)
+//!     .records(10_000)
+//!     .build()
+//!     .run_appender(&appender);
+//! println!(
+//!     "{:.0} records/s, p99 {:?}",
+//!     report.throughput(),
+//!     report.latency(0.99)
+//! );
+//! # }
+//! ```
+
+use log::{Level, Log, Record};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::append::Append;
+
+/// A description of a synthetic load.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Load {
+    threads: usize,
+    records: u64,
+    level: Level,
+    target: String,
+    message_len: usize,
+    fields: usize,
+}
+
+impl Load {
+    /// Creates a new `Load` builder.
+    pub fn builder() -> LoadBuilder {
+        LoadBuilder {
+            threads: 1,
+            records: 100_000,
+            level: Level::Info,
+            target: "log4rs::load".to_owned(),
+            message_len: 100,
+            fields: 0,
+        }
+    }
+
+    /// Returns the number of records logged in total.
+    pub fn total_records(&self) -> u64 {
+        self.records * self.threads as u64
+    }
+
+    /// Appends the records to `appender` directly, bypassing the loggers.
+    pub fn run_appender<A>(&self, appender: &A) -> Report
+    where
+        A: Append + ?Sized,
+    {
+        let report = self.run(|record| appender.append(record).is_ok());
+        appender.flush();
+        report
+    }
+
+    /// Logs the records through `logger`, such as the `Logger` of a
+    /// log4rs `Config`, including its level checks and filters.
+    pub fn run_logger<L>(&self, logger: &L) -> Report
+    where
+        L: Log + ?Sized,
+    {
+        let report = self.run(|record| {
+            logger.log(record);
+            true
+        });
+        logger.flush();
+        report
+    }
+
+    fn run<F>(&self, log: F) -> Report
+    where
+        F: Fn(&Record) -> bool + Sync,
+    {
+        let padding = "x".repeat(self.message_len.saturating_sub(16));
+        let fields = (0..self.fields)
+            .map(|i| (format!("field{}", i), format!("value{}", i)))
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let results = thread::scope(|s| {
+            let handles = (0..self.threads)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut latencies = Vec::with_capacity(self.records as usize);
+                        let mut errors = 0;
+                        for i in 0..self.records {
+                            let start = Instant::now();
+                            let ok = log(&Record::builder()
+                                .level(self.level)
+                                .target(&self.target)
+                                .module_path_static(Some(module_path!()))
+                                .file_static(Some(file!()))
+                                .line(Some(line!()))
+                                .args(format_args!("record {:>8} {}", i, padding))
+                                .key_values(&fields)
+                                .build());
+                            latencies.push(start.elapsed());
+                            if !ok {
+                                errors += 1;
+                            }
+                        }
+                        (latencies, errors)
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        let elapsed = start.elapsed();
+
+        let mut latencies = vec![];
+        let mut errors = 0;
+        for (thread_latencies, thread_errors) in results {
+            latencies.extend(thread_latencies);
+            errors += thread_errors;
+        }
+        latencies.sort_unstable();
+        Report {
+            elapsed,
+            errors,
+            latencies,
+        }
+    }
+}
+
+/// A builder for `Load`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct LoadBuilder {
+    threads: usize,
+    records: u64,
+    level: Level,
+    target: String,
+    message_len: usize,
+    fields: usize,
+}
+
+impl LoadBuilder {
+    /// Sets the number of threads logging concurrently.
+    ///
+    /// Defaults to 1.
+    pub fn threads(mut self, threads: usize) -> LoadBuilder {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets the number of records each thread logs.
+    ///
+    /// Defaults to 100,000.
+    pub fn records(mut self, records: u64) -> LoadBuilder {
+        self.records = records;
+        self
+    }
+
+    /// Sets the level of the records.
+    ///
+    /// Defaults to `Info`.
+    pub fn level(mut self, level: Level) -> LoadBuilder {
+        self.level = level;
+        self
+    }
+
+    /// Sets the target of the records.
+    ///
+    /// Defaults to `log4rs::load`.
+    pub fn target<T: Into<String>>(mut self, target: T) -> LoadBuilder {
+        self.target = target.into();
+        self
+    }
+
+    /// Sets the approximate length of the records' messages in bytes.
+    ///
+    /// Defaults to 100.
+    pub fn message_len(mut self, message_len: usize) -> LoadBuilder {
+        self.message_len = message_len;
+        self
+    }
+
+    /// Sets the number of key-value fields each record carries.
+    ///
+    /// Defaults to 0.
+    pub fn fields(mut self, fields: usize) -> LoadBuilder {
+        self.fields = fields;
+        self
+    }
+
+    /// Consumes the `LoadBuilder`, producing a `Load`.
+    pub fn build(self) -> Load {
+        Load {
+            threads: self.threads,
+            records: self.records,
+            level: self.level,
+            target: self.target,
+            message_len: self.message_len,
+            fields: self.fields,
+        }
+    }
+}
+
+/// The results of running a `Load`.
+#[derive(Clone, Debug)]
+pub struct Report {
+    elapsed: Duration,
+    errors: u64,
+    // The time each logging call took, sorted.
+    latencies: Vec<Duration>,
+}
+
+impl Report {
+    /// Returns the number of records logged.
+    pub fn records(&self) -> u64 {
+        self.latencies.len() as u64
+    }
+
+    /// Returns the number of records the appender returned an error for.
+    ///
+    /// Always 0 for loads run against a logger.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Returns how long the load took to run, from starting the threads to
+    /// the last of them finishing.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the number of records logged per second.
+    pub fn throughput(&self) -> f64 {
+        self.records() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Returns the time within which the fraction `quantile` of the logging
+    /// calls returned, such as 0.99 for the 99th percentile.
+    pub fn latency(&self, quantile: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let index = (quantile.clamp(0., 1.) * (self.latencies.len() - 1) as f64).round();
+        self.latencies[index as usize]
+    }
+
+    /// Returns the time the slowest logging call took.
+    pub fn max_latency(&self) -> Duration {
+        self.latencies.last().copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Counting {
+        appended: AtomicU64,
+        fields: AtomicU64,
+    }
+
+    impl Append for Counting {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            let n = self.appended.fetch_add(1, Ordering::Relaxed);
+            self.fields
+                .fetch_add(record.key_values().count() as u64, Ordering::Relaxed);
+            assert_eq!(record.target(), "bench");
+            assert!(record.args().to_string().len() >= 40);
+            if n % 10 == 0 {
+                anyhow::bail!("every tenth record fails");
+            }
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn run_appender() {
+        let load = Load::builder()
+            .threads(3)
+            .records(100)
+            .target("bench")
+            .message_len(40)
+            .fields(2)
+            .build();
+        assert_eq!(load.total_records(), 300);
+
+        let appender = Counting::default();
+        let report = load.run_appender(&appender);
+        assert_eq!(appender.appended.load(Ordering::Relaxed), 300);
+        assert_eq!(appender.fields.load(Ordering::Relaxed), 600);
+        assert_eq!(report.records(), 300);
+        assert_eq!(report.errors(), 30);
+        assert!(report.throughput() > 0.);
+        assert!(report.latency(0.5) <= report.latency(0.99));
+        assert!(report.latency(1.) == report.max_latency());
+    }
+}
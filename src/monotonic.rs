@@ -0,0 +1,104 @@
+use log::{kv, Record};
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use crate::{encode, record};
+
+/// Keeps the timestamps of the records written by an appender from going
+/// backwards.
+#[derive(Debug)]
+pub(crate) struct MonotonicTime {
+    max_step: Duration,
+    last: Mutex<Option<SystemTime>>,
+}
+
+impl MonotonicTime {
+    pub(crate) fn new(max_step: Duration) -> MonotonicTime {
+        MonotonicTime {
+            max_step,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Calls `f` with `record`, stamped with the current time or, if the
+    /// clock has stepped back by no more than the maximum step since the
+    /// previous record, with the previous record's time.
+    ///
+    /// A clamped record is passed on as a copy carrying how far the clock
+    /// stepped back, in microseconds, as its `time_clamped_us` field. Larger
+    /// steps are taken to be deliberate corrections and let through.
+    pub(crate) fn with_time<F, R>(&self, record: &Record, f: F) -> R
+    where
+        F: FnOnce(&Record) -> R,
+    {
+        // Held while the record is written so records stamped in order are
+        // also written in order.
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let now = encode::record_time();
+        let clamped = last.and_then(|last| {
+            last.duration_since(now)
+                .ok()
+                .filter(|step| !step.is_zero() && *step <= self.max_step)
+                .map(|step| (last, step))
+        });
+        let time = clamped.map_or(now, |(last, _)| last);
+        *last = Some(time);
+
+        encode::with_record_time(time, || match clamped {
+            Some((_, step)) => {
+                let fields = [("time_clamped_us", kv::Value::from(step.as_micros() as u64))];
+                record::with_extra_fields(record, &fields, f)
+            }
+            None => f(record),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clamps_small_steps() {
+        let guard = MonotonicTime::new(Duration::from_secs(60));
+        let ahead = SystemTime::now() + Duration::from_secs(30);
+        *guard.last.lock().unwrap() = Some(ahead);
+
+        let record = Record::builder().args(format_args!("")).build();
+        let (time, clamped) = guard.with_time(&record, |record| {
+            (
+                encode::record_time(),
+                record
+                    .key_values()
+                    .get(kv::Key::from("time_clamped_us"))
+                    .is_some(),
+            )
+        });
+        assert_eq!(time, ahead);
+        assert!(clamped);
+        assert!(encode::record_time() < ahead);
+    }
+
+    #[test]
+    fn passes_large_steps() {
+        let guard = MonotonicTime::new(Duration::from_secs(1));
+        let ahead = SystemTime::now() + Duration::from_secs(30);
+        *guard.last.lock().unwrap() = Some(ahead);
+
+        let record = Record::builder().args(format_args!("")).build();
+        let (time, clamped) = guard.with_time(&record, |record| {
+            (
+                encode::record_time(),
+                record
+                    .key_values()
+                    .get(kv::Key::from("time_clamped_us"))
+                    .is_some(),
+            )
+        });
+        assert!(time < ahead);
+        assert!(!clamped);
+        assert_eq!(*guard.last.lock().unwrap(), Some(time));
+    }
+}
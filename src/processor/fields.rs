@@ -0,0 +1,130 @@
+//! The fields processor.
+//!
+//! Requires the `fields_processor` feature.
+
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::processor::{Event, Processor};
+
+/// The fields processor's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldsProcessorConfig {
+    fields: BTreeMap<String, String>,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+/// A processor that adds a fixed set of key-value fields to events, such as
+/// the name of the service or the region it runs in.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct FieldsProcessor {
+    fields: Vec<(String, String)>,
+    overwrite: bool,
+}
+
+impl FieldsProcessor {
+    /// Creates a new `FieldsProcessor` adding the specified fields.
+    ///
+    /// Fields an event already has keep their value.
+    pub fn new<I, K, V>(fields: I) -> FieldsProcessor
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        FieldsProcessor {
+            fields: fields
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+            overwrite: false,
+        }
+    }
+
+    /// Sets whether the added fields replace the values of fields the event
+    /// already has.
+    ///
+    /// Defaults to `false`.
+    pub fn overwrite(mut self, overwrite: bool) -> FieldsProcessor {
+        self.overwrite = overwrite;
+        self
+    }
+}
+
+impl Processor for FieldsProcessor {
+    fn process(&self, event: &mut Event) {
+        for (key, value) in &self.fields {
+            if self.overwrite || event.field(key).is_none() {
+                event.set_field(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// A deserializer for the `FieldsProcessor`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: fields
+///
+/// # The fields to add to each event. Required.
+/// fields:
+///   service: billing
+///   region: eu-west-1
+///
+/// # Whether the fields replace the values of fields the event already has.
+/// # Defaults to false.
+/// overwrite: false
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct FieldsProcessorDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for FieldsProcessorDeserializer {
+    type Trait = dyn Processor;
+
+    type Config = FieldsProcessorConfig;
+
+    fn deserialize(
+        &self,
+        config: FieldsProcessorConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Processor>> {
+        Ok(Box::new(
+            FieldsProcessor::new(config.fields).overwrite(config.overwrite),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Record;
+
+    use super::*;
+
+    #[test]
+    fn add() {
+        let fields = [("region", "us-east-1")];
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .key_values(&fields)
+            .build();
+        let processor = FieldsProcessor::new(vec![("service", "billing"), ("region", "eu-west-1")]);
+
+        let mut event = Event::new(&record);
+        processor.process(&mut event);
+        assert_eq!(event.field("service"), Some("billing"));
+        assert_eq!(event.field("region"), Some("us-east-1"));
+
+        let mut event = Event::new(&record);
+        processor.overwrite(true).process(&mut event);
+        assert_eq!(event.field("region"), Some("eu-west-1"));
+    }
+}
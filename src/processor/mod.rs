@@ -0,0 +1,249 @@
+//! Processors
+
+use log::{
+    kv::{self, Key, Value as KvValue, VisitSource},
+    Level, Record,
+};
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::Deserializable;
+
+#[cfg(feature = "fields_processor")]
+pub mod fields;
+#[cfg(feature = "redact_processor")]
+pub mod redact;
+#[cfg(feature = "target_processor")]
+pub mod target;
+
+/// The trait implemented by log4rs processors.
+///
+/// Processors are associated with appenders and modify the log events that
+/// pass that appender's filters before they are encoded, for example to add
+/// fields, redact values, or rewrite targets.
+pub trait Processor: fmt::Debug + Send + Sync + 'static {
+    /// Processes a log event.
+    fn process(&self, event: &mut Event);
+}
+
+#[cfg(feature = "config_parsing")]
+impl Deserializable for dyn Processor {
+    fn name() -> &'static str {
+        "processor"
+    }
+}
+
+/// A log event being processed.
+///
+/// The event's key-value fields are held as strings, so the appender sees
+/// the fields of processed events as string values.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Event {
+    level: Level,
+    target: String,
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Event {
+    /// Creates an event from a record.
+    pub fn new(record: &Record) -> Event {
+        let mut fields = Fields(vec![]);
+        let _ = record.key_values().visit(&mut fields);
+        Event {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            fields: fields.0,
+        }
+    }
+
+    /// Returns the event's level.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns the event's target.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Sets the event's target.
+    pub fn set_target(&mut self, target: String) {
+        self.target = target;
+    }
+
+    /// Returns the event's formatted message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Sets the event's message.
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+
+    /// Returns the event's key-value fields, in order.
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    /// Returns the value of the field with the specified key, if the event
+    /// has one.
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &**v)
+    }
+
+    /// Sets the value of the field with the specified key, adding the field
+    /// after the others if the event does not have one.
+    pub fn set_field(&mut self, key: String, value: String) {
+        match self.fields.iter_mut().find(|(k, _)| *k == key) {
+            Some(field) => field.1 = value,
+            None => self.fields.push((key, value)),
+        }
+    }
+
+    /// Removes the field with the specified key, returning its value.
+    pub fn remove_field(&mut self, key: &str) -> Option<String> {
+        let index = self.fields.iter().position(|(k, _)| k == key)?;
+        Some(self.fields.remove(index).1)
+    }
+
+    /// Returns the event's key-value fields, with mutable values.
+    pub fn fields_mut(&mut self) -> impl Iterator<Item = (&str, &mut String)> {
+        self.fields.iter_mut().map(|(k, v)| (&**k, v))
+    }
+}
+
+struct Fields(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: KvValue<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+/// Calls `f` with `record`, or with a copy of it modified by `processors` if
+/// there are any.
+pub(crate) fn with_processed<F, R>(processors: &[Box<dyn Processor>], record: &Record, f: F) -> R
+where
+    F: FnOnce(&Record) -> R,
+{
+    if processors.is_empty() {
+        return f(record);
+    }
+
+    let mut event = Event::new(record);
+    for processor in processors {
+        processor.process(&mut event);
+    }
+
+    f(&Record::builder()
+        .level(event.level)
+        .target(&event.target)
+        .args(format_args!("{}", event.message))
+        .module_path(record.module_path())
+        .file(record.file())
+        .line(record.line())
+        .key_values(&event.fields)
+        .build())
+}
+
+/// Configuration for a processor.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ProcessorConfig {
+    /// The processor kind.
+    pub kind: String,
+    /// The processor configuration.
+    pub config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> de::Deserialize<'de> for ProcessorConfig {
+    fn deserialize<D>(d: D) -> Result<ProcessorConfig, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(ProcessorConfig {
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Upper;
+
+    impl Processor for Upper {
+        fn process(&self, event: &mut Event) {
+            let message = event.message().to_uppercase();
+            event.set_message(message);
+            event.set_field("processed".to_owned(), "true".to_owned());
+        }
+    }
+
+    #[derive(Debug)]
+    struct Tenant;
+
+    impl Processor for Tenant {
+        fn process(&self, event: &mut Event) {
+            if let Some(user) = event.remove_field("user") {
+                event.set_target(format!("{}::{}", event.target(), user));
+            }
+        }
+    }
+
+    #[test]
+    fn chain() {
+        let fields = [("user", "alice"), ("id", "7")];
+        let record = Record::builder()
+            .level(Level::Warn)
+            .target("app")
+            .args(format_args!("hello"))
+            .line(Some(3))
+            .key_values(&fields)
+            .build();
+
+        let processors: Vec<Box<dyn Processor>> = vec![Box::new(Upper), Box::new(Tenant)];
+        with_processed(&processors, &record, |record| {
+            assert_eq!(record.level(), Level::Warn);
+            assert_eq!(record.target(), "app::alice");
+            assert_eq!(record.args().to_string(), "HELLO");
+            assert_eq!(record.line(), Some(3));
+            let kvs = record.key_values();
+            assert!(kvs.get(Key::from_str("user")).is_none());
+            assert_eq!(kvs.get(Key::from_str("id")).unwrap().to_string(), "7");
+            assert_eq!(
+                kvs.get(Key::from_str("processed")).unwrap().to_string(),
+                "true"
+            );
+        });
+
+        with_processed(&[], &record, |processed| {
+            assert_eq!(processed.target(), "app");
+            assert_eq!(processed.key_values().count(), 2);
+        });
+    }
+}
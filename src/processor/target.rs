@@ -0,0 +1,128 @@
+//! The target processor.
+//!
+//! Requires the `target_processor` feature.
+
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::processor::{Event, Processor};
+
+/// The target processor's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetProcessorConfig {
+    rename: BTreeMap<String, String>,
+}
+
+/// A processor that rewrites the targets of events, for example to give the
+/// events of a dependency's internal modules a shorter target.
+///
+/// Each rule renames a target prefix. Like logger names, a prefix only
+/// matches whole `::` separated components, so the prefix `hyper` matches
+/// `hyper` and `hyper::proto` but not `hyperlocal`. When several prefixes
+/// match, the longest one is renamed.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct TargetProcessor {
+    rules: Vec<(String, String)>,
+}
+
+impl TargetProcessor {
+    /// Creates a new `TargetProcessor` without rules.
+    pub fn new() -> TargetProcessor {
+        TargetProcessor::default()
+    }
+
+    /// Adds a rule renaming the target prefix `from` to `to`.
+    pub fn rename<F, T>(mut self, from: F, to: T) -> TargetProcessor
+    where
+        F: Into<String>,
+        T: Into<String>,
+    {
+        self.rules.push((from.into(), to.into()));
+        self
+    }
+}
+
+impl Processor for TargetProcessor {
+    fn process(&self, event: &mut Event) {
+        let target = event.target();
+        let rule = self
+            .rules
+            .iter()
+            .filter(|(from, _)| {
+                target.starts_with(&**from)
+                    && (target.len() == from.len() || target[from.len()..].starts_with("::"))
+            })
+            .max_by_key(|(from, _)| from.len());
+        if let Some((from, to)) = rule {
+            let target = format!("{}{}", to, &target[from.len()..]);
+            event.set_target(target);
+        }
+    }
+}
+
+/// A deserializer for the `TargetProcessor`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: target
+///
+/// # A map of target prefixes to their replacements. Required.
+/// rename:
+///   hyper::proto::h1: http
+///   my_app::internal: my_app
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TargetProcessorDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for TargetProcessorDeserializer {
+    type Trait = dyn Processor;
+
+    type Config = TargetProcessorConfig;
+
+    fn deserialize(
+        &self,
+        config: TargetProcessorConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Processor>> {
+        Ok(Box::new(
+            config
+                .rename
+                .into_iter()
+                .fold(TargetProcessor::new(), |processor, (from, to)| {
+                    processor.rename(from, to)
+                }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Record;
+
+    use super::*;
+
+    fn process(processor: &TargetProcessor, target: &str) -> String {
+        let record = Record::builder().target(target).build();
+        let mut event = Event::new(&record);
+        processor.process(&mut event);
+        event.target().to_owned()
+    }
+
+    #[test]
+    fn rename() {
+        let processor = TargetProcessor::new()
+            .rename("hyper", "http")
+            .rename("hyper::proto::h1", "h1");
+        assert_eq!(process(&processor, "hyper"), "http");
+        assert_eq!(process(&processor, "hyper::client"), "http::client");
+        assert_eq!(process(&processor, "hyper::proto::h1::io"), "h1::io");
+        assert_eq!(process(&processor, "hyperlocal"), "hyperlocal");
+    }
+}
@@ -0,0 +1,241 @@
+use log::{
+    kv::{self, Source, VisitSource},
+    Level, Record,
+};
+
+use crate::{
+    config::{DerivedField, KvLimits},
+    derived, kv_limit,
+};
+
+/// An owned copy of the parts of a `Record` that log4rs preserves when a
+/// record has to outlive the logging call, for example while buffered.
+#[derive(Clone, Debug)]
+pub(crate) struct OwnedRecord {
+    level: Level,
+    target: String,
+    message: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    key_values: Vec<(String, String)>,
+}
+
+impl OwnedRecord {
+    pub(crate) fn new(record: &Record) -> OwnedRecord {
+        OwnedRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            module_path: record.module_path().map(ToOwned::to_owned),
+            file: record.file().map(ToOwned::to_owned),
+            line: record.line(),
+            key_values: owned_fields(record.key_values()),
+        }
+    }
+
+    /// Calls `f` with a `Record` rebuilt from this copy.
+    pub(crate) fn with_record<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Record) -> R,
+    {
+        self.with_record_fields(&[], f)
+    }
+
+    /// Calls `f` with a `Record` rebuilt from this copy, carrying `fields`
+    /// after its own key-value fields.
+    pub(crate) fn with_record_fields<F, R>(&self, fields: &[(&str, &str)], f: F) -> R
+    where
+        F: FnOnce(&Record) -> R,
+    {
+        let key_values = Chain(&self.key_values, &fields);
+
+        f(&Record::builder()
+            .level(self.level)
+            .target(&self.target)
+            .args(format_args!("{}", self.message))
+            .module_path(self.module_path.as_deref())
+            .file(self.file.as_deref())
+            .line(self.line)
+            .key_values(&key_values)
+            .build())
+    }
+}
+
+/// Copies the key-value fields of a source, formatting their values.
+fn owned_fields(source: &dyn Source) -> Vec<(String, String)> {
+    struct Collect(Vec<(String, String)>);
+
+    impl<'kvs> VisitSource<'kvs> for Collect {
+        fn visit_pair(
+            &mut self,
+            key: kv::Key<'kvs>,
+            value: kv::Value<'kvs>,
+        ) -> Result<(), kv::Error> {
+            self.0.push((key.to_string(), value.to_string()));
+            Ok(())
+        }
+    }
+
+    let mut collect = Collect(vec![]);
+    // Collect never fails, and a failing source keeps what it visited.
+    let _ = source.visit(&mut collect);
+    collect.0
+}
+
+/// Calls `f` with a copy of `record` carrying `fields` after its own
+/// key-value fields.
+pub(crate) fn with_extra_fields<F, R>(record: &Record, fields: &dyn Source, f: F) -> R
+where
+    F: FnOnce(&Record) -> R,
+{
+    with_key_values(record, &Chain(record.key_values(), fields), f)
+}
+
+/// Calls `f` with a copy of `record` carrying `key_values` instead of its own
+/// key-value fields.
+fn with_key_values<F, R>(record: &Record, key_values: &dyn Source, f: F) -> R
+where
+    F: FnOnce(&Record) -> R,
+{
+    f(&Record::builder()
+        .metadata(record.metadata().clone())
+        .args(*record.args())
+        .module_path(record.module_path())
+        .file(record.file())
+        .line(record.line())
+        .key_values(key_values)
+        .build())
+}
+
+/// The key-value fields a logger adds to the records it dispatches, and the
+/// limits they are cut down to.
+pub(crate) struct RecordFields<'a> {
+    /// Added to records which don't have a field with the same key.
+    pub(crate) defaults: &'a [(String, String)],
+    /// Computed from the record once the defaults are added.
+    pub(crate) derived: &'a [DerivedField],
+    pub(crate) limits: &'a KvLimits,
+}
+
+impl RecordFields<'_> {
+    /// Calls `f` with a copy of `record` carrying the default and derived
+    /// fields and cut down to the limits, or with `record` itself if none of
+    /// them change it.
+    ///
+    /// The copy is built once, with a single source of key-value fields,
+    /// however many of the fields and limits apply.
+    pub(crate) fn with_fields<F, R>(&self, record: &Record, f: F) -> R
+    where
+        F: FnOnce(&Record) -> R,
+    {
+        let key_values = record.key_values();
+        let defaults = self
+            .defaults
+            .iter()
+            .filter(|(key, _)| key_values.get(kv::Key::from_str(key)).is_none())
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        let with_defaults = Chain(key_values, &defaults);
+        let values = derived::derive(self.derived, record, &with_defaults);
+        let derived = values
+            .iter()
+            .map(|(name, value)| (*name, value.to_kv()))
+            .collect::<Vec<_>>();
+        let all = Chain(&with_defaults, &derived);
+
+        if let Some(limited) = kv_limit::limit(self.limits, &all) {
+            let limited = limited
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect::<Vec<_>>();
+            return with_key_values(record, &limited, f);
+        }
+        if defaults.is_empty() && derived.is_empty() {
+            return f(record);
+        }
+        with_key_values(record, &all, f)
+    }
+}
+
+struct Chain<'a>(&'a dyn Source, &'a dyn Source);
+
+impl Source for Chain<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        self.0.visit(visitor)?;
+        self.1.visit(visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_fields() {
+        let fields = [
+            ("service".to_owned(), "billing".to_owned()),
+            ("region".to_owned(), "eu".to_owned()),
+        ];
+        let kvs = [("region", "us")];
+        let record = Record::builder().key_values(&kvs).build();
+
+        let record_fields = RecordFields {
+            defaults: &fields,
+            derived: &[],
+            limits: &KvLimits::default(),
+        };
+        let mut seen = vec![];
+        record_fields.with_fields(&record, |record| {
+            struct Collect<'a>(&'a mut Vec<(String, String)>);
+
+            impl<'kvs> VisitSource<'kvs> for Collect<'_> {
+                fn visit_pair(
+                    &mut self,
+                    key: kv::Key<'kvs>,
+                    value: kv::Value<'kvs>,
+                ) -> Result<(), kv::Error> {
+                    self.0.push((key.to_string(), value.to_string()));
+                    Ok(())
+                }
+            }
+
+            record.key_values().visit(&mut Collect(&mut seen)).unwrap();
+        });
+        assert_eq!(
+            seen,
+            [
+                ("region".to_owned(), "us".to_owned()),
+                ("service".to_owned(), "billing".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn fields_and_limits() {
+        let defaults = [("service".to_owned(), "billing".to_owned())];
+        let derived = [DerivedField::new(
+            "team",
+            "upper(kv.service)".parse().unwrap(),
+        )];
+        let limits = KvLimits::builder().max_fields(3).build();
+        let record_fields = RecordFields {
+            defaults: &defaults,
+            derived: &derived,
+            limits: &limits,
+        };
+        let kvs = [("a", "1"), ("b", "2")];
+        let record = Record::builder().key_values(&kvs).build();
+
+        let seen = record_fields.with_fields(&record, |record| owned_fields(record.key_values()));
+        assert_eq!(
+            seen,
+            [
+                ("a".to_owned(), "1".to_owned()),
+                ("b".to_owned(), "2".to_owned()),
+                ("service".to_owned(), "billing".to_owned()),
+                ("_truncated_fields".to_owned(), "1".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use log::Record;
+use std::{fmt::Write, iter::Peekable, str::Chars};
+
+use crate::config::Sanitize;
+
+/// Calls `f` with `record`, or with a copy of it whose message has been
+/// sanitized according to `mode` if it contains control characters.
+pub(crate) fn with_sanitized<F, R>(mode: Sanitize, record: &Record, f: F) -> R
+where
+    F: FnOnce(&Record) -> R,
+{
+    if mode == Sanitize::Off {
+        return f(record);
+    }
+
+    let message = record.args().to_string();
+    if !message.chars().any(is_unsafe) {
+        return f(record);
+    }
+
+    let sanitized = match mode {
+        Sanitize::Off => message,
+        Sanitize::Strip => strip(&message),
+        Sanitize::Escape => escape(&message),
+    };
+
+    f(&Record::builder()
+        .metadata(record.metadata().clone())
+        .args(format_args!("{}", sanitized))
+        .module_path(record.module_path())
+        .file(record.file())
+        .line(record.line())
+        .key_values(record.key_values())
+        .build())
+}
+
+fn is_unsafe(c: char) -> bool {
+    c.is_control() && c != '\n' && c != '\t'
+}
+
+fn escape(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    for c in message.chars() {
+        if is_unsafe(c) {
+            let _ = write!(out, "{}", c.escape_default());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn strip(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                Some('[') => skip_csi(&mut chars),
+                Some(']' | 'P' | 'X' | '^' | '_') => skip_string(&mut chars),
+                _ => {}
+            },
+            '\u{9b}' => skip_csi(&mut chars),
+            c if is_unsafe(c) => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Skips the parameters and final byte of a control sequence.
+fn skip_csi(chars: &mut Peekable<Chars>) {
+    for c in chars {
+        if ('@'..='~').contains(&c) {
+            break;
+        }
+    }
+}
+
+/// Skips the body of a string sequence up to its terminator.
+fn skip_string(chars: &mut Peekable<Chars>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{7}' | '\u{9c}' => break,
+            '\u{1b}' if chars.peek() == Some(&'\\') => {
+                chars.next();
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sanitized(mode: Sanitize, message: &str) -> String {
+        with_sanitized(
+            mode,
+            &Record::builder().args(format_args!("{}", message)).build(),
+            |record| record.args().to_string(),
+        )
+    }
+
+    #[test]
+    fn off() {
+        assert_eq!(sanitized(Sanitize::Off, "a\u{1b}[31mb"), "a\u{1b}[31mb");
+    }
+
+    #[test]
+    fn strip() {
+        assert_eq!(sanitized(Sanitize::Strip, "plain\ttext\n"), "plain\ttext\n");
+        assert_eq!(sanitized(Sanitize::Strip, "a\u{1b}[1;31mb\u{1b}[0m"), "ab");
+        assert_eq!(sanitized(Sanitize::Strip, "a\u{1b}]0;title\u{7}b"), "ab");
+        assert_eq!(sanitized(Sanitize::Strip, "a\u{1b}]8;;x\u{1b}\\b"), "ab");
+        assert_eq!(sanitized(Sanitize::Strip, "a\rb\u{0}c\u{7f}d"), "abcd");
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(
+            sanitized(Sanitize::Escape, "plain\ttext\n"),
+            "plain\ttext\n"
+        );
+        assert_eq!(
+            sanitized(Sanitize::Escape, "a\u{1b}[31mb\r"),
+            "a\\u{1b}[31mb\\r"
+        );
+    }
+}
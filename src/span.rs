@@ -0,0 +1,291 @@
+//! Begin and end records for spans of work.
+//!
+//! A `Span` logs a record when it begins and another when it ends, both
+//! carrying the same generated correlation ID, so that log-based tracing can
+//! pair them up. Records logged in between can carry the ID too, by adding
+//! `Span::id` as a key-value field.
+//!
+//! Both records have the key-value fields `span` (the name of the span),
+//! `span_id` and `span_event` (`begin` or `end`). If enabled, the end record
+//! also has `elapsed_ms`, the time since the span began in milliseconds.
+//!
+//! ```
+//! use log4rs::span::Span;
+//!
+//! let span = Span::builder("checkout").elapsed(true).begin();
+//! log::info!(span_id = span.id(); "charging card");
+//! span.end();
+//! ```
+
+use log::{kv, Level, Log, Metadata, Record};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The key of the key-value field holding a span's name.
+pub const SPAN_KEY: &str = "span";
+/// The key of the key-value field holding a span's correlation ID.
+pub const SPAN_ID_KEY: &str = "span_id";
+/// The key of the key-value field holding `begin` or `end`.
+pub const SPAN_EVENT_KEY: &str = "span_event";
+/// The key of the key-value field holding the duration of a span in
+/// milliseconds, on its end record.
+pub const ELAPSED_KEY: &str = "elapsed_ms";
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static ID_PREFIX: Mutex<Option<u64>> = Mutex::new(None);
+
+/// Returns a new correlation ID, unique within the process and unlikely to
+/// be shared with other processes.
+fn next_id() -> String {
+    let prefix = *ID_PREFIX
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(|| {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0);
+            (u64::from(std::process::id()) << 32 ^ nanos) & 0xffff_ffff_ffff
+        });
+    format!(
+        "{:012x}-{:x}",
+        prefix,
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// A builder for `Span`s.
+#[derive(Clone, Debug)]
+pub struct SpanBuilder {
+    name: String,
+    target: String,
+    level: Level,
+    elapsed: bool,
+}
+
+impl SpanBuilder {
+    /// Sets the target of the begin and end records.
+    ///
+    /// Defaults to `span`.
+    pub fn target(mut self, target: &str) -> SpanBuilder {
+        self.target = target.to_owned();
+        self
+    }
+
+    /// Sets the level of the begin and end records.
+    ///
+    /// Defaults to `Level::Info`.
+    pub fn level(mut self, level: Level) -> SpanBuilder {
+        self.level = level;
+        self
+    }
+
+    /// Determines if the end record carries the time since the span began.
+    ///
+    /// Defaults to `false`.
+    pub fn elapsed(mut self, elapsed: bool) -> SpanBuilder {
+        self.elapsed = elapsed;
+        self
+    }
+
+    /// Begins the span, logging its begin record to the global logger.
+    pub fn begin(self) -> Span {
+        self.begin_on(log::logger())
+    }
+
+    fn begin_on(self, logger: &'static dyn Log) -> Span {
+        let span = Span {
+            name: self.name,
+            id: next_id(),
+            target: self.target,
+            level: self.level,
+            elapsed: self.elapsed,
+            start: Instant::now(),
+            logger,
+            ended: false,
+        };
+        span.log("begin", None);
+        span
+    }
+}
+
+/// A span of work, which logs a record when it begins and another when it
+/// ends or is dropped.
+pub struct Span {
+    name: String,
+    id: String,
+    target: String,
+    level: Level,
+    elapsed: bool,
+    start: Instant,
+    logger: &'static dyn Log,
+    ended: bool,
+}
+
+impl std::fmt::Debug for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Span")
+            .field("name", &self.name)
+            .field("id", &self.id)
+            .field("target", &self.target)
+            .field("level", &self.level)
+            .finish()
+    }
+}
+
+impl Span {
+    /// Creates a builder for a span with the specified name.
+    pub fn builder(name: &str) -> SpanBuilder {
+        SpanBuilder {
+            name: name.to_owned(),
+            target: "span".to_owned(),
+            level: Level::Info,
+            elapsed: false,
+        }
+    }
+
+    /// Begins a span with the specified name and the default settings.
+    pub fn begin(name: &str) -> Span {
+        Span::builder(name).begin()
+    }
+
+    /// Returns the name of the span.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the correlation ID shared by the span's records.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Ends the span, logging its end record.
+    ///
+    /// Dropping the span does the same.
+    pub fn end(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        let elapsed = if self.elapsed {
+            Some(self.start.elapsed().as_millis() as u64)
+        } else {
+            None
+        };
+        self.log("end", elapsed);
+    }
+
+    fn log(&self, event: &str, elapsed: Option<u64>) {
+        let mut key_values = vec![
+            (SPAN_KEY, kv::Value::from(&*self.name)),
+            (SPAN_ID_KEY, kv::Value::from(&*self.id)),
+            (SPAN_EVENT_KEY, kv::Value::from(event)),
+        ];
+        if let Some(elapsed) = elapsed {
+            key_values.push((ELAPSED_KEY, kv::Value::from(elapsed)));
+        }
+
+        let metadata = Metadata::builder()
+            .level(self.level)
+            .target(&self.target)
+            .build();
+        if self.logger.enabled(&metadata) {
+            self.logger.log(
+                &Record::builder()
+                    .metadata(metadata)
+                    .args(format_args!("{} {}", event, self.name))
+                    .key_values(&key_values)
+                    .build(),
+            );
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The message and key-value fields of each record logged.
+    type Captured = (String, Vec<(String, String)>);
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<Captured>>);
+
+    impl Log for Collect {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            struct Visit<'a>(&'a mut Vec<(String, String)>);
+
+            impl<'kvs> kv::VisitSource<'kvs> for Visit<'_> {
+                fn visit_pair(
+                    &mut self,
+                    key: kv::Key<'kvs>,
+                    value: kv::Value<'kvs>,
+                ) -> Result<(), kv::Error> {
+                    self.0.push((key.to_string(), value.to_string()));
+                    Ok(())
+                }
+            }
+
+            let mut key_values = vec![];
+            let _ = record.key_values().visit(&mut Visit(&mut key_values));
+            self.0
+                .lock()
+                .unwrap()
+                .push((record.args().to_string(), key_values));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn pairs() {
+        let logger: &'static Collect = Box::leak(Box::default());
+
+        let span = Span::builder("checkout").elapsed(true).begin_on(logger);
+        let id = span.id().to_owned();
+        drop(span);
+        Span::builder("refund").begin_on(logger).end();
+
+        let records = logger.0.lock().unwrap();
+        assert_eq!(records.len(), 4);
+        let field = |i: usize, key: &str| {
+            records[i]
+                .1
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        };
+
+        assert_eq!(records[0].0, "begin checkout");
+        assert_eq!(records[1].0, "end checkout");
+        assert_eq!(field(0, SPAN_ID_KEY), Some(id.clone()));
+        assert_eq!(field(1, SPAN_ID_KEY), Some(id.clone()));
+        assert_eq!(field(0, SPAN_EVENT_KEY).as_deref(), Some("begin"));
+        assert_eq!(field(1, SPAN_EVENT_KEY).as_deref(), Some("end"));
+        assert_eq!(field(0, ELAPSED_KEY), None);
+        assert!(field(1, ELAPSED_KEY).is_some());
+
+        assert_ne!(field(2, SPAN_ID_KEY), Some(id));
+        assert_eq!(field(2, SPAN_ID_KEY), field(3, SPAN_ID_KEY));
+        assert_eq!(field(3, ELAPSED_KEY), None);
+    }
+}
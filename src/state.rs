@@ -0,0 +1,212 @@
+//! Durable state for components.
+//!
+//! Some components need to remember things across restarts, such as when a
+//! trigger is next due to fire or how far a roller's sequence has counted. A
+//! `StateFile` keeps such values as string key-value pairs in a single file
+//! per appender, next to the appender's log file, so that each component
+//! does not invent a file format of its own. Components should prefix their
+//! keys with their kind, such as `date_roller.next`, as they share the file
+//! with the other components of the appender.
+//!
+//! The file holds one `key=value` pair per line. Every update rewrites a
+//! temporary file and renames it over the state file, so a crash leaves
+//! either the old or the new state behind, never a torn one.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+/// A set of key-value pairs persisted to a file.
+#[derive(Debug)]
+pub struct StateFile {
+    path: PathBuf,
+    values: Mutex<BTreeMap<String, String>>,
+}
+
+impl StateFile {
+    /// Opens the state file at the specified path, loading any state stored
+    /// in it.
+    ///
+    /// A missing file is treated as empty; it is created on the first update.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<StateFile> {
+        let path = path.as_ref().to_owned();
+        let values = match fs::read_to_string(&path) {
+            Ok(contents) => parse(&contents).map_err(|line| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid state on line {} of {}", line, path.display()),
+                )
+            })?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(StateFile {
+            path,
+            values: Mutex::new(values),
+        })
+    }
+
+    /// Opens the state file of the appender writing to the specified log
+    /// file, `<file>.state`.
+    pub fn for_log<P: AsRef<Path>>(log: P) -> io::Result<StateFile> {
+        let mut path = log.as_ref().as_os_str().to_owned();
+        path.push(".state");
+        StateFile::open(PathBuf::from(path))
+    }
+
+    /// Returns the path of the state file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the value stored under a key.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.lock().get(key).cloned()
+    }
+
+    /// Returns the value stored under a key, parsed.
+    ///
+    /// Returns `None` if there is no value or it does not parse, so that a
+    /// component falls back to its initial state rather than failing on a
+    /// state file written by a different version of it.
+    pub fn get_parsed<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.lock().get(key).and_then(|value| value.parse().ok())
+    }
+
+    /// Stores a value under a key.
+    pub fn set<V: fmt::Display>(&self, key: &str, value: V) -> io::Result<()> {
+        self.update(|values| {
+            values.insert(key.to_owned(), value.to_string());
+        })
+    }
+
+    /// Removes the value stored under a key.
+    pub fn remove(&self, key: &str) -> io::Result<()> {
+        self.update(|values| {
+            values.remove(key);
+        })
+    }
+
+    /// Updates several values at once, writing the file a single time.
+    ///
+    /// Keys may not contain `=` or line breaks, and values may not contain
+    /// line breaks; the update fails without changing the state otherwise.
+    pub fn update<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut BTreeMap<String, String>) -> R,
+    {
+        let mut values = self.lock();
+        let mut updated = values.clone();
+        let ret = f(&mut updated);
+        if updated != *values {
+            self.write(&updated)?;
+            *values = updated;
+        }
+        Ok(ret)
+    }
+
+    fn write(&self, values: &BTreeMap<String, String>) -> io::Result<()> {
+        let mut contents = String::new();
+        for (key, value) in values {
+            if key.is_empty() || key.contains(['=', '\n', '\r']) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid state key `{}`", key.escape_debug()),
+                ));
+            }
+            if value.contains(['\n', '\r']) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid value for state key `{}`", key),
+                ));
+            }
+            contents.push_str(key);
+            contents.push('=');
+            contents.push_str(value);
+            contents.push('\n');
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, String>> {
+        self.values.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Parses the contents of a state file, returning the number of the first
+/// invalid line on failure.
+fn parse(contents: &str) -> Result<BTreeMap<String, String>, usize> {
+    let mut values = BTreeMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                values.insert(key.to_owned(), value.to_owned());
+            }
+            _ => return Err(i + 1),
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("app.log");
+
+        let state = StateFile::for_log(&log).unwrap();
+        assert_eq!(state.path(), dir.path().join("app.log.state"));
+        assert_eq!(state.get("date_roller.next"), None);
+        state.set("date_roller.next", 1_700_000_000).unwrap();
+        state
+            .update(|values| {
+                values.insert("fixed_window.count".to_owned(), "3".to_owned());
+                values.insert("spool.offset".to_owned(), "a=b".to_owned());
+            })
+            .unwrap();
+        state.remove("fixed_window.count").unwrap();
+        assert!(state.set("bad=key", 1).is_err());
+        assert!(state.set("key", "bad\nvalue").is_err());
+        drop(state);
+
+        let state = StateFile::for_log(&log).unwrap();
+        assert_eq!(
+            state.get_parsed::<u64>("date_roller.next"),
+            Some(1_700_000_000)
+        );
+        assert_eq!(state.get("fixed_window.count"), None);
+        assert_eq!(state.get("spool.offset").as_deref(), Some("a=b"));
+        assert_eq!(state.get_parsed::<u64>("spool.offset"), None);
+        assert!(!dir.path().join("app.log.state.tmp").exists());
+    }
+
+    #[test]
+    fn invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state");
+        fs::write(&path, "a=1\nnonsense\n").unwrap();
+        let err = StateFile::open(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
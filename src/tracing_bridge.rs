@@ -0,0 +1,362 @@
+//! A bridge routing `tracing` events through log4rs.
+//!
+//! Requires the `tracing_bridge` feature.
+//!
+//! A `TracingBridge` is a `tracing` subscriber which turns each event into a
+//! log record, so that code instrumented with `tracing` is logged through the
+//! same configured appenders as code using `log`. The event's `message` field
+//! becomes the record's message and its other fields become key-values.
+//!
+//! While a span is entered, its fields are inserted into the MDC of the
+//! entering thread, and the values they replaced are restored when the span
+//! is exited.
+//!
+//! ```
+//! use log4rs::tracing_bridge::TracingBridge;
+//!
+//! let bridge = TracingBridge::builder().build();
+//! tracing_core::dispatcher::set_global_default(tracing_core::Dispatch::new(bridge)).unwrap();
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use log::{kv::Value, Level, Log};
+use tracing_core::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Interest, Metadata, Subscriber,
+};
+
+/// The field of an event holding its message.
+const MESSAGE_FIELD: &str = "message";
+
+/// The MDC values replaced by the fields of an entered span.
+type Replaced = Vec<(&'static str, Option<String>)>;
+
+thread_local! {
+    /// The spans entered on this thread.
+    static ENTERED: RefCell<Vec<(u64, Replaced)>> = const { RefCell::new(vec![]) };
+}
+
+/// A builder for `TracingBridge`s.
+#[derive(Clone, Default)]
+pub struct TracingBridgeBuilder {
+    logger: Option<Arc<dyn Log>>,
+}
+
+impl fmt::Debug for TracingBridgeBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TracingBridgeBuilder").finish()
+    }
+}
+
+impl TracingBridgeBuilder {
+    /// Sets the logger the records are sent to.
+    ///
+    /// Defaults to the global logger.
+    pub fn logger(mut self, logger: Arc<dyn Log>) -> TracingBridgeBuilder {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Consumes the builder, producing a `TracingBridge`.
+    pub fn build(self) -> TracingBridge {
+        TracingBridge {
+            logger: self.logger,
+            spans: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+struct Span {
+    fields: Vec<(&'static str, String)>,
+    refs: usize,
+}
+
+/// A `tracing` subscriber logging events through a `log::Log`.
+pub struct TracingBridge {
+    logger: Option<Arc<dyn Log>>,
+    spans: Mutex<HashMap<u64, Span>>,
+    next_id: AtomicU64,
+}
+
+impl fmt::Debug for TracingBridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TracingBridge").finish()
+    }
+}
+
+impl TracingBridge {
+    /// Creates a new `TracingBridgeBuilder`.
+    pub fn builder() -> TracingBridgeBuilder {
+        TracingBridgeBuilder::default()
+    }
+
+    fn logger(&self) -> &dyn Log {
+        match self.logger {
+            Some(ref logger) => &**logger,
+            None => log::logger(),
+        }
+    }
+
+    fn spans(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Span>> {
+        self.spans.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Subscriber for TracingBridge {
+    fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+        // The configuration can be reloaded, so interest is never cached.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let level = level(metadata);
+        if self.logger.is_none() && level > log::max_level() {
+            return false;
+        }
+        self.logger().enabled(
+            &log::Metadata::builder()
+                .level(level)
+                .target(metadata.target())
+                .build(),
+        )
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut fields = Fields(vec![]);
+        attrs.record(&mut fields);
+        self.spans().insert(
+            id,
+            Span {
+                fields: fields.0,
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        if let Some(span) = self.spans().get_mut(&id.into_u64()) {
+            let mut fields = Fields(vec![]);
+            values.record(&mut fields);
+            for (name, value) in fields.0 {
+                match span.fields.iter_mut().find(|f| f.0 == name) {
+                    Some(field) => field.1 = value,
+                    None => span.fields.push((name, value)),
+                }
+            }
+        }
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut fields = Fields(vec![]);
+        event.record(&mut fields);
+
+        let mut message = String::new();
+        let mut key_values = vec![];
+        for (name, value) in &fields.0 {
+            if *name == MESSAGE_FIELD {
+                message.clone_from(value);
+            } else {
+                key_values.push((*name, Value::from(&**value)));
+            }
+        }
+
+        self.logger().log(
+            &log::Record::builder()
+                .level(level(metadata))
+                .target(metadata.target())
+                .module_path_static(metadata.module_path())
+                .file_static(metadata.file())
+                .line(metadata.line())
+                .args(format_args!("{}", message))
+                .key_values(&key_values)
+                .build(),
+        );
+    }
+
+    fn enter(&self, id: &Id) {
+        let fields = match self.spans().get(&id.into_u64()) {
+            Some(span) => span.fields.clone(),
+            None => return,
+        };
+        let replaced = fields
+            .into_iter()
+            .map(|(name, value)| (name, log_mdc::insert(name, value)))
+            .collect();
+        ENTERED.with(|entered| entered.borrow_mut().push((id.into_u64(), replaced)));
+    }
+
+    fn exit(&self, id: &Id) {
+        let replaced = ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            let idx = entered.iter().rposition(|e| e.0 == id.into_u64())?;
+            Some(entered.remove(idx).1)
+        });
+        for (name, value) in replaced.into_iter().flatten().rev() {
+            match value {
+                Some(value) => {
+                    log_mdc::insert(name, value);
+                }
+                None => {
+                    log_mdc::remove(name);
+                }
+            }
+        }
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(span) = self.spans().get_mut(&id.into_u64()) {
+            span.refs += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let mut spans = self.spans();
+        match spans.get_mut(&id.into_u64()) {
+            Some(span) if span.refs > 1 => {
+                span.refs -= 1;
+                false
+            }
+            Some(_) => {
+                spans.remove(&id.into_u64());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn level(metadata: &Metadata<'_>) -> Level {
+    match *metadata.level() {
+        tracing_core::Level::ERROR => Level::Error,
+        tracing_core::Level::WARN => Level::Warn,
+        tracing_core::Level::INFO => Level::Info,
+        tracing_core::Level::DEBUG => Level::Debug,
+        tracing_core::Level::TRACE => Level::Trace,
+    }
+}
+
+/// The fields of an event or span, formatted as strings.
+struct Fields(Vec<(&'static str, String)>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name(), value.to_owned()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let mut s = String::new();
+        let _ = write!(s, "{:?}", value);
+        self.0.push((field.name(), s));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::{
+        kv::{Key, VisitSource},
+        Metadata,
+    };
+    use tracing_core::Dispatch;
+
+    use super::*;
+
+    /// The level, message, key-values and MDC `request` of a record.
+    type Logged = (Level, String, String, Option<String>);
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<Logged>>);
+
+    impl Log for Collect {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Info
+        }
+
+        fn log(&self, record: &log::Record) {
+            struct Pairs<'a>(&'a mut String);
+
+            impl<'a, 'kvs> VisitSource<'kvs> for Pairs<'a> {
+                fn visit_pair(
+                    &mut self,
+                    key: Key<'kvs>,
+                    value: Value<'kvs>,
+                ) -> Result<(), log::kv::Error> {
+                    let _ = write!(self.0, "{}={} ", key, value);
+                    Ok(())
+                }
+            }
+
+            let mut pairs = String::new();
+            let _ = record.key_values().visit(&mut Pairs(&mut pairs));
+            self.0.lock().unwrap().push((
+                record.level(),
+                record.args().to_string(),
+                pairs,
+                log_mdc::get("request", |v| v.map(str::to_owned)),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn bridge() -> (Arc<Collect>, Dispatch) {
+        let collect = Arc::new(Collect::default());
+        let bridge = TracingBridge::builder().logger(collect.clone()).build();
+        (collect, Dispatch::new(bridge))
+    }
+
+    #[test]
+    fn events() {
+        let (collect, dispatch) = bridge();
+        tracing_core::dispatcher::with_default(&dispatch, || {
+            tracing::warn!(user = "ann", attempt = 2, "login failed for {}", "ann");
+            tracing::debug!("not enabled");
+        });
+
+        let records = collect.0.lock().unwrap();
+        assert_eq!(
+            *records,
+            [(
+                Level::Warn,
+                "login failed for ann".to_owned(),
+                "user=ann attempt=2 ".to_owned(),
+                None,
+            )]
+        );
+    }
+
+    #[test]
+    fn span_fields_in_mdc() {
+        let (collect, dispatch) = bridge();
+        log_mdc::insert("request", "outer");
+        tracing_core::dispatcher::with_default(&dispatch, || {
+            let span = tracing::info_span!("handle", request = 7);
+            {
+                let _guard = span.enter();
+                tracing::info!("inside");
+            }
+            tracing::info!("outside");
+        });
+        log_mdc::remove("request");
+
+        let records = collect.0.lock().unwrap();
+        let mdc = records.iter().map(|r| r.3.as_deref()).collect::<Vec<_>>();
+        assert_eq!(mdc, [Some("7"), Some("outer")]);
+    }
+}
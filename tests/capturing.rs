@@ -0,0 +1,25 @@
+//! Installing a `CapturingAppender` sets the global logger, so this runs in
+//! its own test binary.
+#![cfg(feature = "test_support")]
+
+use log::Level;
+use log4rs::append::capturing::CapturingAppender;
+
+#[test]
+fn install() {
+    let appender = CapturingAppender::new();
+    let other = CapturingAppender::new();
+    {
+        let _guard = appender.install().unwrap();
+        let _other = other.install().unwrap();
+        log::info!("first");
+        std::thread::spawn(|| log::info!("elsewhere"))
+            .join()
+            .unwrap();
+    }
+    log::info!("second");
+
+    assert_eq!(appender.matching(|r| r.level == Level::Info).len(), 1);
+    assert!(appender.contains(Level::Info, "first"));
+    assert_eq!(other.records(), appender.records());
+}